@@ -1,3 +1,5 @@
+pub mod money;
+
 // todo use smth like https://github.com/greyblake/nutype
 
 newtype_uuid!(TenantId);
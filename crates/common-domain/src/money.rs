@@ -0,0 +1,364 @@
+use rust_decimal::prelude::ToPrimitive;
+use rust_decimal::{Decimal, RoundingStrategy};
+use std::fmt;
+
+/// ISO 4217 currencies with the exponent of their minor unit (2 for cents, 0 for JPY, 3 for KWD ...).
+/// The withdrawn codes still referenced by existing customers are kept with their last exponent.
+static ISO_4217: &[(&str, u8)] = &[
+    ("AED", 2),
+    ("AFN", 2),
+    ("ALL", 2),
+    ("AMD", 2),
+    ("ANG", 2),
+    ("AOA", 2),
+    ("ARS", 2),
+    ("AUD", 2),
+    ("AWG", 2),
+    ("AZN", 2),
+    ("BAM", 2),
+    ("BBD", 2),
+    ("BDT", 2),
+    ("BGN", 2),
+    ("BHD", 3),
+    ("BIF", 0),
+    ("BMD", 2),
+    ("BND", 2),
+    ("BOB", 2),
+    ("BRL", 2),
+    ("BSD", 2),
+    ("BTN", 2),
+    ("BWP", 2),
+    ("BYN", 2),
+    ("BYR", 0),
+    ("BZD", 2),
+    ("CAD", 2),
+    ("CDF", 2),
+    ("CHF", 2),
+    ("CLF", 4),
+    ("CLP", 0),
+    ("CNY", 2),
+    ("COP", 2),
+    ("CRC", 2),
+    ("CUC", 2),
+    ("CUP", 2),
+    ("CVE", 2),
+    ("CZK", 2),
+    ("DJF", 0),
+    ("DKK", 2),
+    ("DOP", 2),
+    ("DZD", 2),
+    ("EEK", 2),
+    ("EGP", 2),
+    ("ERN", 2),
+    ("ETB", 2),
+    ("EUR", 2),
+    ("FJD", 2),
+    ("FKP", 2),
+    ("GBP", 2),
+    ("GEL", 2),
+    ("GHS", 2),
+    ("GIP", 2),
+    ("GMD", 2),
+    ("GNF", 0),
+    ("GQE", 2),
+    ("GTQ", 2),
+    ("GYD", 2),
+    ("HKD", 2),
+    ("HNL", 2),
+    ("HRK", 2),
+    ("HTG", 2),
+    ("HUF", 2),
+    ("IDR", 2),
+    ("ILS", 2),
+    ("INR", 2),
+    ("IQD", 3),
+    ("IRR", 2),
+    ("ISK", 0),
+    ("JMD", 2),
+    ("JOD", 3),
+    ("JPY", 0),
+    ("KES", 2),
+    ("KGS", 2),
+    ("KHR", 2),
+    ("KMF", 0),
+    ("KPW", 2),
+    ("KRW", 0),
+    ("KWD", 3),
+    ("KYD", 2),
+    ("KZT", 2),
+    ("LAK", 2),
+    ("LBP", 2),
+    ("LKR", 2),
+    ("LRD", 2),
+    ("LSL", 2),
+    ("LTL", 2),
+    ("LVL", 2),
+    ("LYD", 3),
+    ("MAD", 2),
+    ("MDL", 2),
+    ("MGA", 2),
+    ("MKD", 2),
+    ("MMK", 2),
+    ("MNT", 2),
+    ("MOP", 2),
+    ("MRO", 2),
+    ("MRU", 2),
+    ("MUR", 2),
+    ("MVR", 2),
+    ("MWK", 2),
+    ("MXN", 2),
+    ("MYR", 2),
+    ("MZM", 2),
+    ("MZN", 2),
+    ("NAD", 2),
+    ("NGN", 2),
+    ("NIO", 2),
+    ("NOK", 2),
+    ("NPR", 2),
+    ("NZD", 2),
+    ("OMR", 3),
+    ("PAB", 2),
+    ("PEN", 2),
+    ("PGK", 2),
+    ("PHP", 2),
+    ("PKR", 2),
+    ("PLN", 2),
+    ("PYG", 0),
+    ("QAR", 2),
+    ("RON", 2),
+    ("RSD", 2),
+    ("RUB", 2),
+    ("RWF", 0),
+    ("SAR", 2),
+    ("SBD", 2),
+    ("SCR", 2),
+    ("SDG", 2),
+    ("SEK", 2),
+    ("SGD", 2),
+    ("SHP", 2),
+    ("SLE", 2),
+    ("SLL", 2),
+    ("SOS", 2),
+    ("SRD", 2),
+    ("SSP", 2),
+    ("STN", 2),
+    ("SVC", 2),
+    ("SYP", 2),
+    ("SZL", 2),
+    ("THB", 2),
+    ("TJS", 2),
+    ("TMT", 2),
+    ("TND", 3),
+    ("TOP", 2),
+    ("TRY", 2),
+    ("TTD", 2),
+    ("TWD", 2),
+    ("TZS", 2),
+    ("UAH", 2),
+    ("UGX", 0),
+    ("USD", 2),
+    ("UYI", 0),
+    ("UYU", 2),
+    ("UYW", 4),
+    ("UZS", 2),
+    ("VEB", 2),
+    ("VED", 2),
+    ("VES", 2),
+    ("VND", 0),
+    ("VUV", 0),
+    ("WST", 2),
+    ("XAF", 0),
+    ("XCD", 2),
+    ("XDR", 2),
+    ("XOF", 0),
+    ("XPF", 0),
+    ("YER", 2),
+    ("ZAR", 2),
+    ("ZMK", 2),
+    ("ZMW", 2),
+    ("ZWL", 2),
+    ("ZWR", 2),
+];
+
+/// How an amount with more decimals than the currency minor unit is rounded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Rounding {
+    /// 0.5 rounds away from zero. The rule of invoiced amounts.
+    #[default]
+    HalfAwayFromZero,
+    /// 0.5 rounds to the even neighbour, so that rounding errors do not accumulate.
+    HalfEven,
+    /// Towards zero, never billing more than the exact amount.
+    Down,
+    /// Away from zero, never billing less than the exact amount.
+    Up,
+}
+
+impl From<Rounding> for RoundingStrategy {
+    fn from(value: Rounding) -> Self {
+        match value {
+            Rounding::HalfAwayFromZero => RoundingStrategy::MidpointAwayFromZero,
+            Rounding::HalfEven => RoundingStrategy::MidpointNearestEven,
+            Rounding::Down => RoundingStrategy::ToZero,
+            Rounding::Up => RoundingStrategy::AwayFromZero,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Currency {
+    code: &'static str,
+    exponent: u8,
+}
+
+impl Currency {
+    pub fn from_code(code: &str) -> Option<Currency> {
+        ISO_4217
+            .binary_search_by(|(c, _)| (*c).cmp(code))
+            .ok()
+            .map(|idx| Currency {
+                code: ISO_4217[idx].0,
+                exponent: ISO_4217[idx].1,
+            })
+    }
+
+    pub fn code(&self) -> &'static str {
+        self.code
+    }
+
+    /// The number of decimals of the minor unit.
+    pub fn exponent(&self) -> u8 {
+        self.exponent
+    }
+
+    /// Rounds an amount in major units to the minor unit of the currency.
+    pub fn round(&self, amount: Decimal, rounding: Rounding) -> Decimal {
+        amount.round_dp_with_strategy(self.exponent as u32, rounding.into())
+    }
+
+    /// Converts an amount in major units to minor units. None if it does not fit.
+    pub fn to_minor(&self, amount: Decimal, rounding: Rounding) -> Option<i64> {
+        let mut rounded = self.round(amount, rounding);
+        rounded.rescale(self.exponent as u32);
+        rounded.mantissa().to_i64()
+    }
+
+    pub fn to_major(&self, minor: i64) -> Decimal {
+        Decimal::new(minor, self.exponent as u32)
+    }
+}
+
+impl fmt::Display for Currency {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.code)
+    }
+}
+
+/// An amount of a currency, as an integer number of its minor unit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Money {
+    minor: i64,
+    currency: Currency,
+}
+
+impl Money {
+    pub fn from_minor(minor: i64, currency: Currency) -> Self {
+        Money { minor, currency }
+    }
+
+    pub fn from_major(amount: Decimal, currency: Currency, rounding: Rounding) -> Option<Self> {
+        currency
+            .to_minor(amount, rounding)
+            .map(|minor| Money { minor, currency })
+    }
+
+    pub fn zero(currency: Currency) -> Self {
+        Money { minor: 0, currency }
+    }
+
+    pub fn minor(&self) -> i64 {
+        self.minor
+    }
+
+    pub fn currency(&self) -> Currency {
+        self.currency
+    }
+
+    pub fn to_major(&self) -> Decimal {
+        self.currency.to_major(self.minor)
+    }
+
+    /// None if the currencies differ, or on overflow.
+    pub fn checked_add(&self, other: Money) -> Option<Money> {
+        if self.currency != other.currency {
+            return None;
+        }
+        self.minor
+            .checked_add(other.minor)
+            .map(|minor| Money::from_minor(minor, self.currency))
+    }
+}
+
+impl fmt::Display for Money {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}", self.to_major(), self.currency)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_decimal_macros::dec;
+
+    #[test]
+    fn test_iso_4217_table_is_sorted() {
+        assert!(ISO_4217.windows(2).all(|w| w[0].0 < w[1].0));
+    }
+
+    #[test]
+    fn test_minor_units_by_currency() {
+        let eur = Currency::from_code("EUR").unwrap();
+        let jpy = Currency::from_code("JPY").unwrap();
+        let kwd = Currency::from_code("KWD").unwrap();
+
+        assert_eq!(
+            eur.to_minor(dec!(12.345), Rounding::HalfAwayFromZero),
+            Some(1235)
+        );
+        assert_eq!(
+            jpy.to_minor(dec!(12.5), Rounding::HalfAwayFromZero),
+            Some(13)
+        );
+        assert_eq!(
+            kwd.to_minor(dec!(12.3456), Rounding::HalfAwayFromZero),
+            Some(12346)
+        );
+        assert_eq!(
+            jpy.to_minor(dec!(1000), Rounding::HalfAwayFromZero),
+            Some(1000)
+        );
+
+        assert_eq!(kwd.to_major(12346), dec!(12.346));
+        assert_eq!(Money::from_minor(1235, eur).to_string(), "12.35 EUR");
+        assert_eq!(Money::from_minor(1000, jpy).to_string(), "1000 JPY");
+
+        assert!(Currency::from_code("XYZ").is_none());
+    }
+
+    #[test]
+    fn test_rounding_rules() {
+        let eur = Currency::from_code("EUR").unwrap();
+
+        assert_eq!(
+            eur.to_minor(dec!(0.125), Rounding::HalfAwayFromZero),
+            Some(13)
+        );
+        assert_eq!(eur.to_minor(dec!(0.125), Rounding::HalfEven), Some(12));
+        assert_eq!(eur.to_minor(dec!(0.129), Rounding::Down), Some(12));
+        assert_eq!(eur.to_minor(dec!(0.121), Rounding::Up), Some(13));
+        assert_eq!(
+            eur.to_minor(dec!(-0.125), Rounding::HalfAwayFromZero),
+            Some(-13)
+        );
+    }
+}
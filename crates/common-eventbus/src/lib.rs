@@ -107,6 +107,36 @@ impl Event {
         )
     }
 
+    pub fn invoice_voided(
+        actor: Uuid,
+        invoice_id: Uuid,
+        tenant_id: Uuid,
+        replacement_invoice_id: Option<Uuid>,
+    ) -> Self {
+        Self::new(
+            EventData::InvoiceVoided(TenantEventDataWithMetadataDetails {
+                tenant_id,
+                entity_id: invoice_id,
+                metadata: replacement_invoice_id
+                    .map(|id| ("replacement_invoice_id".to_string(), id.to_string()))
+                    .into_iter()
+                    .collect(),
+            }),
+            Some(actor),
+        )
+    }
+
+    pub fn invoice_payment_reminder(invoice_id: Uuid, tenant_id: Uuid, attempt: u32) -> Self {
+        Self::new(
+            EventData::InvoicePaymentReminder(TenantEventDataWithMetadataDetails {
+                tenant_id,
+                entity_id: invoice_id,
+                metadata: HashMap::from_iter(vec![("attempt".to_string(), attempt.to_string())]),
+            }),
+            None,
+        )
+    }
+
     pub fn plan_created_draft(actor: Uuid, plan_version_id: Uuid, tenant_id: Uuid) -> Self {
         Self::new(
             EventData::PlanCreatedDraft(TenantEventDataDetails {
@@ -201,6 +231,24 @@ impl Event {
         )
     }
 
+    pub fn subscription_trial_ended(
+        subscription_id: Uuid,
+        tenant_id: Uuid,
+        action_after_trial: String,
+    ) -> Self {
+        Self::new(
+            EventData::SubscriptionTrialEnded(TenantEventDataWithMetadataDetails {
+                tenant_id,
+                entity_id: subscription_id,
+                metadata: HashMap::from_iter(vec![(
+                    "action_after_trial".to_string(),
+                    action_after_trial,
+                )]),
+            }),
+            None,
+        )
+    }
+
     pub fn user_created(actor: Option<Uuid>, user_id: Uuid) -> Self {
         Self::new(
             EventData::UserCreated(EventDataDetails { entity_id: user_id }),
@@ -242,6 +290,8 @@ pub enum EventData {
     OrganizationCreated(EventDataDetails),
     InvoiceCreated(TenantEventDataDetails),
     InvoiceFinalized(TenantEventDataDetails),
+    InvoiceVoided(TenantEventDataWithMetadataDetails),
+    InvoicePaymentReminder(TenantEventDataWithMetadataDetails),
     PlanCreatedDraft(TenantEventDataDetails),
     PlanPublishedVersion(TenantEventDataDetails),
     PlanDiscardedVersion(TenantEventDataDetails),
@@ -251,6 +301,7 @@ pub enum EventData {
     ProductFamilyCreated(TenantEventDataDetails),
     SubscriptionCreated(TenantEventDataDetails),
     SubscriptionCanceled(TenantEventDataDetails),
+    SubscriptionTrialEnded(TenantEventDataWithMetadataDetails),
     TenantCreated(TenantEventDataDetails),
     UserCreated(EventDataDetails),
     UserUpdated(EventDataWithMetadataDetails),
@@ -272,3 +323,10 @@ pub struct TenantEventDataDetails {
     pub tenant_id: Uuid,
     pub entity_id: Uuid,
 }
+
+#[derive(Debug, Clone)]
+pub struct TenantEventDataWithMetadataDetails {
+    pub tenant_id: Uuid,
+    pub entity_id: Uuid,
+    pub metadata: HashMap<String, String>,
+}
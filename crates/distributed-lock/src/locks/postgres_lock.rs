@@ -59,6 +59,13 @@ pub enum LockKey {
     InvoicingIssue,
     InvoicingFinalize,
     InvoicingPrice,
+    InvoicingDunning,
+    InvoicingUsageThreshold,
+    InvoicingCreditGrantExpiry,
+    SubscriptionsPlanChange,
+    SubscriptionsTrial,
+    SubscriptionsResume,
+    SubscriptionsCommitment,
     CurrencyRates,
 }
 
@@ -70,6 +77,13 @@ impl LockKey {
             LockKey::InvoicingIssue => 1002,
             LockKey::InvoicingFinalize => 1003,
             LockKey::InvoicingPrice => 1004,
+            LockKey::InvoicingDunning => 1005,
+            LockKey::InvoicingUsageThreshold => 1006,
+            LockKey::InvoicingCreditGrantExpiry => 1007,
+            LockKey::SubscriptionsPlanChange => 1100,
+            LockKey::SubscriptionsTrial => 1101,
+            LockKey::SubscriptionsResume => 1102,
+            LockKey::SubscriptionsCommitment => 1103,
             LockKey::CurrencyRates => 2000,
         }
     }
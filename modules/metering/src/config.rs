@@ -16,6 +16,14 @@ pub struct Config {
     #[envconfig(from = "METEROID_API_EXTERNAL_URL", default = "http://127.0.0.1:50061")]
     pub meteroid_endpoint: String,
 
+    // the events accepted by an instance are reported as duplicates from memory for that duration, which covers their delivery to the store
+    #[envconfig(from = "METERING_DEDUP_WINDOW_SECONDS", default = "86400")]
+    pub dedup_window_seconds: i64,
+
+    // maximum number of event ids kept for deduplication
+    #[envconfig(from = "METERING_DEDUP_CAPACITY", default = "1000000")]
+    pub dedup_capacity: usize,
+
     #[cfg(feature = "kafka")]
     #[envconfig(nested)]
     pub kafka: KafkaConfig,
@@ -14,6 +14,7 @@ use clickhouse_rs::Pool;
  * OpenstackClickhouseExtension is a Clickhouse extension that provides
  * custom queries for some Openstack-related events with custom aggregation/query requirements.
  * Currently, it only supports the "openstack.instance.uptime" event. Other events are supported via standard queries (ex: bandwidth)
+ * Gauges reported as a single event with a value (ex: instance size) can use a SumOverTime meter instead.
  */
 pub(crate) struct OpenstackClickhouseExtension {}
 
@@ -2,9 +2,10 @@ use crate::config::{ClickhouseConfig, KafkaConfig};
 use crate::connectors::errors::ConnectorError;
 use crate::connectors::Connector;
 use crate::domain::{Meter, QueryMeterParams, Usage};
+use crate::ingest::domain::ProcessedEvent;
 use async_trait::async_trait;
 use chrono::{DateTime, Utc};
-use clickhouse_rs::{Options, Pool};
+use clickhouse_rs::{ClientHandle, Options, Pool};
 use std::collections::HashMap;
 
 use error_stack::{Result, ResultExt};
@@ -16,6 +17,7 @@ pub mod extensions;
 pub mod sql;
 
 use crate::connectors::clickhouse::extensions::ConnectorClickhouseExtension;
+use crate::connectors::clickhouse::sql::query_meter::MeterViewKind;
 use chrono_tz::Tz;
 
 #[derive(Clone)]
@@ -62,6 +64,53 @@ impl ClickhouseConnector {
             .change_context(ConnectorError::InitError(
                 "Could not create event table".to_string(),
             ))?;
+        client
+            .execute(sql::init::create_migrations_table_sql())
+            .await
+            .change_context(ConnectorError::InitError(
+                "Could not create migrations table".to_string(),
+            ))?;
+
+        let applied: Vec<u32> = client
+            .query(sql::init::applied_migrations_sql())
+            .fetch_all()
+            .await
+            .and_then(|block| {
+                block
+                    .rows()
+                    .map(|row| row.get::<u32, _>("version"))
+                    .collect()
+            })
+            .change_context(ConnectorError::InitError(
+                "Could not get the applied migrations".to_string(),
+            ))?;
+
+        for migration in sql::init::migrations()
+            .into_iter()
+            .filter(|m| !applied.contains(&m.version))
+        {
+            log::info!(
+                "Applying clickhouse migration {} {}",
+                migration.version,
+                migration.name
+            );
+            for statement in &migration.statements {
+                client.execute(statement.clone()).await.change_context(
+                    ConnectorError::InitError(format!(
+                        "Could not apply migration {}",
+                        migration.name
+                    )),
+                )?;
+            }
+            client
+                .execute(sql::init::record_migration_sql(&migration))
+                .await
+                .change_context(ConnectorError::InitError(format!(
+                    "Could not record migration {}",
+                    migration.name
+                )))?;
+        }
+
         client
             .execute(kafka_table_ddl)
             .await
@@ -108,6 +157,34 @@ impl ClickhouseConnector {
     }
 }
 
+impl ClickhouseConnector {
+    /// The kind of the view of the meter, as the meters registered as materialized views keep them.
+    async fn meter_view_kind(
+        &self,
+        client: &mut ClientHandle,
+        params: &QueryMeterParams,
+    ) -> Result<MeterViewKind, ConnectorError> {
+        let block = client
+            .query(sql::query_meter::meter_view_engine_sql(
+                &params.namespace,
+                &params.meter_slug,
+            ))
+            .fetch_all()
+            .await
+            .change_context(ConnectorError::QueryError)?;
+
+        match block.rows().next() {
+            Some(row) => {
+                let engine: String = row
+                    .get("engine")
+                    .change_context(ConnectorError::QueryError)?;
+                Ok(MeterViewKind::from_engine(&engine))
+            }
+            None => Ok(MeterViewKind::Events),
+        }
+    }
+}
+
 #[async_trait]
 impl Connector for ClickhouseConnector {
     #[tracing::instrument(skip_all)]
@@ -118,9 +195,8 @@ impl Connector for ClickhouseConnector {
             .await
             .change_context(ConnectorError::ResourceUnavailable)?;
 
-        let ddl = sql::create_meter::create_meter_view(
-            meter, true, // TODO consider making this configurable
-        );
+        let ddl =
+            sql::create_meter::create_meter_view(meter).map_err(ConnectorError::InvalidMeter)?;
 
         client
             .execute(ddl)
@@ -143,8 +219,11 @@ impl Connector for ClickhouseConnector {
             .and_then(|ext| ext.build_query(&params))
         {
             Some(ext) => ext,
-            None => sql::query_meter::query_meter_view_sql(params.clone())
-                .map_err(ConnectorError::InvalidQuery)?,
+            None => {
+                let kind = self.meter_view_kind(&mut client, &params).await?;
+                sql::query_meter::query_meter_view_sql(params.clone(), kind)
+                    .map_err(ConnectorError::InvalidQuery)?
+            }
         };
 
         let block = client
@@ -205,4 +284,64 @@ impl Connector for ClickhouseConnector {
 
         parsed
     }
+
+    #[tracing::instrument(skip_all)]
+    async fn get_event_records(
+        &self,
+        tenant_id: &str,
+        event_ids: &[String],
+    ) -> Result<Vec<ProcessedEvent>, ConnectorError> {
+        if event_ids.is_empty() {
+            return Ok(vec![]);
+        }
+
+        let mut client = self
+            .pool
+            .get_handle()
+            .await
+            .change_context(ConnectorError::ResourceUnavailable)?;
+
+        let query = sql::query_raw::query_event_records_sql(tenant_id, event_ids);
+
+        let block = client
+            .query(&query)
+            .fetch_all()
+            .await
+            .change_context(ConnectorError::QueryError)?;
+
+        block
+            .rows()
+            .map(|row| {
+                let event_timestamp: DateTime<Tz> = row
+                    .get("event_timestamp")
+                    .change_context(ConnectorError::QueryError)?;
+                let property_keys: Vec<String> = row
+                    .get("property_keys")
+                    .change_context(ConnectorError::QueryError)?;
+                let property_values: Vec<String> = row
+                    .get("property_values")
+                    .change_context(ConnectorError::QueryError)?;
+
+                Ok(ProcessedEvent {
+                    event_id: row
+                        .get("event_id")
+                        .change_context(ConnectorError::QueryError)?,
+                    event_name: row
+                        .get("event_name")
+                        .change_context(ConnectorError::QueryError)?,
+                    customer_id: row
+                        .get("customer_id")
+                        .change_context(ConnectorError::QueryError)?,
+                    tenant_id: tenant_id.to_string(),
+                    event_timestamp: event_timestamp.naive_utc(),
+                    properties: property_keys.into_iter().zip(property_values).collect(),
+                    sign: row.get("sign").change_context(ConnectorError::QueryError)?,
+                    correction_reason: String::new(),
+                    record_id: row
+                        .get("record_id")
+                        .change_context(ConnectorError::QueryError)?,
+                })
+            })
+            .collect()
+    }
 }
@@ -1,149 +1,104 @@
-use crate::connectors::clickhouse::sql::init::get_events_table_name;
-use crate::connectors::clickhouse::sql::{escape_sql_identifier, get_meter_view_name, Column};
+use crate::connectors::clickhouse::sql::init::{deduplicated_events_sql, get_events_table_name};
+use crate::connectors::clickhouse::sql::{escape_sql_identifier, get_meter_view_name};
 use crate::domain::{Meter, MeterAggregation};
 
-use std::fmt;
-
-impl fmt::Display for MeterAggregation {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        match self {
-            MeterAggregation::Sum => write!(f, "sum"),
-            MeterAggregation::Avg => write!(f, "avg"),
-            MeterAggregation::Min => write!(f, "min"),
-            MeterAggregation::Max => write!(f, "max"),
-            MeterAggregation::Count => write!(f, "count"),
-            MeterAggregation::CountDistinct => write!(f, "uniq"),
-            MeterAggregation::Latest => write!(f, "argMax"), // TODO unimplemented
-        }
-    }
-}
+// The meters are plain views over the raw events, aggregated at query time, rather than materialized views of aggregate states:
+// - the materialized views are fed with every record inserted, so an event ingested twice would be aggregated twice,
+//   while the view reads a single ingestion of each event (cf deduplicated_events_sql)
+// - the Min, Max and Latest states cannot be corrected by a compensating record
+// - a time-weighted gauge cannot be pre-aggregated per window, as each value is held until the next event of its series
+pub fn create_meter_view(meter: Meter) -> Result<String, String> {
+    let view_name = get_meter_view_name(&meter.namespace, &meter.meter_slug);
 
-fn create_meter_view_to_select_sql(meter: Meter) -> String {
-    let agg_state_fn = format!("{}State", meter.aggregation);
+    // the events are counted whatever their value
+    let value_property = match meter.aggregation {
+        MeterAggregation::Count => None,
+        _ => Some(
+            meter
+                .value_property
+                .filter(|v| !v.is_empty())
+                .ok_or_else(|| {
+                    format!(
+                        "{:?} aggregation requires a value property",
+                        meter.aggregation
+                    )
+                })?,
+        ),
+    };
+
+    Ok(format!(
+        "CREATE VIEW IF NOT EXISTS {} AS {}\n",
+        view_name,
+        netted_events_sql(
+            &meter.namespace,
+            &meter.event_name,
+            value_property.as_deref(),
+            &meter.group_by
+        ),
+    ))
+}
 
-    // TODO we moved from day to minute aggregation. Not sure if we should keep it like this (or we can make it configurable)
-    // Also we want to make sure that we can group by full day, as it's the main view. Maybe an extra MV ?
-    let mut selects = vec![
+/**
+ * Selects the events of a meter with their value and dimensions, one row per current record.
+ * Each event is read once whatever its retried ingestions, and the records cancelled by a compensating record are netted out,
+ * so that duplicates and corrections apply to the queries of these rows.
+ * Without a value property, the value of an event is 1, which is enough to count them.
+ */
+pub(crate) fn netted_events_sql(
+    namespace: &str,
+    event_name: &str,
+    value_property: Option<&str>,
+    dimensions: &[String],
+) -> String {
+    let mut sorted_dimensions = dimensions.to_vec();
+    sorted_dimensions.sort();
+    sorted_dimensions.dedup();
+
+    let value_expr = match value_property.filter(|v| !v.is_empty()) {
+        Some(value_property) => format!(
+            "cast(properties['{}'], 'Float64')",
+            escape_sql_identifier(value_property)
+        ),
+        None => "toFloat64(1)".to_string(),
+    };
+
+    // a series is a customer with a combination of dimensions, holding the value of a gauge
+    let mut series = vec!["customer_id".to_string()];
+    let mut group_by = vec![
         "customer_id".to_string(),
-        "tumbleStart(toDateTime(event_timestamp), toIntervalMinute(1)) AS windowstart".to_string(),
-        "tumbleEnd(toDateTime(event_timestamp), toIntervalMinute(1)) AS windowend".to_string(),
+        "event_id".to_string(),
+        "event_timestamp".to_string(),
+        "value".to_string(),
     ];
-
-    // we rasterize the value property to be an option of non empty string
-    let value_property_nes =
-        meter
-            .value_property
-            .and_then(|v| if v.is_empty() { None } else { Some(v) });
-
-    match value_property_nes {
-        Some(value_property) => {
-            selects.push(format!(
-                "{}(cast(properties['{}'], 'Float64')) AS value",
-                agg_state_fn,
-                escape_sql_identifier(&value_property)
-            ));
-        }
-        None => {
-            if matches!(meter.aggregation, MeterAggregation::Count) {
-                selects.push(format!("{}(*) AS value", agg_state_fn));
-            } else {
-                // TODO should only allow for Count
-                unimplemented!("Only Count aggregation is supported without value property")
-            }
-        }
-    }
-
-    let mut order_by = vec![
-        "windowstart".to_string(),
-        "windowend".to_string(),
+    let mut selects = vec![
         "customer_id".to_string(),
+        "event_id".to_string(),
+        "event_timestamp".to_string(),
+        format!("{} AS value", value_expr),
     ];
-    let mut sorted_group_by = meter.group_by;
-    sorted_group_by.sort();
 
-    for k in &sorted_group_by {
+    for k in &sorted_dimensions {
         let column_name = escape_sql_identifier(k);
-        order_by.push(escape_sql_identifier(&column_name));
-        // selects.push(format!("JSON_VALUE(data, '{}') as {}", escape_sql_identifier(v), escape_sql_identifier(k)));
+        series.push(column_name.clone());
+        group_by.push(column_name.clone());
         selects.push(format!("properties['{}'] as {}", column_name, column_name));
     }
+    selects.push(format!("cityHash64({}) AS series", series.join(", ")));
 
     let events_table_name = get_events_table_name();
 
-    let query = format!(
-        "SELECT {} FROM {} WHERE {}.tenant_id = '{}' AND {}.event_name = '{}' GROUP BY {}",
+    format!(
+        "SELECT {} FROM ({}) GROUP BY {} HAVING sum(sign) > 0",
         selects.join(", "),
-        events_table_name,
-        events_table_name,
-        escape_sql_identifier(&meter.namespace),
-        events_table_name,
-        escape_sql_identifier(&meter.event_name),
-        order_by.join(", "), // TODO check
-    );
-
-    query
-}
-
-pub fn create_meter_view(meter: Meter, populate: bool) -> String {
-    let view_name = get_meter_view_name(&meter.namespace, &meter.meter_slug);
-    let mut columns = vec![
-        Column {
-            name: "customer_id".to_string(),
-            col_type: "String".to_string(),
-        },
-        Column {
-            name: "windowstart".to_string(),
-            col_type: "DateTime".to_string(),
-        },
-        Column {
-            name: "windowend".to_string(),
-            col_type: "DateTime".to_string(),
-        },
-    ];
-
-    // Add value column based on aggregation type
-    let agg_column_type = format!("AggregateFunction({}, Float64)", meter.aggregation);
-    columns.push(Column {
-        name: "value".to_string(),
-        col_type: agg_column_type,
-    });
-
-    // Add group by columns
-    let mut order_by = vec![
-        "windowstart".to_string(),
-        "windowend".to_string(),
-        "customer_id".to_string(),
-    ];
-    for key in &meter.group_by {
-        order_by.push(key.clone());
-        columns.push(Column {
-            name: key.clone(),
-            col_type: "String".to_string(),
-        });
-    }
-
-    // Construct SQL
-    let mut sql = format!("CREATE MATERIALIZED VIEW IF NOT EXISTS {} (\n", view_name);
-    for col in &columns {
-        sql.push_str(&format!("    {} {},\n", col.name, col.col_type));
-    }
-    sql.pop();
-    sql.pop(); // Remove the last comma
-    sql.push_str(&format!(
-        ") ENGINE = AggregatingMergeTree() ORDER BY ({})\n",
-        order_by.join(", ")
-    ));
-
-    if populate {
-        sql.push_str("POPULATE\n");
-    }
-
-    let select_query = create_meter_view_to_select_sql(meter);
-
-    // Add SELECT statement
-    sql.push_str(&format!("AS {}\n", select_query)); // Add your select statement here
-
-    sql
+        deduplicated_events_sql(&format!(
+            "{}.tenant_id = '{}' AND {}.event_name = '{}'",
+            events_table_name,
+            escape_sql_identifier(namespace),
+            events_table_name,
+            escape_sql_identifier(event_name),
+        )),
+        group_by.join(", "),
+    )
 }
 
 #[cfg(test)]
@@ -167,31 +122,120 @@ mod tests {
         };
 
         let expected = r#"
-            CREATE MATERIALIZED VIEW IF NOT EXISTS meteroid.METER_NStestnamespace_Mtestslug (
-                customer_id String,
-                windowstart DateTime,
-                windowend DateTime,
-                value AggregateFunction(count, Float64),
-                test_group1 String,
-                test_group2 String)
-            ENGINE = AggregatingMergeTree()
-            ORDER BY (windowstart, windowend, customer_id, test_group1, test_group2)
-            POPULATE
+            CREATE VIEW IF NOT EXISTS meteroid.METER_NStestnamespace_Mtestslug
             AS SELECT
                 customer_id,
-                tumbleStart(toDateTime(event_timestamp), toIntervalMinute(1)) AS windowstart,
-                tumbleEnd(toDateTime(event_timestamp), toIntervalMinute(1)) AS windowend,
-                countState(cast(properties['test_value'], 'Float64')) AS value,
+                event_id,
+                event_timestamp,
+                toFloat64(1) AS value,
                 properties['test_group1'] as test_group1,
-                properties['test_group2'] as test_group2
-            FROM meteroid.raw_events
-            WHERE meteroid.raw_events.tenant_id = 'test_namespace'
-                AND meteroid.raw_events.event_name = 'test_event'
-                GROUP BY windowstart, windowend, customer_id, test_group1, test_group2
+                properties['test_group2'] as test_group2,
+                cityHash64(customer_id, test_group1, test_group2) AS series
+            FROM (
+                SELECT * FROM meteroid.raw_events
+                WHERE meteroid.raw_events.tenant_id = 'test_namespace'
+                    AND meteroid.raw_events.event_name = 'test_event'
+                ORDER BY event_timestamp, event_name, customer_id, toString(properties)
+                LIMIT 1 BY event_id, record_id
+            )
+            GROUP BY customer_id, event_id, event_timestamp, value, test_group1, test_group2
+            HAVING sum(sign) > 0
         "#;
 
-        let result = create_meter_view(meter, true);
+        let result = create_meter_view(meter).unwrap();
         // assert equal ignoring whitespace
         assert_eq!(clean_sql(&result), clean_sql(expected));
     }
+
+    #[test]
+    fn test_create_meter_view_latest() {
+        let meter = Meter {
+            namespace: "test_namespace".to_string(),
+            meter_slug: "test_slug".to_string(),
+            event_name: "test_event".to_string(),
+            aggregation: MeterAggregation::Latest,
+            group_by: vec![],
+            value_property: Some("seats".to_string()),
+        };
+
+        let expected = r#"
+            CREATE VIEW IF NOT EXISTS meteroid.METER_NStestnamespace_Mtestslug
+            AS SELECT
+                customer_id,
+                event_id,
+                event_timestamp,
+                cast(properties['seats'], 'Float64') AS value,
+                cityHash64(customer_id) AS series
+            FROM (
+                SELECT * FROM meteroid.raw_events
+                WHERE meteroid.raw_events.tenant_id = 'test_namespace'
+                    AND meteroid.raw_events.event_name = 'test_event'
+                ORDER BY event_timestamp, event_name, customer_id, toString(properties)
+                LIMIT 1 BY event_id, record_id
+            )
+            GROUP BY customer_id, event_id, event_timestamp, value
+            HAVING sum(sign) > 0
+        "#;
+
+        let result = create_meter_view(meter).unwrap();
+        assert_eq!(clean_sql(&result), clean_sql(expected));
+    }
+
+    #[test]
+    fn test_create_meter_view_sum_over_time() {
+        let meter = Meter {
+            namespace: "test_namespace".to_string(),
+            meter_slug: "test_slug".to_string(),
+            event_name: "test_event".to_string(),
+            aggregation: MeterAggregation::SumOverTime,
+            group_by: vec!["region".to_string(), "bucket".to_string()],
+            value_property: Some("gb".to_string()),
+        };
+
+        let expected = r#"
+            CREATE VIEW IF NOT EXISTS meteroid.METER_NStestnamespace_Mtestslug
+            AS SELECT
+                customer_id,
+                event_id,
+                event_timestamp,
+                cast(properties['gb'], 'Float64') AS value,
+                properties['bucket'] as bucket,
+                properties['region'] as region,
+                cityHash64(customer_id, bucket, region) AS series
+            FROM (
+                SELECT * FROM meteroid.raw_events
+                WHERE meteroid.raw_events.tenant_id = 'test_namespace'
+                    AND meteroid.raw_events.event_name = 'test_event'
+                ORDER BY event_timestamp, event_name, customer_id, toString(properties)
+                LIMIT 1 BY event_id, record_id
+            )
+            GROUP BY customer_id, event_id, event_timestamp, value, bucket, region
+            HAVING sum(sign) > 0
+        "#;
+
+        let result = create_meter_view(meter).unwrap();
+        assert_eq!(clean_sql(&result), clean_sql(expected));
+    }
+
+    #[test]
+    fn test_create_meter_view_without_value_property() {
+        let meter = |aggregation| Meter {
+            namespace: "test_namespace".to_string(),
+            meter_slug: "test_slug".to_string(),
+            event_name: "test_event".to_string(),
+            aggregation,
+            group_by: vec![],
+            value_property: Some("".to_string()),
+        };
+
+        assert_eq!(
+            create_meter_view(meter(MeterAggregation::SumOverTime)),
+            Err("SumOverTime aggregation requires a value property".to_string())
+        );
+        assert_eq!(
+            create_meter_view(meter(MeterAggregation::Max)),
+            Err("Max aggregation requires a value property".to_string())
+        );
+        assert!(create_meter_view(meter(MeterAggregation::Count)).is_ok());
+    }
 }
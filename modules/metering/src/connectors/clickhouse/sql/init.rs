@@ -28,17 +28,110 @@ const COMMON_COLUMNS: &str = "tenant_id String,
     event_name String,
     customer_id String,
     event_timestamp DateTime64(9, 'UTC'),
-    properties Map(String, String)";
+    properties Map(String, String),
+    sign Int8 DEFAULT 1,
+    correction_reason String DEFAULT '',
+    record_id String DEFAULT ''";
+
+// the lookup of the records of some events, when deduplicating or correcting them
+const EVENT_ID_INDEX: &str = "INDEX event_id_idx event_id TYPE bloom_filter GRANULARITY 4";
+
+/// The records of the events table matching the condition, keeping a single ingestion of each event.
+/// The retries of an ingestion store the event again with an empty record_id, and the first record stored is kept,
+/// while each correction record has its own record_id (cf ProcessedEvent::record_id).
+pub(crate) fn deduplicated_events_sql(condition: &str) -> String {
+    format!(
+        "SELECT * FROM {} WHERE {} ORDER BY event_timestamp, event_name, customer_id, toString(properties) LIMIT 1 BY event_id, record_id",
+        get_events_table_name(),
+        condition
+    )
+}
+
+// the migrations applied to the tables of this database
+fn get_migrations_table_name() -> String {
+    format!("{}.schema_migrations", DATABASE)
+}
+
+pub(crate) struct Migration {
+    pub version: u32,
+    pub name: &'static str,
+    pub statements: Vec<String>,
+}
+
+/// The changes to the tables created by a previous version, applied once and in order at startup (cf ClickhouseConnector::init).
+/// The tables are then created in their current version if they do not exist, so the migrations must also run on an empty database.
+pub(crate) fn migrations() -> Vec<Migration> {
+    vec![
+        Migration {
+            version: 1,
+            name: "event_corrections",
+            statements: vec![
+                format!(
+                    "ALTER TABLE {}
+                    ADD COLUMN IF NOT EXISTS sign Int8 DEFAULT 1,
+                    ADD COLUMN IF NOT EXISTS correction_reason String DEFAULT ''",
+                    get_events_table_name()
+                ),
+                // the kafka engine table cannot be altered, it is recreated with the correction columns
+                format!("DROP TABLE IF EXISTS {}", get_kafka_mv_table_name()),
+                format!("DROP TABLE IF EXISTS {}", get_kafka_events_table_name()),
+            ],
+        },
+        Migration {
+            version: 2,
+            name: "event_deduplication",
+            statements: vec![
+                // the index is built for the parts written or merged from now on
+                format!(
+                    "ALTER TABLE {}
+                    ADD COLUMN IF NOT EXISTS record_id String DEFAULT '',
+                    ADD INDEX IF NOT EXISTS event_id_idx event_id TYPE bloom_filter GRANULARITY 4",
+                    get_events_table_name()
+                ),
+                // recreated with the record_id column
+                format!("DROP TABLE IF EXISTS {}", get_kafka_mv_table_name()),
+                format!("DROP TABLE IF EXISTS {}", get_kafka_events_table_name()),
+            ],
+        },
+    ]
+}
+
+pub(crate) fn create_migrations_table_sql() -> String {
+    format!(
+        "CREATE TABLE IF NOT EXISTS {} (
+            version UInt32,
+            name String,
+            applied_at DateTime DEFAULT now()
+        ) ENGINE = MergeTree
+        ORDER BY version",
+        get_migrations_table_name()
+    )
+}
+
+pub(crate) fn applied_migrations_sql() -> String {
+    format!("SELECT version FROM {}", get_migrations_table_name())
+}
+
+pub(crate) fn record_migration_sql(migration: &Migration) -> String {
+    format!(
+        "INSERT INTO {} (version, name) VALUES ({}, '{}')",
+        get_migrations_table_name(),
+        migration.version,
+        migration.name
+    )
+}
 
 pub(crate) fn create_events_table_sql() -> String {
     format!(
         "CREATE TABLE IF NOT EXISTS {} (
+            {},
             {}
         ) ENGINE = MergeTree
         PARTITION BY toYYYYMM(event_timestamp)
         ORDER BY (tenant_id, event_timestamp, event_name, customer_id)",
         get_events_table_name(),
-        COMMON_COLUMNS
+        COMMON_COLUMNS,
+        EVENT_ID_INDEX
     )
 }
 /*
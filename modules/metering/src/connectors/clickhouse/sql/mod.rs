@@ -26,15 +26,18 @@ fn encode_identifier(identifier: &str) -> String {
 
 pub fn get_meter_view_name(namespace: &str, meter_slug: &str) -> String {
     format!(
-        "{}.{}_NS{}_M{}",
+        "{}.{}",
         DATABASE,
+        get_meter_table_name(namespace, meter_slug)
+    )
+}
+
+// the name of the meter view, without its database
+pub(crate) fn get_meter_table_name(namespace: &str, meter_slug: &str) -> String {
+    format!(
+        "{}_NS{}_M{}",
         METER_TABLE_PREFIX,
         encode_identifier(namespace),
         encode_identifier(meter_slug)
     )
 }
-
-struct Column {
-    name: String,
-    col_type: String,
-}
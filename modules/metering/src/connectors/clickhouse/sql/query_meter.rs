@@ -1,8 +1,57 @@
-use crate::connectors::clickhouse::sql::get_meter_view_name;
+use crate::connectors::clickhouse::sql::create_meter::netted_events_sql;
+use crate::connectors::clickhouse::sql::{get_meter_table_name, get_meter_view_name, DATABASE};
 use crate::domain::{MeterAggregation, QueryMeterParams, WindowSize};
+use chrono::Utc;
 
-pub fn query_meter_view_sql(params: QueryMeterParams) -> Result<String, String> {
-    let view_name = get_meter_view_name(&params.namespace, &params.meter_slug);
+/// How the view of a meter stores its values, which depends on when the meter was registered.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MeterViewKind {
+    /// A plain view over the netted events, aggregated at query time.
+    Events,
+    /// A materialized view of aggregate states, registered before the meters were views over the events.
+    /// Its states reflect neither the corrections nor the deduplication of the events,
+    /// so the meter is aggregated from the netted events instead.
+    Legacy,
+}
+
+impl MeterViewKind {
+    /// The kind of an existing view, from its engine in system.tables.
+    pub fn from_engine(engine: &str) -> Self {
+        if engine == "MaterializedView" {
+            MeterViewKind::Legacy
+        } else {
+            MeterViewKind::Events
+        }
+    }
+}
+
+pub fn meter_view_engine_sql(namespace: &str, meter_slug: &str) -> String {
+    format!(
+        "SELECT engine FROM system.tables WHERE database = '{}' AND name = '{}'",
+        DATABASE,
+        get_meter_table_name(namespace, meter_slug)
+    )
+}
+
+pub fn query_meter_view_sql(
+    params: QueryMeterParams,
+    kind: MeterViewKind,
+) -> Result<String, String> {
+    if let MeterAggregation::SumOverTime = params.aggregation {
+        return query_sum_over_time_sql(&params);
+    }
+
+    let view_name = match kind {
+        // the events are put in minute windows, that the queried windows are made of
+        MeterViewKind::Events => format!(
+            "(SELECT *, tumbleStart(toDateTime(event_timestamp), toIntervalMinute(1)) AS windowstart, tumbleEnd(toDateTime(event_timestamp), toIntervalMinute(1)) AS windowend FROM {})",
+            get_meter_view_name(&params.namespace, &params.meter_slug)
+        ),
+        MeterViewKind::Legacy => format!(
+            "(SELECT *, tumbleStart(toDateTime(event_timestamp), toIntervalMinute(1)) AS windowstart, tumbleEnd(toDateTime(event_timestamp), toIntervalMinute(1)) AS windowend FROM ({}))",
+            legacy_events_sql(&params)?
+        ),
+    };
 
     let mut select_columns = Vec::new();
     let mut group_by_columns = Vec::new();
@@ -56,13 +105,17 @@ pub fn query_meter_view_sql(params: QueryMeterParams) -> Result<String, String>
     }
 
     let aggregation_column = match &params.aggregation {
-        MeterAggregation::Sum => "sumMerge(value) AS value",
-        MeterAggregation::Avg => "avgMerge(value) AS value",
-        MeterAggregation::Min => "minMerge(value) AS value",
-        MeterAggregation::Max => "maxMerge(value) AS value",
-        MeterAggregation::Count => "toFloat64(countMerge(value)) AS value",
-        // TODO
-        MeterAggregation::Latest => "argMaxMerge(value, windowstart) AS value",
+        MeterAggregation::Sum => "sum(value) AS value",
+        MeterAggregation::Avg => "avg(value) AS value",
+        MeterAggregation::Min => "min(value) AS value",
+        MeterAggregation::Max => "max(value) AS value",
+        // a row per current record, cf netted_events_sql
+        MeterAggregation::Count => "toFloat64(count()) AS value",
+        // the event id breaks ties between events sharing the same timestamp, so that the result is deterministic
+        MeterAggregation::Latest => "argMax(value, (event_timestamp, event_id)) AS value",
+        MeterAggregation::SumOverTime => {
+            unreachable!("SumOverTime is queried from the gauge view")
+        }
         MeterAggregation::CountDistinct => {
             // let mut columns = Vec::new();
             // for (column, values) in &params.filter_group_by {
@@ -137,3 +190,326 @@ pub fn query_meter_view_sql(params: QueryMeterParams) -> Result<String, String>
 
     Ok(sql)
 }
+
+/**
+ * The netted events of a meter registered as a materialized view, read from the raw events rather than from its states.
+ * The events are selected with the dimensions used by the query, the ones of the meter being unknown here.
+ */
+fn legacy_events_sql(params: &QueryMeterParams) -> Result<String, String> {
+    // the events are counted whatever their value, as with the count states
+    let value_property = match params.aggregation {
+        MeterAggregation::Count => None,
+        _ => params.value_property.as_deref().filter(|v| !v.is_empty()),
+    };
+
+    if value_property.is_none() && !matches!(params.aggregation, MeterAggregation::Count) {
+        return Err(format!(
+            "The meter {} was registered as a materialized view, its aggregation key is required to query it",
+            params.meter_slug
+        ));
+    }
+
+    let mut dimensions = params.group_by.clone();
+    dimensions.extend(params.filter_group_by.keys().cloned());
+
+    Ok(netted_events_sql(
+        &params.namespace,
+        &params.event_name,
+        value_property,
+        &dimensions,
+    ))
+}
+
+fn filter_clauses(params: &QueryMeterParams) -> Result<Vec<String>, String> {
+    let mut where_clauses = Vec::new();
+
+    if !params.customers.is_empty() {
+        let subjects_condition = params
+            .customers
+            .iter()
+            .map(|customer| format!("customer_id = '{}'", customer.id))
+            .collect::<Vec<_>>()
+            .join(" OR ");
+        where_clauses.push(format!("({})", subjects_condition));
+    }
+
+    for (column, values) in &params.filter_group_by {
+        if values.is_empty() {
+            return Err(format!("Empty filter for group by: {}", column));
+        }
+        let column_condition = values
+            .iter()
+            .map(|value| format!("{} = '{}'", column, value))
+            .collect::<Vec<_>>()
+            .join(" OR ");
+        where_clauses.push(format!("({})", column_condition));
+    }
+
+    Ok(where_clauses)
+}
+
+/**
+ * Integrates a gauge over time (value * seconds), from the view created for SumOverTime meters.
+ * Each event sets the value of its series (customer & dimensions) until the next event of the same series,
+ * the last one being held until the end of the queried period. Held periods are then split over the windows.
+ * This generalizes the uptime computed by the openstack extension, where the value is 1 while the instance is running.
+ */
+pub fn query_sum_over_time_sql(params: &QueryMeterParams) -> Result<String, String> {
+    let view_name = get_meter_view_name(&params.namespace, &params.meter_slug);
+
+    let from = params.from.timestamp();
+    let to = params.to.unwrap_or_else(Utc::now).timestamp();
+
+    if to <= from {
+        return Err("The end of the period must be after its start".to_string());
+    }
+
+    let tz = params
+        .window_time_zone
+        .as_ref()
+        .unwrap_or(&"UTC".to_string())
+        .clone();
+
+    let windows = match &params.window_size {
+        Some(window_size) => {
+            let (start_fn, seconds_in_interval) = match window_size {
+                WindowSize::Minute => ("toStartOfMinute", 60),
+                WindowSize::Hour => ("toStartOfHour", 3600),
+                WindowSize::Day => ("toStartOfDay", 86400),
+            };
+            // upper bound, the windows after the end of the period do not match any held period
+            let count = (to - from) / seconds_in_interval + 2;
+            format!(
+                "SELECT
+                    toDateTime({start_fn}(toDateTime({from}, '{tz}')) + number * {seconds_in_interval}) AS windowstart,
+                    toDateTime({start_fn}(toDateTime({from}, '{tz}')) + (number + 1) * {seconds_in_interval}) AS windowend
+                FROM numbers({count})"
+            )
+        }
+        None => format!(
+            "SELECT toDateTime({from}, 'UTC') AS windowstart, toDateTime({to}, 'UTC') AS windowend"
+        ),
+    };
+
+    let mut where_clauses = filter_clauses(params)?;
+    where_clauses.push(format!("event_timestamp < toDateTime({}, 'UTC')", to));
+
+    let dimensions: String = params
+        .group_by
+        .iter()
+        .map(|column| format!("{}, ", column))
+        .collect();
+
+    let mut select_columns = match params.window_size {
+        Some(_) => vec![
+            "w.windowstart AS windowstart".to_string(),
+            "w.windowend AS windowend".to_string(),
+        ],
+        // column names expected when aggregating the whole period, as with the meter views
+        None => vec![
+            "w.windowstart AS `min(windowstart)`".to_string(),
+            "w.windowend AS `max(windowend)`".to_string(),
+        ],
+    };
+    select_columns.push(
+        "sum(p.value * dateDiff('second', greatest(w.windowstart, p.period_start), least(w.windowend, p.period_end))) AS value"
+            .to_string(),
+    );
+    select_columns.push("p.customer_id AS customer_id".to_string());
+    let mut group_by_columns = vec![
+        "w.windowstart".to_string(),
+        "w.windowend".to_string(),
+        "customer_id".to_string(),
+    ];
+    for column in &params.group_by {
+        select_columns.push(format!("p.{} AS {}", column, column));
+        group_by_columns.push(column.clone());
+    }
+
+    let mut sql = format!(
+        "WITH
+    held AS (
+        SELECT
+            customer_id,
+            {dimensions}value,
+            toDateTime(event_timestamp) AS start_time,
+            leadInFrame(toNullable(toDateTime(event_timestamp))) OVER (
+                PARTITION BY series
+                ORDER BY event_timestamp, event_id
+                ROWS BETWEEN CURRENT ROW AND UNBOUNDED FOLLOWING
+            ) AS end_time
+        FROM {view_name}
+        WHERE {where_clauses}
+    ),
+    periods AS (
+        SELECT
+            customer_id,
+            {dimensions}value,
+            greatest(start_time, toDateTime({from}, 'UTC')) AS period_start,
+            least(ifNull(end_time, toDateTime({to}, 'UTC')), toDateTime({to}, 'UTC')) AS period_end
+        FROM held
+        WHERE start_time < toDateTime({to}, 'UTC')
+          AND (end_time IS NULL OR end_time > toDateTime({from}, 'UTC'))
+    ),
+    windows AS (
+        {windows}
+    )
+SELECT {select_columns}
+FROM windows w
+CROSS JOIN periods p
+WHERE p.period_start < w.windowend AND p.period_end > w.windowstart
+GROUP BY {group_by_columns}",
+        where_clauses = where_clauses.join(" AND "),
+        select_columns = select_columns.join(", "),
+        group_by_columns = group_by_columns.join(", "),
+    );
+    if params.window_size.is_some() {
+        sql.push_str(" ORDER BY windowstart");
+    }
+
+    Ok(sql)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::Customer;
+    use chrono::TimeZone;
+    use std::collections::HashMap;
+
+    fn sum_over_time_params(window_size: Option<WindowSize>) -> QueryMeterParams {
+        QueryMeterParams {
+            aggregation: MeterAggregation::SumOverTime,
+            namespace: "tenant1".to_string(),
+            meter_slug: "storage".to_string(),
+            event_name: "storage".to_string(),
+            value_property: Some("gb".to_string()),
+            customers: vec![Customer {
+                id: "customer1".to_string(),
+                external_id: "customer1".to_string(),
+            }],
+            filter_group_by: HashMap::from([("region".to_string(), vec!["eu".to_string()])]),
+            group_by: vec!["bucket".to_string()],
+            window_size,
+            window_time_zone: None,
+            from: Utc.with_ymd_and_hms(2023, 6, 1, 0, 0, 0).unwrap(),
+            to: Some(Utc.with_ymd_and_hms(2023, 6, 2, 0, 0, 0).unwrap()),
+        }
+    }
+
+    #[test]
+    fn test_sum_over_time_hourly_windows() {
+        let query = query_meter_view_sql(
+            sum_over_time_params(Some(WindowSize::Hour)),
+            MeterViewKind::Events,
+        )
+        .unwrap();
+
+        assert!(query.contains("FROM meteroid.METER_NStenant1_Mstorage"));
+        assert!(query.contains("PARTITION BY series"));
+        assert!(query.contains("WHERE (customer_id = 'customer1') AND (region = 'eu') AND event_timestamp < toDateTime(1685664000, 'UTC')"));
+        assert!(query.contains("toStartOfHour(toDateTime(1685577600, 'UTC')) + number * 3600"));
+        assert!(query.contains("FROM numbers(26)"));
+        assert!(query.contains("p.bucket AS bucket"));
+        assert!(query.contains(
+            "GROUP BY w.windowstart, w.windowend, customer_id, bucket ORDER BY windowstart"
+        ));
+    }
+
+    #[test]
+    fn test_sum_over_time_whole_period() {
+        let query =
+            query_meter_view_sql(sum_over_time_params(None), MeterViewKind::Events).unwrap();
+
+        assert!(query.contains("SELECT toDateTime(1685577600, 'UTC') AS windowstart, toDateTime(1685664000, 'UTC') AS windowend"));
+        assert!(
+            query.contains("w.windowstart AS `min(windowstart)`, w.windowend AS `max(windowend)`")
+        );
+        assert!(!query.contains("ORDER BY windowstart"));
+    }
+
+    #[test]
+    fn test_sum_over_time_invalid_period() {
+        let mut params = sum_over_time_params(None);
+        params.to = Some(params.from);
+
+        assert!(query_meter_view_sql(params, MeterViewKind::Events).is_err());
+    }
+
+    fn params(aggregation: MeterAggregation) -> QueryMeterParams {
+        QueryMeterParams {
+            aggregation,
+            group_by: vec![],
+            filter_group_by: HashMap::new(),
+            ..sum_over_time_params(Some(WindowSize::Day))
+        }
+    }
+
+    #[test]
+    fn test_latest_from_events() {
+        let query =
+            query_meter_view_sql(params(MeterAggregation::Latest), MeterViewKind::Events).unwrap();
+
+        assert!(query.contains("argMax(value, (event_timestamp, event_id)) AS value"));
+        assert!(query.contains("FROM (SELECT *, tumbleStart(toDateTime(event_timestamp), toIntervalMinute(1)) AS windowstart"));
+        assert!(query.contains("FROM meteroid.METER_NStenant1_Mstorage)"));
+    }
+
+    #[test]
+    fn test_additive_from_events() {
+        let count =
+            query_meter_view_sql(params(MeterAggregation::Count), MeterViewKind::Events).unwrap();
+        assert!(count.contains("toFloat64(count()) AS value"));
+        assert!(count.contains("FROM meteroid.METER_NStenant1_Mstorage)"));
+
+        let sum =
+            query_meter_view_sql(params(MeterAggregation::Sum), MeterViewKind::Events).unwrap();
+        assert!(sum.contains("sum(value) AS value"));
+    }
+
+    #[test]
+    fn test_legacy_view_from_events() {
+        let legacy = |aggregation| {
+            let mut params = params(aggregation);
+            params.filter_group_by =
+                HashMap::from([("region".to_string(), vec!["eu".to_string()])]);
+            query_meter_view_sql(params, MeterViewKind::Legacy)
+        };
+
+        let sum = legacy(MeterAggregation::Sum).unwrap();
+        assert!(sum.contains("sum(value) AS value"));
+        assert!(sum.contains(
+            "cast(properties['gb'], 'Float64') AS value, properties['region'] as region"
+        ));
+        assert!(sum.contains("LIMIT 1 BY event_id, record_id) GROUP BY"));
+        assert!(sum.contains("HAVING sum(sign) > 0))"));
+        assert!(!sum.contains("METER_NStenant1_Mstorage"));
+
+        assert!(legacy(MeterAggregation::Count)
+            .unwrap()
+            .contains("toFloat64(count()) AS value"));
+        assert!(legacy(MeterAggregation::Avg)
+            .unwrap()
+            .contains("avg(value) AS value"));
+
+        // the value of the events cannot be selected without the aggregation key, only their count
+        let without_key = |aggregation| {
+            let mut params = params(aggregation);
+            params.value_property = None;
+            query_meter_view_sql(params, MeterViewKind::Legacy)
+        };
+        assert!(without_key(MeterAggregation::Sum).is_err());
+        assert!(without_key(MeterAggregation::Count)
+            .unwrap()
+            .contains("toFloat64(1) AS value"));
+    }
+
+    #[test]
+    fn test_meter_view_kind() {
+        assert_eq!(MeterViewKind::from_engine("View"), MeterViewKind::Events);
+        assert_eq!(
+            MeterViewKind::from_engine("MaterializedView"),
+            MeterViewKind::Legacy
+        );
+    }
+}
@@ -1,4 +1,5 @@
-use crate::connectors::clickhouse::sql::init::get_events_table_name;
+use crate::connectors::clickhouse::sql::escape_sql_identifier;
+use crate::connectors::clickhouse::sql::init::{deduplicated_events_sql, get_events_table_name};
 use chrono::{DateTime, Utc};
 
 // TODO improve
@@ -35,3 +36,20 @@ pub fn query_raw_event_table_sql(
 
     query
 }
+
+pub fn query_event_records_sql(tenant_id: &str, event_ids: &[String]) -> String {
+    let event_ids = event_ids
+        .iter()
+        .map(|id| format!("'{}'", escape_sql_identifier(id)))
+        .collect::<Vec<_>>()
+        .join(", ");
+
+    format!(
+        "SELECT event_id, event_name, customer_id, event_timestamp, mapKeys(properties) AS property_keys, mapValues(properties) AS property_values, sign, record_id FROM ({})",
+        deduplicated_events_sql(&format!(
+            "tenant_id = '{}' AND event_id IN ({})",
+            escape_sql_identifier(tenant_id),
+            event_ids
+        ))
+    )
+}
@@ -15,6 +15,9 @@ pub enum ConnectorError {
     #[error("Failed to register meter")]
     RegisterError,
 
+    #[error("Invalid meter: {0}")]
+    InvalidMeter(String),
+
     #[error("Failed to query metering database")]
     QueryError,
 
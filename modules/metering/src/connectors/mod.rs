@@ -1,9 +1,10 @@
-mod errors;
+pub mod errors;
 
 pub mod clickhouse;
 
 use crate::connectors::errors::ConnectorError;
 use crate::domain::{Meter, QueryMeterParams, Usage};
+use crate::ingest::domain::ProcessedEvent;
 use error_stack::Result;
 
 use tonic::async_trait;
@@ -13,6 +14,13 @@ pub trait Connector {
     async fn register_meter(&self, meter: Meter) -> Result<(), ConnectorError>;
 
     async fn query_meter(&self, params: QueryMeterParams) -> Result<Vec<Usage>, ConnectorError>;
+
+    /// All the stored records of these events, including the compensating ones, with a single ingestion of each event.
+    async fn get_event_records(
+        &self,
+        tenant_id: &str,
+        event_ids: &[String],
+    ) -> Result<Vec<ProcessedEvent>, ConnectorError>;
 }
 
 pub struct PrintConnector {}
@@ -28,4 +36,13 @@ impl Connector for PrintConnector {
         println!("Querying meter: {:?}", params);
         Ok(vec![])
     }
+
+    async fn get_event_records(
+        &self,
+        tenant_id: &str,
+        event_ids: &[String],
+    ) -> Result<Vec<ProcessedEvent>, ConnectorError> {
+        println!("Getting events: {} {:?}", tenant_id, event_ids);
+        Ok(vec![])
+    }
 }
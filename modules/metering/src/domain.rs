@@ -11,6 +11,7 @@ pub enum MeterAggregation {
     Count,
     Latest,
     CountDistinct,
+    SumOverTime,
 }
 
 impl From<AggregationType> for MeterAggregation {
@@ -23,6 +24,7 @@ impl From<AggregationType> for MeterAggregation {
             AggregationType::Count => MeterAggregation::Count,
             AggregationType::Latest => MeterAggregation::Latest,
             AggregationType::CountDistinct => MeterAggregation::CountDistinct,
+            AggregationType::SumOverTime => MeterAggregation::SumOverTime,
         }
     }
 }
@@ -57,6 +59,7 @@ pub struct QueryMeterParams {
     pub namespace: String,
     pub meter_slug: String,
     pub event_name: String,
+    pub value_property: Option<String>,
     pub customers: Vec<Customer>,
     pub filter_group_by: HashMap<String, Vec<String>>,
     pub group_by: Vec<String>,
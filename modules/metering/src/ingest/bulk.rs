@@ -0,0 +1,495 @@
+use std::collections::{HashMap, VecDeque};
+
+use metering_grpc::meteroid::metering::v1::event::CustomerId;
+use metering_grpc::meteroid::metering::v1::{
+    Event, IngestLineDuplicate, IngestLineFailure, IngestResponse, IngestStreamResponse,
+};
+use serde::{Deserialize, Serialize};
+
+/// Bulk imports are written to the sink by batches of this size, the next batch being read once the previous one is sent.
+pub const BULK_BATCH_SIZE: usize = 500;
+
+/// Lines of an import longer than this are rejected without being buffered.
+pub const MAX_LINE_LENGTH: usize = 64 * 1024;
+
+/// The failures and duplicates of an import are listed up to this number each, the next ones being only counted.
+pub const MAX_REPORTED_LINES: usize = 1000;
+
+/// Maximum size of an http import body.
+pub const MAX_IMPORT_SIZE: usize = 512 * 1024 * 1024;
+
+#[derive(Debug, Serialize, PartialEq)]
+pub struct LineFailure {
+    pub line: u64,
+    pub event_id: String,
+    pub reason: String,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct BulkIngestReport {
+    pub ingested_count: u64,
+    pub failures: Vec<LineFailure>,
+    pub duplicates: Vec<LineDuplicate>,
+    pub truncated_failures_count: u64,
+    pub truncated_duplicates_count: u64,
+}
+
+#[derive(Debug, Serialize, PartialEq)]
+pub struct LineDuplicate {
+    pub line: u64,
+    pub event_id: String,
+}
+
+impl BulkIngestReport {
+    pub fn fail(&mut self, line: u64, event_id: String, reason: String) {
+        if self.failures.len() < MAX_REPORTED_LINES {
+            self.failures.push(LineFailure {
+                line,
+                event_id,
+                reason,
+            });
+        } else {
+            self.truncated_failures_count += 1;
+        }
+    }
+
+    fn duplicate(&mut self, line: u64, event_id: String) {
+        if self.duplicates.len() < MAX_REPORTED_LINES {
+            self.duplicates.push(LineDuplicate { line, event_id });
+        } else {
+            self.truncated_duplicates_count += 1;
+        }
+    }
+
+    /// Records the result of a batch, `lines` being the line and the event_id of each event of the batch.
+    /// When an event_id is repeated within the batch, the failures are matched to its first lines
+    /// and the duplicates to its last ones, as the first occurrence is the one ingested.
+    pub fn record(&mut self, lines: &[(u64, String)], res: IngestResponse) {
+        let mut lines_by_id: HashMap<&str, VecDeque<u64>> = HashMap::new();
+        for (line, event_id) in lines {
+            lines_by_id.entry(event_id).or_default().push_back(*line);
+        }
+
+        let failed = res.failures.len() + res.duplicates.len();
+        self.ingested_count += lines.len().saturating_sub(failed) as u64;
+
+        for failure in res.failures {
+            let line = lines_by_id
+                .get_mut(failure.idempotency_key.as_str())
+                .and_then(|lines| lines.pop_front())
+                .unwrap_or_default();
+            self.fail(line, failure.idempotency_key, failure.reason);
+        }
+        for event_id in res.duplicates {
+            let line = lines_by_id
+                .get_mut(event_id.as_str())
+                .and_then(|lines| lines.pop_back())
+                .unwrap_or_default();
+            self.duplicate(line, event_id);
+        }
+    }
+}
+
+impl From<BulkIngestReport> for IngestStreamResponse {
+    fn from(report: BulkIngestReport) -> Self {
+        IngestStreamResponse {
+            ingested_count: report.ingested_count,
+            failures: report
+                .failures
+                .into_iter()
+                .map(|f| IngestLineFailure {
+                    line: f.line,
+                    idempotency_key: f.event_id,
+                    reason: f.reason,
+                })
+                .collect(),
+            duplicates: report
+                .duplicates
+                .into_iter()
+                .map(|d| IngestLineDuplicate {
+                    line: d.line,
+                    idempotency_key: d.event_id,
+                })
+                .collect(),
+            truncated_failures_count: report.truncated_failures_count,
+            truncated_duplicates_count: report.truncated_duplicates_count,
+        }
+    }
+}
+
+/// Splits a body received by chunks into lines. Empty lines are kept, so that line numbers match the file.
+/// A line longer than `MAX_LINE_LENGTH` is returned as an error, its bytes being dropped up to the next line break.
+#[derive(Default)]
+pub struct LineSplitter {
+    buffer: Vec<u8>,
+    // the bytes of the buffer before this offset contain no line break
+    scanned: usize,
+    // set while dropping the rest of a line that is too long
+    skipping: bool,
+}
+
+impl LineSplitter {
+    pub fn push(&mut self, chunk: &[u8]) -> Vec<Result<String, String>> {
+        self.buffer.extend_from_slice(chunk);
+
+        let mut lines = vec![];
+        let mut start = 0;
+        while let Some(pos) = self.buffer[self.scanned..].iter().position(|b| *b == b'\n') {
+            let end = self.scanned + pos;
+            if self.skipping {
+                self.skipping = false;
+            } else if end - start > MAX_LINE_LENGTH {
+                lines.push(Err(line_too_long()));
+            } else {
+                lines.push(Ok(to_line(&self.buffer[start..end])));
+            }
+            start = end + 1;
+            self.scanned = start;
+        }
+        self.buffer.drain(..start);
+        self.scanned = self.buffer.len();
+
+        if self.buffer.len() > MAX_LINE_LENGTH {
+            if !self.skipping {
+                lines.push(Err(line_too_long()));
+                self.skipping = true;
+            }
+            self.buffer.clear();
+            self.scanned = 0;
+        }
+        lines
+    }
+
+    pub fn finish(self) -> Option<Result<String, String>> {
+        if self.buffer.is_empty() || self.skipping {
+            None
+        } else {
+            Some(Ok(to_line(&self.buffer)))
+        }
+    }
+}
+
+fn line_too_long() -> String {
+    format!("Line longer than {} bytes", MAX_LINE_LENGTH)
+}
+
+fn to_line(bytes: &[u8]) -> String {
+    let line = String::from_utf8_lossy(bytes);
+    line.strip_suffix('\r').unwrap_or(&line).to_string()
+}
+
+/// A line that could not be parsed, with its event_id when it could be read.
+#[derive(Debug)]
+pub struct InvalidLine {
+    pub event_id: String,
+    pub reason: String,
+}
+
+impl InvalidLine {
+    fn new(event_id: &str, reason: String) -> Self {
+        InvalidLine {
+            event_id: event_id.to_string(),
+            reason,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct JsonEvent {
+    event_id: String,
+    event_name: String,
+    meteroid_customer_id: Option<String>,
+    external_customer_id: Option<String>,
+    timestamp: Option<String>,
+    #[serde(default)]
+    properties: HashMap<String, serde_json::Value>,
+}
+
+/// Parses an NDJSON line, with the same fields as the grpc `Event`. Non-string properties are kept as their json value.
+pub fn parse_json_line(line: &str) -> Result<Event, InvalidLine> {
+    let value: serde_json::Value = serde_json::from_str(line)
+        .map_err(|e| InvalidLine::new("", format!("Invalid json : {}", e)))?;
+    let event_id = value
+        .get("event_id")
+        .and_then(|v| v.as_str())
+        .unwrap_or_default()
+        .to_string();
+
+    let event: JsonEvent = serde_json::from_value(value)
+        .map_err(|e| InvalidLine::new(&event_id, format!("Invalid json : {}", e)))?;
+
+    let properties = event
+        .properties
+        .into_iter()
+        .map(|(key, value)| match value {
+            serde_json::Value::String(s) => (key, s),
+            value => (key, value.to_string()),
+        })
+        .collect();
+
+    Ok(Event {
+        event_id: event.event_id,
+        event_name: event.event_name,
+        customer_id: to_customer_id(event.meteroid_customer_id, event.external_customer_id)
+            .map_err(|e| InvalidLine::new(&event_id, e))?,
+        timestamp: event.timestamp.unwrap_or_default(),
+        properties,
+    })
+}
+
+/// The header line of a CSV import. The event columns are named after the grpc `Event` fields,
+/// any other column is a property (empty cells are skipped).
+pub struct CsvHeader {
+    columns: Vec<String>,
+}
+
+impl CsvHeader {
+    pub fn parse(line: &str) -> Result<CsvHeader, String> {
+        let columns = parse_csv_record(line)?;
+
+        for required in ["event_id", "event_name"] {
+            if !columns.iter().any(|c| c == required) {
+                return Err(format!("Missing {} column", required));
+            }
+        }
+        if !columns
+            .iter()
+            .any(|c| c == "meteroid_customer_id" || c == "external_customer_id")
+        {
+            return Err("Missing meteroid_customer_id or external_customer_id column".to_string());
+        }
+
+        Ok(CsvHeader { columns })
+    }
+
+    pub fn parse_line(&self, line: &str) -> Result<Event, InvalidLine> {
+        let values = parse_csv_record(line).map_err(|e| InvalidLine::new("", e))?;
+        if values.len() != self.columns.len() {
+            let event_id = self
+                .columns
+                .iter()
+                .position(|c| c == "event_id")
+                .and_then(|i| values.get(i))
+                .map(|v| v.as_str())
+                .unwrap_or_default();
+            return Err(InvalidLine::new(
+                event_id,
+                format!(
+                    "Expected {} columns, found {}",
+                    self.columns.len(),
+                    values.len()
+                ),
+            ));
+        }
+
+        let mut event = Event::default();
+        let mut meteroid_customer_id = None;
+        let mut external_customer_id = None;
+
+        for (column, value) in self.columns.iter().zip(values) {
+            match column.as_str() {
+                "event_id" => event.event_id = value,
+                "event_name" => event.event_name = value,
+                "timestamp" => event.timestamp = value,
+                _ if value.is_empty() => {}
+                "meteroid_customer_id" => meteroid_customer_id = Some(value),
+                "external_customer_id" => external_customer_id = Some(value),
+                _ => {
+                    event.properties.insert(column.clone(), value);
+                }
+            }
+        }
+        event.customer_id = to_customer_id(meteroid_customer_id, external_customer_id)
+            .map_err(|e| InvalidLine::new(&event.event_id, e))?;
+
+        Ok(event)
+    }
+}
+
+// a line is a single record, quoted fields cannot contain line breaks
+fn parse_csv_record(line: &str) -> Result<Vec<String>, String> {
+    let mut reader = csv::ReaderBuilder::new()
+        .has_headers(false)
+        .from_reader(line.as_bytes());
+
+    match reader.records().next() {
+        Some(record) => Ok(record
+            .map_err(|e| format!("Invalid csv : {}", e))?
+            .iter()
+            .map(|v| v.to_string())
+            .collect()),
+        None => Ok(vec![]),
+    }
+}
+
+fn to_customer_id(
+    meteroid_customer_id: Option<String>,
+    external_customer_id: Option<String>,
+) -> Result<Option<CustomerId>, String> {
+    match (meteroid_customer_id, external_customer_id) {
+        (Some(_), Some(_)) => {
+            Err("Only one of meteroid_customer_id and external_customer_id can be set".to_string())
+        }
+        (Some(id), None) => Ok(Some(CustomerId::MeteroidCustomerId(id))),
+        (None, Some(id)) => Ok(Some(CustomerId::ExternalCustomerId(id))),
+        (None, None) => Ok(None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use metering_grpc::meteroid::metering::v1::IngestFailure;
+
+    #[test]
+    fn test_line_splitter() {
+        let mut splitter = LineSplitter::default();
+
+        assert_eq!(splitter.push(b"a,b\r\nc"), vec![Ok("a,b".to_string())]);
+        assert_eq!(
+            splitter.push(b",d\n\ne"),
+            vec![Ok("c,d".to_string()), Ok("".to_string())]
+        );
+        assert_eq!(splitter.finish(), Some(Ok("e".to_string())));
+    }
+
+    #[test]
+    fn test_line_splitter_max_length() {
+        let mut splitter = LineSplitter::default();
+        let long_line = vec![b'a'; MAX_LINE_LENGTH + 1];
+
+        // a long line is rejected once, while it is received
+        assert_eq!(splitter.push(b"a\n"), vec![Ok("a".to_string())]);
+        assert_eq!(splitter.push(&long_line), vec![Err(line_too_long())]);
+        assert_eq!(splitter.push(&long_line), vec![]);
+        assert_eq!(splitter.push(b"a\nb\n"), vec![Ok("b".to_string())]);
+
+        // or within a chunk
+        let mut chunk = long_line.clone();
+        chunk.extend_from_slice(b"\nc");
+        assert_eq!(splitter.push(&chunk), vec![Err(line_too_long())]);
+        assert_eq!(splitter.finish(), Some(Ok("c".to_string())));
+
+        let mut splitter = LineSplitter::default();
+        assert_eq!(splitter.push(&long_line), vec![Err(line_too_long())]);
+        assert_eq!(splitter.finish(), None);
+    }
+
+    #[test]
+    fn test_parse_json_line() {
+        let event = parse_json_line(
+            r#"{"event_id": "evt_1", "event_name": "api_calls", "external_customer_id": "cus_1", "timestamp": "2024-01-01T00:00:00Z", "properties": {"endpoint": "/users", "count": 3}}"#,
+        )
+        .unwrap();
+
+        assert_eq!(event.event_id, "evt_1");
+        assert_eq!(
+            event.customer_id,
+            Some(CustomerId::ExternalCustomerId("cus_1".to_string()))
+        );
+        assert_eq!(event.properties["endpoint"], "/users");
+        assert_eq!(event.properties["count"], "3");
+
+        assert_eq!(
+            parse_json_line(r#"{"event_id": "evt_1"}"#)
+                .unwrap_err()
+                .event_id,
+            "evt_1"
+        );
+        assert_eq!(parse_json_line("{").unwrap_err().event_id, "");
+    }
+
+    #[test]
+    fn test_parse_csv_line() {
+        let header =
+            CsvHeader::parse("event_id,event_name,external_customer_id,timestamp,endpoint,region")
+                .unwrap();
+
+        let event = header
+            .parse_line(r#"evt_1,api_calls,cus_1,2024-01-01T00:00:00Z,"/users,/orgs","#)
+            .unwrap();
+
+        assert_eq!(event.event_id, "evt_1");
+        assert_eq!(event.timestamp, "2024-01-01T00:00:00Z");
+        assert_eq!(
+            event.customer_id,
+            Some(CustomerId::ExternalCustomerId("cus_1".to_string()))
+        );
+        assert_eq!(
+            event.properties,
+            HashMap::from([("endpoint".to_string(), "/users,/orgs".to_string())])
+        );
+
+        assert_eq!(
+            header
+                .parse_line("evt_2,api_calls,cus_1")
+                .unwrap_err()
+                .event_id,
+            "evt_2"
+        );
+        assert!(CsvHeader::parse("event_id,event_name,timestamp").is_err());
+    }
+
+    #[test]
+    fn test_report_record() {
+        let mut report = BulkIngestReport::default();
+
+        let lines = vec![
+            (1, "evt_1".to_string()),
+            (2, "evt_2".to_string()),
+            (3, "evt_3".to_string()),
+            (4, "evt_2".to_string()),
+        ];
+        report.record(
+            &lines,
+            IngestResponse {
+                failures: vec![IngestFailure {
+                    idempotency_key: "evt_3".to_string(),
+                    reason: "Unable to resolve external id".to_string(),
+                }],
+                duplicates: vec!["evt_2".to_string()],
+            },
+        );
+
+        assert_eq!(report.ingested_count, 2);
+        assert_eq!(
+            report.failures,
+            vec![LineFailure {
+                line: 3,
+                event_id: "evt_3".to_string(),
+                reason: "Unable to resolve external id".to_string(),
+            }]
+        );
+        assert_eq!(
+            report.duplicates,
+            vec![LineDuplicate {
+                line: 4,
+                event_id: "evt_2".to_string(),
+            }]
+        );
+        assert_eq!(report.truncated_failures_count, 0);
+    }
+
+    #[test]
+    fn test_report_truncated() {
+        let mut report = BulkIngestReport::default();
+
+        let lines: Vec<(u64, String)> = (1..=MAX_REPORTED_LINES as u64 + 2)
+            .map(|line| (line, format!("evt_{}", line)))
+            .collect();
+        report.record(
+            &lines,
+            IngestResponse {
+                failures: vec![],
+                duplicates: lines.iter().map(|(_, id)| id.clone()).collect(),
+            },
+        );
+        report.fail(0, String::new(), "Invalid json".to_string());
+
+        assert_eq!(report.ingested_count, 0);
+        assert_eq!(report.duplicates.len(), MAX_REPORTED_LINES);
+        assert_eq!(report.truncated_duplicates_count, 2);
+        assert_eq!(report.failures.len(), 1);
+        assert_eq!(report.truncated_failures_count, 0);
+    }
+}
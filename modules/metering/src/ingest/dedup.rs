@@ -0,0 +1,106 @@
+use chrono::{DateTime, Duration, Utc};
+use quick_cache::sync::{Cache, EntryAction, EntryResult};
+
+/**
+ * Keeps track of the events accepted by the ingest path, keyed by `tenant_id:event_id` (see `ProcessedEvent::key`),
+ * so that a client retrying a batch gets its events reported as duplicates while they are still in flight to the store.
+ * Keys are considered duplicates for `window` after they were first accepted.
+ *
+ * The keys are kept in memory, per process. The events already stored are found by the connector instead,
+ * and the meters read a single ingestion of each event whatever reaches the store (cf deduplicated_events_sql).
+ */
+pub struct EventDeduplicator {
+    seen: Cache<String, DateTime<Utc>>,
+    window: Duration,
+}
+
+impl EventDeduplicator {
+    pub fn new(capacity: usize, window: Duration) -> Self {
+        EventDeduplicator {
+            seen: Cache::new(capacity),
+            window,
+        }
+    }
+
+    /// Reserves the key for this event. Returns false if it was already accepted within the window.
+    pub fn reserve(&self, key: &str, now: DateTime<Utc>) -> bool {
+        // the entry is checked and refreshed under the lock of its shard, so that concurrent requests
+        // cannot both reserve an expired key
+        let result = self.seen.entry(key, None, |_, seen_at| {
+            if now - *seen_at < self.window {
+                EntryAction::Retain(false)
+            } else {
+                *seen_at = now;
+                EntryAction::Retain(true)
+            }
+        });
+
+        match result {
+            EntryResult::Retained(reserved) => reserved,
+            EntryResult::Vacant(guard) | EntryResult::Replaced(guard, _) => {
+                let _ = guard.insert(now);
+                true
+            }
+            // unreachable without a timeout, the event is accepted rather than dropped
+            EntryResult::Removed(_, _) | EntryResult::Timeout => true,
+        }
+    }
+
+    /// Releases a reservation, for events that could not be delivered to the sink and can be retried.
+    pub fn release(&self, key: &str) {
+        self.seen.remove(key);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::EventDeduplicator;
+    use chrono::{Duration, Utc};
+
+    #[test]
+    fn test_reserve_within_window() {
+        let dedup = EventDeduplicator::new(100, Duration::hours(1));
+        let now = Utc::now();
+
+        assert!(dedup.reserve("tenant:event", now));
+        assert!(!dedup.reserve("tenant:event", now + Duration::minutes(30)));
+        assert!(dedup.reserve("other_tenant:event", now));
+
+        // the window is over, the event is accepted again
+        assert!(dedup.reserve("tenant:event", now + Duration::hours(2)));
+        assert!(!dedup.reserve("tenant:event", now + Duration::hours(2)));
+    }
+
+    #[test]
+    fn test_release() {
+        let dedup = EventDeduplicator::new(100, Duration::hours(1));
+        let now = Utc::now();
+
+        assert!(dedup.reserve("tenant:event", now));
+        dedup.release("tenant:event");
+        assert!(dedup.reserve("tenant:event", now));
+    }
+
+    #[test]
+    fn test_concurrent_reserve_of_expired_key() {
+        let dedup = std::sync::Arc::new(EventDeduplicator::new(100, Duration::hours(1)));
+        let now = Utc::now();
+
+        assert!(dedup.reserve("tenant:event", now));
+
+        let later = now + Duration::hours(2);
+        let reserved = (0..8)
+            .map(|_| {
+                let dedup = dedup.clone();
+                std::thread::spawn(move || dedup.reserve("tenant:event", later))
+            })
+            .collect::<Vec<_>>()
+            .into_iter()
+            .map(|handle| handle.join().unwrap())
+            .filter(|reserved| *reserved)
+            .count();
+
+        // only one of the requests gets the expired key
+        assert_eq!(reserved, 1);
+    }
+}
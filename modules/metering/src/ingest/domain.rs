@@ -1,10 +1,12 @@
 use chrono::NaiveDateTime;
 use std::collections::HashMap;
+use std::hash::{DefaultHasher, Hash, Hasher};
 
 use metering_grpc::meteroid::metering::v1::Event;
 use serde::Serialize;
+use uuid::Uuid;
 
-#[derive(Clone, Default, Debug, Serialize, Eq, PartialEq)]
+#[derive(Clone, Debug, Serialize, Eq, PartialEq)]
 pub struct ProcessedEvent {
     pub event_id: String,
     pub event_name: String,
@@ -12,15 +14,141 @@ pub struct ProcessedEvent {
     pub tenant_id: String,
     pub event_timestamp: NaiveDateTime,
     pub properties: HashMap<String, String>,
+    // 1 for an ingested event, -1 for a compensating record cancelling a previous record with the same content
+    pub sign: i8,
+    // the audit reason of an amendment or a void, empty for ingested events
+    pub correction_reason: String,
+    // empty for ingested events, so that the retries of an ingestion are read once (cf deduplicated_events_sql),
+    // and unique for the correction records, that reuse the event_id of the records they replace
+    pub record_id: String,
 }
 
 impl ProcessedEvent {
     pub fn key(&self) -> String {
         format!("{}:{}", self.tenant_id, self.event_id)
     }
+
+    /// The record cancelling this one in the meters, when the event is amended or voided.
+    pub fn compensation(&self, reason: &str) -> ProcessedEvent {
+        ProcessedEvent {
+            sign: -1,
+            correction_reason: reason.to_string(),
+            record_id: Uuid::new_v4().to_string(),
+            ..self.clone()
+        }
+    }
+
+    /// Identifies the compensation of this record, so that it is not cancelled twice by concurrent or retried corrections.
+    /// The key depends on the content of the record, as an amended event keeps its event_id.
+    pub fn compensation_key(&self) -> String {
+        let mut properties: Vec<_> = self.properties.iter().collect();
+        properties.sort();
+
+        let mut hasher = DefaultHasher::new();
+        self.event_name.hash(&mut hasher);
+        self.customer_id.hash(&mut hasher);
+        self.event_timestamp.hash(&mut hasher);
+        properties.hash(&mut hasher);
+
+        format!("{}:compensation:{:x}", self.key(), hasher.finish())
+    }
+
+    fn same_content(&self, other: &ProcessedEvent) -> bool {
+        self.event_id == other.event_id
+            && self.event_name == other.event_name
+            && self.customer_id == other.customer_id
+            && self.event_timestamp == other.event_timestamp
+            && self.properties == other.properties
+    }
+}
+
+/// Nets the stored records of some events, returning the ones that are not cancelled by a compensating record.
+/// An event has a single current record, unless a correction was only partially written (it is then fixed by retrying it).
+pub fn current_records(records: Vec<ProcessedEvent>) -> Vec<ProcessedEvent> {
+    let mut netted: Vec<(ProcessedEvent, i64)> = vec![];
+
+    for record in records {
+        let sign = record.sign as i64;
+        match netted.iter_mut().find(|(r, _)| r.same_content(&record)) {
+            Some((_, net)) => *net += sign,
+            None => netted.push((record, sign)),
+        }
+    }
+
+    netted
+        .into_iter()
+        .filter(|(_, net)| *net > 0)
+        .map(|(record, _)| ProcessedEvent {
+            sign: 1,
+            correction_reason: String::new(),
+            ..record
+        })
+        .collect()
 }
 
 pub struct FailedEvent {
     pub event: Event,
     pub reason: String,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(event_id: &str, value: &str) -> ProcessedEvent {
+        ProcessedEvent {
+            event_id: event_id.to_string(),
+            event_name: "api_calls".to_string(),
+            customer_id: "customer".to_string(),
+            tenant_id: "tenant".to_string(),
+            event_timestamp: chrono::DateTime::from_timestamp(1700000000, 0)
+                .unwrap()
+                .naive_utc(),
+            properties: HashMap::from([("value".to_string(), value.to_string())]),
+            sign: 1,
+            correction_reason: String::new(),
+            record_id: String::new(),
+        }
+    }
+
+    #[test]
+    fn test_current_records() {
+        let voided = record("evt_1", "10");
+        let amended = record("evt_2", "10");
+        let amendment = record("evt_2", "12");
+        let untouched = record("evt_3", "5");
+
+        let current = current_records(vec![
+            voided.clone(),
+            voided.compensation("duplicate"),
+            amended.clone(),
+            amended.compensation("wrong value"),
+            amendment.clone(),
+            untouched.clone(),
+        ]);
+
+        assert_eq!(current, vec![amendment, untouched]);
+    }
+
+    #[test]
+    fn test_compensation_key() {
+        let original = record("evt_1", "10");
+        let mut same = original.clone();
+        same.properties
+            .insert("region".to_string(), "eu".to_string());
+        let mut reordered = record("evt_1", "10");
+        reordered
+            .properties
+            .insert("region".to_string(), "eu".to_string());
+
+        assert_eq!(same.compensation_key(), reordered.compensation_key());
+        assert_ne!(original.compensation_key(), same.compensation_key());
+        assert_ne!(
+            original.compensation_key(),
+            record("evt_1", "12").compensation_key()
+        );
+        assert!(original
+            .compensation_key()
+            .starts_with("tenant:evt_1:compensation:"));
+    }
+}
@@ -0,0 +1,202 @@
+use axum::body::Body;
+use axum::extract::{Query, State};
+use axum::http::{header, HeaderMap, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::routing::post;
+use axum::{Extension, Json, Router};
+use common_grpc::middleware::server::auth::{extract_tenant, AuthorizedState};
+use futures::StreamExt;
+use metering_grpc::meteroid::metering::v1::Event;
+use serde::{Deserialize, Serialize};
+use tower_http::limit::RequestBodyLimitLayer;
+
+use crate::ingest::bulk::{
+    parse_json_line, BulkIngestReport, CsvHeader, LineSplitter, BULK_BATCH_SIZE, MAX_IMPORT_SIZE,
+};
+use crate::ingest::service::EventsService;
+
+/// The bulk imports, served over HTTP by the grpc server (behind its api key auth and metric layers),
+/// so that files can be uploaded without a grpc client.
+pub fn routes(events_service: EventsService) -> Router {
+    Router::new()
+        .nest("/v1/events", import_routes())
+        .with_state(events_service)
+        // imports are streamed and written by batches, their lines are limited by LineSplitter
+        .layer(RequestBodyLimitLayer::new(MAX_IMPORT_SIZE))
+}
+
+fn import_routes() -> Router<EventsService> {
+    Router::new().route("/import", post(import_events))
+}
+
+#[derive(Deserialize)]
+struct ImportParams {
+    #[serde(default)]
+    allow_backfilling: bool,
+}
+
+#[derive(Serialize)]
+struct ImportResponse {
+    #[serde(flatten)]
+    report: BulkIngestReport,
+    // set if the import was interrupted, the report then covers the lines read so far
+    error: Option<String>,
+}
+
+enum ImportFormat {
+    Ndjson,
+    Csv(Option<CsvHeader>),
+}
+
+/// Imports events from an NDJSON (`application/x-ndjson`) or CSV (`text/csv`) body, one event per line.
+/// The body is read as it is written to the sink, and the response reports the lines that could not be ingested.
+async fn import_events(
+    State(events_service): State<EventsService>,
+    authorized_state: Option<Extension<AuthorizedState>>,
+    Query(params): Query<ImportParams>,
+    headers: HeaderMap,
+    body: Body,
+) -> Response {
+    let tenant_id = match extract_tenant(authorized_state.as_deref()) {
+        Ok(tenant_id) => tenant_id.to_string(),
+        Err(e) => {
+            log::warn!("Error authenticating import: {}", e);
+            return StatusCode::UNAUTHORIZED.into_response();
+        }
+    };
+
+    let content_type = headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or_default();
+    let mut format = if content_type.starts_with("application/x-ndjson") {
+        ImportFormat::Ndjson
+    } else if content_type.starts_with("text/csv") {
+        ImportFormat::Csv(None)
+    } else {
+        return (
+            StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            "Expected application/x-ndjson or text/csv",
+        )
+            .into_response();
+    };
+
+    let mut report = BulkIngestReport::default();
+    let mut batch: Vec<(u64, Event)> = vec![];
+    let mut splitter = LineSplitter::default();
+    let mut line_number = 0;
+    let mut stream = body.into_data_stream();
+
+    let res: Result<(), (StatusCode, String)> = async {
+        loop {
+            let (lines, done) = match stream.next().await {
+                Some(chunk) => (
+                    splitter.push(&chunk.map_err(|e| {
+                        (
+                            StatusCode::BAD_REQUEST,
+                            format!("Unable to read the body : {}", e),
+                        )
+                    })?),
+                    false,
+                ),
+                None => (
+                    std::mem::take(&mut splitter).finish().into_iter().collect(),
+                    true,
+                ),
+            };
+
+            for line in lines {
+                line_number += 1;
+                let line = match line {
+                    Ok(line) => line,
+                    Err(reason) if matches!(format, ImportFormat::Csv(None)) => {
+                        return Err((
+                            StatusCode::UNPROCESSABLE_ENTITY,
+                            format!("Invalid csv header : {}", reason),
+                        ))
+                    }
+                    Err(reason) => {
+                        report.fail(line_number, String::new(), reason);
+                        continue;
+                    }
+                };
+                if line.trim().is_empty() {
+                    continue;
+                }
+
+                let parsed = match &mut format {
+                    ImportFormat::Ndjson => parse_json_line(&line),
+                    ImportFormat::Csv(header @ None) => match CsvHeader::parse(&line) {
+                        Ok(parsed) => {
+                            *header = Some(parsed);
+                            continue;
+                        }
+                        Err(e) => {
+                            return Err((
+                                StatusCode::UNPROCESSABLE_ENTITY,
+                                format!("Invalid csv header : {}", e),
+                            ))
+                        }
+                    },
+                    ImportFormat::Csv(Some(header)) => header.parse_line(&line),
+                };
+
+                match parsed {
+                    Ok(event) => batch.push((line_number, event)),
+                    Err(invalid) => report.fail(line_number, invalid.event_id, invalid.reason),
+                }
+
+                if batch.len() == BULK_BATCH_SIZE {
+                    events_service
+                        .ingest_lines(
+                            &tenant_id,
+                            std::mem::take(&mut batch),
+                            params.allow_backfilling,
+                            &mut report,
+                        )
+                        .await
+                        .map_err(|e| {
+                            (StatusCode::INTERNAL_SERVER_ERROR, e.message().to_string())
+                        })?;
+                }
+            }
+
+            if done {
+                break;
+            }
+        }
+
+        events_service
+            .ingest_lines(
+                &tenant_id,
+                std::mem::take(&mut batch),
+                params.allow_backfilling,
+                &mut report,
+            )
+            .await
+            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.message().to_string()))
+    }
+    .await;
+
+    match res {
+        Ok(()) => (
+            StatusCode::OK,
+            Json(ImportResponse {
+                report,
+                error: None,
+            }),
+        )
+            .into_response(),
+        Err((status, e)) => {
+            log::error!("Import interrupted at line {}: {}", line_number, e);
+            (
+                status,
+                Json(ImportResponse {
+                    report,
+                    error: Some(e),
+                }),
+            )
+                .into_response()
+        }
+    }
+}
@@ -6,54 +6,58 @@ use std::sync::Arc;
 use crate::cache::CUSTOMER_ID_CACHE;
 use common_grpc::middleware::client::LayeredClientService;
 use metering_grpc::meteroid::metering::v1::event::CustomerId;
-use metering_grpc::meteroid::metering::v1::{Event, IngestFailure, IngestRequest, IngestResponse};
-use tonic::{Request, Response, Status};
+use metering_grpc::meteroid::metering::v1::{
+    AmendEventsRequest, AmendEventsResponse, Event, IngestFailure, IngestRequest, IngestResponse,
+    IngestStreamResponse, VoidEventsRequest, VoidEventsResponse,
+};
+use tonic::{Request, Response, Status, Streaming};
 use tracing::error;
+use uuid::Uuid;
 
-use crate::ingest::domain::{FailedEvent, ProcessedEvent};
+use crate::connectors::Connector;
+use crate::ingest::bulk::{BulkIngestReport, BULK_BATCH_SIZE};
+use crate::ingest::dedup::EventDeduplicator;
+use crate::ingest::domain::{current_records, FailedEvent, ProcessedEvent};
 use crate::ingest::sinks::Sink;
+use crate::utils::datetime_to_timestamp;
 use common_grpc::middleware::server::auth::RequestExt;
 use meteroid_grpc::meteroid::internal::v1::internal_service_client::InternalServiceClient;
-use meteroid_grpc::meteroid::internal::v1::ResolveCustomerExternalIdsRequest;
+use meteroid_grpc::meteroid::internal::v1::{
+    MarkDraftInvoicesOutdatedRequest, ResolveCustomerExternalIdsRequest,
+};
 
 #[derive(Clone)]
 pub struct EventsService {
     pub internal_client: InternalServiceClient<LayeredClientService>,
     pub sink: Arc<dyn Sink + Send + Sync>,
+    pub deduplicator: Arc<EventDeduplicator>,
+    pub connector: Arc<dyn Connector + Send + Sync>,
 }
 
 impl EventsService {
     pub fn new(
         internal_client: InternalServiceClient<LayeredClientService>,
         sink: Arc<dyn Sink + Send + Sync>,
+        deduplicator: Arc<EventDeduplicator>,
+        connector: Arc<dyn Connector + Send + Sync>,
     ) -> Self {
         EventsService {
             internal_client,
             sink,
+            deduplicator,
+            connector,
         }
     }
-}
 
-#[tonic::async_trait]
-impl EventsServiceGrpc for EventsService {
-    #[tracing::instrument(skip(self, request))]
-    async fn ingest(
+    /// Validates the events and resolves their customer ids, returning the events that could not be processed separately.
+    async fn resolve_events(
         &self,
-        request: Request<IngestRequest>,
-    ) -> Result<Response<IngestResponse>, Status> {
-        let tenant_id = request.tenant()?.to_string();
-
-        let req = request.into_inner();
-
-        let events = req.events;
-
-        let allow_backfilling = req.allow_backfilling;
-
-        if events.is_empty() {
-            return Err(Status::invalid_argument("No events provided"));
-        } else if events.len() > 500 {
-            return Err(Status::invalid_argument("Too many events provided"));
-        }
+        tenant_id: &str,
+        events: Vec<Event>,
+        now: DateTime<Utc>,
+        allow_backfilling: bool,
+    ) -> Result<(Vec<ProcessedEvent>, Vec<FailedEvent>), Status> {
+        let tenant_id = tenant_id.to_string();
 
         let mut failed_events = vec![];
 
@@ -64,8 +68,6 @@ impl EventsServiceGrpc for EventsService {
         let mut unresolved = vec![];
         let mut unresolved_ids = vec![];
 
-        let now = chrono::Utc::now();
-
         for event in events {
             match validate_event(&event, &now, allow_backfilling) {
                 Ok((id, ts)) => match id {
@@ -148,6 +150,48 @@ impl EventsServiceGrpc for EventsService {
             })
         }
 
+        Ok((resolved, failed_events))
+    }
+
+    /// Ingests a batch of events, skipping and reporting the ones already ingested.
+    /// The meters read a single ingestion of each event anyway (cf deduplicated_events_sql), this keeps the duplicates
+    /// out of the sink: the ones still in flight are found by the `EventDeduplicator`, the stored ones by the connector.
+    pub(crate) async fn ingest_batch(
+        &self,
+        tenant_id: &str,
+        events: Vec<Event>,
+        allow_backfilling: bool,
+    ) -> Result<IngestResponse, Status> {
+        let tenant_id = tenant_id.to_string();
+
+        let now = chrono::Utc::now();
+
+        let (resolved, failed_events) = self
+            .resolve_events(&tenant_id, events, now, allow_backfilling)
+            .await?;
+
+        // - skip the events accepted by this instance within the dedup window (including duplicates within this batch)
+        let mut duplicates = vec![];
+        let resolved: Vec<ProcessedEvent> = resolved
+            .into_iter()
+            .filter(|event| {
+                let reserved = self.deduplicator.reserve(&event.key(), now);
+                if !reserved {
+                    duplicates.push(event.event_id.clone());
+                }
+                reserved
+            })
+            .collect();
+
+        // - skip the events already stored, ingested through another instance or before the dedup window
+        let stored = self.get_stored_event_ids(&tenant_id, &resolved).await;
+        let (stored, resolved): (Vec<ProcessedEvent>, Vec<ProcessedEvent>) = resolved
+            .into_iter()
+            .partition(|event| stored.contains(&event.event_id));
+        duplicates.extend(stored.into_iter().map(|event| event.event_id));
+
+        let keys: Vec<String> = resolved.iter().map(|e| e.key()).collect();
+
         let default_attributes = &[
             KeyValue {
                 key: "tenant_id".into(),
@@ -160,6 +204,8 @@ impl EventsServiceGrpc for EventsService {
             .send(resolved, default_attributes)
             .await
             .map_err(|e| {
+                // nothing was sent, so the events can be retried
+                keys.iter().for_each(|key| self.deduplicator.release(key));
                 Status::internal("Unable to send events")
                     .set_source(Arc::new(e))
                     .clone()
@@ -173,15 +219,385 @@ impl EventsServiceGrpc for EventsService {
             })
             .collect();
 
-        failures.extend(res.into_iter().map(|rec| IngestFailure {
-            idempotency_key: rec.event.event_id,
-            reason: rec.error.to_string(),
+        failures.extend(res.into_iter().map(|rec| {
+            self.deduplicator.release(&rec.event.key());
+            IngestFailure {
+                idempotency_key: rec.event.event_id,
+                reason: rec.error.to_string(),
+            }
         }));
 
         if !failures.is_empty() {
             error!("Failed count {}", failures.len());
         }
-        Ok(Response::new(IngestResponse { failures }))
+        if !duplicates.is_empty() {
+            log::info!("Skipped {} duplicate events", duplicates.len());
+        }
+        Ok(IngestResponse {
+            failures,
+            duplicates,
+        })
+    }
+
+    /// Ingests a batch of a bulk import, reporting the failures by line.
+    pub(crate) async fn ingest_lines(
+        &self,
+        tenant_id: &str,
+        batch: Vec<(u64, Event)>,
+        allow_backfilling: bool,
+        report: &mut BulkIngestReport,
+    ) -> Result<(), Status> {
+        if batch.is_empty() {
+            return Ok(());
+        }
+
+        let lines: Vec<(u64, String)> = batch
+            .iter()
+            .map(|(line, event)| (*line, event.event_id.clone()))
+            .collect();
+        let events = batch.into_iter().map(|(_, event)| event).collect();
+
+        let res = self
+            .ingest_batch(tenant_id, events, allow_backfilling)
+            .await?;
+
+        report.record(&lines, res);
+
+        Ok(())
+    }
+
+    /// The ids of these events that are already stored.
+    /// The events are sent if the connector cannot be reached, as the meters deduplicate them when reading.
+    async fn get_stored_event_ids(
+        &self,
+        tenant_id: &str,
+        events: &[ProcessedEvent],
+    ) -> Vec<String> {
+        if events.is_empty() {
+            return vec![];
+        }
+
+        let event_ids: Vec<String> = events.iter().map(|e| e.event_id.clone()).collect();
+
+        match self
+            .connector
+            .get_event_records(tenant_id, &event_ids)
+            .await
+        {
+            Ok(records) => records.into_iter().map(|r| r.event_id).collect(),
+            Err(e) => {
+                error!("Unable to check the stored events : {}", e);
+                vec![]
+            }
+        }
+    }
+
+    /// The current records of these events, as stored by the connector.
+    async fn get_current_records(
+        &self,
+        tenant_id: &str,
+        event_ids: &[String],
+    ) -> Result<Vec<ProcessedEvent>, Status> {
+        let records = self
+            .connector
+            .get_event_records(tenant_id, event_ids)
+            .await
+            .map_err(|e| Status::internal(format!("Failed to get events : {}", e)))?;
+
+        Ok(current_records(records))
+    }
+
+    /// Reserves the compensations of the current records of an event, so that a record cannot be cancelled twice,
+    /// by concurrent corrections or by a correction reading the records before the previous one is stored.
+    /// Returns false, reserving nothing, if one of them was already compensated within the deduplication window.
+    fn reserve_compensations(&self, records: &[&ProcessedEvent], now: DateTime<Utc>) -> bool {
+        let mut reserved: Vec<String> = vec![];
+        for record in records {
+            let key = record.compensation_key();
+            if !self.deduplicator.reserve(&key, now) {
+                reserved
+                    .iter()
+                    .for_each(|key| self.deduplicator.release(key));
+                return false;
+            }
+            reserved.push(key);
+        }
+        true
+    }
+
+    /// Sends the correction records through the sink, then flags the drafts that may have billed the corrected usage.
+    /// Corrections bypass the deduplication of the ingested events, as they reuse the event_id of the records they replace.
+    /// Their compensations are reserved instead (cf reserve_compensations), and released if they could not be sent.
+    async fn send_corrections(
+        &self,
+        tenant_id: &str,
+        records: Vec<ProcessedEvent>,
+    ) -> Result<Vec<IngestFailure>, Status> {
+        if records.is_empty() {
+            return Ok(vec![]);
+        }
+
+        let mut customer_ids: Vec<String> = records.iter().map(|r| r.customer_id.clone()).collect();
+        customer_ids.sort();
+        customer_ids.dedup();
+        let since = records.iter().map(|r| r.event_timestamp).min();
+
+        let default_attributes = &[KeyValue {
+            key: "tenant_id".into(),
+            value: tenant_id.to_string().into(),
+        }];
+
+        let compensation_keys: Vec<String> = records
+            .iter()
+            .filter(|r| r.sign < 0)
+            .map(|r| r.compensation_key())
+            .collect();
+
+        let res = self
+            .sink
+            .send(records, default_attributes)
+            .await
+            .map_err(|e| {
+                // nothing was sent, so the corrections can be retried
+                compensation_keys
+                    .iter()
+                    .for_each(|key| self.deduplicator.release(key));
+                Status::internal("Unable to send events")
+                    .set_source(Arc::new(e))
+                    .clone()
+            })?;
+
+        let mut failures: Vec<IngestFailure> = res
+            .into_iter()
+            .map(|rec| {
+                if rec.event.sign < 0 {
+                    self.deduplicator.release(&rec.event.compensation_key());
+                }
+                IngestFailure {
+                    idempotency_key: rec.event.event_id,
+                    reason: rec.error.to_string(),
+                }
+            })
+            .collect();
+        failures.dedup_by(|a, b| a.idempotency_key == b.idempotency_key);
+
+        // the corrections are stored at this point, a failure here only delays the refresh of the drafts
+        let mut client = self.internal_client.clone();
+        let res = client
+            .mark_draft_invoices_outdated(MarkDraftInvoicesOutdatedRequest {
+                tenant_id: tenant_id.to_string(),
+                customer_ids,
+                since: since.map(|ts| datetime_to_timestamp(ts.and_utc())),
+            })
+            .await;
+        if let Err(e) = res {
+            error!("Unable to mark the draft invoices as outdated : {}", e);
+        }
+
+        Ok(failures)
+    }
+}
+
+const CORRECTION_IN_PROGRESS: &str =
+    "The event is already being corrected, retry once the previous correction is stored";
+
+#[tonic::async_trait]
+impl EventsServiceGrpc for EventsService {
+    #[tracing::instrument(skip(self, request))]
+    async fn ingest(
+        &self,
+        request: Request<IngestRequest>,
+    ) -> Result<Response<IngestResponse>, Status> {
+        let tenant_id = request.tenant()?.to_string();
+
+        let req = request.into_inner();
+
+        let events = req.events;
+
+        let allow_backfilling = req.allow_backfilling;
+
+        if events.is_empty() {
+            return Err(Status::invalid_argument("No events provided"));
+        } else if events.len() > 500 {
+            return Err(Status::invalid_argument("Too many events provided"));
+        }
+
+        let res = self
+            .ingest_batch(&tenant_id, events, allow_backfilling)
+            .await?;
+
+        Ok(Response::new(res))
+    }
+
+    #[tracing::instrument(skip(self, request))]
+    async fn ingest_stream(
+        &self,
+        request: Request<Streaming<IngestRequest>>,
+    ) -> Result<Response<IngestStreamResponse>, Status> {
+        let tenant_id = request.tenant()?.to_string();
+
+        let mut stream = request.into_inner();
+
+        let mut report = BulkIngestReport::default();
+        let mut line = 0;
+
+        // the next message is only read once the previous batches are written to the sink
+        while let Some(req) = stream.message().await? {
+            let mut events = req.events.into_iter().peekable();
+            while events.peek().is_some() {
+                let batch: Vec<(u64, Event)> = events
+                    .by_ref()
+                    .take(BULK_BATCH_SIZE)
+                    .map(|event| {
+                        line += 1;
+                        (line, event)
+                    })
+                    .collect();
+
+                self.ingest_lines(&tenant_id, batch, req.allow_backfilling, &mut report)
+                    .await?;
+            }
+        }
+
+        Ok(Response::new(report.into()))
+    }
+
+    #[tracing::instrument(skip(self, request))]
+    async fn amend_events(
+        &self,
+        request: Request<AmendEventsRequest>,
+    ) -> Result<Response<AmendEventsResponse>, Status> {
+        let tenant_id = request.tenant()?.to_string();
+
+        let req = request.into_inner();
+
+        if req.reason.trim().is_empty() {
+            return Err(Status::invalid_argument("No reason provided"));
+        }
+        if req.events.is_empty() {
+            return Err(Status::invalid_argument("No events provided"));
+        } else if req.events.len() > 500 {
+            return Err(Status::invalid_argument("Too many events provided"));
+        }
+
+        let now = chrono::Utc::now();
+
+        // amendments usually target past usage, so backfilling is always allowed
+        let (resolved, failed_events) = self
+            .resolve_events(&tenant_id, req.events, now, true)
+            .await?;
+
+        let mut failures: Vec<IngestFailure> = failed_events
+            .into_iter()
+            .map(|e| IngestFailure {
+                idempotency_key: e.event.event_id,
+                reason: e.reason,
+            })
+            .collect();
+
+        let event_ids: Vec<String> = resolved.iter().map(|e| e.event_id.clone()).collect();
+        let current = self.get_current_records(&tenant_id, &event_ids).await?;
+
+        let mut records = vec![];
+        for event in resolved {
+            let previous: Vec<&ProcessedEvent> = current
+                .iter()
+                .filter(|r| r.event_id == event.event_id)
+                .collect();
+
+            if previous.is_empty() {
+                failures.push(IngestFailure {
+                    idempotency_key: event.event_id,
+                    reason: "Event not found".to_string(),
+                });
+                continue;
+            }
+
+            if !self.reserve_compensations(&previous, now) {
+                failures.push(IngestFailure {
+                    idempotency_key: event.event_id,
+                    reason: CORRECTION_IN_PROGRESS.to_string(),
+                });
+                continue;
+            }
+
+            let amendment = ProcessedEvent {
+                correction_reason: req.reason.clone(),
+                record_id: Uuid::new_v4().to_string(),
+                ..event
+            };
+
+            // an amendment can restore the content of a record compensated before, that can then be compensated again
+            let key = amendment.compensation_key();
+            if !previous.iter().any(|r| r.compensation_key() == key) {
+                self.deduplicator.release(&key);
+            }
+
+            records.extend(previous.iter().map(|r| r.compensation(&req.reason)));
+            records.push(amendment);
+        }
+
+        failures.extend(self.send_corrections(&tenant_id, records).await?);
+
+        if !failures.is_empty() {
+            error!("Failed amendments count {}", failures.len());
+        }
+        Ok(Response::new(AmendEventsResponse { failures }))
+    }
+
+    #[tracing::instrument(skip(self, request))]
+    async fn void_events(
+        &self,
+        request: Request<VoidEventsRequest>,
+    ) -> Result<Response<VoidEventsResponse>, Status> {
+        let tenant_id = request.tenant()?.to_string();
+
+        let req = request.into_inner();
+
+        if req.reason.trim().is_empty() {
+            return Err(Status::invalid_argument("No reason provided"));
+        }
+        if req.event_ids.is_empty() {
+            return Err(Status::invalid_argument("No events provided"));
+        } else if req.event_ids.len() > 500 {
+            return Err(Status::invalid_argument("Too many events provided"));
+        }
+
+        let now = chrono::Utc::now();
+
+        let current = self.get_current_records(&tenant_id, &req.event_ids).await?;
+
+        let mut failures = vec![];
+        let mut records = vec![];
+        for event_id in req.event_ids {
+            let previous: Vec<&ProcessedEvent> =
+                current.iter().filter(|r| r.event_id == event_id).collect();
+
+            if previous.is_empty() {
+                failures.push(IngestFailure {
+                    idempotency_key: event_id,
+                    reason: "Event not found".to_string(),
+                });
+                continue;
+            }
+
+            if !self.reserve_compensations(&previous, now) {
+                failures.push(IngestFailure {
+                    idempotency_key: event_id,
+                    reason: CORRECTION_IN_PROGRESS.to_string(),
+                });
+                continue;
+            }
+
+            records.extend(previous.iter().map(|r| r.compensation(&req.reason)));
+        }
+
+        failures.extend(self.send_corrections(&tenant_id, records).await?);
+
+        if !failures.is_empty() {
+            error!("Failed voids count {}", failures.len());
+        }
+        Ok(Response::new(VoidEventsResponse { failures }))
     }
 }
 
@@ -198,6 +614,9 @@ fn to_processed_event(
         tenant_id,
         event_timestamp: ts.naive_utc(),
         properties: event.properties,
+        sign: 1,
+        correction_reason: String::new(),
+        record_id: String::new(),
     }
 }
 
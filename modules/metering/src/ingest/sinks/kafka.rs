@@ -225,6 +225,9 @@ mod tests {
             tenant_id: "tenantid".to_string(),
             event_timestamp: chrono::Utc::now().naive_utc(),
             properties: HashMap::from([("key".to_string(), "value".to_string())]),
+            sign: 1,
+            correction_reason: String::new(),
+            record_id: String::new(),
         };
 
         let attributes = vec![];
@@ -259,6 +262,9 @@ mod tests {
             tenant_id: "tenantid".to_string(),
             event_timestamp: chrono::Utc::now().naive_utc(),
             properties: HashMap::from([("key".to_string(), big_data.to_string())]),
+            sign: 1,
+            correction_reason: String::new(),
+            record_id: String::new(),
         };
 
         async fn check_error(sink: &KafkaSink, input: Vec<ProcessedEvent>, error: IngestError) {
@@ -7,6 +7,7 @@ use metering_grpc::meteroid::metering::v1::{
 };
 use tonic::{Request, Response, Status};
 
+use crate::connectors::errors::ConnectorError;
 use crate::connectors::Connector;
 use crate::domain::Meter;
 
@@ -39,6 +40,18 @@ impl MetersServiceGrpc for MetersService {
             .try_into()
             .map_err(|_| Status::internal("unknown aggregation_type"))?;
 
+        if aggregation_type == AggregationType::SumOverTime
+            && meter
+                .aggregation_key
+                .as_deref()
+                .unwrap_or_default()
+                .is_empty()
+        {
+            return Err(Status::invalid_argument(
+                "the SumOverTime aggregation requires an aggregation key",
+            ));
+        }
+
         let meter_aggregation = aggregation_type.into();
 
         let meter = Meter {
@@ -50,11 +63,15 @@ impl MetersServiceGrpc for MetersService {
             group_by: meter.dimensions,
         };
 
-        self.connector.register_meter(meter).await.map_err(|e| {
-            Status::internal("Failed to register meter")
-                .set_source(Arc::new(e.into_error()))
-                .clone()
-        })?;
+        self.connector
+            .register_meter(meter)
+            .await
+            .map_err(|e| match e.current_context() {
+                ConnectorError::InvalidMeter(reason) => Status::invalid_argument(reason.clone()),
+                _ => Status::internal("Failed to register meter")
+                    .set_source(Arc::new(e.into_error()))
+                    .clone(),
+            })?;
 
         Ok(Response::new(RegisterMeterResponse { metadata: vec![] }))
     }
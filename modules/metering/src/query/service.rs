@@ -59,6 +59,7 @@ impl UsageQueryServiceGrpc for UsageQueryService {
             namespace: req.tenant_id,
             meter_slug: req.meter_slug,
             event_name: req.event_name,
+            value_property: req.aggregation_key,
             customers: req
                 .customers
                 .iter()
@@ -2,6 +2,8 @@ use crate::auth::ExternalApiAuthLayer;
 use crate::config::Config;
 
 use crate::ingest;
+use crate::ingest::dedup::EventDeduplicator;
+use crate::ingest::service::EventsService;
 
 #[cfg(feature = "kafka")]
 use crate::ingest::sinks::kafka::KafkaSink;
@@ -11,6 +13,7 @@ use common_grpc::middleware::server as common_middleware;
 use common_grpc::middleware::client::{build_layered_client_service, LayeredClientService};
 use meteroid_grpc::meteroid::internal::v1::internal_service_client::InternalServiceClient;
 use std::sync::Arc;
+use tonic::service::Routes;
 use tonic::transport::{Channel, Endpoint, Server};
 use tonic_tracing_opentelemetry::middleware as otel_middleware;
 
@@ -31,7 +34,7 @@ fn only_internal(path: &str) -> bool {
 }
 
 fn only_api(path: &str) -> bool {
-    path.starts_with("/meteroid.metering.v1.EventsService")
+    path.starts_with("/meteroid.metering.v1.EventsService") || path.starts_with("/v1/events")
 }
 
 pub async fn start_api_server(config: Config) -> Result<(), Box<dyn std::error::Error>> {
@@ -92,13 +95,26 @@ pub async fn start_api_server(config: Config) -> Result<(), Box<dyn std::error::
     let api_key_auth_layer = ExternalApiAuthLayer::new(internal_client.clone()).filter(only_api);
 
     // Ingest => Api key only (though we may want a way to ingest from the  for debugging, later)
-    let event_service = ingest::service(internal_client.clone(), sink.clone());
+    let deduplicator = Arc::new(EventDeduplicator::new(
+        config.dedup_capacity,
+        chrono::Duration::seconds(config.dedup_window_seconds),
+    ));
+    let events_service = EventsService::new(
+        internal_client.clone(),
+        sink.clone(),
+        deduplicator,
+        connector.clone(),
+    );
+    let event_service = ingest::service(events_service.clone());
+    // bulk imports over http/1.1 => Api key only
+    let import_routes = Routes::from(ingest::http::routes(events_service));
 
     // Meters & queries => Admin only. Some passthrough is possible via admin
     let meter_service = crate::meters::service(connector.clone());
     let query_service = crate::query::service(connector.clone());
 
     Server::builder()
+        .accept_http1(true)
         .layer(common_middleware::metric::create())
         .layer(api_key_auth_layer.clone())
         .layer(admin_auth_layer.clone())
@@ -106,6 +122,7 @@ pub async fn start_api_server(config: Config) -> Result<(), Box<dyn std::error::
             otel_middleware::server::OtelGrpcLayer::default()
                 .filter(otel_middleware::filters::reject_healthcheck),
         )
+        .add_routes(import_routes)
         .add_service(health_service)
         .add_service(reflection_service)
         .add_service(meter_service)
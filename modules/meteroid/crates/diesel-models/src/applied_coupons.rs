@@ -16,6 +16,7 @@ pub struct AppliedCouponRow {
     pub applied_count: Option<i32>,
     pub last_applied_at: Option<chrono::NaiveDateTime>,
     pub created_at: chrono::NaiveDateTime,
+    pub promotion_code_id: Option<Uuid>,
 }
 
 #[derive(Debug, Insertable)]
@@ -30,6 +31,7 @@ pub struct AppliedCouponRowNew {
     pub applied_amount: Option<Decimal>,
     pub applied_count: Option<i32>,
     pub last_applied_at: Option<chrono::NaiveDateTime>,
+    pub promotion_code_id: Option<Uuid>,
 }
 
 #[derive(Debug, Queryable, Selectable)]
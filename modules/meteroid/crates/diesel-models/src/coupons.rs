@@ -1,3 +1,4 @@
+use crate::enums::CouponStackingEnum;
 use chrono::NaiveDateTime;
 use diesel::{AsChangeset, Identifiable, Insertable, Queryable, Selectable};
 use uuid::Uuid;
@@ -21,6 +22,10 @@ pub struct CouponRow {
     pub disabled: bool,
     pub last_redemption_at: Option<NaiveDateTime>,
     pub archived_at: Option<NaiveDateTime>,
+    pub applies_to: Option<serde_json::Value>,
+    pub duration_months: Option<i32>,
+    pub stacking: CouponStackingEnum,
+    pub priority: i32,
 }
 
 #[derive(Debug, Default, Insertable)]
@@ -36,6 +41,10 @@ pub struct CouponRowNew {
     pub redemption_limit: Option<i32>,
     pub recurring_value: Option<i32>,
     pub reusable: bool,
+    pub applies_to: Option<serde_json::Value>,
+    pub duration_months: Option<i32>,
+    pub stacking: CouponStackingEnum,
+    pub priority: i32,
 }
 
 #[derive(AsChangeset)]
@@ -47,5 +56,7 @@ pub struct CouponRowPatch {
     pub tenant_id: Uuid,
     pub description: Option<String>,
     pub discount: Option<serde_json::Value>,
+    pub stacking: Option<CouponStackingEnum>,
+    pub priority: Option<i32>,
     pub updated_at: NaiveDateTime,
 }
@@ -0,0 +1,43 @@
+use chrono::{NaiveDate, NaiveDateTime};
+use diesel::{Identifiable, Insertable, Queryable, Selectable};
+use uuid::Uuid;
+
+#[derive(Queryable, Debug, Clone, Identifiable, Selectable)]
+#[diesel(table_name = crate::schema::credit_grant)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct CreditGrantRow {
+    pub id: Uuid,
+    pub tenant_id: Uuid,
+    pub customer_id: Uuid,
+    pub name: String,
+    pub amount_cents: i32,
+    pub remaining_cents: i32,
+    pub currency: String,
+    pub priority: i32,
+    pub effective_date: NaiveDate,
+    pub expiry_date: Option<NaiveDate>,
+    pub price_component_ids: Vec<Option<Uuid>>,
+    pub metric_ids: Vec<Option<Uuid>>,
+    pub created_at: NaiveDateTime,
+    pub created_by: Option<Uuid>,
+    pub expired_at: Option<NaiveDateTime>,
+}
+
+#[derive(Debug, Insertable)]
+#[diesel(table_name = crate::schema::credit_grant)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct CreditGrantRowNew {
+    pub id: Uuid,
+    pub tenant_id: Uuid,
+    pub customer_id: Uuid,
+    pub name: String,
+    pub amount_cents: i32,
+    pub remaining_cents: i32,
+    pub currency: String,
+    pub priority: i32,
+    pub effective_date: NaiveDate,
+    pub expiry_date: Option<NaiveDate>,
+    pub price_component_ids: Vec<Option<Uuid>>,
+    pub metric_ids: Vec<Option<Uuid>>,
+    pub created_by: Option<Uuid>,
+}
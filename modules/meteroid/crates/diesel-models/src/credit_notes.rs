@@ -2,9 +2,9 @@ use chrono::NaiveDateTime;
 use uuid::Uuid;
 
 use crate::enums::CreditNoteStatus;
-use diesel::{Identifiable, Queryable};
+use diesel::{Identifiable, Insertable, Queryable, Selectable};
 
-#[derive(Queryable, Debug, Identifiable)]
+#[derive(Queryable, Debug, Identifiable, Selectable)]
 #[diesel(table_name = crate::schema::credit_note)]
 #[diesel(check_for_backend(diesel::pg::Pg))]
 pub struct CreditNoteRow {
@@ -14,10 +14,40 @@ pub struct CreditNoteRow {
     pub refunded_amount_cents: Option<i64>,
     pub credited_amount_cents: Option<i64>,
     pub currency: String,
-    pub finalized_at: NaiveDateTime,
+    pub finalized_at: Option<NaiveDateTime>,
     pub plan_version_id: Option<Uuid>,
     pub invoice_id: Uuid,
     pub tenant_id: Uuid,
     pub customer_id: Uuid,
     pub status: CreditNoteStatus,
+    pub credit_note_number: Option<String>,
+    pub reason: Option<String>,
+    pub memo: Option<String>,
+    pub line_items: serde_json::Value,
+    pub subtotal: i64,
+    pub tax_amount: i64,
+    pub total: i64,
+    pub voided_at: Option<NaiveDateTime>,
+    pub pdf_document_id: Option<String>,
+}
+
+#[derive(Debug, Insertable)]
+#[diesel(table_name = crate::schema::credit_note)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct CreditNoteRowNew {
+    pub id: Uuid,
+    pub refunded_amount_cents: Option<i64>,
+    pub credited_amount_cents: Option<i64>,
+    pub currency: String,
+    pub plan_version_id: Option<Uuid>,
+    pub invoice_id: Uuid,
+    pub tenant_id: Uuid,
+    pub customer_id: Uuid,
+    pub status: CreditNoteStatus,
+    pub reason: Option<String>,
+    pub memo: Option<String>,
+    pub line_items: serde_json::Value,
+    pub subtotal: i64,
+    pub tax_amount: i64,
+    pub total: i64,
 }
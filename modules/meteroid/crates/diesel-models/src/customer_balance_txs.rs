@@ -16,6 +16,7 @@ pub struct CustomerBalanceTxRow {
     pub tenant_id: Uuid,
     pub customer_id: Uuid,
     pub created_by: Option<Uuid>,
+    pub credit_grant_id: Option<Uuid>,
 }
 
 #[derive(Debug, Clone, Insertable)]
@@ -30,6 +31,7 @@ pub struct CustomerBalanceTxRowNew {
     pub tenant_id: Uuid,
     pub customer_id: Uuid,
     pub created_by: Option<Uuid>,
+    pub credit_grant_id: Option<Uuid>,
 }
 
 #[derive(Clone, Debug, Identifiable, Queryable, Selectable)]
@@ -1,6 +1,7 @@
 use chrono::NaiveDateTime;
 use uuid::Uuid;
 
+use crate::enums::CustomerTaxStatusEnum;
 use diesel::{AsChangeset, Identifiable, Insertable, Queryable, Selectable};
 
 #[derive(Clone, Debug, Identifiable, Queryable, Selectable)]
@@ -25,6 +26,8 @@ pub struct CustomerRow {
     pub billing_address: Option<serde_json::Value>,
     pub shipping_address: Option<serde_json::Value>,
     pub invoicing_entity_id: Uuid,
+    pub vat_number: Option<String>,
+    pub tax_status: CustomerTaxStatusEnum,
 }
 
 #[derive(Clone, Debug, Queryable, Selectable)]
@@ -54,6 +57,8 @@ pub struct CustomerRowNew {
     pub billing_address: Option<serde_json::Value>,
     pub shipping_address: Option<serde_json::Value>,
     pub invoicing_entity_id: Uuid,
+    pub vat_number: Option<String>,
+    pub tax_status: CustomerTaxStatusEnum,
     // for seed, else default to None
     pub created_at: Option<NaiveDateTime>,
 }
@@ -73,6 +78,8 @@ pub struct CustomerRowPatch {
     pub billing_address: Option<serde_json::Value>,
     pub shipping_address: Option<serde_json::Value>,
     pub invoicing_entity_id: Option<Uuid>,
+    pub vat_number: Option<String>,
+    pub tax_status: Option<CustomerTaxStatusEnum>,
 }
 
 #[derive(AsChangeset, Debug)]
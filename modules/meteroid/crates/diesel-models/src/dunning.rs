@@ -0,0 +1,61 @@
+use chrono::NaiveDateTime;
+use diesel::{Identifiable, Insertable, Queryable, Selectable};
+use uuid::Uuid;
+
+use crate::enums::{DunningSubscriptionActionEnum, InvoiceDunningStatusEnum};
+
+#[derive(Queryable, Debug, Clone, Identifiable, Selectable)]
+#[diesel(table_name = crate::schema::dunning_config)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct DunningConfigRow {
+    pub id: Uuid,
+    pub tenant_id: Uuid,
+    pub invoicing_entity_id: Uuid,
+    pub enabled: bool,
+    pub retry_schedule_days: serde_json::Value,
+    pub end_after_days: i32,
+    pub subscription_action: DunningSubscriptionActionEnum,
+    pub mark_uncollectible: bool,
+    pub created_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+}
+
+#[derive(Debug, Insertable)]
+#[diesel(table_name = crate::schema::dunning_config)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct DunningConfigRowNew {
+    pub id: Uuid,
+    pub tenant_id: Uuid,
+    pub invoicing_entity_id: Uuid,
+    pub enabled: bool,
+    pub retry_schedule_days: serde_json::Value,
+    pub end_after_days: i32,
+    pub subscription_action: DunningSubscriptionActionEnum,
+    pub mark_uncollectible: bool,
+}
+
+#[derive(Queryable, Debug, Clone, Identifiable, Selectable)]
+#[diesel(table_name = crate::schema::invoice_dunning)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct InvoiceDunningRow {
+    pub id: Uuid,
+    pub tenant_id: Uuid,
+    pub invoice_id: Uuid,
+    pub status: InvoiceDunningStatusEnum,
+    pub attempts: i32,
+    pub started_at: NaiveDateTime,
+    pub next_attempt_at: NaiveDateTime,
+    pub last_attempt_at: Option<NaiveDateTime>,
+    pub ended_at: Option<NaiveDateTime>,
+}
+
+#[derive(Debug, Insertable)]
+#[diesel(table_name = crate::schema::invoice_dunning)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct InvoiceDunningRowNew {
+    pub id: Uuid,
+    pub tenant_id: Uuid,
+    pub invoice_id: Uuid,
+    pub started_at: NaiveDateTime,
+    pub next_attempt_at: NaiveDateTime,
+}
@@ -18,6 +18,7 @@ pub enum BillingMetricAggregateEnum {
     Mean,
     Sum,
     CountDistinct,
+    SumOverTime,
 }
 
 #[derive(diesel_derive_enum::DbEnum, Debug, Clone)]
@@ -29,6 +30,16 @@ pub enum BillingPeriodEnum {
     Annual,
 }
 
+#[derive(diesel_derive_enum::DbEnum, Debug, Clone, Default)]
+#[ExistingTypePath = "crate::schema::sql_types::CouponStackingEnum"]
+#[DbValueStyle = "SCREAMING_SNAKE_CASE"]
+pub enum CouponStackingEnum {
+    #[default]
+    Stackable,
+    Exclusive,
+    BestOf,
+}
+
 #[derive(diesel_derive_enum::DbEnum, Debug, Clone)]
 #[ExistingTypePath = "crate::schema::sql_types::CreditNoteStatus"]
 #[DbValueStyle = "SCREAMING_SNAKE_CASE"]
@@ -38,6 +49,24 @@ pub enum CreditNoteStatus {
     Voided,
 }
 
+#[derive(diesel_derive_enum::DbEnum, Debug, Clone)]
+#[ExistingTypePath = "crate::schema::sql_types::CustomerTaxStatusEnum"]
+#[DbValueStyle = "SCREAMING_SNAKE_CASE"]
+pub enum CustomerTaxStatusEnum {
+    Taxable,
+    Exempt,
+    ReverseCharge,
+}
+
+#[derive(diesel_derive_enum::DbEnum, Debug, Clone)]
+#[ExistingTypePath = "crate::schema::sql_types::DunningSubscriptionActionEnum"]
+#[DbValueStyle = "SCREAMING_SNAKE_CASE"]
+pub enum DunningSubscriptionActionEnum {
+    None,
+    Pause,
+    Cancel,
+}
+
 #[derive(diesel_derive_enum::DbEnum, Debug, Clone)]
 #[ExistingTypePath = "crate::schema::sql_types::FangTaskState"]
 #[DbValueStyle = "snake_case"]
@@ -49,6 +78,16 @@ pub enum FangTaskState {
     Retried,
 }
 
+#[derive(diesel_derive_enum::DbEnum, Debug, Clone)]
+#[ExistingTypePath = "crate::schema::sql_types::InvoiceDunningStatusEnum"]
+#[DbValueStyle = "SCREAMING_SNAKE_CASE"]
+pub enum InvoiceDunningStatusEnum {
+    Active,
+    Recovered,
+    Exhausted,
+    Aborted,
+}
+
 #[derive(diesel_derive_enum::DbEnum, Debug, Clone)]
 #[ExistingTypePath = "crate::schema::sql_types::InvoiceExternalStatusEnum"]
 #[DbValueStyle = "SCREAMING_SNAKE_CASE"]
@@ -81,6 +120,7 @@ pub enum InvoiceType {
     Adjustment,
     // Imported,
     UsageThreshold,
+    Commitment,
 }
 
 #[derive(diesel_derive_enum::DbEnum, Debug, Clone)]
@@ -151,6 +191,14 @@ pub enum SubscriptionFeeBillingPeriod {
     Annual,
 }
 
+#[derive(diesel_derive_enum::DbEnum, Debug, Clone)]
+#[ExistingTypePath = "crate::schema::sql_types::SubscriptionCommitmentTypeEnum"]
+#[DbValueStyle = "SCREAMING_SNAKE_CASE"]
+pub enum SubscriptionCommitmentTypeEnum {
+    Prepaid,
+    Postpaid,
+}
+
 #[derive(diesel_derive_enum::DbEnum, Debug, Clone)]
 #[ExistingTypePath = "crate::schema::sql_types::SubscriptionEventType"]
 #[DbValueStyle = "SCREAMING_SNAKE_CASE"]
@@ -161,6 +209,8 @@ pub enum SubscriptionEventType {
     Cancelled,
     Reactivated,
     Updated,
+    Paused,
+    Resumed,
 }
 
 #[derive(diesel_derive_enum::DbEnum, Debug, Clone)]
@@ -195,4 +245,6 @@ pub enum WebhookOutEventTypeEnum {
     SubscriptionCreated,
     InvoiceCreated,
     InvoiceFinalized,
+    SubscriptionTrialEnded,
+    InvoicePaymentReminder,
 }
@@ -7,6 +7,7 @@ use chrono::NaiveDateTime;
 use crate::customers::CustomerRow;
 use crate::plan_versions::PlanVersionRowLatest;
 use diesel::{AsChangeset, Identifiable, Insertable, Queryable, Selectable};
+use rust_decimal::Decimal;
 use uuid::Uuid;
 
 #[derive(Debug, Identifiable, Queryable, Selectable)]
@@ -37,7 +38,7 @@ pub struct InvoiceRow {
     pub finalized_at: Option<NaiveDateTime>,
     pub net_terms: i32,
     pub memo: Option<String>,
-    pub tax_rate: i32,
+    pub tax_rate: Decimal,
     pub local_id: String,
     pub reference: Option<String>,
     pub invoice_number: String,
@@ -53,6 +54,9 @@ pub struct InvoiceRow {
     pub xml_document_id: Option<String>,
     pub pdf_document_id: Option<String>,
     pub applied_coupon_ids: Vec<Option<Uuid>>,
+    pub tax_breakdown: serde_json::Value,
+    pub replaces_invoice_id: Option<Uuid>,
+    pub coupons: serde_json::Value,
 }
 
 #[derive(Debug, AsChangeset)]
@@ -64,8 +68,11 @@ pub struct InvoiceRowLinesPatch {
     pub subtotal: i64,
     pub subtotal_recurring: i64,
     pub total: i64,
+    pub tax_rate: Decimal,
     pub tax_amount: i64,
+    pub tax_breakdown: serde_json::Value,
     pub applied_credits: i64,
+    pub coupons: serde_json::Value,
 }
 
 #[derive(Insertable, Debug)]
@@ -93,8 +100,9 @@ pub struct InvoiceRowNew {
     pub finalized_at: Option<NaiveDateTime>,
     pub subtotal: i64,
     pub subtotal_recurring: i64,
-    pub tax_rate: i32,
+    pub tax_rate: Decimal,
     pub tax_amount: i64,
+    pub tax_breakdown: serde_json::Value,
     pub total: i64,
     pub amount_due: i64,
     pub net_terms: i32,
@@ -105,6 +113,8 @@ pub struct InvoiceRowNew {
     pub plan_name: Option<String>,
     pub customer_details: serde_json::Value,
     pub seller_details: serde_json::Value,
+    pub replaces_invoice_id: Option<Uuid>,
+    pub coupons: serde_json::Value,
 }
 
 #[derive(Debug, Queryable, Selectable)]
@@ -28,6 +28,9 @@ pub struct InvoicingEntityRow {
     pub country: String,
     pub accounting_currency: String,
     pub tenant_id: Uuid,
+    pub credit_note_number_pattern: String,
+    pub invoice_number_year: Option<i32>,
+    pub credit_note_number_year: Option<i32>,
 }
 
 #[derive(Debug, AsChangeset)]
@@ -37,6 +40,7 @@ pub struct InvoicingEntityRowPatch {
     pub id: Uuid,
     pub legal_name: Option<String>,
     pub invoice_number_pattern: Option<String>,
+    pub credit_note_number_pattern: Option<String>,
     pub grace_period_hours: Option<i32>,
     pub net_terms: Option<i32>,
     pub invoice_footer_info: Option<String>,
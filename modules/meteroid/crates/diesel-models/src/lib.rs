@@ -2,8 +2,10 @@ pub mod api_tokens;
 pub mod bi;
 pub mod billable_metrics;
 pub mod configs;
+pub mod credit_grants;
 pub mod credit_notes;
 pub mod customers;
+pub mod dunning;
 pub mod enums;
 pub mod errors;
 pub mod fang;
@@ -15,6 +17,7 @@ pub mod plans;
 pub mod price_components;
 pub mod product_families;
 pub mod products;
+pub mod promotion_codes;
 pub mod query;
 pub mod schedules;
 pub mod schema;
@@ -31,8 +34,11 @@ pub mod invoicing_entities;
 pub mod outbox;
 pub mod stats;
 pub mod subscription_add_ons;
+pub mod subscription_commitments;
 pub mod subscription_components;
 pub mod subscription_events;
+pub mod subscription_plan_changes;
+pub mod tax_rates;
 pub mod tenants;
 pub mod users;
 pub mod webhooks;
@@ -0,0 +1,32 @@
+use chrono::NaiveDateTime;
+use diesel::{Identifiable, Insertable, Queryable, Selectable};
+use uuid::Uuid;
+
+#[derive(Queryable, Debug, Identifiable, Selectable)]
+#[diesel(table_name = crate::schema::promotion_code)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct PromotionCodeRow {
+    pub id: Uuid,
+    pub code: String,
+    pub coupon_id: Uuid,
+    pub tenant_id: Uuid,
+    pub customer_id: Option<Uuid>,
+    pub expires_at: Option<NaiveDateTime>,
+    pub redemption_limit: Option<i32>,
+    pub redemption_count: i32,
+    pub archived_at: Option<NaiveDateTime>,
+    pub created_at: NaiveDateTime,
+}
+
+#[derive(Debug, Insertable)]
+#[diesel(table_name = crate::schema::promotion_code)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct PromotionCodeRowNew {
+    pub id: Uuid,
+    pub code: String,
+    pub coupon_id: Uuid,
+    pub tenant_id: Uuid,
+    pub customer_id: Option<Uuid>,
+    pub expires_at: Option<NaiveDateTime>,
+    pub redemption_limit: Option<i32>,
+}
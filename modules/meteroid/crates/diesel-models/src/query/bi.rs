@@ -23,6 +23,25 @@ impl BiMrrMovementLogRowNew {
     }
 }
 impl BiMrrMovementLogRow {
+    pub async fn list_by_invoice_id(
+        conn: &mut PgConn,
+        param_invoice_id: uuid::Uuid,
+    ) -> DbResult<Vec<BiMrrMovementLogRow>> {
+        use crate::schema::bi_mrr_movement_log::dsl::*;
+        use diesel::{ExpressionMethods, QueryDsl};
+        use diesel_async::RunQueryDsl;
+
+        let query = bi_mrr_movement_log.filter(invoice_id.eq(param_invoice_id));
+
+        log::debug!("{}", debug_query::<diesel::pg::Pg, _>(&query).to_string());
+
+        query
+            .get_results(conn)
+            .await
+            .attach_printable("Error while listing bi_mrr_movement_log by invoice")
+            .into_db_result()
+    }
+
     pub async fn insert_movement_log_batch(
         conn: &mut PgConn,
         invoices: Vec<BiMrrMovementLogRowNew>,
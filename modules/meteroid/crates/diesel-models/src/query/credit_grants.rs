@@ -0,0 +1,196 @@
+use crate::credit_grants::{CreditGrantRow, CreditGrantRowNew};
+use crate::errors::IntoDbResult;
+use crate::extend::cursor_pagination::{
+    CursorPaginate, CursorPaginatedVec, CursorPaginationRequest,
+};
+use crate::{DbResult, PgConn};
+use chrono::NaiveDate;
+use diesel::{debug_query, ExpressionMethods, QueryDsl, SelectableHelper};
+use diesel_async::RunQueryDsl;
+use error_stack::ResultExt;
+use uuid::Uuid;
+
+impl CreditGrantRowNew {
+    pub async fn insert(&self, conn: &mut PgConn) -> DbResult<CreditGrantRow> {
+        use crate::schema::credit_grant::dsl as cg_dsl;
+
+        let query = diesel::insert_into(cg_dsl::credit_grant).values(self);
+
+        log::debug!("{}", debug_query::<diesel::pg::Pg, _>(&query));
+
+        query
+            .get_result(conn)
+            .await
+            .attach_printable("Error while inserting credit grant")
+            .into_db_result()
+    }
+}
+
+impl CreditGrantRow {
+    pub async fn list_by_customer_id(
+        conn: &mut PgConn,
+        tenant_id: Uuid,
+        customer_id: Uuid,
+    ) -> DbResult<Vec<CreditGrantRow>> {
+        use crate::schema::credit_grant::dsl as cg_dsl;
+
+        let query = cg_dsl::credit_grant
+            .filter(cg_dsl::tenant_id.eq(tenant_id))
+            .filter(cg_dsl::customer_id.eq(customer_id))
+            .order(cg_dsl::id.desc())
+            .select(CreditGrantRow::as_select());
+
+        log::debug!("{}", debug_query::<diesel::pg::Pg, _>(&query));
+
+        query
+            .get_results(conn)
+            .await
+            .attach_printable("Error while listing credit grants")
+            .into_db_result()
+    }
+
+    /// The grants of the customer with a remaining amount, that were not written off.
+    pub async fn list_available_by_customer_id(
+        conn: &mut PgConn,
+        tenant_id: Uuid,
+        customer_id: Uuid,
+    ) -> DbResult<Vec<CreditGrantRow>> {
+        use crate::schema::credit_grant::dsl as cg_dsl;
+
+        let query = cg_dsl::credit_grant
+            .filter(cg_dsl::tenant_id.eq(tenant_id))
+            .filter(cg_dsl::customer_id.eq(customer_id))
+            .filter(cg_dsl::remaining_cents.gt(0))
+            .filter(cg_dsl::expired_at.is_null())
+            .select(CreditGrantRow::as_select());
+
+        log::debug!("{}", debug_query::<diesel::pg::Pg, _>(&query));
+
+        query
+            .get_results(conn)
+            .await
+            .attach_printable("Error while listing available credit grants")
+            .into_db_result()
+    }
+
+    pub async fn select_available_for_update_by_customer_id(
+        conn: &mut PgConn,
+        tenant_id: Uuid,
+        customer_id: Uuid,
+    ) -> DbResult<Vec<CreditGrantRow>> {
+        use crate::schema::credit_grant::dsl as cg_dsl;
+
+        let query = cg_dsl::credit_grant
+            .for_no_key_update()
+            .filter(cg_dsl::tenant_id.eq(tenant_id))
+            .filter(cg_dsl::customer_id.eq(customer_id))
+            .filter(cg_dsl::remaining_cents.gt(0))
+            .filter(cg_dsl::expired_at.is_null())
+            .select(CreditGrantRow::as_select());
+
+        log::debug!("{}", debug_query::<diesel::pg::Pg, _>(&query));
+
+        query
+            .get_results(conn)
+            .await
+            .attach_printable("Error while selecting for update available credit grants")
+            .into_db_result()
+    }
+
+    pub async fn select_for_update_by_id(
+        conn: &mut PgConn,
+        id: Uuid,
+        tenant_id: Uuid,
+    ) -> DbResult<CreditGrantRow> {
+        use crate::schema::credit_grant::dsl as cg_dsl;
+
+        let query = cg_dsl::credit_grant
+            .for_no_key_update()
+            .filter(cg_dsl::id.eq(id))
+            .filter(cg_dsl::tenant_id.eq(tenant_id))
+            .select(CreditGrantRow::as_select());
+
+        log::debug!("{}", debug_query::<diesel::pg::Pg, _>(&query));
+
+        query
+            .first(conn)
+            .await
+            .attach_printable("Error while selecting for update credit grant by id")
+            .into_db_result()
+    }
+
+    /// Grants with a remaining amount that expired at or before the date, and were not written off yet.
+    pub async fn list_expired(
+        conn: &mut PgConn,
+        input_date_param: NaiveDate,
+        pagination: CursorPaginationRequest,
+    ) -> DbResult<CursorPaginatedVec<CreditGrantRow>> {
+        use crate::schema::credit_grant::dsl as cg_dsl;
+
+        let query = cg_dsl::credit_grant
+            .filter(cg_dsl::expiry_date.le(input_date_param))
+            .filter(cg_dsl::expired_at.is_null())
+            .select(CreditGrantRow::as_select())
+            .cursor_paginate(pagination, "id");
+
+        log::debug!("{}", debug_query::<diesel::pg::Pg, _>(&query));
+
+        query
+            .load_and_get_next_cursor(conn, |a| a.id)
+            .await
+            .attach_printable("Error while paginating expired credit grants")
+            .into_db_result()
+    }
+
+    pub async fn consume(conn: &mut PgConn, id: Uuid, cents: i32) -> DbResult<CreditGrantRow> {
+        use crate::schema::credit_grant::dsl as cg_dsl;
+
+        let query = diesel::update(cg_dsl::credit_grant)
+            .filter(cg_dsl::id.eq(id))
+            .set(cg_dsl::remaining_cents.eq(cg_dsl::remaining_cents - cents));
+
+        log::debug!("{}", debug_query::<diesel::pg::Pg, _>(&query));
+
+        query
+            .get_result(conn)
+            .await
+            .attach_printable("Error while consuming credit grant")
+            .into_db_result()
+    }
+
+    /// Gives back the amount consumed by a voided invoice.
+    pub async fn restore(conn: &mut PgConn, id: Uuid, cents: i32) -> DbResult<CreditGrantRow> {
+        use crate::schema::credit_grant::dsl as cg_dsl;
+
+        let query = diesel::update(cg_dsl::credit_grant)
+            .filter(cg_dsl::id.eq(id))
+            .set(cg_dsl::remaining_cents.eq(cg_dsl::remaining_cents + cents));
+
+        log::debug!("{}", debug_query::<diesel::pg::Pg, _>(&query));
+
+        query
+            .get_result(conn)
+            .await
+            .attach_printable("Error while restoring credit grant")
+            .into_db_result()
+    }
+
+    pub async fn expire(conn: &mut PgConn, id: Uuid) -> DbResult<CreditGrantRow> {
+        use crate::schema::credit_grant::dsl as cg_dsl;
+
+        let query = diesel::update(cg_dsl::credit_grant)
+            .filter(cg_dsl::id.eq(id))
+            .set((
+                cg_dsl::remaining_cents.eq(0),
+                cg_dsl::expired_at.eq(diesel::dsl::now),
+            ));
+
+        log::debug!("{}", debug_query::<diesel::pg::Pg, _>(&query));
+
+        query
+            .get_result(conn)
+            .await
+            .attach_printable("Error while expiring credit grant")
+            .into_db_result()
+    }
+}
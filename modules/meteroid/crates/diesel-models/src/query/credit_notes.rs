@@ -0,0 +1,226 @@
+use crate::credit_notes::{CreditNoteRow, CreditNoteRowNew};
+use crate::enums::CreditNoteStatus;
+use crate::errors::IntoDbResult;
+use crate::extend::pagination::{Paginate, PaginatedVec, PaginationRequest};
+use crate::{DbResult, PgConn};
+use diesel::{debug_query, ExpressionMethods, QueryDsl, SelectableHelper};
+use diesel_async::RunQueryDsl;
+use error_stack::ResultExt;
+use tap::TapFallible;
+use uuid::Uuid;
+
+impl CreditNoteRowNew {
+    pub async fn insert(&self, conn: &mut PgConn) -> DbResult<CreditNoteRow> {
+        use crate::schema::credit_note::dsl as cn_dsl;
+
+        let query = diesel::insert_into(cn_dsl::credit_note).values(self);
+
+        log::debug!("{}", debug_query::<diesel::pg::Pg, _>(&query));
+
+        query
+            .get_result(conn)
+            .await
+            .attach_printable("Error while inserting credit note")
+            .into_db_result()
+    }
+}
+
+impl CreditNoteRow {
+    pub async fn find_by_id(
+        conn: &mut PgConn,
+        tenant_id: Uuid,
+        id: Uuid,
+    ) -> DbResult<CreditNoteRow> {
+        use crate::schema::credit_note::dsl as cn_dsl;
+
+        let query = cn_dsl::credit_note
+            .filter(cn_dsl::id.eq(id))
+            .filter(cn_dsl::tenant_id.eq(tenant_id))
+            .select(CreditNoteRow::as_select());
+
+        log::debug!("{}", debug_query::<diesel::pg::Pg, _>(&query));
+
+        query
+            .first(conn)
+            .await
+            .attach_printable("Error while finding credit note by id")
+            .into_db_result()
+    }
+
+    pub async fn select_for_update(
+        conn: &mut PgConn,
+        tenant_id: Uuid,
+        id: Uuid,
+    ) -> DbResult<CreditNoteRow> {
+        use crate::schema::credit_note::dsl as cn_dsl;
+
+        let query = cn_dsl::credit_note
+            .for_no_key_update()
+            .filter(cn_dsl::id.eq(id))
+            .filter(cn_dsl::tenant_id.eq(tenant_id))
+            .select(CreditNoteRow::as_select());
+
+        log::debug!("{}", debug_query::<diesel::pg::Pg, _>(&query));
+
+        query
+            .first(conn)
+            .await
+            .attach_printable("Error while selecting credit note for update")
+            .into_db_result()
+    }
+
+    pub async fn list_active_by_invoice_id(
+        conn: &mut PgConn,
+        tenant_id: Uuid,
+        invoice_id: Uuid,
+    ) -> DbResult<Vec<CreditNoteRow>> {
+        use crate::schema::credit_note::dsl as cn_dsl;
+
+        let query = cn_dsl::credit_note
+            .filter(cn_dsl::tenant_id.eq(tenant_id))
+            .filter(cn_dsl::invoice_id.eq(invoice_id))
+            .filter(cn_dsl::status.ne(CreditNoteStatus::Voided))
+            .select(CreditNoteRow::as_select());
+
+        log::debug!("{}", debug_query::<diesel::pg::Pg, _>(&query));
+
+        query
+            .get_results(conn)
+            .await
+            .tap_err(|e| log::error!("Error while listing credit notes by invoice: {:?}", e))
+            .attach_printable("Error while listing credit notes by invoice")
+            .into_db_result()
+    }
+
+    pub async fn list(
+        conn: &mut PgConn,
+        tenant_id: Uuid,
+        customer_id: Option<Uuid>,
+        invoice_id: Option<Uuid>,
+        status: Option<CreditNoteStatus>,
+        pagination: PaginationRequest,
+    ) -> DbResult<PaginatedVec<CreditNoteRow>> {
+        use crate::schema::credit_note::dsl as cn_dsl;
+
+        let mut query = cn_dsl::credit_note
+            .filter(cn_dsl::tenant_id.eq(tenant_id))
+            .select(CreditNoteRow::as_select())
+            .into_boxed();
+
+        if let Some(customer_id) = customer_id {
+            query = query.filter(cn_dsl::customer_id.eq(customer_id))
+        }
+
+        if let Some(invoice_id) = invoice_id {
+            query = query.filter(cn_dsl::invoice_id.eq(invoice_id))
+        }
+
+        if let Some(status) = status {
+            query = query.filter(cn_dsl::status.eq(status))
+        }
+
+        let paginated_query = query.order(cn_dsl::created_at.desc()).paginate(pagination);
+
+        log::debug!("{}", debug_query::<diesel::pg::Pg, _>(&paginated_query));
+
+        paginated_query
+            .load_and_count_pages(conn)
+            .await
+            .attach_printable("Error while listing credit notes")
+            .into_db_result()
+    }
+
+    pub async fn list_by_ids(conn: &mut PgConn, ids: Vec<Uuid>) -> DbResult<Vec<CreditNoteRow>> {
+        use crate::schema::credit_note::dsl as cn_dsl;
+
+        let query = cn_dsl::credit_note
+            .filter(cn_dsl::id.eq_any(ids))
+            .select(CreditNoteRow::as_select());
+
+        log::debug!("{}", debug_query::<diesel::pg::Pg, _>(&query));
+
+        query
+            .get_results(conn)
+            .await
+            .attach_printable("Error while listing credit notes by ids")
+            .into_db_result()
+    }
+
+    pub async fn finalize(
+        conn: &mut PgConn,
+        id: Uuid,
+        tenant_id: Uuid,
+        credit_note_number: String,
+    ) -> DbResult<CreditNoteRow> {
+        use crate::schema::credit_note::dsl as cn_dsl;
+
+        let now = chrono::Utc::now().naive_utc();
+
+        let query = diesel::update(cn_dsl::credit_note)
+            .filter(cn_dsl::id.eq(id))
+            .filter(cn_dsl::tenant_id.eq(tenant_id))
+            .filter(cn_dsl::status.eq(CreditNoteStatus::Draft))
+            .set((
+                cn_dsl::status.eq(CreditNoteStatus::Finalized),
+                cn_dsl::credit_note_number.eq(credit_note_number),
+                cn_dsl::finalized_at.eq(now),
+                cn_dsl::updated_at.eq(now),
+            ))
+            .returning(CreditNoteRow::as_returning());
+
+        log::debug!("{}", debug_query::<diesel::pg::Pg, _>(&query));
+
+        query
+            .get_result(conn)
+            .await
+            .attach_printable("Error while finalizing credit note")
+            .into_db_result()
+    }
+
+    pub async fn void(conn: &mut PgConn, id: Uuid, tenant_id: Uuid) -> DbResult<CreditNoteRow> {
+        use crate::schema::credit_note::dsl as cn_dsl;
+
+        let now = chrono::Utc::now().naive_utc();
+
+        let query = diesel::update(cn_dsl::credit_note)
+            .filter(cn_dsl::id.eq(id))
+            .filter(cn_dsl::tenant_id.eq(tenant_id))
+            .filter(cn_dsl::status.ne(CreditNoteStatus::Voided))
+            .set((
+                cn_dsl::status.eq(CreditNoteStatus::Voided),
+                cn_dsl::voided_at.eq(now),
+                cn_dsl::updated_at.eq(now),
+            ))
+            .returning(CreditNoteRow::as_returning());
+
+        log::debug!("{}", debug_query::<diesel::pg::Pg, _>(&query));
+
+        query
+            .get_result(conn)
+            .await
+            .attach_printable("Error while voiding credit note")
+            .into_db_result()
+    }
+
+    pub async fn save_documents(
+        conn: &mut PgConn,
+        id: Uuid,
+        tenant_id: Uuid,
+        pdf_document_id: String,
+    ) -> DbResult<usize> {
+        use crate::schema::credit_note::dsl as cn_dsl;
+
+        let query = diesel::update(cn_dsl::credit_note)
+            .filter(cn_dsl::id.eq(id))
+            .filter(cn_dsl::tenant_id.eq(tenant_id))
+            .set(cn_dsl::pdf_document_id.eq(pdf_document_id));
+
+        log::debug!("{}", debug_query::<diesel::pg::Pg, _>(&query));
+
+        query
+            .execute(conn)
+            .await
+            .attach_printable("Error while saving credit note documents")
+            .into_db_result()
+    }
+}
@@ -24,7 +24,26 @@ impl CustomerBalanceTxRowNew {
     }
 }
 
-impl CustomerBalanceTxRow {}
+impl CustomerBalanceTxRow {
+    pub async fn list_by_invoice_id(
+        conn: &mut PgConn,
+        tenant_id: Uuid,
+        invoice_id: Uuid,
+    ) -> DbResult<Vec<CustomerBalanceTxRow>> {
+        use crate::schema::customer_balance_tx::dsl as cbtx_dsl;
+
+        let query = cbtx_dsl::customer_balance_tx
+            .filter(cbtx_dsl::tenant_id.eq(tenant_id))
+            .filter(cbtx_dsl::invoice_id.eq(invoice_id));
+        log::debug!("{}", debug_query::<diesel::pg::Pg, _>(&query).to_string());
+
+        query
+            .get_results(conn)
+            .await
+            .attach_printable("Error while listing customer balance txs by invoice_id")
+            .into_db_result()
+    }
+}
 
 impl CustomerBalancePendingTxRowNew {
     pub async fn insert(self, conn: &mut PgConn) -> DbResult<CustomerBalancePendingTxRow> {
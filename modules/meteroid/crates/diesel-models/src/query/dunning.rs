@@ -0,0 +1,257 @@
+use crate::dunning::{
+    DunningConfigRow, DunningConfigRowNew, InvoiceDunningRow, InvoiceDunningRowNew,
+};
+use crate::enums::{InvoiceDunningStatusEnum, InvoiceExternalStatusEnum, InvoiceStatusEnum};
+use crate::errors::IntoDbResult;
+use crate::extend::cursor_pagination::{
+    CursorPaginate, CursorPaginatedVec, CursorPaginationRequest,
+};
+use crate::invoices::InvoiceRow;
+use crate::{DbResult, PgConn};
+use chrono::NaiveDateTime;
+use diesel::{
+    debug_query, BoolExpressionMethods, ExpressionMethods, JoinOnDsl, OptionalExtension, QueryDsl,
+    SelectableHelper,
+};
+use diesel_async::RunQueryDsl;
+use error_stack::ResultExt;
+use uuid::Uuid;
+
+impl DunningConfigRowNew {
+    pub async fn upsert(&self, conn: &mut PgConn) -> DbResult<DunningConfigRow> {
+        use crate::schema::dunning_config::dsl as dc_dsl;
+
+        let query = diesel::insert_into(dc_dsl::dunning_config)
+            .values(self)
+            .on_conflict(dc_dsl::invoicing_entity_id)
+            .do_update()
+            .set((
+                dc_dsl::enabled.eq(self.enabled),
+                dc_dsl::retry_schedule_days.eq(&self.retry_schedule_days),
+                dc_dsl::end_after_days.eq(self.end_after_days),
+                dc_dsl::subscription_action.eq(&self.subscription_action),
+                dc_dsl::mark_uncollectible.eq(self.mark_uncollectible),
+                dc_dsl::updated_at.eq(chrono::Utc::now().naive_utc()),
+            ));
+
+        log::debug!("{}", debug_query::<diesel::pg::Pg, _>(&query));
+
+        query
+            .get_result(conn)
+            .await
+            .attach_printable("Error while upserting dunning config")
+            .into_db_result()
+    }
+}
+
+impl DunningConfigRow {
+    pub async fn find_by_invoicing_entity_id(
+        conn: &mut PgConn,
+        tenant_id: Uuid,
+        invoicing_entity_id: Uuid,
+    ) -> DbResult<Option<DunningConfigRow>> {
+        use crate::schema::dunning_config::dsl as dc_dsl;
+
+        let query = dc_dsl::dunning_config
+            .filter(dc_dsl::tenant_id.eq(tenant_id))
+            .filter(dc_dsl::invoicing_entity_id.eq(invoicing_entity_id))
+            .select(DunningConfigRow::as_select());
+
+        log::debug!("{}", debug_query::<diesel::pg::Pg, _>(&query));
+
+        query
+            .first(conn)
+            .await
+            .optional()
+            .attach_printable("Error while finding dunning config")
+            .into_db_result()
+    }
+}
+
+impl InvoiceDunningRowNew {
+    pub async fn insert(&self, conn: &mut PgConn) -> DbResult<Option<InvoiceDunningRow>> {
+        use crate::schema::invoice_dunning::dsl as id_dsl;
+
+        let query = diesel::insert_into(id_dsl::invoice_dunning)
+            .values(self)
+            .on_conflict(id_dsl::invoice_id)
+            .do_nothing();
+
+        log::debug!("{}", debug_query::<diesel::pg::Pg, _>(&query));
+
+        query
+            .get_result(conn)
+            .await
+            .optional()
+            .attach_printable("Error while inserting invoice dunning")
+            .into_db_result()
+    }
+}
+
+impl InvoiceDunningRow {
+    /// Overdue or failed invoices, not dunned yet, whose invoicing entity has dunning enabled.
+    pub async fn list_invoices_to_dun(
+        conn: &mut PgConn,
+        now: NaiveDateTime,
+        pagination: CursorPaginationRequest,
+    ) -> DbResult<CursorPaginatedVec<InvoiceRow>> {
+        use crate::schema::customer::dsl as c_dsl;
+        use crate::schema::dunning_config::dsl as dc_dsl;
+        use crate::schema::invoice::dsl as i_dsl;
+        use crate::schema::invoice_dunning::dsl as id_dsl;
+
+        let query = i_dsl::invoice
+            .inner_join(c_dsl::customer.on(i_dsl::customer_id.eq(c_dsl::id)))
+            .inner_join(
+                dc_dsl::dunning_config.on(c_dsl::invoicing_entity_id
+                    .eq(dc_dsl::invoicing_entity_id)
+                    .and(dc_dsl::enabled.eq(true))),
+            )
+            .left_join(id_dsl::invoice_dunning.on(id_dsl::invoice_id.eq(i_dsl::id)))
+            .filter(id_dsl::id.is_null())
+            .filter(i_dsl::status.eq(InvoiceStatusEnum::Finalized))
+            .filter(i_dsl::amount_due.gt(0))
+            .filter(
+                i_dsl::external_status
+                    .is_null()
+                    .or(i_dsl::external_status.ne_all(vec![
+                        InvoiceExternalStatusEnum::Paid,
+                        InvoiceExternalStatusEnum::Void,
+                        InvoiceExternalStatusEnum::Uncollectible,
+                        InvoiceExternalStatusEnum::Deleted,
+                    ])),
+            )
+            .filter(
+                i_dsl::due_at
+                    .lt(now)
+                    .or(i_dsl::external_status.eq(InvoiceExternalStatusEnum::PaymentFailed)),
+            )
+            .select(InvoiceRow::as_select())
+            .cursor_paginate(pagination, "id");
+
+        log::debug!("{}", debug_query::<diesel::pg::Pg, _>(&query));
+
+        query
+            .load_and_get_next_cursor(conn, |a| a.id)
+            .await
+            .attach_printable("Error while paginating invoices to dun")
+            .into_db_result()
+    }
+
+    pub async fn select_for_update_by_id(
+        conn: &mut PgConn,
+        id: Uuid,
+    ) -> DbResult<InvoiceDunningRow> {
+        use crate::schema::invoice_dunning::dsl as id_dsl;
+
+        let query = id_dsl::invoice_dunning
+            .for_update()
+            .filter(id_dsl::id.eq(id))
+            .select(InvoiceDunningRow::as_select());
+
+        log::debug!("{}", debug_query::<diesel::pg::Pg, _>(&query));
+
+        query
+            .get_result(conn)
+            .await
+            .attach_printable("Error while locking invoice dunning for update")
+            .into_db_result()
+    }
+
+    pub async fn find_by_invoice_id(
+        conn: &mut PgConn,
+        tenant_id: Uuid,
+        invoice_id: Uuid,
+    ) -> DbResult<Option<InvoiceDunningRow>> {
+        use crate::schema::invoice_dunning::dsl as id_dsl;
+
+        let query = id_dsl::invoice_dunning
+            .filter(id_dsl::tenant_id.eq(tenant_id))
+            .filter(id_dsl::invoice_id.eq(invoice_id))
+            .select(InvoiceDunningRow::as_select());
+
+        log::debug!("{}", debug_query::<diesel::pg::Pg, _>(&query));
+
+        query
+            .first(conn)
+            .await
+            .optional()
+            .attach_printable("Error while finding invoice dunning")
+            .into_db_result()
+    }
+
+    pub async fn list_due(
+        conn: &mut PgConn,
+        now: NaiveDateTime,
+        pagination: CursorPaginationRequest,
+    ) -> DbResult<CursorPaginatedVec<InvoiceDunningRow>> {
+        use crate::schema::invoice_dunning::dsl as id_dsl;
+
+        let query = id_dsl::invoice_dunning
+            .filter(id_dsl::status.eq(InvoiceDunningStatusEnum::Active))
+            .filter(id_dsl::next_attempt_at.le(now))
+            .select(InvoiceDunningRow::as_select())
+            .cursor_paginate(pagination, "id");
+
+        log::debug!("{}", debug_query::<diesel::pg::Pg, _>(&query));
+
+        query
+            .load_and_get_next_cursor(conn, |a| a.id)
+            .await
+            .attach_printable("Error while paginating due invoice dunnings")
+            .into_db_result()
+    }
+
+    pub async fn record_attempt(
+        conn: &mut PgConn,
+        id: Uuid,
+        attempts: i32,
+        next_attempt_at: NaiveDateTime,
+    ) -> DbResult<()> {
+        use crate::schema::invoice_dunning::dsl as id_dsl;
+
+        let query = diesel::update(id_dsl::invoice_dunning)
+            .filter(id_dsl::id.eq(id))
+            .set((
+                id_dsl::attempts.eq(attempts),
+                id_dsl::next_attempt_at.eq(next_attempt_at),
+                id_dsl::last_attempt_at.eq(chrono::Utc::now().naive_utc()),
+            ));
+
+        log::debug!("{}", debug_query::<diesel::pg::Pg, _>(&query));
+
+        query
+            .execute(conn)
+            .await
+            .attach_printable("Error while recording invoice dunning attempt")
+            .into_db_result()?;
+
+        Ok(())
+    }
+
+    pub async fn end(
+        conn: &mut PgConn,
+        id: Uuid,
+        status: InvoiceDunningStatusEnum,
+    ) -> DbResult<()> {
+        use crate::schema::invoice_dunning::dsl as id_dsl;
+
+        let query = diesel::update(id_dsl::invoice_dunning)
+            .filter(id_dsl::id.eq(id))
+            .filter(id_dsl::status.eq(InvoiceDunningStatusEnum::Active))
+            .set((
+                id_dsl::status.eq(status),
+                id_dsl::ended_at.eq(chrono::Utc::now().naive_utc()),
+            ));
+
+        log::debug!("{}", debug_query::<diesel::pg::Pg, _>(&query));
+
+        query
+            .execute(conn)
+            .await
+            .attach_printable("Error while ending invoice dunning")
+            .into_db_result()?;
+
+        Ok(())
+    }
+}
@@ -2,11 +2,13 @@ use crate::errors::IntoDbResult;
 use crate::invoices::{
     DetailedInvoiceRow, InvoiceRow, InvoiceRowLinesPatch, InvoiceRowNew, InvoiceWithCustomerRow,
 };
-use chrono::NaiveDateTime;
+use chrono::{NaiveDate, NaiveDateTime};
 
 use crate::{DbResult, PgConn};
 
-use crate::enums::{InvoiceExternalStatusEnum, InvoiceStatusEnum, InvoicingProviderEnum};
+use crate::enums::{
+    InvoiceExternalStatusEnum, InvoiceStatusEnum, InvoiceType, InvoicingProviderEnum,
+};
 use crate::extend::cursor_pagination::{
     CursorPaginate, CursorPaginatedVec, CursorPaginationRequest,
 };
@@ -123,6 +125,29 @@ impl InvoiceRow {
             .into_db_result()
     }
 
+    pub async fn select_for_update_by_id(
+        conn: &mut PgConn,
+        param_tenant_id: uuid::Uuid,
+        param_invoice_id: uuid::Uuid,
+    ) -> DbResult<InvoiceRow> {
+        use crate::schema::invoice::dsl as i_dsl;
+        use diesel_async::RunQueryDsl;
+
+        let query = i_dsl::invoice
+            .for_no_key_update()
+            .filter(i_dsl::tenant_id.eq(param_tenant_id))
+            .filter(i_dsl::id.eq(param_invoice_id))
+            .select(InvoiceRow::as_select());
+
+        log::debug!("{}", debug_query::<diesel::pg::Pg, _>(&query));
+
+        query
+            .first(conn)
+            .await
+            .attach_printable("Error while selecting invoice for update")
+            .into_db_result()
+    }
+
     pub async fn list_by_ids(
         conn: &mut PgConn,
         param_invoice_ids: Vec<uuid::Uuid>,
@@ -257,6 +282,28 @@ impl InvoiceRow {
             .into_db_result()
     }
 
+    pub async fn void(conn: &mut PgConn, id: uuid::Uuid, tenant_id: uuid::Uuid) -> DbResult<usize> {
+        use crate::schema::invoice::dsl as i_dsl;
+        use diesel_async::RunQueryDsl;
+
+        let query = diesel::update(i_dsl::invoice)
+            .filter(i_dsl::id.eq(id))
+            .filter(i_dsl::tenant_id.eq(tenant_id))
+            .filter(i_dsl::status.eq(InvoiceStatusEnum::Finalized))
+            .set((
+                i_dsl::status.eq(InvoiceStatusEnum::Void),
+                i_dsl::updated_at.eq(chrono::Utc::now().naive_utc()),
+            ));
+
+        log::debug!("{}", debug_query::<diesel::pg::Pg, _>(&query).to_string());
+
+        query
+            .execute(conn)
+            .await
+            .attach_printable("Error while voiding invoice")
+            .into_db_result()
+    }
+
     pub async fn save_invoice_documents(
         conn: &mut PgConn,
         id: uuid::Uuid,
@@ -284,6 +331,116 @@ impl InvoiceRow {
             .into_db_result()
     }
 
+    pub async fn find_recurring_by_subscription_id_and_date(
+        conn: &mut PgConn,
+        subscription_id: uuid::Uuid,
+        invoice_date: chrono::NaiveDate,
+    ) -> DbResult<Option<InvoiceRow>> {
+        use crate::schema::invoice::dsl as i_dsl;
+        use diesel::OptionalExtension;
+        use diesel_async::RunQueryDsl;
+
+        let query = i_dsl::invoice
+            .filter(i_dsl::subscription_id.eq(subscription_id))
+            .filter(i_dsl::invoice_date.eq(invoice_date))
+            .filter(i_dsl::invoice_type.eq(InvoiceType::Recurring))
+            .filter(i_dsl::status.ne(InvoiceStatusEnum::Void))
+            .select(InvoiceRow::as_select());
+
+        log::debug!("{}", debug_query::<diesel::pg::Pg, _>(&query));
+
+        query
+            .first(conn)
+            .await
+            .optional()
+            .attach_printable("Error while fetching recurring invoice by subscription and date")
+            .into_db_result()
+    }
+
+    /// The usage-threshold invoices of the subscription, dated within the given range (inclusive).
+    pub async fn list_usage_threshold_by_subscription_id(
+        conn: &mut PgConn,
+        subscription_id: uuid::Uuid,
+        from: chrono::NaiveDate,
+        to: chrono::NaiveDate,
+    ) -> DbResult<Vec<InvoiceRow>> {
+        use crate::schema::invoice::dsl as i_dsl;
+        use diesel_async::RunQueryDsl;
+
+        let query = i_dsl::invoice
+            .filter(i_dsl::subscription_id.eq(subscription_id))
+            .filter(i_dsl::invoice_type.eq(InvoiceType::UsageThreshold))
+            .filter(i_dsl::status.ne(InvoiceStatusEnum::Void))
+            .filter(i_dsl::invoice_date.between(from, to))
+            .order(i_dsl::created_at.asc())
+            .select(InvoiceRow::as_select());
+
+        log::debug!("{}", debug_query::<diesel::pg::Pg, _>(&query));
+
+        query
+            .get_results(conn)
+            .await
+            .attach_printable("Error while listing usage threshold invoices by subscription")
+            .into_db_result()
+    }
+
+    pub async fn find_latest_recurring_by_subscription_id(
+        conn: &mut PgConn,
+        subscription_id: uuid::Uuid,
+    ) -> DbResult<Option<InvoiceRow>> {
+        use crate::schema::invoice::dsl as i_dsl;
+        use diesel::OptionalExtension;
+        use diesel_async::RunQueryDsl;
+
+        let query = i_dsl::invoice
+            .filter(i_dsl::subscription_id.eq(subscription_id))
+            .filter(i_dsl::invoice_type.eq(InvoiceType::Recurring))
+            .filter(i_dsl::status.ne(InvoiceStatusEnum::Void))
+            .order(i_dsl::invoice_date.desc())
+            .select(InvoiceRow::as_select());
+
+        log::debug!("{}", debug_query::<diesel::pg::Pg, _>(&query));
+
+        query
+            .first(conn)
+            .await
+            .optional()
+            .attach_printable("Error while fetching latest recurring invoice by subscription")
+            .into_db_result()
+    }
+
+    /// Voids the draft and pending recurring invoices of the subscription dated after the given date.
+    pub async fn void_upcoming_by_subscription_id(
+        conn: &mut PgConn,
+        tenant_id: uuid::Uuid,
+        subscription_id: uuid::Uuid,
+        after: chrono::NaiveDate,
+    ) -> DbResult<usize> {
+        use crate::schema::invoice::dsl as i_dsl;
+        use diesel_async::RunQueryDsl;
+
+        let query = diesel::update(i_dsl::invoice)
+            .filter(i_dsl::tenant_id.eq(tenant_id))
+            .filter(i_dsl::subscription_id.eq(subscription_id))
+            .filter(i_dsl::invoice_type.eq(InvoiceType::Recurring))
+            .filter(
+                i_dsl::status.eq_any(vec![InvoiceStatusEnum::Draft, InvoiceStatusEnum::Pending]),
+            )
+            .filter(i_dsl::invoice_date.gt(after))
+            .set((
+                i_dsl::status.eq(InvoiceStatusEnum::Void),
+                i_dsl::updated_at.eq(chrono::Utc::now().naive_utc()),
+            ));
+
+        log::debug!("{}", debug_query::<diesel::pg::Pg, _>(&query));
+
+        query
+            .execute(conn)
+            .await
+            .attach_printable("Error while voiding upcoming invoices of subscription")
+            .into_db_result()
+    }
+
     pub async fn list_outdated(
         conn: &mut PgConn,
         pagination: CursorPaginationRequest,
@@ -294,6 +451,8 @@ impl InvoiceRow {
             .filter(
                 i_dsl::status.ne_all(vec![InvoiceStatusEnum::Void, InvoiceStatusEnum::Finalized]),
             )
+            // other invoice types have fixed lines, that are not computed from the subscription
+            .filter(i_dsl::invoice_type.eq(InvoiceType::Recurring))
             .filter(
                 i_dsl::data_updated_at
                     .is_null()
@@ -311,6 +470,36 @@ impl InvoiceRow {
             .into_db_result()
     }
 
+    /// Marks the drafts of these customers dated on or after `since` as outdated, so that their data is refreshed
+    /// by the price worker. Used when usage that may have been billed by these drafts is amended.
+    pub async fn mark_drafts_outdated(
+        conn: &mut PgConn,
+        tenant_id: uuid::Uuid,
+        customer_ids: Vec<uuid::Uuid>,
+        since: NaiveDate,
+    ) -> DbResult<usize> {
+        use crate::schema::invoice::dsl as i_dsl;
+        use diesel_async::RunQueryDsl;
+
+        let query = diesel::update(i_dsl::invoice)
+            .filter(i_dsl::tenant_id.eq(tenant_id))
+            .filter(i_dsl::customer_id.eq_any(customer_ids))
+            .filter(i_dsl::invoice_date.ge(since))
+            .filter(
+                i_dsl::status.ne_all(vec![InvoiceStatusEnum::Void, InvoiceStatusEnum::Finalized]),
+            )
+            .filter(i_dsl::invoice_type.eq(InvoiceType::Recurring))
+            .set(i_dsl::data_updated_at.eq(None::<NaiveDateTime>));
+
+        log::debug!("{}", debug_query::<diesel::pg::Pg, _>(&query));
+
+        query
+            .execute(conn)
+            .await
+            .attach_printable("Error while marking draft invoices as outdated")
+            .into_db_result()
+    }
+
     pub async fn list_to_issue(
         conn: &mut PgConn,
         max_attempts: i32,
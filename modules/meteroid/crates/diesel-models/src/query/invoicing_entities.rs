@@ -177,6 +177,7 @@ impl InvoicingEntityRow {
         id: &uuid::Uuid,
         tenant_id: &uuid::Uuid,
         new_invoice_number: i64,
+        year: i32,
     ) -> DbResult<InvoicingEntityRow> {
         use crate::schema::invoicing_entity::dsl;
         use diesel_async::RunQueryDsl;
@@ -184,7 +185,10 @@ impl InvoicingEntityRow {
         let query = diesel::update(dsl::invoicing_entity)
             .filter(dsl::id.eq(id))
             .filter(dsl::tenant_id.eq(tenant_id))
-            .set(dsl::next_invoice_number.eq(new_invoice_number + 1));
+            .set((
+                dsl::next_invoice_number.eq(new_invoice_number + 1),
+                dsl::invoice_number_year.eq(year),
+            ));
 
         log::debug!("{}", debug_query::<diesel::pg::Pg, _>(&query).to_string());
 
@@ -194,6 +198,33 @@ impl InvoicingEntityRow {
             .attach_printable("Error while updating invoicing entity number")
             .into_db_result()
     }
+
+    pub async fn update_credit_note_number(
+        conn: &mut PgConn,
+        id: &uuid::Uuid,
+        tenant_id: &uuid::Uuid,
+        new_credit_note_number: i64,
+        year: i32,
+    ) -> DbResult<InvoicingEntityRow> {
+        use crate::schema::invoicing_entity::dsl;
+        use diesel_async::RunQueryDsl;
+
+        let query = diesel::update(dsl::invoicing_entity)
+            .filter(dsl::id.eq(id))
+            .filter(dsl::tenant_id.eq(tenant_id))
+            .set((
+                dsl::next_credit_note_number.eq(new_credit_note_number + 1),
+                dsl::credit_note_number_year.eq(year),
+            ));
+
+        log::debug!("{}", debug_query::<diesel::pg::Pg, _>(&query));
+
+        query
+            .get_result(conn)
+            .await
+            .attach_printable("Error while updating invoicing entity credit note number")
+            .into_db_result()
+    }
 }
 
 impl InvoicingEntityRowPatch {
@@ -5,8 +5,11 @@ pub mod bi;
 pub mod billable_metrics;
 pub mod configs;
 pub mod coupons;
+pub mod credit_grants;
+pub mod credit_notes;
 pub mod customer_balance_txs;
 pub mod customers;
+pub mod dunning;
 pub mod historical_rates_from_usd;
 pub mod invoices;
 pub mod invoicing_entities;
@@ -18,13 +21,17 @@ pub mod plans;
 pub mod price_components;
 pub mod product_families;
 pub mod products;
+pub mod promotion_codes;
 pub mod schedules;
 pub mod slot_transactions;
 pub mod stats;
 pub mod subscription_add_ons;
+pub mod subscription_commitments;
 pub mod subscription_components;
 pub mod subscription_events;
+pub mod subscription_plan_changes;
 pub mod subscriptions;
+pub mod tax_rates;
 pub mod tenants;
 pub mod users;
 pub mod webhooks;
@@ -0,0 +1,128 @@
+use crate::errors::IntoDbResult;
+use crate::promotion_codes::{PromotionCodeRow, PromotionCodeRowNew};
+use crate::{DbResult, PgConn};
+use diesel::{debug_query, ExpressionMethods, QueryDsl, SelectableHelper};
+use diesel_async::RunQueryDsl;
+use error_stack::ResultExt;
+use uuid::Uuid;
+
+impl PromotionCodeRowNew {
+    pub async fn insert(&self, conn: &mut PgConn) -> DbResult<PromotionCodeRow> {
+        use crate::schema::promotion_code::dsl as pc_dsl;
+
+        let query = diesel::insert_into(pc_dsl::promotion_code).values(self);
+
+        log::debug!("{}", debug_query::<diesel::pg::Pg, _>(&query));
+
+        query
+            .get_result(conn)
+            .await
+            .attach_printable("Error while inserting promotion code")
+            .into_db_result()
+    }
+}
+
+impl PromotionCodeRow {
+    pub async fn list_by_coupon_id(
+        conn: &mut PgConn,
+        tenant_id: Uuid,
+        coupon_id: Uuid,
+    ) -> DbResult<Vec<PromotionCodeRow>> {
+        use crate::schema::promotion_code::dsl as pc_dsl;
+
+        let query = pc_dsl::promotion_code
+            .filter(pc_dsl::tenant_id.eq(tenant_id))
+            .filter(pc_dsl::coupon_id.eq(coupon_id))
+            .order(pc_dsl::created_at.asc())
+            .select(PromotionCodeRow::as_select());
+
+        log::debug!("{}", debug_query::<diesel::pg::Pg, _>(&query));
+
+        query
+            .get_results(conn)
+            .await
+            .attach_printable("Error while listing promotion codes")
+            .into_db_result()
+    }
+
+    pub async fn list_by_codes(
+        conn: &mut PgConn,
+        tenant_id: Uuid,
+        codes: &[String],
+    ) -> DbResult<Vec<PromotionCodeRow>> {
+        use crate::schema::promotion_code::dsl as pc_dsl;
+
+        let query = pc_dsl::promotion_code
+            .filter(pc_dsl::tenant_id.eq(tenant_id))
+            .filter(pc_dsl::code.eq_any(codes))
+            .select(PromotionCodeRow::as_select());
+
+        log::debug!("{}", debug_query::<diesel::pg::Pg, _>(&query));
+
+        query
+            .get_results(conn)
+            .await
+            .attach_printable("Error while listing promotion codes by code")
+            .into_db_result()
+    }
+
+    pub async fn list_by_ids_for_update(
+        conn: &mut PgConn,
+        tenant_id: Uuid,
+        ids: &[Uuid],
+    ) -> DbResult<Vec<PromotionCodeRow>> {
+        use crate::schema::promotion_code::dsl as pc_dsl;
+
+        let query = pc_dsl::promotion_code
+            .filter(pc_dsl::tenant_id.eq(tenant_id))
+            .filter(pc_dsl::id.eq_any(ids))
+            .select(PromotionCodeRow::as_select())
+            .for_update();
+
+        log::debug!("{}", debug_query::<diesel::pg::Pg, _>(&query));
+
+        query
+            .get_results(conn)
+            .await
+            .attach_printable("Error while listing promotion codes for update")
+            .into_db_result()
+    }
+
+    pub async fn inc_redemption_count(conn: &mut PgConn, id: Uuid, delta: i32) -> DbResult<()> {
+        use crate::schema::promotion_code::dsl as pc_dsl;
+
+        let query = diesel::update(pc_dsl::promotion_code)
+            .filter(pc_dsl::id.eq(id))
+            .set(pc_dsl::redemption_count.eq(pc_dsl::redemption_count + delta));
+
+        log::debug!("{}", debug_query::<diesel::pg::Pg, _>(&query));
+
+        query
+            .execute(conn)
+            .await
+            .map(|_| ())
+            .attach_printable("Error while incrementing promotion code redemption count")
+            .into_db_result()
+    }
+
+    pub async fn archive(
+        conn: &mut PgConn,
+        tenant_id: Uuid,
+        id: Uuid,
+    ) -> DbResult<PromotionCodeRow> {
+        use crate::schema::promotion_code::dsl as pc_dsl;
+
+        let query = diesel::update(pc_dsl::promotion_code)
+            .filter(pc_dsl::id.eq(id))
+            .filter(pc_dsl::tenant_id.eq(tenant_id))
+            .set(pc_dsl::archived_at.eq(chrono::Utc::now().naive_utc()));
+
+        log::debug!("{}", debug_query::<diesel::pg::Pg, _>(&query));
+
+        query
+            .get_result(conn)
+            .await
+            .attach_printable("Error while archiving promotion code")
+            .into_db_result()
+    }
+}
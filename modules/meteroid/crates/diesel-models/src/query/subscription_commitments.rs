@@ -0,0 +1,153 @@
+use crate::errors::IntoDbResult;
+use crate::extend::cursor_pagination::{
+    CursorPaginate, CursorPaginatedVec, CursorPaginationRequest,
+};
+use crate::subscription_commitments::{SubscriptionCommitmentRow, SubscriptionCommitmentRowNew};
+use crate::{DbResult, PgConn};
+use chrono::NaiveDate;
+use diesel::{debug_query, ExpressionMethods, QueryDsl, SelectableHelper};
+use diesel_async::RunQueryDsl;
+use error_stack::ResultExt;
+use uuid::Uuid;
+
+impl SubscriptionCommitmentRowNew {
+    pub async fn insert(&self, conn: &mut PgConn) -> DbResult<SubscriptionCommitmentRow> {
+        use crate::schema::subscription_commitment::dsl as sc_dsl;
+
+        let query = diesel::insert_into(sc_dsl::subscription_commitment).values(self);
+
+        log::debug!("{}", debug_query::<diesel::pg::Pg, _>(&query));
+
+        query
+            .get_result(conn)
+            .await
+            .attach_printable("Error while inserting subscription commitment")
+            .into_db_result()
+    }
+}
+
+impl SubscriptionCommitmentRow {
+    /// The commitments of the subscription, with the remaining amount of their credit grant.
+    pub async fn list_by_subscription_id(
+        conn: &mut PgConn,
+        tenant_id: Uuid,
+        subscription_id: Uuid,
+    ) -> DbResult<Vec<(SubscriptionCommitmentRow, i32)>> {
+        use crate::schema::credit_grant::dsl as cg_dsl;
+        use crate::schema::subscription_commitment::dsl as sc_dsl;
+
+        let query = sc_dsl::subscription_commitment
+            .inner_join(cg_dsl::credit_grant)
+            .filter(sc_dsl::tenant_id.eq(tenant_id))
+            .filter(sc_dsl::subscription_id.eq(subscription_id))
+            .order(sc_dsl::start_date.asc())
+            .select((
+                SubscriptionCommitmentRow::as_select(),
+                cg_dsl::remaining_cents,
+            ));
+
+        log::debug!("{}", debug_query::<diesel::pg::Pg, _>(&query));
+
+        query
+            .get_results(conn)
+            .await
+            .attach_printable("Error while listing subscription commitments")
+            .into_db_result()
+    }
+
+    pub async fn find_by_id(
+        conn: &mut PgConn,
+        id: Uuid,
+        tenant_id: Uuid,
+    ) -> DbResult<(SubscriptionCommitmentRow, i32)> {
+        use crate::schema::credit_grant::dsl as cg_dsl;
+        use crate::schema::subscription_commitment::dsl as sc_dsl;
+
+        let query = sc_dsl::subscription_commitment
+            .inner_join(cg_dsl::credit_grant)
+            .filter(sc_dsl::id.eq(id))
+            .filter(sc_dsl::tenant_id.eq(tenant_id))
+            .select((
+                SubscriptionCommitmentRow::as_select(),
+                cg_dsl::remaining_cents,
+            ));
+
+        log::debug!("{}", debug_query::<diesel::pg::Pg, _>(&query));
+
+        query
+            .first(conn)
+            .await
+            .attach_printable("Error while finding subscription commitment by id")
+            .into_db_result()
+    }
+
+    /// Commitments whose term ended before the date, and that were not closed yet.
+    pub async fn list_ended(
+        conn: &mut PgConn,
+        input_date_param: NaiveDate,
+        pagination: CursorPaginationRequest,
+    ) -> DbResult<CursorPaginatedVec<SubscriptionCommitmentRow>> {
+        use crate::schema::subscription_commitment::dsl as sc_dsl;
+
+        let query = sc_dsl::subscription_commitment
+            .filter(sc_dsl::end_date.lt(input_date_param))
+            .filter(sc_dsl::closed_at.is_null())
+            .select(SubscriptionCommitmentRow::as_select())
+            .cursor_paginate(pagination, "id");
+
+        log::debug!("{}", debug_query::<diesel::pg::Pg, _>(&query));
+
+        query
+            .load_and_get_next_cursor(conn, |a| a.id)
+            .await
+            .attach_printable("Error while paginating ended subscription commitments")
+            .into_db_result()
+    }
+
+    pub async fn select_for_update_by_id(
+        conn: &mut PgConn,
+        id: Uuid,
+        tenant_id: Uuid,
+    ) -> DbResult<SubscriptionCommitmentRow> {
+        use crate::schema::subscription_commitment::dsl as sc_dsl;
+
+        let query = sc_dsl::subscription_commitment
+            .for_no_key_update()
+            .filter(sc_dsl::id.eq(id))
+            .filter(sc_dsl::tenant_id.eq(tenant_id))
+            .select(SubscriptionCommitmentRow::as_select());
+
+        log::debug!("{}", debug_query::<diesel::pg::Pg, _>(&query));
+
+        query
+            .first(conn)
+            .await
+            .attach_printable("Error while selecting for update subscription commitment by id")
+            .into_db_result()
+    }
+
+    pub async fn close(
+        conn: &mut PgConn,
+        id: Uuid,
+        unused_cents: i32,
+        invoice_id: Option<Uuid>,
+    ) -> DbResult<SubscriptionCommitmentRow> {
+        use crate::schema::subscription_commitment::dsl as sc_dsl;
+
+        let query = diesel::update(sc_dsl::subscription_commitment)
+            .filter(sc_dsl::id.eq(id))
+            .set((
+                sc_dsl::unused_cents.eq(unused_cents),
+                sc_dsl::invoice_id.eq(invoice_id),
+                sc_dsl::closed_at.eq(diesel::dsl::now),
+            ));
+
+        log::debug!("{}", debug_query::<diesel::pg::Pg, _>(&query));
+
+        query
+            .get_result(conn)
+            .await
+            .attach_printable("Error while closing subscription commitment")
+            .into_db_result()
+    }
+}
@@ -1,5 +1,6 @@
 use crate::errors::IntoDbResult;
 
+use crate::enums::SubscriptionEventType;
 use crate::subscription_events::SubscriptionEventRow;
 use crate::{DbResult, PgConn};
 use chrono::NaiveDate;
@@ -62,4 +63,91 @@ impl SubscriptionEventRow {
             .attach_printable("Error while fetching subscription events")
             .into_db_result()
     }
+
+    pub async fn find_latest_by_subscription_id_and_type(
+        conn: &mut PgConn,
+        subscription_uid: uuid::Uuid,
+        event_type_param: SubscriptionEventType,
+    ) -> DbResult<Option<SubscriptionEventRow>> {
+        use crate::schema::subscription_event::dsl::*;
+        use diesel::OptionalExtension;
+        use diesel_async::RunQueryDsl;
+
+        let query = subscription_event
+            .filter(subscription_id.eq(subscription_uid))
+            .filter(event_type.eq(event_type_param))
+            .order(created_at.desc());
+
+        log::debug!("{}", debug_query::<diesel::pg::Pg, _>(&query));
+
+        query
+            .first(conn)
+            .await
+            .optional()
+            .attach_printable("Error while fetching latest subscription event")
+            .into_db_result()
+    }
+
+    pub async fn list_by_mrr_movement_log_ids(
+        conn: &mut PgConn,
+        movement_log_ids: &[uuid::Uuid],
+    ) -> DbResult<Vec<SubscriptionEventRow>> {
+        use crate::schema::subscription_event::dsl::*;
+        use diesel_async::RunQueryDsl;
+
+        let query = subscription_event
+            .filter(bi_mrr_movement_log_id.eq_any(movement_log_ids))
+            .order(created_at.asc());
+
+        log::debug!("{}", debug_query::<diesel::pg::Pg, _>(&query).to_string());
+
+        query
+            .get_results(conn)
+            .await
+            .attach_printable("Error while fetching subscription events by mrr movement logs")
+            .into_db_result()
+    }
+
+    pub async fn unlink_mrr_movement_logs(
+        conn: &mut PgConn,
+        movement_log_ids: &[uuid::Uuid],
+    ) -> DbResult<usize> {
+        use crate::schema::subscription_event::dsl::*;
+        use diesel_async::RunQueryDsl;
+
+        let query = diesel::update(subscription_event)
+            .filter(bi_mrr_movement_log_id.eq_any(movement_log_ids))
+            .set(bi_mrr_movement_log_id.eq(None::<uuid::Uuid>));
+
+        log::debug!("{}", debug_query::<diesel::pg::Pg, _>(&query));
+
+        query
+            .execute(conn)
+            .await
+            .attach_printable("Error while unlinking subscription events from mrr movement logs")
+            .into_db_result()
+    }
+
+    pub async fn link_mrr_movement_log(
+        conn: &mut PgConn,
+        event_id: uuid::Uuid,
+        movement_log_id: uuid::Uuid,
+    ) -> DbResult<()> {
+        use crate::schema::subscription_event::dsl::*;
+        use diesel_async::RunQueryDsl;
+
+        let query = diesel::update(subscription_event)
+            .filter(id.eq(event_id))
+            .set(bi_mrr_movement_log_id.eq(movement_log_id));
+
+        log::debug!("{}", debug_query::<diesel::pg::Pg, _>(&query));
+
+        query
+            .execute(conn)
+            .await
+            .attach_printable("Error while linking subscription event to mrr movement log")
+            .into_db_result()?;
+
+        Ok(())
+    }
 }
@@ -0,0 +1,139 @@
+use crate::errors::IntoDbResult;
+use crate::extend::cursor_pagination::{
+    CursorPaginate, CursorPaginatedVec, CursorPaginationRequest,
+};
+use crate::subscription_plan_changes::{SubscriptionPlanChangeRow, SubscriptionPlanChangeRowNew};
+use crate::{DbResult, PgConn};
+
+use chrono::NaiveDate;
+use diesel::{debug_query, ExpressionMethods, OptionalExtension, QueryDsl, SelectableHelper};
+use error_stack::ResultExt;
+use uuid::Uuid;
+
+impl SubscriptionPlanChangeRowNew {
+    pub async fn insert(&self, conn: &mut PgConn) -> DbResult<SubscriptionPlanChangeRow> {
+        use crate::schema::subscription_plan_change::dsl::*;
+        use diesel_async::RunQueryDsl;
+
+        let query = diesel::insert_into(subscription_plan_change).values(self);
+
+        log::debug!("{}", debug_query::<diesel::pg::Pg, _>(&query));
+
+        query
+            .get_result(conn)
+            .await
+            .attach_printable("Error while inserting subscription plan change")
+            .into_db_result()
+    }
+}
+
+impl SubscriptionPlanChangeRow {
+    pub async fn find_pending_by_subscription_id(
+        conn: &mut PgConn,
+        tenant_id: Uuid,
+        subscription_id: Uuid,
+    ) -> DbResult<Option<SubscriptionPlanChangeRow>> {
+        use crate::schema::subscription_plan_change::dsl as spc_dsl;
+        use diesel_async::RunQueryDsl;
+
+        let query = spc_dsl::subscription_plan_change
+            .filter(spc_dsl::tenant_id.eq(tenant_id))
+            .filter(spc_dsl::subscription_id.eq(subscription_id))
+            .filter(spc_dsl::applied_at.is_null())
+            .select(SubscriptionPlanChangeRow::as_select());
+
+        log::debug!("{}", debug_query::<diesel::pg::Pg, _>(&query));
+
+        query
+            .first(conn)
+            .await
+            .optional()
+            .attach_printable("Error while fetching pending subscription plan change")
+            .into_db_result()
+    }
+
+    pub async fn select_for_update_by_id(
+        conn: &mut PgConn,
+        id: Uuid,
+    ) -> DbResult<SubscriptionPlanChangeRow> {
+        use crate::schema::subscription_plan_change::dsl as spc_dsl;
+        use diesel_async::RunQueryDsl;
+
+        let query = spc_dsl::subscription_plan_change
+            .for_update()
+            .filter(spc_dsl::id.eq(id))
+            .select(SubscriptionPlanChangeRow::as_select());
+
+        log::debug!("{}", debug_query::<diesel::pg::Pg, _>(&query));
+
+        query
+            .get_result(conn)
+            .await
+            .attach_printable("Error while locking subscription plan change")
+            .into_db_result()
+    }
+
+    pub async fn list_due(
+        conn: &mut PgConn,
+        date: NaiveDate,
+        pagination: CursorPaginationRequest,
+    ) -> DbResult<CursorPaginatedVec<SubscriptionPlanChangeRow>> {
+        use crate::schema::subscription_plan_change::dsl as spc_dsl;
+
+        let query = spc_dsl::subscription_plan_change
+            .filter(spc_dsl::applied_at.is_null())
+            .filter(spc_dsl::effective_date.le(date))
+            .select(SubscriptionPlanChangeRow::as_select())
+            .cursor_paginate(pagination, "id");
+
+        log::debug!("{}", debug_query::<diesel::pg::Pg, _>(&query));
+
+        query
+            .load_and_get_next_cursor(conn, |a| a.id)
+            .await
+            .attach_printable("Error while paginating due subscription plan changes")
+            .into_db_result()
+    }
+
+    pub async fn mark_as_applied(conn: &mut PgConn, id: Uuid) -> DbResult<()> {
+        use crate::schema::subscription_plan_change::dsl as spc_dsl;
+        use diesel_async::RunQueryDsl;
+
+        let query = diesel::update(spc_dsl::subscription_plan_change)
+            .filter(spc_dsl::id.eq(id))
+            .filter(spc_dsl::applied_at.is_null())
+            .set(spc_dsl::applied_at.eq(chrono::Utc::now().naive_utc()));
+
+        log::debug!("{}", debug_query::<diesel::pg::Pg, _>(&query));
+
+        query
+            .execute(conn)
+            .await
+            .attach_printable("Error while marking subscription plan change as applied")
+            .into_db_result()?;
+
+        Ok(())
+    }
+
+    pub async fn delete_pending_by_subscription_id(
+        conn: &mut PgConn,
+        tenant_id: Uuid,
+        subscription_id: Uuid,
+    ) -> DbResult<usize> {
+        use crate::schema::subscription_plan_change::dsl as spc_dsl;
+        use diesel_async::RunQueryDsl;
+
+        let query = diesel::delete(spc_dsl::subscription_plan_change)
+            .filter(spc_dsl::tenant_id.eq(tenant_id))
+            .filter(spc_dsl::subscription_id.eq(subscription_id))
+            .filter(spc_dsl::applied_at.is_null());
+
+        log::debug!("{}", debug_query::<diesel::pg::Pg, _>(&query));
+
+        query
+            .execute(conn)
+            .await
+            .attach_printable("Error while deleting pending subscription plan change")
+            .into_db_result()
+    }
+}
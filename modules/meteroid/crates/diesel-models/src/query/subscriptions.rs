@@ -13,7 +13,7 @@ use diesel::{
 };
 use diesel_async::RunQueryDsl;
 
-use crate::enums::InvoiceType;
+use crate::enums::{BillingPeriodEnum, InvoiceType};
 use crate::extend::cursor_pagination::{
     CursorPaginate, CursorPaginatedVec, CursorPaginationRequest,
 };
@@ -161,6 +161,212 @@ impl SubscriptionRow {
         Ok(())
     }
 
+    pub async fn deactivate_subscription(
+        conn: &mut PgConn,
+        id: Uuid,
+        tenant_id: Uuid,
+    ) -> DbResult<()> {
+        use crate::schema::subscription::dsl as s_dsl;
+
+        let query = diesel::update(s_dsl::subscription)
+            .filter(s_dsl::id.eq(id))
+            .filter(s_dsl::tenant_id.eq(tenant_id))
+            .set(s_dsl::activated_at.eq(None::<chrono::NaiveDateTime>));
+
+        log::debug!("{}", debug_query::<diesel::pg::Pg, _>(&query));
+
+        query
+            .execute(conn)
+            .await
+            .attach_printable("Error while deactivating subscription")
+            .into_db_result()?;
+
+        Ok(())
+    }
+
+    /// Ends the billing of a subscription whose last billing cycle is over.
+    pub async fn end_billing_cycles(
+        conn: &mut PgConn,
+        id: Uuid,
+        billing_end_date: NaiveDate,
+    ) -> DbResult<()> {
+        use crate::schema::subscription::dsl as s_dsl;
+
+        let query = diesel::update(s_dsl::subscription)
+            .filter(s_dsl::id.eq(id))
+            .filter(s_dsl::billing_end_date.is_null())
+            .set(s_dsl::billing_end_date.eq(billing_end_date));
+
+        log::debug!("{}", debug_query::<diesel::pg::Pg, _>(&query));
+
+        query
+            .execute(conn)
+            .await
+            .attach_printable("Error while ending the billing cycles of subscription")
+            .into_db_result()?;
+
+        Ok(())
+    }
+
+    pub async fn pause_subscription(
+        conn: &mut PgConn,
+        id: Uuid,
+        tenant_id: Uuid,
+        resume_at: Option<NaiveDate>,
+    ) -> DbResult<()> {
+        use crate::schema::subscription::dsl as s_dsl;
+
+        let query = diesel::update(s_dsl::subscription)
+            .filter(s_dsl::id.eq(id))
+            .filter(s_dsl::tenant_id.eq(tenant_id))
+            .filter(s_dsl::paused_at.is_null())
+            .set((
+                s_dsl::paused_at.eq(chrono::Utc::now().naive_utc()),
+                s_dsl::resume_at.eq(resume_at),
+            ));
+
+        log::debug!("{}", debug_query::<diesel::pg::Pg, _>(&query));
+
+        query
+            .execute(conn)
+            .await
+            .attach_printable("Error while pausing subscription")
+            .into_db_result()?;
+
+        Ok(())
+    }
+
+    /// Billing restarts at the new billing start date, after the cycles elapsed before the pause.
+    pub async fn resume_subscription(
+        conn: &mut PgConn,
+        id: Uuid,
+        tenant_id: Uuid,
+        billing_start_date: NaiveDate,
+        elapsed_billing_cycles: i32,
+    ) -> DbResult<()> {
+        use crate::schema::subscription::dsl as s_dsl;
+
+        let query = diesel::update(s_dsl::subscription)
+            .filter(s_dsl::id.eq(id))
+            .filter(s_dsl::tenant_id.eq(tenant_id))
+            .filter(s_dsl::paused_at.is_not_null())
+            .set((
+                s_dsl::paused_at.eq(None::<chrono::NaiveDateTime>),
+                s_dsl::resume_at.eq(None::<NaiveDate>),
+                s_dsl::billing_start_date.eq(billing_start_date),
+                s_dsl::elapsed_billing_cycles.eq(elapsed_billing_cycles),
+            ));
+
+        log::debug!("{}", debug_query::<diesel::pg::Pg, _>(&query));
+
+        query
+            .execute(conn)
+            .await
+            .attach_printable("Error while resuming subscription")
+            .into_db_result()?;
+
+        Ok(())
+    }
+
+    /// Paused subscriptions whose auto-resume date is reached.
+    pub async fn list_to_resume(
+        conn: &mut PgConn,
+        input_date_param: NaiveDate,
+        pagination: CursorPaginationRequest,
+    ) -> DbResult<CursorPaginatedVec<SubscriptionRow>> {
+        use crate::schema::subscription::dsl as s_dsl;
+
+        let query = s_dsl::subscription
+            .filter(s_dsl::paused_at.is_not_null())
+            .filter(s_dsl::resume_at.le(input_date_param))
+            .filter(s_dsl::canceled_at.is_null())
+            .select(SubscriptionRow::as_select())
+            .cursor_paginate(pagination, "id");
+
+        log::debug!("{}", debug_query::<diesel::pg::Pg, _>(&query));
+
+        query
+            .load_and_get_next_cursor(conn, |a| a.id)
+            .await
+            .attach_printable("Error while paginating subscriptions to resume")
+            .into_db_result()
+    }
+
+    /// Billed subscriptions with a usage threshold, that are not paused.
+    pub async fn list_with_invoice_threshold(
+        conn: &mut PgConn,
+        input_date_param: NaiveDate,
+        pagination: CursorPaginationRequest,
+    ) -> DbResult<CursorPaginatedVec<SubscriptionRow>> {
+        use crate::schema::subscription::dsl as s_dsl;
+
+        let query = s_dsl::subscription
+            .filter(s_dsl::invoice_threshold.is_not_null())
+            .filter(s_dsl::activated_at.is_not_null())
+            .filter(s_dsl::paused_at.is_null())
+            .filter(s_dsl::billing_start_date.lt(input_date_param))
+            .filter(
+                s_dsl::billing_end_date
+                    .is_null()
+                    .or(s_dsl::billing_end_date.ge(input_date_param)),
+            )
+            .select(SubscriptionRow::as_select())
+            .cursor_paginate(pagination, "id");
+
+        log::debug!("{}", debug_query::<diesel::pg::Pg, _>(&query));
+
+        query
+            .load_and_get_next_cursor(conn, |a| a.id)
+            .await
+            .attach_printable("Error while paginating subscriptions with invoice threshold")
+            .into_db_result()
+    }
+
+    /// Subscriptions in trial whose billing started, and that were not processed yet.
+    pub async fn list_expired_trials(
+        conn: &mut PgConn,
+        input_date_param: NaiveDate,
+        pagination: CursorPaginationRequest,
+    ) -> DbResult<CursorPaginatedVec<SubscriptionRow>> {
+        use crate::schema::subscription::dsl as s_dsl;
+
+        let query = s_dsl::subscription
+            .filter(s_dsl::trial_start_date.is_not_null())
+            .filter(s_dsl::trial_ended_at.is_null())
+            .filter(s_dsl::canceled_at.is_null())
+            .filter(s_dsl::billing_start_date.le(input_date_param))
+            .select(SubscriptionRow::as_select())
+            .cursor_paginate(pagination, "id");
+
+        log::debug!("{}", debug_query::<diesel::pg::Pg, _>(&query));
+
+        query
+            .load_and_get_next_cursor(conn, |a| a.id)
+            .await
+            .attach_printable("Error while paginating expired trials")
+            .into_db_result()
+    }
+
+    pub async fn end_trial(conn: &mut PgConn, id: Uuid, tenant_id: Uuid) -> DbResult<()> {
+        use crate::schema::subscription::dsl as s_dsl;
+
+        let query = diesel::update(s_dsl::subscription)
+            .filter(s_dsl::id.eq(id))
+            .filter(s_dsl::tenant_id.eq(tenant_id))
+            .filter(s_dsl::trial_ended_at.is_null())
+            .set(s_dsl::trial_ended_at.eq(chrono::Utc::now().naive_utc()));
+
+        log::debug!("{}", debug_query::<diesel::pg::Pg, _>(&query));
+
+        query
+            .execute(conn)
+            .await
+            .attach_printable("Error while ending subscription trial")
+            .into_db_result()?;
+
+        Ok(())
+    }
+
     pub async fn get_subscription_id_by_invoice_id(
         conn: &mut PgConn,
         tenant_id_param: &uuid::Uuid,
@@ -235,6 +441,43 @@ impl SubscriptionRow {
             .into_db_result()
     }
 
+    pub async fn list_active_ids_by_plan_version_id(
+        conn: &mut PgConn,
+        tenant_id: Uuid,
+        plan_version_id: Uuid,
+        subscription_ids: Option<Vec<Uuid>>,
+        customer_ids: Option<Vec<Uuid>>,
+    ) -> DbResult<Vec<Uuid>> {
+        use crate::schema::subscription::dsl as s_dsl;
+
+        let mut query = s_dsl::subscription
+            .filter(s_dsl::tenant_id.eq(tenant_id))
+            .filter(s_dsl::plan_version_id.eq(plan_version_id))
+            .filter(s_dsl::canceled_at.is_null())
+            // paused subscriptions are left out, their MRR being restored as is on resume
+            .filter(s_dsl::paused_at.is_null())
+            .select(s_dsl::id)
+            .into_boxed();
+
+        if let Some(subscription_ids) = subscription_ids {
+            query = query.filter(s_dsl::id.eq_any(subscription_ids));
+        }
+
+        if let Some(customer_ids) = customer_ids {
+            query = query.filter(s_dsl::customer_id.eq_any(customer_ids));
+        }
+
+        let query = query.order(s_dsl::id.asc());
+
+        log::debug!("{}", debug_query::<diesel::pg::Pg, _>(&query));
+
+        query
+            .get_results(conn)
+            .await
+            .attach_printable("Error while listing subscriptions by plan version")
+            .into_db_result()
+    }
+
     pub async fn list_subscription_to_invoice_candidates(
         conn: &mut PgConn,
         input_date_param: NaiveDate,
@@ -256,6 +499,14 @@ impl SubscriptionRow {
             )
             // only if started. lt => we consider that initial invoice was already created
             .filter(s_dsl::billing_start_date.lt(input_date_param))
+            // not if blocked at the end of its trial
+            .filter(
+                s_dsl::activated_at
+                    .is_not_null()
+                    .or(s_dsl::trial_ended_at.is_null()),
+            )
+            // not while paused
+            .filter(s_dsl::paused_at.is_null())
             // only if no future recurring invoice exist.
             // (requires a single recurring invoice in parallel. For now, this is true)
             .left_join(
@@ -304,6 +555,34 @@ impl SubscriptionRow {
         Ok(())
     }
 
+    pub async fn update_plan_version(
+        conn: &mut PgConn,
+        subscription_id: Uuid,
+        tenant_id: Uuid,
+        plan_version_id: Uuid,
+        period: BillingPeriodEnum,
+    ) -> DbResult<()> {
+        use crate::schema::subscription::dsl as s_dsl;
+
+        let query = diesel::update(s_dsl::subscription)
+            .filter(s_dsl::id.eq(subscription_id))
+            .filter(s_dsl::tenant_id.eq(tenant_id))
+            .set((
+                s_dsl::plan_version_id.eq(plan_version_id),
+                s_dsl::period.eq(period),
+            ));
+
+        log::debug!("{}", debug_query::<diesel::pg::Pg, _>(&query));
+
+        query
+            .execute(conn)
+            .await
+            .attach_printable("Error while updating subscription plan version")
+            .into_db_result()?;
+
+        Ok(())
+    }
+
     pub async fn lock_subscription_for_update(
         conn: &mut PgConn,
         subscription_id_param: uuid::Uuid,
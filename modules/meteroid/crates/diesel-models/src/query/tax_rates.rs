@@ -0,0 +1,88 @@
+use crate::errors::IntoDbResult;
+use crate::tax_rates::{TaxRateRow, TaxRateRowNew};
+use crate::{DbResult, PgConn};
+use diesel::{debug_query, ExpressionMethods, QueryDsl};
+use diesel_async::RunQueryDsl;
+use error_stack::ResultExt;
+use tap::TapFallible;
+use uuid::Uuid;
+
+impl TaxRateRowNew {
+    pub async fn insert(&self, conn: &mut PgConn) -> DbResult<TaxRateRow> {
+        use crate::schema::tax_rate::dsl as tr_dsl;
+
+        let query = diesel::insert_into(tr_dsl::tax_rate).values(self);
+
+        log::debug!("{}", debug_query::<diesel::pg::Pg, _>(&query));
+
+        query
+            .get_result(conn)
+            .await
+            .attach_printable("Error while inserting tax rate")
+            .into_db_result()
+    }
+}
+
+impl TaxRateRow {
+    pub async fn list_by_tenant_id(
+        conn: &mut PgConn,
+        tenant_id: Uuid,
+    ) -> DbResult<Vec<TaxRateRow>> {
+        use crate::schema::tax_rate::dsl as tr_dsl;
+
+        let query = tr_dsl::tax_rate
+            .filter(tr_dsl::tenant_id.eq(tenant_id))
+            .filter(tr_dsl::archived_at.is_null())
+            .order((tr_dsl::country.asc(), tr_dsl::region.asc()));
+
+        log::debug!("{}", debug_query::<diesel::pg::Pg, _>(&query));
+
+        query
+            .get_results(conn)
+            .await
+            .tap_err(|e| log::error!("Error while listing tax rates: {:?}", e))
+            .attach_printable("Error while listing tax rates")
+            .into_db_result()
+    }
+
+    pub async fn list_by_country(
+        conn: &mut PgConn,
+        tenant_id: Uuid,
+        country: &str,
+    ) -> DbResult<Vec<TaxRateRow>> {
+        use crate::schema::tax_rate::dsl as tr_dsl;
+
+        let query = tr_dsl::tax_rate
+            .filter(tr_dsl::tenant_id.eq(tenant_id))
+            .filter(tr_dsl::country.eq(country))
+            .filter(tr_dsl::archived_at.is_null());
+
+        log::debug!("{}", debug_query::<diesel::pg::Pg, _>(&query));
+
+        query
+            .get_results(conn)
+            .await
+            .tap_err(|e| log::error!("Error while listing tax rates by country: {:?}", e))
+            .attach_printable("Error while listing tax rates by country")
+            .into_db_result()
+    }
+
+    pub async fn archive(conn: &mut PgConn, id: Uuid, tenant_id: Uuid) -> DbResult<usize> {
+        use crate::schema::tax_rate::dsl as tr_dsl;
+
+        let query = diesel::update(tr_dsl::tax_rate)
+            .filter(tr_dsl::id.eq(id))
+            .filter(tr_dsl::tenant_id.eq(tenant_id))
+            .filter(tr_dsl::archived_at.is_null())
+            .set(tr_dsl::archived_at.eq(diesel::dsl::now));
+
+        log::debug!("{}", debug_query::<diesel::pg::Pg, _>(&query));
+
+        query
+            .execute(conn)
+            .await
+            .tap_err(|e| log::error!("Error while archiving tax rate: {:?}", e))
+            .attach_printable("Error while archiving tax rate")
+            .into_db_result()
+    }
+}
@@ -13,14 +13,30 @@ pub mod sql_types {
     #[diesel(postgres_type(name = "BillingPeriodEnum"))]
     pub struct BillingPeriodEnum;
 
+    #[derive(diesel::query_builder::QueryId, diesel::sql_types::SqlType)]
+    #[diesel(postgres_type(name = "CouponStackingEnum"))]
+    pub struct CouponStackingEnum;
+
     #[derive(diesel::query_builder::QueryId, diesel::sql_types::SqlType)]
     #[diesel(postgres_type(name = "CreditNoteStatus"))]
     pub struct CreditNoteStatus;
 
+    #[derive(diesel::query_builder::QueryId, diesel::sql_types::SqlType)]
+    #[diesel(postgres_type(name = "CustomerTaxStatusEnum"))]
+    pub struct CustomerTaxStatusEnum;
+
+    #[derive(diesel::query_builder::QueryId, diesel::sql_types::SqlType)]
+    #[diesel(postgres_type(name = "DunningSubscriptionActionEnum"))]
+    pub struct DunningSubscriptionActionEnum;
+
     #[derive(diesel::query_builder::QueryId, diesel::sql_types::SqlType)]
     #[diesel(postgres_type(name = "fang_task_state"))]
     pub struct FangTaskState;
 
+    #[derive(diesel::query_builder::QueryId, diesel::sql_types::SqlType)]
+    #[diesel(postgres_type(name = "InvoiceDunningStatusEnum"))]
+    pub struct InvoiceDunningStatusEnum;
+
     #[derive(diesel::query_builder::QueryId, diesel::sql_types::SqlType)]
     #[diesel(postgres_type(name = "InvoiceExternalStatusEnum"))]
     pub struct InvoiceExternalStatusEnum;
@@ -57,6 +73,10 @@ pub mod sql_types {
     #[diesel(postgres_type(name = "PlanTypeEnum"))]
     pub struct PlanTypeEnum;
 
+    #[derive(diesel::query_builder::QueryId, diesel::sql_types::SqlType)]
+    #[diesel(postgres_type(name = "SubscriptionCommitmentTypeEnum"))]
+    pub struct SubscriptionCommitmentTypeEnum;
+
     #[derive(diesel::query_builder::QueryId, diesel::sql_types::SqlType)]
     #[diesel(postgres_type(name = "SubscriptionEventType"))]
     pub struct SubscriptionEventType;
@@ -112,6 +132,7 @@ diesel::table! {
         applied_count -> Nullable<Int4>,
         last_applied_at -> Nullable<Timestamp>,
         created_at -> Timestamp,
+        promotion_code_id -> Nullable<Uuid>,
     }
 }
 
@@ -211,6 +232,9 @@ diesel::table! {
 }
 
 diesel::table! {
+    use diesel::sql_types::*;
+    use super::sql_types::CouponStackingEnum;
+
     coupon (id) {
         id -> Uuid,
         code -> Text,
@@ -227,6 +251,10 @@ diesel::table! {
         disabled -> Bool,
         last_redemption_at -> Nullable<Timestamp>,
         archived_at -> Nullable<Timestamp>,
+        applies_to -> Nullable<Jsonb>,
+        duration_months -> Nullable<Int4>,
+        stacking -> CouponStackingEnum,
+        priority -> Int4,
     }
 }
 
@@ -241,16 +269,48 @@ diesel::table! {
         refunded_amount_cents -> Nullable<Int8>,
         credited_amount_cents -> Nullable<Int8>,
         currency -> Text,
-        finalized_at -> Timestamp,
+        finalized_at -> Nullable<Timestamp>,
         plan_version_id -> Nullable<Uuid>,
         invoice_id -> Uuid,
         tenant_id -> Uuid,
         customer_id -> Uuid,
         status -> CreditNoteStatus,
+        credit_note_number -> Nullable<Text>,
+        reason -> Nullable<Text>,
+        memo -> Nullable<Text>,
+        line_items -> Jsonb,
+        subtotal -> Int8,
+        tax_amount -> Int8,
+        total -> Int8,
+        voided_at -> Nullable<Timestamp>,
+        pdf_document_id -> Nullable<Text>,
     }
 }
 
 diesel::table! {
+    credit_grant (id) {
+        id -> Uuid,
+        tenant_id -> Uuid,
+        customer_id -> Uuid,
+        name -> Text,
+        amount_cents -> Int4,
+        remaining_cents -> Int4,
+        currency -> Text,
+        priority -> Int4,
+        effective_date -> Date,
+        expiry_date -> Nullable<Date>,
+        price_component_ids -> Array<Nullable<Uuid>>,
+        metric_ids -> Array<Nullable<Uuid>>,
+        created_at -> Timestamp,
+        created_by -> Nullable<Uuid>,
+        expired_at -> Nullable<Timestamp>,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+    use super::sql_types::CustomerTaxStatusEnum;
+
     customer (id) {
         id -> Uuid,
         name -> Text,
@@ -270,6 +330,8 @@ diesel::table! {
         billing_address -> Nullable<Jsonb>,
         shipping_address -> Nullable<Jsonb>,
         invoicing_entity_id -> Uuid,
+        vat_number -> Nullable<Text>,
+        tax_status -> CustomerTaxStatusEnum,
     }
 }
 
@@ -299,6 +361,25 @@ diesel::table! {
         tenant_id -> Uuid,
         customer_id -> Uuid,
         created_by -> Nullable<Uuid>,
+        credit_grant_id -> Nullable<Uuid>,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+    use super::sql_types::DunningSubscriptionActionEnum;
+
+    dunning_config (id) {
+        id -> Uuid,
+        tenant_id -> Uuid,
+        invoicing_entity_id -> Uuid,
+        enabled -> Bool,
+        retry_schedule_days -> Jsonb,
+        end_after_days -> Int4,
+        subscription_action -> DunningSubscriptionActionEnum,
+        mark_uncollectible -> Bool,
+        created_at -> Timestamp,
+        updated_at -> Timestamp,
     }
 }
 
@@ -381,7 +462,7 @@ diesel::table! {
         finalized_at -> Nullable<Timestamp>,
         net_terms -> Int4,
         memo -> Nullable<Text>,
-        tax_rate -> Int4,
+        tax_rate -> Numeric,
         local_id -> Text,
         reference -> Nullable<Text>,
         invoice_number -> Text,
@@ -397,6 +478,26 @@ diesel::table! {
         xml_document_id -> Nullable<Text>,
         pdf_document_id -> Nullable<Text>,
         applied_coupon_ids -> Array<Nullable<Uuid>>,
+        tax_breakdown -> Jsonb,
+        replaces_invoice_id -> Nullable<Uuid>,
+        coupons -> Jsonb,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+    use super::sql_types::InvoiceDunningStatusEnum;
+
+    invoice_dunning (id) {
+        id -> Uuid,
+        tenant_id -> Uuid,
+        invoice_id -> Uuid,
+        status -> InvoiceDunningStatusEnum,
+        attempts -> Int4,
+        started_at -> Timestamp,
+        next_attempt_at -> Timestamp,
+        last_attempt_at -> Nullable<Timestamp>,
+        ended_at -> Nullable<Timestamp>,
     }
 }
 
@@ -426,6 +527,9 @@ diesel::table! {
         #[max_length = 50]
         accounting_currency -> Varchar,
         tenant_id -> Uuid,
+        credit_note_number_pattern -> Text,
+        invoice_number_year -> Nullable<Int4>,
+        credit_note_number_year -> Nullable<Int4>,
     }
 }
 
@@ -529,6 +633,21 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    promotion_code (id) {
+        id -> Uuid,
+        code -> Text,
+        coupon_id -> Uuid,
+        tenant_id -> Uuid,
+        customer_id -> Nullable<Uuid>,
+        expires_at -> Nullable<Timestamp>,
+        redemption_limit -> Nullable<Int4>,
+        redemption_count -> Int4,
+        archived_at -> Nullable<Timestamp>,
+        created_at -> Timestamp,
+    }
+}
+
 diesel::table! {
     product (id) {
         id -> Uuid,
@@ -619,6 +738,12 @@ diesel::table! {
         currency -> Varchar,
         mrr_cents -> Int8,
         period -> BillingPeriodEnum,
+        trial_ended_at -> Nullable<Timestamp>,
+        paused_at -> Nullable<Timestamp>,
+        resume_at -> Nullable<Date>,
+        minimum_spend -> Nullable<Numeric>,
+        spend_cap -> Nullable<Numeric>,
+        elapsed_billing_cycles -> Int4,
     }
 }
 
@@ -637,6 +762,27 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    use diesel::sql_types::*;
+    use super::sql_types::SubscriptionCommitmentTypeEnum;
+
+    subscription_commitment (id) {
+        id -> Uuid,
+        tenant_id -> Uuid,
+        subscription_id -> Uuid,
+        commitment_type -> SubscriptionCommitmentTypeEnum,
+        amount_cents -> Int4,
+        start_date -> Date,
+        end_date -> Date,
+        credit_grant_id -> Uuid,
+        invoice_id -> Nullable<Uuid>,
+        unused_cents -> Nullable<Int4>,
+        created_at -> Timestamp,
+        created_by -> Nullable<Uuid>,
+        closed_at -> Nullable<Timestamp>,
+    }
+}
+
 diesel::table! {
     use diesel::sql_types::*;
     use super::sql_types::SubscriptionFeeBillingPeriod;
@@ -668,6 +814,34 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    subscription_plan_change (id) {
+        id -> Uuid,
+        subscription_id -> Uuid,
+        tenant_id -> Uuid,
+        plan_version_id -> Uuid,
+        components -> Jsonb,
+        mrr_delta -> Int8,
+        effective_date -> Date,
+        created_at -> Timestamp,
+        created_by -> Uuid,
+        applied_at -> Nullable<Timestamp>,
+    }
+}
+
+diesel::table! {
+    tax_rate (id) {
+        id -> Uuid,
+        tenant_id -> Uuid,
+        name -> Text,
+        country -> Text,
+        region -> Nullable<Text>,
+        rate -> Numeric,
+        created_at -> Timestamp,
+        archived_at -> Nullable<Timestamp>,
+    }
+}
+
 diesel::table! {
     use diesel::sql_types::*;
     use super::sql_types::TenantEnvironmentEnum;
@@ -748,6 +922,7 @@ diesel::joinable!(add_on -> tenant (tenant_id));
 diesel::joinable!(api_token -> tenant (tenant_id));
 diesel::joinable!(applied_coupon -> coupon (coupon_id));
 diesel::joinable!(applied_coupon -> customer (customer_id));
+diesel::joinable!(applied_coupon -> promotion_code (promotion_code_id));
 diesel::joinable!(applied_coupon -> subscription (subscription_id));
 diesel::joinable!(bi_delta_mrr_daily -> historical_rates_from_usd (historical_rate_id));
 diesel::joinable!(bi_mrr_movement_log -> credit_note (credit_note_id));
@@ -758,6 +933,9 @@ diesel::joinable!(bi_revenue_daily -> historical_rates_from_usd (historical_rate
 diesel::joinable!(billable_metric -> product_family (product_family_id));
 diesel::joinable!(billable_metric -> tenant (tenant_id));
 diesel::joinable!(coupon -> tenant (tenant_id));
+diesel::joinable!(credit_grant -> customer (customer_id));
+diesel::joinable!(credit_grant -> tenant (tenant_id));
+diesel::joinable!(credit_grant -> user (created_by));
 diesel::joinable!(credit_note -> customer (customer_id));
 diesel::joinable!(credit_note -> invoice (invoice_id));
 diesel::joinable!(credit_note -> plan_version (plan_version_id));
@@ -769,13 +947,18 @@ diesel::joinable!(customer_balance_pending_tx -> customer_balance_tx (tx_id));
 diesel::joinable!(customer_balance_pending_tx -> invoice (invoice_id));
 diesel::joinable!(customer_balance_pending_tx -> tenant (tenant_id));
 diesel::joinable!(customer_balance_pending_tx -> user (created_by));
+diesel::joinable!(customer_balance_tx -> credit_grant (credit_grant_id));
 diesel::joinable!(customer_balance_tx -> customer (customer_id));
 diesel::joinable!(customer_balance_tx -> invoice (invoice_id));
 diesel::joinable!(customer_balance_tx -> tenant (tenant_id));
 diesel::joinable!(customer_balance_tx -> user (created_by));
+diesel::joinable!(dunning_config -> invoicing_entity (invoicing_entity_id));
+diesel::joinable!(dunning_config -> tenant (tenant_id));
 diesel::joinable!(invoice -> customer (customer_id));
 diesel::joinable!(invoice -> plan_version (plan_version_id));
 diesel::joinable!(invoice -> tenant (tenant_id));
+diesel::joinable!(invoice_dunning -> invoice (invoice_id));
+diesel::joinable!(invoice_dunning -> tenant (tenant_id));
 diesel::joinable!(invoicing_entity -> tenant (tenant_id));
 diesel::joinable!(organization_member -> organization (organization_id));
 diesel::joinable!(organization_member -> user (user_id));
@@ -785,6 +968,9 @@ diesel::joinable!(price_component -> billable_metric (billable_metric_id));
 diesel::joinable!(price_component -> plan_version (plan_version_id));
 diesel::joinable!(price_component -> product (product_item_id));
 diesel::joinable!(product -> product_family (product_family_id));
+diesel::joinable!(promotion_code -> coupon (coupon_id));
+diesel::joinable!(promotion_code -> customer (customer_id));
+diesel::joinable!(promotion_code -> tenant (tenant_id));
 diesel::joinable!(product -> tenant (tenant_id));
 diesel::joinable!(product_family -> tenant (tenant_id));
 diesel::joinable!(schedule -> plan_version (plan_version_id));
@@ -795,11 +981,20 @@ diesel::joinable!(subscription -> plan_version (plan_version_id));
 diesel::joinable!(subscription -> tenant (tenant_id));
 diesel::joinable!(subscription_add_on -> add_on (add_on_id));
 diesel::joinable!(subscription_add_on -> subscription (subscription_id));
+diesel::joinable!(subscription_commitment -> credit_grant (credit_grant_id));
+diesel::joinable!(subscription_commitment -> invoice (invoice_id));
+diesel::joinable!(subscription_commitment -> subscription (subscription_id));
+diesel::joinable!(subscription_commitment -> tenant (tenant_id));
+diesel::joinable!(subscription_commitment -> user (created_by));
 diesel::joinable!(subscription_component -> price_component (price_component_id));
 diesel::joinable!(subscription_component -> product (product_item_id));
 diesel::joinable!(subscription_component -> subscription (subscription_id));
 diesel::joinable!(subscription_event -> bi_mrr_movement_log (bi_mrr_movement_log_id));
 diesel::joinable!(subscription_event -> subscription (subscription_id));
+diesel::joinable!(subscription_plan_change -> plan_version (plan_version_id));
+diesel::joinable!(subscription_plan_change -> subscription (subscription_id));
+diesel::joinable!(subscription_plan_change -> tenant (tenant_id));
+diesel::joinable!(tax_rate -> tenant (tenant_id));
 diesel::joinable!(tenant -> organization (organization_id));
 diesel::joinable!(webhook_in_event -> provider_config (provider_config_id));
 diesel::joinable!(webhook_out_endpoint -> tenant (tenant_id));
@@ -815,14 +1010,17 @@ diesel::allow_tables_to_appear_in_same_query!(
     bi_revenue_daily,
     billable_metric,
     coupon,
+    credit_grant,
     credit_note,
     customer,
     customer_balance_pending_tx,
     customer_balance_tx,
+    dunning_config,
     fang_tasks,
     fang_tasks_archive,
     historical_rates_from_usd,
     invoice,
+    invoice_dunning,
     invoicing_entity,
     organization,
     organization_member,
@@ -831,14 +1029,18 @@ diesel::allow_tables_to_appear_in_same_query!(
     plan_version,
     price_component,
     product,
+    promotion_code,
     product_family,
     provider_config,
     schedule,
     slot_transaction,
     subscription,
     subscription_add_on,
+    subscription_commitment,
     subscription_component,
     subscription_event,
+    subscription_plan_change,
+    tax_rate,
     tenant,
     user,
     webhook_in_event,
@@ -0,0 +1,39 @@
+use crate::enums::SubscriptionCommitmentTypeEnum;
+use chrono::{NaiveDate, NaiveDateTime};
+use diesel::{Identifiable, Insertable, Queryable, Selectable};
+use uuid::Uuid;
+
+#[derive(Queryable, Debug, Clone, Identifiable, Selectable)]
+#[diesel(table_name = crate::schema::subscription_commitment)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct SubscriptionCommitmentRow {
+    pub id: Uuid,
+    pub tenant_id: Uuid,
+    pub subscription_id: Uuid,
+    pub commitment_type: SubscriptionCommitmentTypeEnum,
+    pub amount_cents: i32,
+    pub start_date: NaiveDate,
+    pub end_date: NaiveDate,
+    pub credit_grant_id: Uuid,
+    pub invoice_id: Option<Uuid>,
+    pub unused_cents: Option<i32>,
+    pub created_at: NaiveDateTime,
+    pub created_by: Option<Uuid>,
+    pub closed_at: Option<NaiveDateTime>,
+}
+
+#[derive(Debug, Insertable)]
+#[diesel(table_name = crate::schema::subscription_commitment)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct SubscriptionCommitmentRowNew {
+    pub id: Uuid,
+    pub tenant_id: Uuid,
+    pub subscription_id: Uuid,
+    pub commitment_type: SubscriptionCommitmentTypeEnum,
+    pub amount_cents: i32,
+    pub start_date: NaiveDate,
+    pub end_date: NaiveDate,
+    pub credit_grant_id: Uuid,
+    pub invoice_id: Option<Uuid>,
+    pub created_by: Option<Uuid>,
+}
@@ -0,0 +1,33 @@
+use chrono::{NaiveDate, NaiveDateTime};
+use diesel::{Identifiable, Insertable, Queryable, Selectable};
+use uuid::Uuid;
+
+#[derive(Queryable, Debug, Clone, Identifiable, Selectable)]
+#[diesel(table_name = crate::schema::subscription_plan_change)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct SubscriptionPlanChangeRow {
+    pub id: Uuid,
+    pub subscription_id: Uuid,
+    pub tenant_id: Uuid,
+    pub plan_version_id: Uuid,
+    pub components: serde_json::Value,
+    pub mrr_delta: i64,
+    pub effective_date: NaiveDate,
+    pub created_at: NaiveDateTime,
+    pub created_by: Uuid,
+    pub applied_at: Option<NaiveDateTime>,
+}
+
+#[derive(Debug, Insertable)]
+#[diesel(table_name = crate::schema::subscription_plan_change)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct SubscriptionPlanChangeRowNew {
+    pub id: Uuid,
+    pub subscription_id: Uuid,
+    pub tenant_id: Uuid,
+    pub plan_version_id: Uuid,
+    pub components: serde_json::Value,
+    pub mrr_delta: i64,
+    pub effective_date: NaiveDate,
+    pub created_by: Uuid,
+}
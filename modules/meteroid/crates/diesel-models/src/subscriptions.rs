@@ -32,6 +32,12 @@ pub struct SubscriptionRow {
     pub currency: String,
     pub mrr_cents: i64,
     pub period: BillingPeriodEnum,
+    pub trial_ended_at: Option<NaiveDateTime>,
+    pub paused_at: Option<NaiveDateTime>,
+    pub resume_at: Option<NaiveDate>,
+    pub minimum_spend: Option<Decimal>,
+    pub spend_cap: Option<Decimal>,
+    pub elapsed_billing_cycles: i32,
 }
 
 #[derive(Insertable, Debug)]
@@ -53,6 +59,8 @@ pub struct SubscriptionRowNew {
     pub currency: String,
     pub mrr_cents: i64,
     pub period: BillingPeriodEnum,
+    pub minimum_spend: Option<Decimal>,
+    pub spend_cap: Option<Decimal>,
 }
 
 pub struct CancelSubscriptionParams {
@@ -77,6 +85,9 @@ pub struct SubscriptionForDisplayRow {
     #[diesel(select_expression = plan_version::version)]
     #[diesel(select_expression_type = plan_version::version)]
     pub version: i32,
+    #[diesel(select_expression = plan_version::billing_cycles)]
+    #[diesel(select_expression_type = plan_version::billing_cycles)]
+    pub billing_cycles: Option<i32>,
     #[diesel(select_expression = plan::name)]
     #[diesel(select_expression_type = plan::name)]
     pub plan_name: String,
@@ -125,6 +136,7 @@ mod subscription_invoice_candidate {
         pub activated_at: Option<NaiveDateTime>,
         pub canceled_at: Option<NaiveDateTime>,
         pub period: BillingPeriodEnum,
+        pub elapsed_billing_cycles: i32,
     }
 
     #[derive(Debug, Queryable, Selectable)]
@@ -135,6 +147,7 @@ mod subscription_invoice_candidate {
         pub currency: String,
         pub net_terms: i32,
         pub version: i32,
+        pub billing_cycles: Option<i32>,
         #[diesel(select_expression = crate::schema::plan::name)]
         #[diesel(select_expression_type = crate::schema::plan::name)]
         pub plan_name: String,
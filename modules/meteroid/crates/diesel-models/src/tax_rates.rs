@@ -0,0 +1,30 @@
+use chrono::NaiveDateTime;
+use diesel::{Identifiable, Insertable, Queryable, Selectable};
+use rust_decimal::Decimal;
+use uuid::Uuid;
+
+#[derive(Queryable, Debug, Clone, Identifiable, Selectable)]
+#[diesel(table_name = crate::schema::tax_rate)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct TaxRateRow {
+    pub id: Uuid,
+    pub tenant_id: Uuid,
+    pub name: String,
+    pub country: String,
+    pub region: Option<String>,
+    pub rate: Decimal,
+    pub created_at: NaiveDateTime,
+    pub archived_at: Option<NaiveDateTime>,
+}
+
+#[derive(Debug, Insertable)]
+#[diesel(table_name = crate::schema::tax_rate)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct TaxRateRowNew {
+    pub id: Uuid,
+    pub tenant_id: Uuid,
+    pub name: String,
+    pub country: String,
+    pub region: Option<String>,
+    pub rate: Decimal,
+}
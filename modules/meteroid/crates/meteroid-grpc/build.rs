@@ -17,6 +17,7 @@ fn generate_grpc_types(root: &Path) -> Result<(), BuildError> {
         "billablemetrics",
         "customers",
         "coupons",
+        "creditnotes",
         "instance",
         "invoices",
         "invoicingentities",
@@ -28,6 +29,7 @@ fn generate_grpc_types(root: &Path) -> Result<(), BuildError> {
         "schedules",
         "stats",
         "subscriptions",
+        "taxes",
         "tenants",
         "users",
         "webhooksout",
@@ -48,6 +48,12 @@ pub mod meteroid {
             }
         }
 
+        pub mod creditnotes {
+            pub mod v1 {
+                tonic::include_proto!("meteroid.api.creditnotes.v1");
+            }
+        }
+
         pub mod instance {
             pub mod v1 {
                 tonic::include_proto!("meteroid.api.instance.v1");
@@ -114,6 +120,12 @@ pub mod meteroid {
             }
         }
 
+        pub mod taxes {
+            pub mod v1 {
+                tonic::include_proto!("meteroid.api.taxes.v1");
+            }
+        }
+
         pub mod tenants {
             pub mod v1 {
                 include_proto_serde!("meteroid.api.tenants.v1");
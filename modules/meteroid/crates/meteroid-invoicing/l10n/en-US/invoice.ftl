@@ -12,9 +12,20 @@ quantity = Quantity
 unit-price = Unit Price
 tax-rate = Tax Rate
 tax = Tax
+tax-base = Taxable amount:
 amount = Amount
 subtotal = Subtotal
+discount = Discount
 total-due = Total Due
 legal-info = Legal Information
 vat-exempt-legal = Tax not applicable
+vat-reverse-charge-legal = Reverse charge - VAT to be accounted for by the recipient (art. 196 of the VAT Directive 2006/112/EC)
 exchange-rate-info = Exchange rate on {$date}:  {$equality} | Total amount converted = {$amount_converted}
+credit-note-title = Credit Note
+credit-note-number = Credit Note n°{$credit_note_number}
+credited-invoice = Credited invoice:
+credit-note-reason = Reason:
+credit-note-lines = Credited Lines
+total-credited = Total Credited
+credit-note-refunded = This amount has been refunded.
+credit-note-credited = This amount has been credited to your customer balance, and will be deducted from your next invoices.
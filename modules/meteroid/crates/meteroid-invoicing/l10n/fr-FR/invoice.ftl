@@ -12,10 +12,21 @@ quantity = Quantité
 unit-price = Prix unitaire HT
 tax-rate = Taux de TVA
 tax = TVA
+tax-base = Base HT :
 amount = Total HT
 subtotal = Sous-total
+discount = Remise
 total-due = Total dû
 legal-info = Informations légales
 vat-exempt-legal = TVA non applicable - art. 259-1 du CGI
+vat-reverse-charge-legal = Autoliquidation - TVA due par le preneur (art. 283-2 du CGI, art. 196 de la directive 2006/112/CE)
 exchange-rate-info = Taux de change au {$date}:  {$equality} | Montant total converti = {$amount_converted}
 
+credit-note-title = Avoir
+credit-note-number = Avoir n°{$credit_note_number}
+credited-invoice = Facture d'origine :
+credit-note-reason = Motif :
+credit-note-lines = Lignes créditées
+total-credited = Total crédité
+credit-note-refunded = Ce montant vous a été remboursé.
+credit-note-credited = Ce montant a été crédité sur votre solde client, et sera déduit de vos prochaines factures.
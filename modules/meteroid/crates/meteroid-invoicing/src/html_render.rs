@@ -2,9 +2,9 @@ use crate::errors::InvoicingError;
 use crate::model::*;
 use chrono::prelude::*;
 use chrono::NaiveDate;
+use common_domain::money::{Currency, Money, Rounding};
 use maud::{html, Markup, DOCTYPE};
 use rust_decimal::Decimal;
-use rusty_money::{iso, FormattableCurrency};
 
 #[allow(clippy::all)]
 mod l10n {
@@ -27,43 +27,30 @@ mod l10n {
 
 static CSS: &str = include_str!("../assets/styles.css");
 
-pub fn render_invoice(invoice: &Invoice) -> Result<Markup, InvoicingError> {
+fn resolve_lang(lang: &str) -> Result<&'static str, InvoicingError> {
     let lang_id =
-        unic_langid::parser::parse_language_identifier(invoice.lang.as_bytes()).map_err(|_| {
-            InvoicingError::I18nError(format!("Invalid language identifier : {}", &invoice.lang))
+        unic_langid::parser::parse_language_identifier(lang.as_bytes()).map_err(|_| {
+            InvoicingError::I18nError(format!("Invalid language identifier : {}", lang))
         })?;
 
-    let lang = match lang_id.language.as_str() {
+    Ok(match lang_id.language.as_str() {
         "fr" => "fr-FR",
         _ => "en-US",
-    };
+    })
+}
+
+pub fn render_invoice(invoice: &Invoice) -> Result<Markup, InvoicingError> {
+    let lang = resolve_lang(&invoice.lang)?;
 
     Ok(html! {
         (DOCTYPE)
         html lang="en" {
-            head {
-                meta charset="UTF-8";
-                meta name="viewport" content="width=device-width, initial-scale=1.0";
-                title { (l10n::invoice::invoice_title(lang) )};
-                link href="https://fonts.googleapis.com/css2?family=Inter:wght@400;500;600;700&display=swap" rel="stylesheet"; // TODO include it in docker
-                style {
-                    (CSS)
-                    r#"
-                    body {
-                        font-family: 'Inter', sans-serif;
-                        font-optical-sizing: auto;
-                        font-style: normal;
-                    }
-                    "#
-
-                }
-
-            }
+            (render_head(&l10n::invoice::invoice_title(lang)))
             body class="" {
                 div class="container mx-auto px-2 py-4 bg-white text-sm" {
                     (render_header(lang, &invoice.organization, &invoice.metadata)?)
                     (render_billing_info(lang, &invoice.organization, &invoice.customer, &invoice.metadata)?)
-                    (render_invoice_lines(lang, &invoice.lines, &invoice.metadata.currency)?)
+                    (render_invoice_lines(lang, &l10n::invoice::invoice_lines(lang).to_string(), &invoice.lines, &invoice.metadata.currency)?)
                     (render_invoice_summary(lang, &invoice.metadata ))
                     (render_legal_info(lang, &invoice.organization, &invoice.metadata)?)
                 }
@@ -72,6 +59,49 @@ pub fn render_invoice(invoice: &Invoice) -> Result<Markup, InvoicingError> {
     })
 }
 
+pub fn render_credit_note(credit_note: &CreditNote) -> Result<Markup, InvoicingError> {
+    let lang = resolve_lang(&credit_note.lang)?;
+
+    Ok(html! {
+        (DOCTYPE)
+        html lang="en" {
+            (render_head(&l10n::invoice::credit_note_title(lang)))
+            body class="" {
+                div class="container mx-auto px-2 py-4 bg-white text-sm" {
+                    (render_credit_note_header(lang, &credit_note.organization, &credit_note.metadata)?)
+                    (render_credit_note_billing_info(lang, &credit_note.organization, &credit_note.customer, &credit_note.metadata)?)
+                    (render_invoice_lines(lang, &l10n::invoice::credit_note_lines(lang).to_string(), &credit_note.lines, &credit_note.metadata.currency)?)
+                    (render_credit_note_summary(lang, &credit_note.metadata))
+                    (render_credit_note_legal_info(lang, &credit_note.organization, &credit_note.metadata))
+                }
+            }
+        }
+    })
+}
+
+fn render_head(title: &str) -> Markup {
+    html! {
+        head {
+            meta charset="UTF-8";
+            meta name="viewport" content="width=device-width, initial-scale=1.0";
+            title { (title) };
+            link href="https://fonts.googleapis.com/css2?family=Inter:wght@400;500;600;700&display=swap" rel="stylesheet"; // TODO include it in docker
+            style {
+                (CSS)
+                r#"
+                body {
+                    font-family: 'Inter', sans-serif;
+                    font-optical-sizing: auto;
+                    font-style: normal;
+                }
+                "#
+
+            }
+
+        }
+    }
+}
+
 fn render_header(
     lang: &str,
     organization: &Organization,
@@ -129,12 +159,13 @@ fn render_billing_info(
 
 fn render_invoice_lines(
     lang: &str,
+    title: &str,
     lines: &[InvoiceLine],
-    currency: &iso::Currency,
+    currency: &Currency,
 ) -> Result<Markup, InvoicingError> {
     Ok(html! {
         div class="mb-8" {
-            h2 class="px-2 text-md font-semibold mb-4 text-gray-700 uppercase" { (l10n::invoice::invoice_lines(lang)) }
+            h2 class="px-2 text-md font-semibold mb-4 text-gray-700 uppercase" { (title) }
             table class="w-full border-collapse" {
                 thead {
                     tr class="text-gray-500 text-sm" {
@@ -142,6 +173,7 @@ fn render_invoice_lines(
                         th class="p-2 text-right" { (l10n::invoice::quantity(lang)) }
                         th class="p-2 text-right" { (l10n::invoice::unit_price(lang)) }
                         th class="p-2 text-right" { (l10n::invoice::tax_rate(lang)) }
+                        th class="p-2 text-right" { (l10n::invoice::tax(lang)) }
                         th class="p-2 text-right" { (l10n::invoice::amount(lang)) }
                     }
                 }
@@ -172,13 +204,18 @@ fn render_invoice_lines(
                                     (format_percentage_dec(vat_rate))
                                 }
                             }
+                            td class="p-2 text-right text-gray-600" {
+                                  @if let Some(vat_amount) = line.vat_amount {
+                                    (format_currency_minor(vat_amount, currency))
+                                }
+                            }
                             td class="p-2 text-right font-medium text-gray-800" {
                                 (format_currency_minor(line.subtotal, currency))
                             }
                         }
                         @if !line.sub_lines.is_empty() {
                             tr class="bg-gray-50" {
-                                td colspan="4" class="p-2" {
+                                td colspan="6" class="p-2" {
                                     table class="w-full text-sm" {
                                         @for sub_line in &line.sub_lines {
                                             tr {
@@ -209,9 +246,31 @@ fn render_invoice_summary(lang: &str, invoice: &InvoiceMetadata) -> Markup {
                         td class="p-2" { (l10n::invoice::subtotal(lang)) }
                         td class="p-2 text-right font-medium" { (format_currency_minor(invoice.subtotal, &invoice.currency)) }
                     }
-                    tr {
-                        td class="p-2 text-gray-600" { (l10n::invoice::tax(lang)) " " (format_percentage_minor(invoice.tax_rate)) }
-                        td class="p-2 text-right font-medium text-gray-800" { (format_currency_minor(invoice.tax_amount, &invoice.currency)) }
+                    @for coupon in &invoice.coupons {
+                        tr {
+                            td class="p-2 text-gray-600" { (l10n::invoice::discount(lang)) " (" (coupon.code) ")" }
+                            td class="p-2 text-right font-medium text-gray-800" { "-" (format_currency_minor(coupon.total, &invoice.currency)) }
+                        }
+                    }
+                    @if invoice.tax_breakdown.is_empty() {
+                        tr {
+                            td class="p-2 text-gray-600" { (l10n::invoice::tax(lang)) }
+                            td class="p-2 text-right font-medium text-gray-800" { (format_currency_minor(invoice.tax_amount, &invoice.currency)) }
+                        }
+                    }
+                    @for tax in &invoice.tax_breakdown {
+                        tr {
+                            td class="p-2 text-gray-600" {
+                                @if let Some(name) = &tax.name {
+                                    (name)
+                                } @else {
+                                    (l10n::invoice::tax(lang))
+                                }
+                                " " (format_percentage_dec(tax.rate))
+                                div class="text-xs" { (l10n::invoice::tax_base(lang)) " " (format_currency_minor(tax.taxable_amount, &invoice.currency)) }
+                            }
+                            td class="p-2 text-right font-medium text-gray-800" { (format_currency_minor(tax.tax_amount, &invoice.currency)) }
+                        }
                     }
                     tr class="border-t border-gray-200" {
                         td class="p-2 text-lg font-semibold text-gray-700" { (l10n::invoice::total_due(lang)) }
@@ -238,10 +297,13 @@ fn render_legal_info(
                 rate,
                 organization.accounting_currency.code()
             );
-            let amount_converted = format_currency_dec(
-                Decimal::from(invoice.total_amount) * rate,
-                &organization.accounting_currency,
-            );
+            let amount_converted = Money::from_major(
+                invoice.currency.to_major(invoice.total_amount) * rate,
+                organization.accounting_currency,
+                Rounding::HalfAwayFromZero,
+            )
+            .map(|amount| amount.to_string())
+            .unwrap_or_default();
 
             l10n::invoice::exchange_rate_info(lang, &equality, &amount_converted, &date).ok()
         }
@@ -252,8 +314,9 @@ fn render_legal_info(
         div class="px-2 mb-8 text-gray-700" {
             h2 class="text-md font-semibold mb-4 text-gray-700 uppercase" { (l10n::invoice::legal_info(lang)) }
 
-            // TODO need proper tax info engine for other EU countries
-            @if invoice.tax_rate == 0 {
+            @if invoice.tax_breakdown.iter().any(|x| x.treatment == TaxTreatment::ReverseCharge) {
+                p { (l10n::invoice::vat_reverse_charge_legal(lang)) }
+            } @else if invoice.tax_amount == 0 {
                 p { (l10n::invoice::vat_exempt_legal(lang)) }
             }
             @if let Some(footer_info) = &organization.footer_info {
@@ -273,12 +336,125 @@ fn render_legal_info(
     })
 }
 
-fn format_currency_dec(amount: Decimal, currency: &iso::Currency) -> String {
-    rusty_money::Money::from_decimal(amount, currency).to_string() // TODO improve i18n format (currency after for FR, dot or coma, ..)
+fn render_credit_note_header(
+    lang: &str,
+    organization: &Organization,
+    credit_note: &CreditNoteMetadata,
+) -> Result<Markup, InvoicingError> {
+    Ok(html! {
+        div class="px-2 flex justify-between items-center border-b pb-4" {
+            h1 class="text-xl font-semibold text-gray-800" { (l10n::invoice::credit_note_number(lang, &credit_note.number)
+                        .map_err(|_| InvoicingError::I18nError(format!("Failed to localise credit note number for value: {}", &credit_note.number)))? )
+            }
+             @if let Some(logo_url) = &organization.logo_url {
+                img src=(logo_url) alt=(l10n::invoice::company_logo_alt(lang));
+            }
+        }
+    })
 }
 
-fn format_currency_minor(amount: i64, currency: &iso::Currency) -> String {
-    rusty_money::Money::from_minor(amount, currency).to_string()
+fn render_credit_note_billing_info(
+    lang: &str,
+    organization: &Organization,
+    customer: &Customer,
+    credit_note: &CreditNoteMetadata,
+) -> Result<Markup, InvoicingError> {
+    Ok(html! {
+        div class="grid grid-cols-3 mb-8" {
+            div class="flex flex-col p-4 border-b border-r border-gray-200" {
+                h2 class="text-md mb-2 text-gray-700" { (l10n::invoice::bill_from(lang)) }
+                (render_address( organization, lang))
+            }
+            div class="flex flex-col p-4 border-b border-gray-200" {
+                h2 class="text-md mb-2 text-gray-700" { (l10n::invoice::bill_to(lang)) }
+                 (render_address( customer, lang))
+            }
+            div class="p-4 border-b border-l border-gray-200" {
+                h2 class="text-right text-md mb-2 text-gray-700" { (l10n::invoice::total_credited(lang)) }
+                p class="text-right mb-2 text-xl font-bold text-green-600" { (format_currency_minor(credit_note.total_amount, &credit_note.currency)) }
+
+                div class="grid grid-cols-2 text-xs " {
+                  div {
+                    p class="text-gray-600" { (l10n::invoice::issue_date(lang)) }
+                    p class="font-medium" { (format_date(lang, &credit_note.issue_date)?) }
+                  }
+                  div{
+                    p class="text-gray-600" { (l10n::invoice::credited_invoice(lang)) }
+                    p class="font-medium" { (credit_note.invoice_number) }
+                  }
+                }
+            }
+        }
+        @if let Some(reason) = &credit_note.reason {
+                p class="mb-4 px-2" { span class="text-gray-600" { (l10n::invoice::credit_note_reason(lang)) } " " (reason) }
+        }
+        @if let Some(memo) = &credit_note.memo {
+                p class="mb-4 rounded-lg p-4 bg-gray-50" { (memo) }
+        }
+    })
+}
+
+fn render_credit_note_summary(lang: &str, credit_note: &CreditNoteMetadata) -> Markup {
+    html! {
+        div class="grid grid-cols-2 border-b border-gray-200 mb-8" {
+            div {}
+            div class="mb-4 rounded-lg p-4 bg-gray-50" {
+                table class="w-full" {
+                    tr class="font-semibold"  {
+                        td class="p-2" { (l10n::invoice::subtotal(lang)) }
+                        td class="p-2 text-right font-medium" { (format_currency_minor(credit_note.subtotal, &credit_note.currency)) }
+                    }
+                    tr {
+                        td class="p-2 text-gray-600" { (l10n::invoice::tax(lang)) }
+                        td class="p-2 text-right font-medium text-gray-800" { (format_currency_minor(credit_note.tax_amount, &credit_note.currency)) }
+                    }
+                    tr class="border-t border-gray-200" {
+                        td class="p-2 text-lg font-semibold text-gray-700" { (l10n::invoice::total_credited(lang)) }
+                        td class="p-2 text-right text-lg font-bold text-green-600" { (format_currency_minor(credit_note.total_amount, &credit_note.currency)) }
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn render_credit_note_legal_info(
+    lang: &str,
+    organization: &Organization,
+    credit_note: &CreditNoteMetadata,
+) -> Markup {
+    html! {
+        div class="px-2 mb-8 text-gray-700" {
+            h2 class="text-md font-semibold mb-4 text-gray-700 uppercase" { (l10n::invoice::legal_info(lang)) }
+
+            @if credit_note.refunded {
+                p { (l10n::invoice::credit_note_refunded(lang)) }
+            } @else {
+                p { (l10n::invoice::credit_note_credited(lang)) }
+            }
+            @if let Some(footer_info) = &organization.footer_info {
+                p { (footer_info) }
+            }
+            @if let Some(footer_legal) = &organization.footer_legal  {
+                p { (footer_legal) }
+            }
+        }
+    }
+}
+
+// TODO improve i18n format (currency after for FR, dot or coma, ..)
+fn format_currency_dec(amount: Decimal, currency: &Currency) -> String {
+    // unit prices can be more precise than the minor unit
+    let mut amount = amount.normalize();
+    if amount.scale() < currency.exponent() as u32 {
+        amount.rescale(currency.exponent() as u32);
+    }
+    format!("{} {}", amount, currency)
+}
+
+// the amounts are stored in minor units of the ISO 4217 exponent of the currency
+fn format_currency_minor(amount: i64, currency: &Currency) -> String {
+    Money::from_minor(amount, *currency).to_string()
 }
 
 fn format_quantity(quantity: Decimal) -> String {
@@ -288,12 +464,6 @@ fn format_quantity(quantity: Decimal) -> String {
 fn format_percentage_dec(rate: Decimal) -> String {
     format!("{}%", rate.normalize())
 }
-fn format_percentage_minor(rate: i32) -> String {
-    format!(
-        "{}%",
-        (Decimal::from(rate) / Decimal::from(100)).normalize()
-    )
-}
 
 fn format_date(lang: &str, date: &NaiveDate) -> Result<String, InvoicingError> {
     // TODO use icu crate for date formatting when adding new languages, fluent has no date formatting as of now https://github.com/projectfluent/fluent-rs/pull/335
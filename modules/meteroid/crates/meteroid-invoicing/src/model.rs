@@ -1,5 +1,5 @@
+use common_domain::money::Currency;
 use rust_decimal::Decimal;
-use rusty_money::iso;
 
 pub struct Invoice {
     pub lang: String,
@@ -28,7 +28,7 @@ pub struct Organization {
     pub tax_id: Option<String>,
     pub footer_info: Option<String>,
     pub footer_legal: Option<String>,
-    pub accounting_currency: iso::Currency,
+    pub accounting_currency: Currency,
     pub exchange_rate: Option<Decimal>,
 }
 
@@ -45,14 +45,36 @@ pub struct InvoiceMetadata {
     pub issue_date: chrono::NaiveDate,
     pub payment_term: u32,
     pub subtotal: i64,
+    pub coupons: Vec<CouponLine>,
     pub tax_amount: i64,
-    pub tax_rate: i32,
+    pub tax_breakdown: Vec<TaxBreakdownItem>,
     pub total_amount: i64,
-    pub currency: iso::Currency,
+    pub currency: Currency,
     pub due_date: chrono::NaiveDate,
     pub memo: Option<String>,
 }
 
+pub struct CreditNote {
+    pub lang: String,
+    pub organization: Organization,
+    pub customer: Customer,
+    pub metadata: CreditNoteMetadata,
+    pub lines: Vec<InvoiceLine>,
+}
+
+pub struct CreditNoteMetadata {
+    pub number: String,
+    pub issue_date: chrono::NaiveDate,
+    pub invoice_number: String,
+    pub reason: Option<String>,
+    pub subtotal: i64,
+    pub tax_amount: i64,
+    pub total_amount: i64,
+    pub currency: Currency,
+    pub refunded: bool,
+    pub memo: Option<String>,
+}
+
 pub struct InvoiceLine {
     pub name: String,
     pub description: Option<String>,
@@ -61,6 +83,7 @@ pub struct InvoiceLine {
     pub quantity: Option<Decimal>,
     pub unit_price: Option<Decimal>,
     pub vat_rate: Option<Decimal>,
+    pub vat_amount: Option<i64>,
     pub start_date: chrono::NaiveDate,
     pub end_date: chrono::NaiveDate,
     pub sub_lines: Vec<InvoiceSubLine>,
@@ -73,3 +96,24 @@ pub struct InvoiceSubLine {
     pub unit_price: Decimal,
     // pub attributes: Option<SubLineAttributes>,
 }
+
+pub struct CouponLine {
+    pub code: String,
+    pub total: i64,
+}
+
+pub struct TaxBreakdownItem {
+    pub name: Option<String>,
+    pub rate: Decimal,
+    pub treatment: TaxTreatment,
+    pub taxable_amount: i64,
+    pub tax_amount: i64,
+}
+
+#[derive(PartialEq, Eq)]
+pub enum TaxTreatment {
+    Standard,
+    Exempt,
+    ReverseCharge,
+    OutOfScope,
+}
@@ -1,5 +1,6 @@
 use chrono::NaiveDate;
 use std::collections::HashMap;
+use std::sync::Arc;
 use uuid::Uuid;
 
 use crate::compute::errors::ComputeError;
@@ -41,6 +42,32 @@ impl SlotClient for crate::Store {
     }
 }
 
+/// Returns the given slot counts by price component in place of the current ones, to preview an invoice.
+pub struct OverriddenSlotClient {
+    pub inner: Arc<dyn SlotClient + Send + Sync>,
+    pub overrides: HashMap<Uuid, u32>,
+}
+
+#[async_trait::async_trait]
+impl SlotClient for OverriddenSlotClient {
+    async fn fetch_slots(
+        &self,
+        tenant_id: &Uuid,
+        subscription_id: &Uuid,
+        component_id: &Uuid,
+        invoice_date: &NaiveDate,
+    ) -> Result<u32, ComputeError> {
+        match self.overrides.get(component_id) {
+            Some(v) => Ok(*v),
+            None => {
+                self.inner
+                    .fetch_slots(tenant_id, subscription_id, component_id, invoice_date)
+                    .await
+            }
+        }
+    }
+}
+
 pub struct MockSlotClient {
     pub data: HashMap<(Uuid, NaiveDate), u32>,
 }
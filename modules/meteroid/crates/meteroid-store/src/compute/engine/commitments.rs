@@ -0,0 +1,306 @@
+use super::period::{calculate_period_idx, calculate_period_range};
+use super::shared::{adjustment_line, to_minor};
+use crate::compute::errors::ComputeError;
+use crate::domain::{LineItem, Period, SubscriptionDetails};
+use chrono::NaiveDate;
+use common_domain::money::Currency;
+use rust_decimal::Decimal;
+
+/// Computes the spend cap and minimum spend lines of the subscription, for the lines of an invoice.
+/// The usage billed by the invoice (the arrears of the previous period) is capped by a negative line,
+/// then a true-up line makes up for the difference between the invoice subtotal and the minimum spend.
+/// `deduction_lines` are the usage of the period already billed by usage-threshold invoices, deducted from the invoice:
+/// the cap never deducts what these invoices billed.
+pub fn compute_commitment_lines(
+    subscription_details: &SubscriptionDetails,
+    invoice_date: NaiveDate,
+    lines: &[LineItem],
+    deduction_lines: &[LineItem],
+    currency: &Currency,
+) -> Result<Vec<LineItem>, ComputeError> {
+    let billing_start_date = subscription_details.billing_start_date;
+    let billing_day = subscription_details.billing_day as u32;
+    let billing_period = &subscription_details.period;
+
+    let invoice_period_idx = calculate_period_idx(
+        billing_start_date,
+        billing_day,
+        invoice_date,
+        billing_period,
+    );
+
+    let mut commitment_lines = Vec::new();
+
+    if let Some(cap_line) = compute_spend_cap_line(
+        subscription_details,
+        invoice_date,
+        lines,
+        deduction_lines,
+        currency,
+    )? {
+        commitment_lines.push(cap_line);
+    }
+
+    // the last invoice of a plan with billing cycles only bills the arrears of the last period,
+    // the cycles elapsed before a resume being counted
+    let opens_billed_period = subscription_details.billing_cycles.is_none_or(|cycles| {
+        invoice_period_idx + subscription_details.elapsed_billing_cycles < cycles
+    });
+
+    if let Some(minimum_spend) = positive_minor(subscription_details.minimum_spend, currency)? {
+        let subtotal = lines
+            .iter()
+            .chain(&commitment_lines)
+            .map(|l| l.subtotal)
+            .sum::<i64>();
+
+        if subtotal < minimum_spend && opens_billed_period {
+            commitment_lines.push(adjustment_line(
+                "Minimum spend true-up",
+                minimum_spend - subtotal,
+                calculate_period_range(
+                    billing_start_date,
+                    billing_day,
+                    invoice_period_idx,
+                    billing_period,
+                ),
+            ));
+        }
+    }
+
+    Ok(commitment_lines)
+}
+
+/// Computes the negative line capping the usage billed by the lines of an invoice to the spend cap of the subscription.
+/// The usage already billed by usage-threshold invoices (`deduction_lines`) is never deducted by the cap.
+pub fn compute_spend_cap_line(
+    subscription_details: &SubscriptionDetails,
+    invoice_date: NaiveDate,
+    lines: &[LineItem],
+    deduction_lines: &[LineItem],
+    currency: &Currency,
+) -> Result<Option<LineItem>, ComputeError> {
+    let spend_cap = match positive_minor(subscription_details.spend_cap, currency)? {
+        Some(spend_cap) => spend_cap,
+        None => return Ok(None),
+    };
+
+    let usage_lines = lines
+        .iter()
+        .filter(|l| l.metric_id.is_some())
+        .collect::<Vec<_>>();
+    let usage = usage_lines.iter().map(|l| l.subtotal).sum::<i64>();
+    let invoiced = -deduction_lines.iter().map(|l| l.subtotal).sum::<i64>();
+    let capped = spend_cap.max(invoiced);
+
+    if usage <= capped {
+        return Ok(None);
+    }
+
+    let period = Period {
+        start: usage_lines
+            .iter()
+            .map(|l| l.start_date)
+            .min()
+            .unwrap_or(invoice_date),
+        end: usage_lines
+            .iter()
+            .map(|l| l.end_date)
+            .max()
+            .unwrap_or(invoice_date),
+    };
+
+    Ok(Some(adjustment_line("Usage cap", capped - usage, period)))
+}
+
+fn positive_minor(
+    amount: Option<Decimal>,
+    currency: &Currency,
+) -> Result<Option<i64>, ComputeError> {
+    match amount {
+        Some(amount) if amount.is_sign_positive() && !amount.is_zero() => {
+            to_minor(amount, currency).map(Some)
+        }
+        _ => Ok(None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::enums::BillingPeriodEnum;
+    use chrono::NaiveTime;
+    use rust_decimal_macros::dec;
+    use uuid::Uuid;
+
+    fn date(s: &str) -> NaiveDate {
+        s.parse().unwrap()
+    }
+
+    fn subscription(
+        minimum_spend: Option<Decimal>,
+        spend_cap: Option<Decimal>,
+        billing_cycles: Option<i32>,
+    ) -> SubscriptionDetails {
+        let start = date("2024-01-01");
+        SubscriptionDetails {
+            id: Uuid::now_v7(),
+            tenant_id: Uuid::now_v7(),
+            customer_id: Uuid::now_v7(),
+            plan_version_id: Uuid::now_v7(),
+            customer_external_id: None,
+            billing_start_date: start,
+            billing_end_date: None,
+            billing_day: 1,
+            currency: "EUR".to_string(),
+            net_terms: 0,
+            schedules: vec![],
+            price_components: vec![],
+            add_ons: vec![],
+            applied_coupons: vec![],
+            metrics: vec![],
+            mrr_cents: 0,
+            version: 1,
+            billing_cycles,
+            plan_name: "plan".to_string(),
+            plan_id: Uuid::now_v7(),
+            customer_name: "customer".to_string(),
+            canceled_at: None,
+            invoice_memo: None,
+            invoice_threshold: None,
+            created_at: start.and_time(NaiveTime::MIN),
+            cancellation_reason: None,
+            activated_at: Some(start.and_time(NaiveTime::MIN)),
+            created_by: Uuid::now_v7(),
+            trial_start_date: None,
+            period: BillingPeriodEnum::Monthly,
+            paused_at: None,
+            resume_at: None,
+            minimum_spend,
+            spend_cap,
+            elapsed_billing_cycles: 0,
+        }
+    }
+
+    fn line(subtotal: i64, metric_id: Option<Uuid>, start: &str, end: &str) -> LineItem {
+        LineItem {
+            metric_id,
+            ..adjustment_line(
+                "line",
+                subtotal,
+                Period {
+                    start: date(start),
+                    end: date(end),
+                },
+            )
+        }
+    }
+
+    fn usage(subtotal: i64) -> LineItem {
+        line(subtotal, Some(Uuid::now_v7()), "2024-02-01", "2024-03-01")
+    }
+
+    fn rate(subtotal: i64) -> LineItem {
+        line(subtotal, None, "2024-03-01", "2024-04-01")
+    }
+
+    fn compute(
+        subscription: &SubscriptionDetails,
+        invoice_date: &str,
+        lines: &[LineItem],
+        deduction_lines: &[LineItem],
+    ) -> Vec<(String, i64)> {
+        compute_commitment_lines(
+            subscription,
+            date(invoice_date),
+            lines,
+            deduction_lines,
+            &Currency::from_code("EUR").unwrap(),
+        )
+        .unwrap()
+        .into_iter()
+        .map(|l| (l.name, l.subtotal))
+        .collect()
+    }
+
+    #[test]
+    fn test_spend_cap() {
+        let subscription = subscription(None, Some(dec!(5)), None);
+
+        assert_eq!(
+            compute(&subscription, "2024-03-01", &[usage(1000), rate(200)], &[]),
+            vec![("Usage cap".to_string(), -500)]
+        );
+        assert_eq!(
+            compute(&subscription, "2024-03-01", &[usage(400), rate(200)], &[]),
+            vec![]
+        );
+    }
+
+    #[test]
+    fn test_spend_cap_with_threshold_invoices() {
+        let subscription = subscription(None, Some(dec!(5)), None);
+
+        // 300 billed at the threshold, 200 left to bill up to the cap
+        assert_eq!(
+            compute(&subscription, "2024-03-01", &[usage(1000)], &[usage(-300)]),
+            vec![("Usage cap".to_string(), -500)]
+        );
+
+        // 800 billed above the cap, nothing left to bill and nothing credited
+        assert_eq!(
+            compute(&subscription, "2024-03-01", &[usage(1000)], &[usage(-800)]),
+            vec![("Usage cap".to_string(), -200)]
+        );
+        assert_eq!(
+            compute(&subscription, "2024-03-01", &[usage(700)], &[usage(-800)]),
+            vec![]
+        );
+    }
+
+    #[test]
+    fn test_minimum_spend_true_up() {
+        let subscription = subscription(Some(dec!(20)), Some(dec!(5)), None);
+
+        let lines = compute(&subscription, "2024-03-01", &[usage(1000), rate(200)], &[]);
+
+        // the true-up applies to the subtotal after the cap
+        assert_eq!(
+            lines,
+            vec![
+                ("Usage cap".to_string(), -500),
+                ("Minimum spend true-up".to_string(), 1300)
+            ]
+        );
+
+        assert_eq!(
+            compute(&subscription, "2024-03-01", &[rate(2500)], &[]),
+            vec![]
+        );
+    }
+
+    #[test]
+    fn test_last_billing_cycle() {
+        let subscription = subscription(Some(dec!(20)), None, Some(3));
+
+        // the third and last period starts on the 1st of march
+        assert_eq!(
+            compute(&subscription, "2024-03-01", &[rate(200)], &[]),
+            vec![("Minimum spend true-up".to_string(), 1800)]
+        );
+
+        // the invoice closing the last cycle only bills the arrears, without true-up
+        assert_eq!(
+            compute(&subscription, "2024-04-01", &[usage(200)], &[]),
+            vec![]
+        );
+
+        // a cycle elapsed before a pause counts
+        let resumed = SubscriptionDetails {
+            billing_start_date: date("2024-02-01"),
+            elapsed_billing_cycles: 1,
+            ..subscription
+        };
+        assert_eq!(compute(&resumed, "2024-04-01", &[usage(200)], &[]), vec![]);
+    }
+}
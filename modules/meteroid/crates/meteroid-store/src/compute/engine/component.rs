@@ -12,8 +12,8 @@ use crate::utils::local_id::LocalId;
 
 use crate::compute::clients::slots::SlotClient;
 use crate::compute::clients::usage::{GroupedUsageData, UsageData};
-use crate::compute::engine::shared::{only_positive, only_positive_decimal};
-use crate::utils::decimals::ToSubunit;
+use crate::compute::engine::shared::{only_positive, only_positive_decimal, to_minor};
+use common_domain::money::Currency;
 
 use super::super::clients::usage::UsageClient;
 use super::super::errors::ComputeError;
@@ -25,8 +25,6 @@ pub struct ComponentEngine {
     subscription_details: Arc<SubscriptionDetails>,
 }
 
-// TODO we can't really use "to_cents" or similar. The minimum unit depends on the currency.
-
 impl ComponentEngine {
     pub fn new(
         usage_client: Arc<dyn UsageClient + Send + Sync>,
@@ -45,7 +43,7 @@ impl ComponentEngine {
         component: &T,
         periods: ComponentPeriods,
         invoice_date: &NaiveDate,
-        precision: u8,
+        currency: &Currency,
     ) -> Result<Vec<LineItem>, ComputeError> {
         let fixed_period = periods.advance;
         let is_first_period = periods.arrear.is_none();
@@ -59,7 +57,7 @@ impl ComponentEngine {
                     &dec!(1),
                     fixed_period,
                     periods.proration_factor,
-                    precision,
+                    currency,
                 )?);
             }
             SubscriptionFee::OneTime { rate, quantity } => {
@@ -70,7 +68,7 @@ impl ComponentEngine {
                         &Decimal::from(*quantity),
                         fixed_period,
                         periods.proration_factor,
-                        precision,
+                        currency,
                     )?);
                 }
             }
@@ -85,7 +83,7 @@ impl ComponentEngine {
                         &Decimal::from(*quantity),
                         fixed_period,
                         periods.proration_factor,
-                        precision,
+                        currency,
                     )?);
                 }
                 BillingType::Arrears => {
@@ -95,7 +93,7 @@ impl ComponentEngine {
                             &Decimal::from(*quantity),
                             arrears,
                             periods.proration_factor,
-                            precision,
+                            currency,
                         )?);
                     }
                 }
@@ -122,7 +120,7 @@ impl ComponentEngine {
                     &Decimal::from(slots),
                     fixed_period,
                     periods.proration_factor,
-                    precision,
+                    currency,
                 )?);
             }
             SubscriptionFee::Capacity {
@@ -138,7 +136,7 @@ impl ComponentEngine {
                     &dec!(1),
                     fixed_period,
                     None, // no proration on capacity, as it provides a fixed amount
-                    precision,
+                    currency,
                 )?);
 
                 if let Some(arrear_period) = periods.arrear {
@@ -151,10 +149,10 @@ impl ComponentEngine {
                         let overage_units = usage - Decimal::from(*included);
 
                         if overage_units > Decimal::ZERO {
-                            let overage_price = overage_rate
-                                .to_subunit_opt(precision)
-                                .ok_or(ComputeError::ConversionError)?;
-                            let overage_total = overage_price * overage_units.to_i64().unwrap_or(0);
+                            let overage_total = to_minor(
+                                overage_rate * Decimal::from(overage_units.to_i64().unwrap_or(0)),
+                                currency,
+                            )?;
 
                             let overage_line = InvoiceLineInner {
                                 quantity: None,
@@ -207,11 +205,7 @@ impl ComponentEngine {
 
                                 let price_total = rate.per_unit_price * quantity;
 
-                                let price_cents = only_positive(
-                                    price_total
-                                        .to_subunit_opt(precision)
-                                        .ok_or(ComputeError::ConversionError)?,
-                                );
+                                let price_cents = only_positive(to_minor(price_total, currency)?);
 
                                 if price_cents > 0 {
                                     // we concat rate.dimension1.value and rate.dimension2.value (if defined), separed by a coma. No coma if rate.dimension2 is None
@@ -258,19 +252,31 @@ impl ComponentEngine {
 
                             match model {
                                 UsagePricingModel::PerUnit { rate } => {
-                                    lines.push(InvoiceLineInner::simple(
-                                        rate,
-                                        &usage_units,
-                                        arrear_period,
-                                        precision,
-                                    )?);
+                                    let metric = self.metric(metric_id)?;
+
+                                    if metric.has_unit_conversion() {
+                                        lines.push(fees::compute_converted_per_unit_price(
+                                            usage_units,
+                                            rate,
+                                            &metric.name,
+                                            arrear_period,
+                                            currency,
+                                        )?);
+                                    } else {
+                                        lines.push(InvoiceLineInner::simple(
+                                            rate,
+                                            &usage_units,
+                                            arrear_period,
+                                            currency,
+                                        )?);
+                                    }
                                 }
                                 UsagePricingModel::Tiered { tiers, block_size } => {
                                     lines.push(fees::compute_tier_price(
                                         usage_units,
                                         tiers,
                                         arrear_period,
-                                        precision,
+                                        currency,
                                         block_size,
                                     )?);
                                 }
@@ -279,7 +285,7 @@ impl ComponentEngine {
                                         usage_units,
                                         tiers,
                                         arrear_period,
-                                        precision,
+                                        currency,
                                         block_size,
                                     )?);
                                 }
@@ -295,9 +301,7 @@ impl ComponentEngine {
                                         vec![SubLineItem {
                                             local_id: LocalId::no_prefix(),
                                             name: "Package".to_string(),
-                                            total: price_total
-                                                .to_subunit_opt(precision)
-                                                .ok_or(ComputeError::ConversionError)?,
+                                            total: to_minor(price_total, currency)?,
                                             quantity: total_packages,
                                             unit_price: *rate,
                                             attributes: Some(SubLineAttributes::Package {
@@ -333,23 +337,30 @@ impl ComponentEngine {
                 price_component_id: component.price_component_id(),
                 product_id: component.product_item_id(),
                 metric_id: component.fee_ref().metric_id(),
+                add_on_id: component.add_on_id(),
                 subtotal: line.total as i64, // TODO
                 description: None,
+                tax_rate: Decimal::ZERO,
+                tax_amount: 0,
+                is_manual: false,
             })
             .collect())
     }
 
+    fn metric(&self, metric_id: &Uuid) -> Result<&BillableMetric, ComputeError> {
+        self.subscription_details
+            .metrics
+            .iter()
+            .find(|metric| &metric.id == metric_id)
+            .ok_or(ComputeError::MetricNotFound)
+    }
+
     async fn fetch_usage(
         &self,
         period: Period,
         metric_id: Uuid,
     ) -> Result<UsageData, ComputeError> {
-        let metric = self
-            .subscription_details
-            .metrics
-            .iter()
-            .find(|metric| metric.id == metric_id)
-            .ok_or(ComputeError::MetricNotFound)?;
+        let metric = self.metric(&metric_id)?;
 
         let usage = self
             .usage_client
@@ -362,24 +373,26 @@ impl ComponentEngine {
             )
             .await?;
 
-        if let Some(factor) = metric.unit_conversion_factor {
-            if factor == 0 {
-                return Ok(usage);
-            }
-            let usage = UsageData {
-                period: usage.period,
-                data: usage
-                    .data
-                    .iter()
-                    .map(|usage| GroupedUsageData {
-                        value: usage.value / Decimal::from_i32(factor).unwrap_or(dec!(1)),
-                        dimensions: usage.dimensions.clone(),
-                    })
-                    .collect(),
-            };
+        if !metric.has_unit_conversion() {
             return Ok(usage);
         }
-        Ok(usage)
+
+        // each group is converted and rounded separately, as it is priced separately
+        Ok(UsageData {
+            period: usage.period,
+            data: usage
+                .data
+                .into_iter()
+                .map(|usage| GroupedUsageData {
+                    value: fees::convert_usage_units(
+                        usage.value,
+                        metric.unit_conversion_factor,
+                        metric.unit_conversion_rounding.as_ref(),
+                    ),
+                    dimensions: usage.dimensions,
+                })
+                .collect(),
+        })
     }
 
     async fn fetch_slots(
@@ -417,18 +430,13 @@ impl InvoiceLineInner {
         quantity: &Decimal,
         period: Period,
         proration_factor: Option<f64>,
-        precision: u8,
+        currency: &Currency,
     ) -> Result<InvoiceLineInner, ComputeError> {
         let unit_price_cents = prorate_dec(*rate, proration_factor);
 
         let total = rate * quantity;
 
-        let total_cents = prorate(
-            total
-                .to_subunit_opt(precision)
-                .ok_or(ComputeError::ConversionError)?,
-            proration_factor,
-        );
+        let total_cents = prorate(to_minor(total, currency)?, proration_factor);
 
         Ok(InvoiceLineInner {
             quantity: Some(*quantity),
@@ -445,9 +453,9 @@ impl InvoiceLineInner {
         rate: &Decimal,
         quantity: &Decimal,
         period: Period,
-        precision: u8,
+        currency: &Currency,
     ) -> Result<InvoiceLineInner, ComputeError> {
-        Self::simple_prorated(rate, quantity, period, None, precision)
+        Self::simple_prorated(rate, quantity, period, None, currency)
     }
 
     pub fn from_sublines(
@@ -1,15 +1,75 @@
 use crate::compute::engine::component::InvoiceLineInner;
+use crate::compute::engine::shared::to_minor;
 use crate::compute::ComputeError;
+use crate::domain::enums::UnitConversionRoundingEnum;
 use crate::domain::{Period, SubLineAttributes, SubLineItem, TierRow};
-use crate::utils::decimals::ToSubunit;
 use crate::utils::local_id::LocalId;
-use rust_decimal::Decimal;
+use common_domain::money::Currency;
+use rust_decimal::{Decimal, RoundingStrategy};
+
+/// Converts a raw usage (bytes, milliseconds ...) to the billable unit of the metric (GB, hours ...),
+/// dividing by the conversion factor then rounding.
+pub fn convert_usage_units(
+    raw_usage: Decimal,
+    factor: Option<i32>,
+    rounding: Option<&UnitConversionRoundingEnum>,
+) -> Decimal {
+    let units = match factor {
+        Some(factor) if factor != 0 => raw_usage / Decimal::from(factor),
+        _ => raw_usage,
+    };
+
+    let strategy = RoundingStrategy::MidpointAwayFromZero;
+
+    match rounding {
+        Some(UnitConversionRoundingEnum::Up) => units.ceil(),
+        Some(UnitConversionRoundingEnum::Down) => units.floor(),
+        Some(UnitConversionRoundingEnum::Nearest) => units.round_dp_with_strategy(0, strategy),
+        Some(UnitConversionRoundingEnum::NearestHalf) => {
+            (units * Decimal::TWO).round_dp_with_strategy(0, strategy) / Decimal::TWO
+        }
+        Some(UnitConversionRoundingEnum::NearestDecile) => {
+            units.round_dp_with_strategy(1, strategy)
+        }
+        Some(UnitConversionRoundingEnum::None) | None => units,
+    }
+    .normalize()
+}
+
+/// A per-unit usage price, with a sub-line showing the usage converted to the billable unit of the metric.
+pub fn compute_converted_per_unit_price(
+    usage_units: Decimal,
+    rate: &Decimal,
+    unit_name: &str,
+    period: Period,
+    currency: &Currency,
+) -> Result<InvoiceLineInner, ComputeError> {
+    let total = to_minor(usage_units * rate, currency)?;
+
+    InvoiceLineInner::from_sublines(
+        vec![SubLineItem {
+            local_id: LocalId::no_prefix(),
+            name: unit_name.to_string(),
+            total,
+            quantity: usage_units,
+            unit_price: *rate,
+            attributes: None,
+        }],
+        period,
+        None,
+    )
+    .map(|line| InvoiceLineInner {
+        quantity: Some(usage_units),
+        unit_price: Some(*rate),
+        ..line
+    })
+}
 
 pub fn compute_volume_price(
     usage_units: Decimal,
     tiers: &[TierRow],
     period: Period,
-    precision: u8,
+    currency: &Currency,
     _block_size: &Option<u64>,
 ) -> Result<InvoiceLineInner, ComputeError> {
     let mut applicable_price_per_unit = Decimal::new(0, 0);
@@ -61,18 +121,14 @@ pub fn compute_volume_price(
     Ok(InvoiceLineInner {
         quantity: Some(usage_units),
         unit_price: None,
-        total: price
-            .to_subunit_opt(precision)
-            .ok_or(ComputeError::ConversionError)? as u64,
+        total: to_minor(price, currency)? as u64,
         period,
         custom_line_name: None,
         is_prorated: false,
         sublines: vec![SubLineItem {
             local_id: LocalId::no_prefix(),
             name: "Volume".to_string(),
-            total: price
-                .to_subunit_opt(precision)
-                .ok_or(ComputeError::ConversionError)?,
+            total: to_minor(price, currency)?,
             quantity: usage_units,
             unit_price: applicable_price_per_unit,
             attributes: subline_attr,
@@ -84,7 +140,7 @@ pub fn compute_tier_price(
     usage_units: Decimal,
     tiers: &[TierRow],
     period: Period,
-    precision: u8,
+    currency: &Currency,
     _block_size: &Option<u64>,
 ) -> Result<InvoiceLineInner, ComputeError> {
     let mut subtotal = Decimal::new(0, 0);
@@ -137,9 +193,7 @@ pub fn compute_tier_price(
                     tier.first_unit,
                     last_unit.map(|s| s.to_string()).unwrap_or("∞".to_string())
                 ),
-                total: fee
-                    .to_subunit_opt(precision)
-                    .ok_or(ComputeError::ConversionError)?,
+                total: to_minor(fee, currency)?,
                 quantity: units_in_this_tier,
                 unit_price: tier_price,
                 attributes: Some(SubLineAttributes::Tiered {
@@ -156,12 +210,102 @@ pub fn compute_tier_price(
     Ok(InvoiceLineInner {
         quantity: Some(usage_units),
         unit_price: None,
-        total: subtotal
-            .to_subunit_opt(precision)
-            .ok_or(ComputeError::ConversionError)? as u64,
+        total: to_minor(subtotal, currency)? as u64,
         period,
         custom_line_name: None,
         is_prorated: false,
         sublines: sub_lines,
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+    use rstest::rstest;
+    use rust_decimal_macros::dec;
+
+    #[rstest]
+    #[case(dec!(1_500_000_000), Some(1_000_000_000), None, dec!(1.5))]
+    #[case(dec!(1_500_000_000), Some(1_000_000_000), Some(UnitConversionRoundingEnum::Up), dec!(2))]
+    #[case(dec!(1_500_000_000), Some(1_000_000_000), Some(UnitConversionRoundingEnum::Down), dec!(1))]
+    #[case(dec!(2_500_000), Some(1_000_000), Some(UnitConversionRoundingEnum::Nearest), dec!(3))]
+    #[case(dec!(5_399_000), Some(3_600_000), Some(UnitConversionRoundingEnum::NearestHalf), dec!(1.5))]
+    #[case(dec!(5_000), Some(3_600), Some(UnitConversionRoundingEnum::NearestDecile), dec!(1.4))]
+    #[case(dec!(5_400), Some(3_600), Some(UnitConversionRoundingEnum::None), dec!(1.5))]
+    #[case(dec!(10.2), None, Some(UnitConversionRoundingEnum::Up), dec!(11))]
+    #[case(dec!(10.2), Some(0), None, dec!(10.2))]
+    #[trace]
+    fn test_convert_usage_units(
+        #[case] raw_usage: Decimal,
+        #[case] factor: Option<i32>,
+        #[case] rounding: Option<UnitConversionRoundingEnum>,
+        #[case] expected: Decimal,
+    ) {
+        assert_eq!(
+            convert_usage_units(raw_usage, factor, rounding.as_ref()),
+            expected
+        );
+    }
+
+    #[test]
+    fn test_converted_usage_priced_per_unit() {
+        let eur = Currency::from_code("EUR").unwrap();
+        let period = Period {
+            start: NaiveDate::from_ymd_opt(2024, 1, 1).unwrap(),
+            end: NaiveDate::from_ymd_opt(2024, 2, 1).unwrap(),
+        };
+
+        // 12.3 GB of egress, at 0.09 per GB
+        let units = convert_usage_units(
+            dec!(12_345_678_901),
+            Some(1_000_000_000),
+            Some(&UnitConversionRoundingEnum::NearestDecile),
+        );
+        let line =
+            compute_converted_per_unit_price(units, &dec!(0.09), "Egress (GB)", period, &eur)
+                .unwrap();
+
+        assert_eq!(line.quantity, Some(dec!(12.3)));
+        assert_eq!(line.total, 111);
+        assert_eq!(line.sublines.len(), 1);
+        assert_eq!(line.sublines[0].name, "Egress (GB)");
+        assert_eq!(line.sublines[0].quantity, dec!(12.3));
+        assert_eq!(line.sublines[0].total, 111);
+    }
+
+    #[test]
+    fn test_tier_price_of_converted_usage() {
+        let eur = Currency::from_code("EUR").unwrap();
+        let period = Period {
+            start: NaiveDate::from_ymd_opt(2024, 1, 1).unwrap(),
+            end: NaiveDate::from_ymd_opt(2024, 2, 1).unwrap(),
+        };
+        let tiers = vec![
+            TierRow {
+                first_unit: 0,
+                rate: dec!(0),
+                flat_fee: None,
+                flat_cap: None,
+            },
+            TierRow {
+                first_unit: 10,
+                rate: dec!(2),
+                flat_fee: None,
+                flat_cap: None,
+            },
+        ];
+
+        // 15.2 hours of compute rounded up to 16, the first 10 being free
+        let units = convert_usage_units(
+            dec!(54_720_000),
+            Some(3_600_000),
+            Some(&UnitConversionRoundingEnum::Up),
+        );
+        let line = compute_tier_price(units, &tiers, period, &eur, &None).unwrap();
+
+        assert_eq!(line.quantity, Some(dec!(16)));
+        assert_eq!(line.total, 1200);
+        assert_eq!(line.sublines[1].quantity, dec!(6));
+    }
+}
@@ -1,14 +1,23 @@
+use std::collections::HashMap;
 use std::sync::Arc;
 
-use super::period::calculate_component_period;
+use super::commitments::{compute_commitment_lines, compute_spend_cap_line};
+use super::period::{
+    calculate_billing_cycles_end, calculate_component_closing_period, calculate_component_period,
+    calculate_component_proration_period,
+};
+use super::ramps::compute_ramp_lines;
+use crate::compute::clients::slots::OverriddenSlotClient;
 use crate::compute::engine::component::ComponentEngine;
 use crate::compute::errors::ComputeError;
-use crate::constants::Currency;
 use crate::domain::*;
-use crate::repositories::TenantInterface;
+use crate::repositories::invoices::usage_threshold_deductions;
+use crate::utils::periods::calculate_periods_for_date;
 use crate::Store;
 use chrono::NaiveDate;
+use common_domain::money::Currency;
 use itertools::Itertools;
+use uuid::Uuid;
 
 #[async_trait::async_trait]
 pub trait InvoiceLineInterface {
@@ -17,6 +26,39 @@ pub trait InvoiceLineInterface {
         invoice_date: &NaiveDate,
         subscription_details: &SubscriptionDetails,
     ) -> Result<Vec<LineItem>, ComputeError>;
+
+    /// Computes the lines of the invoice at `invoice_date` as `compute_dated_invoice_lines`,
+    /// with the slot counts of `slots` by price component in place of the current ones.
+    async fn compute_dated_invoice_lines_with_slots(
+        &self,
+        invoice_date: &NaiveDate,
+        subscription_details: &SubscriptionDetails,
+        slots: &HashMap<Uuid, u32>,
+    ) -> Result<Vec<LineItem>, ComputeError>;
+
+    /// Computes the lines of the given fees for the rest of the current period, starting at `change_date`.
+    async fn compute_prorated_lines<T: SubscriptionFeeInterface + Sync>(
+        &self,
+        change_date: &NaiveDate,
+        subscription_details: &SubscriptionDetails,
+        fee_records: &[T],
+    ) -> Result<Vec<LineItem>, ComputeError>;
+
+    /// Computes the usage accrued in the current period of the usage-based fees, up to `date` (excluded).
+    async fn compute_accrued_usage_lines(
+        &self,
+        date: &NaiveDate,
+        subscription_details: &SubscriptionDetails,
+    ) -> Result<Vec<LineItem>, ComputeError>;
+
+    /// Computes the lines closing the current period at `date` (excluded), ex: when the subscription is paused:
+    /// the fees billed in arrears prorated up to `date` and the usage accrued up to `date`, capped by the spend cap.
+    /// The usage already billed by usage-threshold invoices is deducted.
+    async fn compute_closing_lines(
+        &self,
+        date: &NaiveDate,
+        subscription_details: &SubscriptionDetails,
+    ) -> Result<Vec<LineItem>, ComputeError>;
 }
 
 #[async_trait::async_trait]
@@ -28,15 +70,27 @@ impl InvoiceLineInterface for Store {
         &self,
         invoice_date: &NaiveDate,
         subscription_details: &SubscriptionDetails,
+    ) -> Result<Vec<LineItem>, ComputeError> {
+        self.compute_dated_invoice_lines_with_slots(
+            invoice_date,
+            subscription_details,
+            &HashMap::new(),
+        )
+        .await
+    }
+
+    async fn compute_dated_invoice_lines_with_slots(
+        &self,
+        invoice_date: &NaiveDate,
+        subscription_details: &SubscriptionDetails,
+        slots: &HashMap<Uuid, u32>,
     ) -> Result<Vec<LineItem>, ComputeError> {
         if *invoice_date < subscription_details.billing_start_date {
             return Err(ComputeError::InvalidInvoiceDate);
         }
 
-        let currency = self
-            .get_reporting_currency_by_tenant_id(subscription_details.tenant_id)
-            .await
-            .map_err(|_| ComputeError::InternalError)?;
+        let currency = Currency::from_code(&subscription_details.currency)
+            .ok_or(ComputeError::ConversionError)?;
 
         let billing_start_date = subscription_details.billing_start_date;
         let billing_day = subscription_details.billing_day;
@@ -44,7 +98,10 @@ impl InvoiceLineInterface for Store {
 
         let component_engine = ComponentEngine::new(
             self.usage_client.clone(),
-            Arc::new(self.clone()), // TODO just use store
+            Arc::new(OverriddenSlotClient {
+                inner: Arc::new(self.clone()), // TODO just use store
+                overrides: slots.clone(),
+            }),
             Arc::new(subscription_details.clone()),
         );
 
@@ -68,13 +125,280 @@ impl InvoiceLineInterface for Store {
         )
         .await?;
 
-        let invoice_lines = price_components_lines
+        let mut invoice_lines: Vec<LineItem> = price_components_lines
             .into_iter()
             .chain(add_ons_lines)
             .collect();
 
+        let billing_cycles_end = calculate_billing_cycles_end(
+            billing_start_date,
+            billing_day as u32,
+            subscription_details.billing_cycles,
+            subscription_details.elapsed_billing_cycles,
+            &subscription_details.period,
+        );
+
+        if let Some(billing_cycles_end) = billing_cycles_end {
+            if invoice_date > billing_cycles_end {
+                return Ok(vec![]);
+            }
+            // the invoice closing the last cycle only bills its arrears
+            invoice_lines.retain(|l| l.start_date < billing_cycles_end);
+        }
+
+        let ramp_lines = compute_ramp_lines(
+            subscription_details,
+            invoice_date,
+            &invoice_lines,
+            &currency,
+        )?;
+        invoice_lines.extend(ramp_lines);
+
+        let deduction_lines =
+            usage_threshold_deductions(self, subscription_details.id, invoice_date, &invoice_lines)
+                .await
+                .map_err(|_e| ComputeError::InternalError)?;
+
+        let commitment_lines = compute_commitment_lines(
+            subscription_details,
+            invoice_date,
+            &invoice_lines,
+            &deduction_lines,
+            &currency,
+        )?;
+        invoice_lines.extend(deduction_lines);
+        invoice_lines.extend(commitment_lines);
+
         Ok(invoice_lines)
     }
+
+    async fn compute_prorated_lines<T: SubscriptionFeeInterface + Sync>(
+        &self,
+        change_date: &NaiveDate,
+        subscription_details: &SubscriptionDetails,
+        fee_records: &[T],
+    ) -> Result<Vec<LineItem>, ComputeError> {
+        if *change_date < subscription_details.billing_start_date {
+            return Err(ComputeError::InvalidInvoiceDate);
+        }
+
+        let currency = Currency::from_code(&subscription_details.currency)
+            .ok_or(ComputeError::ConversionError)?;
+
+        let component_engine = ComponentEngine::new(
+            self.usage_client.clone(),
+            Arc::new(self.clone()),
+            Arc::new(subscription_details.clone()),
+        );
+
+        let mut lines = Vec::new();
+        for fee in fee_records {
+            let period = calculate_component_proration_period(
+                subscription_details.billing_start_date,
+                subscription_details.billing_day as u32,
+                *change_date,
+                fee.period_ref(),
+            );
+
+            if let Some(period) = period {
+                let fee_lines = component_engine
+                    .compute_component(fee, period, change_date, &currency)
+                    .await?;
+                lines.extend(fee_lines.into_iter().filter(|l| l.total > 0));
+            }
+        }
+
+        Ok(lines)
+    }
+
+    async fn compute_accrued_usage_lines(
+        &self,
+        date: &NaiveDate,
+        subscription_details: &SubscriptionDetails,
+    ) -> Result<Vec<LineItem>, ComputeError> {
+        if *date <= subscription_details.billing_start_date {
+            return Ok(vec![]);
+        }
+
+        let currency = Currency::from_code(&subscription_details.currency)
+            .ok_or(ComputeError::ConversionError)?;
+
+        let component_engine = ComponentEngine::new(
+            self.usage_client.clone(),
+            Arc::new(self.clone()),
+            Arc::new(subscription_details.clone()),
+        );
+
+        let mut lines = compute_accrued_lines(
+            &component_engine,
+            &subscription_details.price_components,
+            subscription_details,
+            *date,
+            &currency,
+        )
+        .await?;
+
+        lines.extend(
+            compute_accrued_lines(
+                &component_engine,
+                &subscription_details.add_ons,
+                subscription_details,
+                *date,
+                &currency,
+            )
+            .await?,
+        );
+
+        Ok(lines)
+    }
+
+    async fn compute_closing_lines(
+        &self,
+        date: &NaiveDate,
+        subscription_details: &SubscriptionDetails,
+    ) -> Result<Vec<LineItem>, ComputeError> {
+        if *date <= subscription_details.billing_start_date {
+            return Ok(vec![]);
+        }
+
+        let currency = Currency::from_code(&subscription_details.currency)
+            .ok_or(ComputeError::ConversionError)?;
+
+        let component_engine = ComponentEngine::new(
+            self.usage_client.clone(),
+            Arc::new(self.clone()),
+            Arc::new(subscription_details.clone()),
+        );
+
+        let mut lines = compute_closing_fee_lines(
+            &component_engine,
+            &subscription_details.price_components,
+            subscription_details,
+            *date,
+            &currency,
+        )
+        .await?;
+
+        lines.extend(
+            compute_closing_fee_lines(
+                &component_engine,
+                &subscription_details.add_ons,
+                subscription_details,
+                *date,
+                &currency,
+            )
+            .await?,
+        );
+
+        let deduction_lines =
+            usage_threshold_deductions(self, subscription_details.id, *date, &lines)
+                .await
+                .map_err(|_e| ComputeError::InternalError)?;
+
+        let cap_line = compute_spend_cap_line(
+            subscription_details,
+            *date,
+            &lines,
+            &deduction_lines,
+            &currency,
+        )?;
+        lines.extend(deduction_lines);
+        lines.extend(cap_line);
+
+        Ok(lines)
+    }
+}
+
+// the fees billed in arrears over the days elapsed in the current period, the fees billed in advance being already billed
+async fn compute_closing_fee_lines<T: SubscriptionFeeInterface>(
+    component_engine: &ComponentEngine,
+    fee_records: &[T],
+    subscription_details: &SubscriptionDetails,
+    date: NaiveDate,
+    currency: &Currency,
+) -> Result<Vec<LineItem>, ComputeError> {
+    let mut lines = Vec::new();
+
+    for fee in fee_records {
+        let periods = calculate_component_closing_period(
+            subscription_details.billing_start_date,
+            subscription_details.billing_day as u32,
+            date,
+            fee.period_ref(),
+        );
+
+        if let Some(periods) = periods {
+            let fee_lines = component_engine
+                .compute_component(fee, periods, &date, currency)
+                .await?;
+
+            lines.extend(
+                fee_lines
+                    .into_iter()
+                    .filter(|l| l.end_date == date && l.total > 0),
+            );
+        }
+    }
+
+    Ok(lines)
+}
+
+// the usage of the current period is billed as arrears, over the days elapsed so far
+async fn compute_accrued_lines<T: SubscriptionFeeInterface>(
+    component_engine: &ComponentEngine,
+    fee_records: &[T],
+    subscription_details: &SubscriptionDetails,
+    date: NaiveDate,
+    currency: &Currency,
+) -> Result<Vec<LineItem>, ComputeError> {
+    let mut lines = Vec::new();
+
+    for fee in fee_records {
+        if fee.fee_ref().metric_id().is_none() {
+            continue;
+        }
+
+        let billing_period = match fee.period_ref().as_billing_period_opt() {
+            Some(billing_period) => billing_period,
+            None => continue,
+        };
+
+        let current = calculate_periods_for_date(
+            subscription_details.billing_start_date,
+            subscription_details.billing_day as u32,
+            date,
+            &billing_period,
+        )
+        .advance;
+
+        if current.start >= date {
+            continue;
+        }
+
+        let accrued = Period {
+            start: current.start,
+            end: date,
+        };
+
+        let periods = ComponentPeriods {
+            arrear: Some(accrued),
+            advance: current,
+            proration_factor: None,
+        };
+
+        let fee_lines = component_engine
+            .compute_component(fee, periods, &date, currency)
+            .await?;
+
+        // the fixed part of a capacity is billed in advance by the recurring invoices
+        lines.extend(
+            fee_lines
+                .into_iter()
+                .filter(|l| l.end_date == date && l.total > 0),
+        );
+    }
+
+    Ok(lines)
 }
 
 async fn compute_invoice_lines<T: SubscriptionFeeInterface>(
@@ -116,7 +440,7 @@ async fn compute_invoice_lines<T: SubscriptionFeeInterface>(
     for (period, components) in component_period_components {
         for component in components {
             let lines = component_engine
-                .compute_component(component, period.clone(), &invoice_date, currency.precision)
+                .compute_component(component, period.clone(), &invoice_date, currency)
                 .await?;
             invoice_lines.extend(lines);
         }
@@ -124,3 +448,242 @@ async fn compute_invoice_lines<T: SubscriptionFeeInterface>(
 
     Ok(invoice_lines)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compute::clients::slots::SlotClient;
+    use crate::compute::clients::usage::{GroupedUsageData, Metadata, UsageClient, UsageData};
+    use crate::domain::enums::{
+        BillingMetricAggregateEnum, BillingPeriodEnum, BillingType, SubscriptionFeeBillingPeriod,
+    };
+    use rust_decimal::Decimal;
+    use rust_decimal_macros::dec;
+
+    // 10 units a day
+    struct DailyUsageClient;
+
+    #[async_trait::async_trait]
+    impl UsageClient for DailyUsageClient {
+        async fn register_meter(
+            &self,
+            _tenant_id: &Uuid,
+            _metric: &BillableMetric,
+        ) -> Result<Vec<Metadata>, ComputeError> {
+            Ok(vec![])
+        }
+
+        async fn fetch_usage(
+            &self,
+            _tenant_id: &Uuid,
+            _customer_id: &Uuid,
+            _customer_external_id: &Option<String>,
+            _metric: &BillableMetric,
+            period: Period,
+        ) -> Result<UsageData, ComputeError> {
+            let days = period.end.signed_duration_since(period.start).num_days();
+            Ok(UsageData {
+                data: vec![GroupedUsageData {
+                    value: Decimal::from(days * 10),
+                    dimensions: HashMap::new(),
+                }],
+                period,
+            })
+        }
+    }
+
+    struct NoSlotClient;
+
+    #[async_trait::async_trait]
+    impl SlotClient for NoSlotClient {
+        async fn fetch_slots(
+            &self,
+            _tenant_id: &Uuid,
+            _subscription_id: &Uuid,
+            _component_id: &Uuid,
+            _invoice_date: &NaiveDate,
+        ) -> Result<u32, ComputeError> {
+            Ok(0)
+        }
+    }
+
+    fn date(s: &str) -> NaiveDate {
+        s.parse().unwrap()
+    }
+
+    fn subscription() -> SubscriptionDetails {
+        let start = date("2024-01-01");
+        SubscriptionDetails {
+            id: Uuid::now_v7(),
+            tenant_id: Uuid::now_v7(),
+            customer_id: Uuid::now_v7(),
+            plan_version_id: Uuid::now_v7(),
+            customer_external_id: None,
+            billing_start_date: start,
+            billing_end_date: None,
+            billing_day: 1,
+            currency: "EUR".to_string(),
+            net_terms: 0,
+            schedules: vec![],
+            price_components: vec![],
+            add_ons: vec![],
+            applied_coupons: vec![],
+            metrics: vec![],
+            mrr_cents: 0,
+            version: 1,
+            billing_cycles: None,
+            plan_name: "plan".to_string(),
+            plan_id: Uuid::now_v7(),
+            customer_name: "customer".to_string(),
+            canceled_at: None,
+            invoice_memo: None,
+            invoice_threshold: None,
+            created_at: start.and_time(chrono::NaiveTime::MIN),
+            cancellation_reason: None,
+            activated_at: Some(start.and_time(chrono::NaiveTime::MIN)),
+            created_by: Uuid::now_v7(),
+            trial_start_date: None,
+            period: BillingPeriodEnum::Monthly,
+            paused_at: None,
+            resume_at: None,
+            minimum_spend: None,
+            spend_cap: None,
+            elapsed_billing_cycles: 0,
+        }
+    }
+
+    fn metric(id: Uuid) -> BillableMetric {
+        BillableMetric {
+            id,
+            name: "API calls".to_string(),
+            description: None,
+            code: "api_calls".to_string(),
+            aggregation_type: BillingMetricAggregateEnum::Count,
+            aggregation_key: None,
+            unit_conversion_factor: None,
+            unit_conversion_rounding: None,
+            segmentation_matrix: None,
+            usage_group_key: None,
+            created_at: date("2024-01-01").and_hms_opt(0, 0, 0).unwrap(),
+            created_by: Uuid::now_v7(),
+            updated_at: None,
+            archived_at: None,
+            tenant_id: Uuid::now_v7(),
+            product_family_id: Uuid::now_v7(),
+        }
+    }
+
+    fn component(
+        name: &str,
+        period: SubscriptionFeeBillingPeriod,
+        fee: SubscriptionFee,
+    ) -> SubscriptionComponent {
+        SubscriptionComponent {
+            id: Uuid::now_v7(),
+            price_component_id: Some(Uuid::now_v7()),
+            product_item_id: None,
+            subscription_id: Uuid::now_v7(),
+            name: name.to_string(),
+            period,
+            fee,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_closing_lines_in_the_middle_of_a_period() {
+        let metric_id = Uuid::now_v7();
+        let subscription = SubscriptionDetails {
+            price_components: vec![
+                component(
+                    "Platform",
+                    SubscriptionFeeBillingPeriod::Monthly,
+                    SubscriptionFee::Rate { rate: dec!(31) },
+                ),
+                component(
+                    "Support",
+                    SubscriptionFeeBillingPeriod::Monthly,
+                    SubscriptionFee::Recurring {
+                        rate: dec!(62),
+                        quantity: 1,
+                        billing_type: BillingType::Arrears,
+                    },
+                ),
+                component(
+                    "API calls",
+                    SubscriptionFeeBillingPeriod::Monthly,
+                    SubscriptionFee::Usage {
+                        metric_id,
+                        model: UsagePricingModel::PerUnit { rate: dec!(0.5) },
+                    },
+                ),
+                component(
+                    "Setup",
+                    SubscriptionFeeBillingPeriod::OneTime,
+                    SubscriptionFee::OneTime {
+                        rate: dec!(100),
+                        quantity: 1,
+                    },
+                ),
+            ],
+            metrics: vec![metric(metric_id)],
+            ..subscription()
+        };
+
+        let component_engine = ComponentEngine::new(
+            Arc::new(DailyUsageClient),
+            Arc::new(NoSlotClient),
+            Arc::new(subscription.clone()),
+        );
+
+        // paused on the 16th, the fees billed in advance were billed on the 1st
+        let pause_date = date("2024-01-16");
+        let lines = compute_closing_fee_lines(
+            &component_engine,
+            &subscription.price_components,
+            &subscription,
+            pause_date,
+            &Currency::from_code("EUR").unwrap(),
+        )
+        .await
+        .unwrap();
+
+        let lines = lines
+            .into_iter()
+            .map(|l| (l.name, l.total, l.start_date, l.end_date, l.metric_id))
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            lines,
+            vec![
+                // 15 of the 31 days of january
+                (
+                    "Support".to_string(),
+                    3000,
+                    date("2024-01-01"),
+                    pause_date,
+                    None
+                ),
+                // the usage of the 15 days, 150 calls at 0.5
+                (
+                    "API calls".to_string(),
+                    7500,
+                    date("2024-01-01"),
+                    pause_date,
+                    Some(metric_id)
+                ),
+            ]
+        );
+
+        // nothing is left to bill when paused on the first day of a period
+        let lines = compute_closing_fee_lines(
+            &component_engine,
+            &subscription.price_components,
+            &subscription,
+            date("2024-02-01"),
+            &Currency::from_code("EUR").unwrap(),
+        )
+        .await
+        .unwrap();
+        assert!(lines.is_empty());
+    }
+}
@@ -1,7 +1,9 @@
+mod commitments;
 mod component;
 pub mod invoice;
 
 pub mod period;
+mod ramps;
 
 mod fees;
 mod shared;
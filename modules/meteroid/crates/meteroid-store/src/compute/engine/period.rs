@@ -71,6 +71,104 @@ pub fn calculate_component_period(
     }
 }
 
+/// The periods to bill for a change happening within the current period, on `change_date`.
+/// The advance period starts at the change date and the proration factor covers the remaining days only.
+pub fn calculate_component_proration_period(
+    billing_start_date: NaiveDate,
+    billing_day: u32,
+    change_date: NaiveDate,
+    billing_period: &SubscriptionFeeBillingPeriod,
+) -> Option<ComponentPeriods> {
+    // one-time fees are not billed again on a change
+    let period_enum = billing_period.as_billing_period_opt()?;
+
+    let period_idx =
+        calculate_period_idx(billing_start_date, billing_day, change_date, &period_enum);
+    let current_period =
+        calculate_period_range(billing_start_date, billing_day, period_idx, &period_enum);
+
+    let periods = calculate_component_period(
+        billing_start_date,
+        billing_day,
+        current_period.start,
+        billing_period,
+    )?;
+
+    let total_days = periods
+        .advance
+        .end
+        .signed_duration_since(periods.advance.start)
+        .num_days();
+    let remaining_days = periods
+        .advance
+        .end
+        .signed_duration_since(change_date)
+        .num_days();
+
+    if total_days <= 0 || remaining_days <= 0 {
+        return None;
+    }
+
+    let remaining_factor = remaining_days as f64 / total_days as f64;
+
+    Some(ComponentPeriods {
+        proration_factor: Some(periods.proration_factor.unwrap_or(1.0) * remaining_factor),
+        advance: Period {
+            start: change_date,
+            end: periods.advance.end,
+        },
+        arrear: None,
+    })
+}
+
+/// The periods to bill when the current period is closed early, on `close_date`, ex: when the subscription is paused.
+/// The arrear period ends at the close date and the proration factor covers the elapsed days only.
+pub fn calculate_component_closing_period(
+    billing_start_date: NaiveDate,
+    billing_day: u32,
+    close_date: NaiveDate,
+    billing_period: &SubscriptionFeeBillingPeriod,
+) -> Option<ComponentPeriods> {
+    // one-time fees are billed with the first period only
+    let period_enum = billing_period.as_billing_period_opt()?;
+
+    let period_idx =
+        calculate_period_idx(billing_start_date, billing_day, close_date, &period_enum);
+    let current_period =
+        calculate_period_range(billing_start_date, billing_day, period_idx, &period_enum);
+
+    let periods = calculate_component_period(
+        billing_start_date,
+        billing_day,
+        current_period.start,
+        billing_period,
+    )?;
+
+    let total_days = periods
+        .advance
+        .end
+        .signed_duration_since(periods.advance.start)
+        .num_days();
+    let elapsed_days = close_date
+        .signed_duration_since(periods.advance.start)
+        .num_days();
+
+    if total_days <= 0 || elapsed_days <= 0 || elapsed_days >= total_days {
+        return None;
+    }
+
+    let elapsed_factor = elapsed_days as f64 / total_days as f64;
+
+    Some(ComponentPeriods {
+        proration_factor: Some(periods.proration_factor.unwrap_or(1.0) * elapsed_factor),
+        arrear: Some(Period {
+            start: periods.advance.start,
+            end: close_date,
+        }),
+        advance: periods.advance,
+    })
+}
+
 fn calculate_proration_factor(period: &Period) -> Option<f64> {
     let days_in_period = period.end.signed_duration_since(period.start).num_days() as u64; // +1 ?
     let days_in_month_from = period.start.days_in_month() as u64;
@@ -140,7 +238,7 @@ pub fn calculate_period_range(
     }
 }
 
-fn calculate_period_idx(
+pub fn calculate_period_idx(
     billing_start_date: NaiveDate,
     billing_day: u32,
     invoice_date: NaiveDate,
@@ -168,6 +266,46 @@ fn calculate_period_idx(
     }
 }
 
+/// The number of periods started from the billing start date up to `date` included, ex: when the subscription is paused.
+pub fn calculate_elapsed_billing_cycles(
+    billing_start_date: NaiveDate,
+    billing_day: u32,
+    date: NaiveDate,
+    billing_period: &BillingPeriodEnum,
+) -> i32 {
+    if date < billing_start_date {
+        0
+    } else {
+        calculate_period_idx(billing_start_date, billing_day, date, billing_period) + 1
+    }
+}
+
+/// The end of the last billed period, for a plan billed for a limited number of cycles.
+/// The cycles elapsed before a resume (paused periods excluded) are deducted from the periods since the billing start date.
+pub fn calculate_billing_cycles_end(
+    billing_start_date: NaiveDate,
+    billing_day: u32,
+    billing_cycles: Option<i32>,
+    elapsed_billing_cycles: i32,
+    billing_period: &BillingPeriodEnum,
+) -> Option<NaiveDate> {
+    billing_cycles.filter(|cycles| *cycles > 0).map(|cycles| {
+        match cycles - elapsed_billing_cycles {
+            remaining if remaining > 0 => {
+                calculate_period_range(
+                    billing_start_date,
+                    billing_day,
+                    remaining - 1,
+                    billing_period,
+                )
+                .end
+            }
+            // all the cycles were billed before the pause
+            _ => billing_start_date,
+        }
+    })
+}
+
 fn add_months_at_billing_day(
     date: NaiveDate,
     months_to_add: u32,
@@ -179,8 +317,12 @@ fn add_months_at_billing_day(
 
 #[cfg(test)]
 mod test {
-    use super::{calculate_period_idx, calculate_period_range};
-    use crate::domain::enums::BillingPeriodEnum;
+    use super::{
+        calculate_billing_cycles_end, calculate_component_closing_period,
+        calculate_component_proration_period, calculate_elapsed_billing_cycles,
+        calculate_period_idx, calculate_period_range,
+    };
+    use crate::domain::enums::{BillingPeriodEnum, SubscriptionFeeBillingPeriod};
 
     use chrono::NaiveDate;
     use rstest::rstest;
@@ -384,4 +526,205 @@ mod test {
         );
         assert_eq!(period_idx, expected_period_idx);
     }
+
+    #[rstest]
+    #[case(
+        SubscriptionFeeBillingPeriod::Monthly,
+        "2021-01-01",
+        1,
+        "2021-03-16",
+        Some(("2021-03-16", "2021-04-01", 16.0 / 31.0))
+    )]
+    #[case(
+        SubscriptionFeeBillingPeriod::Monthly,
+        "2021-01-01",
+        1,
+        "2021-03-01",
+        Some(("2021-03-01", "2021-04-01", 1.0))
+    )]
+    #[case(
+        SubscriptionFeeBillingPeriod::Annual,
+        "2021-01-01",
+        1,
+        "2021-07-02",
+        Some(("2021-07-02", "2022-01-01", 183.0 / 365.0))
+    )]
+    #[case(
+        SubscriptionFeeBillingPeriod::OneTime,
+        "2021-01-01",
+        1,
+        "2021-03-16",
+        None
+    )]
+    #[trace]
+    fn test_calculate_component_proration_period(
+        #[case] billing_period: SubscriptionFeeBillingPeriod,
+        #[case] billing_start_date: NaiveDate,
+        #[case] billing_day: u32,
+        #[case] change_date: NaiveDate,
+        #[case] expected: Option<(&str, &str, f64)>,
+    ) {
+        let periods = calculate_component_proration_period(
+            billing_start_date,
+            billing_day,
+            change_date,
+            &billing_period,
+        );
+
+        match (periods, expected) {
+            (None, None) => {}
+            (Some(periods), Some((start, end, factor))) => {
+                assert_eq!(periods.advance.start, start.parse::<NaiveDate>().unwrap());
+                assert_eq!(periods.advance.end, end.parse::<NaiveDate>().unwrap());
+                assert!(periods.arrear.is_none());
+                assert!((periods.proration_factor.unwrap() - factor).abs() < 1e-9);
+            }
+            (periods, expected) => panic!("expected {:?}, got {:?}", expected, periods),
+        }
+    }
+
+    #[rstest]
+    #[case(
+        SubscriptionFeeBillingPeriod::Monthly,
+        "2021-01-01",
+        1,
+        "2021-03-16",
+        Some(("2021-03-01", "2021-03-16", 15.0 / 31.0))
+    )]
+    #[case(
+        SubscriptionFeeBillingPeriod::Monthly,
+        "2021-01-01",
+        1,
+        "2021-03-01",
+        None
+    )]
+    #[case(
+        SubscriptionFeeBillingPeriod::Annual,
+        "2021-01-01",
+        1,
+        "2021-07-02",
+        Some(("2021-01-01", "2021-07-02", 182.0 / 365.0))
+    )]
+    #[case(
+        SubscriptionFeeBillingPeriod::OneTime,
+        "2021-01-01",
+        1,
+        "2021-03-16",
+        None
+    )]
+    #[trace]
+    fn test_calculate_component_closing_period(
+        #[case] billing_period: SubscriptionFeeBillingPeriod,
+        #[case] billing_start_date: NaiveDate,
+        #[case] billing_day: u32,
+        #[case] close_date: NaiveDate,
+        #[case] expected: Option<(&str, &str, f64)>,
+    ) {
+        let periods = calculate_component_closing_period(
+            billing_start_date,
+            billing_day,
+            close_date,
+            &billing_period,
+        );
+
+        match (periods, expected) {
+            (None, None) => {}
+            (Some(periods), Some((start, end, factor))) => {
+                let arrear = periods.arrear.unwrap();
+                assert_eq!(arrear.start, start.parse::<NaiveDate>().unwrap());
+                assert_eq!(arrear.end, end.parse::<NaiveDate>().unwrap());
+                assert_eq!(periods.advance.start, arrear.start);
+                assert!((periods.proration_factor.unwrap() - factor).abs() < 1e-9);
+            }
+            (periods, expected) => panic!("expected {:?}, got {:?}", expected, periods),
+        }
+    }
+
+    #[rstest]
+    #[case(
+        BillingPeriodEnum::Monthly,
+        "2021-01-01",
+        1,
+        Some(12),
+        Some("2022-01-01")
+    )]
+    #[case(
+        BillingPeriodEnum::Monthly,
+        "2021-01-10",
+        1,
+        Some(3),
+        Some("2021-04-01")
+    )]
+    #[case(
+        BillingPeriodEnum::Annual,
+        "2021-01-01",
+        1,
+        Some(2),
+        Some("2023-01-01")
+    )]
+    #[case(BillingPeriodEnum::Monthly, "2021-01-01", 1, Some(0), None)]
+    #[case(BillingPeriodEnum::Monthly, "2021-01-01", 1, None, None)]
+    #[trace]
+    fn test_calculate_billing_cycles_end(
+        #[case] billing_period: BillingPeriodEnum,
+        #[case] billing_start_date: NaiveDate,
+        #[case] billing_day: u32,
+        #[case] billing_cycles: Option<i32>,
+        #[case] expected: Option<&str>,
+    ) {
+        let end = calculate_billing_cycles_end(
+            billing_start_date,
+            billing_day,
+            billing_cycles,
+            0,
+            &billing_period,
+        );
+
+        assert_eq!(end, expected.map(|e| e.parse::<NaiveDate>().unwrap()));
+    }
+
+    #[rstest]
+    // resumed on the 10th of march, after 2 of the 3 cycles were billed
+    #[case("2021-03-10", Some(3), 2, Some("2021-04-01"))]
+    #[case("2021-03-10", Some(3), 1, Some("2021-05-01"))]
+    #[case("2021-03-10", Some(3), 3, Some("2021-03-10"))]
+    #[case("2021-03-10", None, 2, None)]
+    #[trace]
+    fn test_calculate_billing_cycles_end_after_resume(
+        #[case] billing_start_date: NaiveDate,
+        #[case] billing_cycles: Option<i32>,
+        #[case] elapsed_billing_cycles: i32,
+        #[case] expected: Option<&str>,
+    ) {
+        let end = calculate_billing_cycles_end(
+            billing_start_date,
+            1,
+            billing_cycles,
+            elapsed_billing_cycles,
+            &BillingPeriodEnum::Monthly,
+        );
+
+        assert_eq!(end, expected.map(|e| e.parse::<NaiveDate>().unwrap()));
+    }
+
+    #[rstest]
+    #[case("2021-01-10", "2021-01-05", 0)]
+    #[case("2021-01-10", "2021-01-20", 1)]
+    #[case("2021-01-10", "2021-02-01", 2)]
+    #[case("2021-01-01", "2021-03-15", 3)]
+    #[trace]
+    fn test_calculate_elapsed_billing_cycles(
+        #[case] billing_start_date: NaiveDate,
+        #[case] paused_date: NaiveDate,
+        #[case] expected: i32,
+    ) {
+        let elapsed = calculate_elapsed_billing_cycles(
+            billing_start_date,
+            1,
+            paused_date,
+            &BillingPeriodEnum::Monthly,
+        );
+
+        assert_eq!(elapsed, expected);
+    }
 }
@@ -0,0 +1,116 @@
+use std::collections::BTreeMap;
+
+use super::period::{calculate_period_idx, calculate_period_range};
+use super::shared::{adjustment_line, to_minor};
+use crate::compute::errors::ComputeError;
+use crate::domain::adjustments::discount::StandardDiscount;
+use crate::domain::{LineItem, SubscriptionDetails};
+use chrono::NaiveDate;
+use common_domain::money::Currency;
+use rust_decimal::Decimal;
+
+/// Computes the adjustment lines of the plan ramps for the lines of an invoice.
+/// A percent discount applies to the lines of each period with the ramp of that period, as the usage of
+/// a period is billed by the next invoice. An amount discount and the minimum apply once per invoice,
+/// with the ramp of the period opened by the invoice, the minimum being compared to the discounted subtotal.
+pub fn compute_ramp_lines(
+    subscription_details: &SubscriptionDetails,
+    invoice_date: NaiveDate,
+    lines: &[LineItem],
+    currency: &Currency,
+) -> Result<Vec<LineItem>, ComputeError> {
+    let billing_period = &subscription_details.period;
+
+    let ramps = match subscription_details
+        .schedules
+        .iter()
+        .find(|s| &s.billing_period == billing_period)
+    {
+        Some(schedule) => &schedule.ramps,
+        None => return Ok(vec![]),
+    };
+
+    let billing_start_date = subscription_details.billing_start_date;
+    let billing_day = subscription_details.billing_day as u32;
+    // the ramps go on from the cycle reached before the pause
+    let elapsed_cycles = subscription_details.elapsed_billing_cycles.max(0) as u32;
+
+    let period_idx = |date: NaiveDate| {
+        calculate_period_idx(billing_start_date, billing_day, date, billing_period).max(0) as u32
+            + elapsed_cycles
+    };
+    let period_range = |idx: u32| {
+        calculate_period_range(
+            billing_start_date,
+            billing_day,
+            idx.saturating_sub(elapsed_cycles) as i32,
+            billing_period,
+        )
+    };
+
+    let mut subtotals: BTreeMap<u32, i64> = BTreeMap::new();
+    for line in lines {
+        *subtotals.entry(period_idx(line.start_date)).or_default() += line.subtotal;
+    }
+
+    let mut ramp_lines = Vec::new();
+
+    for (idx, subtotal) in subtotals {
+        if let Some(ramp) = ramps.active_ramp(idx, billing_period) {
+            if let StandardDiscount::Percent(percent) = &ramp.ramp_adjustment.discount {
+                let discount = to_minor(
+                    currency.to_major(subtotal) * percent.percentage / Decimal::ONE_HUNDRED,
+                    currency,
+                )?
+                .min(subtotal);
+
+                if discount > 0 {
+                    ramp_lines.push(adjustment_line("Discount", -discount, period_range(idx)));
+                }
+            }
+        }
+    }
+
+    let invoice_period_idx = period_idx(invoice_date);
+
+    // the last invoice of a plan with billing cycles only bills the arrears of the last period
+    let opens_billed_period = subscription_details
+        .billing_cycles
+        .is_none_or(|cycles| (invoice_period_idx as i32) < cycles);
+
+    let ramp = match ramps.active_ramp(invoice_period_idx, billing_period) {
+        Some(ramp) if opens_billed_period => ramp,
+        _ => return Ok(ramp_lines),
+    };
+
+    let mut subtotal = lines
+        .iter()
+        .chain(&ramp_lines)
+        .map(|l| l.subtotal)
+        .sum::<i64>();
+
+    if let StandardDiscount::Amount(amount) = &ramp.ramp_adjustment.discount {
+        let discount = (amount.value_in_cents as i64).min(subtotal);
+
+        if discount > 0 {
+            subtotal -= discount;
+            ramp_lines.push(adjustment_line(
+                "Discount",
+                -discount,
+                period_range(invoice_period_idx),
+            ));
+        }
+    }
+
+    let minimum = ramp.ramp_adjustment.minimum.value_in_cents as i64;
+
+    if subtotal < minimum {
+        ramp_lines.push(adjustment_line(
+            "Minimum commitment",
+            minimum - subtotal,
+            period_range(invoice_period_idx),
+        ));
+    }
+
+    Ok(ramp_lines)
+}
@@ -1,5 +1,16 @@
+use crate::compute::ComputeError;
+use crate::domain::{LineItem, Period};
+use crate::utils::local_id::LocalId;
+use common_domain::money::{Currency, Rounding};
 use rust_decimal::Decimal;
 
+/// Converts an amount in major units to the minor units of the currency, rounded half away from zero.
+pub fn to_minor(amount: Decimal, currency: &Currency) -> Result<i64, ComputeError> {
+    currency
+        .to_minor(amount, Rounding::HalfAwayFromZero)
+        .ok_or(ComputeError::ConversionError)
+}
+
 pub fn only_positive(price_cents: i64) -> u64 {
     if price_cents > 0 {
         price_cents as u64
@@ -15,3 +26,27 @@ pub fn only_positive_decimal(price: Decimal) -> Decimal {
         Decimal::from(0)
     }
 }
+
+/// A line adjusting the invoice for a period, not tied to a price component.
+pub fn adjustment_line(name: &str, total: i64, period: Period) -> LineItem {
+    LineItem {
+        local_id: LocalId::no_prefix(),
+        name: name.to_string(),
+        total,
+        subtotal: total,
+        quantity: None,
+        unit_price: None,
+        start_date: period.start,
+        end_date: period.end,
+        sub_lines: vec![],
+        is_prorated: false,
+        price_component_id: None,
+        product_id: None,
+        metric_id: None,
+        add_on_id: None,
+        description: None,
+        tax_rate: Decimal::ZERO,
+        tax_amount: 0,
+        is_manual: false,
+    }
+}
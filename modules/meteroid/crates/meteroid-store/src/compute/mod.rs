@@ -3,5 +3,7 @@ mod engine;
 mod errors;
 
 pub use engine::invoice::InvoiceLineInterface;
-pub use engine::period::calculate_period_range;
+pub use engine::period::{
+    calculate_billing_cycles_end, calculate_elapsed_billing_cycles, calculate_period_range,
+};
 pub use errors::ComputeError;
@@ -49,7 +49,4 @@ impl Currencies {
     pub fn resolve_currency(currency: &str) -> Option<&Currency> {
         CURRENCIES.iter().find(|c| c.code == currency)
     }
-    pub fn resolve_currency_precision(currency: &str) -> Option<u8> {
-        Self::resolve_currency(currency).map(|c| c.precision)
-    }
 }
@@ -43,6 +43,18 @@ pub struct BillableMetric {
     pub product_family_id: Uuid,
 }
 
+impl BillableMetric {
+    /// Whether the raw usage is converted or rounded to the billable unit of the metric.
+    pub fn has_unit_conversion(&self) -> bool {
+        self.unit_conversion_factor
+            .is_some_and(|factor| factor != 0 && factor != 1)
+            || self
+                .unit_conversion_rounding
+                .as_ref()
+                .is_some_and(|rounding| !matches!(rounding, UnitConversionRoundingEnum::None))
+    }
+}
+
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Dimension {
     pub key: String,
@@ -1,7 +1,11 @@
+use crate::domain::enums::CouponStackingEnum;
+use crate::domain::LineItem;
 use crate::errors::StoreError;
-use chrono::NaiveDateTime;
+use chrono::{Months, NaiveDate, NaiveDateTime};
 use diesel_models::coupons::{CouponRow, CouponRowNew, CouponRowPatch};
+use diesel_models::promotion_codes::{PromotionCodeRow, PromotionCodeRowNew};
 use error_stack::Report;
+use o2o::o2o;
 use rust_decimal::Decimal;
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
@@ -21,11 +25,25 @@ pub struct Coupon {
     pub updated_at: NaiveDateTime,
     pub last_redemption_at: Option<NaiveDateTime>,
     pub archived_at: Option<NaiveDateTime>,
+    pub applies_to: CouponAppliesTo,
+    pub duration_months: Option<i32>, // applies only to the invoices of the first months after it was applied
+    pub stacking: CouponStackingEnum,
+    pub priority: i32, // coupons are applied by ascending priority
 }
 
 impl Coupon {
     pub fn is_infinite(&self) -> bool {
-        self.recurring_value.is_none()
+        self.recurring_value.is_none() && self.duration_months.is_none()
+    }
+
+    /// Whether the coupon applied at `applied_at` still applies to an invoice dated `invoice_date`.
+    pub fn is_within_duration(&self, applied_at: NaiveDateTime, invoice_date: NaiveDate) -> bool {
+        self.duration_months.is_none_or(|months| {
+            applied_at
+                .date()
+                .checked_add_months(Months::new(months.max(0) as u32))
+                .is_some_and(|end| invoice_date < end)
+        })
     }
 
     pub fn is_expired(&self, now: NaiveDateTime) -> bool {
@@ -39,6 +57,15 @@ impl Coupon {
     pub fn currency(&self) -> Option<&str> {
         self.discount.currency()
     }
+
+    /// The order in which the coupons of an invoice are applied: by priority, then percentages before fixed amounts.
+    pub fn application_order(&self) -> (i32, u8) {
+        let kind = match self.discount {
+            CouponDiscount::Percentage(_) => 0,
+            CouponDiscount::Fixed { .. } => 1,
+        };
+        (self.priority, kind)
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -56,6 +83,61 @@ impl CouponDiscount {
     }
 }
 
+/// Restricts a coupon to some plans or plan versions, and to the lines of some price components or add-ons.
+/// An empty restriction applies to everything.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct CouponAppliesTo {
+    #[serde(default)]
+    pub plan_ids: Vec<Uuid>,
+    #[serde(default)]
+    pub plan_version_ids: Vec<Uuid>,
+    #[serde(default)]
+    pub price_component_ids: Vec<Uuid>,
+    #[serde(default)]
+    pub add_on_ids: Vec<Uuid>,
+}
+
+impl CouponAppliesTo {
+    pub fn applies_to_plan(&self, plan_id: Uuid, plan_version_id: Uuid) -> bool {
+        (self.plan_ids.is_empty() && self.plan_version_ids.is_empty())
+            || self.plan_ids.contains(&plan_id)
+            || self.plan_version_ids.contains(&plan_version_id)
+    }
+
+    pub fn restricts_lines(&self) -> bool {
+        !self.price_component_ids.is_empty() || !self.add_on_ids.is_empty()
+    }
+
+    pub fn applies_to_line(&self, line: &LineItem) -> bool {
+        !self.restricts_lines()
+            || line
+                .price_component_id
+                .is_some_and(|id| self.price_component_ids.contains(&id))
+            || line
+                .add_on_id
+                .is_some_and(|id| self.add_on_ids.contains(&id))
+    }
+
+    pub(crate) fn from_json(value: Option<serde_json::Value>) -> Result<Self, StoreError> {
+        value
+            .map(|v| {
+                serde_json::from_value(v)
+                    .map_err(|e| StoreError::SerdeError("coupon applies_to".to_string(), e))
+            })
+            .transpose()
+            .map(Option::unwrap_or_default)
+    }
+
+    fn to_json(&self) -> Result<Option<serde_json::Value>, StoreError> {
+        if *self == CouponAppliesTo::default() {
+            return Ok(None);
+        }
+        serde_json::to_value(self)
+            .map(Some)
+            .map_err(|e| StoreError::SerdeError("coupon applies_to".to_string(), e))
+    }
+}
+
 impl TryInto<Coupon> for CouponRow {
     type Error = Report<StoreError>;
 
@@ -77,6 +159,10 @@ impl TryInto<Coupon> for CouponRow {
             updated_at: self.updated_at,
             last_redemption_at: self.last_redemption_at,
             archived_at: self.archived_at,
+            applies_to: CouponAppliesTo::from_json(self.applies_to)?,
+            duration_months: self.duration_months,
+            stacking: self.stacking.into(),
+            priority: self.priority,
         })
     }
 }
@@ -91,6 +177,10 @@ pub struct CouponNew {
     pub redemption_limit: Option<i32>,
     pub recurring_value: Option<i32>,
     pub reusable: bool,
+    pub applies_to: CouponAppliesTo,
+    pub duration_months: Option<i32>,
+    pub stacking: CouponStackingEnum,
+    pub priority: i32,
 }
 
 impl TryInto<CouponRowNew> for CouponNew {
@@ -110,6 +200,10 @@ impl TryInto<CouponRowNew> for CouponNew {
             redemption_limit: self.redemption_limit,
             recurring_value: self.recurring_value,
             reusable: self.reusable,
+            applies_to: self.applies_to.to_json()?,
+            duration_months: self.duration_months,
+            stacking: self.stacking.into(),
+            priority: self.priority,
         })
     }
 }
@@ -120,6 +214,8 @@ pub struct CouponPatch {
     pub tenant_id: Uuid,
     pub description: Option<String>,
     pub discount: Option<CouponDiscount>,
+    pub stacking: Option<CouponStackingEnum>,
+    pub priority: Option<i32>,
     pub updated_at: NaiveDateTime,
 }
 
@@ -139,7 +235,163 @@ impl TryInto<CouponRowPatch> for CouponPatch {
             tenant_id: self.tenant_id,
             description: self.description,
             discount: json_discount,
+            stacking: self.stacking.map(Into::into),
+            priority: self.priority,
             updated_at: self.updated_at,
         })
     }
 }
+
+/// A customer-facing code redeeming a coupon, with its own limits.
+#[derive(Debug, Clone, o2o)]
+#[from_owned(PromotionCodeRow)]
+pub struct PromotionCode {
+    pub id: Uuid,
+    pub code: String,
+    pub coupon_id: Uuid,
+    pub tenant_id: Uuid,
+    pub customer_id: Option<Uuid>, // can only be redeemed by this customer
+    pub expires_at: Option<NaiveDateTime>,
+    pub redemption_limit: Option<i32>,
+    pub redemption_count: i32,
+    pub archived_at: Option<NaiveDateTime>,
+    pub created_at: NaiveDateTime,
+}
+
+impl PromotionCode {
+    /// Checks that the code can be redeemed `count` more times by the customer, or tells why not.
+    pub fn validate_redemption(
+        &self,
+        customer_id: Uuid,
+        count: i32,
+        now: NaiveDateTime,
+    ) -> Result<(), String> {
+        let error = |reason: &str| Err(format!("promotion code {} {}", self.code, reason));
+
+        if self.archived_at.is_some() {
+            return error("is archived");
+        }
+        if self.expires_at.is_some_and(|x| x <= now) {
+            return error("is expired");
+        }
+        if self.customer_id.is_some_and(|x| x != customer_id) {
+            return error("is restricted to another customer");
+        }
+        if self
+            .redemption_limit
+            .is_some_and(|limit| limit < self.redemption_count + count)
+        {
+            return error("has reached its maximum redemptions");
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, o2o)]
+#[owned_into(PromotionCodeRowNew)]
+#[ghosts(id: {Uuid::now_v7()})]
+pub struct PromotionCodeNew {
+    pub code: String,
+    pub coupon_id: Uuid,
+    pub tenant_id: Uuid,
+    pub customer_id: Option<Uuid>,
+    pub expires_at: Option<NaiveDateTime>,
+    pub redemption_limit: Option<i32>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_decimal::Decimal;
+
+    fn coupon(applies_to: CouponAppliesTo, duration_months: Option<i32>) -> Coupon {
+        let now = chrono::Utc::now().naive_utc();
+        Coupon {
+            id: Uuid::now_v7(),
+            code: "WELCOME".to_string(),
+            description: String::new(),
+            tenant_id: Uuid::now_v7(),
+            discount: CouponDiscount::Percentage(Decimal::TEN),
+            expires_at: None,
+            redemption_limit: None,
+            recurring_value: None,
+            reusable: true,
+            created_at: now,
+            updated_at: now,
+            last_redemption_at: None,
+            archived_at: None,
+            applies_to,
+            duration_months,
+            stacking: CouponStackingEnum::Stackable,
+            priority: 0,
+        }
+    }
+
+    fn date(y: i32, m: u32, d: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(y, m, d).unwrap()
+    }
+
+    #[test]
+    fn test_coupon_duration() {
+        let applied_at = date(2025, 1, 15).and_hms_opt(10, 0, 0).unwrap();
+
+        let coupon = coupon(CouponAppliesTo::default(), Some(2));
+        assert!(!coupon.is_infinite());
+        assert!(coupon.is_within_duration(applied_at, date(2025, 1, 15)));
+        assert!(coupon.is_within_duration(applied_at, date(2025, 2, 15)));
+        assert!(!coupon.is_within_duration(applied_at, date(2025, 3, 15)));
+
+        let forever = self::coupon(CouponAppliesTo::default(), None);
+        assert!(forever.is_infinite());
+        assert!(forever.is_within_duration(applied_at, date(2030, 1, 1)));
+    }
+
+    #[test]
+    fn test_coupon_applies_to_plan() {
+        let plan_id = Uuid::now_v7();
+        let version_id = Uuid::now_v7();
+
+        assert!(CouponAppliesTo::default().applies_to_plan(plan_id, version_id));
+
+        let by_plan = CouponAppliesTo {
+            plan_ids: vec![plan_id],
+            ..Default::default()
+        };
+        assert!(by_plan.applies_to_plan(plan_id, Uuid::now_v7()));
+        assert!(!by_plan.applies_to_plan(Uuid::now_v7(), version_id));
+
+        let by_version = CouponAppliesTo {
+            plan_version_ids: vec![version_id],
+            ..Default::default()
+        };
+        assert!(by_version.applies_to_plan(plan_id, version_id));
+        assert!(!by_version.applies_to_plan(plan_id, Uuid::now_v7()));
+    }
+
+    #[test]
+    fn test_promotion_code_redemption() {
+        let now = chrono::Utc::now().naive_utc();
+        let customer_id = Uuid::now_v7();
+
+        let code = PromotionCode {
+            id: Uuid::now_v7(),
+            code: "SPRING".to_string(),
+            coupon_id: Uuid::now_v7(),
+            tenant_id: Uuid::now_v7(),
+            customer_id: Some(customer_id),
+            expires_at: Some(now + chrono::Duration::days(1)),
+            redemption_limit: Some(2),
+            redemption_count: 1,
+            archived_at: None,
+            created_at: now,
+        };
+
+        assert!(code.validate_redemption(customer_id, 1, now).is_ok());
+        assert!(code.validate_redemption(customer_id, 2, now).is_err());
+        assert!(code.validate_redemption(Uuid::now_v7(), 1, now).is_err());
+        assert!(code
+            .validate_redemption(customer_id, 1, now + chrono::Duration::days(2))
+            .is_err());
+    }
+}
@@ -0,0 +1,324 @@
+use crate::domain::invoice_lines::LineItem;
+use crate::errors::StoreError;
+use crate::StoreResult;
+use chrono::{NaiveDate, NaiveDateTime};
+use diesel_models::credit_grants::{CreditGrantRow, CreditGrantRowNew};
+use error_stack::Report;
+use itertools::Itertools;
+use o2o::o2o;
+use std::cmp::min;
+use uuid::Uuid;
+
+/// A prepaid credit of the customer, consumed by its invoices from its effective date until its expiry.
+/// Grants are consumed before the rest of the customer balance, by ascending priority.
+#[derive(Debug, Clone, o2o, PartialEq, Eq)]
+#[from_owned(CreditGrantRow)]
+pub struct CreditGrant {
+    pub id: Uuid,
+    pub tenant_id: Uuid,
+    pub customer_id: Uuid,
+    pub name: String,
+    pub amount_cents: i32,
+    pub remaining_cents: i32,
+    pub currency: String,
+    pub priority: i32,
+    pub effective_date: NaiveDate,
+    pub expiry_date: Option<NaiveDate>,
+    #[from(~.into_iter().flatten().collect())]
+    pub price_component_ids: Vec<Uuid>,
+    #[from(~.into_iter().flatten().collect())]
+    pub metric_ids: Vec<Uuid>,
+    pub created_at: NaiveDateTime,
+    pub created_by: Option<Uuid>,
+    pub expired_at: Option<NaiveDateTime>,
+}
+
+impl CreditGrant {
+    /// Whether the grant can be consumed by an invoice issued at this date.
+    pub fn is_active(&self, date: NaiveDate) -> bool {
+        self.expired_at.is_none()
+            && self.remaining_cents > 0
+            && self.effective_date <= date
+            && self.expiry_date.is_none_or(|expiry| date < expiry)
+    }
+
+    pub fn is_restricted(&self) -> bool {
+        !self.price_component_ids.is_empty() || !self.metric_ids.is_empty()
+    }
+
+    /// Whether the grant can pay for this invoice line.
+    pub fn applies_to(&self, line: &LineItem) -> bool {
+        if !self.is_restricted() {
+            return true;
+        }
+
+        let by_component = line
+            .price_component_id
+            .is_some_and(|id| self.price_component_ids.contains(&id));
+        let by_metric = line
+            .metric_id
+            .is_some_and(|id| self.metric_ids.contains(&id));
+
+        by_component || by_metric
+    }
+}
+
+#[derive(Debug, Clone, o2o)]
+#[owned_into(CreditGrantRowNew)]
+#[ghosts(id: {Uuid::now_v7()}, remaining_cents: {@.amount_cents})]
+pub struct CreditGrantNew {
+    pub tenant_id: Uuid,
+    pub customer_id: Uuid,
+    pub name: String,
+    pub amount_cents: i32,
+    pub currency: String,
+    pub priority: i32,
+    pub effective_date: NaiveDate,
+    pub expiry_date: Option<NaiveDate>,
+    #[into(~.into_iter().map(Some).collect())]
+    pub price_component_ids: Vec<Uuid>,
+    #[into(~.into_iter().map(Some).collect())]
+    pub metric_ids: Vec<Uuid>,
+    pub created_by: Option<Uuid>,
+}
+
+impl CreditGrantNew {
+    pub fn validate(&self, customer_currency: &str) -> StoreResult<()> {
+        if self.amount_cents <= 0 {
+            return Err(Report::new(StoreError::InvalidArgument(
+                "the amount of a credit grant must be positive".to_string(),
+            )));
+        }
+
+        if self.currency != customer_currency {
+            return Err(Report::new(StoreError::InvalidArgument(format!(
+                "the credit grant currency must be the customer currency {}",
+                customer_currency
+            ))));
+        }
+
+        if self
+            .expiry_date
+            .is_some_and(|expiry| expiry <= self.effective_date)
+        {
+            return Err(Report::new(StoreError::InvalidArgument(
+                "the expiry date must be after the effective date".to_string(),
+            )));
+        }
+
+        Ok(())
+    }
+}
+
+/// The part of the customer balance that does not come from a credit grant, and never expires.
+pub fn unallocated_balance(balance_cents: i32, grants: &[CreditGrant]) -> i64 {
+    let granted: i64 = grants
+        .iter()
+        .filter(|g| g.expired_at.is_none())
+        .map(|g| g.remaining_cents as i64)
+        .sum();
+
+    (balance_cents as i64 - granted).max(0)
+}
+
+/// Allocates the active grants to the invoice, by ascending priority then expiry, up to `amount` in total.
+/// A restricted grant only pays for the lines of its price components or metrics, tax included.
+pub fn allocate_credit_grants(
+    grants: &[CreditGrant],
+    line_items: &[LineItem],
+    amount: i64,
+    invoice_date: NaiveDate,
+) -> Vec<(Uuid, i64)> {
+    let mut left = amount.max(0);
+    // what each line can still receive from restricted grants
+    let mut lines_left = line_items
+        .iter()
+        .map(|l| (l.subtotal + l.tax_amount).max(0))
+        .collect::<Vec<_>>();
+
+    grants
+        .iter()
+        .filter(|g| g.is_active(invoice_date))
+        .sorted_by_key(|g| {
+            (
+                g.priority,
+                g.expiry_date.unwrap_or(NaiveDate::MAX),
+                g.created_at,
+            )
+        })
+        .filter_map(|grant| {
+            if left == 0 {
+                return None;
+            }
+
+            let mut allocated = min(grant.remaining_cents as i64, left);
+
+            if grant.is_restricted() {
+                let mut covered = 0;
+
+                for (line, line_left) in line_items.iter().zip(lines_left.iter_mut()) {
+                    if covered == allocated {
+                        break;
+                    }
+                    if grant.applies_to(line) {
+                        let part = min(*line_left, allocated - covered);
+                        *line_left -= part;
+                        covered += part;
+                    }
+                }
+
+                allocated = covered;
+            }
+
+            if allocated == 0 {
+                return None;
+            }
+
+            left -= allocated;
+
+            Some((grant.id, allocated))
+        })
+        .collect()
+}
+
+/// The credits applied to an invoice of `total`: the active grants first, then the rest of the balance.
+pub fn applicable_credits(
+    balance_cents: i32,
+    grants: &[CreditGrant],
+    line_items: &[LineItem],
+    total: i64,
+    invoice_date: NaiveDate,
+) -> i64 {
+    let granted: i64 = allocate_credit_grants(grants, line_items, total, invoice_date)
+        .iter()
+        .map(|(_, cents)| cents)
+        .sum();
+
+    granted + min(total - granted, unallocated_balance(balance_cents, grants))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::local_id::LocalId;
+    use rust_decimal::Decimal;
+
+    fn date(day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(2024, 1, day).unwrap()
+    }
+
+    fn grant(remaining: i32, priority: i32, expiry: Option<NaiveDate>) -> CreditGrant {
+        CreditGrant {
+            id: Uuid::now_v7(),
+            tenant_id: Uuid::nil(),
+            customer_id: Uuid::nil(),
+            name: "Prepaid credits".to_string(),
+            amount_cents: remaining,
+            remaining_cents: remaining,
+            currency: "EUR".to_string(),
+            priority,
+            effective_date: date(1),
+            expiry_date: expiry,
+            price_component_ids: vec![],
+            metric_ids: vec![],
+            created_at: date(1).and_hms_opt(0, 0, 0).unwrap(),
+            created_by: None,
+            expired_at: None,
+        }
+    }
+
+    fn line(metric_id: Option<Uuid>, subtotal: i64) -> LineItem {
+        LineItem {
+            local_id: LocalId::no_prefix(),
+            name: "line".to_string(),
+            total: subtotal,
+            subtotal,
+            quantity: None,
+            unit_price: None,
+            start_date: date(1),
+            end_date: date(31),
+            sub_lines: vec![],
+            is_prorated: false,
+            price_component_id: None,
+            product_id: None,
+            metric_id,
+            add_on_id: None,
+            description: None,
+            tax_rate: Decimal::ZERO,
+            tax_amount: 0,
+            is_manual: false,
+        }
+    }
+
+    #[test]
+    fn test_allocate_credit_grants() {
+        let metric_id = Uuid::now_v7();
+        let lines = vec![line(None, 1000), line(Some(metric_id), 400)];
+
+        let first = grant(300, 0, Some(date(20)));
+        let second = grant(5000, 1, None);
+        let restricted = CreditGrant {
+            metric_ids: vec![metric_id],
+            ..grant(1000, 0, Some(date(25)))
+        };
+        let expired = grant(1000, 0, Some(date(10)));
+        let upcoming = CreditGrant {
+            effective_date: date(16),
+            ..grant(1000, 0, None)
+        };
+
+        let grants = vec![
+            second.clone(),
+            restricted.clone(),
+            expired,
+            upcoming,
+            first.clone(),
+        ];
+
+        // by priority then expiry, the restricted grant only paying for the usage line
+        let allocated = allocate_credit_grants(&grants, &lines, 1400, date(15));
+        assert_eq!(
+            allocated,
+            vec![(first.id, 300), (restricted.id, 400), (second.id, 700)]
+        );
+
+        // never more than the amount to cover
+        let allocated = allocate_credit_grants(&grants, &lines, 200, date(15));
+        assert_eq!(allocated, vec![(first.id, 200)]);
+
+        assert_eq!(unallocated_balance(7000, &grants), 0);
+        assert_eq!(unallocated_balance(10000, &[first, second]), 4700);
+    }
+
+    #[test]
+    fn test_validate_credit_grant() {
+        let new = CreditGrantNew {
+            tenant_id: Uuid::nil(),
+            customer_id: Uuid::nil(),
+            name: "Prepaid credits".to_string(),
+            amount_cents: 1000,
+            currency: "EUR".to_string(),
+            priority: 0,
+            effective_date: date(1),
+            expiry_date: Some(date(31)),
+            price_component_ids: vec![],
+            metric_ids: vec![],
+            created_by: None,
+        };
+
+        assert!(new.validate("EUR").is_ok());
+        assert!(new.validate("USD").is_err());
+        assert!(CreditGrantNew {
+            expiry_date: Some(date(1)),
+            ..new.clone()
+        }
+        .validate("EUR")
+        .is_err());
+        assert!(CreditGrantNew {
+            amount_cents: 0,
+            ..new
+        }
+        .validate("EUR")
+        .is_err());
+    }
+}
@@ -0,0 +1,508 @@
+use super::enums::CreditNoteStatus;
+use crate::domain::invoice_lines::LineItem;
+use crate::domain::taxes::allocate_pro_rata;
+use crate::domain::Invoice;
+use crate::errors::{StoreError, StoreErrorReport};
+use crate::StoreResult;
+use chrono::NaiveDateTime;
+use diesel_models::credit_notes::{CreditNoteRow, CreditNoteRowNew};
+use error_stack::Report;
+use itertools::Itertools;
+use o2o::o2o;
+use rust_decimal::prelude::ToPrimitive;
+use rust_decimal::{Decimal, RoundingStrategy};
+use uuid::Uuid;
+
+#[derive(Debug, Clone, o2o, PartialEq, Eq)]
+#[try_from_owned(CreditNoteRow, StoreErrorReport)]
+pub struct CreditNote {
+    pub id: Uuid,
+    pub created_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+    pub refunded_amount_cents: Option<i64>,
+    pub credited_amount_cents: Option<i64>,
+    pub currency: String,
+    pub finalized_at: Option<NaiveDateTime>,
+    pub plan_version_id: Option<Uuid>,
+    pub invoice_id: Uuid,
+    pub tenant_id: Uuid,
+    pub customer_id: Uuid,
+    #[from(~.into())]
+    pub status: CreditNoteStatus,
+    pub credit_note_number: Option<String>,
+    pub reason: Option<String>,
+    pub memo: Option<String>,
+    #[from(serde_json::from_value(~).map_err(| e | {
+    StoreError::SerdeError("Failed to deserialize line_items".to_string(), e)
+    }) ?)]
+    pub line_items: Vec<LineItem>,
+    pub subtotal: i64,
+    pub tax_amount: i64,
+    pub total: i64,
+    pub voided_at: Option<NaiveDateTime>,
+    pub pdf_document_id: Option<String>,
+}
+
+impl CreditNote {
+    pub fn credit_type(&self) -> CreditNoteCreditType {
+        if self.refunded_amount_cents.unwrap_or(0) > 0 {
+            CreditNoteCreditType::Refund
+        } else {
+            CreditNoteCreditType::CreditToBalance
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CreditNoteCreditType {
+    /// the credited amount is added to the customer balance, and used on the next invoices
+    CreditToBalance,
+    /// the credited amount was paid back to the customer, outside of Meteroid
+    Refund,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CreditNoteLineRequest {
+    pub local_id: String,
+    // tax excluded, the whole remaining line if empty
+    pub amount: Option<i64>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CreditNoteScope {
+    /// everything that was not credited yet on the invoice
+    Full,
+    /// some invoice lines, fully or partially
+    Lines(Vec<CreditNoteLineRequest>),
+    /// a tax included amount, spread on the invoice lines pro rata of what is left to credit on them
+    Amount(i64),
+}
+
+#[derive(Debug, Clone)]
+pub struct CreditNoteNew {
+    pub tenant_id: Uuid,
+    pub invoice_id: Uuid,
+    pub scope: CreditNoteScope,
+    pub credit_type: CreditNoteCreditType,
+    pub reason: Option<String>,
+    pub memo: Option<String>,
+}
+
+impl CreditNoteNew {
+    pub fn into_row(
+        self,
+        invoice: &Invoice,
+        lines: CreditNoteLines,
+    ) -> StoreResult<CreditNoteRowNew> {
+        let (refunded_amount_cents, credited_amount_cents) = match self.credit_type {
+            CreditNoteCreditType::CreditToBalance => (0, lines.total),
+            CreditNoteCreditType::Refund => (lines.total, 0),
+        };
+
+        Ok(CreditNoteRowNew {
+            id: Uuid::now_v7(),
+            refunded_amount_cents: Some(refunded_amount_cents),
+            credited_amount_cents: Some(credited_amount_cents),
+            currency: invoice.currency.clone(),
+            plan_version_id: invoice.plan_version_id,
+            invoice_id: invoice.id,
+            tenant_id: self.tenant_id,
+            customer_id: invoice.customer_id,
+            status: CreditNoteStatus::Draft.into(),
+            reason: self.reason,
+            memo: self.memo,
+            line_items: serde_json::to_value(&lines.line_items).map_err(|e| {
+                StoreError::SerdeError("Failed to serialize line_items".to_string(), e)
+            })?,
+            subtotal: lines.subtotal,
+            tax_amount: lines.tax_amount,
+            total: lines.total,
+        })
+    }
+}
+
+/// What is left to credit on an invoice line, after the invoice discount and the previous credit notes
+struct CreditableLine<'a> {
+    line: &'a LineItem,
+    // the invoiced amount of the line, net of its share of the invoice discount
+    net_amount: i64,
+    amount: i64,
+    tax_amount: i64,
+}
+
+impl CreditableLine<'_> {
+    fn credit_all(&self) -> (i64, i64) {
+        (self.amount, self.tax_amount)
+    }
+
+    /// Splits a tax included amount, using the tax rate of the line
+    fn credit_total(&self, total: i64) -> (i64, i64) {
+        if total == self.amount + self.tax_amount {
+            return self.credit_all();
+        }
+
+        let amount = (Decimal::from(total) * Decimal::ONE_HUNDRED
+            / (Decimal::ONE_HUNDRED + self.line.tax_rate))
+            .round_dp_with_strategy(0, RoundingStrategy::MidpointAwayFromZero)
+            .to_i64()
+            .unwrap_or(0)
+            .min(self.amount)
+            .max(total - self.tax_amount);
+
+        (amount, total - amount)
+    }
+
+    /// Computes the tax of a tax excluded amount, using the tax rate of the line
+    fn credit_amount(&self, amount: i64) -> (i64, i64) {
+        if amount == self.amount {
+            return self.credit_all();
+        }
+
+        let tax_amount = (Decimal::from(amount) * self.line.tax_rate / Decimal::ONE_HUNDRED)
+            .round_dp_with_strategy(0, RoundingStrategy::MidpointAwayFromZero)
+            .to_i64()
+            .unwrap_or(0)
+            .min(self.tax_amount);
+
+        (amount, tax_amount)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CreditNoteLines {
+    pub line_items: Vec<LineItem>,
+    pub subtotal: i64,
+    pub tax_amount: i64,
+    pub total: i64,
+}
+
+impl CreditNoteLines {
+    /// Computes the lines of a new credit note.
+    /// The credited line amounts are net of the invoice discount, and can never exceed
+    /// what was invoiced minus what was already credited by the other (non voided) credit notes.
+    pub fn compute(
+        invoice: &Invoice,
+        credit_notes: &[CreditNote],
+        scope: &CreditNoteScope,
+    ) -> StoreResult<Self> {
+        let discount = invoice.subtotal + invoice.tax_amount - invoice.total;
+        let subtotals = invoice
+            .line_items
+            .iter()
+            .map(|l| l.subtotal)
+            .collect::<Vec<_>>();
+        let line_discounts = allocate_pro_rata(discount, &subtotals);
+
+        let creditable_lines = invoice
+            .line_items
+            .iter()
+            .zip(line_discounts)
+            .map(|(line, line_discount)| {
+                let (credited_amount, credited_tax_amount) = credit_notes
+                    .iter()
+                    .filter(|cn| cn.status != CreditNoteStatus::Voided)
+                    .flat_map(|cn| cn.line_items.iter())
+                    .filter(|l| l.local_id == line.local_id)
+                    .fold((0, 0), |(amount, tax_amount), l| {
+                        (amount + l.subtotal, tax_amount + l.tax_amount)
+                    });
+
+                CreditableLine {
+                    line,
+                    net_amount: line.subtotal - line_discount,
+                    amount: line.subtotal - line_discount - credited_amount,
+                    tax_amount: line.tax_amount - credited_tax_amount,
+                }
+            })
+            .collect::<Vec<_>>();
+
+        let credited: Vec<(&CreditableLine, (i64, i64))> = match scope {
+            CreditNoteScope::Full => creditable_lines
+                .iter()
+                .filter(|l| l.amount > 0 || l.tax_amount > 0)
+                .map(|l| (l, l.credit_all()))
+                .collect(),
+            CreditNoteScope::Lines(requests) => {
+                if !requests.iter().map(|r| &r.local_id).all_unique() {
+                    return Err(Report::new(StoreError::InvalidArgument(
+                        "an invoice line can only be credited once per credit note".to_string(),
+                    )));
+                }
+
+                requests
+                    .iter()
+                    .map(|request| {
+                        let creditable = creditable_lines
+                            .iter()
+                            .find(|l| l.line.local_id == request.local_id)
+                            .ok_or_else(|| {
+                                StoreError::InvalidArgument(format!(
+                                    "unknown invoice line {}",
+                                    request.local_id
+                                ))
+                            })?;
+
+                        match request.amount {
+                            None => Ok((creditable, creditable.credit_all())),
+                            Some(amount) if amount > 0 && amount <= creditable.amount => {
+                                Ok((creditable, creditable.credit_amount(amount)))
+                            }
+                            Some(_) => Err(Report::new(StoreError::InvalidArgument(format!(
+                                "the credited amount of line {} must be positive and at most {}",
+                                request.local_id, creditable.amount
+                            )))),
+                        }
+                    })
+                    .collect::<StoreResult<Vec<_>>>()?
+            }
+            CreditNoteScope::Amount(amount) => {
+                let remaining = creditable_lines
+                    .iter()
+                    .map(|l| l.amount + l.tax_amount)
+                    .collect::<Vec<_>>();
+                let remaining_total: i64 = remaining.iter().filter(|x| **x > 0).sum();
+
+                if *amount <= 0 || *amount > remaining_total {
+                    return Err(Report::new(StoreError::InvalidArgument(format!(
+                        "the credited amount must be positive and at most {}",
+                        remaining_total
+                    ))));
+                }
+
+                creditable_lines
+                    .iter()
+                    .zip(allocate_pro_rata(*amount, &remaining))
+                    .filter(|(_, total)| *total > 0)
+                    .map(|(l, total)| (l, l.credit_total(total)))
+                    .collect()
+            }
+        };
+
+        let line_items = credited
+            .into_iter()
+            .filter(|(_, (amount, tax_amount))| *amount > 0 || *tax_amount > 0)
+            .map(|(creditable, (amount, tax_amount))| {
+                let line = creditable.line;
+                // quantities are only meaningful if the whole (discounted) line is credited
+                let is_whole_line = amount == creditable.net_amount;
+
+                LineItem {
+                    total: amount,
+                    subtotal: amount,
+                    tax_amount,
+                    quantity: line.quantity.filter(|_| is_whole_line),
+                    unit_price: line.unit_price.filter(|_| is_whole_line),
+                    sub_lines: if is_whole_line {
+                        line.sub_lines.clone()
+                    } else {
+                        vec![]
+                    },
+                    ..line.clone()
+                }
+            })
+            .collect::<Vec<_>>();
+
+        if line_items.is_empty() {
+            return Err(Report::new(StoreError::InvalidArgument(
+                "there is nothing left to credit on this invoice".to_string(),
+            )));
+        }
+
+        let subtotal = line_items.iter().map(|l| l.subtotal).sum();
+        let tax_amount = line_items.iter().map(|l| l.tax_amount).sum();
+
+        Ok(CreditNoteLines {
+            line_items,
+            subtotal,
+            tax_amount,
+            total: subtotal + tax_amount,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::enums::{InvoiceStatusEnum, InvoiceType, InvoicingProviderEnum};
+    use crate::domain::{Address, InlineCustomer, InlineInvoicingEntity};
+    use chrono::NaiveDate;
+    use rust_decimal_macros::dec;
+
+    fn line(local_id: &str, subtotal: i64, tax_amount: i64) -> LineItem {
+        let date = NaiveDate::from_ymd_opt(2024, 1, 1).unwrap();
+        LineItem {
+            local_id: local_id.to_string(),
+            name: local_id.to_string(),
+            total: subtotal,
+            subtotal,
+            quantity: Some(dec!(2)),
+            unit_price: None,
+            start_date: date,
+            end_date: date,
+            sub_lines: vec![],
+            is_prorated: false,
+            price_component_id: None,
+            product_id: None,
+            metric_id: None,
+            add_on_id: None,
+            description: None,
+            tax_rate: dec!(20),
+            tax_amount,
+            is_manual: false,
+        }
+    }
+
+    fn address() -> Address {
+        Address {
+            line1: None,
+            line2: None,
+            city: None,
+            country: None,
+            state: None,
+            zip_code: None,
+        }
+    }
+
+    // 1000 + 2000, with a 300 discount and a 20% tax on the discounted lines
+    fn invoice() -> Invoice {
+        let now = NaiveDateTime::default();
+        Invoice {
+            id: Uuid::nil(),
+            status: InvoiceStatusEnum::Finalized,
+            external_status: None,
+            created_at: now,
+            updated_at: None,
+            tenant_id: Uuid::nil(),
+            customer_id: Uuid::nil(),
+            subscription_id: None,
+            currency: "EUR".to_string(),
+            external_invoice_id: None,
+            invoice_number: "INV-1".to_string(),
+            invoicing_provider: InvoicingProviderEnum::Manual,
+            line_items: vec![line("a", 1000, 180), line("b", 2000, 360)],
+            issued: false,
+            issue_attempts: 0,
+            last_issue_attempt_at: None,
+            last_issue_error: None,
+            data_updated_at: None,
+            invoice_date: now.date(),
+            plan_version_id: None,
+            invoice_type: InvoiceType::Recurring,
+            finalized_at: Some(now),
+            subtotal: 3000,
+            subtotal_recurring: 3000,
+            tax_rate: dec!(20),
+            tax_amount: 540,
+            tax_breakdown: vec![],
+            coupons: vec![],
+            total: 3240,
+            amount_due: 3240,
+            applied_credits: 0,
+            net_terms: 0,
+            reference: None,
+            memo: None,
+            local_id: "inv".to_string(),
+            due_at: None,
+            plan_name: None,
+            customer_details: InlineCustomer {
+                id: Uuid::nil(),
+                name: "customer".to_string(),
+                email: None,
+                alias: None,
+                vat_number: None,
+                billing_address: None,
+                snapshot_at: now,
+            },
+            seller_details: InlineInvoicingEntity {
+                id: Uuid::nil(),
+                legal_name: "seller".to_string(),
+                vat_number: None,
+                address: address(),
+                snapshot_at: now,
+            },
+            pdf_document_id: None,
+            xml_document_id: None,
+            replaces_invoice_id: None,
+        }
+    }
+
+    fn credit_note(lines: CreditNoteLines) -> CreditNote {
+        let now = NaiveDateTime::default();
+        CreditNote {
+            id: Uuid::now_v7(),
+            created_at: now,
+            updated_at: now,
+            refunded_amount_cents: Some(0),
+            credited_amount_cents: Some(lines.total),
+            currency: "EUR".to_string(),
+            finalized_at: None,
+            plan_version_id: None,
+            invoice_id: Uuid::nil(),
+            tenant_id: Uuid::nil(),
+            customer_id: Uuid::nil(),
+            status: CreditNoteStatus::Draft,
+            credit_note_number: None,
+            reason: None,
+            memo: None,
+            line_items: lines.line_items,
+            subtotal: lines.subtotal,
+            tax_amount: lines.tax_amount,
+            total: lines.total,
+            voided_at: None,
+            pdf_document_id: None,
+        }
+    }
+
+    #[test]
+    fn test_full_credit_note() {
+        let lines = CreditNoteLines::compute(&invoice(), &[], &CreditNoteScope::Full).unwrap();
+
+        assert_eq!(lines.total, 3240);
+        assert_eq!(lines.subtotal, 2700);
+        assert_eq!(lines.tax_amount, 540);
+        assert_eq!(lines.line_items[0].subtotal, 900);
+        assert_eq!(lines.line_items[1].subtotal, 1800);
+        // the discounted lines are credited as a whole, so they keep their quantity
+        assert_eq!(lines.line_items[0].quantity, Some(dec!(2)));
+        assert_eq!(lines.line_items[1].quantity, Some(dec!(2)));
+    }
+
+    #[test]
+    fn test_partial_credit_notes() {
+        let invoice = invoice();
+
+        let first = CreditNoteLines::compute(
+            &invoice,
+            &[],
+            &CreditNoteScope::Lines(vec![CreditNoteLineRequest {
+                local_id: "b".to_string(),
+                amount: Some(500),
+            }]),
+        )
+        .unwrap();
+        assert_eq!(first.line_items.len(), 1);
+        // partially credited lines have no quantity
+        assert_eq!(first.line_items[0].quantity, None);
+        assert_eq!(first.subtotal, 500);
+        assert_eq!(first.tax_amount, 100);
+
+        let credit_notes = vec![credit_note(first)];
+
+        let second =
+            CreditNoteLines::compute(&invoice, &credit_notes, &CreditNoteScope::Amount(1200))
+                .unwrap();
+        assert_eq!(second.total, 1200);
+        assert_eq!(second.subtotal, 1000);
+        assert_eq!(second.tax_amount, 200);
+
+        let credit_notes = vec![credit_notes[0].clone(), credit_note(second)];
+
+        let rest =
+            CreditNoteLines::compute(&invoice, &credit_notes, &CreditNoteScope::Full).unwrap();
+        assert_eq!(rest.total, 3240 - 600 - 1200);
+
+        let too_much =
+            CreditNoteLines::compute(&invoice, &credit_notes, &CreditNoteScope::Amount(1441));
+        assert!(too_much.is_err());
+    }
+}
@@ -7,6 +7,7 @@ use serde::{Deserialize, Serialize};
 use serde_json::Value;
 use uuid::Uuid;
 
+use crate::domain::enums::CustomerTaxStatusEnum;
 use crate::errors::StoreError;
 
 #[derive(Clone, Debug, PartialEq, Eq)]
@@ -29,6 +30,8 @@ pub struct Customer {
     pub currency: String,
     pub billing_address: Option<Address>,
     pub shipping_address: Option<ShippingAddress>,
+    pub vat_number: Option<String>,
+    pub tax_status: CustomerTaxStatusEnum,
 }
 
 impl TryFrom<CustomerRow> for Customer {
@@ -54,6 +57,8 @@ impl TryFrom<CustomerRow> for Customer {
             billing_address: value.billing_address.map(|v| v.try_into()).transpose()?,
             shipping_address: value.shipping_address.map(|v| v.try_into()).transpose()?,
             invoicing_entity_id: value.invoicing_entity_id,
+            vat_number: value.vat_number,
+            tax_status: value.tax_status.into(),
         })
     }
 }
@@ -81,6 +86,8 @@ impl TryInto<CustomerRow> for Customer {
             billing_address: self.billing_address.map(|v| v.try_into()).transpose()?,
             shipping_address: self.shipping_address.map(|v| v.try_into()).transpose()?,
             invoicing_entity_id: self.invoicing_entity_id,
+            vat_number: self.vat_number,
+            tax_status: self.tax_status.into(),
         })
     }
 }
@@ -106,6 +113,8 @@ pub struct CustomerNew {
     pub currency: String,
     pub billing_address: Option<Address>,
     pub shipping_address: Option<ShippingAddress>,
+    pub vat_number: Option<String>,
+    pub tax_status: CustomerTaxStatusEnum,
     //
     pub created_by: Uuid,
     pub invoicing_entity_id: Option<Uuid>,
@@ -147,6 +156,8 @@ impl TryInto<CustomerRowNew> for CustomerNewWrapper {
                 .shipping_address
                 .map(|v| v.try_into())
                 .transpose()?,
+            vat_number: self.inner.vat_number,
+            tax_status: self.inner.tax_status.into(),
             created_at: self.inner.force_created_date,
         })
     }
@@ -166,6 +177,9 @@ pub struct CustomerPatch {
     pub billing_address: Option<serde_json::Value>, // TODO avoid json in domain
     pub shipping_address: Option<serde_json::Value>,
     pub invoicing_entity_id: Option<Uuid>,
+    pub vat_number: Option<String>,
+    #[into(~.map(|x| x.into()))]
+    pub tax_status: Option<CustomerTaxStatusEnum>,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
@@ -0,0 +1,210 @@
+use crate::domain::enums::{DunningSubscriptionActionEnum, InvoiceDunningStatusEnum};
+use crate::errors::{StoreError, StoreErrorReport};
+use chrono::NaiveDateTime;
+use diesel_models::dunning::{
+    DunningConfigRow, DunningConfigRowNew, InvoiceDunningRow, InvoiceDunningRowNew,
+};
+use error_stack::Report;
+use o2o::o2o;
+use uuid::Uuid;
+
+/// The dunning policy of an invoicing entity, applied to its overdue or failed invoices.
+#[derive(Debug, Clone, o2o)]
+#[try_from_owned(DunningConfigRow, StoreErrorReport)]
+pub struct DunningConfig {
+    pub id: Uuid,
+    pub tenant_id: Uuid,
+    pub invoicing_entity_id: Uuid,
+    pub enabled: bool,
+    // days after the start of the dunning at which a reminder is sent, ex: [3, 7, 14]
+    #[from(serde_json::from_value(~).map_err(| e | {
+    StoreError::SerdeError("Failed to deserialize retry_schedule_days".to_string(), e)
+    }) ?)]
+    pub retry_schedule_days: Vec<u32>,
+    #[from(~ as u32)]
+    pub end_after_days: u32,
+    #[map(~.into())]
+    pub subscription_action: DunningSubscriptionActionEnum,
+    pub mark_uncollectible: bool,
+    pub created_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+}
+
+#[derive(Debug, Clone, o2o)]
+#[owned_try_into(DunningConfigRowNew, StoreErrorReport)]
+#[ghosts(id: {Uuid::now_v7()})]
+pub struct DunningConfigNew {
+    pub tenant_id: Uuid,
+    pub invoicing_entity_id: Uuid,
+    pub enabled: bool,
+    #[into(serde_json::to_value(& ~).map_err(| e | {
+    StoreError::SerdeError("Failed to serialize retry_schedule_days".to_string(), e)
+    }) ?)]
+    pub retry_schedule_days: Vec<u32>,
+    #[into(~ as i32)]
+    pub end_after_days: u32,
+    #[map(~.into())]
+    pub subscription_action: DunningSubscriptionActionEnum,
+    pub mark_uncollectible: bool,
+}
+
+impl DunningConfigNew {
+    pub fn validate(&self) -> Result<(), StoreErrorReport> {
+        if !self.retry_schedule_days.windows(2).all(|w| w[0] < w[1]) {
+            return Err(Report::new(StoreError::InvalidArgument(
+                "the retry schedule must be strictly increasing".to_string(),
+            )));
+        }
+
+        if self
+            .retry_schedule_days
+            .last()
+            .is_some_and(|last| *last >= self.end_after_days)
+        {
+            return Err(Report::new(StoreError::InvalidArgument(
+                "the retries must happen before the end of the dunning".to_string(),
+            )));
+        }
+
+        if self.end_after_days > i32::MAX as u32 {
+            return Err(Report::new(StoreError::InvalidArgument(
+                "end_after_days is out of range".to_string(),
+            )));
+        }
+
+        Ok(())
+    }
+}
+
+/// What the dunning worker does on an attempt, given the number of attempts already made.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DunningStep {
+    // the 1-based attempt number
+    Reminder(u32),
+    End,
+}
+
+impl DunningConfig {
+    pub fn step(&self, attempts: u32) -> DunningStep {
+        if (attempts as usize) < self.retry_schedule_days.len() {
+            DunningStep::Reminder(attempts + 1)
+        } else {
+            DunningStep::End
+        }
+    }
+
+    /// When the attempt following `attempts` is due.
+    pub fn attempt_at(&self, started_at: NaiveDateTime, attempts: u32) -> NaiveDateTime {
+        let days = self
+            .retry_schedule_days
+            .get(attempts as usize)
+            .copied()
+            .unwrap_or(self.end_after_days);
+
+        started_at + chrono::Duration::days(days as i64)
+    }
+}
+
+#[derive(Debug, Clone, o2o)]
+#[from_owned(InvoiceDunningRow)]
+pub struct InvoiceDunning {
+    pub id: Uuid,
+    pub tenant_id: Uuid,
+    pub invoice_id: Uuid,
+    #[map(~.into())]
+    pub status: InvoiceDunningStatusEnum,
+    #[from(~ as u32)]
+    pub attempts: u32,
+    pub started_at: NaiveDateTime,
+    pub next_attempt_at: NaiveDateTime,
+    pub last_attempt_at: Option<NaiveDateTime>,
+    pub ended_at: Option<NaiveDateTime>,
+}
+
+#[derive(Debug, Clone, o2o)]
+#[owned_into(InvoiceDunningRowNew)]
+#[ghosts(id: {Uuid::now_v7()})]
+pub struct InvoiceDunningNew {
+    pub tenant_id: Uuid,
+    pub invoice_id: Uuid,
+    pub started_at: NaiveDateTime,
+    pub next_attempt_at: NaiveDateTime,
+}
+
+#[derive(Debug, Clone)]
+pub enum InvoiceDunningOutcome {
+    Reminded {
+        invoice_id: Uuid,
+        attempt: u32,
+    },
+    Ended {
+        invoice_id: Uuid,
+        status: InvoiceDunningStatusEnum,
+        subscription_action: Option<DunningSubscriptionActionEnum>,
+    },
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(retry_schedule_days: Vec<u32>, end_after_days: u32) -> DunningConfigNew {
+        DunningConfigNew {
+            tenant_id: Uuid::now_v7(),
+            invoicing_entity_id: Uuid::now_v7(),
+            enabled: true,
+            retry_schedule_days,
+            end_after_days,
+            subscription_action: DunningSubscriptionActionEnum::Cancel,
+            mark_uncollectible: true,
+        }
+    }
+
+    #[test]
+    fn test_validate_dunning_config() {
+        assert!(config(vec![], 0).validate().is_ok());
+        assert!(config(vec![1, 3, 7], 14).validate().is_ok());
+        assert!(config(vec![3, 3], 14).validate().is_err());
+        assert!(config(vec![7, 3], 14).validate().is_err());
+        assert!(config(vec![3, 14], 14).validate().is_err());
+    }
+
+    #[test]
+    fn test_dunning_schedule() {
+        let new = config(vec![1, 3, 7], 14);
+        let config = DunningConfig {
+            id: Uuid::now_v7(),
+            tenant_id: new.tenant_id,
+            invoicing_entity_id: new.invoicing_entity_id,
+            enabled: new.enabled,
+            retry_schedule_days: new.retry_schedule_days,
+            end_after_days: new.end_after_days,
+            subscription_action: new.subscription_action,
+            mark_uncollectible: new.mark_uncollectible,
+            created_at: NaiveDateTime::default(),
+            updated_at: NaiveDateTime::default(),
+        };
+
+        let started_at = chrono::NaiveDate::from_ymd_opt(2024, 1, 1)
+            .unwrap()
+            .and_hms_opt(0, 0, 0)
+            .unwrap();
+
+        assert_eq!(config.step(0), DunningStep::Reminder(1));
+        assert_eq!(config.step(2), DunningStep::Reminder(3));
+        assert_eq!(config.step(3), DunningStep::End);
+
+        assert_eq!(
+            config.attempt_at(started_at, 0),
+            started_at + chrono::Duration::days(1)
+        );
+        assert_eq!(
+            config.attempt_at(started_at, 2),
+            started_at + chrono::Duration::days(7)
+        );
+        assert_eq!(
+            config.attempt_at(started_at, 3),
+            started_at + chrono::Duration::days(14)
+        );
+    }
+}
@@ -21,6 +21,7 @@ pub enum BillingMetricAggregateEnum {
     Mean,
     Sum,
     CountDistinct,
+    SumOverTime,
 }
 
 #[derive(o2o, Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
@@ -61,7 +62,20 @@ impl Ord for BillingPeriodEnum {
     }
 }
 
-#[derive(o2o, Serialize, Deserialize, Debug, Clone)]
+/// How a coupon combines with the other coupons applied to the same invoice.
+#[derive(o2o, Serialize, Deserialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[map_owned(diesel_enums::CouponStackingEnum)]
+pub enum CouponStackingEnum {
+    /// combines with all the other stackable coupons
+    #[default]
+    Stackable,
+    /// applies alone, the other coupons are ignored
+    Exclusive,
+    /// only the largest of the best-of discounts applies, along with the stackable coupons
+    BestOf,
+}
+
+#[derive(o2o, Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
 #[map_owned(diesel_enums::CreditNoteStatus)]
 pub enum CreditNoteStatus {
     Draft,
@@ -69,6 +83,23 @@ pub enum CreditNoteStatus {
     Voided,
 }
 
+#[derive(o2o, Serialize, Deserialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[map_owned(diesel_enums::CustomerTaxStatusEnum)]
+pub enum CustomerTaxStatusEnum {
+    #[default]
+    Taxable,
+    Exempt,
+    ReverseCharge,
+}
+
+#[derive(o2o, Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[map_owned(diesel_enums::DunningSubscriptionActionEnum)]
+pub enum DunningSubscriptionActionEnum {
+    None,
+    Pause,
+    Cancel,
+}
+
 #[derive(o2o, Serialize, Deserialize, Debug, Clone)]
 #[map_owned(diesel_enums::FangTaskState)]
 pub enum FangTaskState {
@@ -79,6 +110,15 @@ pub enum FangTaskState {
     Retried,
 }
 
+#[derive(o2o, Serialize, Deserialize, Debug, Clone, Eq, PartialEq)]
+#[map_owned(diesel_enums::InvoiceDunningStatusEnum)]
+pub enum InvoiceDunningStatusEnum {
+    Active,
+    Recovered,
+    Exhausted,
+    Aborted,
+}
+
 #[derive(o2o, Serialize, Deserialize, Debug, Clone, Eq, PartialEq)]
 #[map_owned(diesel_enums::InvoiceExternalStatusEnum)]
 pub enum InvoiceExternalStatusEnum {
@@ -108,6 +148,7 @@ pub enum InvoiceType {
     Adjustment,
     // Imported,
     UsageThreshold,
+    Commitment,
 }
 
 #[derive(o2o, Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
@@ -180,6 +221,15 @@ pub enum WebhookOutEventTypeEnum {
     SubscriptionCreated,
     InvoiceCreated,
     InvoiceFinalized,
+    SubscriptionTrialEnded,
+    InvoicePaymentReminder,
+}
+
+#[derive(o2o, Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[map_owned(diesel_enums::SubscriptionCommitmentTypeEnum)]
+pub enum SubscriptionCommitmentTypeEnum {
+    Prepaid,
+    Postpaid,
 }
 
 #[derive(o2o, Serialize, Deserialize, Debug, Clone)]
@@ -191,6 +241,8 @@ pub enum SubscriptionEventType {
     Cancelled,
     Reactivated,
     Updated,
+    Paused,
+    Resumed,
 }
 
 #[derive(o2o, Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Hash)]
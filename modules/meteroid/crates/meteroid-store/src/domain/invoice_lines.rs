@@ -1,3 +1,8 @@
+use crate::errors::StoreError;
+use crate::StoreResult;
+use chrono::NaiveDate;
+use common_domain::money::{Currency, Rounding};
+use error_stack::Report;
 use rust_decimal::Decimal;
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
@@ -22,8 +27,90 @@ pub struct LineItem {
     pub price_component_id: Option<Uuid>, // local_id ?
     pub product_id: Option<Uuid>,
     pub metric_id: Option<Uuid>,
+    // set on the lines of the add-ons of the subscription
+    pub add_on_id: Option<Uuid>,
 
     pub description: Option<String>,
+
+    #[serde(default)]
+    pub tax_rate: Decimal,
+    #[serde(default)]
+    pub tax_amount: i64,
+
+    // added by hand on a draft, kept as is when the invoice is refreshed
+    #[serde(default)]
+    pub is_manual: bool,
+}
+
+/// The discount of one of the coupons applied to an invoice.
+#[derive(PartialEq, Debug, Deserialize, Serialize, Eq, Clone)]
+pub struct CouponLineItem {
+    pub coupon_id: Uuid,
+    pub applied_coupon_id: Uuid,
+    pub code: String,
+    pub value: i64,
+}
+
+/// A line added by hand to a draft invoice, priced in major units.
+#[derive(Debug, Clone)]
+pub struct ManualLineItem {
+    pub name: String,
+    pub description: Option<String>,
+    pub quantity: Decimal,
+    pub unit_price: Decimal,
+    pub start_date: NaiveDate,
+    pub end_date: NaiveDate,
+    pub product_id: Option<Uuid>,
+}
+
+impl ManualLineItem {
+    pub fn to_line_item(&self, local_id: String, currency: &str) -> StoreResult<LineItem> {
+        if self.name.trim().is_empty() {
+            return Err(Report::new(StoreError::InvalidArgument(
+                "the name of a line is required".to_string(),
+            )));
+        }
+
+        if self.quantity.is_sign_negative() {
+            return Err(Report::new(StoreError::InvalidArgument(
+                "the quantity of a line must not be negative".to_string(),
+            )));
+        }
+
+        if self.end_date < self.start_date {
+            return Err(Report::new(StoreError::InvalidArgument(
+                "the end date of a line must not be before its start date".to_string(),
+            )));
+        }
+
+        let total = Currency::from_code(currency)
+            .and_then(|c| c.to_minor(self.quantity * self.unit_price, Rounding::HalfAwayFromZero))
+            .ok_or(Report::new(StoreError::InvalidArgument(format!(
+                "cannot convert the line amount to {}",
+                currency
+            ))))?;
+
+        Ok(LineItem {
+            local_id,
+            name: self.name.clone(),
+            total,
+            subtotal: total,
+            quantity: Some(self.quantity),
+            unit_price: Some(self.unit_price),
+            start_date: self.start_date,
+            end_date: self.end_date,
+            sub_lines: vec![],
+            is_prorated: false,
+            price_component_id: None,
+            product_id: self.product_id,
+            metric_id: None,
+            add_on_id: None,
+            description: self.description.clone(),
+            tax_rate: Decimal::ZERO,
+            tax_amount: 0,
+            is_manual: true,
+        })
+    }
 }
 
 #[derive(PartialEq, Debug, Deserialize, Serialize, Eq, Clone)]
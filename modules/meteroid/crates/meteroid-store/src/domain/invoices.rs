@@ -1,12 +1,17 @@
 use super::enums::{
-    InvoiceExternalStatusEnum, InvoiceStatusEnum, InvoiceType, InvoicingProviderEnum,
+    CouponStackingEnum, InvoiceExternalStatusEnum, InvoiceStatusEnum, InvoiceType,
+    InvoicingProviderEnum,
 };
 use crate::domain::coupons::CouponDiscount;
-use crate::domain::invoice_lines::LineItem;
+use crate::domain::credit_grants::{applicable_credits, CreditGrant};
+use crate::domain::invoice_lines::{CouponLineItem, LineItem, ManualLineItem};
+use crate::domain::taxes::{LineTaxes, ResolvedTax, TaxBreakdownItem};
 use crate::domain::{Address, AppliedCouponDetailed, Customer, PlanVersionLatest};
 use crate::errors::{StoreError, StoreErrorReport};
-use crate::utils::decimals::ToSubunit;
-use chrono::{NaiveDate, NaiveDateTime};
+use crate::utils::local_id::{IdType, LocalId};
+use crate::StoreResult;
+use chrono::{NaiveDate, NaiveDateTime, NaiveTime};
+use common_domain::money::{Currency, Rounding};
 use diesel_models::invoices::DetailedInvoiceRow;
 use diesel_models::invoices::InvoiceRow;
 use diesel_models::invoices::InvoiceRowLinesPatch;
@@ -55,8 +60,16 @@ pub struct Invoice {
     pub finalized_at: Option<NaiveDateTime>,
     pub subtotal: i64,
     pub subtotal_recurring: i64,
-    pub tax_rate: i32, // TODO decimal, I guess we need to support more than 2 dec
+    pub tax_rate: Decimal,
     pub tax_amount: i64,
+    #[from(serde_json::from_value(~).map_err(| e | {
+    StoreError::SerdeError("Failed to deserialize tax_breakdown".to_string(), e)
+    }) ?)]
+    pub tax_breakdown: Vec<TaxBreakdownItem>,
+    #[from(serde_json::from_value(~).map_err(| e | {
+    StoreError::SerdeError("Failed to deserialize coupons".to_string(), e)
+    }) ?)]
+    pub coupons: Vec<CouponLineItem>,
     pub total: i64,
     pub amount_due: i64,
     pub applied_credits: i64,
@@ -77,6 +90,8 @@ pub struct Invoice {
     pub seller_details: InlineInvoicingEntity,
     pub pdf_document_id: Option<String>,
     pub xml_document_id: Option<String>,
+    /// The voided invoice this one corrects.
+    pub replaces_invoice_id: Option<Uuid>,
 }
 
 #[derive(Debug, o2o)]
@@ -113,8 +128,16 @@ pub struct InvoiceNew {
     pub finalized_at: Option<NaiveDateTime>,
     pub subtotal: i64,
     pub subtotal_recurring: i64,
-    pub tax_rate: i32,
+    pub tax_rate: Decimal,
     pub tax_amount: i64,
+    #[into(serde_json::to_value(& ~).map_err(| e | {
+    StoreError::SerdeError("Failed to serialize tax_breakdown".to_string(), e)
+    }) ?)]
+    pub tax_breakdown: Vec<TaxBreakdownItem>,
+    #[into(serde_json::to_value(& ~).map_err(| e | {
+    StoreError::SerdeError("Failed to serialize coupons".to_string(), e)
+    }) ?)]
+    pub coupons: Vec<CouponLineItem>,
     pub total: i64,
     pub amount_due: i64,
     pub net_terms: i32,
@@ -131,6 +154,7 @@ pub struct InvoiceNew {
     StoreError::SerdeError("Failed to serialize seller_details".to_string(), e)
     }) ?)]
     pub seller_details: InlineInvoicingEntity,
+    pub replaces_invoice_id: Option<Uuid>,
 }
 
 #[derive(Debug, o2o)]
@@ -144,10 +168,17 @@ pub struct InvoiceLinesPatch {
     pub subtotal: i64,
     pub subtotal_recurring: i64,
     pub total: i64,
+    pub tax_rate: Decimal,
     pub tax_amount: i64,
+    #[into(serde_json::to_value(& ~).map_err(| e | {
+    StoreError::SerdeError("Failed to serialize tax_breakdown".to_string(), e)
+    }) ?)]
+    pub tax_breakdown: Vec<TaxBreakdownItem>,
+    #[into(serde_json::to_value(& ~).map_err(| e | {
+    StoreError::SerdeError("Failed to serialize coupons".to_string(), e)
+    }) ?)]
+    pub coupons: Vec<CouponLineItem>,
     pub applied_credits: i64,
-    #[ghost({vec![]})]
-    pub applied_coupons: Vec<(Uuid, i64)>,
 }
 
 impl InvoiceLinesPatch {
@@ -155,26 +186,39 @@ impl InvoiceLinesPatch {
         detailed_invoice: &DetailedInvoice,
         line_items: Vec<LineItem>,
         applied_coupons: &[AppliedCouponDetailed],
+        tax: &ResolvedTax,
+        credit_grants: &[CreditGrant],
     ) -> Self {
         let totals = InvoiceTotals::from_params(InvoiceTotalsParams {
             line_items: &line_items,
             total: detailed_invoice.invoice.total,
             amount_due: detailed_invoice.invoice.amount_due,
-            tax_rate: detailed_invoice.invoice.tax_rate,
-            customer_balance_cents: detailed_invoice.customer.balance_value_cents,
+            tax,
+            // the credits are applied below, as the grants depend on the lines
+            customer_balance_cents: 0,
             subscription_applied_coupons: &applied_coupons.to_vec(),
             invoice_currency: detailed_invoice.invoice.currency.as_str(),
         });
 
+        let applied_credits = applicable_credits(
+            detailed_invoice.customer.balance_value_cents,
+            credit_grants,
+            &totals.line_items,
+            totals.total,
+            detailed_invoice.invoice.invoice_date,
+        );
+
         InvoiceLinesPatch {
-            line_items,
-            amount_due: totals.amount_due,
+            line_items: totals.line_items,
+            amount_due: totals.amount_due - applied_credits,
             subtotal: totals.subtotal,
             subtotal_recurring: totals.subtotal_recurring,
             total: totals.total,
+            tax_rate: tax.rate,
             tax_amount: totals.tax_amount,
-            applied_credits: totals.applied_credits,
-            applied_coupons: totals.applied_coupons,
+            tax_breakdown: totals.tax_breakdown,
+            coupons: totals.coupons,
+            applied_credits,
         }
     }
 }
@@ -235,41 +279,184 @@ impl TryFrom<DetailedInvoiceRow> for DetailedInvoice {
     }
 }
 
+/// A standalone invoice of a customer, created as a draft with lines added by hand.
+#[derive(Debug, Clone)]
+pub struct OneOffInvoiceNew {
+    pub tenant_id: Uuid,
+    pub customer_id: Uuid,
+    pub invoice_date: NaiveDate,
+    pub line_items: Vec<ManualLineItem>,
+    pub reference: Option<String>,
+    pub memo: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+pub enum InvoiceLineEdit {
+    Add(ManualLineItem),
+    Update {
+        local_id: String,
+        line: ManualLineItem,
+    },
+    Remove {
+        local_id: String,
+    },
+}
+
+impl Invoice {
+    /// Applies the edit to the lines of a draft invoice. The lines computed from the subscription
+    /// of a recurring invoice cannot be edited, as they are recomputed when the invoice is refreshed.
+    pub fn edit_line(&mut self, edit: InvoiceLineEdit) -> StoreResult<()> {
+        if !matches!(
+            self.status,
+            InvoiceStatusEnum::Draft | InvoiceStatusEnum::Pending
+        ) {
+            return Err(Report::new(StoreError::InvalidArgument(
+                "only the lines of a draft invoice can be edited".to_string(),
+            )));
+        }
+
+        match edit {
+            InvoiceLineEdit::Add(line) => {
+                let line = line.to_line_item(LocalId::no_prefix(), &self.currency)?;
+                self.line_items.push(line);
+            }
+            InvoiceLineEdit::Update { local_id, line } => {
+                let idx = self.editable_line_idx(&local_id)?;
+                self.line_items[idx] = line.to_line_item(local_id, &self.currency)?;
+            }
+            InvoiceLineEdit::Remove { local_id } => {
+                let idx = self.editable_line_idx(&local_id)?;
+                self.line_items.remove(idx);
+            }
+        }
+
+        Ok(())
+    }
+
+    fn editable_line_idx(&self, local_id: &str) -> StoreResult<usize> {
+        let idx = self
+            .line_items
+            .iter()
+            .position(|l| l.local_id == local_id)
+            .ok_or(Report::new(StoreError::ValueNotFound(format!(
+                "line {} not found",
+                local_id
+            ))))?;
+
+        if self.invoice_type == InvoiceType::Recurring && !self.line_items[idx].is_manual {
+            return Err(Report::new(StoreError::InvalidArgument(
+                "the computed lines of a recurring invoice cannot be edited".to_string(),
+            )));
+        }
+
+        Ok(idx)
+    }
+}
+
+/// A voided invoice, and the draft created to correct it if it was reissued.
+#[derive(Debug, Clone)]
+pub struct VoidedInvoice {
+    pub invoice: DetailedInvoice,
+    pub replacement: Option<DetailedInvoice>,
+}
+
+impl Invoice {
+    /// The draft reissuing this invoice, dated `invoice_date` so that it can be corrected before its finalization.
+    /// A recurring invoice is reissued as an adjustment, so that its lines are kept and can be edited
+    /// instead of being recomputed for the new date.
+    pub fn replacement_draft(&self, invoice_date: NaiveDate) -> InvoiceNew {
+        let invoice_type = match self.invoice_type {
+            InvoiceType::Recurring => InvoiceType::Adjustment,
+            ref other => other.clone(),
+        };
+
+        InvoiceNew {
+            status: InvoiceStatusEnum::Draft,
+            external_status: None,
+            tenant_id: self.tenant_id,
+            customer_id: self.customer_id,
+            subscription_id: self.subscription_id,
+            currency: self.currency.clone(),
+            external_invoice_id: None,
+            invoice_number: "draft".to_string(),
+            invoicing_provider: self.invoicing_provider.clone(),
+            line_items: self.line_items.clone(),
+            issued: false,
+            issue_attempts: 0,
+            last_issue_attempt_at: None,
+            last_issue_error: None,
+            data_updated_at: None,
+            invoice_date,
+            plan_version_id: self.plan_version_id,
+            invoice_type,
+            finalized_at: None,
+            subtotal: self.subtotal,
+            subtotal_recurring: self.subtotal_recurring,
+            tax_rate: self.tax_rate,
+            tax_amount: self.tax_amount,
+            tax_breakdown: self.tax_breakdown.clone(),
+            coupons: self.coupons.clone(),
+            total: self.total,
+            // the credits are applied on finalization, with the balance at that time
+            amount_due: self.total,
+            net_terms: self.net_terms,
+            reference: self.reference.clone(),
+            memo: self.memo.clone(),
+            local_id: LocalId::generate_for(IdType::Invoice),
+            due_at: Some(
+                (invoice_date + chrono::Duration::days(self.net_terms as i64))
+                    .and_time(NaiveTime::MIN),
+            ),
+            plan_name: self.plan_name.clone(),
+            customer_details: self.customer_details.clone(),
+            seller_details: self.seller_details.clone(),
+            replaces_invoice_id: Some(self.id),
+        }
+    }
+}
+
 pub struct InvoiceTotalsParams<'a> {
     pub line_items: &'a Vec<LineItem>,
     pub subscription_applied_coupons: &'a Vec<AppliedCouponDetailed>,
     pub total: i64,
     pub amount_due: i64,
-    pub tax_rate: i32,
+    pub tax: &'a ResolvedTax,
     pub customer_balance_cents: i32,
     pub invoice_currency: &'a str,
 }
 
 pub struct InvoiceTotals {
+    pub line_items: Vec<LineItem>,
     pub amount_due: i64,
     pub subtotal: i64,
     pub subtotal_recurring: i64,
     pub total: i64,
     pub tax_amount: i64,
+    pub tax_breakdown: Vec<TaxBreakdownItem>,
     pub applied_credits: i64,
-    pub applied_coupons: Vec<(Uuid, i64)>,
+    pub coupons: Vec<CouponLineItem>,
 }
 
 struct AppliedCouponsDiscount {
     pub discount_subunit: i64,
-    pub applied_coupons: Vec<(Uuid, i64)>,
+    pub coupons: Vec<CouponLineItem>,
 }
 
 impl InvoiceTotals {
     pub fn from_params(params: InvoiceTotalsParams) -> Self {
         let subtotal = params.line_items.iter().fold(0, |acc, x| acc + x.subtotal);
         let coupons_discount = Self::calculate_coupons_discount(
-            subtotal,
+            params.line_items,
             params.invoice_currency,
             params.subscription_applied_coupons,
         );
         let subtotal_with_discounts = subtotal - coupons_discount.discount_subunit;
-        let tax_amount = subtotal_with_discounts * params.tax_rate as i64 / 100;
+        let line_taxes = LineTaxes::compute(
+            params.line_items,
+            coupons_discount.discount_subunit,
+            params.tax,
+        );
+        let tax_amount = line_taxes.tax_amount;
 
         let total = subtotal_with_discounts + tax_amount;
         let applied_credits = min(total, params.customer_balance_cents as i64);
@@ -282,69 +469,569 @@ impl InvoiceTotals {
             .fold(0, |acc, x| acc + x.subtotal);
 
         Self {
+            line_items: line_taxes.line_items,
             amount_due,
             subtotal,
             subtotal_recurring,
             total,
             tax_amount,
+            tax_breakdown: line_taxes.breakdown,
             applied_credits,
-            applied_coupons: coupons_discount.applied_coupons,
+            coupons: coupons_discount.coupons,
         }
     }
 
     fn calculate_coupons_discount(
-        subtotal: i64,
+        line_items: &[LineItem],
         invoice_currency: &str,
         coupons: &[AppliedCouponDetailed],
     ) -> AppliedCouponsDiscount {
+        let subtotal = line_items.iter().fold(0, |acc, x| acc + x.subtotal);
+
         let applicable_coupons: Vec<&AppliedCouponDetailed> = coupons
             .iter()
             .filter(|x| x.is_invoice_applicable())
-            .sorted_by_key(|x| x.applied_coupon.created_at)
+            .sorted_by_key(|x| {
+                (
+                    x.coupon.application_order(),
+                    x.applied_coupon.created_at,
+                    x.applied_coupon.id,
+                )
+            })
             .collect::<Vec<_>>();
 
-        let mut applied_coupons_amount = vec![];
+        let applicable_coupons =
+            Self::apply_stacking_rules(line_items, invoice_currency, applicable_coupons);
 
-        let mut subtotal_subunits = Decimal::from(subtotal);
+        let mut applied_coupons = vec![];
+
+        // the discounts are capped by what is left of the subtotal, so that it never goes negative
+        let mut remaining_subunits = subtotal;
 
         for applicable_coupon in applicable_coupons {
-            if subtotal_subunits <= Decimal::ONE {
+            if remaining_subunits <= 0 {
                 break;
             }
-            let discount = match &applicable_coupon.coupon.discount {
-                CouponDiscount::Percentage(percentage) => {
-                    subtotal_subunits * percentage / Decimal::ONE_HUNDRED
-                }
-                CouponDiscount::Fixed { amount, currency } => {
-                    // todo currency conversion
-                    if currency != invoice_currency {
-                        continue;
-                    }
-                    // todo domain should use Currency type instead of string
-                    let cur = rusty_money::iso::find(currency).unwrap_or(rusty_money::iso::USD);
-
-                    let consumed_amount = &applicable_coupon
-                        .applied_coupon
-                        .applied_amount
-                        .unwrap_or(Decimal::ZERO);
-
-                    let discount_subunits = (amount - consumed_amount)
-                        .to_subunit_opt(cur.exponent as u8)
-                        .unwrap_or(0);
-
-                    Decimal::from(discount_subunits).min(subtotal_subunits)
-                }
+
+            let Some(discount) = Self::coupon_discount(
+                line_items,
+                invoice_currency,
+                applicable_coupon,
+                remaining_subunits,
+            ) else {
+                continue;
             };
 
-            subtotal_subunits -= discount;
+            remaining_subunits -= discount;
 
-            let discount = discount.to_i64().unwrap_or(0);
-            applied_coupons_amount.push((applicable_coupon.applied_coupon.id, discount));
+            applied_coupons.push(CouponLineItem {
+                coupon_id: applicable_coupon.coupon.id,
+                applied_coupon_id: applicable_coupon.applied_coupon.id,
+                code: applicable_coupon.coupon.code.clone(),
+                value: discount,
+            });
         }
 
         AppliedCouponsDiscount {
-            discount_subunit: applied_coupons_amount.iter().map(|x| x.1).sum(),
-            applied_coupons: applied_coupons_amount,
+            discount_subunit: applied_coupons.iter().map(|x| x.value).sum(),
+            coupons: applied_coupons,
+        }
+    }
+
+    /// Keeps the first exclusive coupon with a discount alone if there is one, otherwise the stackable coupons
+    /// and the best-of coupon with the largest discount. The order of the coupons is preserved.
+    fn apply_stacking_rules<'a>(
+        line_items: &[LineItem],
+        invoice_currency: &str,
+        coupons: Vec<&'a AppliedCouponDetailed>,
+    ) -> Vec<&'a AppliedCouponDetailed> {
+        let subtotal = line_items.iter().fold(0, |acc, x| acc + x.subtotal);
+        let standalone_discount = |coupon: &AppliedCouponDetailed| {
+            Self::coupon_discount(line_items, invoice_currency, coupon, subtotal).unwrap_or(0)
+        };
+
+        if let Some(exclusive) = coupons.iter().find(|x| {
+            x.coupon.stacking == CouponStackingEnum::Exclusive && standalone_discount(x) > 0
+        }) {
+            return vec![*exclusive];
+        }
+
+        // on a tie, the first best-of coupon in order wins
+        let mut best_of: Option<(Uuid, i64)> = None;
+        for coupon in coupons
+            .iter()
+            .filter(|x| x.coupon.stacking == CouponStackingEnum::BestOf)
+        {
+            let discount = standalone_discount(coupon);
+            if best_of.is_none_or(|(_, best)| discount > best) {
+                best_of = Some((coupon.applied_coupon.id, discount));
+            }
+        }
+
+        coupons
+            .into_iter()
+            .filter(|x| match x.coupon.stacking {
+                CouponStackingEnum::Stackable => true,
+                CouponStackingEnum::BestOf => {
+                    best_of.is_some_and(|(id, _)| id == x.applied_coupon.id)
+                }
+                CouponStackingEnum::Exclusive => false,
+            })
+            .collect()
+    }
+
+    /// The discount of a coupon on the lines it applies to, at most `remaining_subunits`.
+    /// None if the coupon does not apply to the invoice.
+    fn coupon_discount(
+        line_items: &[LineItem],
+        invoice_currency: &str,
+        coupon: &AppliedCouponDetailed,
+        remaining_subunits: i64,
+    ) -> Option<i64> {
+        // a coupon restricted to some components or add-ons only discounts their lines
+        let applies_to = &coupon.coupon.applies_to;
+        let discountable_subunits = if applies_to.restricts_lines() {
+            line_items
+                .iter()
+                .filter(|l| applies_to.applies_to_line(l))
+                .fold(0, |acc, x| acc + x.subtotal)
+                .min(remaining_subunits)
+        } else {
+            remaining_subunits
+        };
+
+        if discountable_subunits <= 0 {
+            return None;
+        }
+
+        let discount = match &coupon.coupon.discount {
+            CouponDiscount::Percentage(percentage) => {
+                Decimal::from(discountable_subunits) * percentage / Decimal::ONE_HUNDRED
+            }
+            CouponDiscount::Fixed { amount, currency } => {
+                // todo currency conversion
+                if currency != invoice_currency {
+                    return None;
+                }
+                let cur = Currency::from_code(currency)?;
+
+                let consumed_amount = &coupon
+                    .applied_coupon
+                    .applied_amount
+                    .unwrap_or(Decimal::ZERO);
+
+                let discount_subunits = cur
+                    .to_minor(amount - consumed_amount, Rounding::HalfAwayFromZero)
+                    .unwrap_or(0);
+
+                Decimal::from(discount_subunits)
+            }
+        };
+
+        Some(
+            discount
+                .trunc()
+                .to_i64()
+                .unwrap_or(0)
+                .clamp(0, discountable_subunits),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::coupons::{Coupon, CouponAppliesTo};
+    use crate::domain::AppliedCoupon;
+
+    fn date(day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(2024, 11, day).unwrap()
+    }
+
+    fn manual_line(name: &str, quantity: i64, unit_price: Decimal) -> ManualLineItem {
+        ManualLineItem {
+            name: name.to_string(),
+            description: None,
+            quantity: Decimal::from(quantity),
+            unit_price,
+            start_date: date(1),
+            end_date: date(30),
+            product_id: None,
+        }
+    }
+
+    fn invoice(invoice_type: InvoiceType, line_items: Vec<LineItem>) -> Invoice {
+        let now = date(1).and_hms_opt(0, 0, 0).unwrap();
+
+        Invoice {
+            id: Uuid::now_v7(),
+            status: InvoiceStatusEnum::Draft,
+            external_status: None,
+            created_at: now,
+            updated_at: None,
+            tenant_id: Uuid::nil(),
+            customer_id: Uuid::nil(),
+            subscription_id: None,
+            currency: "EUR".to_string(),
+            external_invoice_id: None,
+            invoice_number: "draft".to_string(),
+            invoicing_provider: InvoicingProviderEnum::Manual,
+            line_items,
+            issued: false,
+            issue_attempts: 0,
+            last_issue_attempt_at: None,
+            last_issue_error: None,
+            data_updated_at: None,
+            invoice_date: date(30),
+            plan_version_id: None,
+            invoice_type,
+            finalized_at: None,
+            subtotal: 0,
+            subtotal_recurring: 0,
+            tax_rate: Decimal::ZERO,
+            tax_amount: 0,
+            tax_breakdown: vec![],
+            coupons: vec![],
+            total: 0,
+            amount_due: 0,
+            applied_credits: 0,
+            net_terms: 0,
+            reference: None,
+            memo: None,
+            local_id: LocalId::no_prefix(),
+            due_at: None,
+            plan_name: None,
+            customer_details: InlineCustomer {
+                id: Uuid::nil(),
+                name: "Customer".to_string(),
+                email: None,
+                alias: None,
+                vat_number: None,
+                billing_address: None,
+                snapshot_at: now,
+            },
+            seller_details: InlineInvoicingEntity {
+                id: Uuid::nil(),
+                legal_name: "Seller".to_string(),
+                vat_number: None,
+                address: Address {
+                    line1: None,
+                    line2: None,
+                    city: None,
+                    country: None,
+                    state: None,
+                    zip_code: None,
+                },
+                snapshot_at: now,
+            },
+            pdf_document_id: None,
+            xml_document_id: None,
+            replaces_invoice_id: None,
+        }
+    }
+
+    fn applied_coupon(
+        discount: CouponDiscount,
+        applies_to: CouponAppliesTo,
+    ) -> AppliedCouponDetailed {
+        let now = date(1).and_hms_opt(0, 0, 0).unwrap();
+        let coupon = Coupon {
+            id: Uuid::now_v7(),
+            code: "SEATS".to_string(),
+            description: String::new(),
+            tenant_id: Uuid::nil(),
+            discount,
+            expires_at: None,
+            redemption_limit: None,
+            recurring_value: None,
+            reusable: true,
+            created_at: now,
+            updated_at: now,
+            last_redemption_at: None,
+            archived_at: None,
+            applies_to,
+            duration_months: None,
+            stacking: CouponStackingEnum::Stackable,
+            priority: 0,
+        };
+        AppliedCouponDetailed {
+            applied_coupon: AppliedCoupon {
+                id: Uuid::now_v7(),
+                coupon_id: coupon.id,
+                customer_id: Uuid::nil(),
+                subscription_id: Uuid::nil(),
+                is_active: true,
+                applied_amount: None,
+                applied_count: None,
+                last_applied_at: None,
+                created_at: now,
+                promotion_code_id: None,
+            },
+            coupon,
+        }
+    }
+
+    fn stacked_coupon(
+        discount: CouponDiscount,
+        stacking: CouponStackingEnum,
+        priority: i32,
+    ) -> AppliedCouponDetailed {
+        let mut applied = applied_coupon(discount, CouponAppliesTo::default());
+        applied.coupon.stacking = stacking;
+        applied.coupon.priority = priority;
+        applied
+    }
+
+    fn percentage(value: i64) -> CouponDiscount {
+        CouponDiscount::Percentage(Decimal::from(value))
+    }
+
+    fn fixed(amount: i64) -> CouponDiscount {
+        CouponDiscount::Fixed {
+            amount: Decimal::from(amount),
+            currency: "EUR".to_string(),
         }
     }
+
+    fn discounts(lines: &[LineItem], coupons: &[AppliedCouponDetailed]) -> Vec<(Uuid, i64)> {
+        InvoiceTotals::calculate_coupons_discount(lines, "EUR", coupons)
+            .coupons
+            .into_iter()
+            .map(|x| (x.applied_coupon_id, x.value))
+            .collect()
+    }
+
+    #[test]
+    fn test_edit_manual_lines() {
+        let mut invoice = invoice(InvoiceType::OneOff, vec![]);
+
+        invoice
+            .edit_line(InvoiceLineEdit::Add(manual_line(
+                "Onboarding",
+                3,
+                Decimal::new(12550, 2),
+            )))
+            .unwrap();
+
+        let line = invoice.line_items[0].clone();
+        assert!(line.is_manual);
+        assert_eq!(line.subtotal, 37650);
+
+        invoice
+            .edit_line(InvoiceLineEdit::Update {
+                local_id: line.local_id.clone(),
+                line: manual_line("Onboarding", 2, Decimal::new(12550, 2)),
+            })
+            .unwrap();
+        assert_eq!(invoice.line_items[0].local_id, line.local_id);
+        assert_eq!(invoice.line_items[0].subtotal, 25100);
+
+        assert!(invoice
+            .edit_line(InvoiceLineEdit::Add(manual_line("", 1, Decimal::ONE)))
+            .is_err());
+
+        invoice
+            .edit_line(InvoiceLineEdit::Remove {
+                local_id: line.local_id,
+            })
+            .unwrap();
+        assert!(invoice.line_items.is_empty());
+
+        invoice.status = InvoiceStatusEnum::Finalized;
+        assert!(invoice
+            .edit_line(InvoiceLineEdit::Add(manual_line(
+                "Onboarding",
+                1,
+                Decimal::ONE
+            )))
+            .is_err());
+    }
+
+    #[test]
+    fn test_computed_lines_are_not_editable() {
+        let computed = manual_line("Seats", 10, Decimal::TEN)
+            .to_line_item(LocalId::no_prefix(), "EUR")
+            .map(|l| LineItem {
+                is_manual: false,
+                ..l
+            })
+            .unwrap();
+
+        let mut recurring = invoice(InvoiceType::Recurring, vec![computed.clone()]);
+        assert!(recurring
+            .edit_line(InvoiceLineEdit::Remove {
+                local_id: computed.local_id.clone(),
+            })
+            .is_err());
+
+        let mut adjustment = invoice(InvoiceType::Adjustment, vec![computed.clone()]);
+        assert!(adjustment
+            .edit_line(InvoiceLineEdit::Remove {
+                local_id: computed.local_id,
+            })
+            .is_ok());
+    }
+
+    #[test]
+    fn test_replacement_draft() {
+        let line = manual_line("Seats", 10, Decimal::TEN)
+            .to_line_item(LocalId::no_prefix(), "EUR")
+            .unwrap();
+
+        let mut voided = invoice(InvoiceType::Recurring, vec![line.clone()]);
+        voided.status = InvoiceStatusEnum::Void;
+        voided.invoice_number = "INV-0042".to_string();
+        voided.total = 10000;
+        voided.amount_due = 4000;
+        voided.applied_credits = 6000;
+        voided.net_terms = 30;
+
+        let replacement = voided.replacement_draft(date(15));
+
+        assert_eq!(replacement.status, InvoiceStatusEnum::Draft);
+        assert_eq!(replacement.invoice_type, InvoiceType::Adjustment);
+        assert_eq!(replacement.invoice_number, "draft");
+        assert_eq!(replacement.replaces_invoice_id, Some(voided.id));
+        assert_eq!(replacement.line_items, vec![line]);
+        assert_eq!(replacement.amount_due, 10000);
+        assert_eq!(
+            replacement.due_at,
+            Some(
+                NaiveDate::from_ymd_opt(2024, 12, 15)
+                    .unwrap()
+                    .and_time(NaiveTime::MIN)
+            )
+        );
+    }
+
+    #[test]
+    fn test_coupons_discount_scoped_to_lines() {
+        let seats_component_id = Uuid::now_v7();
+
+        let seats = LineItem {
+            price_component_id: Some(seats_component_id),
+            ..manual_line("Seats", 10, Decimal::TEN)
+                .to_line_item(LocalId::no_prefix(), "EUR")
+                .unwrap()
+        };
+        let support = manual_line("Support", 1, Decimal::ONE_HUNDRED)
+            .to_line_item(LocalId::no_prefix(), "EUR")
+            .unwrap();
+
+        let seats_only = CouponAppliesTo {
+            price_component_ids: vec![seats_component_id],
+            ..CouponAppliesTo::default()
+        };
+
+        let lines = vec![seats, support];
+
+        let discount = InvoiceTotals::calculate_coupons_discount(
+            &lines,
+            "EUR",
+            &[applied_coupon(percentage(50), seats_only.clone())],
+        );
+        assert_eq!(discount.discount_subunit, 5000);
+
+        let discount = InvoiceTotals::calculate_coupons_discount(
+            &lines,
+            "EUR",
+            &[applied_coupon(fixed(500), seats_only)],
+        );
+        assert_eq!(discount.discount_subunit, 10000);
+
+        let discount = InvoiceTotals::calculate_coupons_discount(
+            &lines,
+            "EUR",
+            &[applied_coupon(percentage(50), CouponAppliesTo::default())],
+        );
+        assert_eq!(discount.discount_subunit, 10000);
+    }
+
+    #[test]
+    fn test_coupons_discount_order_and_floor() {
+        let lines = vec![manual_line("Seats", 10, Decimal::TEN)
+            .to_line_item(LocalId::no_prefix(), "EUR")
+            .unwrap()];
+
+        // the percentage applies before the fixed amount, whatever the order they were applied in
+        let fixed_20 = applied_coupon(fixed(20), CouponAppliesTo::default());
+        let percent_10 = applied_coupon(percentage(10), CouponAppliesTo::default());
+        assert_eq!(
+            discounts(&lines, &[fixed_20.clone(), percent_10.clone()]),
+            vec![
+                (percent_10.applied_coupon.id, 1000),
+                (fixed_20.applied_coupon.id, 2000)
+            ]
+        );
+
+        // a lower priority applies first
+        let mut prioritized = fixed_20.clone();
+        prioritized.coupon.priority = -1;
+        assert_eq!(
+            discounts(&lines, &[percent_10.clone(), prioritized.clone()]),
+            vec![
+                (prioritized.applied_coupon.id, 2000),
+                (percent_10.applied_coupon.id, 800)
+            ]
+        );
+
+        // the subtotal never goes negative
+        let fixed_80 = applied_coupon(fixed(80), CouponAppliesTo::default());
+        let fixed_50 = applied_coupon(fixed(50), CouponAppliesTo::default());
+        let percent_150 = applied_coupon(percentage(150), CouponAppliesTo::default());
+        let discount =
+            InvoiceTotals::calculate_coupons_discount(&lines, "EUR", &[fixed_80, fixed_50]);
+        assert_eq!(discount.discount_subunit, 10000);
+        assert_eq!(discount.coupons[1].value, 2000);
+        let discount = InvoiceTotals::calculate_coupons_discount(&lines, "EUR", &[percent_150]);
+        assert_eq!(discount.discount_subunit, 10000);
+    }
+
+    #[test]
+    fn test_coupons_stacking_rules() {
+        let lines = vec![manual_line("Seats", 10, Decimal::TEN)
+            .to_line_item(LocalId::no_prefix(), "EUR")
+            .unwrap()];
+
+        let stackable = stacked_coupon(percentage(10), CouponStackingEnum::Stackable, 0);
+        let best_of_small = stacked_coupon(fixed(5), CouponStackingEnum::BestOf, 0);
+        let best_of_large = stacked_coupon(percentage(20), CouponStackingEnum::BestOf, 0);
+        let exclusive = stacked_coupon(fixed(30), CouponStackingEnum::Exclusive, 0);
+
+        // only the largest best-of discount combines with the stackable coupons
+        assert_eq!(
+            discounts(
+                &lines,
+                &[
+                    stackable.clone(),
+                    best_of_small.clone(),
+                    best_of_large.clone()
+                ]
+            ),
+            vec![
+                (stackable.applied_coupon.id, 1000),
+                (best_of_large.applied_coupon.id, 1800)
+            ]
+        );
+
+        // an exclusive coupon applies alone
+        assert_eq!(
+            discounts(
+                &lines,
+                &[stackable.clone(), best_of_large.clone(), exclusive.clone()]
+            ),
+            vec![(exclusive.applied_coupon.id, 3000)]
+        );
+
+        // unless it has no discount on the invoice
+        let mut other_currency = exclusive.clone();
+        other_currency.coupon.discount = CouponDiscount::Fixed {
+            amount: Decimal::from(30),
+            currency: "USD".to_string(),
+        };
+        assert_eq!(
+            discounts(&lines, &[stackable.clone(), other_currency]),
+            vec![(stackable.applied_coupon.id, 1000)]
+        );
+    }
 }
@@ -1,8 +1,13 @@
+use std::str::FromStr;
+
+use chrono::{Datelike, NaiveDate};
+use error_stack::Report;
 use o2o::o2o;
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
 use crate::domain::Address;
+use crate::errors::StoreError;
 use diesel_models::invoicing_entities::{InvoicingEntityRow, InvoicingEntityRowPatch};
 
 #[derive(Serialize, Deserialize, o2o)]
@@ -15,8 +20,13 @@ pub struct InvoicingEntity {
     pub legal_name: String,
 
     pub invoice_number_pattern: String,
+    pub credit_note_number_pattern: String,
     pub next_invoice_number: i64,
     pub next_credit_note_number: i64,
+    /// The year of the last invoice number, used by the yearly reset of the counter.
+    pub invoice_number_year: Option<i32>,
+    /// The year of the last credit note number, used by the yearly reset of the counter.
+    pub credit_note_number_year: Option<i32>,
 
     pub grace_period_hours: i32,
     pub net_terms: i32,
@@ -56,6 +66,7 @@ pub struct InvoicingEntityNew {
     pub country: Option<String>,
     pub legal_name: Option<String>,
     pub invoice_number_pattern: Option<String>,
+    pub credit_note_number_pattern: Option<String>,
     pub next_invoice_number: Option<i64>,
     pub next_credit_note_number: Option<i64>,
     pub grace_period_hours: Option<i32>,
@@ -79,6 +90,7 @@ pub struct InvoicingEntityPatch {
     pub id: Uuid,
     pub legal_name: Option<String>,
     pub invoice_number_pattern: Option<String>,
+    pub credit_note_number_pattern: Option<String>,
     pub grace_period_hours: Option<i32>,
     pub net_terms: Option<i32>,
     pub invoice_footer_info: Option<String>,
@@ -93,3 +105,212 @@ pub struct InvoicingEntityPatch {
     pub vat_number: Option<String>,
     pub country: Option<String>,
 }
+
+/// The numbering pattern of the invoices or credit notes of an invoicing entity, ex: `INV-{YYYY}-{number:5:yearly}`.
+///
+/// The placeholders are `{YYYY}` and `{YY}` for the year, `{MM}` for the month, `{DD}` for the day and `{number}`
+/// for the counter of the entity. The counter can be zero-padded to a width, `{number:5}`, and reset every year,
+/// `{number:yearly}`. Everything else is kept as is.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NumberPattern {
+    segments: Vec<PatternSegment>,
+    pub yearly_reset: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum PatternSegment {
+    Literal(String),
+    Year,
+    ShortYear,
+    Month,
+    Day,
+    Number { width: usize },
+}
+
+const MAX_NUMBER_WIDTH: usize = 20;
+
+impl NumberPattern {
+    /// The counter of the number issued at `date`, from the next counter of the sequence and the year of
+    /// its last number.
+    pub fn counter(&self, next_number: i64, last_year: Option<i32>, date: NaiveDate) -> i64 {
+        match last_year {
+            Some(year) if self.yearly_reset && date.year() > year => 1,
+            _ => next_number,
+        }
+    }
+
+    pub fn format(&self, number: i64, date: NaiveDate) -> String {
+        self.segments
+            .iter()
+            .map(|segment| match segment {
+                PatternSegment::Literal(literal) => literal.clone(),
+                PatternSegment::Year => format!("{:04}", date.year()),
+                PatternSegment::ShortYear => format!("{:02}", date.year() % 100),
+                PatternSegment::Month => format!("{:02}", date.month()),
+                PatternSegment::Day => format!("{:02}", date.day()),
+                PatternSegment::Number { width } => format!("{:0width$}", number, width = width),
+            })
+            .collect()
+    }
+
+    fn parse_number(placeholder: &str) -> Result<(PatternSegment, bool), StoreError> {
+        let mut width = 0;
+        let mut yearly_reset = false;
+
+        for option in placeholder.split(':').skip(1) {
+            match option {
+                "yearly" if !yearly_reset => yearly_reset = true,
+                _ => match option.parse::<usize>() {
+                    Ok(w) if w <= MAX_NUMBER_WIDTH && width == 0 => width = w,
+                    _ => {
+                        return Err(StoreError::InvalidArgument(format!(
+                            "invalid number option `{}` in the numbering pattern",
+                            option
+                        )))
+                    }
+                },
+            }
+        }
+
+        Ok((PatternSegment::Number { width }, yearly_reset))
+    }
+}
+
+impl FromStr for NumberPattern {
+    type Err = Report<StoreError>;
+
+    fn from_str(pattern: &str) -> Result<Self, Self::Err> {
+        let mut segments = vec![];
+        let mut yearly_reset = false;
+        let mut rest = pattern;
+
+        while !rest.is_empty() {
+            let Some(start) = rest.find(['{', '}']) else {
+                segments.push(PatternSegment::Literal(rest.to_string()));
+                break;
+            };
+
+            if start > 0 {
+                segments.push(PatternSegment::Literal(rest[..start].to_string()));
+            }
+
+            let end = match rest[start..].find('}') {
+                Some(end) if rest.as_bytes()[start] == b'{' => start + end,
+                _ => {
+                    return Err(Report::new(StoreError::InvalidArgument(
+                        "unbalanced braces in the numbering pattern".to_string(),
+                    )))
+                }
+            };
+
+            let placeholder = &rest[start + 1..end];
+
+            let segment = match placeholder {
+                "YYYY" => PatternSegment::Year,
+                "YY" => PatternSegment::ShortYear,
+                "MM" => PatternSegment::Month,
+                "DD" => PatternSegment::Day,
+                p if p == "number" || p.starts_with("number:") => {
+                    if segments
+                        .iter()
+                        .any(|s| matches!(s, PatternSegment::Number { .. }))
+                    {
+                        return Err(Report::new(StoreError::InvalidArgument(
+                            "the numbering pattern must contain {number} only once".to_string(),
+                        )));
+                    }
+                    let (segment, yearly) = Self::parse_number(p)?;
+                    yearly_reset = yearly;
+                    segment
+                }
+                p => {
+                    return Err(Report::new(StoreError::InvalidArgument(format!(
+                        "unknown placeholder {{{}}} in the numbering pattern",
+                        p
+                    ))))
+                }
+            };
+
+            segments.push(segment);
+            rest = &rest[end + 1..];
+        }
+
+        if !segments
+            .iter()
+            .any(|s| matches!(s, PatternSegment::Number { .. }))
+        {
+            return Err(Report::new(StoreError::InvalidArgument(
+                "the numbering pattern must contain {number}".to_string(),
+            )));
+        }
+
+        // the numbers would repeat after each reset if the year was not part of them
+        if yearly_reset
+            && !segments
+                .iter()
+                .any(|s| matches!(s, PatternSegment::Year | PatternSegment::ShortYear))
+        {
+            return Err(Report::new(StoreError::InvalidArgument(
+                "a yearly {number} requires a {YYYY} or {YY} placeholder".to_string(),
+            )));
+        }
+
+        Ok(NumberPattern {
+            segments,
+            yearly_reset,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(y: i32, m: u32, d: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(y, m, d).unwrap()
+    }
+
+    #[test]
+    fn test_format_number_pattern() {
+        let pattern: NumberPattern = "INV-{YYYY}{MM}-{number:5:yearly}".parse().unwrap();
+        assert!(pattern.yearly_reset);
+        assert_eq!(pattern.format(42, date(2025, 3, 7)), "INV-202503-00042");
+
+        let pattern: NumberPattern = "{YY}/{DD}/{number}".parse().unwrap();
+        assert!(!pattern.yearly_reset);
+        assert_eq!(pattern.format(1234, date(2025, 3, 7)), "25/07/1234");
+
+        // the counter overflows the width rather than being truncated
+        let pattern: NumberPattern = "CN-{number:2}".parse().unwrap();
+        assert_eq!(pattern.format(123, date(2025, 3, 7)), "CN-123");
+    }
+
+    #[test]
+    fn test_invalid_number_pattern() {
+        for pattern in [
+            "INV-",
+            "INV-{YYYY}",
+            "INV-{number}-{number}",
+            "INV-{number",
+            "INV-}{number}",
+            "INV-{foo}-{number}",
+            "INV-{number:abc}",
+            "INV-{number:5:6}",
+            "INV-{number:99}",
+            "INV-{MM}-{number:yearly}",
+        ] {
+            assert!(pattern.parse::<NumberPattern>().is_err(), "{}", pattern);
+        }
+    }
+
+    #[test]
+    fn test_number_pattern_counter() {
+        let yearly: NumberPattern = "INV-{YYYY}-{number:yearly}".parse().unwrap();
+        let continuous: NumberPattern = "INV-{number}".parse().unwrap();
+
+        assert_eq!(yearly.counter(8, Some(2024), date(2024, 12, 31)), 8);
+        assert_eq!(yearly.counter(8, Some(2024), date(2025, 1, 1)), 1);
+        assert_eq!(yearly.counter(8, None, date(2025, 1, 1)), 8);
+        assert_eq!(continuous.counter(8, Some(2024), date(2025, 1, 1)), 8);
+    }
+}
@@ -1,6 +1,9 @@
 pub use api_tokens::*;
 pub use billable_metrics::*;
+pub use credit_grants::*;
+pub use credit_notes::*;
 pub use customers::*;
+pub use dunning::*;
 pub use invoice_lines::*;
 pub use invoices::*;
 pub use invoicing_entities::*;
@@ -13,10 +16,17 @@ pub use product_families::*;
 pub use products::*;
 pub use schedules::*;
 pub use subscription_add_ons::*;
+pub use subscription_commitments::*;
 pub use subscription_components::*;
 pub use subscription_coupons::*;
+pub use subscription_pauses::*;
+pub use subscription_plan_changes::*;
+pub use subscription_trials::*;
 pub use subscriptions::*;
+pub use taxes::*;
 pub use tenants::*;
+pub use upcoming_invoices::*;
+pub use usage_thresholds::*;
 
 pub mod customers;
 pub mod invoices;
@@ -31,6 +41,9 @@ pub mod api_tokens;
 pub mod billable_metrics;
 pub mod configs;
 pub mod coupons;
+pub mod credit_grants;
+pub mod credit_notes;
+pub mod dunning;
 pub mod enums;
 pub mod historical_rates;
 pub mod invoice_lines;
@@ -43,8 +56,15 @@ pub mod products;
 pub mod schedules;
 pub mod stats;
 pub mod subscription_add_ons;
+pub mod subscription_commitments;
 pub mod subscription_components;
 pub mod subscription_coupons;
+pub mod subscription_pauses;
+pub mod subscription_plan_changes;
+pub mod subscription_trials;
 pub mod subscriptions;
+pub mod taxes;
+pub mod upcoming_invoices;
+pub mod usage_thresholds;
 pub mod users;
 pub mod webhooks;
@@ -15,6 +15,8 @@ pub enum OutboxEvent {
     InvoiceFinalized,
     #[serde(rename = "invoice.pdf.requested")]
     InvoicePdfRequested,
+    #[serde(rename = "credit_note.finalized")]
+    CreditNoteFinalized,
     // TODO meter created
 }
 
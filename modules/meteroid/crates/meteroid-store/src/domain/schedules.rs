@@ -1,4 +1,5 @@
 use error_stack::Report;
+use itertools::Itertools;
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
@@ -35,6 +36,33 @@ pub struct PlanRamps {
     pub ramps: Vec<PlanRamp>,
 }
 
+impl PlanRamps {
+    /// The ramp of the period at this index, each ramp starting when the previous one ends.
+    /// A ramp without duration applies to all the following periods.
+    pub fn active_ramp(
+        &self,
+        period_index: u32,
+        billing_period: &BillingPeriodEnum,
+    ) -> Option<&PlanRamp> {
+        let months_elapsed = period_index * billing_period.as_months();
+        let mut ramp_end = 0;
+
+        for ramp in self.ramps.iter().sorted_by_key(|r| r.index) {
+            match ramp.duration_in_months {
+                None => return Some(ramp),
+                Some(duration) => {
+                    ramp_end += duration;
+                    if months_elapsed < ramp_end {
+                        return Some(ramp);
+                    }
+                }
+            }
+        }
+
+        None
+    }
+}
+
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct PlanRamp {
     pub index: u32,
@@ -104,3 +132,65 @@ impl TryInto<SchedulePatchRow> for SchedulePatch {
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::adjustments::discount::Percent;
+    use rust_decimal::Decimal;
+
+    fn ramp(index: u32, duration_in_months: Option<u32>, percentage: i64) -> PlanRamp {
+        PlanRamp {
+            index,
+            duration_in_months,
+            ramp_adjustment: PlanRampAdjustment {
+                minimum: Amount { value_in_cents: 0 },
+                discount: StandardDiscount::Percent(Percent {
+                    percentage: Decimal::from(percentage),
+                }),
+            },
+        }
+    }
+
+    #[test]
+    fn test_active_ramp() {
+        let ramps = PlanRamps {
+            ramps: vec![ramp(1, Some(6), 10), ramp(0, Some(12), 20)],
+        };
+
+        let active = |idx| {
+            ramps
+                .active_ramp(idx, &BillingPeriodEnum::Monthly)
+                .map(|r| r.index)
+        };
+
+        assert_eq!(active(0), Some(0));
+        assert_eq!(active(11), Some(0));
+        assert_eq!(active(12), Some(1));
+        assert_eq!(active(17), Some(1));
+        assert_eq!(active(18), None);
+
+        assert_eq!(
+            ramps
+                .active_ramp(1, &BillingPeriodEnum::Quarterly)
+                .map(|r| r.index),
+            Some(0)
+        );
+        assert_eq!(
+            ramps
+                .active_ramp(4, &BillingPeriodEnum::Quarterly)
+                .map(|r| r.index),
+            Some(1)
+        );
+
+        let open_ended = PlanRamps {
+            ramps: vec![ramp(0, Some(12), 20), ramp(1, None, 0)],
+        };
+        assert_eq!(
+            open_ended
+                .active_ramp(40, &BillingPeriodEnum::Monthly)
+                .map(|r| r.index),
+            Some(1)
+        );
+    }
+}
@@ -28,6 +28,11 @@ impl SubscriptionFeeInterface for SubscriptionAddOn {
         None
     }
 
+    #[inline]
+    fn add_on_id(&self) -> Option<Uuid> {
+        Some(self.add_on_id)
+    }
+
     #[inline]
     fn subscription_id(&self) -> Uuid {
         self.subscription_id
@@ -0,0 +1,285 @@
+use crate::domain::enums::SubscriptionCommitmentTypeEnum;
+use crate::domain::{CreditGrantNew, LineItem};
+use crate::errors::StoreError;
+use crate::utils::local_id::{IdType, LocalId};
+use crate::StoreResult;
+use chrono::{Days, NaiveDate, NaiveDateTime};
+use diesel_models::subscription_commitments::{
+    SubscriptionCommitmentRow, SubscriptionCommitmentRowNew,
+};
+use error_stack::Report;
+use o2o::o2o;
+use rust_decimal::Decimal;
+use uuid::Uuid;
+
+/// A commitment of the subscription to an amount of usage over a term, drawn down by its usage charges.
+/// The drawdown is tracked by a credit grant restricted to the usage metrics of the subscription,
+/// so that the overage is only billed once the commitment is exhausted.
+/// A prepaid commitment is invoiced on creation and its unused part is forfeited at term end,
+/// a postpaid commitment is invoiced at term end with a true-up of its unused part.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SubscriptionCommitment {
+    pub id: Uuid,
+    pub tenant_id: Uuid,
+    pub subscription_id: Uuid,
+    pub commitment_type: SubscriptionCommitmentTypeEnum,
+    pub amount_cents: i32,
+    pub start_date: NaiveDate,
+    pub end_date: NaiveDate,
+    pub credit_grant_id: Uuid,
+    pub invoice_id: Option<Uuid>,
+    pub unused_cents: Option<i32>,
+    pub created_at: NaiveDateTime,
+    pub created_by: Option<Uuid>,
+    pub closed_at: Option<NaiveDateTime>,
+    /// The part not drawn down yet, zero once closed.
+    pub remaining_cents: i32,
+}
+
+impl From<(SubscriptionCommitmentRow, i32)> for SubscriptionCommitment {
+    fn from((row, remaining_cents): (SubscriptionCommitmentRow, i32)) -> Self {
+        SubscriptionCommitment {
+            id: row.id,
+            tenant_id: row.tenant_id,
+            subscription_id: row.subscription_id,
+            commitment_type: row.commitment_type.into(),
+            amount_cents: row.amount_cents,
+            start_date: row.start_date,
+            end_date: row.end_date,
+            credit_grant_id: row.credit_grant_id,
+            invoice_id: row.invoice_id,
+            unused_cents: row.unused_cents,
+            created_at: row.created_at,
+            created_by: row.created_by,
+            closed_at: row.closed_at,
+            remaining_cents,
+        }
+    }
+}
+
+impl SubscriptionCommitment {
+    pub fn drawn_down_cents(&self) -> i32 {
+        self.amount_cents - self.unused_cents.unwrap_or(self.remaining_cents)
+    }
+
+    /// The lines of the invoice closing a postpaid commitment: the drawn down part and the true-up.
+    pub fn true_up_lines(&self, unused_cents: i32) -> Vec<LineItem> {
+        [
+            ("Commitment drawdown", self.amount_cents - unused_cents),
+            ("Commitment true-up", unused_cents),
+        ]
+        .into_iter()
+        .filter(|(_, cents)| *cents > 0)
+        .map(|(name, cents)| commitment_line(name, cents, self.start_date, self.end_date))
+        .collect()
+    }
+}
+
+/// A commitment whose term ended, to be closed by the commitment worker.
+#[derive(Debug, Clone, o2o)]
+#[from_owned(SubscriptionCommitmentRow)]
+pub struct EndedSubscriptionCommitment {
+    pub id: Uuid,
+    pub tenant_id: Uuid,
+    pub subscription_id: Uuid,
+}
+
+#[derive(Debug, Clone)]
+pub struct SubscriptionCommitmentNew {
+    pub tenant_id: Uuid,
+    pub subscription_id: Uuid,
+    pub commitment_type: SubscriptionCommitmentTypeEnum,
+    pub amount_cents: i32,
+    pub start_date: NaiveDate,
+    pub end_date: NaiveDate,
+    pub created_by: Option<Uuid>,
+}
+
+impl SubscriptionCommitmentNew {
+    pub fn validate(&self, existing: &[SubscriptionCommitment]) -> StoreResult<()> {
+        if self.amount_cents <= 0 {
+            return Err(Report::new(StoreError::InvalidArgument(
+                "the amount of a commitment must be positive".to_string(),
+            )));
+        }
+
+        if self.end_date <= self.start_date {
+            return Err(Report::new(StoreError::InvalidArgument(
+                "the end date must be after the start date".to_string(),
+            )));
+        }
+
+        let overlaps = existing
+            .iter()
+            .any(|c| c.start_date < self.end_date && self.start_date < c.end_date);
+
+        if overlaps {
+            return Err(Report::new(StoreError::InvalidArgument(
+                "the term overlaps another commitment of the subscription".to_string(),
+            )));
+        }
+
+        Ok(())
+    }
+
+    /// The grant drawn down by the usage billed in the term, by the invoices dated after the start date
+    /// up to the end date included, as the usage is billed in arrears.
+    /// It does not expire, it is written off when the commitment is closed.
+    pub fn credit_grant(
+        &self,
+        customer_id: Uuid,
+        currency: String,
+        metric_ids: Vec<Uuid>,
+    ) -> CreditGrantNew {
+        CreditGrantNew {
+            tenant_id: self.tenant_id,
+            customer_id,
+            name: "Commitment".to_string(),
+            amount_cents: self.amount_cents,
+            currency,
+            priority: 0,
+            effective_date: self.start_date + Days::new(1),
+            expiry_date: None,
+            price_component_ids: vec![],
+            metric_ids,
+            created_by: self.created_by,
+        }
+    }
+
+    pub fn prepayment_lines(&self) -> Vec<LineItem> {
+        vec![commitment_line(
+            "Prepaid commitment",
+            self.amount_cents,
+            self.start_date,
+            self.end_date,
+        )]
+    }
+
+    pub fn into_row(
+        self,
+        credit_grant_id: Uuid,
+        invoice_id: Option<Uuid>,
+    ) -> SubscriptionCommitmentRowNew {
+        SubscriptionCommitmentRowNew {
+            id: Uuid::now_v7(),
+            tenant_id: self.tenant_id,
+            subscription_id: self.subscription_id,
+            commitment_type: self.commitment_type.into(),
+            amount_cents: self.amount_cents,
+            start_date: self.start_date,
+            end_date: self.end_date,
+            credit_grant_id,
+            invoice_id,
+            created_by: self.created_by,
+        }
+    }
+}
+
+fn commitment_line(name: &str, cents: i32, start_date: NaiveDate, end_date: NaiveDate) -> LineItem {
+    LineItem {
+        local_id: LocalId::generate_for(IdType::Other),
+        name: name.to_string(),
+        total: cents as i64,
+        subtotal: cents as i64,
+        quantity: None,
+        unit_price: None,
+        start_date,
+        end_date,
+        sub_lines: vec![],
+        is_prorated: false,
+        price_component_id: None,
+        product_id: None,
+        metric_id: None,
+        add_on_id: None,
+        description: None,
+        tax_rate: Decimal::ZERO,
+        tax_amount: 0,
+        is_manual: false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(month: u32, day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(2024, month, day).unwrap()
+    }
+
+    fn commitment(start_date: NaiveDate, end_date: NaiveDate) -> SubscriptionCommitment {
+        SubscriptionCommitment {
+            id: Uuid::now_v7(),
+            tenant_id: Uuid::nil(),
+            subscription_id: Uuid::nil(),
+            commitment_type: SubscriptionCommitmentTypeEnum::Postpaid,
+            amount_cents: 100000,
+            start_date,
+            end_date,
+            credit_grant_id: Uuid::now_v7(),
+            invoice_id: None,
+            unused_cents: None,
+            created_at: start_date.and_hms_opt(0, 0, 0).unwrap(),
+            created_by: None,
+            closed_at: None,
+            remaining_cents: 30000,
+        }
+    }
+
+    #[test]
+    fn test_validate_commitment() {
+        let new = SubscriptionCommitmentNew {
+            tenant_id: Uuid::nil(),
+            subscription_id: Uuid::nil(),
+            commitment_type: SubscriptionCommitmentTypeEnum::Prepaid,
+            amount_cents: 100000,
+            start_date: date(1, 1),
+            end_date: date(12, 31),
+            created_by: None,
+        };
+
+        assert!(new.validate(&[]).is_ok());
+        // back to back terms
+        assert!(new
+            .validate(&[commitment(date(12, 31), date(12, 31) + Days::new(365))])
+            .is_ok());
+        assert!(new
+            .validate(&[commitment(date(6, 1), date(12, 31) + Days::new(151))])
+            .is_err());
+        assert!(SubscriptionCommitmentNew {
+            end_date: date(1, 1),
+            ..new.clone()
+        }
+        .validate(&[])
+        .is_err());
+        assert!(SubscriptionCommitmentNew {
+            amount_cents: 0,
+            ..new
+        }
+        .validate(&[])
+        .is_err());
+    }
+
+    #[test]
+    fn test_true_up_lines() {
+        let commitment = commitment(date(1, 1), date(12, 31));
+
+        assert_eq!(commitment.drawn_down_cents(), 70000);
+
+        let lines = commitment.true_up_lines(30000);
+        assert_eq!(
+            lines
+                .iter()
+                .map(|l| (l.name.as_str(), l.subtotal))
+                .collect::<Vec<_>>(),
+            vec![
+                ("Commitment drawdown", 70000),
+                ("Commitment true-up", 30000)
+            ]
+        );
+
+        // fully drawn down
+        let lines = commitment.true_up_lines(0);
+        assert_eq!(lines.len(), 1);
+        assert_eq!(lines[0].subtotal, 100000);
+    }
+}
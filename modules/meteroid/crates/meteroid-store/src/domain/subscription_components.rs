@@ -11,6 +11,7 @@ use crate::errors::StoreError;
 pub trait SubscriptionFeeInterface {
     fn price_component_id(&self) -> Option<Uuid>;
     fn product_item_id(&self) -> Option<Uuid>;
+    fn add_on_id(&self) -> Option<Uuid>;
     fn subscription_id(&self) -> Uuid;
     fn name_ref(&self) -> &String;
     fn period_ref(&self) -> &SubscriptionFeeBillingPeriod;
@@ -39,6 +40,11 @@ impl SubscriptionFeeInterface for SubscriptionComponent {
         self.product_item_id
     }
 
+    #[inline]
+    fn add_on_id(&self) -> Option<Uuid> {
+        None
+    }
+
     #[inline]
     fn subscription_id(&self) -> Uuid {
         self.subscription_id
@@ -95,6 +101,43 @@ pub struct SubscriptionComponentNew {
     pub internal: SubscriptionComponentNewInternal,
 }
 
+impl SubscriptionFeeInterface for SubscriptionComponentNew {
+    #[inline]
+    fn price_component_id(&self) -> Option<Uuid> {
+        self.internal.price_component_id
+    }
+
+    #[inline]
+    fn product_item_id(&self) -> Option<Uuid> {
+        self.internal.product_item_id
+    }
+
+    #[inline]
+    fn add_on_id(&self) -> Option<Uuid> {
+        None
+    }
+
+    #[inline]
+    fn subscription_id(&self) -> Uuid {
+        self.subscription_id
+    }
+
+    #[inline]
+    fn name_ref(&self) -> &String {
+        &self.internal.name
+    }
+
+    #[inline]
+    fn period_ref(&self) -> &SubscriptionFeeBillingPeriod {
+        &self.internal.period
+    }
+
+    #[inline]
+    fn fee_ref(&self) -> &SubscriptionFee {
+        &self.internal.fee
+    }
+}
+
 impl TryInto<SubscriptionComponentRowNew> for SubscriptionComponentNew {
     type Error = StoreError;
 
@@ -146,7 +189,7 @@ pub struct ExtraComponent {
     pub component: SubscriptionComponentNewInternal,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct SubscriptionComponentNewInternal {
     pub price_component_id: Option<Uuid>,
     pub product_item_id: Option<Uuid>,
@@ -1,6 +1,6 @@
 use crate::domain::coupons::{Coupon, CouponDiscount};
 use crate::errors::StoreErrorReport;
-use chrono::NaiveDateTime;
+use chrono::{NaiveDate, NaiveDateTime};
 use diesel_models::applied_coupons::{AppliedCouponDetailedRow, AppliedCouponRow};
 use o2o::o2o;
 use rust_decimal::Decimal;
@@ -15,6 +15,8 @@ pub struct CreateSubscriptionCoupon {
 #[derive(Debug, Clone)]
 pub struct CreateSubscriptionCoupons {
     pub coupons: Vec<CreateSubscriptionCoupon>,
+    /// The customer-facing codes redeemed, each applying the coupon it points to.
+    pub promotion_codes: Vec<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, o2o)]
@@ -30,6 +32,7 @@ pub struct AppliedCoupon {
     pub applied_count: Option<i32>,
     pub last_applied_at: Option<NaiveDateTime>,
     pub created_at: NaiveDateTime,
+    pub promotion_code_id: Option<Uuid>,
 }
 
 #[derive(Debug, Clone)]
@@ -39,6 +42,21 @@ pub struct AppliedCouponDetailed {
 }
 
 impl AppliedCouponDetailed {
+    /// Whether the coupon applies to an invoice dated `invoice_date` of a subscription on the plan version.
+    pub fn applies_to_invoice(
+        &self,
+        plan_id: Uuid,
+        plan_version_id: Uuid,
+        invoice_date: NaiveDate,
+    ) -> bool {
+        self.coupon
+            .applies_to
+            .applies_to_plan(plan_id, plan_version_id)
+            && self
+                .coupon
+                .is_within_duration(self.applied_coupon.created_at, invoice_date)
+    }
+
     pub fn is_invoice_applicable(&self) -> bool {
         self.applied_coupon.is_active
             && !self.reached_recurring_limit()
@@ -0,0 +1,51 @@
+use crate::domain::Subscription;
+use crate::errors::{StoreError, StoreErrorReport};
+use chrono::NaiveDate;
+use diesel_models::subscriptions::SubscriptionRow;
+use error_stack::Report;
+use o2o::o2o;
+use uuid::Uuid;
+
+/// A paused subscription whose auto-resume date is reached.
+#[derive(Debug, Clone, o2o)]
+#[from_owned(SubscriptionRow)]
+pub struct SubscriptionToResume {
+    pub id: Uuid,
+    pub tenant_id: Uuid,
+    pub resume_at: Option<NaiveDate>,
+}
+
+impl Subscription {
+    pub fn validate_pause(
+        &self,
+        today: NaiveDate,
+        resume_at: Option<NaiveDate>,
+    ) -> Result<(), StoreErrorReport> {
+        if self.canceled_at.is_some() {
+            return Err(Report::new(StoreError::InvalidArgument(
+                "a cancelled subscription cannot be paused".to_string(),
+            )));
+        }
+
+        if self.paused_at.is_some() {
+            return Err(Report::new(StoreError::InvalidArgument(
+                "the subscription is already paused".to_string(),
+            )));
+        }
+
+        // the action after trial is applied by the trial worker
+        if self.trial_start_date.is_some() && self.trial_ended_at.is_none() {
+            return Err(Report::new(StoreError::InvalidArgument(
+                "a subscription in trial cannot be paused".to_string(),
+            )));
+        }
+
+        if resume_at.is_some_and(|resume_at| resume_at <= today) {
+            return Err(Report::new(StoreError::InvalidArgument(
+                "the resume date must be in the future".to_string(),
+            )));
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,200 @@
+use crate::domain::invoice_lines::LineItem;
+use crate::domain::taxes::{LineTaxes, ResolvedTax};
+use crate::domain::{CreateSubscriptionComponents, Subscription, SubscriptionComponentNewInternal};
+use crate::errors::{StoreError, StoreErrorReport};
+use chrono::{NaiveDate, NaiveDateTime};
+use diesel_models::subscription_plan_changes::{
+    SubscriptionPlanChangeRow, SubscriptionPlanChangeRowNew,
+};
+use o2o::o2o;
+use uuid::Uuid;
+
+/// A plan change scheduled at the end of the billing period, applied by the plan change worker.
+#[derive(Debug, Clone, o2o)]
+#[try_from_owned(SubscriptionPlanChangeRow, StoreErrorReport)]
+pub struct SubscriptionPlanChange {
+    pub id: Uuid,
+    pub subscription_id: Uuid,
+    pub tenant_id: Uuid,
+    pub plan_version_id: Uuid,
+    #[from(serde_json::from_value(~).map_err(| e | {
+    StoreError::SerdeError("Failed to deserialize components".to_string(), e)
+    }) ?)]
+    pub components: Vec<SubscriptionComponentNewInternal>,
+    pub mrr_delta: i64,
+    pub effective_date: NaiveDate,
+    pub created_at: NaiveDateTime,
+    pub created_by: Uuid,
+    pub applied_at: Option<NaiveDateTime>,
+}
+
+#[derive(Debug, Clone, o2o)]
+#[owned_try_into(SubscriptionPlanChangeRowNew, StoreErrorReport)]
+#[ghosts(id: {Uuid::now_v7()})]
+pub struct SubscriptionPlanChangeNew {
+    pub subscription_id: Uuid,
+    pub tenant_id: Uuid,
+    pub plan_version_id: Uuid,
+    #[into(serde_json::to_value(& ~).map_err(| e | {
+    StoreError::SerdeError("Failed to serialize components".to_string(), e)
+    }) ?)]
+    pub components: Vec<SubscriptionComponentNewInternal>,
+    pub mrr_delta: i64,
+    pub effective_date: NaiveDate,
+    pub created_by: Uuid,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PlanChangeEffectiveAt {
+    Immediate,
+    EndOfBillingPeriod,
+}
+
+#[derive(Debug, Clone)]
+pub struct ChangeSubscriptionPlan {
+    pub subscription_id: Uuid,
+    pub plan_version_id: Uuid,
+    // same parameterization as on creation, applied to the new plan version components
+    pub components: Option<CreateSubscriptionComponents>,
+    pub effective_at: PlanChangeEffectiveAt,
+}
+
+/// The unused part of the current period, for the old and the new components.
+#[derive(Debug, Clone, Default)]
+pub struct PlanChangeProration {
+    // credited to the customer balance
+    pub credit_lines: Vec<LineItem>,
+    // billed in an adjustment invoice
+    pub charge_lines: Vec<LineItem>,
+}
+
+impl PlanChangeProration {
+    /// Both sides are taxed with the customer tax, so that the credit matches what was paid for the unused period.
+    pub fn new(
+        credit_lines: Vec<LineItem>,
+        charge_lines: Vec<LineItem>,
+        tax: &ResolvedTax,
+    ) -> Self {
+        PlanChangeProration {
+            credit_lines: LineTaxes::compute(&credit_lines, 0, tax).line_items,
+            charge_lines: LineTaxes::compute(&charge_lines, 0, tax).line_items,
+        }
+    }
+
+    // tax included
+    pub fn credit_total(&self) -> i64 {
+        self.credit_lines
+            .iter()
+            .map(|l| l.total + l.tax_amount)
+            .sum()
+    }
+
+    // tax included
+    pub fn charge_total(&self) -> i64 {
+        self.charge_lines
+            .iter()
+            .map(|l| l.total + l.tax_amount)
+            .sum()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct SubscriptionPlanChanged {
+    pub subscription: Subscription,
+    pub mrr_delta: i64,
+    pub effective_date: NaiveDate,
+    // only for immediate changes of activated subscriptions
+    pub proration: Option<PlanChangeProration>,
+    pub adjustment_invoice_id: Option<Uuid>,
+}
+
+/// Moves the subscriptions of a plan version to another version of the same plan, at the end of their billing period.
+#[derive(Debug, Clone)]
+pub struct MigrateSubscriptions {
+    pub from_plan_version_id: Uuid,
+    pub to_plan_version_id: Uuid,
+    // all the active subscriptions of the version if None
+    pub subscription_ids: Option<Vec<Uuid>>,
+    pub customer_ids: Option<Vec<Uuid>>,
+    // only reports the impact, nothing is scheduled
+    pub dry_run: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct SubscriptionMigration {
+    pub subscription_id: Uuid,
+    pub customer_id: Uuid,
+    pub customer_name: String,
+    pub effective_date: Option<NaiveDate>,
+    pub current_mrr: i64,
+    pub new_mrr: i64,
+    // subtotal of the invoice at the effective date, before and after the migration
+    pub current_invoice_subtotal: i64,
+    pub new_invoice_subtotal: i64,
+    // the version of the pending plan change of the subscription, replaced by the migration
+    pub replaced_plan_version_id: Option<Uuid>,
+    // the subscription cannot be migrated, ex: the new version has no rate for its billing period
+    pub error: Option<String>,
+}
+
+impl SubscriptionMigration {
+    pub fn mrr_delta(&self) -> i64 {
+        self.new_mrr - self.current_mrr
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::taxes::TaxTreatment;
+    use rust_decimal::Decimal;
+    use rust_decimal_macros::dec;
+
+    fn line(total: i64) -> LineItem {
+        let date = NaiveDate::from_ymd_opt(2024, 1, 16).unwrap();
+        LineItem {
+            local_id: "line".to_string(),
+            name: "line".to_string(),
+            total,
+            subtotal: total,
+            quantity: None,
+            unit_price: None,
+            start_date: date,
+            end_date: date,
+            sub_lines: vec![],
+            is_prorated: true,
+            price_component_id: None,
+            product_id: None,
+            metric_id: None,
+            add_on_id: None,
+            description: None,
+            tax_rate: Decimal::ZERO,
+            tax_amount: 0,
+            is_manual: false,
+        }
+    }
+
+    #[test]
+    fn test_proration_is_taxed_on_both_sides() {
+        let tax = ResolvedTax {
+            name: Some("VAT".to_string()),
+            rate: dec!(20),
+            treatment: TaxTreatment::Standard,
+        };
+
+        let proration = PlanChangeProration::new(vec![line(500)], vec![line(1000)], &tax);
+
+        assert_eq!(proration.credit_lines[0].tax_amount, 100);
+        assert_eq!(proration.credit_lines[0].tax_rate, dec!(20));
+        assert_eq!(proration.credit_total(), 600);
+        assert_eq!(proration.charge_total(), 1200);
+
+        let untaxed = PlanChangeProration::new(
+            vec![line(500)],
+            vec![line(1000)],
+            &ResolvedTax::out_of_scope(),
+        );
+        assert_eq!(untaxed.credit_total(), 500);
+        assert_eq!(untaxed.charge_total(), 1000);
+    }
+}
@@ -0,0 +1,84 @@
+use crate::domain::enums::ActionAfterTrialEnum;
+use chrono::NaiveDate;
+use diesel_models::subscriptions::SubscriptionRow;
+use o2o::o2o;
+use uuid::Uuid;
+
+/// A subscription whose trial is over, but whose action after trial was not applied yet.
+#[derive(Debug, Clone, o2o)]
+#[from_owned(SubscriptionRow)]
+pub struct ExpiredTrial {
+    pub id: Uuid,
+    pub tenant_id: Uuid,
+    pub trial_start_date: Option<NaiveDate>,
+    pub billing_start_date: NaiveDate,
+}
+
+#[derive(Debug, Clone)]
+pub struct SubscriptionTrialEnded {
+    pub subscription_id: Uuid,
+    pub tenant_id: Uuid,
+    pub action_after_trial: ActionAfterTrialEnum,
+    // the plan version the subscription is on after the trial
+    pub plan_version_id: Uuid,
+    pub mrr_delta: i64,
+    pub invoice_id: Option<Uuid>,
+}
+
+impl ActionAfterTrialEnum {
+    /// Billing starts at the end of the trial, so a plan without any configured action charges.
+    /// A downgrade without a downgrade plan cannot be applied, and blocks instead.
+    pub fn resolve(
+        action: Option<ActionAfterTrialEnum>,
+        downgrade_plan_id: Option<Uuid>,
+    ) -> ActionAfterTrialEnum {
+        match action {
+            None | Some(ActionAfterTrialEnum::Charge) => ActionAfterTrialEnum::Charge,
+            Some(ActionAfterTrialEnum::Downgrade) if downgrade_plan_id.is_some() => {
+                ActionAfterTrialEnum::Downgrade
+            }
+            Some(ActionAfterTrialEnum::Downgrade) | Some(ActionAfterTrialEnum::Block) => {
+                ActionAfterTrialEnum::Block
+            }
+        }
+    }
+
+    pub fn as_webhook_str(&self) -> &'static str {
+        match self {
+            ActionAfterTrialEnum::Block => "block",
+            ActionAfterTrialEnum::Charge => "charge",
+            ActionAfterTrialEnum::Downgrade => "downgrade",
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resolve_action_after_trial() {
+        let plan_id = Some(Uuid::now_v7());
+
+        assert_eq!(
+            ActionAfterTrialEnum::resolve(None, None),
+            ActionAfterTrialEnum::Charge
+        );
+        assert_eq!(
+            ActionAfterTrialEnum::resolve(Some(ActionAfterTrialEnum::Charge), plan_id),
+            ActionAfterTrialEnum::Charge
+        );
+        assert_eq!(
+            ActionAfterTrialEnum::resolve(Some(ActionAfterTrialEnum::Downgrade), plan_id),
+            ActionAfterTrialEnum::Downgrade
+        );
+        assert_eq!(
+            ActionAfterTrialEnum::resolve(Some(ActionAfterTrialEnum::Downgrade), None),
+            ActionAfterTrialEnum::Block
+        );
+        assert_eq!(
+            ActionAfterTrialEnum::resolve(Some(ActionAfterTrialEnum::Block), plan_id),
+            ActionAfterTrialEnum::Block
+        );
+    }
+}
@@ -37,6 +37,8 @@ pub struct CreatedSubscription {
     pub mrr_cents: i64,
     #[from(~.into())]
     pub period: BillingPeriodEnum,
+    pub minimum_spend: Option<rust_decimal::Decimal>,
+    pub spend_cap: Option<rust_decimal::Decimal>,
 }
 
 #[derive(Debug, Clone)]
@@ -55,6 +57,8 @@ pub struct Subscription {
     pub plan_name: String,
     pub plan_version_id: Uuid,
     pub version: u32,
+    /// The number of periods after which the subscription stops being billed, from the plan version.
+    pub billing_cycles: Option<i32>,
     pub created_at: NaiveDateTime,
     pub created_by: Uuid,
     // pub created_by_name: String,
@@ -66,6 +70,15 @@ pub struct Subscription {
     pub cancellation_reason: Option<String>,
     pub mrr_cents: u64,
     pub period: BillingPeriodEnum,
+    pub trial_ended_at: Option<NaiveDateTime>,
+    pub paused_at: Option<NaiveDateTime>,
+    pub resume_at: Option<NaiveDate>,
+    /// Minimum billed per period, a true-up line making up for the difference.
+    pub minimum_spend: Option<rust_decimal::Decimal>,
+    /// Maximum billed for usage per period, the excess being deducted.
+    pub spend_cap: Option<rust_decimal::Decimal>,
+    /// The billing periods elapsed before the subscription was last resumed, the billing start date being moved to the resume date.
+    pub elapsed_billing_cycles: i32,
 }
 
 impl From<SubscriptionForDisplayRow> for Subscription {
@@ -85,6 +98,7 @@ impl From<SubscriptionForDisplayRow> for Subscription {
             plan_name: val.plan_name,
             plan_version_id: val.subscription.plan_version_id,
             version: val.version as u32,
+            billing_cycles: val.billing_cycles,
             created_at: val.subscription.created_at,
             created_by: val.subscription.created_by,
             net_terms: val.subscription.net_terms as u32,
@@ -95,6 +109,12 @@ impl From<SubscriptionForDisplayRow> for Subscription {
             cancellation_reason: val.subscription.cancellation_reason,
             mrr_cents: val.subscription.mrr_cents as u64,
             period: val.subscription.period.into(),
+            trial_ended_at: val.subscription.trial_ended_at,
+            paused_at: val.subscription.paused_at,
+            resume_at: val.subscription.resume_at,
+            minimum_spend: val.subscription.minimum_spend,
+            spend_cap: val.subscription.spend_cap,
+            elapsed_billing_cycles: val.subscription.elapsed_billing_cycles,
         }
     }
 }
@@ -113,6 +133,8 @@ pub struct SubscriptionNew {
     pub invoice_memo: Option<String>,
     pub invoice_threshold: Option<rust_decimal::Decimal>,
     pub activated_at: Option<NaiveDateTime>,
+    pub minimum_spend: Option<rust_decimal::Decimal>,
+    pub spend_cap: Option<rust_decimal::Decimal>,
 }
 
 impl SubscriptionNew {
@@ -143,6 +165,8 @@ impl SubscriptionNew {
             },
             mrr_cents: 0,
             period: period.into(),
+            minimum_spend: self.minimum_spend,
+            spend_cap: self.spend_cap,
         }
     }
 }
@@ -177,6 +201,7 @@ pub struct SubscriptionDetails {
 
     //
     pub version: u32,
+    pub billing_cycles: Option<i32>,
     pub plan_name: String,
     pub plan_id: Uuid,
     pub customer_name: String,
@@ -190,6 +215,22 @@ pub struct SubscriptionDetails {
     pub created_by: Uuid,
     pub trial_start_date: Option<chrono::NaiveDate>,
     pub period: BillingPeriodEnum,
+    pub paused_at: Option<chrono::NaiveDateTime>,
+    pub resume_at: Option<chrono::NaiveDate>,
+    pub minimum_spend: Option<rust_decimal::Decimal>,
+    pub spend_cap: Option<rust_decimal::Decimal>,
+    pub elapsed_billing_cycles: i32,
+}
+
+impl SubscriptionDetails {
+    /// The applied coupons restricted to the plan of the subscription and still running at `invoice_date`.
+    pub fn invoice_coupons(&self, invoice_date: NaiveDate) -> Vec<AppliedCouponDetailed> {
+        self.applied_coupons
+            .iter()
+            .filter(|c| c.applies_to_invoice(self.plan_id, self.plan_version_id, invoice_date))
+            .cloned()
+            .collect()
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -0,0 +1,366 @@
+use crate::domain::enums::CustomerTaxStatusEnum;
+use crate::domain::invoice_lines::LineItem;
+use crate::domain::Address;
+use chrono::NaiveDateTime;
+use diesel_models::tax_rates::{TaxRateRow, TaxRateRowNew};
+use o2o::o2o;
+use rust_decimal::prelude::ToPrimitive;
+use rust_decimal::{Decimal, RoundingStrategy};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+#[derive(Debug, Clone, o2o)]
+#[from_owned(TaxRateRow)]
+pub struct TaxRate {
+    pub id: Uuid,
+    pub tenant_id: Uuid,
+    pub name: String,
+    pub country: String,
+    pub region: Option<String>,
+    // percentage, ex: 20 for 20%
+    pub rate: Decimal,
+    pub created_at: NaiveDateTime,
+    pub archived_at: Option<NaiveDateTime>,
+}
+
+#[derive(Debug, Clone, o2o)]
+#[owned_into(TaxRateRowNew)]
+#[ghosts(id: {Uuid::now_v7()})]
+pub struct TaxRateNew {
+    pub tenant_id: Uuid,
+    pub name: String,
+    pub country: String,
+    pub region: Option<String>,
+    pub rate: Decimal,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TaxTreatment {
+    Standard,
+    Exempt,
+    ReverseCharge,
+    // no rate is configured for the customer jurisdiction
+    OutOfScope,
+}
+
+/// The tax that applies to a customer of a given invoicing entity.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct ResolvedTax {
+    pub name: Option<String>,
+    pub rate: Decimal,
+    pub treatment: TaxTreatment,
+}
+
+impl ResolvedTax {
+    pub fn out_of_scope() -> Self {
+        ResolvedTax {
+            name: None,
+            rate: Decimal::ZERO,
+            treatment: TaxTreatment::OutOfScope,
+        }
+    }
+
+    /// Resolves the tax for a customer, in this order :
+    /// - exempt customers are never taxed
+    /// - reverse-charge customers outside the seller country self-assess the tax
+    /// - else we use the rate of the customer jurisdiction (country + state, then country only).
+    ///   Customers without a billing country are taxed in the seller jurisdiction.
+    pub fn resolve(
+        seller_country: &str,
+        customer_tax_status: CustomerTaxStatusEnum,
+        customer_address: Option<&Address>,
+        rates: &[TaxRate],
+    ) -> Self {
+        if customer_tax_status == CustomerTaxStatusEnum::Exempt {
+            return ResolvedTax {
+                name: None,
+                rate: Decimal::ZERO,
+                treatment: TaxTreatment::Exempt,
+            };
+        }
+
+        let customer_country = Self::customer_country(seller_country, customer_address);
+
+        if customer_tax_status == CustomerTaxStatusEnum::ReverseCharge
+            && !customer_country.eq_ignore_ascii_case(seller_country)
+        {
+            return ResolvedTax {
+                name: None,
+                rate: Decimal::ZERO,
+                treatment: TaxTreatment::ReverseCharge,
+            };
+        }
+
+        let region = customer_address.and_then(|a| a.state.as_deref());
+
+        let in_country = |r: &&TaxRate| r.country.eq_ignore_ascii_case(&customer_country);
+
+        let regional = region.and_then(|region| {
+            rates.iter().filter(in_country).find(|r| {
+                r.region
+                    .as_deref()
+                    .is_some_and(|x| x.eq_ignore_ascii_case(region))
+            })
+        });
+
+        regional
+            .or_else(|| rates.iter().filter(in_country).find(|r| r.region.is_none()))
+            .map(|r| ResolvedTax {
+                name: Some(r.name.clone()),
+                rate: r.rate,
+                treatment: TaxTreatment::Standard,
+            })
+            .unwrap_or_else(Self::out_of_scope)
+    }
+
+    pub fn customer_country(seller_country: &str, customer_address: Option<&Address>) -> String {
+        customer_address
+            .and_then(|a| a.country.clone())
+            .filter(|c| !c.is_empty())
+            .unwrap_or_else(|| seller_country.to_string())
+            .to_uppercase()
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct TaxBreakdownItem {
+    pub name: Option<String>,
+    pub rate: Decimal,
+    pub treatment: TaxTreatment,
+    pub taxable_amount: i64,
+    pub tax_amount: i64,
+}
+
+pub struct LineTaxes {
+    pub line_items: Vec<LineItem>,
+    pub breakdown: Vec<TaxBreakdownItem>,
+    pub tax_amount: i64,
+}
+
+impl LineTaxes {
+    /// Computes the tax of each line.
+    /// The invoice level discount is allocated to the lines pro rata of their subtotal,
+    /// so that the sum of the line taxes matches the tax of the discounted subtotal.
+    pub fn compute(line_items: &[LineItem], discount: i64, tax: &ResolvedTax) -> Self {
+        let subtotals = line_items.iter().map(|l| l.subtotal).collect::<Vec<_>>();
+        let line_discounts = allocate_pro_rata(discount, &subtotals);
+
+        let mut taxable_amount = 0;
+        let mut tax_amount = 0;
+
+        let line_items = line_items
+            .iter()
+            .zip(line_discounts)
+            .map(|(line, line_discount)| {
+                let line_taxable = line.subtotal - line_discount;
+                let line_tax = (Decimal::from(line_taxable) * tax.rate / Decimal::ONE_HUNDRED)
+                    .round_dp_with_strategy(0, RoundingStrategy::MidpointAwayFromZero)
+                    .to_i64()
+                    .unwrap_or(0);
+
+                taxable_amount += line_taxable;
+                tax_amount += line_tax;
+
+                LineItem {
+                    tax_rate: tax.rate,
+                    tax_amount: line_tax,
+                    ..line.clone()
+                }
+            })
+            .collect::<Vec<_>>();
+
+        let breakdown = if line_items.is_empty() {
+            vec![]
+        } else {
+            vec![TaxBreakdownItem {
+                name: tax.name.clone(),
+                rate: tax.rate,
+                treatment: tax.treatment,
+                taxable_amount,
+                tax_amount,
+            }]
+        };
+
+        LineTaxes {
+            line_items,
+            breakdown,
+            tax_amount,
+        }
+    }
+}
+
+/// Splits an amount between weighted parts, pro rata of their weight.
+/// Parts with a non-positive weight get nothing, and the last positive part gets the rounding remainder
+/// so that the allocated amounts always sum up to the initial amount.
+pub(crate) fn allocate_pro_rata(amount: i64, weights: &[i64]) -> Vec<i64> {
+    let total_weight: i64 = weights.iter().filter(|w| **w > 0).sum();
+    let last_idx = weights.iter().rposition(|w| *w > 0);
+    let mut remaining = amount;
+
+    weights
+        .iter()
+        .enumerate()
+        .map(|(idx, weight)| {
+            let allocated = if total_weight <= 0 || *weight <= 0 {
+                0
+            } else if Some(idx) == last_idx {
+                remaining
+            } else {
+                (Decimal::from(amount) * Decimal::from(*weight) / Decimal::from(total_weight))
+                    .round_dp_with_strategy(0, RoundingStrategy::ToZero)
+                    .to_i64()
+                    .unwrap_or(0)
+            };
+            remaining -= allocated;
+            allocated
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+    use rust_decimal_macros::dec;
+
+    fn rate(country: &str, region: Option<&str>, rate: Decimal) -> TaxRate {
+        TaxRate {
+            id: Uuid::now_v7(),
+            tenant_id: Uuid::nil(),
+            name: format!("VAT {}", country),
+            country: country.to_string(),
+            region: region.map(|x| x.to_string()),
+            rate,
+            created_at: NaiveDateTime::default(),
+            archived_at: None,
+        }
+    }
+
+    fn address(country: &str, state: Option<&str>) -> Address {
+        Address {
+            line1: None,
+            line2: None,
+            city: None,
+            country: Some(country.to_string()),
+            state: state.map(|x| x.to_string()),
+            zip_code: None,
+        }
+    }
+
+    fn line(subtotal: i64) -> LineItem {
+        let date = NaiveDate::from_ymd_opt(2024, 1, 1).unwrap();
+        LineItem {
+            local_id: "line".to_string(),
+            name: "line".to_string(),
+            total: subtotal,
+            subtotal,
+            quantity: None,
+            unit_price: None,
+            start_date: date,
+            end_date: date,
+            sub_lines: vec![],
+            is_prorated: false,
+            price_component_id: None,
+            product_id: None,
+            metric_id: None,
+            add_on_id: None,
+            description: None,
+            tax_rate: Decimal::ZERO,
+            tax_amount: 0,
+            is_manual: false,
+        }
+    }
+
+    #[test]
+    fn test_resolve_customer_jurisdiction() {
+        let rates = vec![
+            rate("FR", None, dec!(20)),
+            rate("DE", None, dec!(19)),
+            rate("US", Some("NY"), dec!(8.875)),
+        ];
+
+        let resolved = ResolvedTax::resolve(
+            "FR",
+            CustomerTaxStatusEnum::Taxable,
+            Some(&address("DE", None)),
+            &rates,
+        );
+        assert_eq!(resolved.rate, dec!(19));
+        assert_eq!(resolved.treatment, TaxTreatment::Standard);
+
+        let resolved = ResolvedTax::resolve(
+            "FR",
+            CustomerTaxStatusEnum::Taxable,
+            Some(&address("US", Some("NY"))),
+            &rates,
+        );
+        assert_eq!(resolved.rate, dec!(8.875));
+
+        let resolved = ResolvedTax::resolve(
+            "FR",
+            CustomerTaxStatusEnum::Taxable,
+            Some(&address("US", Some("CA"))),
+            &rates,
+        );
+        assert_eq!(resolved, ResolvedTax::out_of_scope());
+
+        let resolved = ResolvedTax::resolve("FR", CustomerTaxStatusEnum::Taxable, None, &rates);
+        assert_eq!(resolved.rate, dec!(20));
+    }
+
+    #[test]
+    fn test_resolve_exempt_and_reverse_charge() {
+        let rates = vec![rate("FR", None, dec!(20)), rate("DE", None, dec!(19))];
+
+        let resolved = ResolvedTax::resolve(
+            "FR",
+            CustomerTaxStatusEnum::Exempt,
+            Some(&address("FR", None)),
+            &rates,
+        );
+        assert_eq!(resolved.treatment, TaxTreatment::Exempt);
+        assert_eq!(resolved.rate, Decimal::ZERO);
+
+        let resolved = ResolvedTax::resolve(
+            "FR",
+            CustomerTaxStatusEnum::ReverseCharge,
+            Some(&address("DE", None)),
+            &rates,
+        );
+        assert_eq!(resolved.treatment, TaxTreatment::ReverseCharge);
+        assert_eq!(resolved.rate, Decimal::ZERO);
+
+        // domestic B2B is taxed normally
+        let resolved = ResolvedTax::resolve(
+            "FR",
+            CustomerTaxStatusEnum::ReverseCharge,
+            Some(&address("FR", None)),
+            &rates,
+        );
+        assert_eq!(resolved.treatment, TaxTreatment::Standard);
+        assert_eq!(resolved.rate, dec!(20));
+    }
+
+    #[test]
+    fn test_line_taxes_with_discount() {
+        let tax = ResolvedTax {
+            name: Some("VAT".to_string()),
+            rate: dec!(20),
+            treatment: TaxTreatment::Standard,
+        };
+
+        let res = LineTaxes::compute(&[line(1000), line(2000), line(333)], 1000, &tax);
+
+        assert_eq!(
+            res.line_items
+                .iter()
+                .map(|l| l.tax_amount)
+                .collect::<Vec<_>>(),
+            vec![140, 280, 47]
+        );
+        assert_eq!(res.tax_amount, 467);
+        assert_eq!(res.breakdown.len(), 1);
+        assert_eq!(res.breakdown[0].taxable_amount, 2333);
+        assert_eq!(res.breakdown[0].tax_amount, 467);
+    }
+}
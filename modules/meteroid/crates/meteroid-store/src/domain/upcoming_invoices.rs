@@ -0,0 +1,37 @@
+use crate::domain::{CreateSubscriptionAddOns, CreateSubscriptionComponents, LineItem};
+use chrono::NaiveDate;
+use std::collections::HashMap;
+use uuid::Uuid;
+
+/// Hypothetical changes to a subscription, applied to the preview of its upcoming invoice.
+/// Nothing is persisted.
+#[derive(Debug, Clone, Default)]
+pub struct UpcomingInvoiceChanges {
+    /// The slot counts by price component, in place of the current ones.
+    pub slots: HashMap<Uuid, u32>,
+    /// The add-ons added to the subscription.
+    pub add_ons: Option<CreateSubscriptionAddOns>,
+    pub plan_version: Option<UpcomingPlanVersion>,
+}
+
+/// Another version of the plan, with the parameterization of its components as on a plan change.
+#[derive(Debug, Clone)]
+pub struct UpcomingPlanVersion {
+    pub plan_version_id: Uuid,
+    pub components: Option<CreateSubscriptionComponents>,
+}
+
+#[derive(Debug, Clone)]
+pub struct UpcomingInvoice {
+    pub subscription_id: Uuid,
+    pub plan_version_id: Uuid,
+    pub invoice_date: NaiveDate,
+    pub currency: String,
+    pub line_items: Vec<LineItem>,
+    pub subtotal: i64,
+    pub tax_amount: i64,
+    pub total: i64,
+    /// The credits of the customer balance and grants, as they would apply today.
+    pub applied_credits: i64,
+    pub amount_due: i64,
+}
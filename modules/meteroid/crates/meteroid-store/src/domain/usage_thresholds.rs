@@ -0,0 +1,173 @@
+use crate::domain::LineItem;
+use crate::utils::local_id::LocalId;
+use diesel_models::subscriptions::SubscriptionRow;
+use o2o::o2o;
+use rust_decimal::Decimal;
+use uuid::Uuid;
+
+/// A subscription with a usage threshold, whose accrued usage is checked by the threshold worker.
+#[derive(Debug, Clone, o2o)]
+#[from_owned(SubscriptionRow)]
+pub struct UsageThresholdCandidate {
+    pub id: Uuid,
+    pub tenant_id: Uuid,
+    pub invoice_threshold: Option<Decimal>,
+}
+
+/// The part of a usage line already billed by usage-threshold invoices.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct InvoicedUsage {
+    pub subtotal: i64,
+    pub total: i64,
+    pub quantity: Decimal,
+}
+
+impl InvoicedUsage {
+    /// Sums the lines of threshold invoices billing the same usage fee, over the same period.
+    pub fn of(line: &LineItem, invoiced_lines: &[LineItem]) -> Self {
+        invoiced_lines
+            .iter()
+            .filter(|invoiced| is_same_usage(line, invoiced))
+            .fold(InvoicedUsage::default(), |acc, invoiced| InvoicedUsage {
+                subtotal: acc.subtotal + invoiced.subtotal,
+                total: acc.total + invoiced.total,
+                quantity: acc.quantity + invoiced.quantity.unwrap_or_default(),
+            })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.subtotal == 0 && self.total == 0
+    }
+}
+
+// usage lines of a same fee and period start at the same date, whatever the date they are billed at
+fn is_same_usage(line: &LineItem, other: &LineItem) -> bool {
+    line.metric_id.is_some()
+        && line.metric_id == other.metric_id
+        && line.price_component_id == other.price_component_id
+        && line.start_date == other.start_date
+}
+
+/// The accrued usage that was not billed yet by the previous threshold invoices of the period.
+pub fn unbilled_usage_lines(accrued: Vec<LineItem>, invoiced_lines: &[LineItem]) -> Vec<LineItem> {
+    accrued
+        .into_iter()
+        .filter_map(|line| {
+            let invoiced = InvoicedUsage::of(&line, invoiced_lines);
+
+            if invoiced.is_empty() {
+                return Some(line);
+            }
+
+            let subtotal = line.subtotal - invoiced.subtotal;
+
+            if subtotal <= 0 {
+                return None;
+            }
+
+            Some(LineItem {
+                local_id: LocalId::no_prefix(),
+                subtotal,
+                total: line.total - invoiced.total,
+                quantity: line.quantity.map(|q| q - invoiced.quantity),
+                // the tiers and dimensions do not apply to the difference
+                sub_lines: vec![],
+                ..line
+            })
+        })
+        .collect()
+}
+
+/// The deduction, on a recurring invoice, of the usage already billed by threshold invoices in the same period.
+pub fn usage_deduction_lines(lines: &[LineItem], invoiced_lines: &[LineItem]) -> Vec<LineItem> {
+    lines
+        .iter()
+        .filter_map(|line| {
+            let invoiced = InvoicedUsage::of(line, invoiced_lines);
+
+            if invoiced.is_empty() {
+                return None;
+            }
+
+            Some(LineItem {
+                local_id: LocalId::no_prefix(),
+                name: format!("{} (already invoiced)", line.name),
+                subtotal: -invoiced.subtotal,
+                total: -invoiced.total,
+                quantity: line.quantity.map(|_| -invoiced.quantity),
+                sub_lines: vec![],
+                is_prorated: false,
+                description: Some("Usage billed on reaching the invoice threshold".to_string()),
+                tax_rate: Decimal::ZERO,
+                tax_amount: 0,
+                ..line.clone()
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+    use rust_decimal_macros::dec;
+
+    fn usage_line(metric_id: Uuid, start: NaiveDate, end: NaiveDate, total: i64) -> LineItem {
+        LineItem {
+            local_id: LocalId::no_prefix(),
+            name: "API calls".to_string(),
+            total,
+            subtotal: total,
+            quantity: Some(Decimal::from(total / 10)),
+            unit_price: Some(dec!(0.1)),
+            start_date: start,
+            end_date: end,
+            sub_lines: vec![],
+            is_prorated: false,
+            price_component_id: None,
+            product_id: None,
+            metric_id: Some(metric_id),
+            add_on_id: None,
+            description: None,
+            tax_rate: Decimal::ZERO,
+            tax_amount: 0,
+            is_manual: false,
+        }
+    }
+
+    #[test]
+    fn test_usage_already_invoiced_at_threshold() {
+        let metric_id = Uuid::now_v7();
+        let start = NaiveDate::from_ymd_opt(2024, 1, 1).unwrap();
+        let threshold_date = NaiveDate::from_ymd_opt(2024, 1, 10).unwrap();
+        let today = NaiveDate::from_ymd_opt(2024, 1, 20).unwrap();
+        let period_end = NaiveDate::from_ymd_opt(2024, 2, 1).unwrap();
+
+        let invoiced = vec![usage_line(metric_id, start, threshold_date, 1000)];
+
+        // a second threshold invoice only bills the usage accrued since the first one
+        let unbilled =
+            unbilled_usage_lines(vec![usage_line(metric_id, start, today, 2500)], &invoiced);
+        assert_eq!(unbilled.len(), 1);
+        assert_eq!(unbilled[0].subtotal, 1500);
+        assert_eq!(unbilled[0].quantity, Some(Decimal::from(150)));
+
+        // nothing new since the last threshold invoice
+        let unbilled =
+            unbilled_usage_lines(vec![usage_line(metric_id, start, today, 1000)], &invoiced);
+        assert!(unbilled.is_empty());
+
+        // the recurring invoice deducts the usage billed at threshold in its period only
+        let recurring_lines = vec![
+            usage_line(metric_id, start, period_end, 4000),
+            usage_line(Uuid::now_v7(), start, period_end, 500),
+        ];
+        let deductions = usage_deduction_lines(&recurring_lines, &invoiced);
+        assert_eq!(deductions.len(), 1);
+        assert_eq!(deductions[0].total, -1000);
+        assert_eq!(deductions[0].metric_id, Some(metric_id));
+
+        let next_period = vec![usage_line(metric_id, period_end, period_end, 4000)];
+        assert!(usage_deduction_lines(&next_period, &invoiced).is_empty());
+    }
+}
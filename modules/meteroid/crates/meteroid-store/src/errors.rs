@@ -42,6 +42,8 @@ pub enum StoreError {
     UserRegistrationClosed(String),
     #[error("Negative customer balance: {0:?}")]
     NegativeCustomerBalanceError(error_stack::Report<DatabaseError>),
+    #[error("Invoicing provider error: {0}")]
+    InvoicingProviderError(String),
     #[error("Metering Service error: {0}")]
     MeteringServiceError(String, #[source] ComputeError),
 }
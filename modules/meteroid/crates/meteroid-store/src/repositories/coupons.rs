@@ -1,7 +1,8 @@
-use crate::domain::coupons::{Coupon, CouponNew, CouponPatch};
+use crate::domain::coupons::{Coupon, CouponNew, CouponPatch, PromotionCode, PromotionCodeNew};
 use crate::errors::StoreError;
 use crate::{Store, StoreResult};
 use diesel_models::coupons::{CouponRow, CouponRowNew, CouponRowPatch};
+use diesel_models::promotion_codes::{PromotionCodeRow, PromotionCodeRowNew};
 use error_stack::Report;
 use uuid::Uuid;
 
@@ -12,6 +13,19 @@ pub trait CouponInterface {
     async fn create_coupon(&self, coupon: CouponNew) -> StoreResult<Coupon>;
     async fn delete_coupon(&self, tenant_id: Uuid, id: Uuid) -> StoreResult<()>;
     async fn update_coupon(&self, coupon: CouponPatch) -> StoreResult<Coupon>;
+
+    async fn list_promotion_codes(
+        &self,
+        tenant_id: Uuid,
+        coupon_id: Uuid,
+    ) -> StoreResult<Vec<PromotionCode>>;
+    async fn create_promotion_code(
+        &self,
+        promotion_code: PromotionCodeNew,
+    ) -> StoreResult<PromotionCode>;
+    /// Archived codes can no longer be redeemed. The coupons already applied with them are kept.
+    async fn archive_promotion_code(&self, tenant_id: Uuid, id: Uuid)
+        -> StoreResult<PromotionCode>;
 }
 
 #[async_trait::async_trait]
@@ -41,6 +55,12 @@ impl CouponInterface for Store {
     async fn create_coupon(&self, coupon: CouponNew) -> StoreResult<Coupon> {
         let mut conn = self.get_conn().await?;
 
+        if coupon.duration_months.is_some_and(|x| x <= 0) {
+            return Err(Report::new(StoreError::InvalidArgument(
+                "the duration in months must be positive".to_string(),
+            )));
+        }
+
         let coupon: CouponRowNew = coupon.try_into()?;
 
         coupon
@@ -70,4 +90,66 @@ impl CouponInterface for Store {
             .map_err(Into::<Report<StoreError>>::into)
             .and_then(TryInto::try_into)
     }
+
+    async fn list_promotion_codes(
+        &self,
+        tenant_id: Uuid,
+        coupon_id: Uuid,
+    ) -> StoreResult<Vec<PromotionCode>> {
+        let mut conn = self.get_conn().await?;
+
+        PromotionCodeRow::list_by_coupon_id(&mut conn, tenant_id, coupon_id)
+            .await
+            .map_err(Into::<Report<StoreError>>::into)
+            .map(|x| x.into_iter().map(Into::into).collect())
+    }
+
+    async fn create_promotion_code(
+        &self,
+        promotion_code: PromotionCodeNew,
+    ) -> StoreResult<PromotionCode> {
+        let mut conn = self.get_conn().await?;
+
+        if promotion_code.code.trim().is_empty() {
+            return Err(Report::new(StoreError::InvalidArgument(
+                "the promotion code must not be empty".to_string(),
+            )));
+        }
+
+        let coupon: Coupon = CouponRow::get_by_id(
+            &mut conn,
+            promotion_code.tenant_id,
+            promotion_code.coupon_id,
+        )
+        .await
+        .map_err(Into::<Report<StoreError>>::into)
+        .and_then(TryInto::try_into)?;
+
+        if coupon.archived_at.is_some() {
+            return Err(Report::new(StoreError::InvalidArgument(format!(
+                "coupon {} is archived",
+                coupon.code
+            ))));
+        }
+
+        let row: PromotionCodeRowNew = promotion_code.into();
+
+        row.insert(&mut conn)
+            .await
+            .map_err(Into::<Report<StoreError>>::into)
+            .map(Into::into)
+    }
+
+    async fn archive_promotion_code(
+        &self,
+        tenant_id: Uuid,
+        id: Uuid,
+    ) -> StoreResult<PromotionCode> {
+        let mut conn = self.get_conn().await?;
+
+        PromotionCodeRow::archive(&mut conn, tenant_id, id)
+            .await
+            .map_err(Into::<Report<StoreError>>::into)
+            .map(Into::into)
+    }
 }
@@ -0,0 +1,259 @@
+use crate::domain::{
+    allocate_credit_grants, CreditGrant, CreditGrantNew, CursorPaginatedVec,
+    CursorPaginationRequest, DetailedInvoice, Invoice,
+};
+use crate::errors::StoreError;
+use crate::repositories::customer_balance::CustomerBalance;
+use crate::repositories::CustomersInterface;
+use crate::store::{PgConn, Store};
+use crate::StoreResult;
+use chrono::NaiveDate;
+use diesel_async::scoped_futures::ScopedFutureExt;
+use diesel_models::credit_grants::{CreditGrantRow, CreditGrantRowNew};
+use diesel_models::customer_balance_txs::CustomerBalanceTxRow;
+use diesel_models::customers::CustomerRow;
+use error_stack::Report;
+use std::cmp::min;
+use uuid::Uuid;
+
+#[async_trait::async_trait]
+pub trait CreditGrantInterface {
+    /// Grants prepaid credits to the customer. The amount is added to the customer balance.
+    async fn create_credit_grant(&self, grant: CreditGrantNew) -> StoreResult<CreditGrant>;
+
+    async fn list_credit_grants(
+        &self,
+        tenant_id: Uuid,
+        customer_id: Uuid,
+    ) -> StoreResult<Vec<CreditGrant>>;
+
+    async fn list_expired_credit_grants(
+        &self,
+        date: NaiveDate,
+        pagination: CursorPaginationRequest,
+    ) -> StoreResult<CursorPaginatedVec<CreditGrant>>;
+
+    /// Writes off the remaining amount of an expired grant from the customer balance.
+    async fn expire_credit_grant(&self, tenant_id: Uuid, id: Uuid) -> StoreResult<CreditGrant>;
+}
+
+#[async_trait::async_trait]
+impl CreditGrantInterface for Store {
+    async fn create_credit_grant(&self, grant: CreditGrantNew) -> StoreResult<CreditGrant> {
+        let customer = self
+            .find_customer_by_id(grant.customer_id, grant.tenant_id)
+            .await?;
+
+        grant.validate(&customer.currency)?;
+
+        let row: CreditGrantRowNew = grant.into();
+
+        self.transaction(|conn| {
+            async move {
+                let inserted: CreditGrant = row
+                    .insert(conn)
+                    .await
+                    .map_err(Into::<Report<StoreError>>::into)?
+                    .into();
+
+                CustomerBalance::update_credit_grant(
+                    conn,
+                    &inserted,
+                    inserted.amount_cents,
+                    None,
+                    "Credit grant",
+                )
+                .await?;
+
+                Ok(inserted)
+            }
+            .scope_boxed()
+        })
+        .await
+    }
+
+    async fn list_credit_grants(
+        &self,
+        tenant_id: Uuid,
+        customer_id: Uuid,
+    ) -> StoreResult<Vec<CreditGrant>> {
+        let mut conn = self.get_conn().await?;
+
+        CreditGrantRow::list_by_customer_id(&mut conn, tenant_id, customer_id)
+            .await
+            .map(|rows| rows.into_iter().map(Into::into).collect())
+            .map_err(Into::<Report<StoreError>>::into)
+    }
+
+    async fn list_expired_credit_grants(
+        &self,
+        date: NaiveDate,
+        pagination: CursorPaginationRequest,
+    ) -> StoreResult<CursorPaginatedVec<CreditGrant>> {
+        let mut conn = self.get_conn().await?;
+
+        let rows = CreditGrantRow::list_expired(&mut conn, date, pagination.into())
+            .await
+            .map_err(Into::<Report<StoreError>>::into)?;
+
+        Ok(CursorPaginatedVec {
+            items: rows.items.into_iter().map(Into::into).collect(),
+            next_cursor: rows.next_cursor,
+        })
+    }
+
+    async fn expire_credit_grant(&self, tenant_id: Uuid, id: Uuid) -> StoreResult<CreditGrant> {
+        self.transaction(|conn| {
+            async move {
+                let grant: CreditGrant =
+                    CreditGrantRow::select_for_update_by_id(conn, id, tenant_id)
+                        .await
+                        .map_err(Into::<Report<StoreError>>::into)?
+                        .into();
+
+                // expired concurrently
+                if grant.expired_at.is_some() {
+                    return Ok(grant);
+                }
+
+                write_off_credit_grant(conn, &grant, "Credit grant expired").await
+            }
+            .scope_boxed()
+        })
+        .await
+    }
+}
+
+/// Writes off the remaining amount of the grant from the customer balance, and marks it as expired.
+pub(crate) async fn write_off_credit_grant(
+    conn: &mut PgConn,
+    grant: &CreditGrant,
+    note: &str,
+) -> StoreResult<CreditGrant> {
+    let customer = CustomerRow::select_for_update(conn, grant.customer_id, grant.tenant_id)
+        .await
+        .map_err(Into::<Report<StoreError>>::into)?;
+
+    // the balance may have been decreased manually below the granted credits
+    let written_off = min(grant.remaining_cents, customer.balance_value_cents.max(0));
+
+    if written_off > 0 {
+        CustomerBalance::update_credit_grant(conn, grant, -written_off, None, note).await?;
+    }
+
+    CreditGrantRow::expire(conn, grant.id)
+        .await
+        .map(Into::into)
+        .map_err(Into::<Report<StoreError>>::into)
+}
+
+pub(crate) async fn list_available_credit_grants(
+    conn: &mut PgConn,
+    tenant_id: Uuid,
+    customer_id: Uuid,
+) -> StoreResult<Vec<CreditGrant>> {
+    CreditGrantRow::list_available_by_customer_id(conn, tenant_id, customer_id)
+        .await
+        .map(|rows| rows.into_iter().map(Into::into).collect())
+        .map_err(Into::<Report<StoreError>>::into)
+}
+
+/// Consumes the credit grants of the customer for the credits applied to the finalized invoice,
+/// each with a ledger entry. Returns the amount paid by the grants, the rest being taken from the balance.
+pub(crate) async fn consume_credit_grants(
+    conn: &mut PgConn,
+    invoice: &DetailedInvoice,
+) -> StoreResult<i64> {
+    let grants = CreditGrantRow::select_available_for_update_by_customer_id(
+        conn,
+        invoice.invoice.tenant_id,
+        invoice.customer.id,
+    )
+    .await
+    .map_err(Into::<Report<StoreError>>::into)?
+    .into_iter()
+    .map(Into::into)
+    .collect::<Vec<CreditGrant>>();
+
+    let allocations = allocate_credit_grants(
+        &grants,
+        &invoice.invoice.line_items,
+        invoice.invoice.applied_credits,
+        invoice.invoice.invoice_date,
+    );
+
+    let mut consumed = 0;
+
+    for (grant_id, cents) in allocations {
+        let grant = grants.iter().find(|g| g.id == grant_id).ok_or(Report::new(
+            StoreError::ValueNotFound(format!("credit grant {}", grant_id)),
+        ))?;
+
+        CreditGrantRow::consume(conn, grant_id, cents as i32)
+            .await
+            .map_err(Into::<Report<StoreError>>::into)?;
+
+        CustomerBalance::update_credit_grant(
+            conn,
+            grant,
+            -(cents as i32),
+            Some(invoice.invoice.id),
+            "Credit grant consumed",
+        )
+        .await?;
+
+        consumed += cents;
+    }
+
+    Ok(consumed)
+}
+
+/// Gives back to the credit grants what was consumed by the voided invoice, each with a ledger entry.
+/// Returns the amount that was paid by the grants, the rest having been taken from the balance.
+pub(crate) async fn restore_credit_grants(
+    conn: &mut PgConn,
+    invoice: &Invoice,
+) -> StoreResult<i64> {
+    let consumptions =
+        CustomerBalanceTxRow::list_by_invoice_id(conn, invoice.tenant_id, invoice.id)
+            .await
+            .map_err(Into::<Report<StoreError>>::into)?
+            .into_iter()
+            .filter(|tx| tx.amount_cents < 0)
+            .filter_map(|tx| {
+                tx.credit_grant_id
+                    .map(|grant_id| (grant_id, -tx.amount_cents))
+            });
+
+    let mut consumed = 0;
+
+    for (grant_id, cents) in consumptions {
+        consumed += cents as i64;
+
+        let grant: CreditGrant =
+            CreditGrantRow::select_for_update_by_id(conn, grant_id, invoice.tenant_id)
+                .await
+                .map_err(Into::<Report<StoreError>>::into)?
+                .into();
+
+        // the remaining amount of an expired grant was written off, the consumed credits expired with it
+        if grant.expired_at.is_some() {
+            continue;
+        }
+
+        CreditGrantRow::restore(conn, grant_id, cents)
+            .await
+            .map_err(Into::<Report<StoreError>>::into)?;
+
+        CustomerBalance::update_credit_grant(
+            conn,
+            &grant,
+            cents,
+            Some(invoice.id),
+            "Credit grant restored",
+        )
+        .await?;
+    }
+
+    Ok(consumed)
+}
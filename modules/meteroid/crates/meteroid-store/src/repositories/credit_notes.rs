@@ -0,0 +1,269 @@
+use crate::domain::enums::{CreditNoteStatus, InvoiceStatusEnum};
+use crate::domain::{
+    CreditNote, CreditNoteLines, CreditNoteNew, Invoice, OutboxEvent, OutboxNew, PaginatedVec,
+    PaginationRequest,
+};
+use crate::errors::StoreError;
+use crate::repositories::customer_balance::{to_balance_cents, CustomerBalance};
+use crate::repositories::invoicing_entities::next_credit_note_number;
+use crate::store::Store;
+use crate::StoreResult;
+use diesel_async::scoped_futures::ScopedFutureExt;
+use diesel_models::credit_notes::CreditNoteRow;
+use diesel_models::invoices::InvoiceRow;
+use error_stack::Report;
+use uuid::Uuid;
+
+#[async_trait::async_trait]
+pub trait CreditNoteInterface {
+    async fn find_credit_note_by_id(&self, tenant_id: Uuid, id: Uuid) -> StoreResult<CreditNote>;
+
+    async fn list_credit_notes(
+        &self,
+        tenant_id: Uuid,
+        customer_id: Option<Uuid>,
+        invoice_id: Option<Uuid>,
+        status: Option<CreditNoteStatus>,
+        pagination: PaginationRequest,
+    ) -> StoreResult<PaginatedVec<CreditNote>>;
+
+    async fn list_credit_notes_by_ids(&self, ids: Vec<Uuid>) -> StoreResult<Vec<CreditNote>>;
+
+    async fn create_credit_note(&self, credit_note: CreditNoteNew) -> StoreResult<CreditNote>;
+
+    async fn finalize_credit_note(&self, id: Uuid, tenant_id: Uuid) -> StoreResult<CreditNote>;
+
+    async fn void_credit_note(&self, id: Uuid, tenant_id: Uuid) -> StoreResult<CreditNote>;
+
+    async fn save_credit_note_documents(
+        &self,
+        id: Uuid,
+        tenant_id: Uuid,
+        pdf_id: String,
+    ) -> StoreResult<()>;
+}
+
+#[async_trait::async_trait]
+impl CreditNoteInterface for Store {
+    async fn find_credit_note_by_id(&self, tenant_id: Uuid, id: Uuid) -> StoreResult<CreditNote> {
+        let mut conn = self.get_conn().await?;
+
+        CreditNoteRow::find_by_id(&mut conn, tenant_id, id)
+            .await
+            .map_err(Into::<Report<StoreError>>::into)
+            .and_then(TryInto::try_into)
+    }
+
+    async fn list_credit_notes(
+        &self,
+        tenant_id: Uuid,
+        customer_id: Option<Uuid>,
+        invoice_id: Option<Uuid>,
+        status: Option<CreditNoteStatus>,
+        pagination: PaginationRequest,
+    ) -> StoreResult<PaginatedVec<CreditNote>> {
+        let mut conn = self.get_conn().await?;
+
+        let rows = CreditNoteRow::list(
+            &mut conn,
+            tenant_id,
+            customer_id,
+            invoice_id,
+            status.map(Into::into),
+            pagination.into(),
+        )
+        .await
+        .map_err(Into::<Report<StoreError>>::into)?;
+
+        Ok(PaginatedVec {
+            items: rows
+                .items
+                .into_iter()
+                .map(TryInto::try_into)
+                .collect::<Result<Vec<_>, _>>()?,
+            total_pages: rows.total_pages,
+            total_results: rows.total_results,
+        })
+    }
+
+    async fn list_credit_notes_by_ids(&self, ids: Vec<Uuid>) -> StoreResult<Vec<CreditNote>> {
+        let mut conn = self.get_conn().await?;
+
+        CreditNoteRow::list_by_ids(&mut conn, ids)
+            .await
+            .map_err(Into::<Report<StoreError>>::into)?
+            .into_iter()
+            .map(TryInto::try_into)
+            .collect()
+    }
+
+    async fn create_credit_note(&self, credit_note: CreditNoteNew) -> StoreResult<CreditNote> {
+        self.transaction(|conn| {
+            async move {
+                // locks the invoice, so that concurrent credit notes cannot exceed the invoice amount
+                let invoice: Invoice = InvoiceRow::select_for_update_by_id(
+                    conn,
+                    credit_note.tenant_id,
+                    credit_note.invoice_id,
+                )
+                .await
+                .map_err(Into::<Report<StoreError>>::into)?
+                .try_into()?;
+
+                if invoice.status != InvoiceStatusEnum::Finalized {
+                    return Err(Report::new(StoreError::InvalidArgument(
+                        "only finalized invoices can be credited".to_string(),
+                    )));
+                }
+
+                let credit_notes = CreditNoteRow::list_active_by_invoice_id(
+                    conn,
+                    credit_note.tenant_id,
+                    credit_note.invoice_id,
+                )
+                .await
+                .map_err(Into::<Report<StoreError>>::into)?
+                .into_iter()
+                .map(TryInto::try_into)
+                .collect::<Result<Vec<CreditNote>, _>>()?;
+
+                let lines = CreditNoteLines::compute(&invoice, &credit_notes, &credit_note.scope)?;
+
+                credit_note
+                    .into_row(&invoice, lines)?
+                    .insert(conn)
+                    .await
+                    .map_err(Into::<Report<StoreError>>::into)
+                    .and_then(TryInto::try_into)
+            }
+            .scope_boxed()
+        })
+        .await
+    }
+
+    async fn finalize_credit_note(&self, id: Uuid, tenant_id: Uuid) -> StoreResult<CreditNote> {
+        self.transaction(|conn| {
+            async move {
+                let credit_note: CreditNote = CreditNoteRow::select_for_update(conn, tenant_id, id)
+                    .await
+                    .map_err(Into::<Report<StoreError>>::into)?
+                    .try_into()?;
+
+                if credit_note.status != CreditNoteStatus::Draft {
+                    return Err(Report::new(StoreError::InvalidArgument(
+                        "only draft credit notes can be finalized".to_string(),
+                    )));
+                }
+
+                let invoice: Invoice =
+                    InvoiceRow::select_for_update_by_id(conn, tenant_id, credit_note.invoice_id)
+                        .await
+                        .map_err(Into::<Report<StoreError>>::into)?
+                        .try_into()?;
+
+                let credit_note_number = next_credit_note_number(
+                    conn,
+                    invoice.seller_details.id,
+                    tenant_id,
+                    chrono::Utc::now().date_naive(),
+                )
+                .await?;
+
+                let finalized: CreditNote =
+                    CreditNoteRow::finalize(conn, id, tenant_id, credit_note_number)
+                        .await
+                        .map_err(Into::<Report<StoreError>>::into)?
+                        .try_into()?;
+
+                let credited = finalized.credited_amount_cents.unwrap_or(0);
+                if credited > 0 {
+                    CustomerBalance::update(
+                        conn,
+                        finalized.customer_id,
+                        tenant_id,
+                        to_balance_cents(credited)?,
+                        Some(finalized.invoice_id),
+                    )
+                    .await?;
+                }
+
+                self.internal
+                    .insert_outbox_item(
+                        conn,
+                        OutboxNew {
+                            event_type: OutboxEvent::CreditNoteFinalized,
+                            resource_id: id,
+                            tenant_id,
+                            payload: None,
+                        },
+                    )
+                    .await?;
+
+                Ok(finalized)
+            }
+            .scope_boxed()
+        })
+        .await
+    }
+
+    async fn void_credit_note(&self, id: Uuid, tenant_id: Uuid) -> StoreResult<CreditNote> {
+        self.transaction(|conn| {
+            async move {
+                let credit_note: CreditNote = CreditNoteRow::select_for_update(conn, tenant_id, id)
+                    .await
+                    .map_err(Into::<Report<StoreError>>::into)?
+                    .try_into()?;
+
+                match credit_note.status {
+                    CreditNoteStatus::Voided => {
+                        return Err(Report::new(StoreError::InvalidArgument(
+                            "credit note is already voided".to_string(),
+                        )));
+                    }
+                    CreditNoteStatus::Draft => {}
+                    CreditNoteStatus::Finalized => {
+                        if credit_note.refunded_amount_cents.unwrap_or(0) > 0 {
+                            return Err(Report::new(StoreError::InvalidArgument(
+                                "refunded credit notes cannot be voided".to_string(),
+                            )));
+                        }
+
+                        // takes the credit back, fails if the customer already used it
+                        let credited = credit_note.credited_amount_cents.unwrap_or(0);
+                        if credited > 0 {
+                            CustomerBalance::update(
+                                conn,
+                                credit_note.customer_id,
+                                tenant_id,
+                                -to_balance_cents(credited)?,
+                                Some(credit_note.invoice_id),
+                            )
+                            .await?;
+                        }
+                    }
+                }
+
+                CreditNoteRow::void(conn, id, tenant_id)
+                    .await
+                    .map_err(Into::<Report<StoreError>>::into)
+                    .and_then(TryInto::try_into)
+            }
+            .scope_boxed()
+        })
+        .await
+    }
+
+    async fn save_credit_note_documents(
+        &self,
+        id: Uuid,
+        tenant_id: Uuid,
+        pdf_id: String,
+    ) -> StoreResult<()> {
+        let mut conn = self.get_conn().await?;
+
+        CreditNoteRow::save_documents(&mut conn, id, tenant_id, pdf_id)
+            .await
+            .map(|_| ())
+            .map_err(Into::<Report<StoreError>>::into)
+    }
+}
@@ -1,4 +1,4 @@
-use crate::domain::Customer;
+use crate::domain::{CreditGrant, Customer};
 use crate::errors::StoreError;
 use crate::store::PgConn;
 use crate::StoreResult;
@@ -23,6 +23,38 @@ impl CustomerBalance {
         cents: i32,
         invoice_id: Option<Uuid>,
     ) -> StoreResult<CustomerBalanceUpdate> {
+        Self::apply(conn, customer_id, tenant_id, cents, invoice_id, None).await
+    }
+
+    /// Updates the balance with a ledger entry of the credit grant, on its creation, consumption or expiry.
+    pub async fn update_credit_grant(
+        conn: &mut PgConn,
+        grant: &CreditGrant,
+        cents: i32,
+        invoice_id: Option<Uuid>,
+        note: &str,
+    ) -> StoreResult<CustomerBalanceUpdate> {
+        Self::apply(
+            conn,
+            grant.customer_id,
+            grant.tenant_id,
+            cents,
+            invoice_id,
+            Some((grant.id, note.to_string())),
+        )
+        .await
+    }
+
+    async fn apply(
+        conn: &mut PgConn,
+        customer_id: Uuid,
+        tenant_id: Uuid,
+        cents: i32,
+        invoice_id: Option<Uuid>,
+        credit_grant: Option<(Uuid, String)>,
+    ) -> StoreResult<CustomerBalanceUpdate> {
+        let (credit_grant_id, note) = credit_grant.unzip();
+
         let _ = CustomerRow::select_for_update(conn, customer_id, tenant_id)
             .await
             .map_err(Into::<Report<StoreError>>::into)?;
@@ -44,11 +76,12 @@ impl CustomerBalance {
             id: Uuid::now_v7(),
             amount_cents: cents,
             balance_cents_after: customer_row_updated.balance_value_cents,
-            note: None,
+            note,
             invoice_id,
             tenant_id,
             customer_id,
             created_by: None,
+            credit_grant_id,
         }
         .insert(conn)
         .await
@@ -60,3 +93,12 @@ impl CustomerBalance {
         })
     }
 }
+
+/// Converts an amount in minor units to the i32 cents the customer balance is stored in.
+pub fn to_balance_cents(amount: i64) -> StoreResult<i32> {
+    i32::try_from(amount).map_err(|_| {
+        Report::new(StoreError::InvalidArgument(
+            "amount exceeds the customer balance capacity".to_string(),
+        ))
+    })
+}
@@ -1,18 +1,19 @@
 use diesel_async::scoped_futures::ScopedFutureExt;
 use error_stack::Report;
+use rust_decimal::Decimal;
 use uuid::Uuid;
 
 use crate::domain::enums::{InvoiceStatusEnum, InvoiceType, InvoicingProviderEnum};
 use crate::domain::{
-    Customer, CustomerBrief, CustomerBuyCredits, CustomerNew, CustomerNewWrapper, CustomerPatch,
-    CustomerTopUpBalance, DetailedInvoice, InlineCustomer, InlineInvoicingEntity, InvoiceNew,
-    InvoiceTotals, InvoiceTotalsParams, InvoicingEntity, LineItem, OrderByRequest, PaginatedVec,
-    PaginationRequest,
+    Address, Customer, CustomerBrief, CustomerBuyCredits, CustomerNew, CustomerNewWrapper,
+    CustomerPatch, CustomerTopUpBalance, DetailedInvoice, InlineCustomer, InlineInvoicingEntity,
+    InvoiceNew, InvoiceTotals, InvoiceTotalsParams, InvoicingEntity, LineItem, OrderByRequest,
+    PaginatedVec, PaginationRequest, ResolvedTax,
 };
 use crate::errors::StoreError;
 use crate::repositories::customer_balance::CustomerBalance;
 use crate::repositories::invoices::insert_invoice;
-use crate::repositories::invoicing_entities::InvoicingEntityInterface;
+use crate::repositories::invoicing_entities::{next_invoice_number, InvoicingEntityInterface};
 use crate::repositories::InvoiceInterface;
 use crate::store::Store;
 use crate::utils::local_id::{IdType, LocalId};
@@ -268,6 +269,8 @@ impl CustomersInterface for Store {
             billing_address: customer.billing_address,
             shipping_address: customer.shipping_address,
             invoicing_entity_id: customer.invoicing_entity_id,
+            vat_number: customer.vat_number,
+            tax_status: customer.tax_status.map(Into::into),
         };
 
         let updated = patch_model
@@ -328,14 +331,21 @@ impl CustomersInterface for Store {
                         price_component_id: None,
                         product_id: None,
                         metric_id: None,
+                        add_on_id: None,
                         description: None,
+                        tax_rate: Decimal::ZERO,
+                        tax_amount: 0,
+                        is_manual: false,
                     }];
 
+                    // credits are a prepayment: the tax is charged on the invoices they are applied to
+                    let tax = ResolvedTax::out_of_scope();
+
                     let totals = InvoiceTotals::from_params(InvoiceTotalsParams {
                         line_items: &line_items,
                         total: 0,
                         amount_due: 0,
-                        tax_rate: 0,
+                        tax: &tax,
                         customer_balance_cents: 0,
                         subscription_applied_coupons: &vec![],
                         invoice_currency: customer.currency.as_str(),
@@ -373,13 +383,15 @@ impl CustomersInterface for Store {
                         due_at,
                         plan_name: None,
                         external_invoice_id: None, // todo check later if we want it sync (instead of issue_worker)
-                        invoice_number: self.internal.format_invoice_number(
-                            invoicing_entity.next_invoice_number,
-                            invoicing_entity.invoice_number_pattern,
+                        invoice_number: next_invoice_number(
+                            conn,
+                            invoicing_entity.id,
+                            req.tenant_id,
                             now.date(),
-                        ),
+                        )
+                        .await?,
                         invoicing_provider: InvoicingProviderEnum::Stripe, // todo get from the customer billing config
-                        line_items,
+                        line_items: totals.line_items,
                         issued: false,
                         issue_attempts: 0,
                         last_issue_attempt_at: None,
@@ -396,16 +408,21 @@ impl CustomersInterface for Store {
                         finalized_at: Some(now),
                         subtotal: totals.subtotal,
                         subtotal_recurring: totals.subtotal_recurring,
-                        tax_rate: 0, // TODO
+                        tax_rate: tax.rate,
                         tax_amount: totals.tax_amount,
+                        tax_breakdown: totals.tax_breakdown,
+                        coupons: totals.coupons,
                         local_id: LocalId::generate_for(IdType::Invoice),
                         customer_details: InlineCustomer {
-                            billing_address: None, // TODO
+                            billing_address: customer
+                                .billing_address
+                                .map(Address::try_from)
+                                .transpose()?,
                             id: req.customer_id,
                             name: customer.name,
                             alias: customer.alias,
                             email: customer.email,
-                            vat_number: None, // TODO
+                            vat_number: customer.vat_number,
                             snapshot_at: now,
                         },
                         seller_details: InlineInvoicingEntity {
@@ -415,19 +432,11 @@ impl CustomersInterface for Store {
                             vat_number: invoicing_entity.vat_number.clone(),
                             snapshot_at: now,
                         },
+                        replaces_invoice_id: None,
                     };
 
                     let inserted_invoice = insert_invoice(conn, invoice_new).await?;
 
-                    InvoicingEntityRow::update_invoicing_entity_number(
-                        conn,
-                        &invoicing_entity.id,
-                        &req.tenant_id,
-                        invoicing_entity.next_invoice_number,
-                    )
-                    .await
-                    .map_err(Into::<Report<StoreError>>::into)?;
-
                     let tx = CustomerBalancePendingTxRowNew {
                         id: Uuid::now_v7(),
                         amount_cents: req.cents,
@@ -0,0 +1,395 @@
+use crate::domain::enums::{
+    DunningSubscriptionActionEnum, InvoiceDunningStatusEnum, InvoiceExternalStatusEnum,
+    InvoiceStatusEnum, SubscriptionEventType,
+};
+use crate::domain::{
+    CursorPaginatedVec, CursorPaginationRequest, DetailedInvoice, DunningConfig, DunningConfigNew,
+    DunningStep, Invoice, InvoiceDunning, InvoiceDunningNew, InvoiceDunningOutcome, Subscription,
+};
+use crate::errors::StoreError;
+use crate::repositories::invoices::log_mrr_movements;
+use crate::repositories::invoicing_entities::InvoicingEntityInterface;
+use crate::store::{PgConn, Store};
+use crate::StoreResult;
+use common_eventbus::Event;
+use diesel_async::scoped_futures::ScopedFutureExt;
+use diesel_models::dunning::{
+    DunningConfigRow, DunningConfigRowNew, InvoiceDunningRow, InvoiceDunningRowNew,
+};
+use diesel_models::invoices::InvoiceRow;
+use diesel_models::subscription_events::SubscriptionEventRow;
+use diesel_models::subscriptions::{CancelSubscriptionParams, SubscriptionRow};
+use error_stack::Report;
+use uuid::Uuid;
+
+#[async_trait::async_trait]
+pub trait DunningInterface {
+    async fn get_dunning_config(
+        &self,
+        tenant_id: Uuid,
+        invoicing_entity_id: Uuid,
+    ) -> StoreResult<Option<DunningConfig>>;
+
+    async fn upsert_dunning_config(&self, config: DunningConfigNew) -> StoreResult<DunningConfig>;
+
+    async fn list_invoices_to_dun(
+        &self,
+        pagination: CursorPaginationRequest,
+    ) -> StoreResult<CursorPaginatedVec<Invoice>>;
+
+    /// Starts the dunning of an invoice, returns None if it was already started.
+    async fn start_invoice_dunning(
+        &self,
+        tenant_id: Uuid,
+        invoice_id: Uuid,
+    ) -> StoreResult<Option<InvoiceDunning>>;
+
+    async fn list_due_invoice_dunnings(
+        &self,
+        pagination: CursorPaginationRequest,
+    ) -> StoreResult<CursorPaginatedVec<InvoiceDunning>>;
+
+    /// Sends the next reminder, or applies the end actions once the retry schedule is exhausted.
+    /// Returns None if the dunning was already ended.
+    async fn process_invoice_dunning(
+        &self,
+        tenant_id: Uuid,
+        invoice_dunning_id: Uuid,
+    ) -> StoreResult<Option<InvoiceDunningOutcome>>;
+}
+
+#[async_trait::async_trait]
+impl DunningInterface for Store {
+    async fn get_dunning_config(
+        &self,
+        tenant_id: Uuid,
+        invoicing_entity_id: Uuid,
+    ) -> StoreResult<Option<DunningConfig>> {
+        let mut conn = self.get_conn().await?;
+
+        DunningConfigRow::find_by_invoicing_entity_id(&mut conn, tenant_id, invoicing_entity_id)
+            .await
+            .map_err(Into::<Report<StoreError>>::into)?
+            .map(TryInto::try_into)
+            .transpose()
+    }
+
+    async fn upsert_dunning_config(&self, config: DunningConfigNew) -> StoreResult<DunningConfig> {
+        config.validate()?;
+
+        // ensures the invoicing entity belongs to the tenant
+        self.get_invoicing_entity(config.tenant_id, Some(config.invoicing_entity_id))
+            .await?;
+
+        let mut conn = self.get_conn().await?;
+
+        let row: DunningConfigRowNew = config.try_into()?;
+
+        row.upsert(&mut conn)
+            .await
+            .map_err(Into::<Report<StoreError>>::into)?
+            .try_into()
+    }
+
+    async fn list_invoices_to_dun(
+        &self,
+        pagination: CursorPaginationRequest,
+    ) -> StoreResult<CursorPaginatedVec<Invoice>> {
+        let mut conn = self.get_conn().await?;
+
+        let rows = InvoiceDunningRow::list_invoices_to_dun(
+            &mut conn,
+            chrono::Utc::now().naive_utc(),
+            pagination.into(),
+        )
+        .await
+        .map_err(Into::<Report<StoreError>>::into)?;
+
+        Ok(CursorPaginatedVec {
+            items: rows
+                .items
+                .into_iter()
+                .map(TryInto::try_into)
+                .collect::<Result<Vec<_>, _>>()?,
+            next_cursor: rows.next_cursor,
+        })
+    }
+
+    async fn start_invoice_dunning(
+        &self,
+        tenant_id: Uuid,
+        invoice_id: Uuid,
+    ) -> StoreResult<Option<InvoiceDunning>> {
+        let mut conn = self.get_conn().await?;
+
+        let (invoice, config) =
+            find_invoice_with_dunning_config(&mut conn, tenant_id, invoice_id).await?;
+
+        let config = match config {
+            Some(config) if config.enabled => config,
+            _ => return Ok(None),
+        };
+
+        let started_at = chrono::Utc::now().naive_utc();
+
+        let row: InvoiceDunningRowNew = InvoiceDunningNew {
+            tenant_id: invoice.tenant_id,
+            invoice_id: invoice.id,
+            started_at,
+            next_attempt_at: config.attempt_at(started_at, 0),
+        }
+        .into();
+
+        row.insert(&mut conn)
+            .await
+            .map(|inserted| inserted.map(Into::into))
+            .map_err(Into::<Report<StoreError>>::into)
+    }
+
+    async fn list_due_invoice_dunnings(
+        &self,
+        pagination: CursorPaginationRequest,
+    ) -> StoreResult<CursorPaginatedVec<InvoiceDunning>> {
+        let mut conn = self.get_conn().await?;
+
+        let rows = InvoiceDunningRow::list_due(
+            &mut conn,
+            chrono::Utc::now().naive_utc(),
+            pagination.into(),
+        )
+        .await
+        .map_err(Into::<Report<StoreError>>::into)?;
+
+        Ok(CursorPaginatedVec {
+            items: rows.items.into_iter().map(Into::into).collect(),
+            next_cursor: rows.next_cursor,
+        })
+    }
+
+    async fn process_invoice_dunning(
+        &self,
+        tenant_id: Uuid,
+        invoice_dunning_id: Uuid,
+    ) -> StoreResult<Option<InvoiceDunningOutcome>> {
+        let outcome = self
+            .transaction(|conn| {
+                async move {
+                    let dunning: InvoiceDunning =
+                        InvoiceDunningRow::select_for_update_by_id(conn, invoice_dunning_id)
+                            .await
+                            .map_err(Into::<Report<StoreError>>::into)?
+                            .into();
+
+                    if dunning.tenant_id != tenant_id
+                        || dunning.status != InvoiceDunningStatusEnum::Active
+                    {
+                        return Ok(None);
+                    }
+
+                    let (invoice, config) =
+                        find_invoice_with_dunning_config(conn, tenant_id, dunning.invoice_id)
+                            .await?;
+
+                    let outcome = match (invoice_dunning_status(&invoice), config) {
+                        (Some(status), _) => end_dunning(conn, &dunning, status, None).await?,
+                        (None, Some(config)) if config.enabled => {
+                            match config.step(dunning.attempts) {
+                                DunningStep::Reminder(attempt) => {
+                                    InvoiceDunningRow::record_attempt(
+                                        conn,
+                                        dunning.id,
+                                        attempt as i32,
+                                        config.attempt_at(dunning.started_at, attempt),
+                                    )
+                                    .await
+                                    .map_err(Into::<Report<StoreError>>::into)?;
+
+                                    InvoiceDunningOutcome::Reminded {
+                                        invoice_id: invoice.id,
+                                        attempt,
+                                    }
+                                }
+                                DunningStep::End => {
+                                    apply_end_actions(conn, &invoice, &config).await?;
+
+                                    end_dunning(
+                                        conn,
+                                        &dunning,
+                                        InvoiceDunningStatusEnum::Exhausted,
+                                        Some(config.subscription_action),
+                                    )
+                                    .await?
+                                }
+                            }
+                        }
+                        // the dunning was disabled in the meantime
+                        (None, _) => {
+                            end_dunning(conn, &dunning, InvoiceDunningStatusEnum::Aborted, None)
+                                .await?
+                        }
+                    };
+
+                    Ok(Some(outcome))
+                }
+                .scope_boxed()
+            })
+            .await?;
+
+        if let Some(InvoiceDunningOutcome::Reminded {
+            invoice_id,
+            attempt,
+        }) = &outcome
+        {
+            let _ = self
+                .eventbus
+                .publish(Event::invoice_payment_reminder(
+                    *invoice_id,
+                    tenant_id,
+                    *attempt,
+                ))
+                .await;
+        }
+
+        Ok(outcome)
+    }
+}
+
+// the dunning config is the one of the invoicing entity of the customer
+async fn find_invoice_with_dunning_config(
+    conn: &mut PgConn,
+    tenant_id: Uuid,
+    invoice_id: Uuid,
+) -> StoreResult<(Invoice, Option<DunningConfig>)> {
+    let DetailedInvoice {
+        invoice, customer, ..
+    } = InvoiceRow::find_by_id(conn, tenant_id, invoice_id)
+        .await
+        .map_err(Into::<Report<StoreError>>::into)?
+        .try_into()?;
+
+    let config = DunningConfigRow::find_by_invoicing_entity_id(
+        conn,
+        tenant_id,
+        customer.invoicing_entity_id,
+    )
+    .await
+    .map_err(Into::<Report<StoreError>>::into)?
+    .map(TryInto::try_into)
+    .transpose()?;
+
+    Ok((invoice, config))
+}
+
+// the terminal status of the dunning if the invoice does not need to be collected anymore
+fn invoice_dunning_status(invoice: &Invoice) -> Option<InvoiceDunningStatusEnum> {
+    if invoice.status == InvoiceStatusEnum::Void {
+        return Some(InvoiceDunningStatusEnum::Aborted);
+    }
+
+    match invoice.external_status {
+        Some(InvoiceExternalStatusEnum::Paid) => Some(InvoiceDunningStatusEnum::Recovered),
+        Some(InvoiceExternalStatusEnum::Void)
+        | Some(InvoiceExternalStatusEnum::Uncollectible)
+        | Some(InvoiceExternalStatusEnum::Deleted) => Some(InvoiceDunningStatusEnum::Aborted),
+        _ => None,
+    }
+}
+
+async fn end_dunning(
+    conn: &mut PgConn,
+    dunning: &InvoiceDunning,
+    status: InvoiceDunningStatusEnum,
+    subscription_action: Option<DunningSubscriptionActionEnum>,
+) -> StoreResult<InvoiceDunningOutcome> {
+    InvoiceDunningRow::end(conn, dunning.id, status.clone().into())
+        .await
+        .map_err(Into::<Report<StoreError>>::into)?;
+
+    Ok(InvoiceDunningOutcome::Ended {
+        invoice_id: dunning.invoice_id,
+        status,
+        subscription_action,
+    })
+}
+
+async fn apply_end_actions(
+    conn: &mut PgConn,
+    invoice: &Invoice,
+    config: &DunningConfig,
+) -> StoreResult<()> {
+    if config.mark_uncollectible {
+        InvoiceRow::update_external_status(
+            conn,
+            invoice.id,
+            invoice.tenant_id,
+            InvoiceExternalStatusEnum::Uncollectible.into(),
+        )
+        .await
+        .map_err(Into::<Report<StoreError>>::into)?;
+    }
+
+    let subscription_id = match (invoice.subscription_id, config.subscription_action) {
+        (Some(subscription_id), DunningSubscriptionActionEnum::Pause)
+        | (Some(subscription_id), DunningSubscriptionActionEnum::Cancel) => subscription_id,
+        _ => return Ok(()),
+    };
+
+    SubscriptionRow::lock_subscription_for_update(conn, subscription_id)
+        .await
+        .map_err(Into::<Report<StoreError>>::into)?;
+
+    let subscription: Subscription =
+        SubscriptionRow::get_subscription_by_id(conn, &invoice.tenant_id, &subscription_id)
+            .await
+            .map_err(Into::<Report<StoreError>>::into)?
+            .into();
+
+    if subscription.canceled_at.is_some() || subscription.paused_at.is_some() {
+        return Ok(());
+    }
+
+    let now = chrono::Utc::now().naive_utc();
+
+    let event_type = match config.subscription_action {
+        DunningSubscriptionActionEnum::Pause => {
+            SubscriptionRow::pause_subscription(conn, subscription_id, invoice.tenant_id, None)
+                .await
+                .map_err(Into::<Report<StoreError>>::into)?;
+
+            SubscriptionEventType::Paused
+        }
+        _ => {
+            SubscriptionRow::cancel_subscription(
+                conn,
+                CancelSubscriptionParams {
+                    subscription_id,
+                    tenant_id: invoice.tenant_id,
+                    billing_end_date: now.date(),
+                    canceled_at: now,
+                    reason: Some("Unpaid invoice".to_string()),
+                },
+            )
+            .await
+            .map_err(Into::<Report<StoreError>>::into)?;
+
+            SubscriptionEventType::Cancelled
+        }
+    };
+
+    let event = SubscriptionEventRow {
+        id: Uuid::now_v7(),
+        subscription_id,
+        event_type: event_type.into(),
+        details: None,
+        created_at: now,
+        mrr_delta: Some(-(subscription.mrr_cents as i64)),
+        bi_mrr_movement_log_id: None,
+        applies_to: now.date(),
+    }
+    .insert(conn)
+    .await
+    .map_err(Into::<Report<StoreError>>::into)?;
+
+    // no further invoice is issued, so the churn is logged against the unpaid one
+    log_mrr_movements(conn, invoice, vec![event]).await
+}
@@ -2,25 +2,40 @@ use crate::domain::enums::{InvoiceExternalStatusEnum, InvoiceType};
 use crate::errors::StoreError;
 use crate::store::Store;
 use crate::{domain, StoreResult};
-use chrono::NaiveDateTime;
+use chrono::{NaiveDate, NaiveDateTime, NaiveTime};
 use diesel_async::scoped_futures::ScopedFutureExt;
 use diesel_models::enums::{MrrMovementType, SubscriptionEventType};
 use diesel_models::{DbResult, PgConn};
 use error_stack::Report;
+use std::future::Future;
 
 use crate::compute::InvoiceLineInterface;
+use crate::domain::enums::{InvoiceStatusEnum, InvoicingProviderEnum};
 use crate::domain::{
-    CursorPaginatedVec, CursorPaginationRequest, DetailedInvoice, Invoice, InvoiceLinesPatch,
-    InvoiceNew, InvoiceWithCustomer, OrderByRequest, OutboxEvent, PaginatedVec, PaginationRequest,
+    usage_deduction_lines, BillingConfig, CouponLineItem, CursorPaginatedVec,
+    CursorPaginationRequest, DetailedInvoice, InlineCustomer, InlineInvoicingEntity, Invoice,
+    InvoiceLineEdit, InvoiceLinesPatch, InvoiceNew, InvoiceTotals, InvoiceTotalsParams,
+    InvoiceWithCustomer, LineItem, OneOffInvoiceNew, OrderByRequest, OutboxEvent, PaginatedVec,
+    PaginationRequest, VoidedInvoice,
 };
-use crate::repositories::customer_balance::CustomerBalance;
-use crate::repositories::SubscriptionInterface;
-use crate::utils::decimals::ToUnit;
+use crate::repositories::credit_grants::{
+    consume_credit_grants, list_available_credit_grants, restore_credit_grants,
+};
+use crate::repositories::customer_balance::{to_balance_cents, CustomerBalance};
+use crate::repositories::invoicing_entities::{next_invoice_number, InvoicingEntityInterface};
+use crate::repositories::taxes::TaxRateInterface;
+use crate::repositories::usage_thresholds::{invoiced_lines, list_threshold_invoices};
+use crate::repositories::{CustomersInterface, SubscriptionInterface};
+use crate::utils::local_id::{IdType, LocalId};
+use common_domain::money::Currency;
 use common_eventbus::Event;
 use diesel_models::applied_coupons::{AppliedCouponDetailedRow, AppliedCouponRow};
+use diesel_models::bi::BiMrrMovementLogRow;
+use diesel_models::credit_notes::CreditNoteRow;
 use diesel_models::customer_balance_txs::CustomerBalancePendingTxRow;
+use diesel_models::errors::DatabaseError;
 use diesel_models::invoices::{InvoiceRow, InvoiceRowLinesPatch, InvoiceRowNew};
-use diesel_models::invoicing_entities::InvoicingEntityRow;
+use diesel_models::subscription_events::SubscriptionEventRow;
 use diesel_models::subscriptions::SubscriptionRow;
 use tracing_log::log;
 use uuid::Uuid;
@@ -85,9 +100,46 @@ pub trait InvoiceInterface {
 
     async fn update_pending_finalization_invoices(&self, now: NaiveDateTime) -> StoreResult<()>;
 
+    async fn mark_draft_invoices_outdated(
+        &self,
+        tenant_id: Uuid,
+        customer_ids: Vec<Uuid>,
+        since: NaiveDate,
+    ) -> StoreResult<usize>;
+
     async fn refresh_invoice_data(&self, id: Uuid, tenant_id: Uuid)
         -> StoreResult<DetailedInvoice>;
 
+    /// Creates a draft invoice of the customer with the given lines, not tied to a subscription.
+    async fn insert_one_off_invoice(
+        &self,
+        invoice: OneOffInvoiceNew,
+    ) -> StoreResult<DetailedInvoice>;
+
+    /// Adds, edits or removes a line of a draft invoice, and refreshes its totals.
+    async fn edit_invoice_line(
+        &self,
+        id: Uuid,
+        tenant_id: Uuid,
+        edit: InvoiceLineEdit,
+    ) -> StoreResult<DetailedInvoice>;
+
+    /// Voids a finalized invoice, reversing its MRR movements and giving back the credits applied to it.
+    /// With `reissue`, a draft replacing it is created with the same lines, to be corrected and finalized.
+    ///
+    /// `cancel_at_provider` runs last, before the commit, so that a failure at the provider rolls the void back.
+    async fn void_invoice<F, Fut>(
+        &self,
+        id: Uuid,
+        tenant_id: Uuid,
+        actor: Uuid,
+        reissue: bool,
+        cancel_at_provider: F,
+    ) -> StoreResult<VoidedInvoice>
+    where
+        F: FnOnce(Invoice) -> Fut + Send,
+        Fut: Future<Output = StoreResult<()>> + Send;
+
     async fn save_invoice_documents(
         &self,
         id: Uuid,
@@ -245,39 +297,40 @@ impl InvoiceInterface for Store {
 
     async fn finalize_invoice(&self, id: Uuid, tenant_id: Uuid) -> StoreResult<()> {
         let patch = compute_invoice_patch(self, id, tenant_id).await?;
-        let applied_coupons_amounts = patch.applied_coupons.clone();
+        let applied_coupons = patch.coupons.clone();
         let row_patch = patch.try_into()?;
 
         self.transaction(|conn| {
             async move {
                 let refreshed = refresh_invoice_data(conn, id, tenant_id, &row_patch).await?;
                 if refreshed.invoice.applied_credits > 0 {
-                    CustomerBalance::update(
-                        conn,
-                        refreshed.customer.id,
-                        tenant_id,
-                        -refreshed.invoice.applied_credits as i32,
-                        Some(refreshed.invoice.id),
-                    )
-                    .await?;
+                    let granted = consume_credit_grants(conn, &refreshed).await?;
+                    let from_balance = refreshed.invoice.applied_credits - granted;
+
+                    if from_balance > 0 {
+                        CustomerBalance::update(
+                            conn,
+                            refreshed.customer.id,
+                            tenant_id,
+                            -to_balance_cents(from_balance)?,
+                            Some(refreshed.invoice.id),
+                        )
+                        .await?;
+                    }
                 }
 
-                let invoicing_entity = InvoicingEntityRow::select_for_update_by_id_and_tenant(
+                // the invoices are numbered in the order of their finalization, so that the sequence
+                // stays chronological even when an invoice is dated in the past
+                let new_invoice_number = next_invoice_number(
                     conn,
-                    &refreshed.customer.invoicing_entity_id,
-                    &tenant_id,
+                    refreshed.invoice.seller_details.id,
+                    tenant_id,
+                    chrono::Utc::now().date_naive(),
                 )
-                .await
-                .map_err(Into::<Report<StoreError>>::into)?;
-
-                let new_invoice_number = self.internal.format_invoice_number(
-                    invoicing_entity.next_invoice_number,
-                    invoicing_entity.invoice_number_pattern,
-                    refreshed.invoice.invoice_date,
-                );
+                .await?;
 
                 let applied_coupons_ids =
-                    refresh_applied_coupons(conn, &refreshed, &applied_coupons_amounts).await?;
+                    refresh_applied_coupons(conn, &refreshed, &applied_coupons).await?;
 
                 let res = InvoiceRow::finalize(
                     conn,
@@ -289,14 +342,12 @@ impl InvoiceInterface for Store {
                 .await
                 .map_err(Into::<Report<StoreError>>::into)?;
 
-                InvoicingEntityRow::update_invoicing_entity_number(
-                    conn,
-                    &refreshed.customer.invoicing_entity_id,
-                    &tenant_id,
-                    invoicing_entity.next_invoice_number,
-                )
-                .await
-                .map_err(Into::<Report<StoreError>>::into)?;
+                // rolls back the allocated number, so that the sequence has no gap
+                if res == 0 {
+                    return Err(Report::new(StoreError::InvalidArgument(
+                        "the invoice is already finalized or void".to_string(),
+                    )));
+                }
 
                 self.internal
                     .insert_outbox_item(
@@ -414,6 +465,19 @@ impl InvoiceInterface for Store {
             .map_err(Into::<Report<StoreError>>::into)
     }
 
+    async fn mark_draft_invoices_outdated(
+        &self,
+        tenant_id: Uuid,
+        customer_ids: Vec<Uuid>,
+        since: NaiveDate,
+    ) -> StoreResult<usize> {
+        let mut conn = self.get_conn().await?;
+
+        InvoiceRow::mark_drafts_outdated(&mut conn, tenant_id, customer_ids, since)
+            .await
+            .map_err(Into::<Report<StoreError>>::into)
+    }
+
     async fn refresh_invoice_data(
         &self,
         id: Uuid,
@@ -426,6 +490,267 @@ impl InvoiceInterface for Store {
         refresh_invoice_data(&mut conn, id, tenant_id, &patch).await
     }
 
+    async fn insert_one_off_invoice(
+        &self,
+        invoice: OneOffInvoiceNew,
+    ) -> StoreResult<DetailedInvoice> {
+        let tenant_id = invoice.tenant_id;
+
+        let customer = self
+            .find_customer_by_id(invoice.customer_id, tenant_id)
+            .await?;
+
+        let invoicing_entity = self
+            .get_invoicing_entity(tenant_id, Some(customer.invoicing_entity_id))
+            .await?;
+
+        let line_items = invoice
+            .line_items
+            .iter()
+            .map(|l| l.to_line_item(LocalId::no_prefix(), &customer.currency))
+            .collect::<StoreResult<Vec<_>>>()?;
+
+        let tax = self.resolve_customer_tax(&customer).await?;
+
+        // the credits are applied on finalization, with the balance at that time
+        let totals = InvoiceTotals::from_params(InvoiceTotalsParams {
+            line_items: &line_items,
+            total: 0,
+            amount_due: 0,
+            tax: &tax,
+            customer_balance_cents: 0,
+            subscription_applied_coupons: &vec![],
+            invoice_currency: customer.currency.as_str(),
+        });
+
+        let invoicing_provider = match &customer.billing_config {
+            BillingConfig::Stripe(_) => InvoicingProviderEnum::Stripe,
+            BillingConfig::Manual => InvoicingProviderEnum::Manual,
+        };
+
+        let now = chrono::Utc::now().naive_utc();
+
+        let invoice_new = InvoiceNew {
+            status: InvoiceStatusEnum::Draft,
+            external_status: None,
+            tenant_id,
+            customer_id: customer.id,
+            subscription_id: None,
+            currency: customer.currency.clone(),
+            external_invoice_id: None,
+            invoice_number: "draft".to_string(),
+            invoicing_provider,
+            line_items: totals.line_items,
+            issued: false,
+            issue_attempts: 0,
+            last_issue_attempt_at: None,
+            last_issue_error: None,
+            data_updated_at: None,
+            invoice_date: invoice.invoice_date,
+            plan_version_id: None,
+            invoice_type: InvoiceType::OneOff,
+            finalized_at: None,
+            subtotal: totals.subtotal,
+            subtotal_recurring: totals.subtotal_recurring,
+            tax_rate: tax.rate,
+            tax_amount: totals.tax_amount,
+            tax_breakdown: totals.tax_breakdown,
+            coupons: totals.coupons,
+            total: totals.total,
+            amount_due: totals.amount_due,
+            net_terms: invoicing_entity.net_terms,
+            reference: invoice.reference,
+            memo: invoice.memo,
+            local_id: LocalId::generate_for(IdType::Invoice),
+            due_at: Some(
+                (invoice.invoice_date + chrono::Duration::days(invoicing_entity.net_terms as i64))
+                    .and_time(NaiveTime::MIN),
+            ),
+            plan_name: None,
+            customer_details: InlineCustomer {
+                id: customer.id,
+                name: customer.name.clone(),
+                email: customer.email.clone(),
+                alias: customer.alias.clone(),
+                vat_number: customer.vat_number.clone(),
+                billing_address: customer.billing_address.clone(),
+                snapshot_at: now,
+            },
+            seller_details: InlineInvoicingEntity {
+                id: invoicing_entity.id,
+                legal_name: invoicing_entity.legal_name.clone(),
+                vat_number: invoicing_entity.vat_number.clone(),
+                address: invoicing_entity.address(),
+                snapshot_at: now,
+            },
+            replaces_invoice_id: None,
+        };
+
+        let inserted = self.insert_invoice(invoice_new).await?;
+
+        let _ = self
+            .eventbus
+            .publish(Event::invoice_created(inserted.id, tenant_id))
+            .await;
+
+        self.find_invoice_by_id(tenant_id, inserted.id).await
+    }
+
+    async fn edit_invoice_line(
+        &self,
+        id: Uuid,
+        tenant_id: Uuid,
+        edit: InvoiceLineEdit,
+    ) -> StoreResult<DetailedInvoice> {
+        let mut invoice = self.find_invoice_by_id(tenant_id, id).await?;
+        let previous_lines = invoice.invoice.line_items.clone();
+
+        invoice.invoice.edit_line(edit)?;
+
+        let patch: InvoiceRowLinesPatch = compute_lines_patch(self, &invoice).await?.try_into()?;
+
+        self.transaction(|conn| {
+            async move {
+                let current: Invoice = InvoiceRow::select_for_update_by_id(conn, tenant_id, id)
+                    .await
+                    .map_err(Into::<Report<StoreError>>::into)
+                    .and_then(TryInto::try_into)?;
+
+                // finalized or edited concurrently
+                if current.status != invoice.invoice.status || current.line_items != previous_lines
+                {
+                    return Err(Report::new(StoreError::InvalidArgument(
+                        "the invoice was modified concurrently, please retry".to_string(),
+                    )));
+                }
+
+                refresh_invoice_data(conn, id, tenant_id, &patch).await
+            }
+            .scope_boxed()
+        })
+        .await
+    }
+
+    async fn void_invoice<F, Fut>(
+        &self,
+        id: Uuid,
+        tenant_id: Uuid,
+        actor: Uuid,
+        reissue: bool,
+        cancel_at_provider: F,
+    ) -> StoreResult<VoidedInvoice>
+    where
+        F: FnOnce(Invoice) -> Fut + Send,
+        Fut: Future<Output = StoreResult<()>> + Send,
+    {
+        let replacement_id = self
+            .transaction(|conn| {
+                async move {
+                    let invoice: Invoice = InvoiceRow::select_for_update_by_id(conn, tenant_id, id)
+                        .await
+                        .map_err(Into::<Report<StoreError>>::into)
+                        .and_then(TryInto::try_into)?;
+
+                    if invoice.status != InvoiceStatusEnum::Finalized {
+                        return Err(Report::new(StoreError::InvalidArgument(
+                            "only finalized invoices can be voided".to_string(),
+                        )));
+                    }
+
+                    if invoice.external_status == Some(InvoiceExternalStatusEnum::Paid) {
+                        return Err(Report::new(StoreError::InvalidArgument(
+                            "paid invoices cannot be voided, a credit note should be issued instead"
+                                .to_string(),
+                        )));
+                    }
+
+                    let credit_notes =
+                        CreditNoteRow::list_active_by_invoice_id(conn, tenant_id, id)
+                            .await
+                            .map_err(Into::<Report<StoreError>>::into)?;
+
+                    if !credit_notes.is_empty() {
+                        return Err(Report::new(StoreError::InvalidArgument(
+                            "invoices with credit notes cannot be voided".to_string(),
+                        )));
+                    }
+
+                    let subscription_events = reverse_mrr_movements(conn, &invoice).await?;
+
+                    if invoice.applied_credits > 0 {
+                        let granted = restore_credit_grants(conn, &invoice).await?;
+                        let from_balance = invoice.applied_credits - granted;
+
+                        if from_balance > 0 {
+                            CustomerBalance::update(
+                                conn,
+                                invoice.customer_id,
+                                tenant_id,
+                                to_balance_cents(from_balance)?,
+                                Some(invoice.id),
+                            )
+                            .await?;
+                        }
+                    }
+
+                    InvoiceRow::void(conn, id, tenant_id)
+                        .await
+                        .map_err(Into::<Report<StoreError>>::into)?;
+
+                    let replacement_id = if reissue {
+                        let replacement_new: InvoiceRowNew = invoice
+                            .replacement_draft(chrono::Utc::now().date_naive())
+                            .try_into()?;
+
+                        let replacement: Invoice = replacement_new
+                            .insert(conn)
+                            .await
+                            .map_err(Into::<Report<StoreError>>::into)
+                            .and_then(TryInto::try_into)?;
+
+                        // the movements of the voided invoice now belong to its replacement
+                        if !subscription_events.is_empty() {
+                            log_mrr_movements(conn, &replacement, subscription_events).await?;
+                        }
+
+                        Some(replacement.id)
+                    } else {
+                        None
+                    };
+
+                    // last, as it cannot be rolled back. Cancelling at the provider is idempotent,
+                    // so a void failing to commit afterward can be retried
+                    cancel_at_provider(invoice).await?;
+
+                    Ok(replacement_id)
+                }
+                .scope_boxed()
+            })
+            .await?;
+
+        let _ = self
+            .eventbus
+            .publish(Event::invoice_voided(actor, id, tenant_id, replacement_id))
+            .await;
+
+        let replacement = match replacement_id {
+            Some(replacement_id) => {
+                let _ = self
+                    .eventbus
+                    .publish(Event::invoice_created(replacement_id, tenant_id))
+                    .await;
+
+                Some(self.find_invoice_by_id(tenant_id, replacement_id).await?)
+            }
+            None => None,
+        };
+
+        Ok(VoidedInvoice {
+            invoice: self.find_invoice_by_id(tenant_id, id).await?,
+            replacement,
+        })
+    }
+
     async fn save_invoice_documents(
         &self,
         id: Uuid,
@@ -458,82 +783,182 @@ async fn process_mrr(inserted: &domain::Invoice, conn: &mut PgConn) -> StoreResu
             .subscription_id
             .ok_or(StoreError::ValueNotFound("subscription_id is null".into()))?;
 
-        let subscription_events = diesel_models::subscription_events::SubscriptionEventRow::fetch_by_subscription_id_and_date(
+        let subscription_events = SubscriptionEventRow::fetch_by_subscription_id_and_date(
             conn,
             subscription_id,
             inserted.invoice_date,
         )
-            .await
-            .map_err(Into::<Report<StoreError>>::into)?;
+        .await
+        .map_err(Into::<Report<StoreError>>::into)?;
 
-        let mut mrr_logs = vec![];
-
-        for event in subscription_events {
-            let mrr_delta = match event.mrr_delta {
-                None | Some(0) => continue,
-                Some(c) => c,
-            };
-
-            let movement_type = match event.event_type {
-                // TODO
-                // SubscriptionEventType::Created => continue,
-                SubscriptionEventType::Created => MrrMovementType::NewBusiness, // TODO
-                SubscriptionEventType::Activated => MrrMovementType::NewBusiness,
-                SubscriptionEventType::Switch => {
-                    if mrr_delta > 0 {
-                        MrrMovementType::Expansion
-                    } else {
-                        MrrMovementType::Contraction
-                    }
+        log_mrr_movements(conn, inserted, subscription_events).await?;
+    }
+    Ok(())
+}
+
+/// Logs the MRR movements of the subscription events against the invoice, and updates the subscription MRR.
+/// Events already logged against another invoice are skipped.
+pub(crate) async fn log_mrr_movements(
+    conn: &mut PgConn,
+    invoice: &domain::Invoice,
+    subscription_events: Vec<SubscriptionEventRow>,
+) -> StoreResult<()> {
+    let subscription_id = invoice
+        .subscription_id
+        .ok_or(StoreError::ValueNotFound("subscription_id is null".into()))?;
+
+    let mut mrr_logs = vec![];
+
+    for event in subscription_events {
+        if event.bi_mrr_movement_log_id.is_some() {
+            continue;
+        }
+
+        let mrr_delta = match event.mrr_delta {
+            None | Some(0) => continue,
+            Some(c) => c,
+        };
+
+        let movement_type = match event.event_type {
+            // TODO
+            // SubscriptionEventType::Created => continue,
+            SubscriptionEventType::Created => MrrMovementType::NewBusiness, // TODO
+            SubscriptionEventType::Activated => MrrMovementType::NewBusiness,
+            SubscriptionEventType::Switch => {
+                if mrr_delta > 0 {
+                    MrrMovementType::Expansion
+                } else {
+                    MrrMovementType::Contraction
                 }
-                SubscriptionEventType::Cancelled => MrrMovementType::Churn,
-                SubscriptionEventType::Reactivated => MrrMovementType::Reactivation,
-                SubscriptionEventType::Updated => {
-                    if mrr_delta > 0 {
-                        MrrMovementType::Expansion
-                    } else {
-                        MrrMovementType::Contraction
-                    }
+            }
+            SubscriptionEventType::Cancelled => MrrMovementType::Churn,
+            SubscriptionEventType::Paused => MrrMovementType::Churn,
+            SubscriptionEventType::Resumed => MrrMovementType::Reactivation,
+            SubscriptionEventType::Reactivated => MrrMovementType::Reactivation,
+            SubscriptionEventType::Updated => {
+                if mrr_delta > 0 {
+                    MrrMovementType::Expansion
+                } else {
+                    MrrMovementType::Contraction
                 }
-            };
-
-            // TODO proper description from event_type + details
-            let description = match event.event_type {
-                SubscriptionEventType::Created => "Subscription created",
-                SubscriptionEventType::Activated => "Subscription activated",
-                SubscriptionEventType::Switch => "Switched plan",
-                SubscriptionEventType::Cancelled => "Subscription cancelled",
-                SubscriptionEventType::Reactivated => "Subscription reactivated",
-                SubscriptionEventType::Updated => "Subscription updated",
-            };
-
-            let new_log = diesel_models::bi::BiMrrMovementLogRowNew {
-                id: Uuid::now_v7(),
-                description: description.to_string(),
-                movement_type,
-                net_mrr_change: mrr_delta,
-                currency: inserted.currency.clone(),
-                applies_to: inserted.invoice_date,
-                invoice_id: inserted.id,
-                credit_note_id: None,
-                plan_version_id: inserted.plan_version_id.unwrap(), // TODO
-                tenant_id: inserted.tenant_id,
-            };
-
-            mrr_logs.push(new_log);
-        }
+            }
+        };
+
+        // TODO proper description from event_type + details
+        let description = match event.event_type {
+            SubscriptionEventType::Created => "Subscription created",
+            SubscriptionEventType::Activated => "Subscription activated",
+            SubscriptionEventType::Switch => "Switched plan",
+            SubscriptionEventType::Cancelled => "Subscription cancelled",
+            SubscriptionEventType::Reactivated => "Subscription reactivated",
+            SubscriptionEventType::Updated => "Subscription updated",
+            SubscriptionEventType::Paused => "Subscription paused",
+            SubscriptionEventType::Resumed => "Subscription resumed",
+        };
+
+        let new_log = diesel_models::bi::BiMrrMovementLogRowNew {
+            id: Uuid::now_v7(),
+            description: description.to_string(),
+            movement_type,
+            net_mrr_change: mrr_delta,
+            currency: invoice.currency.clone(),
+            applies_to: invoice.invoice_date,
+            invoice_id: invoice.id,
+            credit_note_id: None,
+            plan_version_id: invoice.plan_version_id.unwrap(), // TODO
+            tenant_id: invoice.tenant_id,
+        };
+
+        mrr_logs.push((event.id, new_log));
+    }
+
+    let mrr_delta_cents: i64 = mrr_logs.iter().map(|(_, l)| l.net_mrr_change).sum();
+
+    let links = mrr_logs
+        .iter()
+        .map(|(event_id, l)| (*event_id, l.id))
+        .collect::<Vec<_>>();
 
-        let mrr_delta_cents: i64 = mrr_logs.iter().map(|l| l.net_mrr_change).sum();
+    diesel_models::bi::BiMrrMovementLogRow::insert_movement_log_batch(
+        conn,
+        mrr_logs.into_iter().map(|(_, l)| l).collect(),
+    )
+    .await
+    .map_err(Into::<Report<StoreError>>::into)?;
 
-        diesel_models::bi::BiMrrMovementLogRow::insert_movement_log_batch(conn, mrr_logs)
+    for (event_id, movement_log_id) in links {
+        SubscriptionEventRow::link_mrr_movement_log(conn, event_id, movement_log_id)
             .await
             .map_err(Into::<Report<StoreError>>::into)?;
+    }
+
+    SubscriptionRow::update_subscription_mrr_delta(conn, subscription_id, mrr_delta_cents)
+        .await
+        .map_err(Into::<Report<StoreError>>::into)?;
+
+    Ok(())
+}
+
+/// Cancels the MRR movements logged against the voided invoice with opposite movements, and unlinks their
+/// subscription events so that they can be logged again. Returns the unlinked events.
+async fn reverse_mrr_movements(
+    conn: &mut PgConn,
+    invoice: &Invoice,
+) -> StoreResult<Vec<SubscriptionEventRow>> {
+    let movement_logs = BiMrrMovementLogRow::list_by_invoice_id(conn, invoice.id)
+        .await
+        .map_err(Into::<Report<StoreError>>::into)?;
+
+    if movement_logs.is_empty() {
+        return Ok(vec![]);
+    }
+
+    let movement_log_ids = movement_logs.iter().map(|l| l.id).collect::<Vec<_>>();
+
+    let subscription_events =
+        SubscriptionEventRow::list_by_mrr_movement_log_ids(conn, &movement_log_ids)
+            .await
+            .map_err(Into::<Report<StoreError>>::into)?;
+
+    SubscriptionEventRow::unlink_mrr_movement_logs(conn, &movement_log_ids)
+        .await
+        .map_err(Into::<Report<StoreError>>::into)?;
+
+    let mrr_delta_cents: i64 = movement_logs.iter().map(|l| l.net_mrr_change).sum();
+
+    let reversals = movement_logs
+        .into_iter()
+        .map(|l| diesel_models::bi::BiMrrMovementLogRowNew {
+            id: Uuid::now_v7(),
+            description: format!("{} (invoice voided)", l.description),
+            movement_type: l.movement_type,
+            net_mrr_change: -l.net_mrr_change,
+            currency: l.currency,
+            applies_to: l.applies_to,
+            invoice_id: l.invoice_id,
+            credit_note_id: None,
+            plan_version_id: l.plan_version_id,
+            tenant_id: l.tenant_id,
+        })
+        .collect();
+
+    BiMrrMovementLogRow::insert_movement_log_batch(conn, reversals)
+        .await
+        .map_err(Into::<Report<StoreError>>::into)?;
 
-        SubscriptionRow::update_subscription_mrr_delta(conn, subscription_id, mrr_delta_cents)
+    if let Some(subscription_id) = invoice.subscription_id {
+        SubscriptionRow::update_subscription_mrr_delta(conn, subscription_id, -mrr_delta_cents)
             .await
             .map_err(Into::<Report<StoreError>>::into)?;
     }
-    Ok(())
+
+    Ok(subscription_events
+        .into_iter()
+        .map(|e| SubscriptionEventRow {
+            bi_mrr_movement_log_id: None,
+            ..e
+        })
+        .collect())
 }
 
 async fn refresh_invoice_data(
@@ -561,28 +986,94 @@ async fn compute_invoice_patch(
 ) -> StoreResult<InvoiceLinesPatch> {
     let invoice = store.find_invoice_by_id(tenant_id, invoice_id).await?;
 
+    compute_lines_patch(store, &invoice).await
+}
+
+async fn compute_lines_patch(
+    store: &Store,
+    invoice: &DetailedInvoice,
+) -> StoreResult<InvoiceLinesPatch> {
+    let tenant_id = invoice.invoice.tenant_id;
+
+    let credit_grants = {
+        let mut conn = store.get_conn().await?;
+        list_available_credit_grants(&mut conn, tenant_id, invoice.customer.id).await?
+    };
+
     match invoice.invoice.subscription_id {
-        None => Err(StoreError::InvalidArgument(
-            "Cannot refresh invoice without subscription_id".into(),
-        )
-        .into()),
-        Some(subscription_id) => {
+        Some(subscription_id) if invoice.invoice.invoice_type == InvoiceType::Recurring => {
             let subscription_details = store
                 .get_subscription_details(tenant_id, subscription_id)
                 .await?;
-            let lines = store
+            // the usage already billed by threshold invoices is deducted
+            let mut lines = store
                 .compute_dated_invoice_lines(&invoice.invoice.invoice_date, &subscription_details)
                 .await?;
 
+            // the lines added by hand are kept
+            lines.extend(
+                invoice
+                    .invoice
+                    .line_items
+                    .iter()
+                    .filter(|l| l.is_manual)
+                    .cloned(),
+            );
+
+            let tax = store.resolve_customer_tax(&invoice.customer).await?;
+
             Ok(InvoiceLinesPatch::new(
-                &invoice,
+                invoice,
                 lines,
-                &subscription_details.applied_coupons,
+                &subscription_details.invoice_coupons(invoice.invoice.invoice_date),
+                &tax,
+                &credit_grants,
+            ))
+        }
+        // one-off and adjustment invoices are not computed from a subscription, their lines are kept as is
+        _ => {
+            let tax = store.resolve_customer_tax(&invoice.customer).await?;
+
+            Ok(InvoiceLinesPatch::new(
+                invoice,
+                invoice.invoice.line_items.clone(),
+                &[],
+                &tax,
+                &credit_grants,
             ))
         }
     }
 }
 
+// the usage already billed by usage-threshold invoices, in the periods of the recurring invoice
+pub(crate) async fn usage_threshold_deductions(
+    store: &Store,
+    subscription_id: Uuid,
+    invoice_date: NaiveDate,
+    lines: &[LineItem],
+) -> StoreResult<Vec<LineItem>> {
+    let period_start = lines
+        .iter()
+        .filter(|l| l.metric_id.is_some())
+        .map(|l| l.start_date)
+        .min();
+
+    let period_start = match period_start {
+        Some(period_start) => period_start,
+        None => return Ok(vec![]),
+    };
+
+    let mut conn = store.get_conn().await?;
+
+    let threshold_invoices =
+        list_threshold_invoices(&mut conn, subscription_id, period_start, invoice_date).await?;
+
+    Ok(usage_deduction_lines(
+        lines,
+        &invoiced_lines(&threshold_invoices),
+    ))
+}
+
 pub async fn insert_invoice(conn: &mut PgConn, invoice: InvoiceNew) -> StoreResult<Invoice> {
     let insertable_invoice: InvoiceRowNew = invoice.try_into()?;
 
@@ -624,9 +1115,12 @@ async fn process_pending_tx(conn: &mut PgConn, invoice_id: Uuid) -> StoreResult<
 async fn refresh_applied_coupons(
     tx_conn: &mut PgConn,
     invoice: &DetailedInvoice,
-    applied_coupons_amounts: &[(Uuid, i64)],
+    applied_coupons: &[CouponLineItem],
 ) -> DbResult<Vec<Uuid>> {
-    let applied_coupons_ids: Vec<Uuid> = applied_coupons_amounts.iter().map(|(k, _)| *k).collect();
+    let applied_coupons_ids: Vec<Uuid> = applied_coupons
+        .iter()
+        .map(|x| x.applied_coupon_id)
+        .collect();
 
     let applied_coupons_detailed =
         AppliedCouponDetailedRow::list_by_ids_for_update(tx_conn, &applied_coupons_ids).await?;
@@ -637,12 +1131,17 @@ async fn refresh_applied_coupons(
             .recurring_value
             .is_some_and(|x| x == 1)
         {
-            let cur = rusty_money::iso::find(&invoice.invoice.currency).unwrap();
-
-            applied_coupons_amounts
+            let cur = Currency::from_code(&invoice.invoice.currency).ok_or(Report::new(
+                DatabaseError::ValidationError(format!(
+                    "unknown currency {}",
+                    invoice.invoice.currency
+                )),
+            ))?;
+
+            applied_coupons
                 .iter()
-                .find(|x| x.0 == applied_coupon_detailed.applied_coupon.id)
-                .map(|x| x.1.to_unit(cur.exponent as u8))
+                .find(|x| x.applied_coupon_id == applied_coupon_detailed.applied_coupon.id)
+                .map(|x| cur.to_major(x.value))
         } else {
             None
         };
@@ -2,6 +2,8 @@ use chrono::{Datelike, NaiveDate};
 use error_stack::Report;
 use uuid::Uuid;
 
+use crate::domain::invoicing_entities::NumberPattern;
+
 use diesel_models::invoicing_entities::{InvoicingEntityRow, InvoicingEntityRowPatch};
 use diesel_models::organizations::OrganizationRow;
 
@@ -128,6 +130,16 @@ impl InvoicingEntityInterface for Store {
     ) -> StoreResult<InvoicingEntity> {
         let mut conn = self.get_conn().await?;
 
+        for pattern in [
+            &invoicing_entity.invoice_number_pattern,
+            &invoicing_entity.credit_note_number_pattern,
+        ]
+        .into_iter()
+        .flatten()
+        {
+            pattern.parse::<NumberPattern>()?;
+        }
+
         let mut row: InvoicingEntityRowPatch = invoicing_entity.into();
 
         if row.country.is_some() {
@@ -174,16 +186,27 @@ impl StoreInternal {
 
         let currency = self.get_currency_from_country(&country)?;
 
+        let invoice_number_pattern = invoicing_entity
+            .invoice_number_pattern
+            .unwrap_or("INV-{number}".to_string());
+        let credit_note_number_pattern = invoicing_entity
+            .credit_note_number_pattern
+            .unwrap_or("CN-{number}".to_string());
+
+        invoice_number_pattern.parse::<NumberPattern>()?;
+        credit_note_number_pattern.parse::<NumberPattern>()?;
+
         let entity = InvoicingEntity {
             id: Uuid::new_v4(),
             local_id: LocalId::generate_for(IdType::InvoicingEntity),
             is_default: !other_exists,
             legal_name: invoicing_entity.legal_name.unwrap_or(trade_name),
-            invoice_number_pattern: invoicing_entity
-                .invoice_number_pattern
-                .unwrap_or("INV-{number}".to_string()),
+            invoice_number_pattern,
+            credit_note_number_pattern,
             next_invoice_number: 1,
             next_credit_note_number: 1,
+            invoice_number_year: None,
+            credit_note_number_year: None,
             grace_period_hours: invoicing_entity.grace_period_hours.unwrap_or(24),
             net_terms: invoicing_entity.net_terms.unwrap_or(30),
             invoice_footer_info: invoicing_entity.invoice_footer_info.clone(),
@@ -210,12 +233,72 @@ impl StoreInternal {
 
         Ok(invoicing_entity_row.into())
     }
+}
 
-    pub fn format_invoice_number(&self, number: i64, format: String, date: NaiveDate) -> String {
-        format
-            .replace("{number}", &number.to_string())
-            .replace("{YYYY}", &date.year().to_string())
-            .replace("{MM}", &date.month().to_string())
-            .replace("{DD}", &date.day().to_string())
-    }
+/// Allocates the number of an invoice of the invoicing entity issued at `date`, and advances its sequence.
+/// The entity row stays locked until the end of the transaction, so that the numbers are gapless and unique
+/// per entity even under concurrent finalizations.
+pub(crate) async fn next_invoice_number(
+    conn: &mut PgConn,
+    invoicing_entity_id: Uuid,
+    tenant_id: Uuid,
+    date: NaiveDate,
+) -> StoreResult<String> {
+    let entity = InvoicingEntityRow::select_for_update_by_id_and_tenant(
+        conn,
+        &invoicing_entity_id,
+        &tenant_id,
+    )
+    .await
+    .map_err(Into::<Report<StoreError>>::into)?;
+
+    let pattern: NumberPattern = entity.invoice_number_pattern.parse()?;
+    let counter = pattern.counter(entity.next_invoice_number, entity.invoice_number_year, date);
+
+    InvoicingEntityRow::update_invoicing_entity_number(
+        conn,
+        &invoicing_entity_id,
+        &tenant_id,
+        counter,
+        date.year(),
+    )
+    .await
+    .map_err(Into::<Report<StoreError>>::into)?;
+
+    Ok(pattern.format(counter, date))
+}
+
+/// Allocates the number of a credit note of the invoicing entity issued at `date`, as for the invoices.
+pub(crate) async fn next_credit_note_number(
+    conn: &mut PgConn,
+    invoicing_entity_id: Uuid,
+    tenant_id: Uuid,
+    date: NaiveDate,
+) -> StoreResult<String> {
+    let entity = InvoicingEntityRow::select_for_update_by_id_and_tenant(
+        conn,
+        &invoicing_entity_id,
+        &tenant_id,
+    )
+    .await
+    .map_err(Into::<Report<StoreError>>::into)?;
+
+    let pattern: NumberPattern = entity.credit_note_number_pattern.parse()?;
+    let counter = pattern.counter(
+        entity.next_credit_note_number,
+        entity.credit_note_number_year,
+        date,
+    );
+
+    InvoicingEntityRow::update_credit_note_number(
+        conn,
+        &invoicing_entity_id,
+        &tenant_id,
+        counter,
+        date.year(),
+    )
+    .await
+    .map_err(Into::<Report<StoreError>>::into)?;
+
+    Ok(pattern.format(counter, date))
 }
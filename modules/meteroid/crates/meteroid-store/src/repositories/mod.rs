@@ -17,7 +17,10 @@ pub mod billable_metrics;
 pub mod configs;
 mod constants;
 pub mod coupons;
+pub mod credit_grants;
+pub mod credit_notes;
 pub mod customer_balance;
+pub mod dunning;
 pub mod historical_rates;
 pub mod invoicing_entities;
 pub mod organizations;
@@ -27,6 +30,13 @@ pub mod product_families;
 pub mod products;
 pub mod schedules;
 pub mod stats;
+pub mod subscription_commitments;
+pub mod subscription_pauses;
+pub mod subscription_plan_changes;
+pub mod subscription_trials;
 pub mod subscriptions;
+pub mod taxes;
+pub mod upcoming_invoices;
+pub mod usage_thresholds;
 pub mod users;
 pub mod webhooks;
@@ -1,7 +1,7 @@
 use crate::domain::stats::*;
 use crate::errors::StoreError;
-use crate::utils::decimals::ToSubunit;
 use crate::{Store, StoreResult};
+use common_domain::money::{Currency, Rounding};
 use diesel_models::stats::{
     ActiveSubscriptionsCountRow, CustomerTopRevenueRow, DailyNewSignups90DaysRow,
     LastMrrMovementsRow, MrrBreakdownRow, NewSignupsTrend90DaysRow, PendingInvoicesTotalRow,
@@ -79,11 +79,11 @@ impl StatsInterface for Store {
 
         Ok(CountAndValue {
             count: trend.total,
-            value: trend.total_cents.to_subunit_opt(currency.precision).ok_or(
-                StoreError::InvalidArgument(
+            value: Currency::from_code(currency.code)
+                .and_then(|c| c.to_minor(trend.total_cents, Rounding::HalfAwayFromZero))
+                .ok_or(StoreError::InvalidArgument(
                     "Failed to convert pending invoice total cents".to_string(),
-                ),
-            )?,
+                ))?,
         })
     }
 
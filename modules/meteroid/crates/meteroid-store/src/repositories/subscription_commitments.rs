@@ -0,0 +1,302 @@
+use crate::domain::enums::{InvoiceType, SubscriptionCommitmentTypeEnum};
+use crate::domain::{
+    CreditGrant, CursorPaginatedVec, CursorPaginationRequest, EndedSubscriptionCommitment,
+    SubscriptionCommitment, SubscriptionCommitmentNew, SubscriptionFee,
+};
+use crate::errors::StoreError;
+use crate::repositories::credit_grants::write_off_credit_grant;
+use crate::repositories::customer_balance::CustomerBalance;
+use crate::repositories::invoices::insert_invoice;
+use crate::repositories::{CustomersInterface, SubscriptionInterface};
+use crate::store::{PgConn, Store};
+use crate::StoreResult;
+use chrono::NaiveDate;
+use common_eventbus::Event;
+use diesel_async::scoped_futures::ScopedFutureExt;
+use diesel_models::credit_grants::{CreditGrantRow, CreditGrantRowNew};
+use diesel_models::subscription_commitments::SubscriptionCommitmentRow;
+use diesel_models::subscriptions::SubscriptionRow;
+use error_stack::Report;
+use itertools::Itertools;
+use uuid::Uuid;
+
+#[async_trait::async_trait]
+pub trait SubscriptionCommitmentInterface {
+    /// Commits the subscription to an amount of usage over a term, drawn down by its usage charges.
+    /// A prepaid commitment is invoiced right away.
+    async fn create_subscription_commitment(
+        &self,
+        commitment: SubscriptionCommitmentNew,
+    ) -> StoreResult<SubscriptionCommitment>;
+
+    async fn list_subscription_commitments(
+        &self,
+        tenant_id: Uuid,
+        subscription_id: Uuid,
+    ) -> StoreResult<Vec<SubscriptionCommitment>>;
+
+    async fn list_ended_subscription_commitments(
+        &self,
+        date: NaiveDate,
+        pagination: CursorPaginationRequest,
+    ) -> StoreResult<CursorPaginatedVec<EndedSubscriptionCommitment>>;
+
+    /// Closes a commitment at term end. Its unused part is written off the customer balance,
+    /// and a postpaid commitment is invoiced with a true-up of the unused part.
+    async fn close_subscription_commitment(
+        &self,
+        tenant_id: Uuid,
+        id: Uuid,
+    ) -> StoreResult<SubscriptionCommitment>;
+}
+
+#[async_trait::async_trait]
+impl SubscriptionCommitmentInterface for Store {
+    async fn create_subscription_commitment(
+        &self,
+        commitment: SubscriptionCommitmentNew,
+    ) -> StoreResult<SubscriptionCommitment> {
+        let tenant_id = commitment.tenant_id;
+        let subscription_id = commitment.subscription_id;
+
+        let existing = self
+            .list_subscription_commitments(tenant_id, subscription_id)
+            .await?;
+
+        commitment.validate(&existing)?;
+
+        let subscription = self
+            .get_subscription_details(tenant_id, subscription_id)
+            .await?;
+
+        let metric_ids = subscription
+            .price_components
+            .iter()
+            .filter_map(|c| match &c.fee {
+                SubscriptionFee::Usage { metric_id, .. } => Some(*metric_id),
+                _ => None,
+            })
+            .unique()
+            .collect::<Vec<_>>();
+
+        if metric_ids.is_empty() {
+            return Err(Report::new(StoreError::InvalidArgument(
+                "the subscription has no usage fee to draw down the commitment".to_string(),
+            )));
+        }
+
+        let customer = self
+            .find_customer_by_id(subscription.customer_id, tenant_id)
+            .await?;
+
+        let grant = commitment.credit_grant(customer.id, subscription.currency.clone(), metric_ids);
+
+        grant.validate(&customer.currency)?;
+
+        let invoice = match commitment.commitment_type {
+            SubscriptionCommitmentTypeEnum::Prepaid => Some(
+                self.build_subscription_invoice(
+                    &subscription,
+                    commitment.prepayment_lines(),
+                    chrono::Utc::now().date_naive(),
+                    InvoiceType::Commitment,
+                )
+                .await?,
+            ),
+            SubscriptionCommitmentTypeEnum::Postpaid => None,
+        };
+
+        let row: CreditGrantRowNew = grant.into();
+
+        let (inserted, invoice) = self
+            .transaction(|conn| {
+                async move {
+                    SubscriptionRow::lock_subscription_for_update(conn, subscription_id)
+                        .await
+                        .map_err(Into::<Report<StoreError>>::into)?;
+
+                    // created concurrently
+                    let existing = list_commitments(conn, tenant_id, subscription_id).await?;
+                    commitment.validate(&existing)?;
+
+                    let grant: CreditGrant = row
+                        .insert(conn)
+                        .await
+                        .map_err(Into::<Report<StoreError>>::into)?
+                        .into();
+
+                    CustomerBalance::update_credit_grant(
+                        conn,
+                        &grant,
+                        grant.amount_cents,
+                        None,
+                        "Commitment",
+                    )
+                    .await?;
+
+                    let invoice = match invoice {
+                        Some(invoice) => Some(insert_invoice(conn, invoice).await?),
+                        None => None,
+                    };
+
+                    let inserted = commitment
+                        .into_row(grant.id, invoice.as_ref().map(|i| i.id))
+                        .insert(conn)
+                        .await
+                        .map_err(Into::<Report<StoreError>>::into)?;
+
+                    Ok((
+                        SubscriptionCommitment::from((inserted, grant.remaining_cents)),
+                        invoice,
+                    ))
+                }
+                .scope_boxed()
+            })
+            .await?;
+
+        if let Some(invoice) = &invoice {
+            let _ = self
+                .eventbus
+                .publish(Event::invoice_created(invoice.id, tenant_id))
+                .await;
+        }
+
+        Ok(inserted)
+    }
+
+    async fn list_subscription_commitments(
+        &self,
+        tenant_id: Uuid,
+        subscription_id: Uuid,
+    ) -> StoreResult<Vec<SubscriptionCommitment>> {
+        let mut conn = self.get_conn().await?;
+
+        list_commitments(&mut conn, tenant_id, subscription_id).await
+    }
+
+    async fn list_ended_subscription_commitments(
+        &self,
+        date: NaiveDate,
+        pagination: CursorPaginationRequest,
+    ) -> StoreResult<CursorPaginatedVec<EndedSubscriptionCommitment>> {
+        let mut conn = self.get_conn().await?;
+
+        let rows = SubscriptionCommitmentRow::list_ended(&mut conn, date, pagination.into())
+            .await
+            .map_err(Into::<Report<StoreError>>::into)?;
+
+        Ok(CursorPaginatedVec {
+            items: rows.items.into_iter().map(Into::into).collect(),
+            next_cursor: rows.next_cursor,
+        })
+    }
+
+    async fn close_subscription_commitment(
+        &self,
+        tenant_id: Uuid,
+        id: Uuid,
+    ) -> StoreResult<SubscriptionCommitment> {
+        let commitment: SubscriptionCommitment = {
+            let mut conn = self.get_conn().await?;
+
+            SubscriptionCommitmentRow::find_by_id(&mut conn, id, tenant_id)
+                .await
+                .map_err(Into::<Report<StoreError>>::into)?
+                .into()
+        };
+
+        if commitment.closed_at.is_some() {
+            return Ok(commitment);
+        }
+
+        let unused_cents = commitment.remaining_cents;
+
+        let invoice = match commitment.commitment_type {
+            SubscriptionCommitmentTypeEnum::Prepaid => None,
+            SubscriptionCommitmentTypeEnum::Postpaid => {
+                let subscription = self
+                    .get_subscription_details(tenant_id, commitment.subscription_id)
+                    .await?;
+
+                Some(
+                    self.build_subscription_invoice(
+                        &subscription,
+                        commitment.true_up_lines(unused_cents),
+                        chrono::Utc::now().date_naive(),
+                        InvoiceType::Commitment,
+                    )
+                    .await?,
+                )
+            }
+        };
+
+        let closed = self
+            .transaction(|conn| {
+                async move {
+                    let row =
+                        SubscriptionCommitmentRow::select_for_update_by_id(conn, id, tenant_id)
+                            .await
+                            .map_err(Into::<Report<StoreError>>::into)?;
+
+                    let grant: CreditGrant = CreditGrantRow::select_for_update_by_id(
+                        conn,
+                        row.credit_grant_id,
+                        tenant_id,
+                    )
+                    .await
+                    .map_err(Into::<Report<StoreError>>::into)?
+                    .into();
+
+                    // closed or drawn down concurrently, retried by the next run
+                    if row.closed_at.is_some() || grant.remaining_cents != unused_cents {
+                        return Ok(None);
+                    }
+
+                    write_off_credit_grant(conn, &grant, "Commitment ended").await?;
+
+                    let invoice = match invoice {
+                        Some(invoice) => Some(insert_invoice(conn, invoice).await?),
+                        None => None,
+                    };
+
+                    let closed = SubscriptionCommitmentRow::close(
+                        conn,
+                        id,
+                        unused_cents,
+                        invoice.as_ref().map(|i| i.id).or(row.invoice_id),
+                    )
+                    .await
+                    .map_err(Into::<Report<StoreError>>::into)?;
+
+                    Ok(Some((SubscriptionCommitment::from((closed, 0)), invoice)))
+                }
+                .scope_boxed()
+            })
+            .await?;
+
+        match closed {
+            None => Ok(commitment),
+            Some((closed, invoice)) => {
+                if let Some(invoice) = &invoice {
+                    let _ = self
+                        .eventbus
+                        .publish(Event::invoice_created(invoice.id, tenant_id))
+                        .await;
+                }
+
+                Ok(closed)
+            }
+        }
+    }
+}
+
+async fn list_commitments(
+    conn: &mut PgConn,
+    tenant_id: Uuid,
+    subscription_id: Uuid,
+) -> StoreResult<Vec<SubscriptionCommitment>> {
+    SubscriptionCommitmentRow::list_by_subscription_id(conn, tenant_id, subscription_id)
+        .await
+        .map(|rows| rows.into_iter().map(Into::into).collect())
+        .map_err(Into::<Report<StoreError>>::into)
+}
@@ -0,0 +1,357 @@
+use crate::compute::{calculate_elapsed_billing_cycles, InvoiceLineInterface};
+use crate::domain::enums::{InvoiceType, SubscriptionEventType};
+use crate::domain::{
+    CursorPaginatedVec, CursorPaginationRequest, Invoice, Subscription, SubscriptionDetails,
+    SubscriptionToResume,
+};
+use crate::errors::StoreError;
+use crate::repositories::invoices::{insert_invoice, log_mrr_movements};
+use crate::repositories::SubscriptionInterface;
+use crate::store::{PgConn, Store};
+use crate::utils::periods::calculate_resume_periods;
+use crate::StoreResult;
+use chrono::NaiveDate;
+use common_eventbus::Event;
+use diesel_async::scoped_futures::ScopedFutureExt;
+use diesel_models::invoices::InvoiceRow;
+use diesel_models::plan_versions::PlanVersionRow;
+use diesel_models::subscription_events::SubscriptionEventRow;
+use diesel_models::subscriptions::SubscriptionRow;
+use error_stack::Report;
+use uuid::Uuid;
+
+#[async_trait::async_trait]
+pub trait SubscriptionPauseInterface {
+    /// Pauses the subscription until it is resumed, or until `resume_at` if provided.
+    /// The current period is closed at the pause date: the upcoming invoices are voided and replaced by an invoice
+    /// of the fees billed in arrears, prorated up to the pause date, and of the usage accrued up to the pause date.
+    /// No invoice is issued while paused.
+    async fn pause_subscription(
+        &self,
+        tenant_id: Uuid,
+        subscription_id: Uuid,
+        resume_at: Option<NaiveDate>,
+    ) -> StoreResult<Subscription>;
+
+    /// Resumes a paused subscription. Billing restarts at the current date, with a prorated first period.
+    async fn resume_subscription(
+        &self,
+        tenant_id: Uuid,
+        subscription_id: Uuid,
+    ) -> StoreResult<Subscription>;
+
+    async fn list_subscriptions_to_resume(
+        &self,
+        date: NaiveDate,
+        pagination: CursorPaginationRequest,
+    ) -> StoreResult<CursorPaginatedVec<SubscriptionToResume>>;
+}
+
+#[async_trait::async_trait]
+impl SubscriptionPauseInterface for Store {
+    async fn pause_subscription(
+        &self,
+        tenant_id: Uuid,
+        subscription_id: Uuid,
+        resume_at: Option<NaiveDate>,
+    ) -> StoreResult<Subscription> {
+        let subscription = self
+            .get_subscription_details(tenant_id, subscription_id)
+            .await?;
+
+        let today = chrono::Utc::now().date_naive();
+
+        // the current period was only billed if the subscription is active
+        let closing_invoice =
+            if subscription.activated_at.is_some() && today > subscription.billing_start_date {
+                let lines = self.compute_closing_lines(&today, &subscription).await?;
+
+                if lines.iter().any(|l| l.subtotal != 0) {
+                    Some(
+                        self.build_subscription_invoice(
+                            &subscription,
+                            lines,
+                            today,
+                            InvoiceType::Adjustment,
+                        )
+                        .await?,
+                    )
+                } else {
+                    None
+                }
+            } else {
+                None
+            };
+
+        let (paused, closing_invoice_id) = self
+            .transaction(|conn| {
+                async move {
+                    SubscriptionRow::lock_subscription_for_update(conn, subscription_id)
+                        .await
+                        .map_err(Into::<Report<StoreError>>::into)?;
+
+                    let subscription: Subscription =
+                        SubscriptionRow::get_subscription_by_id(conn, &tenant_id, &subscription_id)
+                            .await
+                            .map_err(Into::<Report<StoreError>>::into)?
+                            .into();
+
+                    let now = chrono::Utc::now().naive_utc();
+
+                    subscription.validate_pause(now.date(), resume_at)?;
+
+                    SubscriptionRow::pause_subscription(
+                        conn,
+                        subscription_id,
+                        tenant_id,
+                        resume_at,
+                    )
+                    .await
+                    .map_err(Into::<Report<StoreError>>::into)?;
+
+                    // the upcoming invoice bills the whole period, it is replaced by the closing invoice
+                    InvoiceRow::void_upcoming_by_subscription_id(
+                        conn,
+                        tenant_id,
+                        subscription_id,
+                        today,
+                    )
+                    .await
+                    .map_err(Into::<Report<StoreError>>::into)?;
+
+                    let event = SubscriptionEventRow {
+                        id: Uuid::now_v7(),
+                        subscription_id,
+                        event_type: SubscriptionEventType::Paused.into(),
+                        details: None,
+                        created_at: now,
+                        mrr_delta: Some(-(subscription.mrr_cents as i64)),
+                        bi_mrr_movement_log_id: None,
+                        applies_to: today,
+                    }
+                    .insert(conn)
+                    .await
+                    .map_err(Into::<Report<StoreError>>::into)?;
+
+                    let closing_invoice_id = match closing_invoice {
+                        // logs the mrr movement of the pause event
+                        Some(invoice) => Some(insert_invoice(conn, invoice).await?.id),
+                        None => {
+                            // no further invoice is issued while paused, so the movement is logged against the last one
+                            let last_invoice =
+                                InvoiceRow::find_latest_recurring_by_subscription_id(
+                                    conn,
+                                    subscription_id,
+                                )
+                                .await
+                                .map_err(Into::<Report<StoreError>>::into)?;
+
+                            if let Some(last_invoice) = last_invoice {
+                                let last_invoice: Invoice = last_invoice.try_into()?;
+                                log_mrr_movements(conn, &last_invoice, vec![event]).await?;
+                            }
+
+                            None
+                        }
+                    };
+
+                    let paused = get_subscription(conn, tenant_id, subscription_id).await?;
+
+                    Ok((paused, closing_invoice_id))
+                }
+                .scope_boxed()
+            })
+            .await?;
+
+        if let Some(invoice_id) = closing_invoice_id {
+            let _ = self
+                .eventbus
+                .publish(Event::invoice_created(invoice_id, tenant_id))
+                .await;
+        }
+
+        Ok(paused)
+    }
+
+    async fn resume_subscription(
+        &self,
+        tenant_id: Uuid,
+        subscription_id: Uuid,
+    ) -> StoreResult<Subscription> {
+        let subscription = self
+            .get_subscription_details(tenant_id, subscription_id)
+            .await?;
+
+        if subscription.paused_at.is_none() || subscription.canceled_at.is_some() {
+            return Err(Report::new(StoreError::InvalidArgument(
+                "the subscription is not paused".to_string(),
+            )));
+        }
+
+        let mut conn = self.get_conn().await?;
+
+        let plan_version = PlanVersionRow::find_by_id_and_tenant_id(
+            &mut conn,
+            subscription.plan_version_id,
+            tenant_id,
+        )
+        .await
+        .map_err(Into::<Report<StoreError>>::into)?;
+
+        let today = chrono::Utc::now().date_naive();
+
+        // the billing cycles and ramps go on from the cycle reached before the pause
+        let elapsed_billing_cycles = subscription.elapsed_billing_cycles
+            + subscription.paused_at.map_or(0, |paused_at| {
+                calculate_elapsed_billing_cycles(
+                    subscription.billing_start_date,
+                    subscription.billing_day as u32,
+                    paused_at.date(),
+                    &subscription.period,
+                )
+            });
+
+        // billing restarts at the resume date, the first period being prorated up to the billing day
+        let resumed = SubscriptionDetails {
+            billing_start_date: today,
+            elapsed_billing_cycles,
+            ..subscription.clone()
+        };
+
+        let draft = self
+            .build_first_invoice(
+                &mut conn,
+                &resumed,
+                &plan_version,
+                subscription.period.clone(),
+            )
+            .await?;
+
+        let first_invoice_date =
+            calculate_resume_periods(today, subscription.billing_day as u32, &subscription.period)
+                .advance
+                .end;
+
+        let (resumed, invoice_id) = self
+            .transaction(|conn| {
+                async move {
+                    SubscriptionRow::lock_subscription_for_update(conn, subscription_id)
+                        .await
+                        .map_err(Into::<Report<StoreError>>::into)?;
+
+                    let locked: Subscription =
+                        SubscriptionRow::get_subscription_by_id(conn, &tenant_id, &subscription_id)
+                            .await
+                            .map_err(Into::<Report<StoreError>>::into)?
+                            .into();
+
+                    // resumed concurrently, or cancelled in the meantime
+                    if locked.paused_at.is_none() || locked.canceled_at.is_some() {
+                        return Err(Report::new(StoreError::InvalidArgument(
+                            "the subscription is not paused".to_string(),
+                        )));
+                    }
+
+                    let paused = SubscriptionEventRow::find_latest_by_subscription_id_and_type(
+                        conn,
+                        subscription_id,
+                        SubscriptionEventType::Paused.into(),
+                    )
+                    .await
+                    .map_err(Into::<Report<StoreError>>::into)?;
+
+                    // the MRR is only restored if its loss was logged on pause
+                    let mrr_delta = paused
+                        .filter(|e| e.bi_mrr_movement_log_id.is_some())
+                        .and_then(|e| e.mrr_delta)
+                        .map(|delta| -delta);
+
+                    SubscriptionRow::resume_subscription(
+                        conn,
+                        subscription_id,
+                        tenant_id,
+                        today,
+                        elapsed_billing_cycles,
+                    )
+                    .await
+                    .map_err(Into::<Report<StoreError>>::into)?;
+
+                    // logged with the other events of the period, when the first invoice is inserted
+                    SubscriptionEventRow {
+                        id: Uuid::now_v7(),
+                        subscription_id,
+                        event_type: SubscriptionEventType::Resumed.into(),
+                        details: None,
+                        created_at: chrono::Utc::now().naive_utc(),
+                        mrr_delta,
+                        bi_mrr_movement_log_id: None,
+                        applies_to: first_invoice_date,
+                    }
+                    .insert(conn)
+                    .await
+                    .map_err(Into::<Report<StoreError>>::into)?;
+
+                    let invoice_id = match draft {
+                        Some(draft) => {
+                            let existing = InvoiceRow::find_recurring_by_subscription_id_and_date(
+                                conn,
+                                subscription_id,
+                                draft.invoice_date,
+                            )
+                            .await
+                            .map_err(Into::<Report<StoreError>>::into)?;
+
+                            match existing {
+                                Some(_) => None,
+                                None => Some(insert_invoice(conn, draft).await?.id),
+                            }
+                        }
+                        None => None,
+                    };
+
+                    let resumed = get_subscription(conn, tenant_id, subscription_id).await?;
+
+                    Ok((resumed, invoice_id))
+                }
+                .scope_boxed()
+            })
+            .await?;
+
+        if let Some(invoice_id) = invoice_id {
+            let _ = self
+                .eventbus
+                .publish(Event::invoice_created(invoice_id, tenant_id))
+                .await;
+        }
+
+        Ok(resumed)
+    }
+
+    async fn list_subscriptions_to_resume(
+        &self,
+        date: NaiveDate,
+        pagination: CursorPaginationRequest,
+    ) -> StoreResult<CursorPaginatedVec<SubscriptionToResume>> {
+        let mut conn = self.get_conn().await?;
+
+        let rows = SubscriptionRow::list_to_resume(&mut conn, date, pagination.into())
+            .await
+            .map_err(Into::<Report<StoreError>>::into)?;
+
+        Ok(CursorPaginatedVec {
+            items: rows.items.into_iter().map(Into::into).collect(),
+            next_cursor: rows.next_cursor,
+        })
+    }
+}
+
+async fn get_subscription(
+    conn: &mut PgConn,
+    tenant_id: Uuid,
+    subscription_id: Uuid,
+) -> StoreResult<Subscription> {
+    SubscriptionRow::get_subscription_by_id(conn, &tenant_id, &subscription_id)
+        .await
+        .map(Into::into)
+        .map_err(Into::<Report<StoreError>>::into)
+}
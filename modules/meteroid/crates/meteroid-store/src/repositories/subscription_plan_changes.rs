@@ -0,0 +1,1218 @@
+use crate::compute::InvoiceLineInterface;
+use crate::domain::enums::{InvoiceType, SubscriptionEventType};
+use crate::domain::subscription_add_ons::SubscriptionAddOn;
+use crate::domain::{
+    ChangeSubscriptionPlan, ComponentParameterization, ComponentParameters,
+    CreateSubscriptionComponents, CursorPaginatedVec, CursorPaginationRequest, Customer,
+    ExtraComponent, FeeType, Invoice, InvoiceTotals, InvoiceTotalsParams, MigrateSubscriptions,
+    PlanChangeEffectiveAt, PlanChangeProration, PriceComponent, ResolvedTax, Subscription,
+    SubscriptionComponent, SubscriptionComponentNew, SubscriptionComponentNewInternal,
+    SubscriptionDetails, SubscriptionFee, SubscriptionInvoiceCandidate, SubscriptionMigration,
+    SubscriptionPlanChange, SubscriptionPlanChangeNew, SubscriptionPlanChanged, TenantContext,
+};
+use crate::errors::StoreError;
+use crate::repositories::customer_balance::{to_balance_cents, CustomerBalance};
+use crate::repositories::invoices::{insert_invoice, log_mrr_movements};
+use crate::repositories::invoicing_entities::InvoicingEntityInterface;
+use crate::repositories::subscriptions::{
+    calculate_mrr, extract_billing_period, process_create_subscription_components,
+    subscription_to_draft,
+};
+use crate::repositories::taxes::TaxRateInterface;
+use crate::repositories::{CustomersInterface, SubscriptionInterface};
+use crate::store::{PgConn, Store};
+use crate::StoreResult;
+use chrono::{NaiveDate, NaiveTime};
+use common_domain::money::Currency;
+use diesel_async::scoped_futures::ScopedFutureExt;
+use diesel_models::invoices::InvoiceRow;
+use diesel_models::plan_versions::PlanVersionRow;
+use diesel_models::price_components::PriceComponentRow;
+use diesel_models::query::plans::get_plan_names_by_version_ids;
+use diesel_models::subscription_add_ons::SubscriptionAddOnRow;
+use diesel_models::subscription_components::{
+    SubscriptionComponentRow, SubscriptionComponentRowNew,
+};
+use diesel_models::subscription_events::SubscriptionEventRow;
+use diesel_models::subscription_plan_changes::{
+    SubscriptionPlanChangeRow, SubscriptionPlanChangeRowNew,
+};
+use diesel_models::subscriptions::SubscriptionRow;
+use error_stack::Report;
+use std::collections::HashMap;
+use uuid::Uuid;
+
+#[async_trait::async_trait]
+pub trait SubscriptionPlanChangeInterface {
+    async fn change_subscription_plan(
+        &self,
+        change: ChangeSubscriptionPlan,
+        context: TenantContext,
+    ) -> StoreResult<SubscriptionPlanChanged>;
+
+    async fn list_due_plan_changes(
+        &self,
+        date: NaiveDate,
+        pagination: CursorPaginationRequest,
+    ) -> StoreResult<CursorPaginatedVec<SubscriptionPlanChange>>;
+
+    async fn apply_plan_change(&self, id: Uuid) -> StoreResult<()>;
+
+    async fn migrate_subscriptions(
+        &self,
+        migration: MigrateSubscriptions,
+        context: TenantContext,
+    ) -> StoreResult<Vec<SubscriptionMigration>>;
+}
+
+#[async_trait::async_trait]
+impl SubscriptionPlanChangeInterface for Store {
+    async fn change_subscription_plan(
+        &self,
+        change: ChangeSubscriptionPlan,
+        context: TenantContext,
+    ) -> StoreResult<SubscriptionPlanChanged> {
+        let tenant_id = context.tenant_id;
+        let subscription_id = change.subscription_id;
+        let plan_version_id = change.plan_version_id;
+
+        let subscription = self
+            .get_subscription_details(tenant_id, subscription_id)
+            .await?;
+
+        validate_plan_change(&subscription, change.effective_at)?;
+
+        if subscription.plan_version_id == plan_version_id {
+            return Err(Report::new(StoreError::InvalidArgument(
+                "the subscription is already on this plan version".to_string(),
+            )));
+        }
+
+        let mut conn = self.get_conn().await?;
+
+        let plan_version =
+            PlanVersionRow::find_by_id_and_tenant_id(&mut conn, plan_version_id, tenant_id)
+                .await
+                .map_err(Into::<Report<StoreError>>::into)?;
+
+        if plan_version.is_draft_version {
+            return Err(Report::new(StoreError::InvalidArgument(
+                "cannot change to a draft plan version".to_string(),
+            )));
+        }
+
+        if plan_version.currency != subscription.currency {
+            return Err(Report::new(StoreError::InvalidArgument(
+                "the plan version currency does not match the subscription currency".to_string(),
+            )));
+        }
+
+        let price_components = load_plan_price_components(&mut conn, plan_version_id).await?;
+
+        let components = process_create_subscription_components(
+            &change.components,
+            &price_components,
+            &plan_version_id,
+        )?;
+
+        let (current_mrr, new_mrr) = calculate_components_mrr(&subscription, &components)?;
+        let mrr_delta = new_mrr - current_mrr;
+
+        let today = chrono::Utc::now().date_naive();
+
+        let (effective_date, proration, adjustment_invoice_id) = match change.effective_at {
+            PlanChangeEffectiveAt::EndOfBillingPeriod => {
+                let effective_date = subscription
+                    .calculate_cancellable_end_of_period_date(today)
+                    .ok_or(Report::new(StoreError::InvalidArgument(
+                        "cannot resolve the end of the billing period".to_string(),
+                    )))?;
+
+                let insertable: SubscriptionPlanChangeRowNew = SubscriptionPlanChangeNew {
+                    subscription_id,
+                    tenant_id,
+                    plan_version_id,
+                    components,
+                    mrr_delta,
+                    effective_date,
+                    created_by: context.actor,
+                }
+                .try_into()?;
+
+                self.transaction(|conn| {
+                    async move {
+                        // a new change replaces the pending one
+                        SubscriptionPlanChangeRow::delete_pending_by_subscription_id(
+                            conn,
+                            tenant_id,
+                            subscription_id,
+                        )
+                        .await
+                        .map_err(Into::<Report<StoreError>>::into)?;
+
+                        insertable
+                            .insert(conn)
+                            .await
+                            .map_err(Into::<Report<StoreError>>::into)
+                    }
+                    .scope_boxed()
+                })
+                .await?;
+
+                (effective_date, None, None)
+            }
+            PlanChangeEffectiveAt::Immediate => {
+                let new_components = components
+                    .into_iter()
+                    .map(|internal| SubscriptionComponentNew {
+                        subscription_id,
+                        internal,
+                    })
+                    .collect::<Vec<_>>();
+
+                // the current period was only billed if the subscription is active
+                let proration = if subscription.activated_at.is_some()
+                    && today >= subscription.billing_start_date
+                {
+                    let credit_lines = self
+                        .compute_prorated_lines(
+                            &today,
+                            &subscription,
+                            &subscription.price_components,
+                        )
+                        .await?;
+                    let charge_lines = self
+                        .compute_prorated_lines(&today, &subscription, &new_components)
+                        .await?;
+
+                    let customer = self
+                        .find_customer_by_id(subscription.customer_id, tenant_id)
+                        .await?;
+                    let tax = self.resolve_customer_tax(&customer).await?;
+
+                    Some((
+                        PlanChangeProration::new(credit_lines, charge_lines, &tax),
+                        customer,
+                        tax,
+                    ))
+                } else {
+                    None
+                };
+
+                let adjustment_invoice = match &proration {
+                    Some((proration, customer, tax)) => Some(
+                        self.build_adjustment_invoice(
+                            &subscription,
+                            &plan_version,
+                            proration,
+                            customer,
+                            tax,
+                            today,
+                        )
+                        .await?,
+                    ),
+                    None => None,
+                };
+                let proration = proration.map(|(proration, _, _)| proration);
+
+                let insertable_components = new_components
+                    .into_iter()
+                    .map(TryInto::try_into)
+                    .collect::<Result<Vec<SubscriptionComponentRowNew>, _>>(
+                )?;
+
+                let credit = proration.as_ref().map(|p| p.credit_total()).unwrap_or(0);
+                let customer_id = subscription.customer_id;
+
+                let adjustment_invoice_id = self
+                    .transaction(|conn| {
+                        async move {
+                            SubscriptionRow::lock_subscription_for_update(conn, subscription_id)
+                                .await
+                                .map_err(Into::<Report<StoreError>>::into)?;
+
+                            SubscriptionPlanChangeRow::delete_pending_by_subscription_id(
+                                conn,
+                                tenant_id,
+                                subscription_id,
+                            )
+                            .await
+                            .map_err(Into::<Report<StoreError>>::into)?;
+
+                            swap_subscription_plan(
+                                conn,
+                                tenant_id,
+                                subscription_id,
+                                plan_version_id,
+                                insertable_components,
+                            )
+                            .await?;
+
+                            // the unused part of the current period is credited to the customer balance
+                            if credit > 0 {
+                                CustomerBalance::update(
+                                    conn,
+                                    customer_id,
+                                    tenant_id,
+                                    to_balance_cents(credit)?,
+                                    None,
+                                )
+                                .await?;
+                            }
+
+                            match adjustment_invoice {
+                                Some(invoice) => {
+                                    insert_switch_event(conn, subscription_id, mrr_delta, today)
+                                        .await?;
+                                    // logs the mrr movement of the switch event
+                                    let inserted = insert_invoice(conn, invoice).await?;
+                                    Ok(Some(inserted.id))
+                                }
+                                None => {
+                                    // not billed yet, the movement is logged with the first invoice
+                                    let applies_to = today.max(subscription.billing_start_date);
+                                    let event = insert_switch_event(
+                                        conn,
+                                        subscription_id,
+                                        mrr_delta,
+                                        applies_to,
+                                    )
+                                    .await?;
+                                    log_against_recurring_invoice(
+                                        conn,
+                                        subscription_id,
+                                        applies_to,
+                                        event,
+                                    )
+                                    .await?;
+                                    Ok(None)
+                                }
+                            }
+                        }
+                        .scope_boxed()
+                    })
+                    .await?;
+
+                (today, proration, adjustment_invoice_id)
+            }
+        };
+
+        let subscription: Subscription =
+            SubscriptionRow::get_subscription_by_id(&mut conn, &tenant_id, &subscription_id)
+                .await
+                .map_err(Into::<Report<StoreError>>::into)?
+                .into();
+
+        Ok(SubscriptionPlanChanged {
+            subscription,
+            mrr_delta,
+            effective_date,
+            proration,
+            adjustment_invoice_id,
+        })
+    }
+
+    async fn list_due_plan_changes(
+        &self,
+        date: NaiveDate,
+        pagination: CursorPaginationRequest,
+    ) -> StoreResult<CursorPaginatedVec<SubscriptionPlanChange>> {
+        let mut conn = self.get_conn().await?;
+
+        let rows = SubscriptionPlanChangeRow::list_due(&mut conn, date, pagination.into())
+            .await
+            .map_err(Into::<Report<StoreError>>::into)?;
+
+        Ok(CursorPaginatedVec {
+            items: rows
+                .items
+                .into_iter()
+                .map(TryInto::try_into)
+                .collect::<Result<Vec<_>, _>>()?,
+            next_cursor: rows.next_cursor,
+        })
+    }
+
+    async fn apply_plan_change(&self, id: Uuid) -> StoreResult<()> {
+        self.transaction(|conn| {
+            async move {
+                let change: SubscriptionPlanChange =
+                    SubscriptionPlanChangeRow::select_for_update_by_id(conn, id)
+                        .await
+                        .map_err(Into::<Report<StoreError>>::into)?
+                        .try_into()?;
+
+                if change.applied_at.is_some() {
+                    return Ok(());
+                }
+
+                SubscriptionRow::lock_subscription_for_update(conn, change.subscription_id)
+                    .await
+                    .map_err(Into::<Report<StoreError>>::into)?;
+
+                let subscription: Subscription = SubscriptionRow::get_subscription_by_id(
+                    conn,
+                    &change.tenant_id,
+                    &change.subscription_id,
+                )
+                .await
+                .map_err(Into::<Report<StoreError>>::into)?
+                .into();
+
+                // a paused subscription changes plan once resumed, the change staying due until then
+                if subscription.paused_at.is_some() {
+                    return Ok(());
+                }
+
+                // a subscription cancelled in the meantime keeps its plan
+                if subscription.canceled_at.is_none() {
+                    // billing restarts at the resume date, which a change deferred by a pause takes effect from
+                    let effective_date = change.effective_date.max(subscription.billing_start_date);
+
+                    let insertable_components = change
+                        .components
+                        .into_iter()
+                        .map(|internal| {
+                            SubscriptionComponentNew {
+                                subscription_id: change.subscription_id,
+                                internal,
+                            }
+                            .try_into()
+                        })
+                        .collect::<Result<Vec<SubscriptionComponentRowNew>, _>>()?;
+
+                    swap_subscription_plan(
+                        conn,
+                        change.tenant_id,
+                        change.subscription_id,
+                        change.plan_version_id,
+                        insertable_components,
+                    )
+                    .await?;
+
+                    let event = insert_switch_event(
+                        conn,
+                        change.subscription_id,
+                        change.mrr_delta,
+                        effective_date,
+                    )
+                    .await?;
+
+                    // the invoice of that date may already be drafted, else it will log the event on insert
+                    log_against_recurring_invoice(
+                        conn,
+                        change.subscription_id,
+                        effective_date,
+                        event,
+                    )
+                    .await?;
+                }
+
+                SubscriptionPlanChangeRow::mark_as_applied(conn, id)
+                    .await
+                    .map_err(Into::<Report<StoreError>>::into)
+            }
+            .scope_boxed()
+        })
+        .await
+    }
+
+    async fn migrate_subscriptions(
+        &self,
+        migration: MigrateSubscriptions,
+        context: TenantContext,
+    ) -> StoreResult<Vec<SubscriptionMigration>> {
+        let tenant_id = context.tenant_id;
+
+        let mut conn = self.get_conn().await?;
+
+        let from_version = PlanVersionRow::find_by_id_and_tenant_id(
+            &mut conn,
+            migration.from_plan_version_id,
+            tenant_id,
+        )
+        .await
+        .map_err(Into::<Report<StoreError>>::into)?;
+
+        let to_version = PlanVersionRow::find_by_id_and_tenant_id(
+            &mut conn,
+            migration.to_plan_version_id,
+            tenant_id,
+        )
+        .await
+        .map_err(Into::<Report<StoreError>>::into)?;
+
+        if from_version.id == to_version.id {
+            return Err(Report::new(StoreError::InvalidArgument(
+                "cannot migrate subscriptions to the same plan version".to_string(),
+            )));
+        }
+
+        if from_version.plan_id != to_version.plan_id {
+            return Err(Report::new(StoreError::InvalidArgument(
+                "the plan versions must belong to the same plan".to_string(),
+            )));
+        }
+
+        if to_version.is_draft_version {
+            return Err(Report::new(StoreError::InvalidArgument(
+                "cannot migrate subscriptions to a draft plan version".to_string(),
+            )));
+        }
+
+        if from_version.currency != to_version.currency {
+            return Err(Report::new(StoreError::InvalidArgument(
+                "the plan versions currencies do not match".to_string(),
+            )));
+        }
+
+        let price_components = load_plan_price_components(&mut conn, to_version.id).await?;
+
+        let subscription_ids = SubscriptionRow::list_active_ids_by_plan_version_id(
+            &mut conn,
+            tenant_id,
+            from_version.id,
+            migration.subscription_ids,
+            migration.customer_ids,
+        )
+        .await
+        .map_err(Into::<Report<StoreError>>::into)?;
+
+        let today = chrono::Utc::now().date_naive();
+
+        let mut migrations = Vec::with_capacity(subscription_ids.len());
+        let mut insertable: Vec<SubscriptionPlanChangeRowNew> = Vec::new();
+
+        // TODO batch, the details are fetched one subscription at a time
+        for subscription_id in subscription_ids {
+            let subscription = self
+                .get_subscription_details(tenant_id, subscription_id)
+                .await?;
+
+            let (mut report, plan_change) = self
+                .prepare_migration(&subscription, &price_components, to_version.id, today)
+                .await?;
+
+            if let Some(plan_change) = plan_change {
+                // deleted when the migration is scheduled
+                report.replaced_plan_version_id =
+                    SubscriptionPlanChangeRow::find_pending_by_subscription_id(
+                        &mut conn,
+                        tenant_id,
+                        subscription_id,
+                    )
+                    .await
+                    .map_err(Into::<Report<StoreError>>::into)?
+                    .map(|pending| pending.plan_version_id);
+
+                insertable.push(
+                    SubscriptionPlanChangeNew {
+                        created_by: context.actor,
+                        ..plan_change
+                    }
+                    .try_into()?,
+                );
+            }
+
+            migrations.push(report);
+        }
+
+        if migration.dry_run || insertable.is_empty() {
+            return Ok(migrations);
+        }
+
+        self.transaction(|conn| {
+            async move {
+                for plan_change in insertable {
+                    // the migration replaces the pending changes
+                    SubscriptionPlanChangeRow::delete_pending_by_subscription_id(
+                        conn,
+                        tenant_id,
+                        plan_change.subscription_id,
+                    )
+                    .await
+                    .map_err(Into::<Report<StoreError>>::into)?;
+
+                    plan_change
+                        .insert(conn)
+                        .await
+                        .map_err(Into::<Report<StoreError>>::into)?;
+                }
+
+                Ok(())
+            }
+            .scope_boxed()
+        })
+        .await?;
+
+        Ok(migrations)
+    }
+}
+
+impl Store {
+    /// Resolves the components of the subscription on the new version, and reports the impact with the compute engine.
+    /// A subscription that cannot be migrated is reported with its error, without failing the whole migration.
+    async fn prepare_migration(
+        &self,
+        subscription: &SubscriptionDetails,
+        price_components: &HashMap<Uuid, Vec<PriceComponent>>,
+        plan_version_id: Uuid,
+        today: NaiveDate,
+    ) -> StoreResult<(SubscriptionMigration, Option<SubscriptionPlanChangeNew>)> {
+        let (mut report, migrated) =
+            plan_migration(subscription, price_components, plan_version_id, today);
+
+        let (effective_date, components) = match migrated {
+            Some(migrated) => migrated,
+            None => return Ok((report, None)),
+        };
+
+        let migrated = SubscriptionDetails {
+            plan_version_id,
+            price_components: components
+                .iter()
+                .map(|c| SubscriptionComponent {
+                    id: Uuid::now_v7(),
+                    price_component_id: c.price_component_id,
+                    product_item_id: c.product_item_id,
+                    subscription_id: subscription.id,
+                    name: c.name.clone(),
+                    period: c.period.clone(),
+                    fee: c.fee.clone(),
+                })
+                .collect(),
+            ..subscription.clone()
+        };
+
+        // the first invoice on the new version, compared to the same invoice on the current one
+        let current_lines = self
+            .compute_dated_invoice_lines(&effective_date, subscription)
+            .await;
+        let new_lines = self
+            .compute_dated_invoice_lines(&effective_date, &migrated)
+            .await;
+
+        match (current_lines, new_lines) {
+            (Ok(current_lines), Ok(new_lines)) => {
+                report.current_invoice_subtotal = current_lines.iter().map(|l| l.total).sum();
+                report.new_invoice_subtotal = new_lines.iter().map(|l| l.total).sum();
+            }
+            (Err(err), _) | (_, Err(err)) => {
+                report.error = Some(format!("failed to compute the invoice: {}", err));
+                return Ok((report, None));
+            }
+        }
+
+        let plan_change = SubscriptionPlanChangeNew {
+            subscription_id: subscription.id,
+            tenant_id: subscription.tenant_id,
+            plan_version_id,
+            components,
+            mrr_delta: report.mrr_delta(),
+            effective_date,
+            created_by: subscription.created_by,
+        };
+
+        Ok((report, Some(plan_change)))
+    }
+
+    async fn build_adjustment_invoice(
+        &self,
+        subscription: &SubscriptionDetails,
+        plan_version: &PlanVersionRow,
+        proration: &PlanChangeProration,
+        customer: &Customer,
+        tax: &ResolvedTax,
+        invoice_date: NaiveDate,
+    ) -> StoreResult<crate::domain::InvoiceNew> {
+        let mut conn = self.get_conn().await?;
+
+        let invoicing_entity = self
+            .get_invoicing_entity(subscription.tenant_id, Some(customer.invoicing_entity_id))
+            .await?;
+
+        let plan_name = get_plan_names_by_version_ids(&mut conn, vec![plan_version.id])
+            .await
+            .map_err(Into::<Report<StoreError>>::into)?
+            .remove(&plan_version.id)
+            .unwrap_or_else(|| subscription.plan_name.clone());
+
+        let candidate = SubscriptionInvoiceCandidate {
+            id: subscription.id,
+            tenant_id: subscription.tenant_id,
+            customer_id: subscription.customer_id,
+            plan_version_id: plan_version.id,
+            plan_name: plan_name.clone(),
+            billing_start_date: subscription.billing_start_date,
+            billing_end_date: subscription.billing_end_date,
+            billing_day: subscription.billing_day,
+            activated_at: subscription.activated_at,
+            canceled_at: subscription.canceled_at,
+            currency: subscription.currency.clone(),
+            net_terms: plan_version.net_terms,
+            period: subscription.period.clone(),
+        };
+
+        let mut invoice = subscription_to_draft(&candidate, customer, &invoicing_entity)?;
+
+        // refreshed on finalization, with the balance at that time
+        let totals = InvoiceTotals::from_params(InvoiceTotalsParams {
+            line_items: &proration.charge_lines,
+            total: 0,
+            amount_due: 0,
+            tax,
+            customer_balance_cents: customer
+                .balance_value_cents
+                .saturating_add(to_balance_cents(proration.credit_total())?),
+            subscription_applied_coupons: &vec![],
+            invoice_currency: subscription.currency.as_str(),
+        });
+
+        invoice.invoice_type = InvoiceType::Adjustment;
+        invoice.invoice_date = invoice_date;
+        invoice.due_at = Some(
+            (invoice_date + chrono::Duration::days(plan_version.net_terms as i64))
+                .and_time(NaiveTime::MIN),
+        );
+        invoice.plan_name = Some(plan_name);
+        invoice.line_items = totals.line_items;
+        invoice.subtotal = totals.subtotal;
+        invoice.subtotal_recurring = totals.subtotal_recurring;
+        invoice.tax_rate = tax.rate;
+        invoice.tax_amount = totals.tax_amount;
+        invoice.tax_breakdown = totals.tax_breakdown;
+        invoice.total = totals.total;
+        invoice.amount_due = totals.amount_due;
+
+        Ok(invoice)
+    }
+}
+
+pub(crate) async fn load_plan_price_components(
+    conn: &mut PgConn,
+    plan_version_id: Uuid,
+) -> StoreResult<HashMap<Uuid, Vec<PriceComponent>>> {
+    PriceComponentRow::get_by_plan_ids(conn, &[plan_version_id])
+        .await
+        .map_err(Into::<Report<StoreError>>::into)?
+        .into_iter()
+        .map(|(k, v)| {
+            v.into_iter()
+                .map(TryInto::try_into)
+                .collect::<Result<Vec<PriceComponent>, _>>()
+                .map(|vec| (k, vec))
+        })
+        .collect::<Result<HashMap<_, _>, _>>()
+}
+
+/// Reports the migration of a subscription to the new version, with its components and effective date if it can be migrated.
+pub(crate) fn plan_migration(
+    subscription: &SubscriptionDetails,
+    price_components: &HashMap<Uuid, Vec<PriceComponent>>,
+    plan_version_id: Uuid,
+    today: NaiveDate,
+) -> (
+    SubscriptionMigration,
+    Option<(NaiveDate, Vec<SubscriptionComponentNewInternal>)>,
+) {
+    let mut report = SubscriptionMigration {
+        subscription_id: subscription.id,
+        customer_id: subscription.customer_id,
+        customer_name: subscription.customer_name.clone(),
+        effective_date: None,
+        current_mrr: 0,
+        new_mrr: 0,
+        current_invoice_subtotal: 0,
+        new_invoice_subtotal: 0,
+        replaced_plan_version_id: None,
+        error: None,
+    };
+
+    let parameters = carry_over_parameters(
+        &subscription.price_components,
+        price_components
+            .get(&plan_version_id)
+            .map(Vec::as_slice)
+            .unwrap_or_default(),
+    );
+
+    let components = match process_create_subscription_components(
+        &Some(parameters),
+        price_components,
+        &plan_version_id,
+    ) {
+        Ok(components) => components,
+        Err(err) => {
+            report.error = Some(err.to_string());
+            return (report, None);
+        }
+    };
+
+    match calculate_components_mrr(subscription, &components) {
+        Ok((current_mrr, new_mrr)) => {
+            report.current_mrr = current_mrr;
+            report.new_mrr = new_mrr;
+        }
+        Err(err) => {
+            report.error = Some(err.current_context().to_string());
+            return (report, None);
+        }
+    }
+
+    let effective_date = match subscription.calculate_cancellable_end_of_period_date(today) {
+        Some(effective_date) => effective_date,
+        None => {
+            report.error = Some("cannot resolve the end of the billing period".to_string());
+            return (report, None);
+        }
+    };
+    report.effective_date = Some(effective_date);
+
+    (report, Some((effective_date, components)))
+}
+
+// keeps the billing period, slots and capacity chosen on the current version, for the matching components of the new one
+pub(crate) fn carry_over_parameters(
+    current_components: &[SubscriptionComponent],
+    plan_components: &[PriceComponent],
+) -> CreateSubscriptionComponents {
+    let parameterized_components = plan_components
+        .iter()
+        .filter_map(|pc| {
+            let current = current_components.iter().find(|c| {
+                match (c.product_item_id, pc.product_item_id) {
+                    (Some(a), Some(b)) => a == b,
+                    _ => c.price_component_id.is_some() && c.name == pc.name,
+                }
+            })?;
+
+            let parameters = match (&pc.fee, &current.fee) {
+                // a single rate does not need to be parameterized
+                (FeeType::Rate { rates }, SubscriptionFee::Rate { .. }) if rates.len() > 1 => {
+                    ComponentParameters {
+                        initial_slot_count: None,
+                        billing_period: current.period.as_billing_period_opt(),
+                        committed_capacity: None,
+                    }
+                }
+                (FeeType::Slot { .. }, SubscriptionFee::Slot { initial_slots, .. }) => {
+                    ComponentParameters {
+                        initial_slot_count: Some(*initial_slots),
+                        billing_period: current.period.as_billing_period_opt(),
+                        committed_capacity: None,
+                    }
+                }
+                (FeeType::Capacity { .. }, SubscriptionFee::Capacity { included, .. }) => {
+                    ComponentParameters {
+                        initial_slot_count: None,
+                        billing_period: None,
+                        committed_capacity: Some(*included),
+                    }
+                }
+                _ => return None,
+            };
+
+            Some(ComponentParameterization {
+                component_id: pc.id,
+                parameters,
+            })
+        })
+        .collect();
+
+    // components added to this subscription only are kept as is
+    let extra_components = current_components
+        .iter()
+        .filter(|c| c.price_component_id.is_none())
+        .map(|c| ExtraComponent {
+            component: SubscriptionComponentNewInternal {
+                price_component_id: None,
+                product_item_id: c.product_item_id,
+                name: c.name.clone(),
+                period: c.period.clone(),
+                fee: c.fee.clone(),
+                is_override: false,
+            },
+        })
+        .collect();
+
+    CreateSubscriptionComponents {
+        parameterized_components,
+        overridden_components: vec![],
+        extra_components,
+        remove_components: vec![],
+    }
+}
+
+// add-ons are kept on a plan change, so only the components count
+pub(crate) fn calculate_components_mrr(
+    subscription: &SubscriptionDetails,
+    components: &[SubscriptionComponentNewInternal],
+) -> StoreResult<(i64, i64)> {
+    let currency = Currency::from_code(&subscription.currency).ok_or(Report::new(
+        StoreError::InvalidArgument(format!("unknown currency {}", subscription.currency)),
+    ))?;
+
+    let current_mrr = subscription
+        .price_components
+        .iter()
+        .map(|c| calculate_mrr(&c.fee, &c.period, &currency))
+        .sum::<i64>();
+
+    let new_mrr = components
+        .iter()
+        .map(|c| calculate_mrr(&c.fee, &c.period, &currency))
+        .sum::<i64>();
+
+    Ok((current_mrr, new_mrr))
+}
+
+pub(crate) async fn swap_subscription_plan(
+    conn: &mut PgConn,
+    tenant_id: Uuid,
+    subscription_id: Uuid,
+    plan_version_id: Uuid,
+    components: Vec<SubscriptionComponentRowNew>,
+) -> StoreResult<()> {
+    SubscriptionComponentRow::delete_by_subscription_id(conn, &subscription_id)
+        .await
+        .map_err(Into::<Report<StoreError>>::into)?;
+
+    let inserted = SubscriptionComponentRow::insert_subscription_component_batch(
+        conn,
+        components.iter().collect(),
+    )
+    .await
+    .map_err(Into::<Report<StoreError>>::into)?;
+
+    let add_ons: Vec<SubscriptionAddOn> =
+        SubscriptionAddOnRow::list_by_subscription_id(conn, &tenant_id, &subscription_id)
+            .await
+            .map_err(Into::<Report<StoreError>>::into)?
+            .into_iter()
+            .map(TryInto::try_into)
+            .collect::<Result<Vec<_>, _>>()?;
+
+    let components_periods = inserted
+        .into_iter()
+        .map(|c| c.period.into())
+        .collect::<Vec<_>>();
+
+    let period = extract_billing_period(
+        components_periods
+            .iter()
+            .chain(add_ons.iter().map(|a| &a.period)),
+    );
+
+    SubscriptionRow::update_plan_version(
+        conn,
+        subscription_id,
+        tenant_id,
+        plan_version_id,
+        period.into(),
+    )
+    .await
+    .map_err(Into::<Report<StoreError>>::into)
+}
+
+pub(crate) async fn insert_switch_event(
+    conn: &mut PgConn,
+    subscription_id: Uuid,
+    mrr_delta: i64,
+    applies_to: NaiveDate,
+) -> StoreResult<SubscriptionEventRow> {
+    let event = SubscriptionEventRow {
+        id: Uuid::now_v7(),
+        subscription_id,
+        event_type: SubscriptionEventType::Switch.into(),
+        details: None,
+        created_at: chrono::Utc::now().naive_utc(),
+        mrr_delta: Some(mrr_delta),
+        bi_mrr_movement_log_id: None,
+        applies_to,
+    };
+
+    event
+        .insert(conn)
+        .await
+        .map_err(Into::<Report<StoreError>>::into)?;
+
+    Ok(event)
+}
+
+pub(crate) async fn log_against_recurring_invoice(
+    conn: &mut PgConn,
+    subscription_id: Uuid,
+    invoice_date: NaiveDate,
+    event: SubscriptionEventRow,
+) -> StoreResult<()> {
+    let invoice =
+        InvoiceRow::find_recurring_by_subscription_id_and_date(conn, subscription_id, invoice_date)
+            .await
+            .map_err(Into::<Report<StoreError>>::into)?;
+
+    if let Some(invoice) = invoice {
+        let invoice: Invoice = invoice.try_into()?;
+        log_mrr_movements(conn, &invoice, vec![event]).await?;
+    }
+
+    Ok(())
+}
+
+/// A cancelled subscription keeps its plan. A paused one only changes at the end of a period, once resumed,
+/// as its MRR is restored on resume and its paused period cannot be prorated.
+pub(crate) fn validate_plan_change(
+    subscription: &SubscriptionDetails,
+    effective_at: PlanChangeEffectiveAt,
+) -> StoreResult<()> {
+    if subscription.canceled_at.is_some() {
+        return Err(Report::new(StoreError::InvalidArgument(
+            "cannot change the plan of a cancelled subscription".to_string(),
+        )));
+    }
+
+    if subscription.paused_at.is_some() && effective_at == PlanChangeEffectiveAt::Immediate {
+        return Err(Report::new(StoreError::InvalidArgument(
+            "cannot immediately change the plan of a paused subscription".to_string(),
+        )));
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::enums::{BillingPeriodEnum, SubscriptionFeeBillingPeriod};
+    use crate::domain::TermRate;
+    use rust_decimal_macros::dec;
+
+    fn subscription(components: Vec<SubscriptionComponent>) -> SubscriptionDetails {
+        let date = NaiveDate::from_ymd_opt(2024, 1, 1).unwrap();
+        SubscriptionDetails {
+            id: Uuid::now_v7(),
+            tenant_id: Uuid::now_v7(),
+            customer_id: Uuid::now_v7(),
+            plan_version_id: Uuid::now_v7(),
+            customer_external_id: None,
+            billing_start_date: date,
+            billing_end_date: None,
+            billing_day: 1,
+            currency: "EUR".to_string(),
+            net_terms: 0,
+            schedules: vec![],
+            price_components: components,
+            add_ons: vec![],
+            applied_coupons: vec![],
+            metrics: vec![],
+            mrr_cents: 0,
+            version: 1,
+            billing_cycles: None,
+            plan_name: "plan".to_string(),
+            plan_id: Uuid::now_v7(),
+            customer_name: "customer".to_string(),
+            canceled_at: None,
+            invoice_memo: None,
+            invoice_threshold: None,
+            created_at: date.and_time(NaiveTime::MIN),
+            cancellation_reason: None,
+            activated_at: Some(date.and_time(NaiveTime::MIN)),
+            created_by: Uuid::now_v7(),
+            trial_start_date: None,
+            period: BillingPeriodEnum::Monthly,
+            paused_at: None,
+            resume_at: None,
+            minimum_spend: None,
+            spend_cap: None,
+            elapsed_billing_cycles: 0,
+        }
+    }
+
+    fn subscription_component(
+        product_item_id: Uuid,
+        period: SubscriptionFeeBillingPeriod,
+        fee: SubscriptionFee,
+    ) -> SubscriptionComponent {
+        SubscriptionComponent {
+            id: Uuid::now_v7(),
+            price_component_id: Some(Uuid::now_v7()),
+            product_item_id: Some(product_item_id),
+            subscription_id: Uuid::now_v7(),
+            name: "component".to_string(),
+            period,
+            fee,
+        }
+    }
+
+    fn rate_component(
+        product_item_id: Uuid,
+        rates: Vec<(BillingPeriodEnum, i64)>,
+    ) -> PriceComponent {
+        PriceComponent {
+            id: Uuid::now_v7(),
+            name: "platform fee".to_string(),
+            fee: FeeType::Rate {
+                rates: rates
+                    .into_iter()
+                    .map(|(term, price)| TermRate {
+                        term,
+                        price: price.into(),
+                    })
+                    .collect(),
+            },
+            product_item_id: Some(product_item_id),
+        }
+    }
+
+    #[test]
+    fn test_carry_over_parameters() {
+        let product_item_id = Uuid::now_v7();
+        let current = vec![
+            subscription_component(
+                product_item_id,
+                SubscriptionFeeBillingPeriod::Annual,
+                SubscriptionFee::Rate { rate: dec!(1000) },
+            ),
+            // added to the subscription only
+            SubscriptionComponent {
+                price_component_id: None,
+                product_item_id: None,
+                name: "extra".to_string(),
+                ..subscription_component(
+                    Uuid::now_v7(),
+                    SubscriptionFeeBillingPeriod::Monthly,
+                    SubscriptionFee::Rate { rate: dec!(10) },
+                )
+            },
+        ];
+        let plan_components = vec![
+            rate_component(
+                product_item_id,
+                vec![
+                    (BillingPeriodEnum::Monthly, 100),
+                    (BillingPeriodEnum::Annual, 1100),
+                ],
+            ),
+            // no matching component on the subscription
+            rate_component(Uuid::now_v7(), vec![(BillingPeriodEnum::Monthly, 50)]),
+        ];
+
+        let parameters = carry_over_parameters(&current, &plan_components);
+
+        assert_eq!(parameters.parameterized_components.len(), 1);
+        let parameterized = &parameters.parameterized_components[0];
+        assert_eq!(parameterized.component_id, plan_components[0].id);
+        assert_eq!(
+            parameterized.parameters.billing_period,
+            Some(BillingPeriodEnum::Annual)
+        );
+
+        assert_eq!(parameters.extra_components.len(), 1);
+        assert_eq!(parameters.extra_components[0].component.name, "extra");
+    }
+
+    #[test]
+    fn test_plan_migration_report() {
+        let product_item_id = Uuid::now_v7();
+        let subscription = subscription(vec![subscription_component(
+            product_item_id,
+            SubscriptionFeeBillingPeriod::Monthly,
+            SubscriptionFee::Rate { rate: dec!(100) },
+        )]);
+        let plan_version_id = Uuid::now_v7();
+        let price_components = HashMap::from([(
+            plan_version_id,
+            vec![rate_component(
+                product_item_id,
+                vec![
+                    (BillingPeriodEnum::Monthly, 150),
+                    (BillingPeriodEnum::Annual, 1500),
+                ],
+            )],
+        )]);
+        let today = NaiveDate::from_ymd_opt(2024, 3, 10).unwrap();
+
+        let (report, migrated) =
+            plan_migration(&subscription, &price_components, plan_version_id, today);
+
+        assert_eq!(report.error, None);
+        assert_eq!(report.current_mrr, 10000);
+        assert_eq!(report.new_mrr, 15000);
+        assert_eq!(report.effective_date, NaiveDate::from_ymd_opt(2024, 4, 1));
+        let (effective_date, components) = migrated.unwrap();
+        assert_eq!(Some(effective_date), report.effective_date);
+        assert_eq!(components.len(), 1);
+        assert_eq!(components[0].period, SubscriptionFeeBillingPeriod::Monthly);
+    }
+
+    #[test]
+    fn test_plan_migration_report_errors() {
+        let subscription = subscription(vec![]);
+        let plan_version_id = Uuid::now_v7();
+        // the subscription has no billing period to carry over for these rates
+        let price_components = HashMap::from([(
+            plan_version_id,
+            vec![rate_component(
+                Uuid::now_v7(),
+                vec![
+                    (BillingPeriodEnum::Monthly, 150),
+                    (BillingPeriodEnum::Annual, 1500),
+                ],
+            )],
+        )]);
+        let today = NaiveDate::from_ymd_opt(2024, 3, 10).unwrap();
+
+        let (report, migrated) =
+            plan_migration(&subscription, &price_components, plan_version_id, today);
+        assert!(report.error.is_some());
+        assert!(migrated.is_none());
+
+        let subscription = SubscriptionDetails {
+            currency: "XXX".to_string(),
+            ..subscription
+        };
+        let price_components = HashMap::from([(
+            plan_version_id,
+            vec![rate_component(
+                Uuid::now_v7(),
+                vec![(BillingPeriodEnum::Monthly, 150)],
+            )],
+        )]);
+
+        let (report, migrated) =
+            plan_migration(&subscription, &price_components, plan_version_id, today);
+        assert_eq!(
+            report.error,
+            Some("Invalid Argument: unknown currency XXX".to_string())
+        );
+        assert_eq!(report.effective_date, None);
+        assert!(migrated.is_none());
+    }
+
+    #[test]
+    fn test_validate_plan_change_paused() {
+        let paused = SubscriptionDetails {
+            paused_at: Some(
+                NaiveDate::from_ymd_opt(2024, 3, 10)
+                    .unwrap()
+                    .and_time(NaiveTime::MIN),
+            ),
+            ..subscription(vec![])
+        };
+
+        assert!(validate_plan_change(&paused, PlanChangeEffectiveAt::Immediate).is_err());
+        // applied once resumed
+        assert!(validate_plan_change(&paused, PlanChangeEffectiveAt::EndOfBillingPeriod).is_ok());
+
+        let cancelled = SubscriptionDetails {
+            canceled_at: paused.paused_at,
+            paused_at: None,
+            ..subscription(vec![])
+        };
+        assert!(
+            validate_plan_change(&cancelled, PlanChangeEffectiveAt::EndOfBillingPeriod).is_err()
+        );
+        assert!(
+            validate_plan_change(&subscription(vec![]), PlanChangeEffectiveAt::Immediate).is_ok()
+        );
+    }
+}
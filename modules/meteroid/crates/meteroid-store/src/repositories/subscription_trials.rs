@@ -0,0 +1,361 @@
+use crate::domain::enums::{ActionAfterTrialEnum, BillingPeriodEnum, SubscriptionEventType};
+use crate::domain::{
+    BillingConfig, CursorPaginatedVec, CursorPaginationRequest, ExpiredTrial, InvoiceNew,
+    Subscription, SubscriptionComponentNew, SubscriptionDetails, SubscriptionInvoiceCandidate,
+    SubscriptionTrialEnded,
+};
+use crate::errors::StoreError;
+use crate::repositories::invoices::insert_invoice;
+use crate::repositories::invoicing_entities::InvoicingEntityInterface;
+use crate::repositories::subscription_plan_changes::{
+    calculate_components_mrr, carry_over_parameters, insert_switch_event,
+    load_plan_price_components, swap_subscription_plan,
+};
+use crate::repositories::subscriptions::{
+    extract_billing_period, process_create_subscription_components, subscription_to_draft,
+};
+use crate::repositories::{CustomersInterface, SubscriptionInterface};
+use crate::store::{PgConn, Store};
+use crate::StoreResult;
+use chrono::NaiveDate;
+use common_eventbus::Event;
+use diesel_async::scoped_futures::ScopedFutureExt;
+use diesel_models::invoices::InvoiceRow;
+use diesel_models::plan_versions::PlanVersionRow;
+use diesel_models::query::plans::get_plan_names_by_version_ids;
+use diesel_models::subscription_components::SubscriptionComponentRowNew;
+use diesel_models::subscription_events::SubscriptionEventRow;
+use diesel_models::subscriptions::SubscriptionRow;
+use error_stack::Report;
+use uuid::Uuid;
+
+#[async_trait::async_trait]
+pub trait SubscriptionTrialInterface {
+    async fn list_expired_trials(
+        &self,
+        date: NaiveDate,
+        pagination: CursorPaginationRequest,
+    ) -> StoreResult<CursorPaginatedVec<ExpiredTrial>>;
+
+    /// Applies the action after trial of the plan version, returns None if the trial was already processed.
+    async fn end_subscription_trial(
+        &self,
+        tenant_id: Uuid,
+        subscription_id: Uuid,
+    ) -> StoreResult<Option<SubscriptionTrialEnded>>;
+}
+
+#[async_trait::async_trait]
+impl SubscriptionTrialInterface for Store {
+    async fn list_expired_trials(
+        &self,
+        date: NaiveDate,
+        pagination: CursorPaginationRequest,
+    ) -> StoreResult<CursorPaginatedVec<ExpiredTrial>> {
+        let mut conn = self.get_conn().await?;
+
+        let rows = SubscriptionRow::list_expired_trials(&mut conn, date, pagination.into())
+            .await
+            .map_err(Into::<Report<StoreError>>::into)?;
+
+        Ok(CursorPaginatedVec {
+            items: rows.items.into_iter().map(Into::into).collect(),
+            next_cursor: rows.next_cursor,
+        })
+    }
+
+    async fn end_subscription_trial(
+        &self,
+        tenant_id: Uuid,
+        subscription_id: Uuid,
+    ) -> StoreResult<Option<SubscriptionTrialEnded>> {
+        let subscription = self
+            .get_subscription_details(tenant_id, subscription_id)
+            .await?;
+
+        let mut conn = self.get_conn().await?;
+
+        let plan_version = PlanVersionRow::find_by_id_and_tenant_id(
+            &mut conn,
+            subscription.plan_version_id,
+            tenant_id,
+        )
+        .await
+        .map_err(Into::<Report<StoreError>>::into)?;
+
+        let action_after_trial = ActionAfterTrialEnum::resolve(
+            plan_version.action_after_trial.clone().map(Into::into),
+            plan_version.downgrade_plan_id,
+        );
+
+        let (plan_version, components, mrr_delta) =
+            match (&action_after_trial, plan_version.downgrade_plan_id) {
+                (ActionAfterTrialEnum::Downgrade, Some(downgrade_plan_id)) => {
+                    let target = PlanVersionRow::find_latest_by_plan_id_and_tenant_id(
+                        &mut conn,
+                        downgrade_plan_id,
+                        tenant_id,
+                        Some(false),
+                    )
+                    .await
+                    .map_err(Into::<Report<StoreError>>::into)?
+                    .ok_or(Report::new(StoreError::ValueNotFound(
+                        "no published version of the downgrade plan".to_string(),
+                    )))?;
+
+                    if target.currency != subscription.currency {
+                        return Err(Report::new(StoreError::InvalidArgument(
+                            "the downgrade plan currency does not match the subscription currency"
+                                .to_string(),
+                        )));
+                    }
+
+                    let price_components = load_plan_price_components(&mut conn, target.id).await?;
+
+                    let parameters = carry_over_parameters(
+                        &subscription.price_components,
+                        price_components
+                            .get(&target.id)
+                            .map(Vec::as_slice)
+                            .unwrap_or_default(),
+                    );
+
+                    let components = process_create_subscription_components(
+                        &Some(parameters),
+                        &price_components,
+                        &target.id,
+                    )?;
+
+                    let (current_mrr, new_mrr) =
+                        calculate_components_mrr(&subscription, &components)?;
+
+                    (target, components, new_mrr - current_mrr)
+                }
+                _ => (plan_version, vec![], 0),
+            };
+
+        let period = if components.is_empty() {
+            subscription.period.clone()
+        } else {
+            extract_billing_period(
+                components
+                    .iter()
+                    .map(|c| &c.period)
+                    .chain(subscription.add_ons.iter().map(|a| &a.period)),
+            )
+        };
+
+        let draft = match action_after_trial {
+            ActionAfterTrialEnum::Block => None,
+            _ => {
+                self.build_first_invoice(&mut conn, &subscription, &plan_version, period)
+                    .await?
+            }
+        };
+
+        let plan_version_id = plan_version.id;
+        let action = action_after_trial.clone();
+
+        let ended = self
+            .transaction(|conn| {
+                async move {
+                    SubscriptionRow::lock_subscription_for_update(conn, subscription_id)
+                        .await
+                        .map_err(Into::<Report<StoreError>>::into)?;
+
+                    let locked: Subscription =
+                        SubscriptionRow::get_subscription_by_id(conn, &tenant_id, &subscription_id)
+                            .await
+                            .map_err(Into::<Report<StoreError>>::into)?
+                            .into();
+
+                    // processed concurrently, or cancelled in the meantime
+                    if locked.trial_ended_at.is_some() || locked.canceled_at.is_some() {
+                        return Ok(None);
+                    }
+
+                    let trial_end = locked.billing_start_date;
+
+                    match action {
+                        ActionAfterTrialEnum::Block => {
+                            SubscriptionRow::deactivate_subscription(
+                                conn,
+                                subscription_id,
+                                tenant_id,
+                            )
+                            .await
+                            .map_err(Into::<Report<StoreError>>::into)?;
+                        }
+                        ActionAfterTrialEnum::Charge => {
+                            if locked.activated_at.is_none() {
+                                SubscriptionRow::activate_subscription(
+                                    conn,
+                                    subscription_id,
+                                    tenant_id,
+                                )
+                                .await
+                                .map_err(Into::<Report<StoreError>>::into)?;
+
+                                insert_activated_event(conn, subscription_id, trial_end).await?;
+                            }
+                        }
+                        ActionAfterTrialEnum::Downgrade => {
+                            let insertable_components = components
+                                .into_iter()
+                                .map(|internal| {
+                                    SubscriptionComponentNew {
+                                        subscription_id,
+                                        internal,
+                                    }
+                                    .try_into()
+                                })
+                                .collect::<Result<Vec<SubscriptionComponentRowNew>, _>>()?;
+
+                            swap_subscription_plan(
+                                conn,
+                                tenant_id,
+                                subscription_id,
+                                plan_version_id,
+                                insertable_components,
+                            )
+                            .await?;
+
+                            SubscriptionRow::activate_subscription(
+                                conn,
+                                subscription_id,
+                                tenant_id,
+                            )
+                            .await
+                            .map_err(Into::<Report<StoreError>>::into)?;
+
+                            // logged with the other events of the period, when the first invoice is inserted
+                            insert_switch_event(conn, subscription_id, mrr_delta, trial_end)
+                                .await?;
+                        }
+                    }
+
+                    SubscriptionRow::end_trial(conn, subscription_id, tenant_id)
+                        .await
+                        .map_err(Into::<Report<StoreError>>::into)?;
+
+                    let invoice_id = match draft {
+                        Some(draft) => {
+                            let existing = InvoiceRow::find_recurring_by_subscription_id_and_date(
+                                conn,
+                                subscription_id,
+                                draft.invoice_date,
+                            )
+                            .await
+                            .map_err(Into::<Report<StoreError>>::into)?;
+
+                            match existing {
+                                Some(_) => None,
+                                None => Some(insert_invoice(conn, draft).await?.id),
+                            }
+                        }
+                        None => None,
+                    };
+
+                    Ok(Some(SubscriptionTrialEnded {
+                        subscription_id,
+                        tenant_id,
+                        action_after_trial: action,
+                        plan_version_id,
+                        mrr_delta,
+                        invoice_id,
+                    }))
+                }
+                .scope_boxed()
+            })
+            .await?;
+
+        if let Some(ended) = &ended {
+            if let Some(invoice_id) = ended.invoice_id {
+                let _ = self
+                    .eventbus
+                    .publish(Event::invoice_created(invoice_id, tenant_id))
+                    .await;
+            }
+
+            let _ = self
+                .eventbus
+                .publish(Event::subscription_trial_ended(
+                    subscription_id,
+                    tenant_id,
+                    action_after_trial.as_webhook_str().to_string(),
+                ))
+                .await;
+        }
+
+        Ok(ended)
+    }
+}
+
+impl Store {
+    // the first invoice is only drafted for manual billing, as on subscription creation
+    pub(crate) async fn build_first_invoice(
+        &self,
+        conn: &mut PgConn,
+        subscription: &SubscriptionDetails,
+        plan_version: &PlanVersionRow,
+        period: BillingPeriodEnum,
+    ) -> StoreResult<Option<InvoiceNew>> {
+        let customer = self
+            .find_customer_by_id(subscription.customer_id, subscription.tenant_id)
+            .await?;
+
+        if customer.billing_config != BillingConfig::Manual {
+            return Ok(None);
+        }
+
+        let invoicing_entity = self
+            .get_invoicing_entity(subscription.tenant_id, Some(customer.invoicing_entity_id))
+            .await?;
+
+        let plan_name = get_plan_names_by_version_ids(conn, vec![plan_version.id])
+            .await
+            .map_err(Into::<Report<StoreError>>::into)?
+            .remove(&plan_version.id)
+            .unwrap_or_else(|| subscription.plan_name.clone());
+
+        let candidate = SubscriptionInvoiceCandidate {
+            id: subscription.id,
+            tenant_id: subscription.tenant_id,
+            customer_id: subscription.customer_id,
+            plan_version_id: plan_version.id,
+            plan_name,
+            billing_start_date: subscription.billing_start_date,
+            billing_end_date: subscription.billing_end_date,
+            billing_day: subscription.billing_day,
+            activated_at: subscription.activated_at,
+            canceled_at: subscription.canceled_at,
+            currency: subscription.currency.clone(),
+            net_terms: plan_version.net_terms,
+            period,
+        };
+
+        subscription_to_draft(&candidate, &customer, &invoicing_entity).map(Some)
+    }
+}
+
+// the MRR was already accounted for by the creation event
+async fn insert_activated_event(
+    conn: &mut PgConn,
+    subscription_id: Uuid,
+    applies_to: NaiveDate,
+) -> StoreResult<()> {
+    SubscriptionEventRow {
+        id: Uuid::now_v7(),
+        subscription_id,
+        event_type: SubscriptionEventType::Activated.into(),
+        details: None,
+        created_at: chrono::Utc::now().naive_utc(),
+        mrr_delta: None,
+        bi_mrr_movement_log_id: None,
+        applies_to,
+    }
+    .insert(conn)
+    .await
+    .map(|_| ())
+    .map_err(Into::<Report<StoreError>>::into)
+}
@@ -1,3 +1,4 @@
+use crate::compute::calculate_billing_cycles_end;
 use crate::domain::enums::{
     BillingPeriodEnum, InvoiceStatusEnum, InvoiceType, InvoicingProviderEnum,
     SubscriptionEventType, SubscriptionFeeBillingPeriod,
@@ -9,11 +10,10 @@ use crate::domain::{
     InvoicingEntity, PaginatedVec, PaginationRequest, PriceComponent, Schedule, Subscription,
     SubscriptionAddOnCustomization, SubscriptionAddOnNew, SubscriptionAddOnNewInternal,
     SubscriptionComponent, SubscriptionComponentNew, SubscriptionComponentNewInternal,
-    SubscriptionDetails, SubscriptionFee, SubscriptionInvoiceCandidate, SubscriptionNew,
+    SubscriptionDetails, SubscriptionFee, SubscriptionInvoiceCandidate,
 };
 use crate::errors::StoreError;
 use crate::store::{PgConn, Store};
-use crate::utils::decimals::ToSubunit;
 use crate::{domain, StoreResult};
 use chrono::{NaiveDate, NaiveTime};
 use diesel_async::scoped_futures::ScopedFutureExt;
@@ -24,14 +24,14 @@ use itertools::Itertools;
 use std::collections::HashMap;
 use uuid::Uuid;
 
-use crate::constants::Currencies;
 use crate::domain::add_ons::AddOn;
-use crate::domain::coupons::{Coupon, CouponDiscount};
+use crate::domain::coupons::{Coupon, CouponAppliesTo, CouponDiscount, PromotionCode};
 use crate::domain::subscription_add_ons::SubscriptionAddOn;
 use crate::repositories::historical_rates::HistoricalRatesInterface;
 use crate::repositories::invoicing_entities::InvoicingEntityInterface;
 use crate::repositories::{CustomersInterface, InvoiceInterface};
 use crate::utils::local_id::{IdType, LocalId};
+use common_domain::money::{Currency, Rounding};
 use common_eventbus::Event;
 use diesel_models::add_ons::AddOnRow;
 use diesel_models::applied_coupons::{
@@ -39,7 +39,9 @@ use diesel_models::applied_coupons::{
 };
 use diesel_models::billable_metrics::BillableMetricRow;
 use diesel_models::coupons::CouponRow;
+use diesel_models::plan_versions::PlanVersionRow;
 use diesel_models::price_components::PriceComponentRow;
+use diesel_models::promotion_codes::PromotionCodeRow;
 use diesel_models::query::plans::get_plan_names_by_version_ids;
 use diesel_models::schedules::ScheduleRow;
 use diesel_models::slot_transactions::SlotTransactionRow;
@@ -109,10 +111,10 @@ pub trait SubscriptionInterface {
 // TODO we need to always pass the tenant id and match it with the resource, if not within the resource.
 // and even within it's probably still unsafe no ? Ex: creating components against a wrong subscription within a different tenant
 
-fn calculate_mrr(
+pub(crate) fn calculate_mrr(
     fee: &SubscriptionFee,
     period: &SubscriptionFeeBillingPeriod,
-    precision: u8,
+    currency: &Currency,
 ) -> i64 {
     let mut total_cents = 0;
 
@@ -120,22 +122,30 @@ fn calculate_mrr(
 
     match fee {
         SubscriptionFee::Rate { rate } => {
-            total_cents = rate.to_subunit_opt(precision).unwrap_or(0);
+            total_cents = currency
+                .to_minor(*rate, Rounding::HalfAwayFromZero)
+                .unwrap_or(0);
         }
         SubscriptionFee::Recurring { quantity, rate, .. } => {
             let total = rate * Decimal::from(*quantity);
-            total_cents = total.to_subunit_opt(precision).unwrap_or(0);
+            total_cents = currency
+                .to_minor(total, Rounding::HalfAwayFromZero)
+                .unwrap_or(0);
         }
         SubscriptionFee::Capacity { rate, .. } => {
-            total_cents = rate.to_subunit_opt(precision).unwrap_or(0);
+            total_cents = currency
+                .to_minor(*rate, Rounding::HalfAwayFromZero)
+                .unwrap_or(0);
         }
         SubscriptionFee::Slot {
             initial_slots,
             unit_rate,
             ..
         } => {
-            total_cents =
-                (*initial_slots as i64) * unit_rate.to_subunit_opt(precision).unwrap_or(0);
+            total_cents = (*initial_slots as i64)
+                * currency
+                    .to_minor(*unit_rate, Rounding::HalfAwayFromZero)
+                    .unwrap_or(0);
         }
         SubscriptionFee::OneTime { .. } | SubscriptionFee::Usage { .. } => {
             // doesn't count as mrr
@@ -293,6 +303,22 @@ impl SubscriptionInterface for Store {
         .map_err(Into::<Report<StoreError>>::into)
         .and_then(|x| x.into_iter().map(TryInto::try_into).collect())?;
 
+        let all_promotion_codes: Vec<PromotionCode> = PromotionCodeRow::list_by_codes(
+            &mut conn,
+            tenant_id,
+            &batch
+                .iter()
+                .filter_map(|x| x.coupons.as_ref())
+                .flat_map(|x| x.promotion_codes.iter().cloned())
+                .unique()
+                .collect::<Vec<_>>(),
+        )
+        .await
+        .map_err(Into::<Report<StoreError>>::into)?
+        .into_iter()
+        .map(Into::into)
+        .collect();
+
         let all_coupons: Vec<Coupon> = CouponRow::list_by_ids(
             &mut conn,
             &batch
@@ -300,6 +326,7 @@ impl SubscriptionInterface for Store {
                 .filter_map(|x| x.coupons.as_ref())
                 .flat_map(|x| &x.coupons)
                 .map(|x| x.coupon_id)
+                .chain(all_promotion_codes.iter().map(|x| x.coupon_id))
                 .unique()
                 .collect::<Vec<_>>(),
             &tenant_id,
@@ -352,13 +379,13 @@ impl SubscriptionInterface for Store {
 
             let subscription_currency = &subscription.currency.clone();
 
-            let precision = Currencies::resolve_currency_precision(subscription_currency)
-                .ok_or(StoreError::InsertError)?;
+            let currency =
+                Currency::from_code(subscription_currency).ok_or(StoreError::InsertError)?;
 
             let insertable_subscription_components = process_create_subscription_components(
                 &price_components,
                 &price_components_by_plan_version,
-                &subscription,
+                &subscription.plan_version_id,
             )?;
 
             let insertable_subscription_add_ons =
@@ -366,8 +393,10 @@ impl SubscriptionInterface for Store {
 
             // at this point we can know the period
             let period = extract_billing_period(
-                &insertable_subscription_components,
-                &insertable_subscription_add_ons,
+                insertable_subscription_components
+                    .iter()
+                    .map(|x| &x.period)
+                    .chain(insertable_subscription_add_ons.iter().map(|x| &x.period)),
             );
 
             let should_activate = customer.billing_config != BillingConfig::Manual;
@@ -378,23 +407,34 @@ impl SubscriptionInterface for Store {
                 &insertable_subscription,
                 &coupons,
                 &all_coupons,
+                &all_promotion_codes,
             )?;
 
             let cmrr = insertable_subscription_components
                 .iter()
-                .map(|c| calculate_mrr(&c.fee, &c.period, precision))
+                .map(|c| calculate_mrr(&c.fee, &c.period, &currency))
                 .sum::<i64>();
 
             let ao_mrr = insertable_subscription_add_ons
                 .iter()
-                .map(|c| calculate_mrr(&c.fee, &c.period, precision))
+                .map(|c| calculate_mrr(&c.fee, &c.period, &currency))
                 .sum::<i64>();
 
             let mrr_delta = cmrr + ao_mrr;
 
+            let subscription_coupons = all_coupons
+                .iter()
+                .filter(|c| {
+                    insertable_subscription_coupons
+                        .iter()
+                        .any(|x| x.coupon_id == c.id)
+                })
+                .cloned()
+                .collect::<Vec<_>>();
+
             let mrr_delta = calculate_coupons_discount(
                 self,
-                &all_coupons,
+                &subscription_coupons,
                 subscription_currency,
                 Decimal::from_i64(mrr_delta).unwrap_or(Decimal::ZERO),
             )
@@ -656,6 +696,7 @@ impl SubscriptionInterface for Store {
             metrics: billable_metrics,
             mrr_cents: subscription.mrr_cents,
             version: subscription.version,
+            billing_cycles: subscription.billing_cycles,
             plan_name: subscription.plan_name,
             plan_id: subscription.plan_id,
             customer_name: subscription.customer_name,
@@ -669,6 +710,11 @@ impl SubscriptionInterface for Store {
             created_by: subscription.created_by,
             trial_start_date: subscription.trial_start_date,
             period: subscription.period,
+            paused_at: subscription.paused_at,
+            resume_at: subscription.resume_at,
+            minimum_spend: subscription.minimum_spend,
+            spend_cap: subscription.spend_cap,
+            elapsed_billing_cycles: subscription.elapsed_billing_cycles,
         })
     }
 
@@ -828,12 +874,31 @@ impl SubscriptionInterface for Store {
         .await
         .map_err(Into::<Report<StoreError>>::into)?;
 
+        // no more invoice once the last billing cycle of the plan is billed. The subscription is
+        // ended at the end of its last cycle, so that it is no longer a candidate
+        let mut items: Vec<SubscriptionInvoiceCandidate> =
+            Vec::with_capacity(db_subscriptions.items.len());
+        for s in db_subscriptions.items {
+            let cycles_end = calculate_billing_cycles_end(
+                s.subscription.billing_start_date,
+                s.subscription.billing_day as u32,
+                s.plan_version.billing_cycles,
+                s.subscription.elapsed_billing_cycles,
+                &s.subscription.period.clone().into(),
+            );
+
+            match cycles_end {
+                Some(end) if end <= date => {
+                    SubscriptionRow::end_billing_cycles(&mut conn, s.subscription.id, end)
+                        .await
+                        .map_err(Into::<Report<StoreError>>::into)?;
+                }
+                _ => items.push(s.into()),
+            }
+        }
+
         let res: CursorPaginatedVec<SubscriptionInvoiceCandidate> = CursorPaginatedVec {
-            items: db_subscriptions
-                .items
-                .into_iter()
-                .map(|s| s.into())
-                .collect(),
+            items,
             next_cursor: db_subscriptions.next_cursor,
         };
 
@@ -841,7 +906,7 @@ impl SubscriptionInterface for Store {
     }
 }
 
-fn process_create_subscription_add_ons(
+pub(crate) fn process_create_subscription_add_ons(
     create: &Option<CreateSubscriptionAddOns>,
     add_ons: &[AddOn],
 ) -> Result<Vec<SubscriptionAddOnNewInternal>, StoreError> {
@@ -895,13 +960,31 @@ fn process_create_subscription_coupons(
     subscription: &SubscriptionRowNew,
     create: &Option<CreateSubscriptionCoupons>,
     coupons: &[Coupon],
+    promotion_codes: &[PromotionCode],
 ) -> Result<Vec<AppliedCouponRowNew>, StoreError> {
     let mut processed_coupons = Vec::new();
     if let Some(create) = create {
-        for cs_coupon in &create.coupons {
-            let coupon = coupons.iter().find(|x| x.id == cs_coupon.coupon_id).ok_or(
-                StoreError::ValueNotFound(format!("coupon {} not found", cs_coupon.coupon_id)),
-            )?;
+        let redeemed = create
+            .coupons
+            .iter()
+            .map(|cs_coupon| Ok((cs_coupon.coupon_id, None)))
+            .chain(create.promotion_codes.iter().map(|code| {
+                let promotion_code = promotion_codes.iter().find(|x| &x.code == code).ok_or(
+                    StoreError::ValueNotFound(format!("promotion code {} not found", code)),
+                )?;
+                Ok((promotion_code.coupon_id, Some(promotion_code.id)))
+            }))
+            .collect::<Result<Vec<_>, StoreError>>()?;
+
+        for (coupon_id, promotion_code_id) in redeemed {
+            let coupon =
+                coupons
+                    .iter()
+                    .find(|x| x.id == coupon_id)
+                    .ok_or(StoreError::ValueNotFound(format!(
+                        "coupon {} not found",
+                        coupon_id
+                    )))?;
 
             processed_coupons.push(AppliedCouponRowNew {
                 id: Uuid::now_v7(),
@@ -912,6 +995,7 @@ fn process_create_subscription_coupons(
                 applied_amount: None,
                 applied_count: None,
                 last_applied_at: None,
+                promotion_code_id,
             });
         }
     }
@@ -957,6 +1041,21 @@ async fn apply_coupons(
             .map_err(Into::<DatabaseErrorContainer>::into)?;
     }
 
+    let subscriptions_by_promotion_code: HashMap<Uuid, usize> = subscription_coupons
+        .iter()
+        .filter_map(|x| x.promotion_code_id)
+        .counts();
+
+    for (promotion_code_id, subscriptions_count) in subscriptions_by_promotion_code {
+        PromotionCodeRow::inc_redemption_count(
+            tx_conn,
+            promotion_code_id,
+            subscriptions_count as i32,
+        )
+        .await
+        .map_err(Into::<DatabaseErrorContainer>::into)?;
+    }
+
     Ok(())
 }
 
@@ -1056,6 +1155,90 @@ async fn validate_coupons(
         }
     }
 
+    // check the coupons restricted to some plans
+    let restricted_coupons = coupons
+        .iter()
+        .filter_map(|c| {
+            CouponAppliesTo::from_json(c.applies_to.clone())
+                .ok()
+                .filter(|a| !a.plan_ids.is_empty() || !a.plan_version_ids.is_empty())
+                .map(|a| (c, a))
+        })
+        .collect::<Vec<_>>();
+
+    if !restricted_coupons.is_empty() {
+        let mut plan_ids_by_version: HashMap<Uuid, Uuid> = HashMap::new();
+
+        for applied_coupon in subscription_coupons {
+            let Some((coupon, applies_to)) = restricted_coupons
+                .iter()
+                .find(|(c, _)| c.id == applied_coupon.coupon_id)
+            else {
+                continue;
+            };
+
+            let subscription = subscriptions
+                .iter()
+                .find(|x| x.id == applied_coupon.subscription_id)
+                .ok_or(report!(DatabaseError::ValidationError(format!(
+                    "Subscription {} not found",
+                    applied_coupon.subscription_id
+                ))))?;
+
+            let plan_id = match plan_ids_by_version.get(&subscription.plan_version_id) {
+                Some(plan_id) => *plan_id,
+                None => {
+                    let plan_id = PlanVersionRow::find_by_id_and_tenant_id(
+                        tx_conn,
+                        subscription.plan_version_id,
+                        tenant_id,
+                    )
+                    .await?
+                    .plan_id;
+                    plan_ids_by_version.insert(subscription.plan_version_id, plan_id);
+                    plan_id
+                }
+            };
+
+            if !applies_to.applies_to_plan(plan_id, subscription.plan_version_id) {
+                return Err(report!(DatabaseError::ValidationError(format!(
+                    "coupon {} does not apply to the plan of the subscription",
+                    coupon.code
+                )))
+                .into());
+            }
+        }
+    }
+
+    // check the limits of the redeemed promotion codes
+    let promotion_code_ids = subscription_coupons
+        .iter()
+        .filter_map(|x| x.promotion_code_id)
+        .unique()
+        .collect::<Vec<_>>();
+
+    if !promotion_code_ids.is_empty() {
+        let promotion_codes: Vec<PromotionCode> =
+            PromotionCodeRow::list_by_ids_for_update(tx_conn, tenant_id, &promotion_code_ids)
+                .await?
+                .into_iter()
+                .map(Into::into)
+                .collect();
+
+        for promotion_code in promotion_codes {
+            let redemptions = subscription_coupons
+                .iter()
+                .filter(|x| x.promotion_code_id == Some(promotion_code.id))
+                .collect::<Vec<_>>();
+
+            for redemption in redemptions.iter() {
+                promotion_code
+                    .validate_redemption(redemption.customer_id, redemptions.len() as i32, now)
+                    .map_err(|e| report!(DatabaseError::ValidationError(e)))?;
+            }
+        }
+    }
+
     // check non-reusable coupons
     let non_reusable_coupons = coupons.iter().filter(|x| !x.reusable).collect::<Vec<_>>();
     if !non_reusable_coupons.is_empty() {
@@ -1094,7 +1277,8 @@ async fn calculate_coupons_discount(
     let mut total = amount;
 
     for coupon in coupons {
-        if !coupon.is_infinite() {
+        // the coupons restricted to some components or add-ons are left out of the MRR
+        if !coupon.is_infinite() || coupon.applies_to.restricts_lines() {
             continue;
         }
 
@@ -1134,23 +1318,20 @@ async fn calculate_coupons_discount(
     Ok(total)
 }
 
-fn extract_billing_period(
-    components: &[SubscriptionComponentNewInternal],
-    add_ons: &[SubscriptionAddOnNewInternal],
+// the shortest period of the components and add-ons
+pub(crate) fn extract_billing_period<'a>(
+    periods: impl Iterator<Item = &'a SubscriptionFeeBillingPeriod>,
 ) -> BillingPeriodEnum {
-    components
-        .iter()
-        .map(|x| &x.period)
-        .chain(add_ons.iter().map(|x| &x.period))
+    periods
         .filter_map(|x| x.as_billing_period_opt())
         .min()
         .unwrap_or(BillingPeriodEnum::Monthly)
 }
 
-fn process_create_subscription_components(
+pub(crate) fn process_create_subscription_components(
     param: &Option<CreateSubscriptionComponents>,
     map: &HashMap<Uuid, Vec<PriceComponent>>,
-    sub: &SubscriptionNew,
+    plan_version_id: &Uuid,
 ) -> Result<Vec<SubscriptionComponentNewInternal>, StoreError> {
     let mut processed_components = Vec::new();
 
@@ -1167,7 +1348,7 @@ fn process_create_subscription_components(
         };
 
     let binding = vec![];
-    let plan_price_components = map.get(&sub.plan_version_id).unwrap_or(&binding);
+    let plan_price_components = map.get(plan_version_id).unwrap_or(&binding);
 
     let mut removed_components = Vec::new();
 
@@ -1238,7 +1419,10 @@ fn process_create_subscription_components(
 }
 
 impl SubscriptionDetails {
-    fn calculate_cancellable_end_of_period_date(&self, now: NaiveDate) -> Option<NaiveDate> {
+    pub(crate) fn calculate_cancellable_end_of_period_date(
+        &self,
+        now: NaiveDate,
+    ) -> Option<NaiveDate> {
         // to calculate billing period :
         // if there is a commitment, use that commitment (currently no commitment so let's ignore)
         // else, we take the longest period from the main components (rate/slots/capacity), as that's what the user has already paid
@@ -1315,8 +1499,11 @@ pub fn subscription_to_draft(
         finalized_at: None,
         subtotal: 0,
         subtotal_recurring: 0,
-        tax_rate: 0,
+        // resolved with the lines, when the draft is refreshed
+        tax_rate: Decimal::ZERO,
         tax_amount: 0,
+        tax_breakdown: vec![],
+        coupons: vec![],
         total: 0,
         amount_due: 0,
         net_terms: subscription.net_terms,
@@ -1330,7 +1517,7 @@ pub fn subscription_to_draft(
             id: subscription.customer_id,
             name: customer.name.clone(), // TODO
             billing_address: customer.billing_address.clone(),
-            vat_number: None,
+            vat_number: customer.vat_number.clone(),
             email: customer.email.clone(),
             alias: customer.alias.clone(),
             snapshot_at: chrono::Utc::now().naive_utc(),
@@ -1342,6 +1529,7 @@ pub fn subscription_to_draft(
             address: invoicing_entity.address(),
             snapshot_at: chrono::Utc::now().naive_utc(),
         },
+        replaces_invoice_id: None,
     };
 
     Ok(invoice)
@@ -0,0 +1,105 @@
+use crate::domain::taxes::{ResolvedTax, TaxRate, TaxRateNew};
+use crate::domain::Customer;
+use crate::errors::StoreError;
+use crate::store::{PgConn, Store, StoreInternal};
+use crate::StoreResult;
+use diesel_models::invoicing_entities::InvoicingEntityRow;
+use diesel_models::tax_rates::{TaxRateRow, TaxRateRowNew};
+use error_stack::Report;
+use rust_decimal::Decimal;
+use uuid::Uuid;
+
+#[async_trait::async_trait]
+pub trait TaxRateInterface {
+    async fn list_tax_rates(&self, tenant_id: Uuid) -> StoreResult<Vec<TaxRate>>;
+    async fn create_tax_rate(&self, tax_rate: TaxRateNew) -> StoreResult<TaxRate>;
+    async fn archive_tax_rate(&self, id: Uuid, tenant_id: Uuid) -> StoreResult<()>;
+    async fn resolve_customer_tax(&self, customer: &Customer) -> StoreResult<ResolvedTax>;
+}
+
+#[async_trait::async_trait]
+impl TaxRateInterface for Store {
+    async fn list_tax_rates(&self, tenant_id: Uuid) -> StoreResult<Vec<TaxRate>> {
+        let mut conn = self.get_conn().await?;
+
+        TaxRateRow::list_by_tenant_id(&mut conn, tenant_id)
+            .await
+            .map_err(Into::<Report<StoreError>>::into)
+            .map(|x| x.into_iter().map(Into::into).collect())
+    }
+
+    async fn create_tax_rate(&self, tax_rate: TaxRateNew) -> StoreResult<TaxRate> {
+        if tax_rate.rate < Decimal::ZERO || tax_rate.rate > Decimal::ONE_HUNDRED {
+            return Err(StoreError::InvalidArgument(
+                "tax rate must be between 0 and 100".to_string(),
+            )
+            .into());
+        }
+
+        let mut conn = self.get_conn().await?;
+
+        let row: TaxRateRowNew = TaxRateNew {
+            country: tax_rate.country.to_uppercase(),
+            ..tax_rate
+        }
+        .into();
+
+        row.insert(&mut conn)
+            .await
+            .map_err(Into::<Report<StoreError>>::into)
+            .map(Into::into)
+    }
+
+    async fn archive_tax_rate(&self, id: Uuid, tenant_id: Uuid) -> StoreResult<()> {
+        let mut conn = self.get_conn().await?;
+
+        TaxRateRow::archive(&mut conn, id, tenant_id)
+            .await
+            .map_err(Into::<Report<StoreError>>::into)
+            .map(|_| ())
+    }
+
+    async fn resolve_customer_tax(&self, customer: &Customer) -> StoreResult<ResolvedTax> {
+        let mut conn = self.get_conn().await?;
+
+        self.internal
+            .resolve_customer_tax(&mut conn, customer)
+            .await
+    }
+}
+
+impl StoreInternal {
+    pub async fn resolve_customer_tax(
+        &self,
+        conn: &mut PgConn,
+        customer: &Customer,
+    ) -> StoreResult<ResolvedTax> {
+        let invoicing_entity = InvoicingEntityRow::get_invoicing_entity_by_id_and_tenant(
+            conn,
+            &customer.invoicing_entity_id,
+            &customer.tenant_id,
+        )
+        .await
+        .map_err(Into::<Report<StoreError>>::into)?;
+
+        let customer_country = ResolvedTax::customer_country(
+            &invoicing_entity.country,
+            customer.billing_address.as_ref(),
+        );
+
+        let rates: Vec<TaxRate> =
+            TaxRateRow::list_by_country(conn, customer.tenant_id, &customer_country)
+                .await
+                .map_err(Into::<Report<StoreError>>::into)?
+                .into_iter()
+                .map(Into::into)
+                .collect();
+
+        Ok(ResolvedTax::resolve(
+            &invoicing_entity.country,
+            customer.tax_status,
+            customer.billing_address.as_ref(),
+            &rates,
+        ))
+    }
+}
@@ -0,0 +1,427 @@
+use crate::compute::InvoiceLineInterface;
+use crate::domain::add_ons::AddOn;
+use crate::domain::subscription_add_ons::SubscriptionAddOn;
+use crate::domain::{
+    applicable_credits, BillableMetric, InvoiceTotals, InvoiceTotalsParams, Schedule,
+    SubscriptionComponent, SubscriptionDetails, UpcomingInvoice, UpcomingInvoiceChanges,
+    UpcomingPlanVersion,
+};
+use crate::errors::StoreError;
+use crate::repositories::credit_grants::list_available_credit_grants;
+use crate::repositories::subscription_plan_changes::load_plan_price_components;
+use crate::repositories::subscriptions::{
+    extract_billing_period, process_create_subscription_add_ons,
+    process_create_subscription_components,
+};
+use crate::repositories::taxes::TaxRateInterface;
+use crate::repositories::{CustomersInterface, SubscriptionInterface};
+use crate::store::{PgConn, Store};
+use crate::StoreResult;
+use chrono::NaiveDate;
+use diesel_models::add_ons::AddOnRow;
+use diesel_models::billable_metrics::BillableMetricRow;
+use diesel_models::plan_versions::PlanVersionRow;
+use diesel_models::schedules::ScheduleRow;
+use error_stack::Report;
+use itertools::Itertools;
+use uuid::Uuid;
+
+#[async_trait::async_trait]
+pub trait UpcomingInvoiceInterface {
+    /// Computes the invoice of the subscription at `invoice_date`, by default the end of the current period,
+    /// with the hypothetical `changes` applied. Nothing is persisted.
+    async fn get_upcoming_invoice(
+        &self,
+        tenant_id: Uuid,
+        subscription_id: Uuid,
+        invoice_date: Option<NaiveDate>,
+        changes: UpcomingInvoiceChanges,
+    ) -> StoreResult<UpcomingInvoice>;
+}
+
+#[async_trait::async_trait]
+impl UpcomingInvoiceInterface for Store {
+    async fn get_upcoming_invoice(
+        &self,
+        tenant_id: Uuid,
+        subscription_id: Uuid,
+        invoice_date: Option<NaiveDate>,
+        changes: UpcomingInvoiceChanges,
+    ) -> StoreResult<UpcomingInvoice> {
+        let subscription = self
+            .get_subscription_details(tenant_id, subscription_id)
+            .await?;
+
+        let today = chrono::Utc::now().date_naive();
+
+        if invoice_date.is_some_and(|date| date < today) {
+            return Err(Report::new(StoreError::InvalidArgument(
+                "the invoice date must not be in the past".to_string(),
+            )));
+        }
+
+        // the default date follows the changes, as a new plan version can move the start of billing
+        let subscription = self
+            .apply_upcoming_invoice_changes(subscription, &changes, today)
+            .await?;
+
+        let invoice_date = match invoice_date {
+            Some(date) => date,
+            None if today <= subscription.billing_start_date => subscription.billing_start_date,
+            None => subscription
+                .calculate_cancellable_end_of_period_date(today)
+                .ok_or(Report::new(StoreError::InvalidArgument(
+                    "cannot resolve the end of the billing period".to_string(),
+                )))?,
+        };
+
+        let lines = self
+            .compute_dated_invoice_lines_with_slots(&invoice_date, &subscription, &changes.slots)
+            .await?;
+
+        let customer = self
+            .find_customer_by_id(subscription.customer_id, tenant_id)
+            .await?;
+
+        let credit_grants = {
+            let mut conn = self.get_conn().await?;
+            list_available_credit_grants(&mut conn, tenant_id, customer.id).await?
+        };
+
+        let tax = self.resolve_customer_tax(&customer).await?;
+
+        let totals = InvoiceTotals::from_params(InvoiceTotalsParams {
+            line_items: &lines,
+            total: 0,
+            amount_due: 0,
+            tax: &tax,
+            // the credits are applied below, as the grants depend on the lines
+            customer_balance_cents: 0,
+            subscription_applied_coupons: &subscription.invoice_coupons(invoice_date),
+            invoice_currency: subscription.currency.as_str(),
+        });
+
+        let applied_credits = applicable_credits(
+            customer.balance_value_cents,
+            &credit_grants,
+            &totals.line_items,
+            totals.total,
+            invoice_date,
+        );
+
+        Ok(UpcomingInvoice {
+            subscription_id,
+            plan_version_id: subscription.plan_version_id,
+            invoice_date,
+            currency: subscription.currency,
+            line_items: totals.line_items,
+            subtotal: totals.subtotal,
+            tax_amount: totals.tax_amount,
+            total: totals.total,
+            applied_credits,
+            amount_due: totals.amount_due - applied_credits,
+        })
+    }
+}
+
+impl Store {
+    /// Applies the plan version and add-ons of the changes to the subscription, as they would be on a plan change
+    /// or when added. The slot counts are applied by the compute engine.
+    async fn apply_upcoming_invoice_changes(
+        &self,
+        subscription: SubscriptionDetails,
+        changes: &UpcomingInvoiceChanges,
+        today: NaiveDate,
+    ) -> StoreResult<SubscriptionDetails> {
+        let mut conn = self.get_conn().await?;
+
+        let mut subscription = match &changes.plan_version {
+            Some(plan_version) => {
+                change_plan_version(&mut conn, subscription, plan_version, today).await?
+            }
+            None => subscription,
+        };
+
+        if let Some(add_ons) = &changes.add_ons {
+            let all_add_ons: Vec<AddOn> = AddOnRow::list_by_ids(
+                &mut conn,
+                &add_ons
+                    .add_ons
+                    .iter()
+                    .map(|x| x.add_on_id)
+                    .unique()
+                    .collect::<Vec<_>>(),
+                &subscription.tenant_id,
+            )
+            .await
+            .map_err(Into::<Report<StoreError>>::into)
+            .and_then(|x| x.into_iter().map(TryInto::try_into).collect())?;
+
+            let created_at = chrono::Utc::now().naive_utc();
+
+            subscription.add_ons.extend(
+                process_create_subscription_add_ons(&changes.add_ons, &all_add_ons)?
+                    .into_iter()
+                    .map(|a| SubscriptionAddOn {
+                        id: Uuid::now_v7(),
+                        subscription_id: subscription.id,
+                        add_on_id: a.add_on_id,
+                        name: a.name,
+                        period: a.period,
+                        fee: a.fee,
+                        created_at,
+                    }),
+            );
+        }
+
+        if changes.plan_version.is_some() || changes.add_ons.is_some() {
+            subscription.period = extract_billing_period(
+                subscription
+                    .price_components
+                    .iter()
+                    .map(|c| &c.period)
+                    .chain(subscription.add_ons.iter().map(|a| &a.period)),
+            );
+
+            let metric_ids = subscription
+                .price_components
+                .iter()
+                .filter_map(|c| c.metric_id())
+                .chain(
+                    subscription
+                        .add_ons
+                        .iter()
+                        .filter_map(|a| a.fee.metric_id()),
+                )
+                .unique()
+                .collect::<Vec<_>>();
+
+            subscription.metrics =
+                BillableMetricRow::get_by_ids(&mut conn, &metric_ids, &subscription.tenant_id)
+                    .await
+                    .map_err(Into::<Report<StoreError>>::into)?
+                    .into_iter()
+                    .map(TryInto::try_into)
+                    .collect::<Result<Vec<BillableMetric>, _>>()?;
+        }
+
+        Ok(subscription)
+    }
+}
+
+async fn change_plan_version(
+    conn: &mut PgConn,
+    subscription: SubscriptionDetails,
+    plan_version: &UpcomingPlanVersion,
+    today: NaiveDate,
+) -> StoreResult<SubscriptionDetails> {
+    let plan_version_id = plan_version.plan_version_id;
+
+    let version =
+        PlanVersionRow::find_by_id_and_tenant_id(conn, plan_version_id, subscription.tenant_id)
+            .await
+            .map_err(Into::<Report<StoreError>>::into)?;
+
+    if version.is_draft_version {
+        return Err(Report::new(StoreError::InvalidArgument(
+            "cannot change to a draft plan version".to_string(),
+        )));
+    }
+
+    if version.currency != subscription.currency {
+        return Err(Report::new(StoreError::InvalidArgument(
+            "the plan version currency does not match the subscription currency".to_string(),
+        )));
+    }
+
+    let price_components = load_plan_price_components(conn, plan_version_id).await?;
+
+    let components = process_create_subscription_components(
+        &plan_version.components,
+        &price_components,
+        &plan_version_id,
+    )?;
+
+    let schedules: Vec<Schedule> = ScheduleRow::list(conn, plan_version_id, subscription.tenant_id)
+        .await
+        .map_err(Into::<Report<StoreError>>::into)?
+        .into_iter()
+        .map(TryInto::try_into)
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let components = components
+        .into_iter()
+        .map(|c| SubscriptionComponent {
+            id: Uuid::now_v7(),
+            price_component_id: c.price_component_id,
+            product_item_id: c.product_item_id,
+            subscription_id: subscription.id,
+            name: c.name,
+            period: c.period,
+            fee: c.fee,
+        })
+        .collect();
+
+    Ok(apply_plan_version(
+        subscription,
+        &version,
+        components,
+        schedules,
+        today,
+    ))
+}
+
+/// Puts the subscription on the plan version, with its components, billing cycles and terms.
+/// A subscription still in trial gets the trial of the new version, counted from the same start.
+fn apply_plan_version(
+    subscription: SubscriptionDetails,
+    version: &PlanVersionRow,
+    price_components: Vec<SubscriptionComponent>,
+    schedules: Vec<Schedule>,
+    today: NaiveDate,
+) -> SubscriptionDetails {
+    let (trial_start_date, billing_start_date) = match subscription.trial_start_date {
+        Some(trial_start) if today < subscription.billing_start_date => {
+            match version.trial_duration_days.filter(|days| *days > 0) {
+                Some(days) => (
+                    Some(trial_start),
+                    trial_start + chrono::Duration::days(days as i64),
+                ),
+                None => (None, today.max(trial_start)),
+            }
+        }
+        _ => (
+            subscription.trial_start_date,
+            subscription.billing_start_date,
+        ),
+    };
+
+    SubscriptionDetails {
+        plan_version_id: version.id,
+        version: version.version as u32,
+        net_terms: version.net_terms as u32,
+        billing_cycles: version.billing_cycles,
+        trial_start_date,
+        billing_start_date,
+        price_components,
+        schedules,
+        ..subscription
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::enums::BillingPeriodEnum;
+    use chrono::NaiveTime;
+
+    fn date(day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(2024, 1, day).unwrap()
+    }
+
+    fn subscription(trial_start_date: Option<NaiveDate>) -> SubscriptionDetails {
+        SubscriptionDetails {
+            id: Uuid::now_v7(),
+            tenant_id: Uuid::now_v7(),
+            customer_id: Uuid::now_v7(),
+            plan_version_id: Uuid::now_v7(),
+            customer_external_id: None,
+            billing_start_date: trial_start_date
+                .map_or(date(1), |d| d + chrono::Duration::days(14)),
+            billing_end_date: None,
+            billing_day: 1,
+            currency: "EUR".to_string(),
+            net_terms: 0,
+            schedules: vec![],
+            price_components: vec![],
+            add_ons: vec![],
+            applied_coupons: vec![],
+            metrics: vec![],
+            mrr_cents: 0,
+            version: 1,
+            billing_cycles: None,
+            plan_name: "plan".to_string(),
+            plan_id: Uuid::now_v7(),
+            customer_name: "customer".to_string(),
+            canceled_at: None,
+            invoice_memo: None,
+            invoice_threshold: None,
+            created_at: date(1).and_time(NaiveTime::MIN),
+            cancellation_reason: None,
+            activated_at: Some(date(1).and_time(NaiveTime::MIN)),
+            created_by: Uuid::now_v7(),
+            trial_start_date,
+            period: BillingPeriodEnum::Monthly,
+            paused_at: None,
+            resume_at: None,
+            minimum_spend: None,
+            spend_cap: None,
+            elapsed_billing_cycles: 0,
+        }
+    }
+
+    fn plan_version(trial_duration_days: Option<i32>) -> PlanVersionRow {
+        PlanVersionRow {
+            id: Uuid::now_v7(),
+            is_draft_version: false,
+            plan_id: Uuid::now_v7(),
+            version: 3,
+            trial_duration_days,
+            downgrade_plan_id: None,
+            tenant_id: Uuid::now_v7(),
+            period_start_day: None,
+            net_terms: 30,
+            currency: "EUR".to_string(),
+            billing_cycles: Some(12),
+            created_at: date(1).and_time(NaiveTime::MIN),
+            created_by: Uuid::now_v7(),
+            billing_periods: vec![],
+            trialing_plan_id: None,
+            action_after_trial: None,
+            trial_is_free: true,
+        }
+    }
+
+    #[test]
+    fn test_apply_plan_version() {
+        let subscription = subscription(None);
+        let version = plan_version(Some(30));
+
+        let changed = apply_plan_version(subscription.clone(), &version, vec![], vec![], date(10));
+
+        assert_eq!(changed.plan_version_id, version.id);
+        assert_eq!(changed.version, 3);
+        assert_eq!(changed.net_terms, 30);
+        assert_eq!(changed.billing_cycles, Some(12));
+        // the trial of the version does not apply once billing started
+        assert_eq!(changed.trial_start_date, None);
+        assert_eq!(changed.billing_start_date, subscription.billing_start_date);
+        assert_eq!(changed.id, subscription.id);
+    }
+
+    #[test]
+    fn test_apply_plan_version_during_trial() {
+        let subscription = subscription(Some(date(1)));
+        assert_eq!(subscription.billing_start_date, date(15));
+
+        let changed = apply_plan_version(
+            subscription.clone(),
+            &plan_version(Some(30)),
+            vec![],
+            vec![],
+            date(10),
+        );
+        assert_eq!(changed.trial_start_date, Some(date(1)));
+        assert_eq!(changed.billing_start_date, date(31));
+
+        let changed = apply_plan_version(
+            subscription.clone(),
+            &plan_version(None),
+            vec![],
+            vec![],
+            date(10),
+        );
+        assert_eq!(changed.trial_start_date, None);
+        assert_eq!(changed.billing_start_date, date(10));
+    }
+}
@@ -0,0 +1,254 @@
+use crate::compute::InvoiceLineInterface;
+use crate::domain::enums::InvoiceType;
+use crate::domain::{
+    unbilled_usage_lines, CursorPaginatedVec, CursorPaginationRequest, Invoice, InvoiceNew,
+    InvoiceTotals, InvoiceTotalsParams, LineItem, SubscriptionDetails,
+    SubscriptionInvoiceCandidate, UsageThresholdCandidate,
+};
+use crate::errors::StoreError;
+use crate::repositories::invoices::insert_invoice;
+use crate::repositories::invoicing_entities::InvoicingEntityInterface;
+use crate::repositories::subscriptions::subscription_to_draft;
+use crate::repositories::taxes::TaxRateInterface;
+use crate::repositories::{CustomersInterface, SubscriptionInterface};
+use crate::store::{PgConn, Store};
+use crate::StoreResult;
+use chrono::{NaiveDate, NaiveTime};
+use common_domain::money::{Currency, Rounding};
+use common_eventbus::Event;
+use diesel_async::scoped_futures::ScopedFutureExt;
+use diesel_models::invoices::InvoiceRow;
+use diesel_models::subscriptions::SubscriptionRow;
+use error_stack::Report;
+use uuid::Uuid;
+
+#[async_trait::async_trait]
+pub trait UsageThresholdInterface {
+    async fn list_usage_threshold_candidates(
+        &self,
+        date: NaiveDate,
+        pagination: CursorPaginationRequest,
+    ) -> StoreResult<CursorPaginatedVec<UsageThresholdCandidate>>;
+
+    /// Issues a usage-threshold invoice if the usage accrued in the current period, and not billed yet,
+    /// reaches the invoice threshold of the subscription without exceeding its spend cap. Returns the issued invoice, if any.
+    async fn process_usage_threshold(
+        &self,
+        tenant_id: Uuid,
+        subscription_id: Uuid,
+    ) -> StoreResult<Option<Invoice>>;
+}
+
+#[async_trait::async_trait]
+impl UsageThresholdInterface for Store {
+    async fn list_usage_threshold_candidates(
+        &self,
+        date: NaiveDate,
+        pagination: CursorPaginationRequest,
+    ) -> StoreResult<CursorPaginatedVec<UsageThresholdCandidate>> {
+        let mut conn = self.get_conn().await?;
+
+        let rows = SubscriptionRow::list_with_invoice_threshold(&mut conn, date, pagination.into())
+            .await
+            .map_err(Into::<Report<StoreError>>::into)?;
+
+        Ok(CursorPaginatedVec {
+            items: rows.items.into_iter().map(Into::into).collect(),
+            next_cursor: rows.next_cursor,
+        })
+    }
+
+    async fn process_usage_threshold(
+        &self,
+        tenant_id: Uuid,
+        subscription_id: Uuid,
+    ) -> StoreResult<Option<Invoice>> {
+        let subscription = self
+            .get_subscription_details(tenant_id, subscription_id)
+            .await?;
+
+        let threshold = match subscription.invoice_threshold {
+            Some(threshold) if threshold.is_sign_positive() && !threshold.is_zero() => threshold,
+            _ => return Ok(None),
+        };
+
+        if subscription.paused_at.is_some() {
+            return Ok(None);
+        }
+
+        let currency = Currency::from_code(&subscription.currency).ok_or(Report::new(
+            StoreError::ValueNotFound(format!("unknown currency {}", subscription.currency)),
+        ))?;
+
+        let threshold = currency
+            .to_minor(threshold, Rounding::HalfAwayFromZero)
+            .ok_or(Report::new(StoreError::InvalidArgument(
+                "invoice threshold is out of range".to_string(),
+            )))?;
+
+        let today = chrono::Utc::now().date_naive();
+
+        let accrued = self
+            .compute_accrued_usage_lines(&today, &subscription)
+            .await?;
+
+        let period_start = match accrued.iter().map(|l| l.start_date).min() {
+            Some(period_start) => period_start,
+            None => return Ok(None),
+        };
+
+        let mut conn = self.get_conn().await?;
+
+        let invoiced =
+            list_threshold_invoices(&mut conn, subscription_id, period_start, today).await?;
+        let invoiced_count = invoiced.len();
+        let invoiced_lines = invoiced_lines(&invoiced);
+
+        let lines = unbilled_usage_lines(accrued, &invoiced_lines);
+
+        let unbilled: i64 = lines.iter().map(|l| l.subtotal).sum();
+
+        if unbilled < threshold {
+            return Ok(None);
+        }
+
+        // the usage above the spend cap is not billed, the recurring invoice caps the usage of the period
+        if let Some(spend_cap) = subscription
+            .spend_cap
+            .filter(|cap| cap.is_sign_positive() && !cap.is_zero())
+        {
+            let spend_cap = currency
+                .to_minor(spend_cap, Rounding::HalfAwayFromZero)
+                .ok_or(Report::new(StoreError::InvalidArgument(
+                    "spend cap is out of range".to_string(),
+                )))?;
+            let invoiced_usage: i64 = invoiced_lines.iter().map(|l| l.subtotal).sum();
+
+            if invoiced_usage + unbilled > spend_cap {
+                return Ok(None);
+            }
+        }
+
+        let invoice = self
+            .build_subscription_invoice(&subscription, lines, today, InvoiceType::UsageThreshold)
+            .await?;
+
+        let inserted = self
+            .transaction(|conn| {
+                async move {
+                    SubscriptionRow::lock_subscription_for_update(conn, subscription_id)
+                        .await
+                        .map_err(Into::<Report<StoreError>>::into)?;
+
+                    // processed concurrently
+                    let invoiced =
+                        list_threshold_invoices(conn, subscription_id, period_start, today).await?;
+
+                    if invoiced.len() != invoiced_count {
+                        return Ok(None);
+                    }
+
+                    insert_invoice(conn, invoice).await.map(Some)
+                }
+                .scope_boxed()
+            })
+            .await?;
+
+        if let Some(inserted) = &inserted {
+            let _ = self
+                .eventbus
+                .publish(Event::invoice_created(inserted.id, tenant_id))
+                .await;
+        }
+
+        Ok(inserted)
+    }
+}
+
+impl Store {
+    /// Builds a draft invoice of the subscription with the given lines, other than its recurring invoices.
+    pub(crate) async fn build_subscription_invoice(
+        &self,
+        subscription: &SubscriptionDetails,
+        lines: Vec<LineItem>,
+        invoice_date: NaiveDate,
+        invoice_type: InvoiceType,
+    ) -> StoreResult<InvoiceNew> {
+        let customer = self
+            .find_customer_by_id(subscription.customer_id, subscription.tenant_id)
+            .await?;
+
+        let invoicing_entity = self
+            .get_invoicing_entity(subscription.tenant_id, Some(customer.invoicing_entity_id))
+            .await?;
+
+        let candidate = SubscriptionInvoiceCandidate {
+            id: subscription.id,
+            tenant_id: subscription.tenant_id,
+            customer_id: subscription.customer_id,
+            plan_version_id: subscription.plan_version_id,
+            plan_name: subscription.plan_name.clone(),
+            billing_start_date: subscription.billing_start_date,
+            billing_end_date: subscription.billing_end_date,
+            billing_day: subscription.billing_day,
+            activated_at: subscription.activated_at,
+            canceled_at: subscription.canceled_at,
+            currency: subscription.currency.clone(),
+            net_terms: subscription.net_terms as i32,
+            period: subscription.period.clone(),
+        };
+
+        let mut invoice = subscription_to_draft(&candidate, &customer, &invoicing_entity)?;
+
+        let tax = self.resolve_customer_tax(&customer).await?;
+
+        // refreshed on finalization, with the balance at that time
+        let totals = InvoiceTotals::from_params(InvoiceTotalsParams {
+            line_items: &lines,
+            total: 0,
+            amount_due: 0,
+            tax: &tax,
+            customer_balance_cents: customer.balance_value_cents,
+            subscription_applied_coupons: &vec![],
+            invoice_currency: subscription.currency.as_str(),
+        });
+
+        invoice.invoice_type = invoice_type;
+        invoice.invoice_date = invoice_date;
+        invoice.due_at = Some(
+            (invoice_date + chrono::Duration::days(subscription.net_terms as i64))
+                .and_time(NaiveTime::MIN),
+        );
+        invoice.line_items = totals.line_items;
+        invoice.subtotal = totals.subtotal;
+        invoice.subtotal_recurring = totals.subtotal_recurring;
+        invoice.tax_rate = tax.rate;
+        invoice.tax_amount = totals.tax_amount;
+        invoice.tax_breakdown = totals.tax_breakdown;
+        invoice.total = totals.total;
+        invoice.amount_due = totals.amount_due;
+
+        Ok(invoice)
+    }
+}
+
+pub(crate) async fn list_threshold_invoices(
+    conn: &mut PgConn,
+    subscription_id: Uuid,
+    from: NaiveDate,
+    to: NaiveDate,
+) -> StoreResult<Vec<Invoice>> {
+    InvoiceRow::list_usage_threshold_by_subscription_id(conn, subscription_id, from, to)
+        .await
+        .map_err(Into::<Report<StoreError>>::into)?
+        .into_iter()
+        .map(TryInto::try_into)
+        .collect()
+}
+
+pub(crate) fn invoiced_lines(threshold_invoices: &[Invoice]) -> Vec<LineItem> {
+    threshold_invoices
+        .iter()
+        .flat_map(|invoice| invoice.line_items.iter().cloned())
+        .collect()
+}
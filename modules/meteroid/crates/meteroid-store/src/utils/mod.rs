@@ -1,5 +1,4 @@
 pub mod datetime;
-pub mod decimals;
 pub mod gen;
 pub mod local_id;
 pub mod periods;
@@ -42,6 +42,18 @@ pub fn calculate_periods_for_date(
     }
 }
 
+/**
+ * For a subscription resumed at the given date, this gets the periods of the resume invoice.
+ * The resume date becomes the new billing anchor, and the first period is prorated up to the billing day, as for a new subscription.
+ */
+pub fn calculate_resume_periods(
+    resumed_on: NaiveDate,
+    billing_day: u32,
+    billing_period: &BillingPeriodEnum,
+) -> ComponentPeriods {
+    calculate_periods_for_date(resumed_on, billing_day, resumed_on, billing_period)
+}
+
 /**
  * For a given invoice date, this checks if a component with this period should be billed in that invoice and what are the related arrear/advance periods
  */
@@ -215,7 +227,7 @@ fn add_months_at_billing_day(
 
 #[cfg(test)]
 mod test {
-    use super::{calculate_period_idx, calculate_period_range};
+    use super::{calculate_period_idx, calculate_period_range, calculate_resume_periods};
     use crate::domain::enums::BillingPeriodEnum;
 
     use chrono::NaiveDate;
@@ -381,6 +393,33 @@ mod test {
         assert_eq!(end, expected_period_end);
     }
 
+    #[rstest]
+    #[case(
+        BillingPeriodEnum::Monthly,
+        "2024-01-16",
+        1,
+        "2024-02-01",
+        Some(16.0 / 31.0)
+    )]
+    #[case(BillingPeriodEnum::Monthly, "2024-01-01", 1, "2024-02-01", None)]
+    #[case(BillingPeriodEnum::Monthly, "2024-03-10", 15, "2024-03-15", Some(5.0 / 31.0))]
+    #[case(BillingPeriodEnum::Annual, "2024-01-16", 1, "2025-01-01", None)]
+    #[trace]
+    fn test_calculate_resume_periods(
+        #[case] billing_period: BillingPeriodEnum,
+        #[case] resumed_on: NaiveDate,
+        #[case] billing_day: u32,
+        #[case] expected_period_end: NaiveDate,
+        #[case] expected_proration_factor: Option<f64>,
+    ) {
+        let periods = calculate_resume_periods(resumed_on, billing_day, &billing_period);
+
+        assert_eq!(periods.advance.start, resumed_on);
+        assert_eq!(periods.advance.end, expected_period_end);
+        assert_eq!(periods.arrear, None);
+        assert_eq!(periods.proration_factor, expected_proration_factor);
+    }
+
     #[rstest]
     #[case(BillingPeriodEnum::Monthly, "2021-01-01", 10, "2021-01-02", 0)]
     #[case(BillingPeriodEnum::Monthly, "2021-01-01", 10, "2021-01-10", 1)]
@@ -1,5 +1,7 @@
 use crate::error::{ErrorResponse, StripeError};
-use crate::invoice::{CreateInvoice, CreateInvoiceItem, Invoice, InvoiceItem};
+use crate::invoice::{
+    CreateInvoice, CreateInvoiceItem, DeletedInvoice, Invoice, InvoiceItem, InvoiceSearchResult,
+};
 use crate::request::{Outcome, RetryStrategy};
 use bytes::Bytes;
 use common_domain::StripeSecret;
@@ -121,6 +123,45 @@ impl StripeClient {
         )
     }
 
+    /// Voids a finalized invoice. Draft invoices cannot be voided, they are deleted instead.
+    pub fn void_invoice(
+        &self,
+        invoice_id: &'_ str,
+        secret_key: &'_ StripeSecret,
+    ) -> Response<Invoice> {
+        self.post(
+            &format!("/invoices/{}/void", invoice_id),
+            secret_key,
+            RetryStrategy::default(),
+        )
+    }
+
+    pub fn delete_invoice(
+        &self,
+        invoice_id: &'_ str,
+        secret_key: &'_ StripeSecret,
+    ) -> Response<DeletedInvoice> {
+        self.delete(
+            &format!("/invoices/{}", invoice_id),
+            secret_key,
+            RetryStrategy::default(),
+        )
+    }
+
+    /// Searches the invoices with the [search query language](https://stripe.com/docs/search#search-query-language).
+    pub fn search_invoices(
+        &self,
+        query: &'_ str,
+        secret_key: &'_ StripeSecret,
+    ) -> Response<InvoiceSearchResult> {
+        self.get_query(
+            "/invoices/search",
+            &[("query", query)],
+            secret_key,
+            RetryStrategy::default(),
+        )
+    }
+
     pub fn create_invoice_item(
         &self,
         params: CreateInvoiceItem<'_>,
@@ -149,6 +190,35 @@ impl StripeClient {
         self.execute(request_builder, retry_strategy)
     }
 
+    /// Make a `GET` http request with the given query parameters
+    fn get_query<T: DeserializeOwned + Send + 'static>(
+        &self,
+        path: &str,
+        params: &[(&str, &str)],
+        secret_key: &StripeSecret,
+        retry_strategy: RetryStrategy,
+    ) -> Response<T> {
+        let mut url = self.url(path);
+        url.query_pairs_mut().extend_pairs(params);
+
+        let request_builder = self.create_init_request(Method::GET, url, &secret_key.0, None);
+
+        self.execute(request_builder, retry_strategy)
+    }
+
+    fn delete<T: DeserializeOwned + Send + 'static>(
+        &self,
+        path: &str,
+        secret_key: &StripeSecret,
+        retry_strategy: RetryStrategy,
+    ) -> Response<T> {
+        let url = self.url(path);
+
+        let request_builder = self.create_init_request(Method::DELETE, url, &secret_key.0, None);
+
+        self.execute(request_builder, retry_strategy)
+    }
+
     /// Make a `POST` http request with urlencoded body
     fn post_form<T: DeserializeOwned + Send + 'static, F: Serialize>(
         &self,
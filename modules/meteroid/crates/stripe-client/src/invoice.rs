@@ -145,3 +145,21 @@ pub struct InvoiceItem {
     /// Unique identifier for the object.
     pub id: String,
 }
+
+/// The result of a search of invoices.
+///
+/// For more details see <https://stripe.com/docs/api/invoices/search>
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct InvoiceSearchResult {
+    pub data: Vec<Invoice>,
+}
+
+/// The response of the deletion of a draft invoice.
+///
+/// For more details see <https://stripe.com/docs/api/invoices/delete>
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct DeletedInvoice {
+    pub id: String,
+    #[serde(default)]
+    pub deleted: bool,
+}
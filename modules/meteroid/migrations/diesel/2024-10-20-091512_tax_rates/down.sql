@@ -0,0 +1,9 @@
+alter table invoice drop column if exists tax_breakdown;
+alter table invoice alter column tax_rate type integer using round(tax_rate)::integer;
+
+drop table if exists tax_rate;
+
+alter table customer drop column if exists tax_status;
+alter table customer drop column if exists vat_number;
+
+drop type if exists "CustomerTaxStatusEnum";
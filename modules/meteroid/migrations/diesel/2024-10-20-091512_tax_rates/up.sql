@@ -0,0 +1,24 @@
+create type "CustomerTaxStatusEnum" as enum ('TAXABLE', 'EXEMPT', 'REVERSE_CHARGE');
+
+alter table customer add column if not exists vat_number text;
+alter table customer add column if not exists tax_status "CustomerTaxStatusEnum" not null default 'TAXABLE';
+
+create table if not exists tax_rate
+(
+  id          uuid primary key,
+  tenant_id   uuid         not null references tenant on delete cascade,
+  name        text         not null,
+  country     text         not null,
+  region      text,
+  rate        numeric      not null,
+  created_at  timestamp(3) not null default now(),
+  archived_at timestamp(3)
+);
+
+alter table tax_rate add constraint tax_rate_rate_range check (rate >= 0 and rate <= 100);
+
+create unique index if not exists tax_rate_tenant_id_country_region_idx
+  on tax_rate (tenant_id, country, coalesce(region, '')) where archived_at is null;
+
+alter table invoice alter column tax_rate type numeric using tax_rate::numeric;
+alter table invoice add column if not exists tax_breakdown jsonb not null default '[]';
@@ -0,0 +1,32 @@
+drop trigger if exists trg_update_revenue_credit_note on credit_note;
+create trigger trg_update_revenue_credit_note
+  after update
+  on credit_note
+  for each row
+  when (new.status = 'FINALIZED'::"CreditNoteStatus")
+execute procedure fn_update_revenue_credit_note();
+
+drop trigger if exists trg_update_customer_ytd_summary_credit_note on credit_note;
+create trigger trg_update_customer_ytd_summary_credit_note
+  after insert or update
+  on credit_note
+  for each row
+  when (new.status = 'FINALIZED'::"CreditNoteStatus")
+execute procedure fn_update_customer_ytd_summary_credit_note();
+
+drop index if exists credit_note_tenant_id_invoice_id_idx;
+
+alter table credit_note alter column updated_at drop default;
+alter table credit_note alter column created_at drop default;
+
+alter table credit_note drop column if exists pdf_document_id;
+alter table credit_note drop column if exists voided_at;
+alter table credit_note drop column if exists total;
+alter table credit_note drop column if exists tax_amount;
+alter table credit_note drop column if exists subtotal;
+alter table credit_note drop column if exists line_items;
+alter table credit_note drop column if exists memo;
+alter table credit_note drop column if exists reason;
+alter table credit_note drop column if exists credit_note_number;
+
+alter table credit_note alter column finalized_at set not null;
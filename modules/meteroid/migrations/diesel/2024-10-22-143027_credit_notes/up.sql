@@ -0,0 +1,33 @@
+alter table credit_note alter column finalized_at drop not null;
+alter table credit_note add column if not exists credit_note_number text;
+alter table credit_note add column if not exists reason text;
+alter table credit_note add column if not exists memo text;
+alter table credit_note add column if not exists line_items jsonb not null default '[]';
+alter table credit_note add column if not exists subtotal bigint not null default 0;
+alter table credit_note add column if not exists tax_amount bigint not null default 0;
+alter table credit_note add column if not exists total bigint not null default 0;
+alter table credit_note add column if not exists voided_at timestamp(3);
+alter table credit_note add column if not exists pdf_document_id text;
+
+alter table credit_note alter column created_at set default now();
+alter table credit_note alter column updated_at set default now();
+
+create index if not exists credit_note_tenant_id_invoice_id_idx
+  on credit_note (tenant_id, invoice_id);
+
+-- credit notes are created as drafts and finalized later, so the reporting triggers must only run once, on finalization
+drop trigger if exists trg_update_customer_ytd_summary_credit_note on credit_note;
+create trigger trg_update_customer_ytd_summary_credit_note
+  after update
+  on credit_note
+  for each row
+  when (old.status <> 'FINALIZED'::"CreditNoteStatus" and new.status = 'FINALIZED'::"CreditNoteStatus")
+execute procedure fn_update_customer_ytd_summary_credit_note();
+
+drop trigger if exists trg_update_revenue_credit_note on credit_note;
+create trigger trg_update_revenue_credit_note
+  after update
+  on credit_note
+  for each row
+  when (old.status <> 'FINALIZED'::"CreditNoteStatus" and new.status = 'FINALIZED'::"CreditNoteStatus")
+execute procedure fn_update_revenue_credit_note();
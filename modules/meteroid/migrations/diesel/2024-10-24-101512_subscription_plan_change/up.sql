@@ -0,0 +1,20 @@
+create table if not exists subscription_plan_change
+(
+  id              uuid primary key,
+  subscription_id uuid         not null references subscription on delete cascade,
+  tenant_id       uuid         not null references tenant on delete cascade,
+  plan_version_id uuid         not null references plan_version on delete restrict,
+  components      jsonb        not null,
+  mrr_delta       bigint       not null,
+  effective_date  date         not null,
+  created_at      timestamp(3) not null default now(),
+  created_by      uuid         not null,
+  applied_at      timestamp(3)
+);
+
+-- a subscription can only have a single scheduled plan change
+create unique index if not exists subscription_plan_change_pending_idx
+  on subscription_plan_change (subscription_id) where applied_at is null;
+
+create index if not exists subscription_plan_change_effective_date_idx
+  on subscription_plan_change (effective_date) where applied_at is null;
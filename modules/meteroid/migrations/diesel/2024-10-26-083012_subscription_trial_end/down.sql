@@ -0,0 +1,3 @@
+-- enum values cannot be dropped, SUBSCRIPTION_TRIAL_ENDED is kept
+drop index if exists subscription_trial_pending_idx;
+alter table subscription drop column if exists trial_ended_at;
@@ -0,0 +1,7 @@
+alter table subscription add column if not exists trial_ended_at timestamp(3);
+
+-- trials whose end was not processed yet
+create index if not exists subscription_trial_pending_idx
+  on subscription (billing_start_date) where trial_start_date is not null and trial_ended_at is null;
+
+alter type "WebhookOutEventTypeEnum" add value if not exists 'SUBSCRIPTION_TRIAL_ENDED';
@@ -0,0 +1,7 @@
+-- enum values cannot be dropped, INVOICE_PAYMENT_REMINDER is kept
+
+drop table if exists invoice_dunning;
+drop table if exists dunning_config;
+
+drop type if exists "InvoiceDunningStatusEnum";
+drop type if exists "DunningSubscriptionActionEnum";
@@ -0,0 +1,43 @@
+create type "DunningSubscriptionActionEnum" as enum ('NONE', 'CANCEL');
+create type "InvoiceDunningStatusEnum" as enum ('ACTIVE', 'RECOVERED', 'EXHAUSTED', 'ABORTED');
+
+create table if not exists dunning_config
+(
+  id                  uuid primary key,
+  tenant_id           uuid                            not null references tenant on delete cascade,
+  invoicing_entity_id uuid                            not null references invoicing_entity on delete cascade,
+  enabled             boolean                         not null default true,
+  -- days after the start of the dunning, at which the customer is reminded to pay
+  retry_schedule_days jsonb                           not null default '[]',
+  -- days after the start of the dunning, at which the end actions are applied
+  end_after_days      integer                         not null,
+  subscription_action "DunningSubscriptionActionEnum" not null default 'NONE',
+  mark_uncollectible  boolean                         not null default true,
+  created_at          timestamp(3)                    not null default now(),
+  updated_at          timestamp(3)                    not null default now()
+);
+
+create unique index if not exists dunning_config_invoicing_entity_id_idx
+  on dunning_config (invoicing_entity_id);
+
+create table if not exists invoice_dunning
+(
+  id              uuid primary key,
+  tenant_id       uuid                       not null references tenant on delete cascade,
+  invoice_id      uuid                       not null references invoice on delete cascade,
+  status          "InvoiceDunningStatusEnum" not null default 'ACTIVE',
+  attempts        integer                    not null default 0,
+  started_at      timestamp(3)               not null default now(),
+  next_attempt_at timestamp(3)               not null,
+  last_attempt_at timestamp(3),
+  ended_at        timestamp(3)
+);
+
+-- an invoice is dunned once
+create unique index if not exists invoice_dunning_invoice_id_idx
+  on invoice_dunning (invoice_id);
+
+create index if not exists invoice_dunning_next_attempt_at_idx
+  on invoice_dunning (next_attempt_at) where status = 'ACTIVE';
+
+alter type "WebhookOutEventTypeEnum" add value if not exists 'INVOICE_PAYMENT_REMINDER';
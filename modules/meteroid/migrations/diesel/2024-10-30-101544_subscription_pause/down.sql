@@ -0,0 +1,5 @@
+-- enum values cannot be dropped, PAUSED, RESUMED and PAUSE are kept
+drop index if exists subscription_resume_at_idx;
+
+alter table subscription drop column if exists resume_at;
+alter table subscription drop column if exists paused_at;
@@ -0,0 +1,12 @@
+alter table subscription add column if not exists paused_at timestamp(3);
+
+-- paused subscriptions are resumed automatically at that date, if set
+alter table subscription add column if not exists resume_at date;
+
+create index if not exists subscription_resume_at_idx
+  on subscription (resume_at) where paused_at is not null;
+
+alter type "SubscriptionEventType" add value if not exists 'PAUSED';
+alter type "SubscriptionEventType" add value if not exists 'RESUMED';
+-- dunning can pause the subscription of an unpaid invoice
+alter type "DunningSubscriptionActionEnum" add value if not exists 'PAUSE';
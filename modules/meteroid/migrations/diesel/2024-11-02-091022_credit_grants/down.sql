@@ -0,0 +1,3 @@
+alter table customer_balance_tx drop column if exists credit_grant_id;
+
+drop table if exists credit_grant;
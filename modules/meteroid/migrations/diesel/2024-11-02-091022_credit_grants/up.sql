@@ -0,0 +1,30 @@
+create table if not exists credit_grant
+(
+  id                    uuid primary key,
+  tenant_id             uuid         not null references tenant on delete cascade,
+  customer_id           uuid         not null references customer on delete cascade,
+  name                  text         not null,
+  amount_cents          integer      not null check (amount_cents > 0),
+  -- the part of the grant not consumed or written off yet
+  remaining_cents       integer      not null check (remaining_cents >= 0),
+  currency              text         not null,
+  -- grants are consumed by ascending priority
+  priority              integer      not null default 0,
+  effective_date        date         not null,
+  expiry_date           date,
+  -- restricts the grant to the lines of these price components or metrics, if any
+  price_component_ids   uuid[]       not null default '{}',
+  metric_ids            uuid[]       not null default '{}',
+  created_at            timestamp(3) not null default now(),
+  created_by            uuid references "user" on delete restrict,
+  expired_at            timestamp(3)
+);
+
+create index if not exists credit_grant_customer_id_idx
+  on credit_grant (customer_id);
+
+create index if not exists credit_grant_expiry_date_idx
+  on credit_grant (expiry_date) where expired_at is null;
+
+alter table customer_balance_tx
+  add column if not exists credit_grant_id uuid references credit_grant on delete restrict;
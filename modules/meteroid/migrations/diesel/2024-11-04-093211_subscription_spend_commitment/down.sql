@@ -0,0 +1,2 @@
+alter table subscription drop column if exists spend_cap;
+alter table subscription drop column if exists minimum_spend;
@@ -0,0 +1,4 @@
+-- minimum spend per billing period, trued up when the invoice falls short
+alter table subscription add column if not exists minimum_spend numeric;
+-- maximum billed for usage per billing period
+alter table subscription add column if not exists spend_cap numeric;
@@ -0,0 +1,4 @@
+-- enum values cannot be dropped, COMMITMENT is kept
+drop table if exists subscription_commitment;
+
+drop type if exists "SubscriptionCommitmentTypeEnum";
@@ -0,0 +1,30 @@
+create type "SubscriptionCommitmentTypeEnum" as enum ('PREPAID', 'POSTPAID');
+
+alter type "InvoiceType" add value if not exists 'COMMITMENT';
+
+create table if not exists subscription_commitment
+(
+  id               uuid                             primary key,
+  tenant_id        uuid                             not null references tenant on delete cascade,
+  subscription_id  uuid                             not null references subscription on delete cascade,
+  commitment_type  "SubscriptionCommitmentTypeEnum" not null,
+  amount_cents     integer                          not null check (amount_cents > 0),
+  -- the usage billed by the invoices dated after the start date, up to the end date included, draws down the commitment
+  start_date       date                             not null,
+  end_date         date                             not null check (end_date > start_date),
+  -- the grant restricted to the usage of the subscription, that tracks the drawdown
+  credit_grant_id  uuid                             not null references credit_grant on delete restrict,
+  -- the prepayment invoice, or the invoice issued at term end for a postpaid commitment
+  invoice_id       uuid references invoice on delete set null,
+  -- the part left unused at term end, forfeited if prepaid or trued up if postpaid
+  unused_cents     integer,
+  created_at       timestamp(3)                     not null default now(),
+  created_by       uuid references "user" on delete restrict,
+  closed_at        timestamp(3)
+);
+
+create index if not exists subscription_commitment_subscription_id_idx
+  on subscription_commitment (subscription_id);
+
+create index if not exists subscription_commitment_end_date_idx
+  on subscription_commitment (end_date) where closed_at is null;
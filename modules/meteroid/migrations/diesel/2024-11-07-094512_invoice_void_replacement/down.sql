@@ -0,0 +1,4 @@
+drop index if exists invoice_replaces_invoice_id_idx;
+
+alter table invoice
+  drop column if exists replaces_invoice_id;
@@ -0,0 +1,6 @@
+-- the voided invoice that a reissued invoice corrects
+alter table invoice
+  add column if not exists replaces_invoice_id uuid references invoice on delete set null;
+
+create index if not exists invoice_replaces_invoice_id_idx
+  on invoice (replaces_invoice_id) where replaces_invoice_id is not null;
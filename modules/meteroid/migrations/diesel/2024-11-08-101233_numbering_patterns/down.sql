@@ -0,0 +1,4 @@
+alter table invoicing_entity
+  drop column if exists credit_note_number_pattern,
+  drop column if exists invoice_number_year,
+  drop column if exists credit_note_number_year;
@@ -0,0 +1,5 @@
+alter table invoicing_entity
+  add column if not exists credit_note_number_pattern text not null default 'CN-{number}',
+  -- the year of the last allocated number, for the sequences reset every year
+  add column if not exists invoice_number_year integer,
+  add column if not exists credit_note_number_year integer;
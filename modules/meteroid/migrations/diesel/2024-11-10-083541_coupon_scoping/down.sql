@@ -0,0 +1,8 @@
+alter table applied_coupon
+  drop column if exists promotion_code_id;
+
+drop table if exists promotion_code;
+
+alter table coupon
+  drop column if exists applies_to,
+  drop column if exists duration_months;
@@ -0,0 +1,29 @@
+alter table coupon
+  -- restricts the coupon to plans, plan versions, price components or add-ons
+  add column if not exists applies_to jsonb,
+  -- the coupon only applies to the invoices of the first months after it was applied
+  add column if not exists duration_months integer check (duration_months > 0);
+
+create table if not exists promotion_code
+(
+  id               uuid primary key,
+  code             text         not null,
+  coupon_id        uuid         not null references coupon on delete cascade,
+  tenant_id        uuid         not null references tenant on delete cascade,
+  -- the code can only be redeemed by this customer
+  customer_id      uuid references customer on delete cascade,
+  expires_at       timestamp(3),
+  redemption_limit integer check (redemption_limit > 0),
+  redemption_count integer      not null default 0,
+  archived_at      timestamp(3),
+  created_at       timestamp(3) not null default now()
+);
+
+create unique index if not exists promotion_code_tenant_id_code_idx
+  on promotion_code (tenant_id, code);
+
+create index if not exists promotion_code_coupon_id_idx
+  on promotion_code (coupon_id);
+
+alter table applied_coupon
+  add column if not exists promotion_code_id uuid references promotion_code on delete set null;
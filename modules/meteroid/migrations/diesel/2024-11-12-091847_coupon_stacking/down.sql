@@ -0,0 +1,8 @@
+alter table invoice
+  drop column if exists coupons;
+
+alter table coupon
+  drop column if exists stacking,
+  drop column if exists priority;
+
+drop type if exists "CouponStackingEnum";
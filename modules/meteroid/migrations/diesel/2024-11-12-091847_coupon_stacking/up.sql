@@ -0,0 +1,10 @@
+create type "CouponStackingEnum" as enum ('STACKABLE', 'EXCLUSIVE', 'BEST_OF');
+
+alter table coupon
+  add column if not exists stacking "CouponStackingEnum" not null default 'STACKABLE',
+  -- the coupons of an invoice are applied by ascending priority
+  add column if not exists priority integer not null default 0;
+
+-- the discount of each coupon applied to the invoice
+alter table invoice
+  add column if not exists coupons jsonb not null default '[]';
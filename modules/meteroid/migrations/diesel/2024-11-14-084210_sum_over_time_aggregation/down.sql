@@ -0,0 +1 @@
+-- enum values cannot be dropped, SUM_OVER_TIME is kept
@@ -0,0 +1 @@
+alter type "BillingMetricAggregateEnum" add value if not exists 'SUM_OVER_TIME';
@@ -0,0 +1 @@
+alter table subscription drop column if exists elapsed_billing_cycles;
@@ -0,0 +1,2 @@
+-- billing periods elapsed before the last resume, the billing start date being moved to the resume date
+alter table subscription add column if not exists elapsed_billing_cycles integer not null default 0;
@@ -5,7 +5,7 @@ use hyper::StatusCode;
 use secrecy::ExposeSecret;
 use secrecy::SecretString;
 use stripe_client::invoice::{CollectionMethod, CreateInvoice, MeteroidMetadata};
-use stripe_client::invoice::{CreateInvoiceItem, Invoice, Period};
+use stripe_client::invoice::{CreateInvoiceItem, Invoice, InvoiceStatus, Period};
 use stripe_client::webhook::Event;
 use stripe_client::webhook::EventObject;
 
@@ -130,6 +130,45 @@ impl InvoicingAdapter for Stripe {
 
         Ok(())
     }
+
+    async fn cancel_invoice(
+        &self,
+        invoice: &domain::Invoice,
+        api_key: SecretString,
+    ) -> Result<(), InvoicingAdapterError> {
+        let api_key = &StripeSecret(api_key);
+
+        // the stripe invoice id is not kept, the invoice is found by its metadata
+        let query = format!("metadata['meteroid_invoice_id']:'{}'", invoice.id);
+
+        let stripe_invoices = self
+            .client
+            .search_invoices(&query, api_key)
+            .await
+            .change_context(InvoicingAdapterError::StripeError)?
+            .data;
+
+        for stripe_invoice in stripe_invoices {
+            match stripe_invoice.status {
+                Some(InvoiceStatus::Draft) => {
+                    self.client
+                        .delete_invoice(&stripe_invoice.id, api_key)
+                        .await
+                        .change_context(InvoicingAdapterError::StripeError)?;
+                }
+                Some(InvoiceStatus::Open) | Some(InvoiceStatus::Uncollectible) => {
+                    self.client
+                        .void_invoice(&stripe_invoice.id, api_key)
+                        .await
+                        .change_context(InvoicingAdapterError::StripeError)?;
+                }
+                Some(InvoiceStatus::Paid) => bail!(InvoicingAdapterError::AlreadyPaid),
+                Some(InvoiceStatus::Void) | Some(InvoiceStatus::Deleted) | None => {}
+            }
+        }
+
+        Ok(())
+    }
 }
 
 impl Stripe {
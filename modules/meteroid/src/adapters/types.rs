@@ -48,6 +48,13 @@ pub trait InvoicingAdapter: AdapterCommon + Sync {
         customer: &Customer,
         api_key: SecretString,
     ) -> Result<(), errors::InvoicingAdapterError>;
+
+    /// Cancels a voided invoice at the provider, if it was sent there.
+    async fn cancel_invoice(
+        &self,
+        invoice: &Invoice,
+        api_key: SecretString,
+    ) -> Result<(), errors::InvoicingAdapterError>;
 }
 
 pub trait Adapter: Send + Debug + WebhookAdapter {}
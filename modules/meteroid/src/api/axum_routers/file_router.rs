@@ -18,6 +18,7 @@ use error_stack::{Report, Result, ResultExt};
 use fang::Deserialize;
 use image::ImageFormat::Png;
 use jsonwebtoken::{decode, DecodingKey, Validation};
+use meteroid_store::repositories::credit_notes::CreditNoteInterface;
 use meteroid_store::repositories::InvoiceInterface;
 use secrecy::ExposeSecret;
 use uuid::Uuid;
@@ -26,6 +27,10 @@ pub fn file_routes() -> Router<AppState> {
     Router::new()
         .route("/v1/logo/:uid", get(get_logo))
         .route("/v1/invoice/pdf/:invoice_uid", get(get_invoice_pdf))
+        .route(
+            "/v1/credit-note/pdf/:credit_note_uid",
+            get(get_credit_note_pdf),
+        )
 }
 
 #[axum::debug_handler]
@@ -137,3 +142,69 @@ async fn get_invoice_pdf_handler(
             .into_response()),
     }
 }
+
+#[axum::debug_handler]
+async fn get_credit_note_pdf(
+    Path(uid): Path<String>,
+    Query(params): Query<TokenParams>,
+    State(app_state): State<AppState>,
+) -> impl IntoResponse {
+    match get_credit_note_pdf_handler(uid, params, app_state).await {
+        Ok(r) => r.into_response(),
+        Err(e) => {
+            log::error!("Error handling credit note pdf: {}", e);
+            e.current_context().clone().into_response()
+        }
+    }
+}
+
+async fn get_credit_note_pdf_handler(
+    credit_note_uid: String,
+    token: TokenParams,
+    app_state: AppState,
+) -> Result<Response, errors::RestApiError> {
+    let claims = decode::<ShareableEntityClaims>(
+        &token.token,
+        &DecodingKey::from_secret(app_state.jwt_secret.expose_secret().as_bytes()),
+        &Validation::default(),
+    )
+    .map_err(|_| Report::new(errors::RestApiError::Unauthorized))?
+    .claims;
+
+    let credit_note = app_state
+        .store
+        .find_credit_note_by_id(claims.tenant_id, claims.entity_id)
+        .await
+        .change_context(errors::RestApiError::StoreError)?;
+
+    if credit_note.id.to_string() != credit_note_uid {
+        return Err(Report::new(errors::RestApiError::Forbidden));
+    }
+    match credit_note.pdf_document_id {
+        Some(uid) => {
+            let data = app_state
+                .object_store
+                .retrieve(
+                    Uuid::parse_str(&uid).change_context(errors::RestApiError::StoreError)?,
+                    Prefix::CreditNotePdf,
+                )
+                .await
+                .change_context(errors::RestApiError::ObjectStoreError)?;
+
+            Ok((
+                StatusCode::OK,
+                [
+                    ("Content-Type", "application/pdf"),
+                    ("Content-Disposition", "inline"),
+                ],
+                data,
+            )
+                .into_response())
+        }
+        None => Ok((
+            StatusCode::NOT_FOUND,
+            "No attached PDF. Generation may be pending",
+        )
+            .into_response()),
+    }
+}
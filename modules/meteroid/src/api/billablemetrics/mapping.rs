@@ -16,6 +16,9 @@ pub mod aggregation_type {
                 domain::enums::BillingMetricAggregateEnum::CountDistinct
             }
             server::AggregationType::Latest => domain::enums::BillingMetricAggregateEnum::Latest,
+            server::AggregationType::SumOverTime => {
+                domain::enums::BillingMetricAggregateEnum::SumOverTime
+            }
         }
     }
 
@@ -32,6 +35,9 @@ pub mod aggregation_type {
                 server::AggregationType::CountDistinct
             }
             domain::enums::BillingMetricAggregateEnum::Latest => server::AggregationType::Latest,
+            domain::enums::BillingMetricAggregateEnum::SumOverTime => {
+                server::AggregationType::SumOverTime
+            }
         }
     }
 
@@ -54,6 +60,9 @@ pub mod aggregation_type {
             domain::enums::BillingMetricAggregateEnum::Latest => {
                 metering::meter::AggregationType::Latest
             }
+            domain::enums::BillingMetricAggregateEnum::SumOverTime => {
+                metering::meter::AggregationType::SumOverTime
+            }
         }
     }
 }
@@ -43,6 +43,17 @@ impl BillableMetricsService for BillableMetricsComponents {
             None => (None, None, None),
         };
 
+        // the held value of a gauge is read from the aggregation key
+        if matches!(
+            aggregation_type,
+            Some(domain::enums::BillingMetricAggregateEnum::SumOverTime)
+        ) && aggregation_key.as_deref().unwrap_or_default().is_empty()
+        {
+            return Err(Status::invalid_argument(
+                "the SumOverTime aggregation requires an aggregation key",
+            ));
+        }
+
         let domain_billable_metric: BillableMetric = self
             .store
             .insert_billable_metric(domain::BillableMetricNew {
@@ -12,6 +12,10 @@ pub enum CouponApiError {
     #[code(InvalidArgument)]
     MissingArgument(String),
 
+    #[error("Invalid argument: {0}")]
+    #[code(InvalidArgument)]
+    InvalidArgument(String),
+
     #[error("Store error: {0}")]
     #[code(Internal)]
     StoreError(String, #[source] Box<dyn Error>),
@@ -23,3 +27,9 @@ impl From<Report<StoreError>> for CouponApiError {
         Self::StoreError("Error in api coupon service".to_string(), err)
     }
 }
+
+impl From<tonic::Status> for CouponApiError {
+    fn from(value: tonic::Status) -> Self {
+        Self::InvalidArgument(value.message().to_string())
+    }
+}
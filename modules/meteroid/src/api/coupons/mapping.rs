@@ -13,6 +13,10 @@ pub mod coupons {
                 discount: Some(discount::to_server(&value.discount)),
                 expires_at: value.expires_at.map(chrono_to_timestamp),
                 redemption_limit: value.redemption_limit,
+                applies_to: Some(applies_to::to_server(&value.applies_to)),
+                duration_months: value.duration_months,
+                stacking: stacking::to_server(&value.stacking).into(),
+                priority: value.priority,
             })
         }
     }
@@ -21,6 +25,95 @@ pub mod coupons {
         CouponWrapper::from(value).0
     }
 
+    pub mod stacking {
+        use meteroid_grpc::meteroid::api::coupons::v1 as server;
+        use meteroid_store::domain::enums::CouponStackingEnum;
+
+        pub fn to_server(value: &CouponStackingEnum) -> server::CouponStacking {
+            match value {
+                CouponStackingEnum::Stackable => server::CouponStacking::Stackable,
+                CouponStackingEnum::Exclusive => server::CouponStacking::Exclusive,
+                CouponStackingEnum::BestOf => server::CouponStacking::BestOf,
+            }
+        }
+
+        pub fn to_domain(value: server::CouponStacking) -> CouponStackingEnum {
+            match value {
+                server::CouponStacking::Stackable => CouponStackingEnum::Stackable,
+                server::CouponStacking::Exclusive => CouponStackingEnum::Exclusive,
+                server::CouponStacking::BestOf => CouponStackingEnum::BestOf,
+            }
+        }
+    }
+
+    pub mod applies_to {
+        use crate::api::coupons::error::CouponApiError;
+        use crate::api::shared::conversions::ProtoConv;
+        use meteroid_grpc::meteroid::api::coupons::v1 as server;
+        use meteroid_store::domain;
+        use uuid::Uuid;
+
+        pub fn to_server(value: &domain::coupons::CouponAppliesTo) -> server::CouponAppliesTo {
+            server::CouponAppliesTo {
+                plan_ids: value.plan_ids.iter().map(|x| x.as_proto()).collect(),
+                plan_version_ids: value
+                    .plan_version_ids
+                    .iter()
+                    .map(|x| x.as_proto())
+                    .collect(),
+                price_component_ids: value
+                    .price_component_ids
+                    .iter()
+                    .map(|x| x.as_proto())
+                    .collect(),
+                add_on_ids: value.add_on_ids.iter().map(|x| x.as_proto()).collect(),
+            }
+        }
+
+        pub fn to_domain(
+            value: Option<server::CouponAppliesTo>,
+        ) -> Result<domain::coupons::CouponAppliesTo, CouponApiError> {
+            let Some(value) = value else {
+                return Ok(domain::coupons::CouponAppliesTo::default());
+            };
+
+            let parse = |ids: &[String]| {
+                ids.iter()
+                    .map(Uuid::from_proto_ref)
+                    .collect::<Result<Vec<_>, _>>()
+                    .map_err(CouponApiError::from)
+            };
+
+            Ok(domain::coupons::CouponAppliesTo {
+                plan_ids: parse(&value.plan_ids)?,
+                plan_version_ids: parse(&value.plan_version_ids)?,
+                price_component_ids: parse(&value.price_component_ids)?,
+                add_on_ids: parse(&value.add_on_ids)?,
+            })
+        }
+    }
+
+    pub mod promotion_codes {
+        use crate::api::shared::conversions::ProtoConv;
+        use crate::api::shared::mapping::datetime::chrono_to_timestamp;
+        use meteroid_grpc::meteroid::api::coupons::v1 as server;
+        use meteroid_store::domain;
+
+        pub fn to_server(value: domain::coupons::PromotionCode) -> server::PromotionCode {
+            server::PromotionCode {
+                id: value.id.as_proto(),
+                code: value.code,
+                coupon_id: value.coupon_id.as_proto(),
+                customer_id: value.customer_id.map(|x| x.as_proto()),
+                expires_at: value.expires_at.map(chrono_to_timestamp),
+                redemption_limit: value.redemption_limit,
+                redemption_count: value.redemption_count,
+                archived_at: value.archived_at.map(chrono_to_timestamp),
+                created_at: Some(chrono_to_timestamp(value.created_at)),
+            }
+        }
+    }
+
     pub mod discount {
         use crate::api::shared::conversions::ProtoConv;
         use meteroid_grpc::meteroid::api::coupons::v1 as server;
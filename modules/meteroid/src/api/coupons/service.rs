@@ -2,12 +2,18 @@ use crate::api::coupons::error::CouponApiError;
 use crate::api::coupons::mapping::coupons::CouponWrapper;
 use crate::api::coupons::{mapping, CouponsServiceComponents};
 use crate::api::shared::mapping::datetime::chrono_from_timestamp;
-use crate::{api::utils::parse_uuid, parse_uuid};
+use crate::{
+    api::utils::{parse_uuid, parse_uuid_opt},
+    parse_uuid,
+};
 use common_grpc::middleware::server::auth::RequestExt;
 use meteroid_grpc::meteroid::api::coupons::v1::coupons_service_server::CouponsService;
 use meteroid_grpc::meteroid::api::coupons::v1::{
-    CreateCouponRequest, CreateCouponResponse, EditCouponRequest, EditCouponResponse,
-    ListCouponRequest, ListCouponResponse, RemoveCouponRequest, RemoveCouponResponse,
+    ArchivePromotionCodeRequest, ArchivePromotionCodeResponse, CreateCouponRequest,
+    CreateCouponResponse, CreatePromotionCodeRequest, CreatePromotionCodeResponse,
+    EditCouponRequest, EditCouponResponse, ListCouponRequest, ListCouponResponse,
+    ListPromotionCodesRequest, ListPromotionCodesResponse, RemoveCouponRequest,
+    RemoveCouponResponse,
 };
 use meteroid_store::domain;
 use meteroid_store::repositories::coupons::CouponInterface;
@@ -45,7 +51,9 @@ impl CouponsService for CouponsServiceComponents {
 
         let req = request.into_inner();
 
+        let stacking = mapping::coupons::stacking::to_domain(req.stacking());
         let discount = mapping::coupons::discount::to_domain(req.discount)?;
+        let applies_to = mapping::coupons::applies_to::to_domain(req.applies_to)?;
 
         let new = domain::coupons::CouponNew {
             code: req.code,
@@ -56,6 +64,10 @@ impl CouponsService for CouponsServiceComponents {
             tenant_id,
             recurring_value: req.recurring_value,
             reusable: req.reusable,
+            applies_to,
+            duration_months: req.duration_months,
+            stacking,
+            priority: req.priority,
         };
 
         let added = self
@@ -98,6 +110,10 @@ impl CouponsService for CouponsServiceComponents {
 
         let req = request.into_inner();
 
+        let stacking = req
+            .stacking
+            .is_some()
+            .then(|| mapping::coupons::stacking::to_domain(req.stacking()));
         let discount = mapping::coupons::discount::to_domain(req.discount)?;
 
         let patch = domain::coupons::CouponPatch {
@@ -105,6 +121,8 @@ impl CouponsService for CouponsServiceComponents {
             tenant_id,
             description: Some(req.description),
             discount: Some(discount),
+            stacking,
+            priority: req.priority,
             updated_at: chrono::Utc::now().naive_utc(),
         };
 
@@ -119,4 +137,78 @@ impl CouponsService for CouponsServiceComponents {
             coupon: Some(updated),
         }))
     }
+
+    #[tracing::instrument(skip_all)]
+    async fn list_promotion_codes(
+        &self,
+        request: Request<ListPromotionCodesRequest>,
+    ) -> Result<Response<ListPromotionCodesResponse>, Status> {
+        let tenant_id = request.tenant()?;
+
+        let req = request.into_inner();
+
+        let promotion_codes = self
+            .store
+            .list_promotion_codes(tenant_id, parse_uuid!(&req.coupon_id)?)
+            .await
+            .map_err(Into::<CouponApiError>::into)?
+            .into_iter()
+            .map(mapping::coupons::promotion_codes::to_server)
+            .collect();
+
+        Ok(Response::new(ListPromotionCodesResponse {
+            promotion_codes,
+        }))
+    }
+
+    #[tracing::instrument(skip_all)]
+    async fn create_promotion_code(
+        &self,
+        request: Request<CreatePromotionCodeRequest>,
+    ) -> Result<Response<CreatePromotionCodeResponse>, Status> {
+        let tenant_id = request.tenant()?;
+
+        let req = request.into_inner();
+
+        let new = domain::coupons::PromotionCodeNew {
+            code: req.code,
+            coupon_id: parse_uuid!(&req.coupon_id)?,
+            tenant_id,
+            customer_id: parse_uuid_opt(&req.customer_id, "customer_id")?,
+            expires_at: req.expires_at.map(chrono_from_timestamp).transpose()?,
+            redemption_limit: req.redemption_limit,
+        };
+
+        let added = self
+            .store
+            .create_promotion_code(new)
+            .await
+            .map(mapping::coupons::promotion_codes::to_server)
+            .map_err(Into::<CouponApiError>::into)?;
+
+        Ok(Response::new(CreatePromotionCodeResponse {
+            promotion_code: Some(added),
+        }))
+    }
+
+    #[tracing::instrument(skip_all)]
+    async fn archive_promotion_code(
+        &self,
+        request: Request<ArchivePromotionCodeRequest>,
+    ) -> Result<Response<ArchivePromotionCodeResponse>, Status> {
+        let tenant_id = request.tenant()?;
+
+        let req = request.into_inner();
+
+        let archived = self
+            .store
+            .archive_promotion_code(tenant_id, parse_uuid!(&req.id)?)
+            .await
+            .map(mapping::coupons::promotion_codes::to_server)
+            .map_err(Into::<CouponApiError>::into)?;
+
+        Ok(Response::new(ArchivePromotionCodeResponse {
+            promotion_code: Some(archived),
+        }))
+    }
 }
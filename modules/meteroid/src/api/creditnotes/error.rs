@@ -0,0 +1,45 @@
+use std::error::Error;
+
+use error_stack::Report;
+use thiserror::Error;
+
+use common_grpc_error_as_tonic_macros_impl::ErrorAsTonic;
+use meteroid_store::errors::StoreError;
+
+#[derive(Debug, Error, ErrorAsTonic)]
+pub enum CreditNoteApiError {
+    #[error("Invalid argument: {0}")]
+    #[code(InvalidArgument)]
+    InvalidArgument(String),
+
+    #[error("{0}")]
+    #[code(FailedPrecondition)]
+    FailedPrecondition(String),
+
+    #[error("Store error: {0}")]
+    #[code(Internal)]
+    StoreError(String, #[source] Box<dyn Error>),
+}
+
+impl From<Report<StoreError>> for CreditNoteApiError {
+    fn from(value: Report<StoreError>) -> Self {
+        let mut err = value.current_context();
+
+        loop {
+            if let StoreError::TransactionStoreError(inner_report) = err {
+                err = inner_report.current_context();
+                continue;
+            }
+            return match err {
+                StoreError::InvalidArgument(str) => Self::InvalidArgument(str.clone()),
+                StoreError::NegativeCustomerBalanceError(_) => Self::FailedPrecondition(
+                    "the credited amount was already used by the customer".into(),
+                ),
+                _ => Self::StoreError(
+                    "Error in credit note service".to_string(),
+                    Box::new(value.into_error()),
+                ),
+            };
+        }
+    }
+}
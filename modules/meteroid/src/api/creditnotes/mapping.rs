@@ -0,0 +1,76 @@
+pub mod credit_notes {
+    use crate::api::invoices::mapping::invoices::line_item_domain_to_server;
+    use crate::api::sharable::generate_sharing_key;
+    use crate::api::shared::conversions::{AsProtoOpt, ProtoConv};
+    use meteroid_grpc::meteroid::api::creditnotes::v1 as server;
+    use meteroid_store::domain;
+    use meteroid_store::errors::StoreError;
+    use secrecy::SecretString;
+
+    fn status_domain_to_server(value: domain::enums::CreditNoteStatus) -> server::CreditNoteStatus {
+        match value {
+            domain::enums::CreditNoteStatus::Draft => server::CreditNoteStatus::Draft,
+            domain::enums::CreditNoteStatus::Finalized => server::CreditNoteStatus::Finalized,
+            domain::enums::CreditNoteStatus::Voided => server::CreditNoteStatus::Voided,
+        }
+    }
+
+    pub fn status_server_to_domain(
+        value: server::CreditNoteStatus,
+    ) -> domain::enums::CreditNoteStatus {
+        match value {
+            server::CreditNoteStatus::Draft => domain::enums::CreditNoteStatus::Draft,
+            server::CreditNoteStatus::Finalized => domain::enums::CreditNoteStatus::Finalized,
+            server::CreditNoteStatus::Voided => domain::enums::CreditNoteStatus::Voided,
+        }
+    }
+
+    fn credit_type_domain_to_server(value: domain::CreditNoteCreditType) -> server::CreditType {
+        match value {
+            domain::CreditNoteCreditType::CreditToBalance => server::CreditType::CreditToBalance,
+            domain::CreditNoteCreditType::Refund => server::CreditType::Refund,
+        }
+    }
+
+    pub fn credit_type_server_to_domain(value: server::CreditType) -> domain::CreditNoteCreditType {
+        match value {
+            server::CreditType::CreditToBalance => domain::CreditNoteCreditType::CreditToBalance,
+            server::CreditType::Refund => domain::CreditNoteCreditType::Refund,
+        }
+    }
+
+    pub fn domain_to_server(
+        value: domain::CreditNote,
+        jwt_secret: &SecretString,
+    ) -> error_stack::Result<server::CreditNote, StoreError> {
+        let document_sharing_key = match value.pdf_document_id {
+            Some(_) => Some(generate_sharing_key(value.id, value.tenant_id, jwt_secret)?),
+            None => None,
+        };
+
+        Ok(server::CreditNote {
+            id: value.id.as_proto(),
+            credit_note_number: value.credit_note_number.clone(),
+            status: status_domain_to_server(value.status).into(),
+            invoice_id: value.invoice_id.as_proto(),
+            customer_id: value.customer_id.as_proto(),
+            currency: value.currency.clone(),
+            credit_type: credit_type_domain_to_server(value.credit_type()).into(),
+            subtotal: value.subtotal,
+            tax_amount: value.tax_amount,
+            total: value.total,
+            line_items: value
+                .line_items
+                .into_iter()
+                .map(line_item_domain_to_server)
+                .collect(),
+            reason: value.reason,
+            memo: value.memo,
+            created_at: value.created_at.as_proto(),
+            finalized_at: value.finalized_at.as_proto(),
+            voided_at: value.voided_at.as_proto(),
+            pdf_document_id: value.pdf_document_id,
+            document_sharing_key,
+        })
+    }
+}
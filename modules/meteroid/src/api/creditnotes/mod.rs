@@ -0,0 +1,21 @@
+use meteroid_grpc::meteroid::api::creditnotes::v1::credit_notes_service_server::CreditNotesServiceServer;
+use meteroid_store::Store;
+use secrecy::SecretString;
+
+mod error;
+pub mod mapping;
+mod service;
+
+pub struct CreditNoteServiceComponents {
+    pub store: Store,
+    pub jwt_secret: SecretString,
+}
+
+pub fn service(
+    store: Store,
+    jwt_secret: SecretString,
+) -> CreditNotesServiceServer<CreditNoteServiceComponents> {
+    let inner = CreditNoteServiceComponents { store, jwt_secret };
+
+    CreditNotesServiceServer::new(inner)
+}
@@ -0,0 +1,160 @@
+use tonic::{Request, Response, Status};
+
+use common_grpc::middleware::server::auth::RequestExt;
+use meteroid_grpc::meteroid::api::creditnotes::v1::create_credit_note_request::Scope;
+use meteroid_grpc::meteroid::api::creditnotes::v1::{
+    credit_notes_service_server::CreditNotesService, CreateCreditNoteRequest,
+    CreateCreditNoteResponse, CreditNoteStatus, FinalizeCreditNoteRequest,
+    FinalizeCreditNoteResponse, ListCreditNotesRequest, ListCreditNotesResponse,
+    VoidCreditNoteRequest, VoidCreditNoteResponse,
+};
+use meteroid_store::domain;
+use meteroid_store::domain::{CreditNoteLineRequest, CreditNoteNew, CreditNoteScope};
+use meteroid_store::repositories::credit_notes::CreditNoteInterface;
+
+use crate::api::creditnotes::error::CreditNoteApiError;
+use crate::api::utils::{parse_uuid, parse_uuid_opt, PaginationExt};
+
+use super::{mapping, CreditNoteServiceComponents};
+
+#[tonic::async_trait]
+impl CreditNotesService for CreditNoteServiceComponents {
+    #[tracing::instrument(skip_all)]
+    async fn create_credit_note(
+        &self,
+        request: Request<CreateCreditNoteRequest>,
+    ) -> Result<Response<CreateCreditNoteResponse>, Status> {
+        let tenant_id = request.tenant()?;
+
+        let req = request.into_inner();
+
+        let credit_type = mapping::credit_notes::credit_type_server_to_domain(req.credit_type());
+
+        let scope = match req.scope {
+            Some(Scope::Full(_)) => CreditNoteScope::Full,
+            Some(Scope::Lines(lines)) => CreditNoteScope::Lines(
+                lines
+                    .lines
+                    .into_iter()
+                    .map(|line| CreditNoteLineRequest {
+                        local_id: line.line_id,
+                        amount: line.amount,
+                    })
+                    .collect(),
+            ),
+            Some(Scope::Amount(amount)) => CreditNoteScope::Amount(amount),
+            None => {
+                return Err(CreditNoteApiError::InvalidArgument(
+                    "a credit note scope is required".to_string(),
+                )
+                .into())
+            }
+        };
+
+        let credit_note = self
+            .store
+            .create_credit_note(CreditNoteNew {
+                tenant_id,
+                invoice_id: parse_uuid(&req.invoice_id, "invoice_id")?,
+                scope,
+                credit_type,
+                reason: req.reason,
+                memo: req.memo,
+            })
+            .await
+            .map_err(Into::<CreditNoteApiError>::into)?;
+
+        let credit_note = mapping::credit_notes::domain_to_server(credit_note, &self.jwt_secret)
+            .map_err(Into::<CreditNoteApiError>::into)?;
+
+        Ok(Response::new(CreateCreditNoteResponse {
+            credit_note: Some(credit_note),
+        }))
+    }
+
+    #[tracing::instrument(skip_all)]
+    async fn finalize_credit_note(
+        &self,
+        request: Request<FinalizeCreditNoteRequest>,
+    ) -> Result<Response<FinalizeCreditNoteResponse>, Status> {
+        let tenant_id = request.tenant()?;
+
+        let req = request.into_inner();
+
+        let credit_note = self
+            .store
+            .finalize_credit_note(parse_uuid(&req.id, "id")?, tenant_id)
+            .await
+            .and_then(|x| mapping::credit_notes::domain_to_server(x, &self.jwt_secret))
+            .map_err(Into::<CreditNoteApiError>::into)?;
+
+        Ok(Response::new(FinalizeCreditNoteResponse {
+            credit_note: Some(credit_note),
+        }))
+    }
+
+    #[tracing::instrument(skip_all)]
+    async fn void_credit_note(
+        &self,
+        request: Request<VoidCreditNoteRequest>,
+    ) -> Result<Response<VoidCreditNoteResponse>, Status> {
+        let tenant_id = request.tenant()?;
+
+        let req = request.into_inner();
+
+        let credit_note = self
+            .store
+            .void_credit_note(parse_uuid(&req.id, "id")?, tenant_id)
+            .await
+            .and_then(|x| mapping::credit_notes::domain_to_server(x, &self.jwt_secret))
+            .map_err(Into::<CreditNoteApiError>::into)?;
+
+        Ok(Response::new(VoidCreditNoteResponse {
+            credit_note: Some(credit_note),
+        }))
+    }
+
+    #[tracing::instrument(skip_all)]
+    async fn list_credit_notes(
+        &self,
+        request: Request<ListCreditNotesRequest>,
+    ) -> Result<Response<ListCreditNotesResponse>, Status> {
+        let tenant_id = request.tenant()?;
+
+        let inner = request.into_inner();
+
+        let status = inner
+            .status
+            .and_then(|x| CreditNoteStatus::try_from(x).ok())
+            .map(mapping::credit_notes::status_server_to_domain);
+
+        let pagination_req = domain::PaginationRequest {
+            page: inner.pagination.as_ref().map(|p| p.offset).unwrap_or(0),
+            per_page: inner.pagination.as_ref().map(|p| p.limit),
+        };
+
+        let res = self
+            .store
+            .list_credit_notes(
+                tenant_id,
+                parse_uuid_opt(&inner.customer_id, "customer_id")?,
+                parse_uuid_opt(&inner.invoice_id, "invoice_id")?,
+                status,
+                pagination_req,
+            )
+            .await
+            .map_err(Into::<CreditNoteApiError>::into)?;
+
+        let credit_notes = res
+            .items
+            .into_iter()
+            .map(|x| mapping::credit_notes::domain_to_server(x, &self.jwt_secret))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(Into::<CreditNoteApiError>::into)?;
+
+        Ok(Response::new(ListCreditNotesResponse {
+            pagination_meta: inner.pagination.into_response(res.total_results as u32),
+            credit_notes,
+        }))
+    }
+}
@@ -7,6 +7,10 @@ use meteroid_store::errors::StoreError;
 
 #[derive(Debug, Error, ErrorAsTonic)]
 pub enum CustomerApiError {
+    #[error("Invalid argument: {0}")]
+    #[code(InvalidArgument)]
+    InvalidArgument(String),
+
     #[error("Missing argument: {0}")]
     #[code(InvalidArgument)]
     MissingArgument(String),
@@ -41,6 +45,7 @@ impl From<Report<StoreError>> for CustomerApiError {
                 StoreError::NegativeCustomerBalanceError(_) => {
                     Self::FailedPrecondition("negative customer balance".into())
                 }
+                StoreError::InvalidArgument(str) => Self::InvalidArgument(str.clone()),
                 _ => Self::StoreError(
                     "Error in customer service".to_string(),
                     Box::new(value.into_error()),
@@ -163,10 +163,36 @@ pub mod customer {
                     .map(ServerShippingAddressWrapper::try_from)
                     .transpose()?
                     .map(|v| v.0),
+                vat_number: value.vat_number,
+                tax_status: tax_status_domain_to_server(value.tax_status).into(),
             }))
         }
     }
 
+    pub fn tax_status_domain_to_server(
+        value: domain::enums::CustomerTaxStatusEnum,
+    ) -> server::CustomerTaxStatus {
+        match value {
+            domain::enums::CustomerTaxStatusEnum::Taxable => server::CustomerTaxStatus::Taxable,
+            domain::enums::CustomerTaxStatusEnum::Exempt => server::CustomerTaxStatus::Exempt,
+            domain::enums::CustomerTaxStatusEnum::ReverseCharge => {
+                server::CustomerTaxStatus::ReverseCharge
+            }
+        }
+    }
+
+    pub fn tax_status_server_to_domain(
+        value: server::CustomerTaxStatus,
+    ) -> domain::enums::CustomerTaxStatusEnum {
+        match value {
+            server::CustomerTaxStatus::Taxable => domain::enums::CustomerTaxStatusEnum::Taxable,
+            server::CustomerTaxStatus::Exempt => domain::enums::CustomerTaxStatusEnum::Exempt,
+            server::CustomerTaxStatus::ReverseCharge => {
+                domain::enums::CustomerTaxStatusEnum::ReverseCharge
+            }
+        }
+    }
+
     pub struct ServerCustomerBriefWrapper(pub server::CustomerBrief);
 
     impl TryFrom<domain::Customer> for ServerCustomerBriefWrapper {
@@ -187,3 +213,33 @@ pub mod customer {
         }
     }
 }
+
+pub mod credit_grant {
+    use meteroid_grpc::meteroid::api::customers::v1 as server;
+    use meteroid_store::domain;
+
+    use crate::api::shared::conversions::{AsProtoOpt, ProtoConv};
+    use crate::api::shared::mapping::datetime::chrono_to_timestamp;
+
+    pub fn domain_to_server(value: domain::CreditGrant) -> server::CreditGrant {
+        server::CreditGrant {
+            id: value.id.to_string(),
+            customer_id: value.customer_id.to_string(),
+            name: value.name,
+            amount_cents: value.amount_cents,
+            remaining_cents: value.remaining_cents,
+            currency: value.currency,
+            priority: value.priority,
+            effective_date: value.effective_date.as_proto(),
+            expiry_date: value.expiry_date.as_proto(),
+            price_component_ids: value
+                .price_component_ids
+                .iter()
+                .map(|id| id.to_string())
+                .collect(),
+            metric_ids: value.metric_ids.iter().map(|id| id.to_string()).collect(),
+            created_at: Some(chrono_to_timestamp(value.created_at)),
+            expired_at: value.expired_at.map(chrono_to_timestamp),
+        }
+    }
+}
@@ -1,3 +1,4 @@
+use chrono::NaiveDate;
 use error_stack::Report;
 use tonic::{Request, Response, Status};
 use uuid::Uuid;
@@ -6,22 +7,27 @@ use common_grpc::middleware::server::auth::RequestExt;
 use meteroid_grpc::meteroid::api::customers::v1::list_customer_request::SortBy;
 use meteroid_grpc::meteroid::api::customers::v1::{
     customers_service_server::CustomersService, BuyCustomerCreditsRequest,
-    BuyCustomerCreditsResponse, CreateCustomerRequest, CreateCustomerResponse, CustomerBrief,
-    GetCustomerByAliasRequest, GetCustomerByAliasResponse, GetCustomerByIdRequest,
-    GetCustomerByIdResponse, ListCustomerRequest, ListCustomerResponse, PatchCustomerRequest,
-    PatchCustomerResponse, TopUpCustomerBalanceRequest, TopUpCustomerBalanceResponse,
+    BuyCustomerCreditsResponse, CreateCreditGrantRequest, CreateCreditGrantResponse,
+    CreateCustomerRequest, CreateCustomerResponse, CustomerBrief, GetCustomerByAliasRequest,
+    GetCustomerByAliasResponse, GetCustomerByIdRequest, GetCustomerByIdResponse,
+    ListCreditGrantsRequest, ListCreditGrantsResponse, ListCustomerRequest, ListCustomerResponse,
+    PatchCustomerRequest, PatchCustomerResponse, TopUpCustomerBalanceRequest,
+    TopUpCustomerBalanceResponse,
 };
 use meteroid_store::domain;
 use meteroid_store::domain::{
-    CustomerBuyCredits, CustomerNew, CustomerPatch, CustomerTopUpBalance, OrderByRequest,
+    CreditGrantNew, CustomerBuyCredits, CustomerNew, CustomerPatch, CustomerTopUpBalance,
+    OrderByRequest,
 };
 use meteroid_store::errors::StoreError;
+use meteroid_store::repositories::credit_grants::CreditGrantInterface;
 use meteroid_store::repositories::CustomersInterface;
 
 use crate::api::customers::error::CustomerApiError;
+use crate::api::customers::mapping::credit_grant;
 use crate::api::customers::mapping::customer::{
-    DomainAddressWrapper, DomainBillingConfigWrapper, DomainShippingAddressWrapper,
-    ServerCustomerBriefWrapper, ServerCustomerWrapper,
+    tax_status_server_to_domain, DomainAddressWrapper, DomainBillingConfigWrapper,
+    DomainShippingAddressWrapper, ServerCustomerBriefWrapper, ServerCustomerWrapper,
 };
 use crate::api::shared::conversions::FromProtoOpt;
 use crate::api::utils::parse_uuid;
@@ -44,6 +50,8 @@ impl CustomersService for CustomerServiceComponents {
             .data
             .ok_or(CustomerApiError::MissingArgument("no data".into()))?;
 
+        let tax_status = tax_status_server_to_domain(inner.tax_status());
+
         let billing_config = match inner.billing_config {
             Some(b) => DomainBillingConfigWrapper::try_from(b)?.0,
             None => domain::BillingConfig::Manual,
@@ -70,6 +78,8 @@ impl CustomersService for CustomerServiceComponents {
                 .map(DomainShippingAddressWrapper::try_from)
                 .transpose()?
                 .map(|v| v.0),
+            vat_number: inner.vat_number,
+            tax_status,
             force_created_date: None,
         };
 
@@ -101,6 +111,10 @@ impl CustomersService for CustomerServiceComponents {
                 "customer payload missing".to_string(),
             ))?;
 
+        let tax_status = customer
+            .tax_status
+            .map(|_| tax_status_server_to_domain(customer.tax_status()));
+
         let _ = self
             .store
             .patch_customer(
@@ -122,6 +136,8 @@ impl CustomersService for CustomerServiceComponents {
                     shipping_address: customer
                         .shipping_address
                         .map(|s| serde_json::to_value(s).unwrap()),
+                    vat_number: customer.vat_number.clone(),
+                    tax_status,
                 },
             )
             .await
@@ -279,4 +295,86 @@ impl CustomersService for CustomerServiceComponents {
             invoice: Some(invoice),
         }))
     }
+
+    #[tracing::instrument(skip_all)]
+    async fn create_credit_grant(
+        &self,
+        request: Request<CreateCreditGrantRequest>,
+    ) -> Result<Response<CreateCreditGrantResponse>, Status> {
+        let actor = request.actor()?;
+        let tenant_id = request.tenant()?;
+
+        let req = request.into_inner();
+        let customer_id = parse_uuid(&req.customer_id, "customer_id")?;
+
+        let customer = self
+            .store
+            .find_customer_by_id(customer_id, tenant_id)
+            .await
+            .map_err(Into::<CustomerApiError>::into)?;
+
+        let price_component_ids = req
+            .price_component_ids
+            .iter()
+            .map(|id| Uuid::parse_str(id))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| {
+                Status::invalid_argument(format!("Failed to parse price_component_ids: {}", e))
+            })?;
+
+        let metric_ids = req
+            .metric_ids
+            .iter()
+            .map(|id| Uuid::parse_str(id))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| Status::invalid_argument(format!("Failed to parse metric_ids: {}", e)))?;
+
+        let effective_date = NaiveDate::from_proto_opt(req.effective_date)?
+            .unwrap_or_else(|| chrono::Utc::now().date_naive());
+
+        let grant = self
+            .store
+            .create_credit_grant(CreditGrantNew {
+                tenant_id,
+                customer_id,
+                name: req.name,
+                amount_cents: req.amount_cents,
+                currency: customer.currency,
+                priority: req.priority,
+                effective_date,
+                expiry_date: NaiveDate::from_proto_opt(req.expiry_date)?,
+                price_component_ids,
+                metric_ids,
+                created_by: Some(actor),
+            })
+            .await
+            .map(credit_grant::domain_to_server)
+            .map_err(Into::<CustomerApiError>::into)?;
+
+        Ok(Response::new(CreateCreditGrantResponse {
+            credit_grant: Some(grant),
+        }))
+    }
+
+    #[tracing::instrument(skip_all)]
+    async fn list_credit_grants(
+        &self,
+        request: Request<ListCreditGrantsRequest>,
+    ) -> Result<Response<ListCreditGrantsResponse>, Status> {
+        let tenant_id = request.tenant()?;
+
+        let req = request.into_inner();
+        let customer_id = parse_uuid(&req.customer_id, "customer_id")?;
+
+        let credit_grants = self
+            .store
+            .list_credit_grants(tenant_id, customer_id)
+            .await
+            .map_err(Into::<CustomerApiError>::into)?
+            .into_iter()
+            .map(credit_grant::domain_to_server)
+            .collect();
+
+        Ok(Response::new(ListCreditGrantsResponse { credit_grants }))
+    }
 }
@@ -1,5 +1,6 @@
 use tonic::{Request, Response, Status};
 
+use common_domain::money::Currency;
 use common_grpc::middleware::server::auth::RequestExt;
 use meteroid_grpc::meteroid::api::instance::v1::get_countries_response::Country as GrpcCountry;
 use meteroid_grpc::meteroid::api::instance::v1::get_currencies_response::Currency as GrpcCurrency;
@@ -74,7 +75,9 @@ impl InstanceService for InstanceServiceComponents {
                 code: currency.code.to_string(),
                 name: currency.name.to_string(),
                 symbol: currency.symbol.to_string(),
-                precision: currency.precision as u32,
+                precision: Currency::from_code(currency.code)
+                    .map(|c| c.exponent())
+                    .unwrap_or(currency.precision) as u32,
             })
             .collect();
 
@@ -4,13 +4,16 @@ use tonic::{Request, Response, Status};
 
 use meteroid_grpc::meteroid::internal::v1::internal_service_server::InternalService;
 use meteroid_grpc::meteroid::internal::v1::{
-    ResolveApiKeyRequest, ResolveApiKeyResponse, ResolveCustomerExternalIdsRequest,
-    ResolveCustomerExternalIdsResponse, ResolvedId,
+    MarkDraftInvoicesOutdatedRequest, MarkDraftInvoicesOutdatedResponse, ResolveApiKeyRequest,
+    ResolveApiKeyResponse, ResolveCustomerExternalIdsRequest, ResolveCustomerExternalIdsResponse,
+    ResolvedId,
 };
 use meteroid_store::repositories::api_tokens::ApiTokensInterface;
+use meteroid_store::repositories::InvoiceInterface;
 
 use crate::api::internal::error::InternalApiError;
 use crate::api::internal::InternalServiceComponents;
+use crate::api::shared::mapping::datetime::chrono_from_timestamp;
 use crate::{api::utils::parse_uuid, parse_uuid};
 use meteroid_store::repositories::customers::CustomersInterface;
 
@@ -72,4 +75,37 @@ impl InternalService for InternalServiceComponents {
             hash: res.hash,
         }))
     }
+
+    #[tracing::instrument(skip_all)]
+    async fn mark_draft_invoices_outdated(
+        &self,
+        request: Request<MarkDraftInvoicesOutdatedRequest>,
+    ) -> Result<Response<MarkDraftInvoicesOutdatedResponse>, Status> {
+        let inner = request.into_inner();
+
+        let tenant_id = parse_uuid!(inner.tenant_id)?;
+        let customer_ids = inner
+            .customer_ids
+            .iter()
+            .map(|id| uuid::Uuid::parse_str(id))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| {
+                Status::invalid_argument(format!("Failed to parse customer_ids: {}", e))
+            })?;
+        let since = inner
+            .since
+            .map(chrono_from_timestamp)
+            .transpose()?
+            .ok_or(Status::invalid_argument("since is required"))?;
+
+        let marked = self
+            .store
+            .mark_draft_invoices_outdated(tenant_id, customer_ids, since.date())
+            .await
+            .map_err(Into::<InternalApiError>::into)?;
+
+        Ok(Response::new(MarkDraftInvoicesOutdatedResponse {
+            marked_count: marked as u32,
+        }))
+    }
 }
@@ -3,18 +3,24 @@ use std::error::Error;
 use error_stack::Report;
 use thiserror::Error;
 
-use crate::errors::InvoicingRenderError;
+use crate::errors::{InvoicingAdapterError, InvoicingRenderError};
 use common_grpc_error_as_tonic_macros_impl::ErrorAsTonic;
 use meteroid_store::errors::StoreError;
 
 #[derive(Debug, Error, ErrorAsTonic)]
 pub enum InvoiceApiError {
+    #[error("Invalid argument: {0}")]
+    #[code(InvalidArgument)]
+    InvalidArgument(String),
     #[error("Store error: {0}")]
     #[code(Internal)]
     StoreError(String, #[source] Box<dyn Error>),
     #[error("Render error: {0}")]
     #[code(Internal)]
     RenderError(String, #[source] Box<dyn Error>),
+    #[error("Invoicing provider error: {0}")]
+    #[code(Internal)]
+    ProviderError(String, #[source] Box<dyn Error>),
 }
 
 impl From<Report<StoreError>> for InvoiceApiError {
@@ -30,3 +36,16 @@ impl From<Report<InvoicingRenderError>> for InvoiceApiError {
         Self::RenderError("Error in invoice service".to_string(), err)
     }
 }
+
+impl From<Report<InvoicingAdapterError>> for InvoiceApiError {
+    fn from(value: Report<InvoicingAdapterError>) -> Self {
+        let err = Box::new(value.into_error());
+        Self::ProviderError("Error in invoice service".to_string(), err)
+    }
+}
+
+impl From<tonic::Status> for InvoiceApiError {
+    fn from(value: tonic::Status) -> Self {
+        Self::InvalidArgument(value.message().to_string())
+    }
+}
@@ -1,16 +1,17 @@
 pub mod invoices {
     use crate::api::customers::mapping::customer::ServerAddressWrapper;
-    use crate::api::sharable::ShareableEntityClaims;
-    use crate::api::shared::conversions::{AsProtoOpt, ProtoConv};
-    use error_stack::ResultExt;
+    use crate::api::invoices::error::InvoiceApiError;
+    use crate::api::sharable::generate_sharing_key;
+    use crate::api::shared::conversions::{AsProtoOpt, FromProtoOpt, ProtoConv};
+    use crate::api::taxes::mapping::taxes::breakdown_domain_to_server;
     use meteroid_grpc::meteroid::api::invoices::v1::{
-        DetailedInvoice, InlineCustomer, Invoice, InvoiceStatus, InvoiceType, InvoicingProvider,
-        LineItem,
+        CouponLineItem, DetailedInvoice, InlineCustomer, Invoice, InvoiceStatus, InvoiceType,
+        InvoicingProvider, LineItem, ManualLineItem,
     };
     use meteroid_store::domain;
     use meteroid_store::domain::invoice_lines as domain_invoice_lines;
     use meteroid_store::errors::StoreError;
-    use secrecy::{ExposeSecret, SecretString};
+    use secrecy::SecretString;
 
     fn status_domain_to_server(value: domain::enums::InvoiceStatusEnum) -> InvoiceStatus {
         match value {
@@ -51,6 +52,7 @@ pub mod invoices {
             domain::enums::InvoiceType::OneOff => InvoiceType::OneOff,
             domain::enums::InvoiceType::UsageThreshold => InvoiceType::UsageThreshold,
             domain::enums::InvoiceType::Adjustment => InvoiceType::Adjustment,
+            domain::enums::InvoiceType::Commitment => InvoiceType::Commitment,
         }
     }
 
@@ -61,101 +63,19 @@ pub mod invoices {
         let domain::DetailedInvoice { invoice, .. } = value;
 
         let share_key = if invoice.pdf_document_id.is_some() || invoice.xml_document_id.is_some() {
-            // encode InvoiceShareableClaims
-
-            let exp = chrono::Utc::now().timestamp() as usize + 60 * 60 * 24 * 7; // 7 days
-            let claims = ShareableEntityClaims {
-                exp,
-                sub: invoice.id.to_string(),
-                entity_id: invoice.id,
-                tenant_id: invoice.tenant_id,
-            };
-
-            let encoded = jsonwebtoken::encode(
-                &jsonwebtoken::Header::default(),
-                &claims,
-                &jsonwebtoken::EncodingKey::from_secret(jwt_secret.expose_secret().as_bytes()),
-            )
-            .change_context(StoreError::CryptError(
-                "Failed to encode shareable claims".to_string(),
-            ))?;
-
-            Some(encoded)
+            Some(generate_sharing_key(
+                invoice.id,
+                invoice.tenant_id,
+                &jwt_secret,
+            )?)
         } else {
             None
         };
 
-        let line_items: Vec<LineItem> = invoice.line_items.into_iter()
-            .map(|line| {
-                LineItem {
-                    id: line.local_id,
-                    name: line.name,
-                    subtotal: line.subtotal,
-                    metric_id: line.metric_id.as_proto(),
-                    price_component_id: line.price_component_id.as_proto(),
-                    end_date: line.end_date.as_proto(),
-                    start_date: line.start_date.as_proto(),
-                    quantity: line.quantity.as_proto(),
-                    total: line.total,
-                    unit_price: line.unit_price.as_proto(),
-                    is_prorated: line.is_prorated,
-                    product_id: line.product_id.as_proto(),
-                    description: line.description,
-                    sub_line_items: line.sub_lines.into_iter().map(
-                        |sub_line| {
-                            let attributes = match sub_line.attributes {
-                                Some(domain_invoice_lines::SubLineAttributes::Package { raw_usage }) => {
-                                    Some(meteroid_grpc::meteroid::api::invoices::v1::sub_line_item::SublineAttributes::Package(
-                                        meteroid_grpc::meteroid::api::invoices::v1::sub_line_item::Package {
-                                            raw_usage: raw_usage.as_proto()
-                                        }
-                                    ))
-                                }
-                                Some(domain_invoice_lines::SubLineAttributes::Tiered { first_unit, last_unit, flat_cap, flat_fee }) => {
-                                    Some(meteroid_grpc::meteroid::api::invoices::v1::sub_line_item::SublineAttributes::Tiered(
-                                        meteroid_grpc::meteroid::api::invoices::v1::sub_line_item::TieredOrVolume {
-                                            first_unit,
-                                            last_unit,
-                                            flat_cap: flat_cap.as_proto(),
-                                            flat_fee: flat_fee.as_proto(),
-                                        }
-                                    ))
-                                }
-                                Some(domain_invoice_lines::SubLineAttributes::Volume { first_unit, last_unit, flat_cap, flat_fee }) => {
-                                    Some(meteroid_grpc::meteroid::api::invoices::v1::sub_line_item::SublineAttributes::Volume(
-                                        meteroid_grpc::meteroid::api::invoices::v1::sub_line_item::TieredOrVolume {
-                                            first_unit,
-                                            last_unit,
-                                            flat_cap: flat_cap.as_proto(),
-                                            flat_fee: flat_fee.as_proto(),
-                                        }
-                                    ))
-                                }
-                                Some(domain_invoice_lines::SubLineAttributes::Matrix { dimension1_key, dimension1_value, dimension2_key, dimension2_value }) => {
-                                    Some(meteroid_grpc::meteroid::api::invoices::v1::sub_line_item::SublineAttributes::Matrix(
-                                        meteroid_grpc::meteroid::api::invoices::v1::sub_line_item::Matrix {
-                                            dimension1_key: dimension1_key.clone(),
-                                            dimension1_value: dimension1_value.clone(),
-                                            dimension2_key: dimension2_key.clone(),
-                                            dimension2_value: dimension2_value.clone(),
-                                        }
-                                    ))
-                                }
-                                None => None
-                            };
-
-                            meteroid_grpc::meteroid::api::invoices::v1::SubLineItem {
-                                id: sub_line.local_id.clone(),
-                                name: sub_line.name.clone(),
-                                total: sub_line.total,
-                                quantity: sub_line.quantity.as_proto(),
-                                unit_price: sub_line.unit_price.as_proto(),
-                                subline_attributes: attributes,
-                            }
-                        }
-                    ).collect(),
-                }
-            })
+        let line_items: Vec<LineItem> = invoice
+            .line_items
+            .into_iter()
+            .map(line_item_domain_to_server)
             .collect();
 
         Ok(DetailedInvoice {
@@ -182,8 +102,13 @@ pub mod invoices {
             finalized_at: invoice.finalized_at.as_proto(),
             subtotal: invoice.subtotal,
             subtotal_recurring: invoice.subtotal_recurring,
-            tax_rate: invoice.tax_rate,
+            tax_rate: invoice.tax_rate.as_proto(),
             tax_amount: invoice.tax_amount,
+            tax_breakdown: invoice
+                .tax_breakdown
+                .into_iter()
+                .map(breakdown_domain_to_server)
+                .collect(),
             total: invoice.total,
             amount_due: invoice.amount_due,
             net_terms: invoice.net_terms,
@@ -196,6 +121,7 @@ pub mod invoices {
                 id: invoice.customer_details.id.as_proto(),
                 name: invoice.customer_details.name,
                 snapshot_at: invoice.customer_details.snapshot_at.as_proto(),
+                vat_number: invoice.customer_details.vat_number,
                 billing_address: invoice
                     .customer_details
                     .billing_address
@@ -208,9 +134,108 @@ pub mod invoices {
             document_sharing_key: share_key,
             pdf_document_id: invoice.pdf_document_id,
             xml_document_id: invoice.xml_document_id,
+            replaces_invoice_id: invoice.replaces_invoice_id.as_proto(),
+            coupons: invoice
+                .coupons
+                .into_iter()
+                .map(|coupon| CouponLineItem {
+                    coupon_id: coupon.coupon_id.as_proto(),
+                    applied_coupon_id: coupon.applied_coupon_id.as_proto(),
+                    code: coupon.code,
+                    value: coupon.value,
+                })
+                .collect(),
+        })
+    }
+
+    pub fn manual_line_item_server_to_domain(
+        line: ManualLineItem,
+    ) -> Result<domain::ManualLineItem, InvoiceApiError> {
+        Ok(domain::ManualLineItem {
+            name: line.name,
+            description: line.description,
+            quantity: rust_decimal::Decimal::from_proto(line.quantity)?,
+            unit_price: rust_decimal::Decimal::from_proto(line.unit_price)?,
+            start_date: chrono::NaiveDate::from_proto(line.start_date)?,
+            end_date: chrono::NaiveDate::from_proto(line.end_date)?,
+            product_id: uuid::Uuid::from_proto_opt(line.product_id)?,
         })
     }
 
+    pub fn line_item_domain_to_server(line: domain::LineItem) -> LineItem {
+        LineItem {
+            id: line.local_id,
+            name: line.name,
+            subtotal: line.subtotal,
+            metric_id: line.metric_id.as_proto(),
+            price_component_id: line.price_component_id.as_proto(),
+            end_date: line.end_date.as_proto(),
+            start_date: line.start_date.as_proto(),
+            quantity: line.quantity.as_proto(),
+            total: line.total,
+            unit_price: line.unit_price.as_proto(),
+            is_prorated: line.is_prorated,
+            product_id: line.product_id.as_proto(),
+            description: line.description,
+            tax_rate: line.tax_rate.as_proto(),
+            tax_amount: line.tax_amount,
+            is_manual: line.is_manual,
+            sub_line_items: line.sub_lines.into_iter().map(
+                |sub_line| {
+                    let attributes = match sub_line.attributes {
+                        Some(domain_invoice_lines::SubLineAttributes::Package { raw_usage }) => {
+                            Some(meteroid_grpc::meteroid::api::invoices::v1::sub_line_item::SublineAttributes::Package(
+                                meteroid_grpc::meteroid::api::invoices::v1::sub_line_item::Package {
+                                    raw_usage: raw_usage.as_proto()
+                                }
+                            ))
+                        }
+                        Some(domain_invoice_lines::SubLineAttributes::Tiered { first_unit, last_unit, flat_cap, flat_fee }) => {
+                            Some(meteroid_grpc::meteroid::api::invoices::v1::sub_line_item::SublineAttributes::Tiered(
+                                meteroid_grpc::meteroid::api::invoices::v1::sub_line_item::TieredOrVolume {
+                                    first_unit,
+                                    last_unit,
+                                    flat_cap: flat_cap.as_proto(),
+                                    flat_fee: flat_fee.as_proto(),
+                                }
+                            ))
+                        }
+                        Some(domain_invoice_lines::SubLineAttributes::Volume { first_unit, last_unit, flat_cap, flat_fee }) => {
+                            Some(meteroid_grpc::meteroid::api::invoices::v1::sub_line_item::SublineAttributes::Volume(
+                                meteroid_grpc::meteroid::api::invoices::v1::sub_line_item::TieredOrVolume {
+                                    first_unit,
+                                    last_unit,
+                                    flat_cap: flat_cap.as_proto(),
+                                    flat_fee: flat_fee.as_proto(),
+                                }
+                            ))
+                        }
+                        Some(domain_invoice_lines::SubLineAttributes::Matrix { dimension1_key, dimension1_value, dimension2_key, dimension2_value }) => {
+                            Some(meteroid_grpc::meteroid::api::invoices::v1::sub_line_item::SublineAttributes::Matrix(
+                                meteroid_grpc::meteroid::api::invoices::v1::sub_line_item::Matrix {
+                                    dimension1_key: dimension1_key.clone(),
+                                    dimension1_value: dimension1_value.clone(),
+                                    dimension2_key: dimension2_key.clone(),
+                                    dimension2_value: dimension2_value.clone(),
+                                }
+                            ))
+                        }
+                        None => None
+                    };
+
+                    meteroid_grpc::meteroid::api::invoices::v1::SubLineItem {
+                        id: sub_line.local_id.clone(),
+                        name: sub_line.name.clone(),
+                        total: sub_line.total,
+                        quantity: sub_line.quantity.as_proto(),
+                        unit_price: sub_line.unit_price.as_proto(),
+                        subline_attributes: attributes,
+                    }
+                }
+            ).collect(),
+        }
+    }
+
     pub fn domain_to_server(value: domain::InvoiceWithCustomer) -> Invoice {
         Invoice {
             id: value.invoice.id.to_string(),
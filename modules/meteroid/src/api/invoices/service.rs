@@ -1,18 +1,29 @@
+use secrecy::SecretString;
 use tonic::{Request, Response, Status};
 
 use common_grpc::middleware::server::auth::RequestExt;
 use meteroid_grpc::meteroid::api::invoices::v1::{
-    invoices_service_server::InvoicesService, list_invoices_request::SortBy, GetInvoiceRequest,
-    GetInvoiceResponse, Invoice, ListInvoicesRequest, ListInvoicesResponse, PreviewInvoiceRequest,
-    PreviewInvoiceResponse, RefreshInvoiceDataRequest, RefreshInvoiceDataResponse,
-    RequestPdfGenerationRequest, RequestPdfGenerationResponse,
+    invoices_service_server::InvoicesService, list_invoices_request::SortBy, AddInvoiceLineRequest,
+    AddInvoiceLineResponse, CreateInvoiceRequest, CreateInvoiceResponse, DetailedInvoice,
+    GetInvoiceRequest, GetInvoiceResponse, Invoice, ListInvoicesRequest, ListInvoicesResponse,
+    PreviewInvoiceRequest, PreviewInvoiceResponse, RefreshInvoiceDataRequest,
+    RefreshInvoiceDataResponse, RemoveInvoiceLineRequest, RemoveInvoiceLineResponse,
+    RequestPdfGenerationRequest, RequestPdfGenerationResponse, UpdateInvoiceLineRequest,
+    UpdateInvoiceLineResponse, VoidInvoiceRequest, VoidInvoiceResponse,
 };
 use meteroid_store::domain;
-use meteroid_store::domain::{OrderByRequest, OutboxEvent};
+use meteroid_store::domain::enums::InvoicingProviderEnum;
+use meteroid_store::domain::{InvoiceLineEdit, OrderByRequest, OutboxEvent};
+use meteroid_store::errors::StoreError;
+use meteroid_store::repositories::configs::ConfigsInterface;
 use meteroid_store::repositories::outbox::OutboxInterface;
 use meteroid_store::repositories::InvoiceInterface;
+use meteroid_store::StoreResult;
 
+use crate::adapters::stripe::Stripe;
+use crate::adapters::types::InvoicingAdapter;
 use crate::api::invoices::error::InvoiceApiError;
+use crate::api::shared::conversions::ProtoConv;
 use crate::api::utils::parse_uuid;
 use crate::api::utils::PaginationExt;
 
@@ -178,4 +189,215 @@ impl InvoicesService for InvoiceServiceComponents {
 
         Ok(Response::new(response))
     }
+
+    #[tracing::instrument(skip_all)]
+    async fn create_invoice(
+        &self,
+        request: Request<CreateInvoiceRequest>,
+    ) -> Result<Response<CreateInvoiceResponse>, Status> {
+        let tenant_id = request.tenant()?;
+
+        let req = request.into_inner();
+
+        let line_items = req
+            .line_items
+            .into_iter()
+            .map(mapping::invoices::manual_line_item_server_to_domain)
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let invoice = self
+            .store
+            .insert_one_off_invoice(domain::OneOffInvoiceNew {
+                tenant_id,
+                customer_id: parse_uuid(&req.customer_id, "customer_id")?,
+                invoice_date: chrono::NaiveDate::from_proto(req.invoice_date)?,
+                line_items,
+                reference: req.reference,
+                memo: req.memo,
+            })
+            .await
+            .and_then(|inv| {
+                mapping::invoices::domain_invoice_with_plan_details_to_server(
+                    inv,
+                    self.jwt_secret.clone(),
+                )
+            })
+            .map_err(Into::<InvoiceApiError>::into)?;
+
+        Ok(Response::new(CreateInvoiceResponse {
+            invoice: Some(invoice),
+        }))
+    }
+
+    #[tracing::instrument(skip_all)]
+    async fn add_invoice_line(
+        &self,
+        request: Request<AddInvoiceLineRequest>,
+    ) -> Result<Response<AddInvoiceLineResponse>, Status> {
+        let tenant_id = request.tenant()?;
+
+        let req = request.into_inner();
+
+        let line = mapping::invoices::manual_line_item_server_to_domain(
+            req.line
+                .ok_or(Status::invalid_argument("line is required"))?,
+        )?;
+
+        let invoice = self
+            .edit_invoice_line(
+                tenant_id,
+                parse_uuid(&req.invoice_id, "invoice_id")?,
+                InvoiceLineEdit::Add(line),
+            )
+            .await?;
+
+        Ok(Response::new(AddInvoiceLineResponse {
+            invoice: Some(invoice),
+        }))
+    }
+
+    #[tracing::instrument(skip_all)]
+    async fn update_invoice_line(
+        &self,
+        request: Request<UpdateInvoiceLineRequest>,
+    ) -> Result<Response<UpdateInvoiceLineResponse>, Status> {
+        let tenant_id = request.tenant()?;
+
+        let req = request.into_inner();
+
+        let line = mapping::invoices::manual_line_item_server_to_domain(
+            req.line
+                .ok_or(Status::invalid_argument("line is required"))?,
+        )?;
+
+        let invoice = self
+            .edit_invoice_line(
+                tenant_id,
+                parse_uuid(&req.invoice_id, "invoice_id")?,
+                InvoiceLineEdit::Update {
+                    local_id: req.line_id,
+                    line,
+                },
+            )
+            .await?;
+
+        Ok(Response::new(UpdateInvoiceLineResponse {
+            invoice: Some(invoice),
+        }))
+    }
+
+    #[tracing::instrument(skip_all)]
+    async fn remove_invoice_line(
+        &self,
+        request: Request<RemoveInvoiceLineRequest>,
+    ) -> Result<Response<RemoveInvoiceLineResponse>, Status> {
+        let tenant_id = request.tenant()?;
+
+        let req = request.into_inner();
+
+        let invoice = self
+            .edit_invoice_line(
+                tenant_id,
+                parse_uuid(&req.invoice_id, "invoice_id")?,
+                InvoiceLineEdit::Remove {
+                    local_id: req.line_id,
+                },
+            )
+            .await?;
+
+        Ok(Response::new(RemoveInvoiceLineResponse {
+            invoice: Some(invoice),
+        }))
+    }
+
+    #[tracing::instrument(skip_all)]
+    async fn void_invoice(
+        &self,
+        request: Request<VoidInvoiceRequest>,
+    ) -> Result<Response<VoidInvoiceResponse>, Status> {
+        let tenant_id = request.tenant()?;
+        let actor = request.actor()?;
+
+        let req = request.into_inner();
+
+        let invoice_id = parse_uuid(&req.id, "id")?;
+
+        // validated and voided by the store first, the provider is called before the commit
+        let voided = self
+            .store
+            .void_invoice(
+                invoice_id,
+                tenant_id,
+                actor,
+                req.reissue,
+                |invoice| async move { self.cancel_at_provider(&invoice).await },
+            )
+            .await
+            .map_err(Into::<InvoiceApiError>::into)?;
+
+        let replacement = match voided.replacement {
+            Some(replacement) => Some(
+                mapping::invoices::domain_invoice_with_plan_details_to_server(
+                    replacement,
+                    self.jwt_secret.clone(),
+                )
+                .map_err(Into::<InvoiceApiError>::into)?,
+            ),
+            None => None,
+        };
+
+        let invoice = mapping::invoices::domain_invoice_with_plan_details_to_server(
+            voided.invoice,
+            self.jwt_secret.clone(),
+        )
+        .map_err(Into::<InvoiceApiError>::into)?;
+
+        Ok(Response::new(VoidInvoiceResponse {
+            invoice: Some(invoice),
+            replacement,
+        }))
+    }
+}
+
+impl InvoiceServiceComponents {
+    async fn cancel_at_provider(&self, invoice: &domain::Invoice) -> StoreResult<()> {
+        match invoice.invoicing_provider {
+            InvoicingProviderEnum::Stripe if invoice.issued => {
+                let api_key = self
+                    .store
+                    .find_provider_config(InvoicingProviderEnum::Stripe, invoice.tenant_id)
+                    .await?
+                    .api_security
+                    .api_key;
+
+                Stripe::get()
+                    .cancel_invoice(invoice, SecretString::new(api_key))
+                    .await
+                    .map_err(|err| {
+                        let message = err.current_context().to_string();
+                        err.change_context(StoreError::InvoicingProviderError(message))
+                    })
+            }
+            // not sent to the provider, and not picked up by the issue worker once voided
+            _ => Ok(()),
+        }
+    }
+
+    async fn edit_invoice_line(
+        &self,
+        tenant_id: uuid::Uuid,
+        invoice_id: uuid::Uuid,
+        edit: InvoiceLineEdit,
+    ) -> Result<DetailedInvoice, InvoiceApiError> {
+        self.store
+            .edit_invoice_line(invoice_id, tenant_id, edit)
+            .await
+            .and_then(|inv| {
+                mapping::invoices::domain_invoice_with_plan_details_to_server(
+                    inv,
+                    self.jwt_secret.clone(),
+                )
+            })
+            .map_err(Into::<InvoiceApiError>::into)
+    }
 }
@@ -28,6 +28,10 @@ pub enum InvoicingEntitiesApiError {
 
 impl From<Report<StoreError>> for InvoicingEntitiesApiError {
     fn from(value: Report<StoreError>) -> Self {
+        if let StoreError::InvalidArgument(str) = value.current_context() {
+            return InvoicingEntitiesApiError::InvalidArgument(str.clone());
+        }
+
         let err = Box::new(value.into_error());
         InvoicingEntitiesApiError::StoreError(
             "Error in invoicing entities service".to_string(),
@@ -8,6 +8,7 @@ pub mod invoicing_entities {
         domain::InvoicingEntityNew {
             legal_name: proto.legal_name,
             invoice_number_pattern: proto.invoice_number_pattern,
+            credit_note_number_pattern: proto.credit_note_number_pattern,
             next_invoice_number: None,
             next_credit_note_number: None,
             grace_period_hours: proto.grace_period_hours,
@@ -34,6 +35,7 @@ pub mod invoicing_entities {
             id,
             legal_name: proto.legal_name,
             invoice_number_pattern: proto.invoice_number_pattern,
+            credit_note_number_pattern: proto.credit_note_number_pattern,
             // next_invoice_number: proto.next_invoice_number,
             // next_credit_note_number: proto.next_credit_note_number,
             grace_period_hours: proto.grace_period_hours,
@@ -59,6 +61,7 @@ pub mod invoicing_entities {
             is_default: domain.is_default,
             legal_name: domain.legal_name,
             invoice_number_pattern: domain.invoice_number_pattern,
+            credit_note_number_pattern: domain.credit_note_number_pattern,
             next_invoice_number: domain.next_invoice_number,
             next_credit_note_number: domain.next_credit_note_number,
             grace_period_hours: domain.grace_period_hours,
@@ -78,3 +81,48 @@ pub mod invoicing_entities {
         }
     }
 }
+
+pub mod dunning_configs {
+    use crate::api::shared::conversions::ProtoConv;
+    use meteroid_grpc::meteroid::api::invoicingentities::v1 as server;
+    use meteroid_store::domain::dunning as domain;
+    use meteroid_store::domain::enums::DunningSubscriptionActionEnum;
+    use uuid::Uuid;
+
+    pub fn proto_to_domain(
+        proto: server::DunningConfig,
+        tenant_id: Uuid,
+        invoicing_entity_id: Uuid,
+    ) -> domain::DunningConfigNew {
+        domain::DunningConfigNew {
+            tenant_id,
+            invoicing_entity_id,
+            enabled: proto.enabled,
+            subscription_action: match proto.subscription_action() {
+                server::DunningSubscriptionAction::None => DunningSubscriptionActionEnum::None,
+                server::DunningSubscriptionAction::Pause => DunningSubscriptionActionEnum::Pause,
+                server::DunningSubscriptionAction::Cancel => DunningSubscriptionActionEnum::Cancel,
+            },
+            retry_schedule_days: proto.retry_schedule_days,
+            end_after_days: proto.end_after_days,
+            mark_uncollectible: proto.mark_uncollectible,
+        }
+    }
+
+    pub fn domain_to_proto(domain: domain::DunningConfig) -> server::DunningConfig {
+        let subscription_action = match domain.subscription_action {
+            DunningSubscriptionActionEnum::None => server::DunningSubscriptionAction::None,
+            DunningSubscriptionActionEnum::Pause => server::DunningSubscriptionAction::Pause,
+            DunningSubscriptionActionEnum::Cancel => server::DunningSubscriptionAction::Cancel,
+        };
+
+        server::DunningConfig {
+            invoicing_entity_id: domain.invoicing_entity_id.as_proto(),
+            enabled: domain.enabled,
+            retry_schedule_days: domain.retry_schedule_days,
+            end_after_days: domain.end_after_days,
+            subscription_action: subscription_action.into(),
+            mark_uncollectible: domain.mark_uncollectible,
+        }
+    }
+}
@@ -7,12 +7,14 @@ use uuid::Uuid;
 use common_grpc::middleware::server::auth::RequestExt;
 use meteroid_grpc::meteroid::api::invoicingentities::v1::{
     invoicing_entities_service_server::InvoicingEntitiesService, CreateInvoicingEntityRequest,
-    CreateInvoicingEntityResponse, GetInvoicingEntityRequest, GetInvoicingEntityResponse,
-    ListInvoicingEntitiesRequest, ListInvoicingEntitiesResponse, UpdateInvoicingEntityRequest,
-    UpdateInvoicingEntityResponse, UploadInvoicingEntityLogoRequest,
-    UploadInvoicingEntityLogoResponse,
+    CreateInvoicingEntityResponse, GetDunningConfigRequest, GetDunningConfigResponse,
+    GetInvoicingEntityRequest, GetInvoicingEntityResponse, ListInvoicingEntitiesRequest,
+    ListInvoicingEntitiesResponse, UpdateInvoicingEntityRequest, UpdateInvoicingEntityResponse,
+    UploadInvoicingEntityLogoRequest, UploadInvoicingEntityLogoResponse,
+    UpsertDunningConfigRequest, UpsertDunningConfigResponse,
 };
 use meteroid_store::domain::InvoicingEntityPatch;
+use meteroid_store::repositories::dunning::DunningInterface;
 use meteroid_store::repositories::invoicing_entities::InvoicingEntityInterface;
 
 use crate::api::invoicingentities::error::InvoicingEntitiesApiError;
@@ -167,6 +169,54 @@ impl InvoicingEntitiesService for InvoicingEntitiesServiceComponents {
             logo_uid: logo_attachment_id,
         }))
     }
+
+    #[tracing::instrument(skip_all)]
+    async fn get_dunning_config(
+        &self,
+        request: Request<GetDunningConfigRequest>,
+    ) -> Result<Response<GetDunningConfigResponse>, Status> {
+        let tenant = request.tenant()?;
+        let invoicing_entity_id = Uuid::from_proto(request.into_inner().invoicing_entity_id)?;
+
+        let config = self
+            .store
+            .get_dunning_config(tenant, invoicing_entity_id)
+            .await
+            .map_err(Into::<InvoicingEntitiesApiError>::into)?;
+
+        Ok(Response::new(GetDunningConfigResponse {
+            config: config.map(mapping::dunning_configs::domain_to_proto),
+        }))
+    }
+
+    #[tracing::instrument(skip_all)]
+    async fn upsert_dunning_config(
+        &self,
+        request: Request<UpsertDunningConfigRequest>,
+    ) -> Result<Response<UpsertDunningConfigResponse>, Status> {
+        let tenant = request.tenant()?;
+
+        let config = request
+            .into_inner()
+            .config
+            .ok_or_else(|| Status::invalid_argument("Missing config"))?;
+
+        let invoicing_entity_id = Uuid::from_proto_ref(&config.invoicing_entity_id)?;
+
+        let res = self
+            .store
+            .upsert_dunning_config(mapping::dunning_configs::proto_to_domain(
+                config,
+                tenant,
+                invoicing_entity_id,
+            ))
+            .await
+            .map_err(Into::<InvoicingEntitiesApiError>::into)?;
+
+        Ok(Response::new(UpsertDunningConfigResponse {
+            config: Some(mapping::dunning_configs::domain_to_proto(res)),
+        }))
+    }
 }
 const MAX_IMAGE_SIZE: usize = 2 * 1024 * 1024; // 2 MB
 const MAX_H: u32 = 160;
@@ -10,6 +10,7 @@ mod axum_routers;
 pub mod axum_server;
 pub mod billablemetrics;
 pub mod coupons;
+pub mod creditnotes;
 pub mod customers;
 mod domain_mapping;
 pub mod errors;
@@ -26,6 +27,7 @@ pub mod schedules;
 mod sharable;
 pub mod stats;
 pub mod subscriptions;
+pub mod taxes;
 pub mod tenants;
 pub mod users;
 pub mod webhooksout;
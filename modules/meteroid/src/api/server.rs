@@ -78,9 +78,14 @@ pub async fn start_api_server(
             store.clone(),
             config.jwt_secret.clone(),
         ))
+        .add_service(api::creditnotes::service(
+            store.clone(),
+            config.jwt_secret.clone(),
+        ))
         .add_service(api::stats::service(store.clone()))
         .add_service(api::users::service(store.clone()))
         .add_service(api::subscriptions::service(store.clone()))
+        .add_service(api::taxes::service(store.clone()))
         .add_service(api::webhooksout::service(store.clone()))
         .add_service(api::internal::service(store.clone()))
         .serve(config.grpc_listen_addr)
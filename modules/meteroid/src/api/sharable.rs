@@ -1,3 +1,6 @@
+use error_stack::ResultExt;
+use meteroid_store::errors::StoreError;
+use secrecy::{ExposeSecret, SecretString};
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
@@ -9,3 +12,26 @@ pub struct ShareableEntityClaims {
     pub tenant_id: Uuid,
     pub entity_id: Uuid,
 }
+
+pub fn generate_sharing_key(
+    entity_id: Uuid,
+    tenant_id: Uuid,
+    jwt_secret: &SecretString,
+) -> error_stack::Result<String, StoreError> {
+    let exp = chrono::Utc::now().timestamp() as usize + 60 * 60 * 24 * 7; // 7 days
+    let claims = ShareableEntityClaims {
+        exp,
+        sub: entity_id.to_string(),
+        entity_id,
+        tenant_id,
+    };
+
+    jsonwebtoken::encode(
+        &jsonwebtoken::Header::default(),
+        &claims,
+        &jsonwebtoken::EncodingKey::from_secret(jwt_secret.expose_secret().as_bytes()),
+    )
+    .change_context(StoreError::CryptError(
+        "Failed to encode shareable claims".to_string(),
+    ))
+}
@@ -35,6 +35,10 @@ pub enum SubscriptionApiError {
 
 impl From<Report<StoreError>> for SubscriptionApiError {
     fn from(value: Report<StoreError>) -> Self {
+        if let StoreError::InvalidArgument(str) = value.current_context() {
+            return Self::InvalidArgument(str.clone());
+        }
+
         let err = Box::new(value.into_error());
         Self::StoreError("Error in subscription service".to_string(), err)
     }
@@ -4,6 +4,7 @@ pub mod subscriptions {
     use meteroid_store::domain;
 
     use crate::services::subscription::ext::DbSubscriptionExt;
+    use std::collections::HashMap;
     use tonic::Status;
     use uuid::Uuid;
 
@@ -39,6 +40,10 @@ pub mod subscriptions {
             activated_at: s.activated_at.as_proto(),
             mrr_cents: s.mrr_cents,
             status,
+            paused_at: s.paused_at.as_proto(),
+            resume_at: s.resume_at.as_proto(),
+            minimum_spend: s.minimum_spend.as_proto(),
+            spend_cap: s.spend_cap.as_proto(),
         })
     }
 
@@ -59,6 +64,8 @@ pub mod subscriptions {
             invoice_memo: param.invoice_memo,
             invoice_threshold: rust_decimal::Decimal::from_proto_opt(param.invoice_threshold)?,
             activated_at: None, //NaiveDateTime::from_proto_opt(param.activated_at)?,
+            minimum_spend: rust_decimal::Decimal::from_proto_opt(param.minimum_spend)?,
+            spend_cap: rust_decimal::Decimal::from_proto_opt(param.spend_cap)?,
         };
 
         let res = domain::CreateSubscription {
@@ -81,6 +88,102 @@ pub mod subscriptions {
         Ok(res)
     }
 
+    pub(crate) fn change_plan_proto_to_domain(
+        param: proto2::ChangePlanRequest,
+    ) -> Result<domain::ChangeSubscriptionPlan, Status> {
+        let effective_at = match param.effective_at() {
+            proto2::change_plan_request::EffectiveAt::Immediate => {
+                domain::PlanChangeEffectiveAt::Immediate
+            }
+            proto2::change_plan_request::EffectiveAt::BillingPeriodEnd => {
+                domain::PlanChangeEffectiveAt::EndOfBillingPeriod
+            }
+        };
+
+        Ok(domain::ChangeSubscriptionPlan {
+            subscription_id: Uuid::from_proto(param.subscription_id)?,
+            plan_version_id: Uuid::from_proto(param.plan_version_id)?,
+            components: param
+                .components
+                .map(super::price_components::create_subscription_components_from_grpc)
+                .transpose()?,
+            effective_at,
+        })
+    }
+
+    pub(crate) fn plan_changed_domain_to_proto(
+        changed: domain::SubscriptionPlanChanged,
+    ) -> Result<proto2::ChangePlanResponse, Status> {
+        let (prorated_credit, prorated_charge) = changed
+            .proration
+            .as_ref()
+            .map(|p| (p.credit_total(), p.charge_total()))
+            .unwrap_or((0, 0));
+
+        Ok(proto2::ChangePlanResponse {
+            subscription: Some(domain_to_proto(changed.subscription)?),
+            mrr_delta: changed.mrr_delta,
+            effective_date: changed.effective_date.as_proto(),
+            prorated_credit,
+            prorated_charge,
+            adjustment_invoice_id: changed.adjustment_invoice_id.map(|id| id.as_proto()),
+        })
+    }
+
+    pub(crate) fn migrate_proto_to_domain(
+        param: proto2::MigrateSubscriptionsRequest,
+    ) -> Result<domain::MigrateSubscriptions, Status> {
+        let parse_ids = |ids: Vec<String>| -> Result<Option<Vec<Uuid>>, Status> {
+            if ids.is_empty() {
+                return Ok(None);
+            }
+            ids.into_iter()
+                .map(Uuid::from_proto)
+                .collect::<Result<Vec<_>, _>>()
+                .map(Some)
+        };
+
+        Ok(domain::MigrateSubscriptions {
+            from_plan_version_id: Uuid::from_proto(param.from_plan_version_id)?,
+            to_plan_version_id: Uuid::from_proto(param.to_plan_version_id)?,
+            subscription_ids: parse_ids(param.subscription_ids)?,
+            customer_ids: parse_ids(param.customer_ids)?,
+            dry_run: param.dry_run,
+        })
+    }
+
+    pub(crate) fn migrations_domain_to_proto(
+        migrations: Vec<domain::SubscriptionMigration>,
+    ) -> proto2::MigrateSubscriptionsResponse {
+        let total_mrr_delta = migrations
+            .iter()
+            .filter(|m| m.error.is_none())
+            .map(|m| m.mrr_delta())
+            .sum();
+
+        proto2::MigrateSubscriptionsResponse {
+            migrations: migrations
+                .into_iter()
+                .map(
+                    |m| proto2::migrate_subscriptions_response::SubscriptionMigration {
+                        subscription_id: m.subscription_id.as_proto(),
+                        customer_id: m.customer_id.as_proto(),
+                        mrr_delta: m.mrr_delta(),
+                        customer_name: m.customer_name,
+                        effective_date: m.effective_date.as_proto(),
+                        current_mrr: m.current_mrr,
+                        new_mrr: m.new_mrr,
+                        current_invoice_subtotal: m.current_invoice_subtotal,
+                        new_invoice_subtotal: m.new_invoice_subtotal,
+                        replaced_plan_version_id: m.replaced_plan_version_id.as_proto(),
+                        error: m.error,
+                    },
+                )
+                .collect(),
+            total_mrr_delta,
+        }
+    }
+
     pub(crate) fn created_domain_to_proto(
         sub: domain::CreatedSubscription,
     ) -> Result<proto2::CreatedSubscription, Status> {
@@ -106,6 +209,7 @@ pub mod subscriptions {
 
     pub(crate) fn details_domain_to_proto(
         sub: domain::SubscriptionDetails,
+        commitments: Vec<domain::SubscriptionCommitment>,
     ) -> Result<proto2::SubscriptionDetails, Status> {
         let status = sub.status_proto()? as i32;
         Ok(proto2::SubscriptionDetails {
@@ -134,6 +238,10 @@ pub mod subscriptions {
                 activated_at: sub.activated_at.as_proto(),
                 mrr_cents: sub.mrr_cents,
                 status,
+                paused_at: sub.paused_at.as_proto(),
+                resume_at: sub.resume_at.as_proto(),
+                minimum_spend: sub.minimum_spend.as_proto(),
+                spend_cap: sub.spend_cap.as_proto(),
             }),
             schedules: vec![], // TODO
             price_components: sub
@@ -160,8 +268,116 @@ pub mod subscriptions {
                 .into_iter()
                 .map(super::coupons::applied_coupon_detailed_to_grpc)
                 .collect(),
+            commitments: commitments
+                .into_iter()
+                .map(commitment_domain_to_proto)
+                .collect(),
         })
     }
+
+    pub(crate) fn commitment_domain_to_proto(
+        commitment: domain::SubscriptionCommitment,
+    ) -> proto2::SubscriptionCommitment {
+        let commitment_type = match commitment.commitment_type {
+            domain::enums::SubscriptionCommitmentTypeEnum::Prepaid => {
+                proto2::SubscriptionCommitmentType::Prepaid
+            }
+            domain::enums::SubscriptionCommitmentTypeEnum::Postpaid => {
+                proto2::SubscriptionCommitmentType::Postpaid
+            }
+        };
+
+        proto2::SubscriptionCommitment {
+            id: commitment.id.as_proto(),
+            commitment_type: commitment_type as i32,
+            amount_cents: commitment.amount_cents,
+            start_date: commitment.start_date.as_proto(),
+            end_date: commitment.end_date.as_proto(),
+            remaining_cents: commitment.remaining_cents,
+            drawn_down_cents: commitment.drawn_down_cents(),
+            invoice_id: commitment.invoice_id.map(|id| id.as_proto()),
+            unused_cents: commitment.unused_cents,
+            closed_at: commitment.closed_at.as_proto(),
+            created_at: commitment.created_at.as_proto(),
+        }
+    }
+
+    pub(crate) fn create_commitment_proto_to_domain(
+        param: proto2::CreateSubscriptionCommitmentRequest,
+        tenant_id: Uuid,
+        actor: Uuid,
+    ) -> Result<domain::SubscriptionCommitmentNew, Status> {
+        let commitment_type = match param.commitment_type() {
+            proto2::SubscriptionCommitmentType::Prepaid => {
+                domain::enums::SubscriptionCommitmentTypeEnum::Prepaid
+            }
+            proto2::SubscriptionCommitmentType::Postpaid => {
+                domain::enums::SubscriptionCommitmentTypeEnum::Postpaid
+            }
+        };
+
+        Ok(domain::SubscriptionCommitmentNew {
+            tenant_id,
+            subscription_id: Uuid::from_proto(param.subscription_id)?,
+            commitment_type,
+            amount_cents: param.amount_cents,
+            start_date: NaiveDate::from_proto(param.start_date)?,
+            end_date: NaiveDate::from_proto(param.end_date)?,
+            created_by: Some(actor),
+        })
+    }
+
+    pub(crate) fn upcoming_invoice_changes_proto_to_domain(
+        param: &proto2::GetUpcomingInvoiceRequest,
+    ) -> Result<domain::UpcomingInvoiceChanges, Status> {
+        let mut slots = HashMap::new();
+        for slot in &param.slots {
+            slots.insert(Uuid::from_proto_ref(&slot.price_component_id)?, slot.count);
+        }
+
+        let plan_version = match Uuid::from_proto_opt(param.plan_version_id.clone())? {
+            Some(plan_version_id) => Some(domain::UpcomingPlanVersion {
+                plan_version_id,
+                components: param
+                    .components
+                    .clone()
+                    .map(super::price_components::create_subscription_components_from_grpc)
+                    .transpose()?,
+            }),
+            None => None,
+        };
+
+        Ok(domain::UpcomingInvoiceChanges {
+            slots,
+            add_ons: param
+                .add_ons
+                .clone()
+                .map(super::add_ons::create_subscription_add_ons_from_grpc)
+                .transpose()?,
+            plan_version,
+        })
+    }
+
+    pub(crate) fn upcoming_invoice_domain_to_proto(
+        invoice: domain::UpcomingInvoice,
+    ) -> proto2::GetUpcomingInvoiceResponse {
+        proto2::GetUpcomingInvoiceResponse {
+            subscription_id: invoice.subscription_id.as_proto(),
+            plan_version_id: invoice.plan_version_id.as_proto(),
+            invoice_date: invoice.invoice_date.as_proto(),
+            currency: invoice.currency,
+            line_items: invoice
+                .line_items
+                .into_iter()
+                .map(crate::api::invoices::mapping::invoices::line_item_domain_to_server)
+                .collect(),
+            subtotal: invoice.subtotal,
+            tax_amount: invoice.tax_amount,
+            total: invoice.total,
+            applied_credits: invoice.applied_credits,
+            amount_due: invoice.amount_due,
+        }
+    }
 }
 
 mod price_components {
@@ -758,7 +974,10 @@ pub mod coupons {
             .map(create_subscription_coupon_from_grpc)
             .collect::<tonic::Result<Vec<_>, _>>()?;
 
-        Ok(domain::CreateSubscriptionCoupons { coupons })
+        Ok(domain::CreateSubscriptionCoupons {
+            coupons,
+            promotion_codes: data.promotion_codes.clone(),
+        })
     }
 
     pub fn create_subscription_coupon_from_grpc(
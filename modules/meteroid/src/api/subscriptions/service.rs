@@ -1,3 +1,4 @@
+use chrono::NaiveDate;
 use tonic::{Request, Response, Status};
 
 use common_grpc::middleware::server::auth::RequestExt;
@@ -5,19 +6,28 @@ use common_grpc::middleware::server::auth::RequestExt;
 use meteroid_grpc::meteroid::api::subscriptions::v1::subscriptions_service_server::SubscriptionsService;
 
 use meteroid_grpc::meteroid::api::subscriptions::v1::{
-    CancelSubscriptionRequest, CancelSubscriptionResponse, CreateSubscriptionRequest,
-    CreateSubscriptionResponse, CreateSubscriptionsRequest, CreateSubscriptionsResponse,
-    GetSlotsValueRequest, GetSlotsValueResponse, ListSubscriptionsRequest,
-    ListSubscriptionsResponse, PaginationResponse, SubscriptionDetails, UpdateSlotsRequest,
+    CancelSubscriptionRequest, CancelSubscriptionResponse, ChangePlanRequest, ChangePlanResponse,
+    CreateSubscriptionCommitmentRequest, CreateSubscriptionCommitmentResponse,
+    CreateSubscriptionRequest, CreateSubscriptionResponse, CreateSubscriptionsRequest,
+    CreateSubscriptionsResponse, GetSlotsValueRequest, GetSlotsValueResponse,
+    GetUpcomingInvoiceRequest, GetUpcomingInvoiceResponse, ListSubscriptionsRequest,
+    ListSubscriptionsResponse, MigrateSubscriptionsRequest, MigrateSubscriptionsResponse,
+    PaginationResponse, PauseSubscriptionRequest, PauseSubscriptionResponse,
+    ResumeSubscriptionRequest, ResumeSubscriptionResponse, SubscriptionDetails, UpdateSlotsRequest,
     UpdateSlotsResponse,
 };
 
 use meteroid_store::domain;
+use meteroid_store::repositories::subscription_commitments::SubscriptionCommitmentInterface;
+use meteroid_store::repositories::subscription_pauses::SubscriptionPauseInterface;
+use meteroid_store::repositories::subscription_plan_changes::SubscriptionPlanChangeInterface;
 use meteroid_store::repositories::subscriptions::{
     CancellationEffectiveAt, SubscriptionSlotsInterface,
 };
+use meteroid_store::repositories::upcoming_invoices::UpcomingInvoiceInterface;
 use meteroid_store::repositories::SubscriptionInterface;
 
+use crate::api::shared::conversions::FromProtoOpt;
 use crate::api::subscriptions::error::SubscriptionApiError;
 use crate::api::subscriptions::{mapping, SubscriptionServiceComponents};
 use crate::api::utils::{parse_uuid, parse_uuid_opt};
@@ -99,15 +109,22 @@ impl SubscriptionsService for SubscriptionServiceComponents {
 
         let inner = request.into_inner();
 
+        let subscription_id = parse_uuid!(inner.subscription_id)?;
+
         let subscription = self
             .store
-            .get_subscription_details(tenant_id, parse_uuid!(inner.subscription_id)?)
+            .get_subscription_details(tenant_id, subscription_id)
             .await
-            .map_err(Into::<SubscriptionApiError>::into)
-            .map_err(Into::<Status>::into)
-            .and_then(mapping::subscriptions::details_domain_to_proto)?;
+            .map_err(Into::<SubscriptionApiError>::into)?;
 
-        Ok(Response::new(subscription))
+        let commitments = self
+            .store
+            .list_subscription_commitments(tenant_id, subscription_id)
+            .await
+            .map_err(Into::<SubscriptionApiError>::into)?;
+
+        mapping::subscriptions::details_domain_to_proto(subscription, commitments)
+            .map(Response::new)
     }
 
     #[tracing::instrument(skip_all)]
@@ -234,4 +251,144 @@ impl SubscriptionsService for SubscriptionServiceComponents {
             })
             .map_err(Into::<Status>::into)
     }
+
+    #[tracing::instrument(skip_all)]
+    async fn pause_subscription(
+        &self,
+        request: Request<PauseSubscriptionRequest>,
+    ) -> Result<Response<PauseSubscriptionResponse>, Status> {
+        let tenant_id = request.tenant()?;
+        let inner = request.into_inner();
+
+        let subscription = self
+            .store
+            .pause_subscription(
+                tenant_id,
+                parse_uuid!(inner.subscription_id)?,
+                NaiveDate::from_proto_opt(inner.resume_at)?,
+            )
+            .await
+            .map_err(Into::<SubscriptionApiError>::into)?;
+
+        mapping::subscriptions::domain_to_proto(subscription)
+            .map(|s| {
+                Response::new(PauseSubscriptionResponse {
+                    subscription: Some(s),
+                })
+            })
+            .map_err(Into::<Status>::into)
+    }
+
+    #[tracing::instrument(skip_all)]
+    async fn resume_subscription(
+        &self,
+        request: Request<ResumeSubscriptionRequest>,
+    ) -> Result<Response<ResumeSubscriptionResponse>, Status> {
+        let tenant_id = request.tenant()?;
+        let inner = request.into_inner();
+
+        let subscription = self
+            .store
+            .resume_subscription(tenant_id, parse_uuid!(inner.subscription_id)?)
+            .await
+            .map_err(Into::<SubscriptionApiError>::into)?;
+
+        mapping::subscriptions::domain_to_proto(subscription)
+            .map(|s| {
+                Response::new(ResumeSubscriptionResponse {
+                    subscription: Some(s),
+                })
+            })
+            .map_err(Into::<Status>::into)
+    }
+
+    #[tracing::instrument(skip_all)]
+    async fn change_plan(
+        &self,
+        request: Request<ChangePlanRequest>,
+    ) -> Result<Response<ChangePlanResponse>, Status> {
+        let tenant_id = request.tenant()?;
+        let actor = request.actor()?;
+        let inner = request.into_inner();
+
+        let change = mapping::subscriptions::change_plan_proto_to_domain(inner)?;
+
+        let changed = self
+            .store
+            .change_subscription_plan(change, domain::TenantContext { tenant_id, actor })
+            .await
+            .map_err(Into::<SubscriptionApiError>::into)?;
+
+        mapping::subscriptions::plan_changed_domain_to_proto(changed)
+            .map(Response::new)
+            .map_err(Into::<Status>::into)
+    }
+
+    #[tracing::instrument(skip_all)]
+    async fn migrate_subscriptions(
+        &self,
+        request: Request<MigrateSubscriptionsRequest>,
+    ) -> Result<Response<MigrateSubscriptionsResponse>, Status> {
+        let tenant_id = request.tenant()?;
+        let actor = request.actor()?;
+        let inner = request.into_inner();
+
+        let migration = mapping::subscriptions::migrate_proto_to_domain(inner)?;
+
+        let migrations = self
+            .store
+            .migrate_subscriptions(migration, domain::TenantContext { tenant_id, actor })
+            .await
+            .map_err(Into::<SubscriptionApiError>::into)?;
+
+        Ok(Response::new(
+            mapping::subscriptions::migrations_domain_to_proto(migrations),
+        ))
+    }
+
+    #[tracing::instrument(skip_all)]
+    async fn create_subscription_commitment(
+        &self,
+        request: Request<CreateSubscriptionCommitmentRequest>,
+    ) -> Result<Response<CreateSubscriptionCommitmentResponse>, Status> {
+        let tenant_id = request.tenant()?;
+        let actor = request.actor()?;
+        let inner = request.into_inner();
+
+        let commitment =
+            mapping::subscriptions::create_commitment_proto_to_domain(inner, tenant_id, actor)?;
+
+        let created = self
+            .store
+            .create_subscription_commitment(commitment)
+            .await
+            .map_err(Into::<SubscriptionApiError>::into)?;
+
+        Ok(Response::new(CreateSubscriptionCommitmentResponse {
+            commitment: Some(mapping::subscriptions::commitment_domain_to_proto(created)),
+        }))
+    }
+
+    #[tracing::instrument(skip_all)]
+    async fn get_upcoming_invoice(
+        &self,
+        request: Request<GetUpcomingInvoiceRequest>,
+    ) -> Result<Response<GetUpcomingInvoiceResponse>, Status> {
+        let tenant_id = request.tenant()?;
+        let inner = request.into_inner();
+
+        let subscription_id = parse_uuid!(inner.subscription_id)?;
+        let invoice_date = NaiveDate::from_proto_opt(inner.invoice_date.clone())?;
+        let changes = mapping::subscriptions::upcoming_invoice_changes_proto_to_domain(&inner)?;
+
+        let invoice = self
+            .store
+            .get_upcoming_invoice(tenant_id, subscription_id, invoice_date, changes)
+            .await
+            .map_err(Into::<SubscriptionApiError>::into)?;
+
+        Ok(Response::new(
+            mapping::subscriptions::upcoming_invoice_domain_to_proto(invoice),
+        ))
+    }
 }
@@ -0,0 +1,39 @@
+pub mod taxes {
+    use meteroid_grpc::meteroid::api::taxes::v1 as server;
+    use meteroid_store::domain;
+
+    use crate::api::shared::conversions::ProtoConv;
+
+    pub struct TaxRateWrapper(pub server::TaxRate);
+    impl From<domain::TaxRate> for TaxRateWrapper {
+        fn from(value: domain::TaxRate) -> Self {
+            Self(server::TaxRate {
+                id: value.id.as_proto(),
+                name: value.name,
+                country: value.country,
+                region: value.region,
+                rate: value.rate.as_proto(),
+                created_at: value.created_at.as_proto(),
+            })
+        }
+    }
+
+    pub fn treatment_domain_to_server(value: domain::TaxTreatment) -> server::TaxTreatment {
+        match value {
+            domain::TaxTreatment::Standard => server::TaxTreatment::Standard,
+            domain::TaxTreatment::Exempt => server::TaxTreatment::Exempt,
+            domain::TaxTreatment::ReverseCharge => server::TaxTreatment::ReverseCharge,
+            domain::TaxTreatment::OutOfScope => server::TaxTreatment::OutOfScope,
+        }
+    }
+
+    pub fn breakdown_domain_to_server(value: domain::TaxBreakdownItem) -> server::TaxBreakdownItem {
+        server::TaxBreakdownItem {
+            name: value.name,
+            rate: value.rate.as_proto(),
+            treatment: treatment_domain_to_server(value.treatment).into(),
+            taxable_amount: value.taxable_amount,
+            tax_amount: value.tax_amount,
+        }
+    }
+}
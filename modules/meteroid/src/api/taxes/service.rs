@@ -0,0 +1,90 @@
+use crate::api::shared::conversions::ProtoConv;
+use crate::api::taxes::error::TaxApiError;
+use crate::api::taxes::mapping::taxes::TaxRateWrapper;
+use crate::api::taxes::TaxesServiceComponents;
+use crate::{api::utils::parse_uuid, parse_uuid};
+use common_grpc::middleware::server::auth::RequestExt;
+use meteroid_grpc::meteroid::api::taxes::v1::taxes_service_server::TaxesService;
+use meteroid_grpc::meteroid::api::taxes::v1::{
+    ArchiveTaxRateRequest, ArchiveTaxRateResponse, CreateTaxRateRequest, CreateTaxRateResponse,
+    ListTaxRatesRequest, ListTaxRatesResponse,
+};
+use meteroid_store::domain::TaxRateNew;
+use meteroid_store::repositories::taxes::TaxRateInterface;
+use rust_decimal::Decimal;
+use tonic::{Request, Response, Status};
+
+#[tonic::async_trait]
+impl TaxesService for TaxesServiceComponents {
+    #[tracing::instrument(skip_all)]
+    async fn list_tax_rates(
+        &self,
+        request: Request<ListTaxRatesRequest>,
+    ) -> Result<Response<ListTaxRatesResponse>, Status> {
+        let tenant_id = request.tenant()?;
+
+        let tax_rates = self
+            .store
+            .list_tax_rates(tenant_id)
+            .await
+            .map_err(Into::<TaxApiError>::into)?
+            .into_iter()
+            .map(|x| TaxRateWrapper::from(x).0)
+            .collect();
+
+        Ok(Response::new(ListTaxRatesResponse { tax_rates }))
+    }
+
+    #[tracing::instrument(skip_all)]
+    async fn create_tax_rate(
+        &self,
+        request: Request<CreateTaxRateRequest>,
+    ) -> Result<Response<CreateTaxRateResponse>, Status> {
+        let tenant_id = request.tenant()?;
+
+        let req = request.into_inner();
+
+        if req.country.len() != 2 {
+            return Err(TaxApiError::InvalidArgument(
+                "country must be an ISO 3166-1 alpha-2 code".into(),
+            )
+            .into());
+        }
+
+        let new = TaxRateNew {
+            tenant_id,
+            name: req.name,
+            country: req.country,
+            region: req.region.filter(|x| !x.is_empty()),
+            rate: Decimal::from_proto_ref(&req.rate)?,
+        };
+
+        let created = self
+            .store
+            .create_tax_rate(new)
+            .await
+            .map(|x| TaxRateWrapper::from(x).0)
+            .map_err(Into::<TaxApiError>::into)?;
+
+        Ok(Response::new(CreateTaxRateResponse {
+            tax_rate: Some(created),
+        }))
+    }
+
+    #[tracing::instrument(skip_all)]
+    async fn archive_tax_rate(
+        &self,
+        request: Request<ArchiveTaxRateRequest>,
+    ) -> Result<Response<ArchiveTaxRateResponse>, Status> {
+        let tenant_id = request.tenant()?;
+
+        let req = request.into_inner();
+
+        self.store
+            .archive_tax_rate(parse_uuid!(&req.id)?, tenant_id)
+            .await
+            .map_err(Into::<TaxApiError>::into)?;
+
+        Ok(Response::new(ArchiveTaxRateResponse {}))
+    }
+}
@@ -60,6 +60,12 @@ pub mod event_type {
             }
             WebhookEventTypeProto::InvoiceCreated => WebhookOutEventTypeEnum::InvoiceCreated,
             WebhookEventTypeProto::InvoiceFinalized => WebhookOutEventTypeEnum::InvoiceFinalized,
+            WebhookEventTypeProto::SubscriptionTrialEnded => {
+                WebhookOutEventTypeEnum::SubscriptionTrialEnded
+            }
+            WebhookEventTypeProto::InvoicePaymentReminder => {
+                WebhookOutEventTypeEnum::InvoicePaymentReminder
+            }
         }
     }
 
@@ -71,6 +77,12 @@ pub mod event_type {
             }
             WebhookOutEventTypeEnum::InvoiceCreated => WebhookEventTypeProto::InvoiceCreated,
             WebhookOutEventTypeEnum::InvoiceFinalized => WebhookEventTypeProto::InvoiceFinalized,
+            WebhookOutEventTypeEnum::SubscriptionTrialEnded => {
+                WebhookEventTypeProto::SubscriptionTrialEnded
+            }
+            WebhookOutEventTypeEnum::InvoicePaymentReminder => {
+                WebhookEventTypeProto::InvoicePaymentReminder
+            }
         }
     }
 }
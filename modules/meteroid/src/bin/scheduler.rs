@@ -41,7 +41,20 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             // (Box::new(PriceWorker), LockKey::InvoicingPrice),
             // (Box::new(FinalizeWorker), LockKey::InvoicingFinalize),
             // (Box::new(IssueWorker), LockKey::InvoicingIssue),
+            // (Box::new(DunningWorker), LockKey::InvoicingDunning),
+            // (
+            //     Box::new(UsageThresholdWorker),
+            //     LockKey::InvoicingUsageThreshold,
+            // ),
+            // (
+            //     Box::new(CreditGrantExpiryWorker),
+            //     LockKey::InvoicingCreditGrantExpiry,
+            // ),
             // (Box::new(CurrencyRatesWorker), LockKey::CurrencyRates),
+            // (Box::new(PlanChangeWorker), LockKey::SubscriptionsPlanChange),
+            // (Box::new(TrialWorker), LockKey::SubscriptionsTrial),
+            // (Box::new(ResumeWorker), LockKey::SubscriptionsResume),
+            // (Box::new(CommitmentWorker), LockKey::SubscriptionsCommitment),
         ],
         config,
         pool,
@@ -93,6 +93,7 @@ impl UsageClient for MeteringUsageClient {
             domain::enums::BillingMetricAggregateEnum::CountDistinct => {
                 AggregationType::CountDistinct
             }
+            domain::enums::BillingMetricAggregateEnum::SumOverTime => AggregationType::SumOverTime,
         } as i32;
 
         let filter_properties = match metric.segmentation_matrix.clone() {
@@ -142,6 +143,7 @@ impl UsageClient for MeteringUsageClient {
             tenant_id: tenant_id.to_string(),
             meter_slug: metric.id.to_string(),
             event_name: metric.code.clone(),
+            aggregation_key: metric.aggregation_key.clone(),
             meter_aggregation_type: aggregation_type,
             customers: vec![ResourceIdentifier {
                 meteroid_id: customer_id.to_string(),
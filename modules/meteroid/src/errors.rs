@@ -86,6 +86,8 @@ pub enum InvoicingAdapterError {
     GrpcError,
     #[error("Stripe call error")]
     StripeError,
+    #[error("Invoice is already paid at the provider")]
+    AlreadyPaid,
 }
 
 #[derive(Debug, thiserror::Error)]
@@ -11,6 +11,7 @@ use common_build_info::BuildInfo;
 use common_config::analytics::AnalyticsConfig;
 use common_eventbus::{
     Event, EventData, EventDataDetails, EventDataWithMetadataDetails, TenantEventDataDetails,
+    TenantEventDataWithMetadataDetails,
 };
 use common_eventbus::{EventBusError, EventHandler};
 use common_logging::unwrapper::UnwrapLogger;
@@ -259,6 +260,36 @@ impl AnalyticsHandler {
         Ok(())
     }
 
+    #[tracing::instrument(skip_all)]
+    async fn invoice_voided(
+        &self,
+        event: &Event,
+        event_data_details: &TenantEventDataWithMetadataDetails,
+    ) -> Result<(), EventBusError> {
+        let DetailedInvoice {
+            invoice, customer, ..
+        } = self
+            .store
+            .find_invoice_by_id(event_data_details.tenant_id, event_data_details.entity_id)
+            .await
+            .map_err(|e| EventBusError::EventHandlerFailed(e.to_string()))?;
+
+        self.send_track(
+            "invoice-voided".to_string(),
+            event.actor,
+            serde_json::json!({
+                "invoice_id": invoice.id,
+                "customer_id": customer.id,
+                "subscription_id": invoice.subscription_id,
+                "currency": invoice.currency,
+                "replacement_invoice_id": event_data_details.metadata.get("replacement_invoice_id"),
+            }),
+        )
+        .await;
+
+        Ok(())
+    }
+
     #[tracing::instrument(skip_all)]
     async fn plan_created_draft(
         &self,
@@ -558,6 +589,7 @@ impl EventHandler<Event> for AnalyticsHandler {
             }
             EventData::InvoiceCreated(details) => self.invoice_draft(&event, details).await?,
             EventData::InvoiceFinalized(details) => self.invoice_finalized(&event, details).await?,
+            EventData::InvoiceVoided(details) => self.invoice_voided(&event, details).await?,
             EventData::PlanCreatedDraft(details) => {
                 self.plan_created_draft(&event, details).await?
             }
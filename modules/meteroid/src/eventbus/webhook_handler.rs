@@ -5,11 +5,13 @@ use secrecy::{ExposeSecret, SecretString};
 use serde::Serialize;
 use uuid::Uuid;
 
-use common_eventbus::{Event, EventData, TenantEventDataDetails};
+use common_eventbus::{
+    Event, EventData, TenantEventDataDetails, TenantEventDataWithMetadataDetails,
+};
 use common_eventbus::{EventBusError, EventHandler};
 use meteroid_store::domain::enums::WebhookOutEventTypeEnum;
 use meteroid_store::domain::webhooks::WebhookOutEventNew;
-use meteroid_store::domain::DetailedInvoice;
+use meteroid_store::domain::{CouponLineItem, DetailedInvoice};
 use meteroid_store::repositories::webhooks::WebhooksInterface;
 use meteroid_store::repositories::{CustomersInterface, InvoiceInterface, SubscriptionInterface};
 use meteroid_store::{crypt, Store};
@@ -132,23 +134,19 @@ impl WebhookHandler {
     #[tracing::instrument(skip_all)]
     async fn get_active_endpoints(&self, event: &Event) -> Result<Vec<Endpoint>, EventBusError> {
         let event_type = get_event_type(event);
-        let details = get_tenant_event_details(event);
+        let tenant_id = get_tenant_id(event);
 
-        let endpoints = if let (Some(event_type), Some(details)) = (event_type, details) {
+        let endpoints = if let (Some(event_type), Some(tenant_id)) = (event_type, tenant_id) {
             let endpoints = if self.cache_enabled {
                 get_active_endpoints_by_tenant_cached(
                     self.store.clone(),
-                    &details.tenant_id,
+                    &tenant_id,
                     &self.crypt_key,
                 )
                 .await?
             } else {
-                get_active_endpoints_by_tenant(
-                    self.store.clone(),
-                    &details.tenant_id,
-                    &self.crypt_key,
-                )
-                .await?
+                get_active_endpoints_by_tenant(self.store.clone(), &tenant_id, &self.crypt_key)
+                    .await?
             };
 
             endpoints
@@ -217,6 +215,46 @@ impl WebhookHandler {
         Ok(event)
     }
 
+    #[tracing::instrument(skip_all)]
+    async fn subscription_trial_ended_webhook(
+        &self,
+        event: &Event,
+        event_data_details: &TenantEventDataWithMetadataDetails,
+    ) -> Result<WebhookEvent, EventBusError> {
+        let subscription = self
+            .store
+            .get_subscription_details(event_data_details.tenant_id, event_data_details.entity_id)
+            .await
+            .map_err(|e| EventBusError::EventHandlerFailed(e.to_string()))?;
+
+        let action_after_trial = event_data_details
+            .metadata
+            .get("action_after_trial")
+            .cloned()
+            .ok_or_else(|| {
+                EventBusError::EventHandlerFailed("Missing action after trial".to_string())
+            })?;
+
+        let event = WebhookEvent {
+            event_type: "subscription.trial_ended".to_string(),
+            timestamp: event.event_timestamp,
+            data: to_json(SubscriptionTrialEndedData {
+                subscription: SubscriptionData {
+                    customer_name: subscription.customer_name,
+                    billing_day: subscription.billing_day,
+                    billing_start_date: subscription.billing_start_date,
+                    billing_end_date: subscription.billing_end_date,
+                    currency: subscription.currency,
+                    net_terms: subscription.net_terms,
+                },
+                plan_name: subscription.plan_name,
+                action_after_trial,
+            })?,
+        };
+
+        Ok(event)
+    }
+
     #[tracing::instrument(skip_all)]
     async fn invoice_draft_webhook(
         &self,
@@ -243,6 +281,7 @@ impl WebhookHandler {
                 invoice_date: invoice.invoice_date,
                 amount_cents: Some(invoice.total),
                 plan_name: plan.map(|p| p.plan_name),
+                coupons: invoice.coupons.into_iter().map(Into::into).collect(),
             })?,
         };
 
@@ -273,6 +312,50 @@ impl WebhookHandler {
                 invoice_date: invoice.invoice_date,
                 amount_cents: Some(invoice.total),
                 plan_name: invoice.plan_name,
+                coupons: invoice.coupons.into_iter().map(Into::into).collect(),
+            })?,
+        };
+
+        Ok(event)
+    }
+
+    #[tracing::instrument(skip_all)]
+    async fn invoice_payment_reminder_webhook(
+        &self,
+        event: &Event,
+        event_data_details: &TenantEventDataWithMetadataDetails,
+    ) -> Result<WebhookEvent, EventBusError> {
+        let DetailedInvoice {
+            invoice, customer, ..
+        } = self
+            .store
+            .find_invoice_by_id(event_data_details.tenant_id, event_data_details.entity_id)
+            .await
+            .map_err(|e| EventBusError::EventHandlerFailed(e.to_string()))?;
+
+        let attempt = event_data_details
+            .metadata
+            .get("attempt")
+            .and_then(|attempt| attempt.parse().ok())
+            .ok_or_else(|| EventBusError::EventHandlerFailed("Missing attempt".to_string()))?;
+
+        let event = WebhookEvent {
+            event_type: "invoice.payment_reminder".to_string(),
+            timestamp: event.event_timestamp,
+            data: to_json(InvoicePaymentReminderData {
+                invoice: InvoiceData {
+                    customer_name: customer.name,
+                    currency: invoice.currency,
+                    status: "finalized".to_string(),
+                    invoice_date: invoice.invoice_date,
+                    amount_cents: Some(invoice.total),
+                    plan_name: invoice.plan_name,
+                    coupons: invoice.coupons.into_iter().map(Into::into).collect(),
+                },
+                customer_email: customer.invoicing_email.or(customer.email),
+                amount_due_cents: invoice.amount_due,
+                due_at: invoice.due_at,
+                attempt,
             })?,
         };
 
@@ -300,12 +383,20 @@ impl EventHandler<Event> for WebhookHandler {
             EventData::SubscriptionCreated(details) => {
                 self.subscription_created_webhook(&event, details).await?
             }
+            EventData::SubscriptionTrialEnded(details) => {
+                self.subscription_trial_ended_webhook(&event, details)
+                    .await?
+            }
             EventData::InvoiceCreated(details) => {
                 self.invoice_draft_webhook(&event, details).await?
             }
             EventData::InvoiceFinalized(details) => {
                 self.invoice_finalized_webhook(&event, details).await?
             }
+            EventData::InvoicePaymentReminder(details) => {
+                self.invoice_payment_reminder_webhook(&event, details)
+                    .await?
+            }
             _ => {
                 log::debug!("Skipping event: {:?}", &event);
                 return Ok(());
@@ -369,6 +460,14 @@ struct SubscriptionData {
     pub net_terms: u32,
 }
 
+#[derive(Serialize)]
+struct SubscriptionTrialEndedData {
+    #[serde(flatten)]
+    pub subscription: SubscriptionData,
+    pub plan_name: String,
+    pub action_after_trial: String,
+}
+
 #[derive(Serialize)]
 struct InvoiceData {
     pub customer_name: String,
@@ -377,6 +476,37 @@ struct InvoiceData {
     pub invoice_date: chrono::NaiveDate,
     pub amount_cents: Option<i64>,
     pub plan_name: Option<String>,
+    // the discount of each coupon applied to the invoice
+    pub coupons: Vec<InvoiceCouponData>,
+}
+
+#[derive(Serialize)]
+struct InvoiceCouponData {
+    pub coupon_id: Uuid,
+    pub code: String,
+    pub discount_cents: i64,
+}
+
+impl From<CouponLineItem> for InvoiceCouponData {
+    fn from(value: CouponLineItem) -> Self {
+        Self {
+            coupon_id: value.coupon_id,
+            code: value.code,
+            discount_cents: value.value,
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct InvoicePaymentReminderData {
+    #[serde(flatten)]
+    pub invoice: InvoiceData,
+    // where the reminder should be sent
+    pub customer_email: Option<String>,
+    pub amount_due_cents: i64,
+    pub due_at: Option<chrono::NaiveDateTime>,
+    // 1-based, following the retry schedule of the invoicing entity
+    pub attempt: u32,
 }
 
 fn to_json<T: Serialize>(data: T) -> Result<serde_json::Value, EventBusError> {
@@ -389,16 +519,24 @@ fn get_event_type(event: &Event) -> Option<WebhookOutEventTypeEnum> {
         EventData::SubscriptionCreated(_) => Some(WebhookOutEventTypeEnum::SubscriptionCreated),
         EventData::InvoiceCreated(_) => Some(WebhookOutEventTypeEnum::InvoiceCreated),
         EventData::InvoiceFinalized(_) => Some(WebhookOutEventTypeEnum::InvoiceFinalized),
+        EventData::SubscriptionTrialEnded(_) => {
+            Some(WebhookOutEventTypeEnum::SubscriptionTrialEnded)
+        }
+        EventData::InvoicePaymentReminder(_) => {
+            Some(WebhookOutEventTypeEnum::InvoicePaymentReminder)
+        }
         _ => None,
     }
 }
 
-fn get_tenant_event_details(event: &Event) -> Option<&TenantEventDataDetails> {
+fn get_tenant_id(event: &Event) -> Option<Uuid> {
     match &event.event_data {
-        EventData::CustomerCreated(d) => Some(d),
-        EventData::SubscriptionCreated(d) => Some(d),
-        EventData::InvoiceCreated(d) => Some(d),
-        EventData::InvoiceFinalized(d) => Some(d),
+        EventData::CustomerCreated(d) => Some(d.tenant_id),
+        EventData::SubscriptionCreated(d) => Some(d.tenant_id),
+        EventData::InvoiceCreated(d) => Some(d.tenant_id),
+        EventData::InvoiceFinalized(d) => Some(d.tenant_id),
+        EventData::SubscriptionTrialEnded(d) => Some(d.tenant_id),
+        EventData::InvoicePaymentReminder(d) => Some(d.tenant_id),
         _ => None,
     }
 }
@@ -2,8 +2,8 @@ use chrono::{Datelike, Days, NaiveDate, NaiveTime};
 use error_stack::ResultExt;
 use fake::Fake;
 use meteroid_store::domain::enums::{
-    BillingMetricAggregateEnum, BillingPeriodEnum, InvoiceStatusEnum, InvoiceType,
-    InvoicingProviderEnum, PlanStatusEnum, PlanTypeEnum, TenantEnvironmentEnum,
+    BillingMetricAggregateEnum, BillingPeriodEnum, CustomerTaxStatusEnum, InvoiceStatusEnum,
+    InvoiceType, InvoicingProviderEnum, PlanStatusEnum, PlanTypeEnum, TenantEnvironmentEnum,
 };
 
 use meteroid_store::domain as store_domain;
@@ -27,6 +27,7 @@ use meteroid_store::compute::{calculate_period_range, InvoiceLineInterface};
 use chrono::Utc;
 
 use nanoid::nanoid;
+use rust_decimal::Decimal;
 
 use meteroid_store::domain::{
     Address, BillingConfig, InlineCustomer, InlineInvoicingEntity, TenantContext,
@@ -186,6 +187,8 @@ pub async fn run(
                 alias: Some(alias),
                 name: company_name.to_string(),
                 shipping_address: None,
+                vat_number: None,
+                tax_status: CustomerTaxStatusEnum::Taxable,
             });
         });
     }
@@ -303,6 +306,8 @@ pub async fn run(
             invoice_memo: None,
             invoice_threshold: None,
             activated_at,
+            minimum_spend: None,
+            spend_cap: None,
         };
 
         let create_subscription_components = if parameterized_components.is_empty() {
@@ -478,8 +483,10 @@ pub async fn run(
                 },
                 subtotal: amount_cents,
                 subtotal_recurring: amount_cents, // TODO
-                tax_rate: 0,
+                tax_rate: Decimal::ZERO,
                 tax_amount: 0,
+                tax_breakdown: vec![],
+                coupons: vec![],
                 total: amount_cents,
                 amount_due: amount_cents,
                 net_terms: 30,
@@ -505,6 +512,7 @@ pub async fn run(
                     address: invoicing_entity.address(),
                     snapshot_at: subscription.created_at,
                 },
+                replaces_invoice_id: None,
             };
 
             invoices_to_create.push(invoice);
@@ -5,7 +5,8 @@ use base64::Engine;
 use error_stack::ResultExt;
 use image::ImageFormat::Png;
 use meteroid_invoicing::{html_render, pdf};
-use meteroid_store::domain::{Invoice, InvoicingEntity};
+use meteroid_store::domain::{CreditNote, Invoice, InvoicingEntity};
+use meteroid_store::repositories::credit_notes::CreditNoteInterface;
 use meteroid_store::repositories::historical_rates::HistoricalRatesInterface;
 use meteroid_store::repositories::invoicing_entities::InvoicingEntityInterface;
 use meteroid_store::repositories::InvoiceInterface;
@@ -72,8 +73,8 @@ impl HtmlRenderingService {
 }
 
 pub enum GenerateResult {
-    Success { invoice_id: Uuid, pdf_url: String },
-    Failure { invoice_id: Uuid, error: String },
+    Success { id: Uuid, pdf_url: String },
+    Failure { id: Uuid, error: String },
 }
 
 pub struct PdfRenderingService {
@@ -127,11 +128,11 @@ impl PdfRenderingService {
                 .await
             {
                 Err(error) => GenerateResult::Failure {
-                    invoice_id,
+                    id: invoice_id,
                     error: error.to_string(),
                 },
                 Ok(pdf_url) => GenerateResult::Success {
-                    invoice_id,
+                    id: invoice_id,
                     pdf_url: pdf_url.clone(),
                 },
             };
@@ -154,32 +155,7 @@ impl PdfRenderingService {
         let invoice_id = invoice.id;
         let tenant_id = invoice.tenant_id;
 
-        // let's resolve the logo and encode it to a base64 url
-        let organization_logo = match invoicing_entity.logo_attachment_id.as_ref() {
-            Some(logo_id) => {
-                let logo_uuid =
-                    Uuid::parse_str(logo_id).change_context(InvoicingRenderError::StorageError)?;
-
-                let logo = self
-                    .storage
-                    .retrieve(logo_uuid, Prefix::ImageLogo)
-                    .await
-                    .change_context(InvoicingRenderError::StorageError)?;
-
-                let mut img = image::load_from_memory(&logo)
-                    .change_context(InvoicingRenderError::RenderError)?;
-                img = img.resize(350, 20, image::imageops::FilterType::Nearest);
-                let mut buffer = Vec::new();
-                img.write_to(&mut Cursor::new(&mut buffer), Png)
-                    .change_context(InvoicingRenderError::RenderError)?;
-
-                Some(format!(
-                    "data:image/png;base64,{}",
-                    Base64Engine.encode(&buffer)
-                ))
-            }
-            None => None,
-        };
+        let organization_logo = self.resolve_logo(invoicing_entity).await?;
 
         let mut rate = None;
         if invoice.currency != invoicing_entity.accounting_currency {
@@ -221,10 +197,152 @@ impl PdfRenderingService {
 
         Ok(pdf_id)
     }
+
+    pub async fn generate_credit_note_pdfs(
+        &self,
+        credit_note_ids: Vec<Uuid>,
+    ) -> error_stack::Result<Vec<GenerateResult>, InvoicingRenderError> {
+        let credit_notes = self
+            .store
+            .list_credit_notes_by_ids(credit_note_ids)
+            .await
+            .change_context(InvoicingRenderError::StoreError)?;
+
+        let invoices = self
+            .store
+            .list_invoices_by_ids(
+                credit_notes
+                    .iter()
+                    .map(|credit_note| credit_note.invoice_id)
+                    .collect(),
+            )
+            .await
+            .change_context(InvoicingRenderError::StoreError)?;
+
+        let invoicing_entity_ids = invoices
+            .iter()
+            .map(|invoice| invoice.seller_details.id)
+            .collect::<Vec<Uuid>>();
+
+        let invoicing_entities = self
+            .store
+            .list_invoicing_entities_by_ids(invoicing_entity_ids)
+            .await
+            .change_context(InvoicingRenderError::StoreError)?;
+
+        let mut results = vec![];
+
+        for credit_note in credit_notes {
+            let credit_note_id = credit_note.id;
+            let res = match self
+                .generate_credit_note_pdf_and_save(credit_note, &invoices, &invoicing_entities)
+                .await
+            {
+                Err(error) => GenerateResult::Failure {
+                    id: credit_note_id,
+                    error: error.to_string(),
+                },
+                Ok(pdf_url) => GenerateResult::Success {
+                    id: credit_note_id,
+                    pdf_url,
+                },
+            };
+            results.push(res);
+        }
+        Ok(results)
+    }
+
+    async fn generate_credit_note_pdf_and_save(
+        &self,
+        credit_note: CreditNote,
+        invoices: &[Invoice],
+        invoicing_entities: &[InvoicingEntity],
+    ) -> error_stack::Result<String, InvoicingRenderError> {
+        let invoice = invoices
+            .iter()
+            .find(|invoice| invoice.id == credit_note.invoice_id)
+            .ok_or(InvoicingRenderError::StoreError)
+            .attach_printable("Failed to resolve credited invoice")?;
+
+        let invoicing_entity = invoicing_entities
+            .iter()
+            .find(|entity| entity.id == invoice.seller_details.id)
+            .ok_or(InvoicingRenderError::StoreError)
+            .attach_printable("Failed to resolve invoicing entity")?;
+
+        let credit_note_id = credit_note.id;
+        let tenant_id = credit_note.tenant_id;
+
+        let organization_logo = self.resolve_logo(invoicing_entity).await?;
+
+        let mapped_credit_note = mapper::map_credit_note_to_invoicing(
+            credit_note,
+            invoice,
+            invoicing_entity,
+            &organization_logo,
+        )?;
+
+        let html = html_render::render_credit_note(&mapped_credit_note)
+            .change_context(InvoicingRenderError::RenderError)?
+            .into_string();
+
+        let pdf = self
+            .pdf
+            .generate_pdf(&html)
+            .await
+            .change_context(InvoicingRenderError::PdfError)?;
+
+        let pdf_id = self
+            .storage
+            .store(pdf, Prefix::CreditNotePdf)
+            .await
+            .change_context(InvoicingRenderError::StorageError)?
+            .to_string();
+
+        self.store
+            .save_credit_note_documents(credit_note_id, tenant_id, pdf_id.clone())
+            .await
+            .change_context(InvoicingRenderError::StoreError)?;
+
+        Ok(pdf_id)
+    }
+
+    // resolves the logo and encodes it to a base64 url
+    async fn resolve_logo(
+        &self,
+        invoicing_entity: &InvoicingEntity,
+    ) -> error_stack::Result<Option<String>, InvoicingRenderError> {
+        match invoicing_entity.logo_attachment_id.as_ref() {
+            Some(logo_id) => {
+                let logo_uuid =
+                    Uuid::parse_str(logo_id).change_context(InvoicingRenderError::StorageError)?;
+
+                let logo = self
+                    .storage
+                    .retrieve(logo_uuid, Prefix::ImageLogo)
+                    .await
+                    .change_context(InvoicingRenderError::StorageError)?;
+
+                let mut img = image::load_from_memory(&logo)
+                    .change_context(InvoicingRenderError::RenderError)?;
+                img = img.resize(350, 20, image::imageops::FilterType::Nearest);
+                let mut buffer = Vec::new();
+                img.write_to(&mut Cursor::new(&mut buffer), Png)
+                    .change_context(InvoicingRenderError::RenderError)?;
+
+                Ok(Some(format!(
+                    "data:image/png;base64,{}",
+                    Base64Engine.encode(&buffer)
+                )))
+            }
+            None => Ok(None),
+        }
+    }
 }
 
 mod mapper {
     use crate::errors::InvoicingRenderError;
+    use common_domain::money::Currency;
     use error_stack::Report;
     use meteroid_invoicing::model as invoicing_model;
     use meteroid_store::constants::Countries;
@@ -246,13 +364,13 @@ mod mapper {
             .map(|d| d.date())
             .unwrap_or(invoice.invoice_date);
 
-        let currency = *rusty_money::iso::find(&invoice.currency).ok_or_else(|| {
+        let currency = Currency::from_code(&invoice.currency).ok_or_else(|| {
             Report::new(InvoicingRenderError::InvalidCurrency(
                 invoice.currency.clone(),
             ))
         })?;
 
-        let accounting_currency = *rusty_money::iso::find(&invoicing_entity.accounting_currency)
+        let accounting_currency = Currency::from_code(&invoicing_entity.accounting_currency)
             .ok_or_else(|| {
                 Report::new(InvoicingRenderError::InvalidCurrency(
                     invoicing_entity.accounting_currency.clone(),
@@ -270,55 +388,170 @@ mod mapper {
             payment_term: invoice.net_terms as u32,
             total_amount: invoice.total,
             tax_amount: invoice.tax_amount,
-            tax_rate: invoice.tax_rate,
+            tax_breakdown: invoice
+                .tax_breakdown
+                .into_iter()
+                .map(|tax| invoicing_model::TaxBreakdownItem {
+                    name: tax.name,
+                    rate: tax.rate,
+                    treatment: match tax.treatment {
+                        store_model::TaxTreatment::Standard => {
+                            invoicing_model::TaxTreatment::Standard
+                        }
+                        store_model::TaxTreatment::Exempt => invoicing_model::TaxTreatment::Exempt,
+                        store_model::TaxTreatment::ReverseCharge => {
+                            invoicing_model::TaxTreatment::ReverseCharge
+                        }
+                        store_model::TaxTreatment::OutOfScope => {
+                            invoicing_model::TaxTreatment::OutOfScope
+                        }
+                    },
+                    taxable_amount: tax.taxable_amount,
+                    tax_amount: tax.tax_amount,
+                })
+                .collect(),
             subtotal: invoice.subtotal,
+            coupons: invoice
+                .coupons
+                .into_iter()
+                .map(|coupon| invoicing_model::CouponLine {
+                    code: coupon.code,
+                    total: coupon.value,
+                })
+                .collect(),
             memo: invoice.memo.clone(),
         };
 
-        fn map_address(address: store_model::Address) -> invoicing_model::Address {
-            invoicing_model::Address {
-                line1: address.line1,
-                line2: address.line2,
-                city: address.city,
-                country: address.country,
-                state: address.state,
-                zip_code: address.zip_code,
-            }
+        let organization = map_organization(
+            invoice.seller_details,
+            invoicing_entity,
+            organization_logo,
+            accounting_currency,
+            accounting_rate,
+        );
+
+        let customer = map_customer(invoice.customer_details);
+
+        let lines = map_lines(&invoice.line_items);
+
+        Ok(invoicing_model::Invoice {
+            lang: resolve_lang(invoicing_entity),
+            customer,
+            lines,
+            metadata,
+            organization,
+        })
+    }
+
+    pub fn map_credit_note_to_invoicing(
+        credit_note: store_model::CreditNote,
+        invoice: &store_model::Invoice,
+        invoicing_entity: &store_model::InvoicingEntity,
+        organization_logo: &Option<String>,
+    ) -> error_stack::Result<invoicing_model::CreditNote, InvoicingRenderError> {
+        let currency = Currency::from_code(&credit_note.currency).ok_or_else(|| {
+            Report::new(InvoicingRenderError::InvalidCurrency(
+                credit_note.currency.clone(),
+            ))
+        })?;
+
+        let accounting_currency = Currency::from_code(&invoicing_entity.accounting_currency)
+            .ok_or_else(|| {
+                Report::new(InvoicingRenderError::InvalidCurrency(
+                    invoicing_entity.accounting_currency.clone(),
+                ))
+            })?;
+
+        let refunded = matches!(
+            credit_note.credit_type(),
+            store_model::CreditNoteCreditType::Refund
+        );
+
+        let metadata = invoicing_model::CreditNoteMetadata {
+            number: credit_note.credit_note_number.unwrap_or_default(),
+            issue_date: credit_note
+                .finalized_at
+                .unwrap_or(credit_note.created_at)
+                .date(),
+            invoice_number: invoice.invoice_number.clone(),
+            reason: credit_note.reason,
+            subtotal: credit_note.subtotal,
+            tax_amount: credit_note.tax_amount,
+            total_amount: credit_note.total,
+            currency,
+            refunded,
+            memo: credit_note.memo,
+        };
+
+        Ok(invoicing_model::CreditNote {
+            lang: resolve_lang(invoicing_entity),
+            organization: map_organization(
+                invoice.seller_details.clone(),
+                invoicing_entity,
+                organization_logo,
+                accounting_currency,
+                None,
+            ),
+            customer: map_customer(invoice.customer_details.clone()),
+            metadata,
+            lines: map_lines(&credit_note.line_items),
+        })
+    }
+
+    fn map_address(address: store_model::Address) -> invoicing_model::Address {
+        invoicing_model::Address {
+            line1: address.line1,
+            line2: address.line2,
+            city: address.city,
+            country: address.country,
+            state: address.state,
+            zip_code: address.zip_code,
         }
+    }
 
-        let organization = invoicing_model::Organization {
-            address: map_address(invoice.seller_details.address),
+    fn map_organization(
+        seller_details: store_model::InlineInvoicingEntity,
+        invoicing_entity: &store_model::InvoicingEntity,
+        organization_logo: &Option<String>,
+        accounting_currency: Currency,
+        accounting_rate: Option<HistoricalRate>,
+    ) -> invoicing_model::Organization {
+        invoicing_model::Organization {
+            address: map_address(seller_details.address),
             email: None,        // TODO
             legal_number: None, // TODO
             logo_url: organization_logo.clone(),
-            name: invoice.seller_details.legal_name,
-            tax_id: invoice.seller_details.vat_number,
+            name: seller_details.legal_name,
+            tax_id: seller_details.vat_number,
             footer_info: invoicing_entity.invoice_footer_info.clone(),
             footer_legal: invoicing_entity.invoice_footer_legal.clone(),
             accounting_currency,
             exchange_rate: accounting_rate.and_then(|r| Decimal::from_f32(r.rate)),
-        };
+        }
+    }
 
-        let customer = invoicing_model::Customer {
-            address: invoice
-                .customer_details
+    fn map_customer(customer_details: store_model::InlineCustomer) -> invoicing_model::Customer {
+        invoicing_model::Customer {
+            address: customer_details
                 .billing_address
                 .map(map_address)
                 .unwrap_or_default(),
-            email: invoice.customer_details.email,
+            email: customer_details.email,
             legal_number: None, // TODO
-            name: invoice.customer_details.name,
-            tax_id: invoice.customer_details.vat_number,
-        };
+            name: customer_details.name,
+            tax_id: customer_details.vat_number,
+        }
+    }
 
-        let lines = invoice
-            .line_items
+    fn map_lines(line_items: &[store_model::LineItem]) -> Vec<invoicing_model::InvoiceLine> {
+        line_items
             .iter()
             .map(|line| invoicing_model::InvoiceLine {
                 total: line.total,
                 description: line.description.clone(),
                 quantity: line.quantity,
-                vat_rate: Some(Decimal::from(invoice.tax_rate) / Decimal::from(100)),
+                vat_rate: Some(line.tax_rate),
+                vat_amount: Some(line.tax_amount),
                 unit_price: line.unit_price,
                 name: line.name.clone(),
                 end_date: line.end_date,
@@ -335,18 +568,13 @@ mod mapper {
                     })
                     .collect(),
             })
-            .collect();
+            .collect()
+    }
 
-        let lang = Countries::resolve_country(&invoicing_entity.country)
+    fn resolve_lang(invoicing_entity: &store_model::InvoicingEntity) -> String {
+        Countries::resolve_country(&invoicing_entity.country)
             .map(|c| c.locale)
-            .unwrap_or_else(|| "en-US");
-
-        Ok(invoicing_model::Invoice {
-            lang: lang.to_string(),
-            customer,
-            lines,
-            metadata,
-            organization,
-        })
+            .unwrap_or_else(|| "en-US")
+            .to_string()
     }
 }
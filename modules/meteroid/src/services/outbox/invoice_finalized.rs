@@ -37,6 +37,7 @@ impl InvoiceFinalizedOutboxWorker {
                     vec![
                         OutboxEvent::InvoiceFinalized,
                         OutboxEvent::InvoicePdfRequested,
+                        OutboxEvent::CreditNoteFinalized,
                     ],
                     10,
                 )
@@ -55,22 +56,45 @@ impl InvoiceFinalizedOutboxWorker {
                 continue;
             }
 
-            let outbox_map: HashMap<_, _> = outbox
-                .iter()
-                .map(|entry| (entry.resource_id, entry))
-                .collect();
+            let (credit_notes, invoices): (Vec<_>, Vec<_>) = outbox
+                .into_iter()
+                .partition(|entry| entry.event_type == OutboxEvent::CreditNoteFinalized);
 
-            match self
-                .pdf_service
-                .generate_pdfs(outbox_map.keys().cloned().collect())
-                .await
-            {
-                Ok(results) => self.process_results(&outbox_map, results).await,
-                Err(e) => self.mark_all_as_failed(&outbox, e.to_string()).await,
+            if !invoices.is_empty() {
+                let outbox_map = Self::by_resource_id(&invoices);
+
+                match self
+                    .pdf_service
+                    .generate_pdfs(outbox_map.keys().cloned().collect())
+                    .await
+                {
+                    Ok(results) => self.process_results(&outbox_map, results).await,
+                    Err(e) => self.mark_all_as_failed(&invoices, e.to_string()).await,
+                }
+            }
+
+            if !credit_notes.is_empty() {
+                let outbox_map = Self::by_resource_id(&credit_notes);
+
+                match self
+                    .pdf_service
+                    .generate_credit_note_pdfs(outbox_map.keys().cloned().collect())
+                    .await
+                {
+                    Ok(results) => self.process_results(&outbox_map, results).await,
+                    Err(e) => self.mark_all_as_failed(&credit_notes, e.to_string()).await,
+                }
             }
         }
     }
 
+    fn by_resource_id(outbox: &[Outbox]) -> HashMap<Uuid, &Outbox> {
+        outbox
+            .iter()
+            .map(|entry| (entry.resource_id, entry))
+            .collect()
+    }
+
     async fn process_results(
         &self,
         outbox_map: &HashMap<Uuid, &Outbox>,
@@ -78,10 +102,8 @@ impl InvoiceFinalizedOutboxWorker {
     ) {
         let (successes, failures): (Vec<_>, Vec<_>) =
             results.into_iter().partition_map(|result| match result {
-                GenerateResult::Success { invoice_id, .. } => itertools::Either::Left(invoice_id),
-                GenerateResult::Failure { invoice_id, error } => {
-                    itertools::Either::Right((invoice_id, error))
-                }
+                GenerateResult::Success { id, .. } => itertools::Either::Left(id),
+                GenerateResult::Failure { id, error } => itertools::Either::Right((id, error)),
             });
 
         if !successes.is_empty() {
@@ -100,8 +122,8 @@ impl InvoiceFinalizedOutboxWorker {
         }
 
         stream::iter(failures)
-            .for_each(|(invoice_id, error)| async move {
-                if let Some(&entry) = outbox_map.get(&invoice_id) {
+            .for_each(|(id, error)| async move {
+                if let Some(&entry) = outbox_map.get(&id) {
                     if let Err(e) = self
                         .store
                         .mark_outbox_entry_as_failed(entry.id, error)
@@ -14,6 +14,7 @@ use uuid::Uuid;
 pub enum Prefix {
     InvoicePdf,
     InvoiceXml,
+    CreditNotePdf,
     ImageLogo,
     WebhookArchive {
         provider_uid: String,
@@ -26,6 +27,7 @@ impl Prefix {
         match self {
             Prefix::InvoicePdf => "invoice_pdf".to_string(),
             Prefix::InvoiceXml => "invoice_xml".to_string(),
+            Prefix::CreditNotePdf => "credit_note_pdf".to_string(),
             Prefix::ImageLogo => "image_logo".to_string(),
             Prefix::WebhookArchive {
                 provider_uid,
@@ -8,6 +8,10 @@ pub trait DbSubscriptionExt {
 
 impl DbSubscriptionExt for meteroid_store::domain::Subscription {
     fn status_proto(&self) -> Result<SubscriptionStatus, MappingError> {
+        if self.paused_at.is_some() && self.canceled_at.is_none() {
+            return Ok(SubscriptionStatus::Paused);
+        }
+
         derive_subscription_status_chrono(
             chrono::Utc::now().naive_utc(),
             self.trial_start_date,
@@ -21,6 +25,10 @@ impl DbSubscriptionExt for meteroid_store::domain::Subscription {
 
 impl DbSubscriptionExt for meteroid_store::domain::SubscriptionDetails {
     fn status_proto(&self) -> Result<SubscriptionStatus, MappingError> {
+        if self.paused_at.is_some() && self.canceled_at.is_none() {
+            return Ok(SubscriptionStatus::Paused);
+        }
+
         derive_subscription_status_chrono(
             chrono::Utc::now().naive_utc(),
             self.trial_start_date,
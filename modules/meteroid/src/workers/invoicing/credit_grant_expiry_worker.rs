@@ -0,0 +1,86 @@
+use crate::{errors, singletons};
+
+use common_utils::timed::TimedExt;
+use error_stack::{Result, ResultExt};
+use fang::{AsyncQueueable, AsyncRunnable, Deserialize, FangError, Scheduled, Serialize};
+use meteroid_store::domain::CursorPaginationRequest;
+use meteroid_store::repositories::credit_grants::CreditGrantInterface;
+use meteroid_store::Store;
+
+use crate::workers::metrics::record_call;
+
+const BATCH_SIZE: usize = 100;
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "fang::serde")]
+pub struct CreditGrantExpiryWorker;
+
+#[async_trait::async_trait]
+#[typetag::serde]
+impl AsyncRunnable for CreditGrantExpiryWorker {
+    #[tracing::instrument(skip_all)]
+    async fn run(&self, _queue: &mut dyn AsyncQueueable) -> core::result::Result<(), FangError> {
+        credit_grant_expiry_worker(singletons::get_store().await)
+            .timed(|res, elapsed| record_call("credit_grant_expiry", res, elapsed))
+            .await
+            .map_err(|err| {
+                log::error!("Error in credit grant expiry worker: {}", err);
+                FangError {
+                    description: err.to_string(),
+                }
+            })
+    }
+
+    fn uniq(&self) -> bool {
+        true
+    }
+
+    fn cron(&self) -> Option<Scheduled> {
+        let expression = "0 0 * * * * *"; // every hour
+        Some(Scheduled::CronPattern(expression.to_string()))
+    }
+
+    fn max_retries(&self) -> i32 {
+        0
+    }
+}
+
+/// Writes off the remaining amount of the credit grants reaching their expiry date.
+#[tracing::instrument(skip_all)]
+pub async fn credit_grant_expiry_worker(store: &Store) -> Result<(), errors::WorkerError> {
+    let today = chrono::Utc::now().date_naive();
+
+    let mut last_processed_id = None;
+
+    loop {
+        let paginated_vec = store
+            .list_expired_credit_grants(
+                today,
+                CursorPaginationRequest {
+                    limit: Some(BATCH_SIZE as u32),
+                    cursor: last_processed_id,
+                },
+            )
+            .await
+            .change_context(errors::WorkerError::DatabaseError)?;
+
+        for grant in paginated_vec.items {
+            let res = store
+                .expire_credit_grant(grant.tenant_id, grant.id)
+                .await
+                .change_context(errors::WorkerError::DatabaseError);
+
+            if let Err(e) = res {
+                log::error!("Failed to expire credit grant {} : {}", grant.id, e)
+            }
+        }
+
+        last_processed_id = paginated_vec.next_cursor;
+
+        if paginated_vec.next_cursor.is_none() {
+            break;
+        }
+    }
+
+    Ok(())
+}
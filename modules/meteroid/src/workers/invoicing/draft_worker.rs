@@ -71,8 +71,14 @@ pub async fn draft_worker(store: &Store, today: NaiveDate) -> Result<(), errors:
             .await
             .change_context(errors::WorkerError::DatabaseError)?;
 
+        last_processed_id = paginated_vec.next_cursor;
+
+        // a page can be empty while more candidates follow, as ended subscriptions are skipped
         if paginated_vec.items.is_empty() {
-            break;
+            if last_processed_id.is_none() {
+                break;
+            }
+            continue;
         }
 
         let customer_ids = paginated_vec
@@ -124,8 +130,6 @@ pub async fn draft_worker(store: &Store, today: NaiveDate) -> Result<(), errors:
             .await
             .change_context(errors::WorkerError::DatabaseError)?;
 
-        last_processed_id = paginated_vec.next_cursor;
-
         for inv in &inserted {
             let _ = store
                 .eventbus
@@ -133,7 +137,7 @@ pub async fn draft_worker(store: &Store, today: NaiveDate) -> Result<(), errors:
                 .await;
         }
 
-        if paginated_vec.next_cursor.is_none() {
+        if last_processed_id.is_none() {
             break;
         }
     }
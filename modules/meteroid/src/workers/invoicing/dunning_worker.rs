@@ -0,0 +1,128 @@
+use crate::{errors, singletons};
+
+use common_utils::timed::TimedExt;
+use error_stack::{Result, ResultExt};
+use fang::{AsyncQueueable, AsyncRunnable, Deserialize, FangError, Scheduled, Serialize};
+use meteroid_store::domain::CursorPaginationRequest;
+use meteroid_store::repositories::dunning::DunningInterface;
+use meteroid_store::Store;
+
+use crate::workers::metrics::record_call;
+
+const BATCH_SIZE: usize = 100;
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "fang::serde")]
+pub struct DunningWorker;
+
+#[async_trait::async_trait]
+#[typetag::serde]
+impl AsyncRunnable for DunningWorker {
+    #[tracing::instrument(skip_all)]
+    async fn run(&self, _queue: &mut dyn AsyncQueueable) -> core::result::Result<(), FangError> {
+        dunning_worker(singletons::get_store().await)
+            .timed(|res, elapsed| record_call("dunning", res, elapsed))
+            .await
+            .map_err(|err| {
+                log::error!("Error in dunning worker: {}", err);
+                FangError {
+                    description: err.to_string(),
+                }
+            })
+    }
+
+    fn uniq(&self) -> bool {
+        true
+    }
+
+    fn cron(&self) -> Option<Scheduled> {
+        let expression = "0 0/15 * * * * *"; // every 15 minutes
+        Some(Scheduled::CronPattern(expression.to_string()))
+    }
+
+    fn max_retries(&self) -> i32 {
+        0
+    }
+}
+
+/// Starts the dunning of the overdue or failed invoices, then processes the due attempts
+/// (payment reminders, and end actions once the retry schedule is exhausted).
+#[tracing::instrument(skip_all)]
+pub async fn dunning_worker(store: &Store) -> Result<(), errors::WorkerError> {
+    start_dunnings(store).await?;
+    process_due_dunnings(store).await
+}
+
+async fn start_dunnings(store: &Store) -> Result<(), errors::WorkerError> {
+    let mut last_processed_id = None;
+
+    loop {
+        let paginated_vec = store
+            .list_invoices_to_dun(CursorPaginationRequest {
+                limit: Some(BATCH_SIZE as u32),
+                cursor: last_processed_id,
+            })
+            .await
+            .change_context(errors::WorkerError::DatabaseError)?;
+
+        for invoice in paginated_vec.items {
+            let res = store
+                .start_invoice_dunning(invoice.tenant_id, invoice.id)
+                .await
+                .change_context(errors::WorkerError::DatabaseError);
+
+            if let Err(e) = res {
+                log::error!(
+                    "Failed to start the dunning of invoice {} : {}",
+                    invoice.id,
+                    e
+                )
+            }
+        }
+
+        last_processed_id = paginated_vec.next_cursor;
+
+        if paginated_vec.next_cursor.is_none() {
+            break;
+        }
+    }
+
+    Ok(())
+}
+
+async fn process_due_dunnings(store: &Store) -> Result<(), errors::WorkerError> {
+    let mut last_processed_id = None;
+
+    loop {
+        let paginated_vec = store
+            .list_due_invoice_dunnings(CursorPaginationRequest {
+                limit: Some(BATCH_SIZE as u32),
+                cursor: last_processed_id,
+            })
+            .await
+            .change_context(errors::WorkerError::DatabaseError)?;
+
+        for dunning in paginated_vec.items {
+            let res = store
+                .process_invoice_dunning(dunning.tenant_id, dunning.id)
+                .await
+                .change_context(errors::WorkerError::DatabaseError);
+
+            if let Err(e) = res {
+                log::error!(
+                    "Failed to process the dunning of invoice {} : {}",
+                    dunning.invoice_id,
+                    e
+                )
+            }
+        }
+
+        last_processed_id = paginated_vec.next_cursor;
+
+        if paginated_vec.next_cursor.is_none() {
+            break;
+        }
+    }
+
+    Ok(())
+}
@@ -1,5 +1,8 @@
+pub mod credit_grant_expiry_worker;
 pub mod draft_worker;
+pub mod dunning_worker;
 pub mod finalize_worker;
 pub mod issue_worker;
 pub mod pending_status_worker;
 pub mod price_worker;
+pub mod usage_threshold_worker;
@@ -0,0 +1,91 @@
+use crate::{errors, singletons};
+
+use common_utils::timed::TimedExt;
+use error_stack::{Result, ResultExt};
+use fang::{AsyncQueueable, AsyncRunnable, Deserialize, FangError, Scheduled, Serialize};
+use meteroid_store::domain::CursorPaginationRequest;
+use meteroid_store::repositories::usage_thresholds::UsageThresholdInterface;
+use meteroid_store::Store;
+
+use crate::workers::metrics::record_call;
+
+const BATCH_SIZE: usize = 100;
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "fang::serde")]
+pub struct UsageThresholdWorker;
+
+#[async_trait::async_trait]
+#[typetag::serde]
+impl AsyncRunnable for UsageThresholdWorker {
+    #[tracing::instrument(skip_all)]
+    async fn run(&self, _queue: &mut dyn AsyncQueueable) -> core::result::Result<(), FangError> {
+        usage_threshold_worker(singletons::get_store().await)
+            .timed(|res, elapsed| record_call("usage_threshold", res, elapsed))
+            .await
+            .map_err(|err| {
+                log::error!("Error in usage threshold worker: {}", err);
+                FangError {
+                    description: err.to_string(),
+                }
+            })
+    }
+
+    fn uniq(&self) -> bool {
+        true
+    }
+
+    fn cron(&self) -> Option<Scheduled> {
+        let expression = "0 0 * * * * *"; // every hour
+        Some(Scheduled::CronPattern(expression.to_string()))
+    }
+
+    fn max_retries(&self) -> i32 {
+        0
+    }
+}
+
+/// Issues a usage-threshold invoice for the subscriptions whose unbilled usage of the current period reaches their invoice threshold.
+/// That usage is then deducted from the next recurring invoice.
+#[tracing::instrument(skip_all)]
+pub async fn usage_threshold_worker(store: &Store) -> Result<(), errors::WorkerError> {
+    let today = chrono::Utc::now().date_naive();
+
+    let mut last_processed_id = None;
+
+    loop {
+        let paginated_vec = store
+            .list_usage_threshold_candidates(
+                today,
+                CursorPaginationRequest {
+                    limit: Some(BATCH_SIZE as u32),
+                    cursor: last_processed_id,
+                },
+            )
+            .await
+            .change_context(errors::WorkerError::DatabaseError)?;
+
+        for subscription in paginated_vec.items {
+            let res = store
+                .process_usage_threshold(subscription.tenant_id, subscription.id)
+                .await
+                .change_context(errors::WorkerError::DatabaseError);
+
+            if let Err(e) = res {
+                log::error!(
+                    "Failed to process the usage threshold of subscription {} : {}",
+                    subscription.id,
+                    e
+                )
+            }
+        }
+
+        last_processed_id = paginated_vec.next_cursor;
+
+        if paginated_vec.next_cursor.is_none() {
+            break;
+        }
+    }
+
+    Ok(())
+}
@@ -3,3 +3,4 @@ pub mod fang;
 pub mod invoicing;
 mod metrics;
 pub mod misc;
+pub mod subscriptions;
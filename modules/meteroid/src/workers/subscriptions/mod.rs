@@ -0,0 +1,4 @@
+pub mod commitment_worker;
+pub mod plan_change_worker;
+pub mod resume_worker;
+pub mod trial_worker;
@@ -0,0 +1,91 @@
+use crate::{errors, singletons};
+
+use common_utils::timed::TimedExt;
+use error_stack::{Result, ResultExt};
+use fang::{AsyncQueueable, AsyncRunnable, Deserialize, FangError, Scheduled, Serialize};
+use meteroid_store::domain::CursorPaginationRequest;
+use meteroid_store::repositories::subscription_plan_changes::SubscriptionPlanChangeInterface;
+use meteroid_store::Store;
+
+use crate::workers::metrics::record_call;
+
+const BATCH_SIZE: usize = 100;
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "fang::serde")]
+pub struct PlanChangeWorker;
+
+#[async_trait::async_trait]
+#[typetag::serde]
+impl AsyncRunnable for PlanChangeWorker {
+    #[tracing::instrument(skip_all)]
+    async fn run(&self, _queue: &mut dyn AsyncQueueable) -> core::result::Result<(), FangError> {
+        plan_change_worker(singletons::get_store().await)
+            .timed(|res, elapsed| record_call("plan_change", res, elapsed))
+            .await
+            .map_err(|err| {
+                log::error!("Error in plan change worker: {}", err);
+                FangError {
+                    description: err.to_string(),
+                }
+            })
+    }
+
+    fn uniq(&self) -> bool {
+        true
+    }
+
+    fn cron(&self) -> Option<Scheduled> {
+        let expression = "0 5/10 * * * * *"; // every 10 minutes
+        Some(Scheduled::CronPattern(expression.to_string()))
+    }
+
+    fn max_retries(&self) -> i32 {
+        0
+    }
+}
+
+/// Applies the plan changes scheduled at the end of a billing period, once that date is reached.
+#[tracing::instrument(skip_all)]
+pub async fn plan_change_worker(store: &Store) -> Result<(), errors::WorkerError> {
+    let today = chrono::Utc::now().date_naive();
+
+    let mut last_processed_id = None;
+
+    loop {
+        let paginated_vec = store
+            .list_due_plan_changes(
+                today,
+                CursorPaginationRequest {
+                    limit: Some(BATCH_SIZE as u32),
+                    cursor: last_processed_id,
+                },
+            )
+            .await
+            .change_context(errors::WorkerError::DatabaseError)?;
+
+        for change in paginated_vec.items {
+            let res = store
+                .apply_plan_change(change.id)
+                .await
+                .change_context(errors::WorkerError::DatabaseError);
+
+            if let Err(e) = res {
+                log::error!(
+                    "Failed to apply plan change {} of subscription {} : {}",
+                    change.id,
+                    change.subscription_id,
+                    e
+                )
+            }
+        }
+
+        last_processed_id = paginated_vec.next_cursor;
+
+        if paginated_vec.next_cursor.is_none() {
+            break;
+        }
+    }
+
+    Ok(())
+}
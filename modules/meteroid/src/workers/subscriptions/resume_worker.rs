@@ -0,0 +1,86 @@
+use crate::{errors, singletons};
+
+use common_utils::timed::TimedExt;
+use error_stack::{Result, ResultExt};
+use fang::{AsyncQueueable, AsyncRunnable, Deserialize, FangError, Scheduled, Serialize};
+use meteroid_store::domain::CursorPaginationRequest;
+use meteroid_store::repositories::subscription_pauses::SubscriptionPauseInterface;
+use meteroid_store::Store;
+
+use crate::workers::metrics::record_call;
+
+const BATCH_SIZE: usize = 100;
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "fang::serde")]
+pub struct ResumeWorker;
+
+#[async_trait::async_trait]
+#[typetag::serde]
+impl AsyncRunnable for ResumeWorker {
+    #[tracing::instrument(skip_all)]
+    async fn run(&self, _queue: &mut dyn AsyncQueueable) -> core::result::Result<(), FangError> {
+        resume_worker(singletons::get_store().await)
+            .timed(|res, elapsed| record_call("resume", res, elapsed))
+            .await
+            .map_err(|err| {
+                log::error!("Error in resume worker: {}", err);
+                FangError {
+                    description: err.to_string(),
+                }
+            })
+    }
+
+    fn uniq(&self) -> bool {
+        true
+    }
+
+    fn cron(&self) -> Option<Scheduled> {
+        let expression = "0 0/10 * * * * *"; // every 10 minutes
+        Some(Scheduled::CronPattern(expression.to_string()))
+    }
+
+    fn max_retries(&self) -> i32 {
+        0
+    }
+}
+
+/// Resumes the paused subscriptions whose auto-resume date is reached.
+#[tracing::instrument(skip_all)]
+pub async fn resume_worker(store: &Store) -> Result<(), errors::WorkerError> {
+    let today = chrono::Utc::now().date_naive();
+
+    let mut last_processed_id = None;
+
+    loop {
+        let paginated_vec = store
+            .list_subscriptions_to_resume(
+                today,
+                CursorPaginationRequest {
+                    limit: Some(BATCH_SIZE as u32),
+                    cursor: last_processed_id,
+                },
+            )
+            .await
+            .change_context(errors::WorkerError::DatabaseError)?;
+
+        for subscription in paginated_vec.items {
+            let res = store
+                .resume_subscription(subscription.tenant_id, subscription.id)
+                .await
+                .change_context(errors::WorkerError::DatabaseError);
+
+            if let Err(e) = res {
+                log::error!("Failed to resume subscription {} : {}", subscription.id, e)
+            }
+        }
+
+        last_processed_id = paginated_vec.next_cursor;
+
+        if paginated_vec.next_cursor.is_none() {
+            break;
+        }
+    }
+
+    Ok(())
+}
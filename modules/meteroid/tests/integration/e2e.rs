@@ -3,6 +3,7 @@ use std::str::FromStr;
 use std::sync::Arc;
 
 use chrono::{Datelike, Days, Months};
+use common_grpc::middleware::common::auth::API_KEY_HEADER;
 use opentelemetry::propagation::Injector;
 use rust_decimal::Decimal;
 use rust_decimal_macros::dec;
@@ -252,6 +253,8 @@ async fn test_metering_e2e() {
         })
         .collect();
 
+    let retried_event = events_mapped[0].clone();
+
     // we ingest events in metering
     let ingested = metering_clients
         .events
@@ -265,6 +268,141 @@ async fn test_metering_e2e() {
     let ingested = ingested.into_inner();
 
     assert_eq!(ingested.failures.len(), 0);
+    assert_eq!(ingested.duplicates.len(), 0);
+
+    // a retried event is reported as a duplicate and not ingested twice
+    let ingested = metering_clients
+        .events
+        .ingest(Request::new(IngestRequest {
+            events: vec![retried_event.clone()],
+            allow_backfilling: true,
+        }))
+        .await
+        .expect("Could not ingest events")
+        .into_inner();
+
+    assert_eq!(ingested.failures.len(), 0);
+    assert_eq!(ingested.duplicates, vec![retried_event.event_id.clone()]);
+
+    // bulk ingestion reports the failures by position in the stream
+    let unmetered_event = Event {
+        event_id: uuid::Uuid::new_v4().to_string(),
+        event_name: "api_response".to_string(),
+        ..retried_event.clone()
+    };
+    let invalid_event = Event {
+        event_id: uuid::Uuid::new_v4().to_string(),
+        customer_id: None,
+        ..retried_event.clone()
+    };
+    let ingested = metering_clients
+        .events
+        .ingest_stream(Request::new(tokio_stream::iter(vec![
+            IngestRequest {
+                events: vec![unmetered_event, invalid_event.clone()],
+                allow_backfilling: true,
+            },
+            IngestRequest {
+                events: vec![retried_event.clone()],
+                allow_backfilling: true,
+            },
+        ])))
+        .await
+        .expect("Could not ingest the events stream")
+        .into_inner();
+
+    assert_eq!(ingested.ingested_count, 1);
+    assert_eq!(ingested.failures.len(), 1);
+    assert_eq!(ingested.failures[0].line, 2);
+    assert_eq!(ingested.failures[0].idempotency_key, invalid_event.event_id);
+    assert_eq!(ingested.duplicates.len(), 1);
+    assert_eq!(ingested.duplicates[0].line, 3);
+    assert_eq!(
+        ingested.duplicates[0].idempotency_key,
+        retried_event.event_id
+    );
+
+    // bulk imports over http, from an NDJSON file
+    let import_url = format!(
+        "http://{}/v1/events/import?allow_backfilling=true",
+        metering_config.listen_addr
+    );
+    let http_client = reqwest::Client::new();
+
+    let imported_event_id = uuid::Uuid::new_v4().to_string();
+    let ndjson = [
+        serde_json::json!({
+            "event_id": imported_event_id,
+            "event_name": "api_response",
+            "meteroid_customer_id": customer_1,
+            "timestamp": period_1_start.to_rfc3339(),
+            "properties": {"endpoint": "inference", "tokens": 20},
+        })
+        .to_string(),
+        "".to_string(),
+        serde_json::json!({"event_id": "evt_invalid"}).to_string(),
+        serde_json::json!({
+            "event_id": imported_event_id,
+            "event_name": "api_response",
+            "meteroid_customer_id": customer_1,
+            "timestamp": period_1_start.to_rfc3339(),
+        })
+        .to_string(),
+    ]
+    .join("\n");
+
+    let imported: serde_json::Value = http_client
+        .post(&import_url)
+        .header(API_KEY_HEADER, api_key)
+        .header(reqwest::header::CONTENT_TYPE, "application/x-ndjson")
+        .body(ndjson)
+        .send()
+        .await
+        .expect("Could not import the NDJSON events")
+        .error_for_status()
+        .expect("The NDJSON import failed")
+        .json()
+        .await
+        .expect("Invalid import response");
+
+    assert_eq!(imported["ingested_count"], 1);
+    assert_eq!(imported["failures"].as_array().unwrap().len(), 1);
+    assert_eq!(imported["failures"][0]["line"], 3);
+    assert_eq!(imported["failures"][0]["event_id"], "evt_invalid");
+    assert_eq!(imported["duplicates"][0]["line"], 4);
+    assert_eq!(imported["duplicates"][0]["event_id"], imported_event_id);
+
+    // and from a CSV file
+    let csv = format!(
+        "event_id,event_name,meteroid_customer_id,timestamp,endpoint\n\
+         {},api_response,{},{},inference\n\
+         evt_invalid,api_response,{}\n",
+        uuid::Uuid::new_v4(),
+        customer_1,
+        period_1_start.to_rfc3339(),
+        customer_1,
+    );
+
+    let imported: serde_json::Value = http_client
+        .post(&import_url)
+        .header(API_KEY_HEADER, api_key)
+        .header(reqwest::header::CONTENT_TYPE, "text/csv")
+        .body(csv)
+        .send()
+        .await
+        .expect("Could not import the CSV events")
+        .error_for_status()
+        .expect("The CSV import failed")
+        .json()
+        .await
+        .expect("Invalid import response");
+
+    assert_eq!(imported["ingested_count"], 1);
+    assert_eq!(imported["failures"].as_array().unwrap().len(), 1);
+    assert_eq!(imported["failures"][0]["line"], 3);
+    assert_eq!(imported["failures"][0]["event_id"], "evt_invalid");
+    assert_eq!(imported["duplicates"].as_array().unwrap().len(), 0);
+    assert_eq!(imported["truncated_failures_count"], 0);
 
     // TODO loop & count(*) until it is ingested
     tokio::time::sleep(std::time::Duration::from_secs(5)).await;
@@ -436,6 +574,8 @@ async fn test_metering_e2e() {
                         }),
                         add_ons: None,
                         coupons: None,
+                        minimum_spend: None,
+                        spend_cap: None,
                     },
                 )
             },
@@ -480,8 +620,10 @@ async fn test_metering_e2e() {
             finalized_at: None,
             subtotal: 100,
             subtotal_recurring: 100,
-            tax_rate: 0,
+            tax_rate: Decimal::ZERO,
             tax_amount: 0,
+            tax_breakdown: vec![],
+            coupons: vec![],
             local_id: LocalId::no_prefix(),
             customer_details: InlineCustomer {
                 billing_address: None,
@@ -506,6 +648,7 @@ async fn test_metering_e2e() {
                 },
                 snapshot_at: period_2_start.naive_utc(),
             },
+            replaces_invoice_id: None,
         })
         .await
         .unwrap();
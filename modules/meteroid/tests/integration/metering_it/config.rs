@@ -22,6 +22,8 @@ pub fn mocked_config(
         },
         listen_addr: format!("127.0.0.1:{}", metering_port).parse().unwrap(),
         meteroid_endpoint: format!("http://127.0.0.1:{}", meteroid_port),
+        dedup_window_seconds: 86400,
+        dedup_capacity: 10000,
         common: CommonConfig {
             telemetry: TelemetryConfig::init_from_env().unwrap(),
         },
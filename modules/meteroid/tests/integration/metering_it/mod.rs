@@ -4,3 +4,7 @@ pub mod config;
 pub mod container;
 
 pub mod kafka;
+
+mod test_event_corrections;
+mod test_latest_aggregation;
+mod test_sum_over_time_aggregation;
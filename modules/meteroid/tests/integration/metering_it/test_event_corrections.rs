@@ -0,0 +1,225 @@
+use std::collections::HashMap;
+
+use chrono::{DateTime, NaiveDateTime, Utc};
+
+use metering::connectors::clickhouse::ClickhouseConnector;
+use metering::connectors::Connector;
+use metering::domain::{Customer, Meter, MeterAggregation, QueryMeterParams};
+use metering::ingest::domain::current_records;
+
+use crate::{helpers, metering_it};
+
+const TENANT_ID: &str = "tenant_corrections";
+const CUSTOMER_ID: &str = "customer_corrections";
+const EVENT_NAME: &str = "api_calls";
+
+fn datetime(s: &str) -> DateTime<Utc> {
+    NaiveDateTime::parse_from_str(s, "%Y-%m-%d %H:%M:%S")
+        .unwrap()
+        .and_utc()
+}
+
+// (event_id, timestamp, calls, sign, record_id), the record_id being empty for ingested events
+async fn insert_records(clickhouse_port: u16, records: &[(&str, &str, &str, i8, &str)]) {
+    let values = records
+        .iter()
+        .map(|(event_id, timestamp, calls, sign, record_id)| {
+            format!(
+                "('{}', '{}', '{}', '{}', '{}', map('calls', '{}'), {}, '{}', '{}')",
+                TENANT_ID,
+                event_id,
+                EVENT_NAME,
+                CUSTOMER_ID,
+                timestamp,
+                calls,
+                sign,
+                if record_id.is_empty() {
+                    ""
+                } else {
+                    "wrong value"
+                },
+                record_id
+            )
+        })
+        .collect::<Vec<_>>()
+        .join(", ");
+
+    let mut client = metering_it::clickhouse::get_handle(clickhouse_port)
+        .await
+        .expect("Could not connect to clickhouse");
+
+    client
+        .execute(format!(
+            "INSERT INTO meteroid.raw_events (tenant_id, event_id, event_name, customer_id, event_timestamp, properties, sign, correction_reason, record_id) VALUES {}",
+            values
+        ))
+        .await
+        .expect("Could not insert records");
+}
+
+async fn query(
+    connector: &ClickhouseConnector,
+    aggregation: MeterAggregation,
+    meter_slug: &str,
+) -> f64 {
+    let usage = connector
+        .query_meter(QueryMeterParams {
+            aggregation,
+            namespace: TENANT_ID.to_string(),
+            meter_slug: meter_slug.to_string(),
+            event_name: EVENT_NAME.to_string(),
+            value_property: Some("calls".to_string()),
+            customers: vec![Customer {
+                id: CUSTOMER_ID.to_string(),
+                external_id: CUSTOMER_ID.to_string(),
+            }],
+            filter_group_by: HashMap::new(),
+            group_by: vec![],
+            window_size: None,
+            window_time_zone: None,
+            from: datetime("2024-01-01 00:00:00"),
+            to: Some(datetime("2024-01-02 00:00:00")),
+        })
+        .await
+        .expect("Could not query meter");
+
+    assert_eq!(usage.len(), 1);
+    usage[0].value
+}
+
+#[tokio::test]
+async fn test_event_corrections() {
+    helpers::init::logging();
+
+    let (_clickhouse_container, clickhouse_port) = metering_it::container::start_clickhouse().await;
+
+    // kafka is not started, the ingestion table stays idle and records are inserted directly
+    let config = metering_it::config::mocked_config(
+        0,
+        0,
+        clickhouse_port,
+        0,
+        "meteroid-events-raw".to_string(),
+    );
+
+    let connector = ClickhouseConnector::init(&config.clickhouse, &config.kafka, vec![])
+        .await
+        .expect("Could not init the clickhouse connector");
+
+    for (aggregation, meter_slug) in [
+        (MeterAggregation::Sum, "calls_sum"),
+        (MeterAggregation::Count, "calls_count"),
+        (MeterAggregation::Min, "calls_min"),
+        (MeterAggregation::Max, "calls_max"),
+        (MeterAggregation::Latest, "calls_latest"),
+    ] {
+        connector
+            .register_meter(Meter {
+                aggregation,
+                namespace: TENANT_ID.to_string(),
+                meter_slug: meter_slug.to_string(),
+                event_name: EVENT_NAME.to_string(),
+                value_property: Some("calls".to_string()),
+                group_by: vec![],
+            })
+            .await
+            .expect("Could not register meter");
+    }
+
+    // a meter registered before the event corrections, whose states ignore the sign of the records
+    let mut client = metering_it::clickhouse::get_handle(clickhouse_port)
+        .await
+        .expect("Could not connect to clickhouse");
+    client
+        .execute(format!(
+            "CREATE MATERIALIZED VIEW meteroid.METER_NStenantcorrections_Mcallssumlegacy (customer_id String, windowstart DateTime, windowend DateTime, value AggregateFunction(sum, Float64)) ENGINE = AggregatingMergeTree() ORDER BY (windowstart, windowend, customer_id) AS SELECT customer_id, tumbleStart(toDateTime(event_timestamp), toIntervalMinute(1)) AS windowstart, tumbleEnd(toDateTime(event_timestamp), toIntervalMinute(1)) AS windowend, sumState(cast(properties['calls'], 'Float64')) AS value FROM meteroid.raw_events WHERE tenant_id = '{}' AND event_name = '{}' GROUP BY windowstart, windowend, customer_id",
+            TENANT_ID, EVENT_NAME
+        ))
+        .await
+        .expect("Could not create legacy meter view");
+
+    insert_records(
+        clickhouse_port,
+        &[
+            ("evt_1", "2024-01-01 10:00:00", "10", 1, ""),
+            ("evt_2", "2024-01-01 11:00:00", "20", 1, ""),
+            ("evt_3", "2024-01-01 12:00:00", "30", 1, ""),
+        ],
+    )
+    .await;
+
+    // evt_1 is amended to 15, evt_2 is voided
+    insert_records(
+        clickhouse_port,
+        &[
+            ("evt_1", "2024-01-01 10:00:00", "10", -1, "correction_1"),
+            ("evt_1", "2024-01-01 10:00:00", "15", 1, "correction_2"),
+            ("evt_2", "2024-01-01 11:00:00", "20", -1, "correction_3"),
+        ],
+    )
+    .await;
+
+    // retried ingestions of evt_1 and evt_3, only their first ingestion is read
+    insert_records(
+        clickhouse_port,
+        &[
+            ("evt_1", "2024-01-01 10:00:00", "10", 1, ""),
+            ("evt_3", "2024-01-01 12:30:00", "30", 1, ""),
+        ],
+    )
+    .await;
+
+    assert_eq!(
+        query(&connector, MeterAggregation::Sum, "calls_sum").await,
+        45.0
+    );
+    // the legacy meter is aggregated from the netted events
+    assert_eq!(
+        query(&connector, MeterAggregation::Sum, "calls_sum_legacy").await,
+        45.0
+    );
+    assert_eq!(
+        query(&connector, MeterAggregation::Count, "calls_count").await,
+        2.0
+    );
+    // the amended value of evt_1 replaces the original one
+    assert_eq!(
+        query(&connector, MeterAggregation::Min, "calls_min").await,
+        15.0
+    );
+    assert_eq!(
+        query(&connector, MeterAggregation::Max, "calls_max").await,
+        30.0
+    );
+    assert_eq!(
+        query(&connector, MeterAggregation::Latest, "calls_latest").await,
+        30.0
+    );
+
+    let records = connector
+        .get_event_records(
+            TENANT_ID,
+            &[
+                "evt_1".to_string(),
+                "evt_2".to_string(),
+                "evt_3".to_string(),
+            ],
+        )
+        .await
+        .expect("Could not get event records");
+    assert_eq!(records.len(), 6);
+
+    let mut current: Vec<(String, String)> = current_records(records)
+        .into_iter()
+        .map(|r| (r.event_id, r.properties["calls"].clone()))
+        .collect();
+    current.sort();
+
+    assert_eq!(
+        current,
+        vec![
+            ("evt_1".to_string(), "15".to_string()),
+            ("evt_3".to_string(), "30".to_string()),
+        ]
+    );
+}
@@ -0,0 +1,142 @@
+use std::collections::HashMap;
+
+use chrono::{DateTime, NaiveDateTime, Utc};
+
+use metering::connectors::clickhouse::ClickhouseConnector;
+use metering::connectors::Connector;
+use metering::domain::{Customer, Meter, MeterAggregation, QueryMeterParams, Usage, WindowSize};
+
+use crate::{helpers, metering_it};
+
+const TENANT_ID: &str = "tenant_latest";
+const CUSTOMER_ID: &str = "customer_latest";
+const EVENT_NAME: &str = "seats";
+
+fn datetime(s: &str) -> DateTime<Utc> {
+    NaiveDateTime::parse_from_str(s, "%Y-%m-%d %H:%M:%S")
+        .unwrap()
+        .and_utc()
+}
+
+// (event_id, timestamp, seats)
+async fn insert_events(clickhouse_port: u16, events: &[(&str, &str, &str)]) {
+    let values = events
+        .iter()
+        .map(|(event_id, timestamp, seats)| {
+            format!(
+                "('{}', '{}', '{}', '{}', '{}', map('seats', '{}'))",
+                TENANT_ID, event_id, EVENT_NAME, CUSTOMER_ID, timestamp, seats
+            )
+        })
+        .collect::<Vec<_>>()
+        .join(", ");
+
+    let mut client = metering_it::clickhouse::get_handle(clickhouse_port)
+        .await
+        .expect("Could not connect to clickhouse");
+
+    client
+        .execute(format!(
+            "INSERT INTO meteroid.raw_events (tenant_id, event_id, event_name, customer_id, event_timestamp, properties) VALUES {}",
+            values
+        ))
+        .await
+        .expect("Could not insert events");
+}
+
+async fn query(connector: &ClickhouseConnector, window_size: Option<WindowSize>) -> Vec<Usage> {
+    connector
+        .query_meter(QueryMeterParams {
+            aggregation: MeterAggregation::Latest,
+            namespace: TENANT_ID.to_string(),
+            meter_slug: "seats_latest".to_string(),
+            event_name: EVENT_NAME.to_string(),
+            value_property: Some("seats".to_string()),
+            customers: vec![Customer {
+                id: CUSTOMER_ID.to_string(),
+                external_id: CUSTOMER_ID.to_string(),
+            }],
+            filter_group_by: HashMap::new(),
+            group_by: vec![],
+            window_size,
+            window_time_zone: None,
+            from: datetime("2024-01-01 00:00:00"),
+            to: Some(datetime("2024-01-02 00:00:00")),
+        })
+        .await
+        .expect("Could not query meter")
+}
+
+#[tokio::test]
+async fn test_latest_aggregation_out_of_order() {
+    helpers::init::logging();
+
+    let (_clickhouse_container, clickhouse_port) = metering_it::container::start_clickhouse().await;
+
+    // kafka is not started, the ingestion table stays idle and events are inserted directly
+    let config = metering_it::config::mocked_config(
+        0,
+        0,
+        clickhouse_port,
+        0,
+        "meteroid-events-raw".to_string(),
+    );
+
+    let connector = ClickhouseConnector::init(&config.clickhouse, &config.kafka, vec![])
+        .await
+        .expect("Could not init the clickhouse connector");
+
+    // events ingested before the meter is registered are picked up by the POPULATE
+    insert_events(
+        clickhouse_port,
+        &[
+            ("evt_3", "2024-01-01 12:00:00", "8"),
+            ("evt_1", "2024-01-01 10:00:00", "5"),
+        ],
+    )
+    .await;
+
+    connector
+        .register_meter(Meter {
+            aggregation: MeterAggregation::Latest,
+            namespace: TENANT_ID.to_string(),
+            meter_slug: "seats_latest".to_string(),
+            event_name: EVENT_NAME.to_string(),
+            value_property: Some("seats".to_string()),
+            group_by: vec![],
+        })
+        .await
+        .expect("Could not register meter");
+
+    // late events : an older value must not override a more recent one,
+    // and events sharing a timestamp are ordered by event id
+    insert_events(
+        clickhouse_port,
+        &[
+            ("evt_2", "2024-01-01 11:00:00", "3"),
+            ("evt_4", "2024-01-01 12:00:00", "10"),
+            ("evt_0", "2024-01-01 12:00:00", "1"),
+        ],
+    )
+    .await;
+
+    let usage = query(&connector, None).await;
+
+    assert_eq!(usage.len(), 1);
+    assert_eq!(usage[0].customer_id, CUSTOMER_ID);
+    assert_eq!(usage[0].value, 10.0);
+
+    let usage = query(&connector, Some(WindowSize::Hour)).await;
+
+    let values: Vec<(DateTime<Utc>, f64)> =
+        usage.iter().map(|u| (u.window_start, u.value)).collect();
+
+    assert_eq!(
+        values,
+        vec![
+            (datetime("2024-01-01 10:00:00"), 5.0),
+            (datetime("2024-01-01 11:00:00"), 3.0),
+            (datetime("2024-01-01 12:00:00"), 10.0),
+        ]
+    );
+}
@@ -0,0 +1,162 @@
+use std::collections::HashMap;
+
+use chrono::{DateTime, NaiveDateTime, Utc};
+
+use metering::connectors::clickhouse::ClickhouseConnector;
+use metering::connectors::Connector;
+use metering::domain::{Customer, Meter, MeterAggregation, QueryMeterParams, Usage, WindowSize};
+
+use crate::{helpers, metering_it};
+
+const TENANT_ID: &str = "tenant_gauge";
+const CUSTOMER_ID: &str = "customer_gauge";
+const EVENT_NAME: &str = "storage";
+
+fn datetime(s: &str) -> DateTime<Utc> {
+    NaiveDateTime::parse_from_str(s, "%Y-%m-%d %H:%M:%S")
+        .unwrap()
+        .and_utc()
+}
+
+// (event_id, timestamp, bucket, gb)
+async fn insert_events(clickhouse_port: u16, events: &[(&str, &str, &str, &str)]) {
+    let values = events
+        .iter()
+        .map(|(event_id, timestamp, bucket, gb)| {
+            format!(
+                "('{}', '{}', '{}', '{}', '{}', map('bucket', '{}', 'gb', '{}'))",
+                TENANT_ID, event_id, EVENT_NAME, CUSTOMER_ID, timestamp, bucket, gb
+            )
+        })
+        .collect::<Vec<_>>()
+        .join(", ");
+
+    let mut client = metering_it::clickhouse::get_handle(clickhouse_port)
+        .await
+        .expect("Could not connect to clickhouse");
+
+    client
+        .execute(format!(
+            "INSERT INTO meteroid.raw_events (tenant_id, event_id, event_name, customer_id, event_timestamp, properties) VALUES {}",
+            values
+        ))
+        .await
+        .expect("Could not insert events");
+}
+
+async fn query(
+    connector: &ClickhouseConnector,
+    window_size: Option<WindowSize>,
+    group_by: Vec<String>,
+) -> Vec<Usage> {
+    connector
+        .query_meter(QueryMeterParams {
+            aggregation: MeterAggregation::SumOverTime,
+            namespace: TENANT_ID.to_string(),
+            meter_slug: "storage_gb_seconds".to_string(),
+            event_name: EVENT_NAME.to_string(),
+            value_property: Some("gb".to_string()),
+            customers: vec![Customer {
+                id: CUSTOMER_ID.to_string(),
+                external_id: CUSTOMER_ID.to_string(),
+            }],
+            filter_group_by: HashMap::new(),
+            group_by,
+            window_size,
+            window_time_zone: None,
+            from: datetime("2024-01-01 00:00:00"),
+            to: Some(datetime("2024-01-02 00:00:00")),
+        })
+        .await
+        .expect("Could not query meter")
+}
+
+#[tokio::test]
+async fn test_sum_over_time_aggregation() {
+    helpers::init::logging();
+
+    let (_clickhouse_container, clickhouse_port) = metering_it::container::start_clickhouse().await;
+
+    // kafka is not started, the ingestion table stays idle and events are inserted directly
+    let config = metering_it::config::mocked_config(
+        0,
+        0,
+        clickhouse_port,
+        0,
+        "meteroid-events-raw".to_string(),
+    );
+
+    let connector = ClickhouseConnector::init(&config.clickhouse, &config.kafka, vec![])
+        .await
+        .expect("Could not init the clickhouse connector");
+
+    connector
+        .register_meter(Meter {
+            aggregation: MeterAggregation::SumOverTime,
+            namespace: TENANT_ID.to_string(),
+            meter_slug: "storage_gb_seconds".to_string(),
+            event_name: EVENT_NAME.to_string(),
+            value_property: Some("gb".to_string()),
+            group_by: vec!["bucket".to_string()],
+        })
+        .await
+        .expect("Could not register meter");
+
+    // bucket_1 holds 10GB since before the period, then 20GB from noon
+    // bucket_2 holds 5GB from 6am to 6pm, its events being ingested out of order
+    insert_events(
+        clickhouse_port,
+        &[
+            ("evt_1", "2023-12-31 20:00:00", "bucket_1", "10"),
+            ("evt_2", "2024-01-01 12:00:00", "bucket_1", "20"),
+            ("evt_4", "2024-01-01 18:00:00", "bucket_2", "0"),
+        ],
+    )
+    .await;
+    insert_events(
+        clickhouse_port,
+        &[("evt_3", "2024-01-01 06:00:00", "bucket_2", "5")],
+    )
+    .await;
+
+    let hour = 3600.0;
+
+    let usage = query(&connector, None, vec![]).await;
+
+    assert_eq!(usage.len(), 1);
+    assert_eq!(usage[0].customer_id, CUSTOMER_ID);
+    assert_eq!(
+        usage[0].value,
+        (10.0 * 12.0 + 20.0 * 12.0 + 5.0 * 12.0) * hour
+    );
+
+    let usage = query(&connector, None, vec!["bucket".to_string()]).await;
+
+    let mut values: Vec<(Option<String>, f64)> = usage
+        .iter()
+        .map(|u| (u.group_by.get("bucket").cloned().flatten(), u.value))
+        .collect();
+    values.sort_by(|a, b| a.0.cmp(&b.0));
+
+    assert_eq!(
+        values,
+        vec![
+            (
+                Some("bucket_1".to_string()),
+                (10.0 * 12.0 + 20.0 * 12.0) * hour
+            ),
+            (Some("bucket_2".to_string()), 5.0 * 12.0 * hour),
+        ]
+    );
+
+    let usage = query(&connector, Some(WindowSize::Hour), vec![]).await;
+
+    assert_eq!(usage.len(), 24);
+    let by_window: HashMap<DateTime<Utc>, f64> =
+        usage.iter().map(|u| (u.window_start, u.value)).collect();
+
+    assert_eq!(by_window[&datetime("2024-01-01 00:00:00")], 10.0 * hour);
+    assert_eq!(by_window[&datetime("2024-01-01 06:00:00")], 15.0 * hour);
+    assert_eq!(by_window[&datetime("2024-01-01 12:00:00")], 25.0 * hour);
+    assert_eq!(by_window[&datetime("2024-01-01 18:00:00")], 20.0 * hour);
+}
@@ -150,6 +150,8 @@ async fn test_main() {
                     billing_address: None,
                     shipping_address: None,
                     invoicing_entity_id: None,
+                    vat_number: None,
+                    tax_status: None,
                 }),
             },
         ))
@@ -50,6 +50,10 @@ async fn test_coupons_basic() {
             redemption_limit: Some(10),
             recurring_value: None,
             reusable: false,
+            applies_to: None,
+            duration_months: None,
+            stacking: api::coupons::v1::CouponStacking::Stackable.into(),
+            priority: 0,
         })
         .await
         .unwrap()
@@ -84,6 +88,8 @@ async fn test_coupons_basic() {
             coupon_id: created.id.clone(),
             description: "test-desc-edited".into(),
             discount: Some(percentage_discount.clone()),
+            stacking: None,
+            priority: None,
         })
         .await
         .unwrap()
@@ -54,6 +54,8 @@ async fn test_customers_basic() {
                 billing_address: None,
                 shipping_address: None,
                 invoicing_entity_id: None,
+                vat_number: None,
+                tax_status: None,
             }),
         })
         .await
@@ -83,6 +85,8 @@ async fn test_customers_basic() {
                 billing_address: None,
                 shipping_address: None,
                 invoicing_entity_id: None,
+                vat_number: None,
+                tax_status: None,
             }),
         })
         .await
@@ -186,6 +190,8 @@ async fn test_customers_basic() {
                 billing_address: None,
                 shipping_address: None,
                 invoicing_entity_id: None,
+                vat_number: None,
+                tax_status: None,
             }),
         })
         .await
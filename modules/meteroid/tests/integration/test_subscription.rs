@@ -19,9 +19,16 @@ use meteroid_grpc::meteroid::api::shared::v1::BillingPeriod;
 use meteroid_grpc::meteroid::api::subscriptions::v1::cancel_subscription_request::EffectiveAt;
 use meteroid_grpc::meteroid::api::subscriptions::v1::SubscriptionStatus;
 
-use meteroid_store::domain::{CursorPaginationRequest, LineItem};
+use meteroid_store::domain::enums::BillingPeriodEnum;
+use meteroid_store::domain::{
+    ComponentParameterization, ComponentParameters, CreateSubscriptionComponents,
+    CursorPaginationRequest, LineItem, OrderByRequest, PaginationRequest, UpcomingInvoiceChanges,
+    UpcomingPlanVersion,
+};
 use meteroid_store::repositories::subscriptions::SubscriptionSlotsInterface;
-use meteroid_store::repositories::InvoiceInterface;
+use meteroid_store::repositories::upcoming_invoices::UpcomingInvoiceInterface;
+use meteroid_store::repositories::{InvoiceInterface, SubscriptionInterface};
+use std::collections::HashMap;
 
 struct TestContext {
     setup: MeteroidSetup,
@@ -556,6 +563,132 @@ async fn test_subscription_create_invoice_seats() {
     meteroid_it::container::terminate_meteroid(setup.token, setup.join_handle).await
 }
 
+#[tokio::test]
+#[ignore] // subscription seed is broken
+async fn test_subscription_upcoming_invoice_is_not_persisted() {
+    let TestContext {
+        setup,
+        clients,
+        _container,
+    } = setup_test(SeedLevel::PLANS).await.unwrap();
+    let customer_id = "018c345f-7324-7cd2-a692-78e5ab9158e0".to_string();
+    let plan_version_id = "018c344b-da87-7392-bbae-c5c8780adb1b".to_string();
+    let component_id = "018c344c-9ec9-7608-b115-1537b6985e73".to_string();
+
+    let start = chrono::Utc::now().date_naive();
+
+    let subscription = clients
+        .subscriptions
+        .clone()
+        .create_subscription(tonic::Request::new(
+            api::subscriptions::v1::CreateSubscriptionRequest {
+                subscription: Some(api::subscriptions::v1::CreateSubscription {
+                    plan_version_id: plan_version_id.clone(),
+                    billing_start_date: start.as_proto(),
+                    customer_id: customer_id.clone(),
+                    currency: "USD".to_string(),
+                    components: Some(api::subscriptions::v1::CreateSubscriptionComponents {
+                        parameterized_components: vec![
+                            api::subscriptions::v1::create_subscription_components::ComponentParameterization {
+                                component_id: component_id.clone(),
+                                initial_slot_count: Some(15),
+                                billing_period: Some(BillingPeriod::Monthly.into()),
+                                committed_capacity: None,
+                            }
+                        ],
+                        ..Default::default()
+                    }),
+                    ..Default::default()
+                })
+            },
+        ))
+        .await
+        .unwrap()
+        .into_inner();
+
+    let subscription_id =
+        uuid::Uuid::parse_str(subscription.subscription.map(|s| s.id).unwrap().as_str()).unwrap();
+    let plan_version_id = uuid::Uuid::parse_str(plan_version_id.as_str()).unwrap();
+    let price_component_id = uuid::Uuid::parse_str(component_id.as_str()).unwrap();
+    let customer_id = uuid::Uuid::parse_str(customer_id.as_str()).unwrap();
+    let tenant_id = meteroid_it::db::seed::TENANT_ID;
+
+    let list_invoices = || async {
+        setup
+            .store
+            .list_invoices(
+                tenant_id,
+                Some(customer_id),
+                None,
+                None,
+                OrderByRequest::DateAsc,
+                PaginationRequest {
+                    page: 0,
+                    per_page: None,
+                },
+            )
+            .await
+            .unwrap()
+            .items
+            .len()
+    };
+
+    let invoices_before = list_invoices().await;
+
+    let upcoming = setup
+        .store
+        .get_upcoming_invoice(
+            tenant_id,
+            subscription_id,
+            None,
+            UpcomingInvoiceChanges {
+                slots: HashMap::from([(price_component_id, 40)]),
+                add_ons: None,
+                plan_version: Some(UpcomingPlanVersion {
+                    plan_version_id,
+                    components: Some(CreateSubscriptionComponents {
+                        parameterized_components: vec![ComponentParameterization {
+                            component_id: price_component_id,
+                            parameters: ComponentParameters {
+                                initial_slot_count: Some(40),
+                                billing_period: Some(BillingPeriodEnum::Monthly),
+                                committed_capacity: None,
+                            },
+                        }],
+                        overridden_components: vec![],
+                        extra_components: vec![],
+                        remove_components: vec![],
+                    }),
+                }),
+            },
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(upcoming.plan_version_id, plan_version_id);
+
+    // neither the invoice nor the changes are persisted
+    assert_eq!(list_invoices().await, invoices_before);
+
+    let slots = setup
+        .store
+        .get_current_slots_value(tenant_id, subscription_id, price_component_id, None)
+        .await
+        .unwrap();
+    assert_eq!(slots, 15);
+
+    let details = setup
+        .store
+        .get_subscription_details(tenant_id, subscription_id)
+        .await
+        .unwrap();
+    assert_eq!(details.plan_version_id, plan_version_id);
+    assert_eq!(details.price_components.len(), 1);
+
+    // teardown
+    meteroid_it::container::terminate_meteroid(setup.token, setup.join_handle).await
+}
+
 #[tokio::test]
 #[ignore] // subscription seed is broken
 async fn test_subscription_create_invoice_rate() {
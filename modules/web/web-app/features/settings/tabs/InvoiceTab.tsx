@@ -23,6 +23,7 @@ import {
 
 const invoiceDetailsSchema = z.object({
   invoiceNumberPattern: z.string().optional(),
+  creditNoteNumberPattern: z.string().optional(),
   gracePeriodHours: z.number().optional(),
   netTerms: z.number().optional(),
   invoiceFooterInfo: z.string().optional(),
@@ -80,6 +81,7 @@ export const InvoiceTab = () => {
     if (entity) {
       console.log('entity', entity)
       methods.setValue('invoiceNumberPattern', entity.invoiceNumberPattern)
+      methods.setValue('creditNoteNumberPattern', entity.creditNoteNumberPattern)
       methods.setValue('gracePeriodHours', entity.gracePeriodHours)
       methods.setValue('netTerms', entity.netTerms)
       methods.setValue('invoiceFooterInfo', entity.invoiceFooterInfo)
@@ -111,6 +113,7 @@ export const InvoiceTab = () => {
         invoiceFooterInfo: values.invoiceFooterInfo,
         invoiceFooterLegal: values.invoiceFooterLegal,
         invoiceNumberPattern: values.invoiceNumberPattern,
+        creditNoteNumberPattern: values.creditNoteNumberPattern,
         logoAttachmentId: values.logoAttachmentId,
         netTerms: values.netTerms,
       },
@@ -165,9 +168,17 @@ export const InvoiceTab = () => {
                 name="invoiceNumberPattern"
                 label="Invoice number pattern"
                 control={methods.control}
-                placeholder="ACME Inc."
+                placeholder="INV-{YYYY}-{number:5:yearly}"
                 containerClassName="col-span-6"
-                description="Use the following placeholders: {number} - mandatory sequential number, {YYYY} - year, {MM} - month, {DD} - day"
+                description="Use the following placeholders: {number} - mandatory sequential number, {number:5} - zero-padded to 5 digits, {number:yearly} - reset every year (requires {YYYY} or {YY}), {YYYY} / {YY} - year, {MM} - month, {DD} - day"
+              />
+              <InputFormField
+                name="creditNoteNumberPattern"
+                label="Credit note number pattern"
+                control={methods.control}
+                placeholder="CN-{number}"
+                containerClassName="col-span-6"
+                description="Same placeholders as the invoice number pattern"
               />
 
               <InputFormField
@@ -117,6 +117,8 @@ function formatStatus(status: SubscriptionStatus): ReactNode {
       return <Badge variant="warning">Pending</Badge>
     case SubscriptionStatus.TRIAL:
       return <Badge variant="outline">Trial</Badge>
+    case SubscriptionStatus.PAUSED:
+      return <Badge variant="warning">Paused</Badge>
     default:
       return 'Unknown'
   }